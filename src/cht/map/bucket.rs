@@ -170,6 +170,70 @@ impl<'g, K: 'g + Eq, V: 'g> BucketArray<K, V> {
         Ok(Shared::null())
     }
 
+    #[cfg(feature = "sync")]
+    pub(crate) fn modify_if<F>(
+        &self,
+        guard: &'g Guard,
+        hash: u64,
+        mut eq: impl FnMut(&K) -> bool,
+        mut modifier: F,
+    ) -> Result<Shared<'g, Bucket<K, V>>, F>
+    where
+        K: Clone,
+        F: FnMut(&K, &V) -> Option<V>,
+    {
+        let mut probe = self.probe(guard, hash);
+        while let Some(bucket) = probe.next() {
+            let Ok((_, this_bucket, this_bucket_ptr)) = bucket else {
+                return Err(modifier);
+            };
+
+            let Some(this_bucket_ref) = (unsafe { this_bucket_ptr.as_ref() }) else {
+                // Nothing to modify.
+                return Ok(Shared::null());
+            };
+
+            let this_key = &this_bucket_ref.key;
+
+            if !eq(this_key) {
+                // Different key. Try next bucket.
+                continue;
+            }
+
+            if is_tombstone(this_bucket_ptr) {
+                // Already removed. Do not insert it back.
+                return Ok(Shared::null());
+            }
+
+            let this_value = unsafe { &*this_bucket_ref.maybe_value.as_ptr() };
+
+            let Some(new_value) = modifier(this_key, this_value) else {
+                // Found but the modifier keeps the current value.
+                return Ok(Shared::null());
+            };
+
+            let new_bucket = Owned::new(Bucket::new(this_key.clone(), new_value));
+
+            match this_bucket.compare_exchange_weak(
+                this_bucket_ptr,
+                new_bucket,
+                Ordering::AcqRel,
+                Ordering::Relaxed,
+                guard,
+            ) {
+                // Succeeded. Return the previous value.
+                Ok(_) => return Ok(this_bucket_ptr),
+                // Failed. Drop the new value, and reload to retry.
+                Err(CompareExchangeError { mut new, .. }) => {
+                    unsafe { ptr::drop_in_place(new.maybe_value.as_mut_ptr()) };
+                    probe.reload();
+                }
+            }
+        }
+
+        Ok(Shared::null())
+    }
+
     pub(crate) fn insert_if_not_present<F>(
         &self,
         guard: &'g Guard,
@@ -357,6 +421,29 @@ impl<'g, K: 'g + Eq, V: 'g> BucketArray<K, V> {
 
         Ok(keys)
     }
+
+    /// Returns the key in the bucket at `index % self.buckets.len()`, or `None`
+    /// if the bucket is empty or a tombstone.
+    #[cfg(feature = "sync")]
+    pub(crate) fn key_at<F, T>(
+        &self,
+        guard: &'g Guard,
+        index: usize,
+        with_key: &mut F,
+    ) -> Result<Option<T>, RelocatedError>
+    where
+        F: FnMut(&K) -> T,
+    {
+        let bucket_ptr = self.buckets[index % self.buckets.len()].load_consume(guard);
+
+        if is_sentinel(bucket_ptr) {
+            return Err(RelocatedError);
+        }
+
+        Ok(unsafe { bucket_ptr.as_ref() }
+            .filter(|_| !is_tombstone(bucket_ptr))
+            .map(|bucket_ref| with_key(&bucket_ref.key)))
+    }
 }
 
 struct Probe<'b, 'g, K: 'g, V: 'g> {
@@ -719,6 +806,9 @@ pub(crate) unsafe fn defer_acquire_destroy<'g, T>(guard: &'g Guard, ptr: Shared<
 pub(crate) enum RehashOp {
     Expand,
     Shrink,
+    /// Shrink to the given length.
+    #[cfg(feature = "sync")]
+    ShrinkTo(usize),
     GcOnly,
     Skip,
 }
@@ -749,10 +839,22 @@ impl RehashOp {
         matches!(self, Self::Skip)
     }
 
+    /// Returns the length of the bucket array to hold `len` elements with enough
+    /// room to grow before the next expansion.
+    #[cfg(feature = "sync")]
+    pub(crate) fn fit_len(len: usize) -> usize {
+        len.saturating_mul(2)
+            .checked_next_power_of_two()
+            .unwrap_or(usize::MAX / 2 + 1)
+            .max(BUCKET_ARRAY_DEFAULT_LENGTH)
+    }
+
     fn new_len(self, current_len: usize) -> usize {
         match self {
             Self::Expand => current_len * 2,
             Self::Shrink => current_len / 2,
+            #[cfg(feature = "sync")]
+            Self::ShrinkTo(len) => len,
             Self::GcOnly => current_len,
             Self::Skip => unreachable!(),
         }
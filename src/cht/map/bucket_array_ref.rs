@@ -125,6 +125,70 @@ where
         result
     }
 
+    #[cfg(feature = "sync")]
+    pub(crate) fn modify_entry_if_and<T>(
+        &self,
+        hash: u64,
+        mut eq: impl FnMut(&K) -> bool,
+        mut modifier: impl FnMut(&K, &V) -> Option<V>,
+        with_previous_entry: impl FnOnce(&K, &V) -> T,
+    ) -> Option<T>
+    where
+        K: Clone,
+    {
+        let guard = &crossbeam_epoch::pin();
+        let current_ref = self.get(guard);
+        let mut bucket_array_ref = current_ref;
+
+        let result;
+
+        loop {
+            loop {
+                let rehash_op = RehashOp::new(
+                    bucket_array_ref.capacity(),
+                    &bucket_array_ref.tombstone_count,
+                    self.len,
+                );
+                if rehash_op.is_skip() {
+                    break;
+                }
+                if let Some(r) = bucket_array_ref.rehash(guard, self.build_hasher, rehash_op) {
+                    bucket_array_ref = r;
+                }
+            }
+
+            match bucket_array_ref.modify_if(guard, hash, &mut eq, modifier) {
+                Ok(previous_bucket_ptr) => {
+                    if let Some(previous_bucket_ref) = unsafe { previous_bucket_ptr.as_ref() } {
+                        let Bucket {
+                            key,
+                            maybe_value: value,
+                        } = previous_bucket_ref;
+                        result = Some(with_previous_entry(key, unsafe { &*value.as_ptr() }));
+
+                        unsafe { bucket::defer_destroy_bucket(guard, previous_bucket_ptr) };
+                    } else {
+                        result = None;
+                    }
+
+                    break;
+                }
+                Err(m) => {
+                    modifier = m;
+                    if let Some(r) =
+                        bucket_array_ref.rehash(guard, self.build_hasher, RehashOp::Expand)
+                    {
+                        bucket_array_ref = r;
+                    }
+                }
+            }
+        }
+
+        self.swing(guard, current_ref, bucket_array_ref);
+
+        result
+    }
+
     pub(crate) fn insert_if_not_present_and<T>(
         &self,
         key: K,
@@ -291,6 +355,64 @@ where
 
         result
     }
+
+    /// Shrinks the bucket array to fit the current number of elements (but not
+    /// shorter than `min_len`), dropping the tombstones, if the bucket array is
+    /// more than `min_shrink_factor` times longer than the fitted length. Returns
+    /// `true` if the bucket array was shrunk.
+    #[cfg(feature = "sync")]
+    pub(crate) fn shrink_to_fit(&self, min_len: usize, min_shrink_factor: usize) -> bool {
+        let guard = &crossbeam_epoch::pin();
+        let current_ref = self.get(guard);
+
+        let fit_len = RehashOp::fit_len(self.len.load(Ordering::Relaxed)).max(min_len);
+        if current_ref.buckets.len() <= fit_len.saturating_mul(min_shrink_factor.max(1)) {
+            return false;
+        }
+
+        let bucket_array_ref = loop {
+            // `rehash` returns `None` when another thread was rehashing the array.
+            // In that case, retry to move to the array created by that thread.
+            if let Some(r) =
+                current_ref.rehash(guard, self.build_hasher, RehashOp::ShrinkTo(fit_len))
+            {
+                break r;
+            }
+        };
+
+        self.swing(guard, current_ref, bucket_array_ref);
+
+        bucket_array_ref.buckets.len() < current_ref.buckets.len()
+    }
+
+    #[cfg(feature = "sync")]
+    pub(crate) fn key_at<T>(&self, index: usize, mut with_key: impl FnMut(&K) -> T) -> Option<T> {
+        let guard = &crossbeam_epoch::pin();
+        let current_ref = self.get(guard);
+        let mut bucket_array_ref = current_ref;
+
+        let result;
+
+        loop {
+            match bucket_array_ref.key_at(guard, index, &mut with_key) {
+                Ok(key) => {
+                    result = key;
+                    break;
+                }
+                Err(_) => {
+                    if let Some(r) =
+                        bucket_array_ref.rehash(guard, self.build_hasher, RehashOp::Expand)
+                    {
+                        bucket_array_ref = r;
+                    }
+                }
+            }
+        }
+
+        self.swing(guard, current_ref, bucket_array_ref);
+
+        result
+    }
 }
 
 impl<'a, 'g, K, V, S> BucketArrayRef<'a, K, V, S> {
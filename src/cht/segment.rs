@@ -109,6 +109,10 @@ pub(crate) struct HashMap<K, V, S = DefaultHashBuilder> {
     build_hasher: S,
     len: AtomicUsize,
     segment_shift: u32,
+    /// The length of the bucket arrays allocated up front for the initial
+    /// capacity. `shrink_to_fit` does not shrink the bucket arrays below it.
+    #[cfg(feature = "sync")]
+    initial_bucket_array_len: usize,
 }
 
 #[cfg(test)]
@@ -168,6 +172,11 @@ impl<K, V, S> HashMap<K, V, S> {
         let segment_shift = 64 - actual_num_segments.trailing_zeros();
 
         let mut segments = Vec::with_capacity(actual_num_segments);
+        let initial_bucket_array_len = if capacity == 0 {
+            0
+        } else {
+            (capacity * 2 / actual_num_segments).next_power_of_two()
+        };
 
         if capacity == 0 {
             unsafe {
@@ -175,11 +184,12 @@ impl<K, V, S> HashMap<K, V, S> {
                 segments.set_len(actual_num_segments);
             }
         } else {
-            let actual_capacity = (capacity * 2 / actual_num_segments).next_power_of_two();
-
             for _ in 0..actual_num_segments {
                 segments.push(Segment {
-                    bucket_array: Atomic::new(BucketArray::with_length(0, actual_capacity)),
+                    bucket_array: Atomic::new(BucketArray::with_length(
+                        0,
+                        initial_bucket_array_len,
+                    )),
                     len: AtomicUsize::new(0),
                 });
             }
@@ -192,6 +202,8 @@ impl<K, V, S> HashMap<K, V, S> {
             build_hasher,
             len: AtomicUsize::new(0),
             segment_shift,
+            #[cfg(feature = "sync")]
+            initial_bucket_array_len,
         }
     }
 
@@ -390,6 +402,32 @@ impl<K: Hash + Eq, V, S: BuildHasher> HashMap<K, V, S> {
             })
     }
 
+    /// Replaces the value of a key with the one returned by `modifier`, if the key
+    /// is present and `modifier` returns `Some`. Returns the result of invoking a
+    /// function with a reference to the key-value pair previously corresponding
+    /// to the key. Unlike `insert_with_or_modify`, this never inserts the key.
+    ///
+    /// `modifier` will be invoked at least once if [`Some`] is returned. It
+    /// may also be invoked one or more times if [`None`] is returned.
+    ///
+    /// [`Some`]: https://doc.rust-lang.org/std/option/enum.Option.html#variant.Some
+    /// [`None`]: https://doc.rust-lang.org/std/option/enum.Option.html#variant.None
+    #[cfg(feature = "sync")]
+    #[inline]
+    pub(crate) fn modify_entry_if_and<T>(
+        &self,
+        hash: u64,
+        eq: impl FnMut(&K) -> bool,
+        modifier: impl FnMut(&K, &V) -> Option<V>,
+        with_previous_entry: impl FnOnce(&K, &V) -> T,
+    ) -> Option<T>
+    where
+        K: Clone,
+    {
+        self.bucket_array_ref(hash)
+            .modify_entry_if_and(hash, eq, modifier, with_previous_entry)
+    }
+
     /// If no value corresponds to the key, invoke a default function to insert
     /// a new key-value pair into the map. Otherwise, modify the existing value
     /// and return a clone of the value previously corresponding to the key.
@@ -489,6 +527,59 @@ impl<K: Hash + Eq, V, S: BuildHasher> HashMap<K, V, S> {
         Some(bucket_array_ref.keys(with_key))
     }
 
+    /// Shrinks the bucket array of each segment to fit the number of its elements,
+    /// if the array is more than `min_shrink_factor` times longer than the fitted
+    /// length. The arrays are not shrunk below the length allocated for the initial
+    /// capacity. Returns the number of the segments shrunk.
+    #[cfg(feature = "sync")]
+    pub(crate) fn shrink_to_fit(&self, min_shrink_factor: usize) -> usize {
+        self.segments
+            .iter()
+            .filter(|Segment { bucket_array, len }| {
+                let bucket_array_ref = BucketArrayRef {
+                    bucket_array,
+                    build_hasher: &self.build_hasher,
+                    len,
+                };
+                bucket_array_ref.shrink_to_fit(self.initial_bucket_array_len, min_shrink_factor)
+            })
+            .count()
+    }
+
+    /// Returns the capacity of the segment at `segment`, which is half the number
+    /// of its buckets, or `None` if the segment does not exist.
+    #[cfg(feature = "sync")]
+    pub(crate) fn segment_capacity(&self, segment: usize) -> Option<usize> {
+        let guard = &crossbeam_epoch::pin();
+        let s = self.segments.get(segment)?;
+        let array = unsafe { s.bucket_array.load_consume(guard).as_ref() };
+        Some(array.map_or(0, BucketArray::capacity))
+    }
+
+    /// Returns the key in the bucket at `index` (modulo the number of the
+    /// buckets) of the segment at `segment`, or `None` if the segment does not
+    /// exist or the bucket is empty.
+    #[cfg(feature = "sync")]
+    pub(crate) fn key_at<T>(
+        &self,
+        segment: usize,
+        index: usize,
+        with_key: impl FnMut(&K) -> T,
+    ) -> Option<T> {
+        let Segment {
+            ref bucket_array,
+            ref len,
+        } = self.segments.get(segment)?;
+
+        let bucket_array_ref = BucketArrayRef {
+            bucket_array,
+            build_hasher: &self.build_hasher,
+            len,
+        };
+
+        bucket_array_ref.key_at(index, with_key)
+    }
+
     pub(crate) fn iter(&self) -> Iter<'_, K, V>
     where
         K: Clone,
@@ -790,6 +881,54 @@ mod tests {
         run_deferred();
     }
 
+    #[cfg(feature = "sync")]
+    #[test]
+    fn shrink_to_fit() {
+        const MAX_VALUE: i32 = 4096;
+
+        let map =
+            HashMap::with_num_segments_capacity_and_hasher(4, 0, DefaultHashBuilder::default());
+
+        for i in 0..MAX_VALUE {
+            assert_eq!(map.insert_entry_and(i, map.hash(&i), i, |_, v| *v), None);
+        }
+        let grown_capacity = map.capacity();
+        assert_eq!(map.shrink_to_fit(1), 0);
+
+        for i in 16..MAX_VALUE {
+            assert_eq!(map.remove(map.hash(&i), |&k| k == i), Some(i));
+        }
+        assert_eq!(map.len(), 16);
+
+        assert_eq!(map.shrink_to_fit(1), 4);
+        assert!(map.capacity() < grown_capacity / 4);
+        // Already fitted.
+        assert_eq!(map.shrink_to_fit(1), 0);
+
+        for i in 0..16 {
+            assert_eq!(map.get(map.hash(&i), |&k| k == i), Some(i));
+        }
+        for i in 16..32 {
+            assert_eq!(map.insert_entry_and(i, map.hash(&i), i, |_, v| *v), None);
+        }
+        assert_eq!(map.len(), 32);
+
+        // The bucket arrays are not shrunk below the initial capacity.
+        let map = HashMap::with_num_segments_capacity_and_hasher(
+            4,
+            MAX_VALUE as usize,
+            DefaultHashBuilder::default(),
+        );
+        let initial_capacity = map.capacity();
+        for i in 0..16 {
+            assert_eq!(map.insert_entry_and(i, map.hash(&i), i, |_, v| *v), None);
+        }
+        assert_eq!(map.shrink_to_fit(1), 0);
+        assert_eq!(map.capacity(), initial_capacity);
+
+        run_deferred();
+    }
+
     // Ignore this test and some other tests on 32-bit mips targets to avoid the following
     // error on QEMU user space emulator:
     //
@@ -1756,6 +1895,43 @@ mod tests {
         run_deferred();
     }
 
+    #[cfg(feature = "sync")]
+    #[test]
+    fn modify_entry_if() {
+        const NUM_VALUES: i32 = 512;
+
+        let double_if_even = |_: &i32, v: &i32| (*v % 2 == 0).then(|| *v * 2);
+
+        let map = HashMap::with_capacity(0);
+
+        for i in (0..NUM_VALUES).filter(|i| i % 4 != 0) {
+            assert_eq!(map.insert_entry_and(i, map.hash(&i), i, |_, v| *v), None);
+        }
+
+        for i in 0..NUM_VALUES {
+            let previous =
+                map.modify_entry_if_and(map.hash(&i), |&k| k == i, double_if_even, |_, v| *v);
+            if i % 4 != 0 && i % 2 == 0 {
+                assert_eq!(previous, Some(i));
+            } else {
+                assert_eq!(previous, None);
+            }
+        }
+
+        // The absent keys are not inserted.
+        assert_eq!(map.len(), (NUM_VALUES - NUM_VALUES / 4) as usize);
+        for i in 0..NUM_VALUES {
+            let expected = match i {
+                i if i % 4 == 0 => None,
+                i if i % 2 == 0 => Some(i * 2),
+                i => Some(i),
+            };
+            assert_eq!(map.get(map.hash(&i), |&k| k == i), expected);
+        }
+
+        run_deferred();
+    }
+
     #[test]
     fn keys_in_single_segment() {
         let map =
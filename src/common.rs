@@ -5,9 +5,17 @@ pub(crate) mod concurrent;
 pub(crate) mod deque;
 pub(crate) mod entry;
 pub(crate) mod error;
+pub(crate) mod error_cache;
 pub(crate) mod frequency_sketch;
+pub(crate) mod hasher;
+#[cfg(feature = "numa")]
+pub(crate) mod numa;
+pub(crate) mod sys_memory;
+#[cfg(feature = "sync")]
+pub(crate) mod thread_number;
 pub(crate) mod time;
 pub(crate) mod timer_wheel;
+pub(crate) mod zeroizer;
 
 #[cfg(test)]
 pub(crate) mod test_utils;
@@ -16,6 +24,8 @@ use self::concurrent::constants::{
     DEFAULT_EVICTION_BATCH_SIZE, DEFAULT_MAINTENANCE_TASK_TIMEOUT_MILLIS,
     DEFAULT_MAX_LOG_SYNC_REPEATS,
 };
+#[cfg(feature = "sync")]
+use crate::policy::WriteRetryStrategy;
 
 // Note: `CacheRegion` cannot have more than four enum variants. This is because
 // `crate::{sync,unsync}::DeqNodes` uses a `tagptr::TagNonNull<DeqNode<T>, 2>`
@@ -80,6 +90,24 @@ pub(crate) struct HousekeeperConfig {
     /// The batch size of entries to be processed by each internal eviction method.
     /// Default: `EVICTION_BATCH_SIZE`.
     pub(crate) eviction_batch_size: u32,
+    /// If `true`, the cache operations do not run the pending tasks. They are run
+    /// only by the `run_pending_tasks` method, or when a write has to wait for a
+    /// room in the full write log channel. This also disables watching the memory
+    /// pressure. Default: `false`.
+    #[cfg(feature = "sync")]
+    pub(crate) manual_maintenance: bool,
+    /// The strategy of a writer to wait for a room in the full write log channel.
+    /// Default: `WriteRetryStrategy::default()`
+    #[cfg(feature = "sync")]
+    pub(crate) write_retry_strategy: WriteRetryStrategy,
+    /// The maximum time that a single run of the pending tasks may spend. When set,
+    /// the work left over is continued by the next runs. Default: `None`.
+    #[cfg(feature = "sync")]
+    pub(crate) maintenance_time_budget: Option<Duration>,
+    /// The interval at which a background thread runs the pending tasks to remove
+    /// the expired entries, even while the cache is idle. Default: `None`.
+    #[cfg(feature = "sync")]
+    pub(crate) eager_expiration_interval: Option<Duration>,
 }
 
 impl Default for HousekeeperConfig {
@@ -90,6 +118,14 @@ impl Default for HousekeeperConfig {
             ),
             max_log_sync_repeats: DEFAULT_MAX_LOG_SYNC_REPEATS as u32,
             eviction_batch_size: DEFAULT_EVICTION_BATCH_SIZE,
+            #[cfg(feature = "sync")]
+            manual_maintenance: false,
+            #[cfg(feature = "sync")]
+            write_retry_strategy: WriteRetryStrategy::default(),
+            #[cfg(feature = "sync")]
+            maintenance_time_budget: None,
+            #[cfg(feature = "sync")]
+            eager_expiration_interval: None,
         }
     }
 }
@@ -108,6 +144,14 @@ impl HousekeeperConfig {
             max_log_sync_repeats: max_log_sync_repeats
                 .unwrap_or(DEFAULT_MAX_LOG_SYNC_REPEATS as u32),
             eviction_batch_size: eviction_batch_size.unwrap_or(DEFAULT_EVICTION_BATCH_SIZE),
+            #[cfg(feature = "sync")]
+            manual_maintenance: false,
+            #[cfg(feature = "sync")]
+            write_retry_strategy: WriteRetryStrategy::default(),
+            #[cfg(feature = "sync")]
+            maintenance_time_budget: None,
+            #[cfg(feature = "sync")]
+            eager_expiration_interval: None,
         }
     }
 }
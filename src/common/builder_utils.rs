@@ -1,5 +1,7 @@
 use std::time::Duration;
 
+use super::{error::BuilderError, sys_memory};
+
 const YEAR_SECONDS: u64 = 365 * 24 * 3600;
 
 pub(crate) fn ensure_expirations_or_panic(
@@ -14,3 +16,108 @@ pub(crate) fn ensure_expirations_or_panic(
         assert!(d <= max_duration, "time_to_idle is longer than 1000 years");
     }
 }
+
+pub(crate) fn validate_config(
+    num_segments: Option<usize>,
+    max_capacity: Option<u64>,
+    has_weigher: bool,
+    time_to_live: Option<Duration>,
+    time_to_idle: Option<Duration>,
+) -> Result<(), BuilderError> {
+    let max_duration = Duration::from_secs(1_000 * YEAR_SECONDS);
+    if num_segments == Some(0) {
+        return Err(BuilderError::ZeroSegments);
+    }
+    if has_weigher && max_capacity.is_none() {
+        return Err(BuilderError::WeigherWithoutMaxCapacity);
+    }
+    match time_to_live {
+        Some(d) if d.is_zero() => return Err(BuilderError::ZeroTimeToLive),
+        Some(d) if d > max_duration => return Err(BuilderError::TimeToLiveTooLong),
+        _ => (),
+    }
+    match time_to_idle {
+        Some(d) if d.is_zero() => return Err(BuilderError::ZeroTimeToIdle),
+        Some(d) if d > max_duration => return Err(BuilderError::TimeToIdleTooLong),
+        _ => (),
+    }
+    Ok(())
+}
+
+pub(crate) fn derive_initial_capacity(
+    initial_capacity: Option<usize>,
+    max_capacity: Option<u64>,
+    has_weigher: bool,
+    load_factor: Option<f64>,
+) -> Result<Option<usize>, BuilderError> {
+    let Some(load_factor) = load_factor else {
+        return Ok(initial_capacity);
+    };
+    if !(load_factor > 0.0 && load_factor <= 1.0) {
+        return Err(BuilderError::InvalidLoadFactor);
+    }
+    if initial_capacity.is_some() || has_weigher {
+        return Ok(initial_capacity);
+    }
+    Ok(max_capacity.map(|max_cap| (max_cap as f64 * load_factor).ceil() as usize))
+}
+
+/// Derives the max capacity in bytes from the memory available to the process,
+/// when `memory_fraction` is set. Otherwise, returns `max_capacity` as is.
+pub(crate) fn derive_max_capacity(
+    max_capacity: Option<u64>,
+    has_weigher: bool,
+    memory_fraction: Option<f64>,
+) -> Result<Option<u64>, BuilderError> {
+    let Some(fraction) = memory_fraction else {
+        return Ok(max_capacity);
+    };
+    memory_fraction_of(
+        sys_memory::available_memory(),
+        max_capacity,
+        has_weigher,
+        fraction,
+    )
+    .map(Some)
+}
+
+/// Returns the `fraction` of the `memory` after validating the configuration. If
+/// the `memory` could not be detected, returns the `fallback` max capacity
+/// instead, or an error if there is no fallback.
+pub(crate) fn memory_fraction_of(
+    memory: Option<u64>,
+    fallback: Option<u64>,
+    has_weigher: bool,
+    fraction: f64,
+) -> Result<u64, BuilderError> {
+    if !(fraction > 0.0 && fraction <= 1.0) {
+        return Err(BuilderError::InvalidMemoryFraction);
+    }
+    if !has_weigher {
+        return Err(BuilderError::MemoryFractionWithoutWeigher);
+    }
+    match (memory, fallback) {
+        (Some(memory), _) => Ok((memory as f64 * fraction) as u64),
+        (None, Some(max_capacity)) => Ok(max_capacity),
+        (None, None) => Err(BuilderError::MemoryNotDetected),
+    }
+}
+
+/// Validates the sum of the tenant quotas, `total_quota`, against the max
+/// capacity.
+#[cfg(feature = "sync")]
+pub(crate) fn validate_tenant_quotas(
+    max_capacity: Option<u64>,
+    total_quota: u64,
+) -> Result<(), BuilderError> {
+    match max_capacity {
+        None => Err(BuilderError::TenantQuotasWithoutMaxCapacity),
+        Some(max_capacity) if total_quota > max_capacity => {
+            Err(BuilderError::TenantQuotasExceedMaxCapacity {
+                total: total_quota,
+                max_capacity,
+            })
+        }
+        Some(_) => Ok(()),
+    }
+}
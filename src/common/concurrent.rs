@@ -1,4 +1,7 @@
-use crate::common::{deque::DeqNode, time::Instant};
+use crate::{
+    common::{deque::DeqNode, time::Instant, zeroizer::ValueZeroizer},
+    weigher::WeighContext,
+};
 
 use parking_lot::Mutex;
 use std::{fmt, ptr::NonNull, sync::Arc};
@@ -33,7 +36,7 @@ use self::entry_info::EntryInfo;
 
 use super::timer_wheel::TimerNode;
 
-pub(crate) type Weigher<K, V> = Arc<dyn Fn(&K, &V) -> u32 + Send + Sync + 'static>;
+pub(crate) type Weigher<K, V> = Arc<dyn Fn(&K, &V, &WeighContext) -> u32 + Send + Sync + 'static>;
 
 pub(crate) trait AccessTime {
     fn last_accessed(&self) -> Option<Instant>;
@@ -170,6 +173,11 @@ impl<K> Default for DeqNodes<K> {
 unsafe impl<K> Send for DeqNodes<K> {}
 
 impl<K> DeqNodes<K> {
+    #[cfg(feature = "sync")]
+    pub(crate) fn access_order_q_node(&self) -> Option<KeyDeqNodeAo<K>> {
+        self.access_order_q_node
+    }
+
     pub(crate) fn set_timer_node(&mut self, timer_node: Option<DeqNodeTimer<K>>) {
         self.timer_node = timer_node;
     }
@@ -179,6 +187,11 @@ pub(crate) struct ValueEntry<K, V> {
     pub(crate) value: V,
     info: TrioArc<EntryInfo<K>>,
     nodes: TrioArc<Mutex<DeqNodes<K>>>,
+    zeroizer: ValueZeroizer<V>,
+    /// The version of the value. Always `0` unless the cache was built with
+    /// `support_entry_versions`.
+    #[cfg(feature = "entry-versions")]
+    version: u64,
 }
 
 impl<K, V> ValueEntry<K, V> {
@@ -190,7 +203,29 @@ impl<K, V> ValueEntry<K, V> {
             value,
             info: entry_info,
             nodes: TrioArc::new(Mutex::new(DeqNodes::default())),
+            zeroizer: ValueZeroizer::default(),
+            #[cfg(feature = "entry-versions")]
+            version: 0,
+        }
+    }
+
+    /// Sets the zeroizer to call on the value when this entry is dropped.
+    #[cfg(feature = "sync")]
+    pub(crate) fn with_zeroizer(mut self, zeroizer: ValueZeroizer<V>) -> Self {
+        self.zeroizer = zeroizer;
+        self
+    }
+
+    /// Sets the version of this entry. A new version is given to every inserted or
+    /// updated value.
+    #[cfg(feature = "sync")]
+    #[cfg_attr(not(feature = "entry-versions"), allow(unused_mut, unused_variables))]
+    pub(crate) fn with_version(mut self, version: u64) -> Self {
+        #[cfg(feature = "entry-versions")]
+        {
+            self.version = version;
         }
+        self
     }
 
     pub(crate) fn new_from(value: V, entry_info: TrioArc<EntryInfo<K>>, other: &Self) -> Self {
@@ -200,6 +235,9 @@ impl<K, V> ValueEntry<K, V> {
             value,
             info: entry_info,
             nodes: TrioArc::clone(&other.nodes),
+            zeroizer: other.zeroizer,
+            #[cfg(feature = "entry-versions")]
+            version: other.version,
         }
     }
 
@@ -207,6 +245,14 @@ impl<K, V> ValueEntry<K, V> {
         &self.info
     }
 
+    #[cfg(feature = "sync")]
+    pub(crate) fn version(&self) -> u64 {
+        #[cfg(feature = "entry-versions")]
+        return self.version;
+        #[cfg(not(feature = "entry-versions"))]
+        0
+    }
+
     pub(crate) fn is_admitted(&self) -> bool {
         self.info.is_admitted()
     }
@@ -269,12 +315,44 @@ impl<K, V> ValueEntry<K, V> {
         nodes.access_order_q_node = None;
         nodes.write_order_q_node = None;
     }
+
+    /// Takes the value out of the entry if there is no other reference to the
+    /// entry. Otherwise, returns a clone of the value.
+    #[cfg(feature = "sync")]
+    pub(crate) fn into_value(this: TrioArc<Self>) -> V
+    where
+        V: Clone,
+    {
+        match TrioArc::try_unwrap(this) {
+            Ok(entry) => {
+                #[cfg(feature = "unstable-debug-counters")]
+                self::debug_counters::InternalGlobalDebugCounters::value_entry_dropped();
+
+                // We cannot move the value out of `entry` because `ValueEntry` may
+                // implement `Drop`. Read the fields out instead, and then forget
+                // the entry.
+                let entry = std::mem::ManuallyDrop::new(entry);
+                // SAFETY: The fields are read exactly once, and `entry` will not
+                // be used or dropped after that.
+                unsafe {
+                    std::mem::drop(std::ptr::read(&entry.info));
+                    std::mem::drop(std::ptr::read(&entry.nodes));
+                    std::ptr::read(&entry.value)
+                }
+            }
+            Err(this) => this.value.clone(),
+        }
+    }
 }
 
-#[cfg(feature = "unstable-debug-counters")]
+#[cfg(any(feature = "unstable-debug-counters", feature = "zeroize"))]
 impl<K, V> Drop for ValueEntry<K, V> {
     fn drop(&mut self) {
+        #[cfg(feature = "unstable-debug-counters")]
         self::debug_counters::InternalGlobalDebugCounters::value_entry_dropped();
+
+        #[cfg(feature = "zeroize")]
+        self.zeroizer.zeroize(&mut self.value);
     }
 }
 
@@ -57,4 +57,26 @@ impl AtomicInstant {
         let ts = unsafe { std::mem::transmute(instant.inner_clock()) };
         self.instant.store(ts, Ordering::Release);
     }
+
+    /// Sets the instant unless it is already set to the same or a later instant.
+    pub(crate) fn set_instant_max(&self, instant: Instant) {
+        debug_assert_eq!(
+            TypeId::of::<ClockInstant>(),
+            TypeId::of::<quanta::Instant>()
+        );
+        let ts: u64 = unsafe { std::mem::transmute(instant.inner_clock()) };
+        // `u64::MAX` means unset, so `fetch_max` cannot be used.
+        let mut current = self.instant.load(Ordering::Acquire);
+        while current == u64::MAX || current < ts {
+            match self.instant.compare_exchange_weak(
+                current,
+                ts,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => return,
+                Err(c) => current = c,
+            }
+        }
+    }
 }
@@ -1,16 +1,38 @@
+// The `AtomicInstant` used when `AtomicU64` or `quanta` is not available, e.g. on
+// 32-bit MCUs and WASM.
+//
+// The instant is stored as the signed nanoseconds from a process-wide origin,
+// which covers about 292 years in both directions. With the `atomic64` feature, it
+// is stored in an `AtomicU64`. Otherwise, it is stored in a pair of `AtomicU32`s
+// guarded by a sequence lock, so it takes 12 bytes without any lock or heap
+// allocation.
+
 use super::Instant;
+use crate::common::time::CheckedTimeOps;
+
+use once_cell::sync::Lazy;
+use std::time::Duration;
+
+#[cfg(feature = "atomic64")]
+use std::sync::atomic::{AtomicU64, Ordering};
+
+#[cfg(not(feature = "atomic64"))]
+use std::sync::atomic::{self, AtomicU32, Ordering};
 
-use parking_lot::RwLock;
+/// The value of an `AtomicNanos` representing no instant.
+const UNSET: i64 = i64::MIN;
+
+static ORIGIN: Lazy<Instant> = Lazy::new(Instant::now);
 
 #[derive(Debug)]
 pub(crate) struct AtomicInstant {
-    instant: RwLock<Option<Instant>>,
+    nanos: AtomicNanos,
 }
 
 impl Default for AtomicInstant {
     fn default() -> Self {
         Self {
-            instant: RwLock::new(None),
+            nanos: AtomicNanos::new(UNSET),
         }
     }
 }
@@ -23,18 +45,232 @@ impl AtomicInstant {
     }
 
     pub(crate) fn clear(&self) {
-        *self.instant.write() = None;
+        self.nanos.store(UNSET);
     }
 
     pub(crate) fn is_set(&self) -> bool {
-        self.instant.read().is_some()
+        self.nanos.load() != UNSET
     }
 
     pub(crate) fn instant(&self) -> Option<Instant> {
-        *self.instant.read()
+        let nanos = self.nanos.load();
+        if nanos == UNSET {
+            return None;
+        }
+        let offset = Duration::from_nanos(nanos.unsigned_abs());
+        if nanos >= 0 {
+            ORIGIN.checked_add(offset)
+        } else {
+            ORIGIN.checked_sub(offset)
+        }
     }
 
     pub(crate) fn set_instant(&self, instant: Instant) {
-        *self.instant.write() = Some(instant);
+        self.nanos.store(Self::to_nanos(instant));
+    }
+
+    /// Sets the instant unless it is already set to the same or a later instant.
+    pub(crate) fn set_instant_max(&self, instant: Instant) {
+        // `UNSET` is less than any instant.
+        self.nanos.fetch_max(Self::to_nanos(instant));
+    }
+
+    fn to_nanos(instant: Instant) -> i64 {
+        let origin = *ORIGIN;
+        match instant.checked_duration_since(origin) {
+            Some(d) => i64::try_from(d.as_nanos()).unwrap_or(i64::MAX),
+            None => {
+                let d = origin.checked_duration_since(instant).unwrap_or_default();
+                // `UNSET + 1` is the earliest representable instant.
+                i64::try_from(d.as_nanos()).map_or(UNSET + 1, |n| -n)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "atomic64")]
+#[derive(Debug)]
+struct AtomicNanos(AtomicU64);
+
+#[cfg(feature = "atomic64")]
+impl AtomicNanos {
+    fn new(nanos: i64) -> Self {
+        Self(AtomicU64::new(nanos as u64))
+    }
+
+    fn load(&self) -> i64 {
+        self.0.load(Ordering::Acquire) as i64
+    }
+
+    fn store(&self, nanos: i64) {
+        self.0.store(nanos as u64, Ordering::Release);
+    }
+
+    fn fetch_max(&self, nanos: i64) {
+        let mut current = self.load();
+        while current < nanos {
+            match self.0.compare_exchange_weak(
+                current as u64,
+                nanos as u64,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => return,
+                Err(c) => current = c as i64,
+            }
+        }
+    }
+}
+
+/// An `i64` stored in two `AtomicU32`s. `seq` is odd while a writer is storing the
+/// halves, and the readers retry when it was odd or has changed while they were
+/// loading the halves.
+#[cfg(not(feature = "atomic64"))]
+#[derive(Debug)]
+struct AtomicNanos {
+    seq: AtomicU32,
+    high: AtomicU32,
+    low: AtomicU32,
+}
+
+#[cfg(not(feature = "atomic64"))]
+impl AtomicNanos {
+    fn new(nanos: i64) -> Self {
+        let bits = nanos as u64;
+        Self {
+            seq: AtomicU32::new(0),
+            high: AtomicU32::new((bits >> 32) as u32),
+            low: AtomicU32::new(bits as u32),
+        }
+    }
+
+    fn load(&self) -> i64 {
+        loop {
+            let seq1 = self.seq.load(Ordering::Acquire);
+            if seq1 & 1 == 1 {
+                std::hint::spin_loop();
+                continue;
+            }
+            let high = self.high.load(Ordering::Relaxed);
+            let low = self.low.load(Ordering::Relaxed);
+            atomic::fence(Ordering::Acquire);
+            if self.seq.load(Ordering::Relaxed) == seq1 {
+                return ((u64::from(high) << 32) | u64::from(low)) as i64;
+            }
+        }
+    }
+
+    fn store(&self, nanos: i64) {
+        let seq = self.lock_writer();
+        self.store_halves(nanos);
+        self.seq.store(seq.wrapping_add(2), Ordering::Release);
+    }
+
+    fn fetch_max(&self, nanos: i64) {
+        let seq = self.lock_writer();
+        let high = self.high.load(Ordering::Relaxed);
+        let low = self.low.load(Ordering::Relaxed);
+        let current = ((u64::from(high) << 32) | u64::from(low)) as i64;
+        if current < nanos {
+            self.store_halves(nanos);
+        }
+        self.seq.store(seq.wrapping_add(2), Ordering::Release);
+    }
+
+    /// Makes `seq` odd to exclude the other writers, and returns its even value
+    /// before that.
+    fn lock_writer(&self) -> u32 {
+        let mut seq = self.seq.load(Ordering::Relaxed);
+        loop {
+            if seq & 1 == 1 {
+                std::hint::spin_loop();
+                seq = self.seq.load(Ordering::Relaxed);
+                continue;
+            }
+            match self.seq.compare_exchange_weak(
+                seq,
+                seq.wrapping_add(1),
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => break,
+                Err(current) => seq = current,
+            }
+        }
+        atomic::fence(Ordering::Release);
+        seq
+    }
+
+    fn store_halves(&self, nanos: i64) {
+        let bits = nanos as u64;
+        self.high.store((bits >> 32) as u32, Ordering::Relaxed);
+        self.low.store(bits as u32, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AtomicInstant, Instant};
+    use crate::common::time::CheckedTimeOps;
+
+    use std::{sync::Arc, time::Duration};
+
+    #[test]
+    fn round_trip() {
+        let ai = AtomicInstant::default();
+        assert!(!ai.is_set());
+        assert_eq!(ai.instant(), None);
+
+        let now = Instant::now();
+        let earlier = now.checked_sub(Duration::from_millis(1)).unwrap();
+        let later = now.checked_add(Duration::from_secs(3600)).unwrap();
+        for instant in [now, earlier, later] {
+            ai.set_instant(instant);
+            assert!(ai.is_set());
+            assert_eq!(ai.instant(), Some(instant));
+        }
+
+        ai.clear();
+        assert_eq!(ai.instant(), None);
+    }
+
+    #[test]
+    fn set_instant_max() {
+        let ai = AtomicInstant::default();
+        let now = Instant::now();
+        let earlier = now.checked_sub(Duration::from_millis(1)).unwrap();
+
+        ai.set_instant_max(now);
+        assert_eq!(ai.instant(), Some(now));
+        ai.set_instant_max(earlier);
+        assert_eq!(ai.instant(), Some(now));
+    }
+
+    #[test]
+    fn concurrent_stores() {
+        let now = Instant::now();
+        // More than 2^32 nanoseconds (about 4.3 seconds) apart so that the high
+        // halves differ.
+        let instants = (0..4u64)
+            .map(|i| now.checked_add(Duration::from_secs(i * 5)).unwrap())
+            .collect::<Vec<_>>();
+        let ai = Arc::new(AtomicInstant::new(instants[0]));
+
+        let handles = instants
+            .iter()
+            .map(|&instant| {
+                let ai = Arc::clone(&ai);
+                let instants = instants.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..10_000 {
+                        ai.set_instant(instant);
+                        // Never observe a torn value mixing the halves of two
+                        // instants.
+                        assert!(instants.contains(&ai.instant().unwrap()));
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        handles.into_iter().for_each(|h| h.join().unwrap());
     }
 }
@@ -16,8 +16,38 @@ pub(crate) const WRITE_LOG_CH_SIZE: usize =
 // estimated number of entries to evict)
 pub(crate) const DEFAULT_EVICTION_BATCH_SIZE: u32 = WRITE_LOG_CH_SIZE as u32;
 
+// The number of unlinked deque nodes to keep per deque for reuse. One write log
+// sync worth of nodes.
+pub(crate) const MAX_FREE_DEQ_NODES: usize = WRITE_LOG_CH_SIZE;
+
+// The maintenance tasks shrink a segment of the internal hash table when it is
+// this many times larger than needed for its entries. They try to shrink only
+// after the number of the entries has dropped below 1/this of its high-watermark.
+#[cfg(feature = "sync")]
+pub(crate) const AUTO_SHRINK_FACTOR: usize = 4;
+
 /// The default timeout duration for the `run_pending_tasks` method.
 pub(crate) const DEFAULT_MAINTENANCE_TASK_TIMEOUT_MILLIS: u64 = 100;
 
 #[cfg(feature = "sync")]
-pub(crate) const WRITE_RETRY_INTERVAL_MICROS: u64 = 50;
+pub(crate) const DEFAULT_WRITE_RETRY_INTERVAL_MICROS: u64 = 50;
+
+// The number of times a writer retries while the write log is full, before it
+// waits for its turn to run the pending tasks.
+#[cfg(feature = "sync")]
+pub(crate) const MAX_WRITE_RETRIES: u32 = 8;
+
+// The maximum number of random buckets to probe per key when sampling the keys.
+#[cfg(feature = "sync")]
+pub(crate) const MAX_SAMPLE_ATTEMPTS_PER_KEY: usize = 32;
+
+// The frequency added to an admission candidate that was recently evicted, when the
+// second-chance readmission is enabled. It lets the candidate win against the
+// victims that are read slightly more often.
+#[cfg(feature = "sync")]
+pub(crate) const SECOND_CHANCE_FREQUENCY_BOOST: u32 = 2;
+
+// The maximum number of deque nodes examined at the LRU position to find the
+// entries of a tenant to evict, or to compare with an admission candidate.
+#[cfg(feature = "sync")]
+pub(crate) const MAX_TENANT_WALK: usize = 1024;
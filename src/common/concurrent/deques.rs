@@ -1,4 +1,4 @@
-use super::{KeyHashDate, ValueEntry};
+use super::{constants::MAX_FREE_DEQ_NODES, KeyHashDate, ValueEntry};
 use crate::common::{
     deque::{DeqNode, Deque},
     CacheRegion,
@@ -25,15 +25,24 @@ unsafe impl<K> Send for Deques<K> {}
 impl<K> Default for Deques<K> {
     fn default() -> Self {
         Self {
-            window: Deque::new(CacheRegion::Window),
-            probation: Deque::new(CacheRegion::MainProbation),
-            protected: Deque::new(CacheRegion::MainProtected),
-            write_order: Deque::new(CacheRegion::Other),
+            window: Deque::with_free_list(CacheRegion::Window, MAX_FREE_DEQ_NODES),
+            probation: Deque::with_free_list(CacheRegion::MainProbation, MAX_FREE_DEQ_NODES),
+            protected: Deque::with_free_list(CacheRegion::MainProtected, MAX_FREE_DEQ_NODES),
+            write_order: Deque::with_free_list(CacheRegion::Other, MAX_FREE_DEQ_NODES),
         }
     }
 }
 
 impl<K> Deques<K> {
+    /// Deallocates the free node allocations kept for reuse by the deques.
+    #[cfg(feature = "sync")]
+    pub(crate) fn release_free_nodes(&mut self) {
+        self.window.release_free_nodes();
+        self.probation.release_free_nodes();
+        self.protected.release_free_nodes();
+        self.write_order.release_free_nodes();
+    }
+
     pub(crate) fn select_mut(
         &mut self,
         selector: CacheRegion,
@@ -52,13 +61,14 @@ impl<K> Deques<K> {
         khd: KeyHashDate<K>,
         entry: &TrioArc<ValueEntry<K, V>>,
     ) {
-        let node = Box::new(DeqNode::new(khd));
-        let node = match region {
-            CacheRegion::Window => self.window.push_back(node),
-            CacheRegion::MainProbation => self.probation.push_back(node),
-            CacheRegion::MainProtected => self.protected.push_back(node),
+        let deq = match region {
+            CacheRegion::Window => &mut self.window,
+            CacheRegion::MainProbation => &mut self.probation,
+            CacheRegion::MainProtected => &mut self.protected,
             CacheRegion::Other => unreachable!(),
         };
+        let node = deq.new_node(khd);
+        let node = deq.push_back(node);
         let tagged_node = TagNonNull::compose(node, region as usize);
         entry.set_access_order_q_node(Some(tagged_node));
     }
@@ -68,7 +78,7 @@ impl<K> Deques<K> {
         kd: KeyHashDate<K>,
         entry: &TrioArc<ValueEntry<K, V>>,
     ) {
-        let node = Box::new(DeqNode::new(kd));
+        let node = self.write_order.new_node(kd);
         let node = self.write_order.push_back(node);
         entry.set_write_order_q_node(Some(node));
     }
@@ -1,15 +1,28 @@
-use std::sync::atomic::{self, AtomicBool, AtomicU16, AtomicU32, Ordering};
+use std::sync::atomic::{self, AtomicU16, AtomicU32, Ordering};
 
 use super::{AccessTime, KeyHash};
 use crate::common::{concurrent::atomic_time::AtomicInstant, time::Instant};
 
+/// The bit of `EntryInfo::state` set when the entry has been admitted to the
+/// cache. When unset, the entry is _temporary_ admitted to the cache or evicted
+/// from the cache (so it should not have LRU nodes).
+const ADMITTED: u32 = 1;
+/// The bit of `EntryInfo::state` set when the hot key listener has been called
+/// for the entry.
+#[cfg(feature = "sync")]
+const HOT: u32 = 1 << 1;
+/// The unit of the pin count kept in the upper bits of `EntryInfo::state`.
+#[cfg(feature = "sync")]
+const PIN: u32 = 1 << 2;
+
 #[derive(Debug)]
 pub(crate) struct EntryInfo<K> {
     key_hash: KeyHash<K>,
-    /// `is_admitted` indicates that the entry has been admitted to the cache. When
-    /// `false`, it means the entry is _temporary_ admitted to the cache or evicted
-    /// from the cache (so it should not have LRU nodes).
-    is_admitted: AtomicBool,
+    /// `state` packs the `ADMITTED` and `HOT` flags, and in the bits above them,
+    /// the number of live guards pinning this entry. While the pin count is
+    /// non-zero, the entry is exempt from the eviction and expiration. They share
+    /// a single word so that they do not grow this struct.
+    state: AtomicU32,
     /// `entry_gen` (entry generation) is incremented every time the entry is updated
     /// in the concurrent hash table.
     entry_gen: AtomicU16,
@@ -21,6 +34,12 @@ pub(crate) struct EntryInfo<K> {
     last_modified: AtomicInstant,
     expiration_time: AtomicInstant,
     policy_weight: AtomicU32,
+    /// `op_counts` is allocated only when the cache counts the reads and writes of
+    /// each entry. See `with_op_counts`. The field exists only with the
+    /// `entry-stats` feature, so that the entries do not pay for the pointer
+    /// otherwise.
+    #[cfg(feature = "entry-stats")]
+    op_counts: Option<Box<OpCounts>>,
 }
 
 impl<K> EntryInfo<K> {
@@ -31,7 +50,7 @@ impl<K> EntryInfo<K> {
 
         Self {
             key_hash,
-            is_admitted: AtomicBool::default(),
+            state: AtomicU32::default(),
             // `entry_gen` starts at 1 and `policy_gen` start at 0.
             entry_gen: AtomicU16::new(1),
             policy_gen: AtomicU16::new(0),
@@ -39,7 +58,24 @@ impl<K> EntryInfo<K> {
             last_modified: AtomicInstant::new(timestamp),
             expiration_time: AtomicInstant::default(),
             policy_weight: AtomicU32::new(policy_weight),
+            #[cfg(feature = "entry-stats")]
+            op_counts: None,
+        }
+    }
+
+    /// Makes this entry record its creation time and count its reads and writes.
+    /// Does nothing without the `entry-stats` feature.
+    #[cfg_attr(not(feature = "entry-stats"), allow(unused_mut, unused_variables))]
+    pub(crate) fn with_op_counts(mut self, timestamp: Instant) -> Self {
+        #[cfg(feature = "entry-stats")]
+        {
+            self.op_counts = Some(Box::new(OpCounts {
+                created_at: timestamp,
+                read_count: AtomicU32::default(),
+                write_count: AtomicU32::new(1),
+            }));
         }
+        self
     }
 
     #[inline]
@@ -49,12 +85,16 @@ impl<K> EntryInfo<K> {
 
     #[inline]
     pub(crate) fn is_admitted(&self) -> bool {
-        self.is_admitted.load(Ordering::Acquire)
+        self.state.load(Ordering::Acquire) & ADMITTED != 0
     }
 
     #[inline]
     pub(crate) fn set_admitted(&self, value: bool) {
-        self.is_admitted.store(value, Ordering::Release);
+        if value {
+            self.state.fetch_or(ADMITTED, Ordering::AcqRel);
+        } else {
+            self.state.fetch_and(!ADMITTED, Ordering::AcqRel);
+        }
     }
 
     /// Returns `true` if the `ValueEntry` having this `EntryInfo` is dirty.
@@ -116,6 +156,86 @@ impl<K> EntryInfo<K> {
         self.policy_weight.store(size, Ordering::Release);
     }
 
+    #[cfg(feature = "sync")]
+    #[inline]
+    pub(crate) fn is_pinned(&self) -> bool {
+        self.state.load(Ordering::Acquire) >= PIN
+    }
+
+    #[cfg(feature = "sync")]
+    #[inline]
+    pub(crate) fn pin(&self) {
+        self.state.fetch_add(PIN, Ordering::AcqRel);
+    }
+
+    #[cfg(feature = "sync")]
+    #[inline]
+    pub(crate) fn unpin(&self) {
+        self.state.fetch_sub(PIN, Ordering::AcqRel);
+    }
+
+    /// Marks this entry as hot. Returns `true` if it was not marked yet.
+    #[cfg(feature = "sync")]
+    #[inline]
+    pub(crate) fn mark_hot(&self) -> bool {
+        self.state.fetch_or(HOT, Ordering::AcqRel) & HOT == 0
+    }
+
+    /// Returns the creation time, or `None` if this entry does not count its
+    /// operations.
+    #[cfg(any(feature = "sync", feature = "entry-stats"))]
+    #[inline]
+    pub(crate) fn created_at(&self) -> Option<Instant> {
+        #[cfg(feature = "entry-stats")]
+        return self.op_counts.as_ref().map(|c| c.created_at);
+        #[cfg(not(feature = "entry-stats"))]
+        None
+    }
+
+    /// Returns the number of the reads, or `0` if this entry does not count its
+    /// operations.
+    #[cfg(any(feature = "sync", feature = "entry-stats"))]
+    #[inline]
+    pub(crate) fn read_count(&self) -> u32 {
+        #[cfg(feature = "entry-stats")]
+        return self
+            .op_counts
+            .as_ref()
+            .map_or(0, |c| c.read_count.load(Ordering::Relaxed));
+        #[cfg(not(feature = "entry-stats"))]
+        0
+    }
+
+    #[inline]
+    pub(crate) fn incr_read_count(&self) {
+        #[cfg(feature = "entry-stats")]
+        if let Some(c) = &self.op_counts {
+            OpCounts::saturating_incr(&c.read_count);
+        }
+    }
+
+    /// Returns the number of the writes, or `0` if this entry does not count its
+    /// operations.
+    #[cfg(any(feature = "sync", feature = "entry-stats"))]
+    #[inline]
+    pub(crate) fn write_count(&self) -> u32 {
+        #[cfg(feature = "entry-stats")]
+        return self
+            .op_counts
+            .as_ref()
+            .map_or(0, |c| c.write_count.load(Ordering::Relaxed));
+        #[cfg(not(feature = "entry-stats"))]
+        0
+    }
+
+    #[inline]
+    pub(crate) fn incr_write_count(&self) {
+        #[cfg(feature = "entry-stats")]
+        if let Some(c) = &self.op_counts {
+            OpCounts::saturating_incr(&c.write_count);
+        }
+    }
+
     #[inline]
     pub(crate) fn expiration_time(&self) -> Option<Instant> {
         self.expiration_time.instant()
@@ -130,6 +250,25 @@ impl<K> EntryInfo<K> {
     }
 }
 
+#[cfg(feature = "entry-stats")]
+#[derive(Debug)]
+struct OpCounts {
+    created_at: Instant,
+    /// The number of the cache hits on the entry. Saturates at `u32::MAX`.
+    read_count: AtomicU32,
+    /// The number of the writes (the insert and the updates) on the entry.
+    /// Saturates at `u32::MAX`.
+    write_count: AtomicU32,
+}
+
+#[cfg(feature = "entry-stats")]
+impl OpCounts {
+    #[inline]
+    fn saturating_incr(count: &AtomicU32) {
+        let _ = count.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |c| c.checked_add(1));
+    }
+}
+
 #[cfg(feature = "unstable-debug-counters")]
 impl<K> Drop for EntryInfo<K> {
     fn drop(&mut self) {
@@ -191,7 +330,8 @@ mod test {
         // e.g. "1.64"
         let ver =
             option_env!("RUSTC_SEMVER").expect("RUSTC_SEMVER env var was not set at compile time");
-        let is_quanta_enabled = cfg!(feature = "quanta");
+        // The layout of the timestamps depends only on the `atomic64` feature.
+        let is_atomic64_enabled = cfg!(feature = "atomic64");
         let arch = if cfg!(target_os = "linux") {
             if cfg!(target_pointer_width = "64") {
                 Linux64
@@ -214,16 +354,18 @@ mod test {
             panic!("Unsupported target architecture");
         };
 
-        let expected_sizes = match (arch, is_quanta_enabled) {
+        let expected_sizes = match (arch, is_atomic64_enabled) {
             (Linux64 | Linux32Arm, true) => vec![("1.51", 56)],
             (Linux32X86, true) => vec![("1.51", 48)],
             (Linux32Mips, true) => unimplemented!(),
             (MacOS64, true) => vec![("1.62", 56)],
-            (Linux64, false) => vec![("1.66", 104), ("1.60", 128)],
-            (Linux32X86, false) => unimplemented!(),
-            (Linux32Arm | Linux32Mips, false) => vec![("1.66", 104), ("1.62", 128), ("1.60", 80)],
-            (MacOS64, false) => vec![("1.62", 104)],
+            (Linux64 | Linux32Arm | Linux32Mips, false) => vec![("1.51", 72)],
+            (Linux32X86, false) => vec![("1.51", 64)],
+            (MacOS64, false) => vec![("1.62", 72)],
         };
+        // The `entry-stats` feature adds the pointer to the operation counts,
+        // rounded up to the alignment of the struct.
+        let op_counts_size = if cfg!(feature = "entry-stats") { 8 } else { 0 };
 
         let mut expected = None;
         for (ver_str, size) in expected_sizes {
@@ -234,7 +376,7 @@ mod test {
         }
 
         if let Some(size) = expected {
-            assert_eq!(size_of::<EntryInfo<()>>(), size);
+            assert_eq!(size_of::<EntryInfo<()>>(), size + op_counts_size);
         } else {
             panic!("No expected size for {arch:?} with Rust version {ver}");
         }
@@ -6,8 +6,12 @@ use super::{
 };
 use crate::common::time::{CheckedTimeOps, Instant};
 use crate::common::HousekeeperConfig;
+#[cfg(feature = "sync")]
+use crate::policy::WriteRetryStrategy;
 
 use parking_lot::{Mutex, MutexGuard};
+#[cfg(feature = "sync")]
+use std::sync::atomic::AtomicUsize;
 use std::{
     sync::atomic::{AtomicBool, Ordering},
     time::Duration,
@@ -32,15 +36,17 @@ pub(crate) struct Housekeeper {
     /// A flag to indicate if the last call on `run_pending_tasks` method left some
     /// entries to evict.
     ///
-    /// Used only when the eviction listener closure is set for this cache instance
-    /// because, if not, `run_pending_tasks` will never leave entries to evict.
+    /// Used only when the eviction listener closure or the maintenance time budget
+    /// is set for this cache instance because, if not, `run_pending_tasks` will
+    /// never leave entries to evict.
     more_entries_to_evict: Option<AtomicBool>,
     /// The timeout duration for the `run_pending_tasks` method. This is a safe-guard
     /// to prevent cache read/write operations (that may call `run_pending_tasks`
     /// internally) from being blocked for a long time when the user wrote a slow
     /// eviction listener closure.
     ///
-    /// Used only when the eviction listener closure is set for this cache instance.
+    /// Used only when the eviction listener closure or the maintenance time budget
+    /// is set for this cache instance. If both are set, the shorter one is used.
     maintenance_task_timeout: Option<Duration>,
     /// The maximum repeat count for receiving operation logs from the read and write
     /// log channels. Default: `MAX_LOG_SYNC_REPEATS`.
@@ -49,27 +55,74 @@ pub(crate) struct Housekeeper {
     /// Default: `EVICTION_BATCH_SIZE`.
     eviction_batch_size: u32,
     auto_run_enabled: AtomicBool,
+    /// The strategy of a writer to wait for a room in the full write log channel.
+    #[cfg(feature = "sync")]
+    write_retry_strategy: WriteRetryStrategy,
+    /// The lock taken in FIFO order by the writers waiting for a room in the full
+    /// write log channel, and the number of these writers.
+    #[cfg(feature = "sync")]
+    write_queue: Mutex<()>,
+    #[cfg(feature = "sync")]
+    queued_writers: AtomicUsize,
+}
+
+/// The turn of a writer to send to the full write log channel. Dropping it hands
+/// over the turn to the next queued writer.
+#[cfg(feature = "sync")]
+pub(crate) struct WriteTurn<'a> {
+    housekeeper: &'a Housekeeper,
+    lock: Option<MutexGuard<'a, ()>>,
+}
+
+#[cfg(feature = "sync")]
+impl Drop for WriteTurn<'_> {
+    fn drop(&mut self) {
+        self.housekeeper
+            .queued_writers
+            .fetch_sub(1, Ordering::AcqRel);
+        if let Some(lock) = self.lock.take() {
+            MutexGuard::unlock_fair(lock);
+        }
+    }
 }
 
 impl Housekeeper {
-    pub(crate) fn new(is_eviction_listener_enabled: bool, config: HousekeeperConfig) -> Self {
-        let (more_entries_to_evict, maintenance_task_timeout) = if is_eviction_listener_enabled {
-            (
-                Some(AtomicBool::new(false)),
-                Some(config.maintenance_task_timeout),
-            )
-        } else {
-            (None, None)
-        };
+    pub(crate) fn new(
+        is_eviction_listener_enabled: bool,
+        config: HousekeeperConfig,
+        now: Instant,
+    ) -> Self {
+        #[cfg(feature = "sync")]
+        let time_budget = config.maintenance_time_budget;
+        #[cfg(not(feature = "sync"))]
+        let time_budget: Option<Duration> = None;
+
+        let (more_entries_to_evict, maintenance_task_timeout) =
+            match (is_eviction_listener_enabled, time_budget) {
+                (true, budget) => (
+                    Some(AtomicBool::new(false)),
+                    Some(budget.map_or(config.maintenance_task_timeout, |b| {
+                        b.min(config.maintenance_task_timeout)
+                    })),
+                ),
+                (false, Some(budget)) => (Some(AtomicBool::new(false)), Some(budget)),
+                (false, None) => (None, None),
+            };
 
         Self {
             run_lock: Mutex::default(),
-            run_after: AtomicInstant::new(Self::sync_after(Instant::now())),
+            run_after: AtomicInstant::new(Self::sync_after(now)),
             more_entries_to_evict,
             maintenance_task_timeout,
             max_log_sync_repeats: config.max_log_sync_repeats,
             eviction_batch_size: config.eviction_batch_size,
-            auto_run_enabled: AtomicBool::new(true),
+            auto_run_enabled: AtomicBool::new(!config.manual_maintenance),
+            #[cfg(feature = "sync")]
+            write_retry_strategy: config.write_retry_strategy,
+            #[cfg(feature = "sync")]
+            write_queue: Mutex::default(),
+            #[cfg(feature = "sync")]
+            queued_writers: AtomicUsize::default(),
         }
     }
 
@@ -101,6 +154,32 @@ impl Housekeeper {
             && (ch_len >= ch_flush_point || now >= self.run_after.instant().unwrap())
     }
 
+    pub(crate) fn is_auto_run_enabled(&self) -> bool {
+        self.auto_run_enabled.load(Ordering::Relaxed)
+    }
+
+    #[cfg(feature = "sync")]
+    pub(crate) fn write_retry_strategy(&self) -> &WriteRetryStrategy {
+        &self.write_retry_strategy
+    }
+
+    /// Returns `true` if some writers are waiting for a room in the full write log
+    /// channel.
+    #[cfg(feature = "sync")]
+    pub(crate) fn has_queued_writers(&self) -> bool {
+        self.queued_writers.load(Ordering::Acquire) > 0
+    }
+
+    /// Waits until the writers queued earlier have sent their write ops.
+    #[cfg(feature = "sync")]
+    pub(crate) fn wait_for_write_turn(&self) -> WriteTurn<'_> {
+        self.queued_writers.fetch_add(1, Ordering::AcqRel);
+        WriteTurn {
+            housekeeper: self,
+            lock: Some(self.write_queue.lock()),
+        }
+    }
+
     pub(crate) fn run_pending_tasks<T: InnerSync>(&self, cache: &T) {
         let lock = self.run_lock.lock();
         self.do_run_pending_tasks(cache, lock);
@@ -115,7 +194,7 @@ impl Housekeeper {
         }
     }
 
-    fn do_run_pending_tasks<T: InnerSync>(&self, cache: &T, _lock: MutexGuard<'_, ()>) {
+    fn do_run_pending_tasks<T: InnerSync>(&self, cache: &T, lock: MutexGuard<'_, ()>) {
         let now = cache.now();
         self.run_after.set_instant(Self::sync_after(now));
         let timeout = self.maintenance_task_timeout;
@@ -123,6 +202,10 @@ impl Housekeeper {
         let batch_size = self.eviction_batch_size;
         let more_to_evict = cache.run_pending_tasks(timeout, repeats, batch_size);
         self.set_more_entries_to_evict(more_to_evict);
+        // Hand over the lock to the threads waiting in `run_pending_tasks` in FIFO
+        // order, so that none of the writers waiting for a room in the write log
+        // is starved.
+        MutexGuard::unlock_fair(lock);
     }
 
     fn sync_after(now: Instant) -> Instant {
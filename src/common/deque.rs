@@ -12,7 +12,7 @@
 // For full authorship information, see the version control history of
 // https://github.com/rust-lang/rust/ or https://thanks.rust-lang.org
 
-use std::{marker::PhantomData, ptr::NonNull};
+use std::{marker::PhantomData, mem::MaybeUninit, ptr::NonNull};
 
 use super::CacheRegion;
 
@@ -76,6 +76,15 @@ pub(crate) struct Deque<T> {
     head: Option<NonNull<DeqNode<T>>>,
     tail: Option<NonNull<DeqNode<T>>>,
     cursor: Option<DeqCursor<T>>,
+    /// Free list of the allocations of unlinked nodes, kept for reuse by
+    /// `new_node`. Their contents have already been dropped.
+    ///
+    /// This is not a slab: every node is still a separate heap allocation, because
+    /// nodes are handed out and taken back as `Box<DeqNode<T>>` (e.g. by
+    /// `pop_front` or when they are moved between deques). It only saves the
+    /// allocator round trips of the nodes that are dropped and created again.
+    free_nodes: Vec<Box<MaybeUninit<DeqNode<T>>>>,
+    max_free_nodes: usize,
     marker: PhantomData<Box<DeqNode<T>>>,
 }
 
@@ -102,12 +111,21 @@ impl<T> Drop for Deque<T> {
 // Inner crate public function/methods
 impl<T> Deque<T> {
     pub(crate) fn new(region: CacheRegion) -> Self {
+        Self::with_free_list(region, 0)
+    }
+
+    /// Creates a deque with a free list that keeps up to `max_free_nodes`
+    /// allocations of the nodes dropped by `unlink_and_drop`, and hands them out
+    /// again from `new_node`.
+    pub(crate) fn with_free_list(region: CacheRegion, max_free_nodes: usize) -> Self {
         Self {
             region,
             len: 0,
             head: None,
             tail: None,
             cursor: None,
+            free_nodes: Vec::new(),
+            max_free_nodes,
             marker: PhantomData,
         }
     }
@@ -158,6 +176,32 @@ impl<T> Deque<T> {
         })
     }
 
+    /// Creates a new node for the given element. If there is a free allocation
+    /// in the free list, reuses it instead of allocating a new one.
+    pub(crate) fn new_node(&mut self, element: T) -> Box<DeqNode<T>> {
+        match self.free_nodes.pop() {
+            Some(mut slot) => {
+                slot.write(DeqNode::new(element));
+                // SAFETY: The slot has just been initialized, and `MaybeUninit<T>`
+                // has the same layout as `T`.
+                unsafe { Box::from_raw(Box::into_raw(slot).cast::<DeqNode<T>>()) }
+            }
+            None => Box::new(DeqNode::new(element)),
+        }
+    }
+
+    /// Deallocates the node allocations in the free list.
+    #[cfg(feature = "sync")]
+    pub(crate) fn release_free_nodes(&mut self) {
+        self.free_nodes = Vec::new();
+    }
+
+    /// Returns the number of node allocations in the free list.
+    #[cfg(test)]
+    pub(crate) fn free_node_count(&self) -> usize {
+        self.free_nodes.len()
+    }
+
     pub(crate) fn peek_back(&self) -> Option<&DeqNode<T>> {
         self.tail.as_ref().map(|node| unsafe { node.as_ref() })
     }
@@ -265,10 +309,19 @@ impl<T> Deque<T> {
     /// This method takes care not to create mutable references to `element`, to
     /// maintain validity of aliasing pointers.
     ///
+    /// If the free list has room, only the node's contents are dropped and its
+    /// allocation is kept for reuse by `new_node`.
+    ///
     /// Panics:
     pub(crate) unsafe fn unlink_and_drop(&mut self, node: NonNull<DeqNode<T>>) {
         self.unlink(node);
-        std::mem::drop(Box::from_raw(node.as_ptr()));
+        if self.free_nodes.len() < self.max_free_nodes {
+            std::ptr::drop_in_place(node.as_ptr());
+            let slot = Box::from_raw(node.as_ptr().cast::<MaybeUninit<DeqNode<T>>>());
+            self.free_nodes.push(slot);
+        } else {
+            std::mem::drop(Box::from_raw(node.as_ptr()));
+        }
     }
 
     pub(crate) fn reset_cursor(&mut self) {
@@ -751,6 +804,56 @@ mod tests {
         assert_eq!(node1b.element, "a".to_string());
     }
 
+    #[test]
+    fn free_list() {
+        use std::{cell::RefCell, rc::Rc};
+
+        struct X(u32, Rc<RefCell<Vec<u32>>>);
+
+        impl Drop for X {
+            fn drop(&mut self) {
+                self.1.borrow_mut().push(self.0)
+            }
+        }
+
+        let mut deque: Deque<X> = Deque::with_free_list(MainProbation, 1);
+        let dropped = Rc::new(RefCell::new(Vec::default()));
+
+        let node1 = deque.new_node(X(1, Rc::clone(&dropped)));
+        let node1_ptr = deque.push_back(node1);
+        let node2 = deque.new_node(X(2, Rc::clone(&dropped)));
+        let node2_ptr = deque.push_back(node2);
+        assert_eq!(deque.free_node_count(), 0);
+
+        // The free list has room for one node, so the allocation of node1 is kept.
+        unsafe { deque.unlink_and_drop(node1_ptr) };
+        assert_eq!(deque.free_node_count(), 1);
+        assert_eq!(*dropped.borrow(), &[1]);
+
+        // The free list is full, so node2 is deallocated.
+        unsafe { deque.unlink_and_drop(node2_ptr) };
+        assert_eq!(deque.free_node_count(), 1);
+        assert_eq!(*dropped.borrow(), &[1, 2]);
+
+        // The next node reuses the allocation of node1.
+        let node3 = deque.new_node(X(3, Rc::clone(&dropped)));
+        let node3_ptr = deque.push_back(node3);
+        assert_eq!(node3_ptr, node1_ptr);
+        assert_eq!(deque.free_node_count(), 0);
+        assert_eq!(deque.peek_front().unwrap().element.0, 3);
+
+        // A deque created by `new` does not keep any allocations.
+        let mut deque2: Deque<X> = Deque::new(MainProbation);
+        let node4 = deque2.new_node(X(4, Rc::clone(&dropped)));
+        let node4_ptr = deque2.push_back(node4);
+        unsafe { deque2.unlink_and_drop(node4_ptr) };
+        assert_eq!(deque2.free_node_count(), 0);
+
+        unsafe { deque.unlink_and_drop(node3_ptr) };
+        std::mem::drop(deque);
+        assert_eq!(*dropped.borrow(), &[1, 2, 4, 3]);
+    }
+
     #[test]
     fn drop() {
         use std::{cell::RefCell, rc::Rc};
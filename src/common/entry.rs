@@ -1,4 +1,4 @@
-use std::{fmt::Debug, sync::Arc};
+use std::{fmt::Debug, sync::Arc, time::Duration};
 
 /// A snapshot of a single entry in the cache.
 ///
@@ -22,6 +22,7 @@ pub struct Entry<K, V> {
     value: V,
     is_fresh: bool,
     is_old_value_replaced: bool,
+    metadata: Option<EntryMetadata>,
 }
 
 impl<K, V> Debug for Entry<K, V>
@@ -35,6 +36,7 @@ where
             .field("value", &self.value)
             .field("is_fresh", &self.is_fresh)
             .field("is_old_value_replaced", &self.is_old_value_replaced)
+            .field("metadata", &self.metadata)
             .finish()
     }
 }
@@ -51,9 +53,25 @@ impl<K, V> Entry<K, V> {
             value,
             is_fresh,
             is_old_value_replaced,
+            metadata: None,
         }
     }
 
+    #[cfg(feature = "sync")]
+    pub(crate) fn with_metadata(self, metadata: EntryMetadata) -> Self {
+        Self {
+            metadata: Some(metadata),
+            ..self
+        }
+    }
+
+    /// Consumes this `Entry`, returning the shared key and the value. The entry
+    /// must have been created with the key.
+    #[cfg(feature = "sync")]
+    pub(crate) fn into_shared_key_and_value(self) -> (Arc<K>, V) {
+        (self.key.expect("Bug: Key is None"), self.value)
+    }
+
     /// Returns a reference to the wrapped key.
     pub fn key(&self) -> &K {
         self.key.as_ref().expect("Bug: Key is None")
@@ -89,4 +107,87 @@ impl<K, V> Entry<K, V> {
     pub fn is_old_value_replaced(&self) -> bool {
         self.is_old_value_replaced
     }
+
+    /// Returns the metadata of the entry at the time this `Entry` was constructed.
+    ///
+    /// The metadata is only available for the `Entry` returned by the `get_entry`
+    /// method of the cache. Returns `None` for the `Entry` returned by other
+    /// methods.
+    pub fn metadata(&self) -> Option<&EntryMetadata> {
+        self.metadata.as_ref()
+    }
+}
+
+/// The metadata of a cached entry, such as its age and the time until it expires.
+///
+/// It is carried by the [`Entry`] returned by the `get_entry` method of the cache,
+/// and is useful to emit HTTP headers like `Age` and `Cache-Control: max-age`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EntryMetadata {
+    age: Duration,
+    expires_in: Option<Duration>,
+    version: u64,
+    counts: Option<(u32, u32)>,
+}
+
+impl EntryMetadata {
+    #[cfg(feature = "sync")]
+    pub(crate) fn new(
+        age: Duration,
+        expires_in: Option<Duration>,
+        version: u64,
+        counts: Option<(u32, u32)>,
+    ) -> Self {
+        Self {
+            age,
+            expires_in,
+            version,
+            counts,
+        }
+    }
+
+    /// Returns the time elapsed since the value was inserted or last updated.
+    pub fn age(&self) -> Duration {
+        self.age
+    }
+
+    /// Returns the time until the entry expires by the time-to-live, the
+    /// time-to-idle or the per-entry expiration, whichever comes first. Returns
+    /// `None` if the entry does not expire.
+    ///
+    /// Note that the time-to-idle is counted from the read that returned this
+    /// metadata.
+    pub fn expires_in(&self) -> Option<Duration> {
+        self.expires_in
+    }
+
+    /// Returns the version of the value. When the cache was built with
+    /// `support_entry_versions`, every insert or update of the key gives the value
+    /// a new version that is greater than any version given before by the same
+    /// cache. Otherwise, it is always `0`. The versions require the
+    /// `entry-versions` crate feature.
+    ///
+    /// Pass it to the `insert_if_version` method of the cache to update the value
+    /// only if no one else has updated it since.
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Returns the number of the reads that hit the entry, including the read that
+    /// returned this metadata. The count saturates at `u32::MAX`.
+    ///
+    /// Returns `None` unless the counters are enabled by the `track_entry_counters`
+    /// method of the cache builder, which requires the `entry-stats` crate feature.
+    pub fn read_count(&self) -> Option<u32> {
+        self.counts.map(|(reads, _)| reads)
+    }
+
+    /// Returns the number of the writes on the entry, i.e. `1` for the insert plus
+    /// the number of the updates. The count saturates at `u32::MAX`.
+    ///
+    /// Returns `None` unless the counters are enabled by the `track_entry_counters`
+    /// method of the cache builder, which requires the `entry-stats` crate feature.
+    pub fn write_count(&self) -> Option<u32> {
+        self.counts.map(|(_, writes)| writes)
+    }
 }
@@ -19,3 +19,74 @@ pub enum PredicateError {
     )]
     InvalidationClosuresDisabled,
 }
+
+/// The error type returned by `try_build` methods of the cache builders, such as
+/// [`sync::CacheBuilder::try_build`][sync-try-build], when the builder has an
+/// invalid combination of configurations.
+///
+/// [sync-try-build]: ./sync/struct.CacheBuilder.html#method.try_build
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum BuilderError {
+    /// The number of segments is zero.
+    #[error("The number of segments must be greater than zero")]
+    ZeroSegments,
+
+    /// The time to live is zero. All entries would expire right after they are
+    /// inserted.
+    #[error("time_to_live must be greater than zero")]
+    ZeroTimeToLive,
+
+    /// The time to idle is zero. All entries would expire right after they are
+    /// inserted.
+    #[error("time_to_idle must be greater than zero")]
+    ZeroTimeToIdle,
+
+    /// The time to live is longer than 1000 years.
+    #[error("time_to_live is longer than 1000 years")]
+    TimeToLiveTooLong,
+
+    /// The time to idle is longer than 1000 years.
+    #[error("time_to_idle is longer than 1000 years")]
+    TimeToIdleTooLong,
+
+    /// A weigher is set but the max capacity is not. The weights of the entries
+    /// would never be used as the cache is unbounded.
+    #[error("A weigher is set without the max capacity")]
+    WeigherWithoutMaxCapacity,
+
+    /// The load factor given to `auto_initial_capacity` is not in the range of
+    /// `(0.0, 1.0]`.
+    #[error("The load factor of auto_initial_capacity must be in the range of (0.0, 1.0]")]
+    InvalidLoadFactor,
+
+    /// The max number of concurrent loads is zero.
+    #[error("max_concurrent_loads must not be zero")]
+    ZeroMaxConcurrentLoads,
+
+    /// The fraction given to `max_memory_fraction` is not in the range of
+    /// `(0.0, 1.0]`.
+    #[error("The fraction of max_memory_fraction must be in the range of (0.0, 1.0]")]
+    InvalidMemoryFraction,
+
+    /// `max_memory_fraction` is set without a weigher. The max capacity would be
+    /// a number of entries rather than bytes.
+    #[error("max_memory_fraction is set without a weigher")]
+    MemoryFractionWithoutWeigher,
+
+    /// `max_memory_fraction` is set but the memory available to the process
+    /// could not be detected, and no `max_capacity` is set to fall back to.
+    #[error("Could not detect the memory available to the process for max_memory_fraction")]
+    MemoryNotDetected,
+    /// Tenant quotas are set but the max capacity is not.
+    #[error("Tenant quotas are set without the max capacity")]
+    TenantQuotasWithoutMaxCapacity,
+    /// The sum of the tenant quotas is greater than the max capacity.
+    #[error(
+        "The sum of the tenant quotas {total} is greater than the max capacity {max_capacity}"
+    )]
+    TenantQuotasExceedMaxCapacity { total: u64, max_capacity: u64 },
+    /// The tenant max entries are set but the tenant quotas are not.
+    #[error("Tenant max entries are set without the tenant quotas")]
+    TenantMaxEntriesWithoutQuotas,
+}
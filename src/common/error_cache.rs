@@ -0,0 +1,256 @@
+//! Keeps the errors returned by the `init` closures (or futures) of
+//! `try_get_with` for a while, so that the repeated calls on a failing key return
+//! the cached error instead of calling `init` again.
+
+use std::{
+    any::{Any, TypeId},
+    collections::VecDeque,
+    hash::{BuildHasher, Hash},
+    sync::Arc,
+    time::Duration,
+};
+
+use parking_lot::Mutex;
+
+use super::time::{CheckedTimeOps, Instant};
+
+const ERROR_MAP_NUM_SEGMENTS: usize = 16;
+
+/// The max number of the errors cached at once. When the map is full, the
+/// expired errors are removed, and if none has expired, the oldest error is
+/// removed to make room for the new one.
+const MAX_ERRORS: usize = 4096;
+
+type ErrorObject = Arc<dyn Any + Send + Sync + 'static>;
+
+#[derive(Clone)]
+struct CachedError {
+    // The type ID of the error type `E` of `try_get_with`. A cached error is only
+    // returned to the calls with the same error type.
+    type_id: TypeId,
+    error: ErrorObject,
+    expires_at: Instant,
+    // The sequence number of the insertion, identifying the record of this error
+    // in the insertion order queue.
+    seq: u64,
+}
+
+/// The keys of the cached errors in the order they were cached. As all errors
+/// have the same time to live, this is also the order they expire in.
+///
+/// The queue may have stale records of the replaced or removed errors. They are
+/// skipped when popped, and compacted away when the queue has grown twice as long
+/// as `MAX_ERRORS`.
+struct InsertionOrder<K> {
+    queue: VecDeque<(Arc<K>, u64)>,
+    next_seq: u64,
+}
+
+impl<K> Default for InsertionOrder<K> {
+    fn default() -> Self {
+        Self {
+            queue: VecDeque::default(),
+            next_seq: 0,
+        }
+    }
+}
+
+pub(crate) struct ErrorCache<K, S> {
+    time_to_live: Duration,
+    errors: crate::cht::SegmentedHashMap<Arc<K>, CachedError, S>,
+    order: Mutex<InsertionOrder<K>>,
+}
+
+impl<K, S> ErrorCache<K, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    pub(crate) fn new(time_to_live: Duration, build_hasher: S) -> Self {
+        Self {
+            time_to_live,
+            errors: crate::cht::SegmentedHashMap::with_num_segments_and_hasher(
+                ERROR_MAP_NUM_SEGMENTS,
+                build_hasher,
+            ),
+            order: Mutex::default(),
+        }
+    }
+
+    /// Returns the error cached for the `key` if it has the type `E` and has not
+    /// expired yet.
+    pub(crate) fn get<E>(&self, key: &K, now: Instant) -> Option<Arc<E>>
+    where
+        E: Send + Sync + 'static,
+    {
+        let hash = self.errors.hash(key);
+        let cached = self.errors.get(hash, |k| **k == *key)?;
+        if cached.expires_at <= now {
+            self.errors
+                .remove_if(hash, |k| **k == *key, |_, c| c.expires_at <= now);
+            return None;
+        }
+        if cached.type_id != TypeId::of::<E>() {
+            return None;
+        }
+        cached.error.downcast().ok()
+    }
+
+    /// Caches the `error` for the `key`, replacing the existing one.
+    pub(crate) fn insert<E>(&self, key: Arc<K>, error: Arc<E>, now: Instant)
+    where
+        E: Send + Sync + 'static,
+    {
+        let Some(expires_at) = now.checked_add(self.time_to_live) else {
+            return;
+        };
+        let hash = self.errors.hash(&*key);
+        let mut order = self.order.lock();
+        let cached = CachedError {
+            type_id: TypeId::of::<E>(),
+            error,
+            expires_at,
+            seq: order.next_seq,
+        };
+        order.next_seq += 1;
+
+        if self.errors.len() >= MAX_ERRORS && !self.errors.contains_key(hash, |k| *k == key) {
+            self.pop_expired(&mut order, now);
+            if self.errors.len() >= MAX_ERRORS {
+                // After `pop_expired`, the front of the queue is the oldest error
+                // still cached.
+                if let Some((oldest, seq)) = order.queue.pop_front() {
+                    self.remove_if_seq(&oldest, seq);
+                }
+            }
+        }
+        order.queue.push_back((Arc::clone(&key), cached.seq));
+        self.errors
+            .insert_with_or_modify(key, hash, || cached.clone(), |_, _| cached.clone());
+
+        if order.queue.len() > MAX_ERRORS * 2 {
+            order
+                .queue
+                .retain(|(key, seq)| self.expires_at(key, *seq).is_some());
+        }
+    }
+
+    /// Removes the errors expired at `now`. Called by the housekeeper of the cache,
+    /// and when the map is full.
+    pub(crate) fn remove_expired(&self, now: Instant) {
+        if self.errors.is_empty() {
+            return;
+        }
+        self.pop_expired(&mut self.order.lock(), now);
+    }
+
+    /// Pops the expired and stale records from the front of the queue, removing
+    /// the expired errors. Stops at the first error still cached and not expired,
+    /// so the cost is bounded by the number of the popped records.
+    fn pop_expired(&self, order: &mut InsertionOrder<K>, now: Instant) {
+        while let Some((key, seq)) = order.queue.front() {
+            if matches!(self.expires_at(key, *seq), Some(ts) if ts > now) {
+                break;
+            }
+            if let Some((key, seq)) = order.queue.pop_front() {
+                self.remove_if_seq(&key, seq);
+            }
+        }
+    }
+
+    /// Returns the expiration time of the error cached for the `key`, if it is the
+    /// one inserted with the sequence number `seq`.
+    fn expires_at(&self, key: &Arc<K>, seq: u64) -> Option<Instant> {
+        let hash = self.errors.hash(&**key);
+        self.errors.get_key_value_and_then(
+            hash,
+            |k| k == key,
+            |_, c| (c.seq == seq).then_some(c.expires_at),
+        )
+    }
+
+    fn remove_if_seq(&self, key: &Arc<K>, seq: u64) {
+        let hash = self.errors.hash(&**key);
+        self.errors
+            .remove_if(hash, |k| k == key, |_, c| c.seq == seq);
+    }
+
+    /// Removes the error cached for the `key`, e.g. when a value has been
+    /// inserted for the key.
+    pub(crate) fn remove(&self, key: &K) {
+        if self.errors.is_empty() {
+            return;
+        }
+        let hash = self.errors.hash(key);
+        self.errors.remove(hash, |k| **k == *key);
+    }
+
+    #[cfg(test)]
+    pub(crate) fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::hash_map::RandomState, sync::Arc, time::Duration};
+
+    use super::{ErrorCache, MAX_ERRORS};
+    use crate::common::time::{CheckedTimeOps, Clock, Instant};
+
+    #[test]
+    fn remove_expired() {
+        let (clock, _mock) = Clock::mock();
+        let now = Instant::new(clock.now());
+        let later = now.checked_add(Duration::from_secs(10)).unwrap();
+
+        let errors = ErrorCache::new(Duration::from_secs(10), RandomState::default());
+        errors.insert(Arc::new(1), Arc::new("failed"), now);
+        errors.remove_expired(now);
+        assert_eq!(errors.errors.len(), 1);
+        errors.remove_expired(later);
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn max_errors() {
+        let (clock, _mock) = Clock::mock();
+        let now = Instant::new(clock.now());
+        let later = now.checked_add(Duration::from_secs(10)).unwrap();
+
+        let errors = ErrorCache::new(Duration::from_secs(10), RandomState::default());
+        for i in 0..MAX_ERRORS {
+            errors.insert(Arc::new(i), Arc::new("failed"), now);
+        }
+        // The map is full, so a new error replaces the oldest one.
+        errors.insert(Arc::new(MAX_ERRORS), Arc::new("failed"), now);
+        assert!(errors.get::<&str>(&MAX_ERRORS, now).is_some());
+        assert!(errors.get::<&str>(&0, now).is_none());
+        assert_eq!(errors.errors.len(), MAX_ERRORS);
+
+        // An existing error is replaced without removing another one.
+        errors.insert(Arc::new(1), Arc::new(1u32), now);
+        assert!(errors.get::<u32>(&1, now).is_some());
+        assert!(errors.get::<&str>(&2, now).is_some());
+        assert_eq!(errors.errors.len(), MAX_ERRORS);
+
+        // Once the errors have expired, a new error makes room for itself.
+        errors.insert(Arc::new(MAX_ERRORS + 1), Arc::new("failed"), later);
+        assert!(errors.get::<&str>(&(MAX_ERRORS + 1), later).is_some());
+        assert_eq!(errors.errors.len(), 1);
+        assert_eq!(errors.order.lock().queue.len(), 1);
+    }
+
+    #[test]
+    fn replaced_errors_do_not_grow_order_queue() {
+        let (clock, _mock) = Clock::mock();
+        let now = Instant::new(clock.now());
+
+        let errors = ErrorCache::new(Duration::from_secs(10), RandomState::default());
+        for _ in 0..(MAX_ERRORS * 3) {
+            errors.insert(Arc::new(1), Arc::new("failed"), now);
+        }
+        assert_eq!(errors.errors.len(), 1);
+        assert!(errors.order.lock().queue.len() <= MAX_ERRORS * 2);
+    }
+}
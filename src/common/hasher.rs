@@ -0,0 +1,124 @@
+use std::{
+    collections::hash_map::RandomState,
+    fmt,
+    hash::{BuildHasher, Hasher},
+};
+
+/// A [`BuildHasher`][build-hasher] that wraps another `BuildHasher` and feeds a
+/// per-instance seed to every hasher it builds.
+///
+/// Many fast `BuildHasher` implementations are created with fixed keys (e.g.
+/// `BuildHasherDefault<H>`), so the caches built with them share the same hash
+/// function. An attacker who found a set of colliding keys for one of these
+/// caches can then degrade all of them. Wrapping the `BuildHasher` in a
+/// `SeededState` with a random seed gives each cache its own hash function.
+///
+/// Use [`SeededState::new`](#method.new) to get a random seed, or
+/// [`SeededState::with_seed`](#method.with_seed) to get a deterministic one in
+/// tests. The cache builders also have `build_with_seeded_hasher` method as a
+/// shorthand for the former.
+///
+/// [build-hasher]: https://doc.rust-lang.org/std/hash/trait.BuildHasher.html
+///
+/// # Examples
+///
+/// ```rust
+/// use moka::{sync::Cache, SeededState};
+/// use std::{collections::hash_map::DefaultHasher, hash::BuildHasherDefault};
+///
+/// type FixedState = BuildHasherDefault<DefaultHasher>;
+///
+/// // The type of this cache is: Cache<i32, String, SeededState<FixedState>>
+/// let cache = Cache::builder()
+///     .max_capacity(100)
+///     .build_with_seeded_hasher(FixedState::default());
+/// cache.insert(1, "one".to_string());
+///
+/// // Use a fixed seed for a reproducible test.
+/// let cache: Cache<i32, String, _> = Cache::builder()
+///     .build_with_hasher(SeededState::with_seed(FixedState::default(), 42));
+/// ```
+#[derive(Clone)]
+pub struct SeededState<S = RandomState> {
+    build_hasher: S,
+    seed: u64,
+}
+
+impl<S> SeededState<S> {
+    /// Wraps `build_hasher` with a random seed.
+    pub fn new(build_hasher: S) -> Self {
+        Self::with_seed(build_hasher, random_seed())
+    }
+
+    /// Wraps `build_hasher` with the given `seed`.
+    pub fn with_seed(build_hasher: S, seed: u64) -> Self {
+        Self { build_hasher, seed }
+    }
+
+    /// Returns a reference to the wrapped `BuildHasher`.
+    pub fn inner(&self) -> &S {
+        &self.build_hasher
+    }
+}
+
+impl<S: Default> Default for SeededState<S> {
+    fn default() -> Self {
+        Self::new(S::default())
+    }
+}
+
+impl<S: BuildHasher> BuildHasher for SeededState<S> {
+    type Hasher = S::Hasher;
+
+    fn build_hasher(&self) -> Self::Hasher {
+        let mut hasher = self.build_hasher.build_hasher();
+        hasher.write_u64(self.seed);
+        hasher
+    }
+}
+
+// Do not expose the seed.
+impl<S> fmt::Debug for SeededState<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SeededState").finish_non_exhaustive()
+    }
+}
+
+fn random_seed() -> u64 {
+    // `RandomState` is seeded from the OS at the first use in each thread, and
+    // then gets different keys for every instance.
+    RandomState::new().build_hasher().finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SeededState;
+    use std::{
+        collections::hash_map::DefaultHasher,
+        hash::{BuildHasher, BuildHasherDefault, Hash, Hasher},
+    };
+
+    type FixedState = BuildHasherDefault<DefaultHasher>;
+
+    fn hash_of(build_hasher: &impl BuildHasher, key: &str) -> u64 {
+        let mut hasher = build_hasher.build_hasher();
+        key.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn seeded_hashes() {
+        let fixed1 = SeededState::with_seed(FixedState::default(), 1);
+        let fixed2 = SeededState::with_seed(FixedState::default(), 1);
+        let fixed3 = SeededState::with_seed(FixedState::default(), 2);
+        assert_eq!(hash_of(&fixed1, "a"), hash_of(&fixed2, "a"));
+        assert_ne!(hash_of(&fixed1, "a"), hash_of(&fixed3, "a"));
+
+        let random1 = SeededState::new(FixedState::default());
+        let random2 = SeededState::new(FixedState::default());
+        assert_ne!(hash_of(&random1, "a"), hash_of(&random2, "a"));
+
+        // Clones share the seed.
+        assert_eq!(hash_of(&random1, "a"), hash_of(&random1.clone(), "a"));
+    }
+}
@@ -0,0 +1,310 @@
+//! Support for the NUMA-aware mode of `sync::SegmentedCache`.
+//!
+//! In this mode, each segment is assigned to a NUMA node in round-robin order.
+//! The segment is created on a thread pinned to the CPUs of its node, so that
+//! the memory of its internal data structures is allocated node-local (Linux
+//! uses the first-touch policy by default), and `run_pending_tasks` processes
+//! the segment on a worker thread pinned to the same node.
+
+use crossbeam_channel::Sender;
+use std::{
+    panic::{catch_unwind, resume_unwind, AssertUnwindSafe},
+    path::Path,
+};
+
+type Job = Box<dyn FnOnce() + Send>;
+
+pub(crate) struct NumaTopology {
+    /// The CPU IDs of each NUMA node, ordered by the node ID.
+    node_cpus: Vec<Vec<usize>>,
+}
+
+impl NumaTopology {
+    /// Detects the NUMA topology of the running machine.
+    ///
+    /// Returns `None` if the machine has only one NUMA node, or the topology
+    /// could not be detected (e.g. on a platform other than Linux).
+    pub(crate) fn detect() -> Option<Self> {
+        if cfg!(target_os = "linux") {
+            Self::from_sysfs(Path::new("/sys/devices/system/node"))
+        } else {
+            None
+        }
+    }
+
+    /// Reads the topology from a directory laid out like
+    /// `/sys/devices/system/node` (`node0/cpulist`, `node1/cpulist`, ...).
+    fn from_sysfs(dir: &Path) -> Option<Self> {
+        let mut nodes = std::fs::read_dir(dir)
+            .ok()?
+            .filter_map(|entry| {
+                let entry = entry.ok()?;
+                let name = entry.file_name();
+                let id = name.to_str()?.strip_prefix("node")?.parse::<usize>().ok()?;
+                let cpu_list = std::fs::read_to_string(entry.path().join("cpulist")).ok()?;
+                let cpus = parse_cpu_list(&cpu_list)?;
+                // Skip memory-only nodes.
+                (!cpus.is_empty()).then_some((id, cpus))
+            })
+            .collect::<Vec<_>>();
+
+        if nodes.len() < 2 {
+            return None;
+        }
+
+        nodes.sort_unstable_by_key(|(id, _)| *id);
+        let node_cpus = nodes.into_iter().map(|(_, cpus)| cpus).collect();
+        Some(Self { node_cpus })
+    }
+
+    #[cfg(test)]
+    pub(crate) fn with_node_cpus(node_cpus: Vec<Vec<usize>>) -> Self {
+        Self { node_cpus }
+    }
+
+    pub(crate) fn node_count(&self) -> usize {
+        self.node_cpus.len()
+    }
+
+    /// Returns the NUMA node assigned to the segment at `segment_index`.
+    pub(crate) fn node_of_segment(&self, segment_index: usize) -> usize {
+        segment_index % self.node_count()
+    }
+
+    /// Calls `f` for every NUMA node, each on a scoped thread pinned to the CPUs
+    /// of the node, and returns the results ordered by the node ID.
+    ///
+    /// This spawns a thread per node on every call. Use `NodeWorkers` to run
+    /// tasks repeatedly.
+    pub(crate) fn map_nodes<F, R>(&self, f: F) -> Vec<R>
+    where
+        F: Fn(usize) -> R + Sync,
+        R: Send,
+    {
+        let f = &f;
+        std::thread::scope(|s| {
+            let handles = (0..self.node_count())
+                .map(|node| {
+                    s.spawn(move || {
+                        pin_current_thread(&self.node_cpus[node]);
+                        f(node)
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            handles
+                .into_iter()
+                .map(|h| h.join().unwrap_or_else(|e| std::panic::resume_unwind(e)))
+                .collect()
+        })
+    }
+}
+
+/// A worker thread for each NUMA node, pinned to the CPUs of the node once when
+/// spawned. The threads exit when this is dropped.
+pub(crate) struct NodeWorkers {
+    topology: NumaTopology,
+    // `None` if the thread for the node could not be spawned.
+    senders: Vec<Option<Sender<Job>>>,
+}
+
+impl NodeWorkers {
+    pub(crate) fn spawn(topology: NumaTopology) -> Self {
+        let senders = topology
+            .node_cpus
+            .iter()
+            .enumerate()
+            .map(|(node, cpus)| {
+                let (snd, rcv) = crossbeam_channel::unbounded::<Job>();
+                let cpus = cpus.clone();
+                std::thread::Builder::new()
+                    .name(format!("moka-numa-node-{node}"))
+                    .spawn(move || {
+                        pin_current_thread(&cpus);
+                        for job in rcv {
+                            job();
+                        }
+                    })
+                    .ok()
+                    .map(|_| snd)
+            })
+            .collect();
+        Self { topology, senders }
+    }
+
+    pub(crate) fn topology(&self) -> &NumaTopology {
+        &self.topology
+    }
+
+    /// Calls `f` for every NUMA node on the worker thread of the node, and waits
+    /// for all of them to return. If the worker of a node is not available, `f`
+    /// is called for the node on the current thread.
+    ///
+    /// # Panics
+    ///
+    /// Panics with the same payload if `f` panics for any node.
+    pub(crate) fn run_on_nodes<F>(&self, f: F)
+    where
+        F: Fn(usize) + Clone + Send + 'static,
+    {
+        let (done_snd, done_rcv) = crossbeam_channel::unbounded();
+        for (node, sender) in self.senders.iter().enumerate() {
+            let (f, done) = (f.clone(), done_snd.clone());
+            let job: Job = Box::new(move || {
+                let result = catch_unwind(AssertUnwindSafe(|| f(node)));
+                // Drop `f` before reporting, so that nothing captured by it
+                // outlives this call.
+                drop(f);
+                let _ = done.send(result);
+            });
+            match sender {
+                Some(sender) => {
+                    if let Err(e) = sender.send(job) {
+                        (e.into_inner())();
+                    }
+                }
+                None => job(),
+            }
+        }
+        drop(done_snd);
+
+        // Wait until every job has completed and dropped its sender.
+        let mut panic = None;
+        for result in done_rcv {
+            if let Err(payload) = result {
+                panic.get_or_insert(payload);
+            }
+        }
+        if let Some(payload) = panic {
+            resume_unwind(payload);
+        }
+    }
+}
+
+/// Pins the current thread to `cpus`. Pinning is done on a best effort basis; if
+/// it fails, the thread keeps running on any CPU. The CPU IDs that do not fit in
+/// `cpu_set_t` are ignored.
+#[cfg(target_os = "linux")]
+fn pin_current_thread(cpus: &[usize]) {
+    let max_cpus = std::mem::size_of::<libc::cpu_set_t>() * 8;
+    // SAFETY: `cpu_set_t` is a plain bit mask, so all zeros is a valid value.
+    // `CPU_SET` panics on a CPU ID that does not fit in the mask, so the IDs are
+    // checked beforehand.
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        libc::CPU_ZERO(&mut set);
+        for &cpu in cpus.iter().filter(|&&cpu| cpu < max_cpus) {
+            libc::CPU_SET(cpu, &mut set);
+        }
+        libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set);
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn pin_current_thread(_cpus: &[usize]) {}
+
+/// Parses a CPU list like `0-3,8-11` used by Linux sysfs.
+fn parse_cpu_list(s: &str) -> Option<Vec<usize>> {
+    let mut cpus = Vec::new();
+    for range in s.trim().split(',').filter(|r| !r.is_empty()) {
+        match range.split_once('-') {
+            Some((first, last)) => {
+                let (first, last) = (first.parse::<usize>().ok()?, last.parse::<usize>().ok()?);
+                cpus.extend(first..=last);
+            }
+            None => cpus.push(range.parse().ok()?),
+        }
+    }
+    Some(cpus)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_cpu_list, pin_current_thread, NodeWorkers, NumaTopology};
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn parse_cpu_lists() {
+        assert_eq!(
+            parse_cpu_list("0-3,8-11\n"),
+            Some(vec![0, 1, 2, 3, 8, 9, 10, 11])
+        );
+        assert_eq!(parse_cpu_list("5"), Some(vec![5]));
+        assert_eq!(parse_cpu_list("\n"), Some(vec![]));
+        assert_eq!(parse_cpu_list("0-x"), None);
+    }
+
+    #[test]
+    fn read_topology_from_sysfs() {
+        let dir = std::env::temp_dir().join(format!("moka-numa-test-{}", std::process::id()));
+        for (node, cpu_list) in [("node1", "4-7\n"), ("node0", "0-3\n"), ("node2", "\n")] {
+            std::fs::create_dir_all(dir.join(node)).unwrap();
+            std::fs::write(dir.join(node).join("cpulist"), cpu_list).unwrap();
+        }
+        std::fs::create_dir_all(dir.join("power")).unwrap();
+
+        // node2 has no CPU, so it is ignored.
+        let topology = NumaTopology::from_sysfs(&dir).unwrap();
+        assert_eq!(topology.node_cpus, vec![vec![0, 1, 2, 3], vec![4, 5, 6, 7]]);
+        assert_eq!(topology.node_of_segment(0), 0);
+        assert_eq!(topology.node_of_segment(3), 1);
+
+        // A single node topology is not NUMA.
+        std::fs::remove_dir_all(dir.join("node1")).unwrap();
+        assert!(NumaTopology::from_sysfs(&dir).is_none());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn map_nodes() {
+        let topology = NumaTopology::with_node_cpus(vec![vec![0], vec![0]]);
+        assert_eq!(topology.map_nodes(|node| node * 10), vec![0, 10]);
+    }
+
+    #[test]
+    fn node_workers_are_reused() {
+        let workers = NodeWorkers::spawn(NumaTopology::with_node_cpus(vec![vec![0], vec![0]]));
+
+        let run = || {
+            let threads = Arc::new(Mutex::new(Vec::new()));
+            let t = Arc::clone(&threads);
+            workers.run_on_nodes(move |node| {
+                t.lock().unwrap().push((node, std::thread::current().id()));
+            });
+            let mut threads = Arc::try_unwrap(threads).unwrap().into_inner().unwrap();
+            threads.sort_unstable_by_key(|(node, _)| *node);
+            threads
+        };
+
+        let first = run();
+        assert_eq!(first.len(), 2);
+        assert_ne!(first[0].1, std::thread::current().id());
+        assert_ne!(first[0].1, first[1].1);
+        // The same threads run the tasks again.
+        assert_eq!(run(), first);
+    }
+
+    #[test]
+    fn node_workers_propagate_panics() {
+        let workers = NodeWorkers::spawn(NumaTopology::with_node_cpus(vec![vec![0], vec![0]]));
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            workers.run_on_nodes(|node| assert_eq!(node, 0));
+        }));
+        assert!(result.is_err());
+
+        // The workers are still alive.
+        let count = Arc::new(Mutex::new(0));
+        let c = Arc::clone(&count);
+        workers.run_on_nodes(move |_| *c.lock().unwrap() += 1);
+        assert_eq!(*count.lock().unwrap(), 2);
+    }
+
+    #[test]
+    fn pin_to_out_of_range_cpus() {
+        // The CPU IDs that do not fit in `cpu_set_t` must not panic.
+        std::thread::spawn(|| pin_current_thread(&[0, 1 << 20]))
+            .join()
+            .unwrap();
+    }
+}
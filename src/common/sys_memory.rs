@@ -0,0 +1,353 @@
+//! Detection of the memory available to the process and the memory pressure,
+//! used by the `max_memory_fraction` and `shed_on_memory_pressure` options of the
+//! cache builders.
+
+#[cfg(feature = "sync")]
+use super::{
+    concurrent::atomic_time::AtomicInstant,
+    time::{CheckedTimeOps, Instant},
+};
+
+use std::path::{Path, PathBuf};
+#[cfg(feature = "sync")]
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+/// The mount point of the cgroup file systems.
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+/// The memory is considered under pressure when some tasks were stalled on memory
+/// for more than this percentage of the time in the last 10 seconds.
+#[cfg(feature = "sync")]
+const MEMORY_PRESSURE_THRESHOLD: f64 = 10.0;
+
+/// How often the memory pressure is checked.
+#[cfg(feature = "sync")]
+const MEMORY_PRESSURE_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Returns the amount of memory in bytes available to the process. This is the
+/// total physical memory of the machine, or the memory limit of the cgroup (v2 or
+/// v1) of the process or any of its ancestors if it is smaller.
+///
+/// Returns `None` if the memory could not be detected (e.g. on a platform other
+/// than Linux).
+pub(crate) fn available_memory() -> Option<u64> {
+    if cfg!(target_os = "linux") {
+        let cgroups = std::fs::read_to_string("/proc/self/cgroup").unwrap_or_default();
+        detect(Path::new("/proc/meminfo"), Path::new(CGROUP_ROOT), &cgroups)
+    } else {
+        None
+    }
+}
+
+/// Reads the memory from a file laid out like `/proc/meminfo` and a directory
+/// laid out like `/sys/fs/cgroup`, for the process in the cgroups listed in
+/// `cgroups` in the format of `/proc/self/cgroup`.
+fn detect(meminfo: &Path, cgroup_root: &Path, cgroups: &str) -> Option<u64> {
+    let total = std::fs::read_to_string(meminfo)
+        .ok()
+        .and_then(|s| parse_mem_total(&s));
+
+    let (v2_dir, v1_dir) = cgroup_dirs(cgroup_root, cgroups);
+    // Returns the smallest limit of the cgroup and its ancestors, as the limit of
+    // an ancestor applies to its descendants too. Returns `None` if none of them
+    // has the file.
+    let limit_in = |dir: PathBuf, file: &'static str| {
+        let limits = dir
+            .ancestors()
+            .take_while(|d| d.starts_with(cgroup_root))
+            .filter_map(|d| std::fs::read_to_string(d.join(file)).ok())
+            .map(|s| parse_cgroup_limit(&s))
+            .collect::<Vec<_>>();
+        (!limits.is_empty()).then(|| limits.into_iter().flatten().min())
+    };
+    // cgroup v2 takes precedence over v1.
+    let limit = limit_in(v2_dir, "memory.max")
+        .or_else(|| limit_in(v1_dir, "memory.limit_in_bytes"))
+        .flatten();
+
+    match (total, limit) {
+        (Some(total), Some(limit)) => Some(total.min(limit)),
+        (total, limit) => total.or(limit),
+    }
+}
+
+/// Returns the directories of the cgroup of the process in the v2 hierarchy and
+/// in the v1 memory hierarchy under `cgroup_root`. The root is used for a
+/// hierarchy not listed in `cgroups`, e.g. when `/proc/self/cgroup` could not be
+/// read.
+fn cgroup_dirs(cgroup_root: &Path, cgroups: &str) -> (PathBuf, PathBuf) {
+    let (v2_path, v1_path) = parse_cgroup_paths(cgroups);
+    // The paths are absolute from the roots of the hierarchies, so they must not
+    // be joined as is.
+    let relative = |path: Option<&str>| path.unwrap_or_default().trim_start_matches('/').to_owned();
+    (
+        cgroup_root.join(relative(v2_path)),
+        cgroup_root.join("memory").join(relative(v1_path)),
+    )
+}
+
+/// Parses `/proc/self/cgroup`, and returns the paths of the cgroup of the process
+/// in the v2 hierarchy and in the v1 hierarchy having the memory controller.
+///
+/// Each line is `hierarchy-ID:controller-list:cgroup-path`, and the v2 hierarchy
+/// has the ID `0` and no controller.
+fn parse_cgroup_paths(s: &str) -> (Option<&str>, Option<&str>) {
+    let mut v2 = None;
+    let mut v1_memory = None;
+    for line in s.lines() {
+        let mut fields = line.splitn(3, ':');
+        let (Some(id), Some(controllers), Some(path)) =
+            (fields.next(), fields.next(), fields.next())
+        else {
+            continue;
+        };
+        if id == "0" && controllers.is_empty() {
+            v2 = Some(path);
+        } else if controllers.split(',').any(|c| c == "memory") {
+            v1_memory = Some(path);
+        }
+    }
+    (v2, v1_memory)
+}
+
+/// Parses the `MemTotal` line of `/proc/meminfo`, and returns it in bytes.
+fn parse_mem_total(s: &str) -> Option<u64> {
+    let line = s.lines().find(|l| l.starts_with("MemTotal:"))?;
+    let kib = line
+        .trim_start_matches("MemTotal:")
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse::<u64>()
+        .ok()?;
+    kib.checked_mul(1024)
+}
+
+/// Parses the memory limit of a cgroup. Returns `None` if there is no limit
+/// (`max` in cgroup v2).
+fn parse_cgroup_limit(s: &str) -> Option<u64> {
+    match s.trim() {
+        "max" => None,
+        // cgroup v1 reports a huge number when there is no limit. It will be
+        // capped by `MemTotal`.
+        limit => limit.parse().ok(),
+    }
+}
+
+/// Returns the files of the memory pressure stall information (PSI) to read, in
+/// order: the one of the cgroup (v2) of the process, and the one of the whole
+/// system. Returns no file on a platform other than Linux.
+#[cfg(feature = "sync")]
+fn memory_pressure_files() -> Vec<PathBuf> {
+    if cfg!(target_os = "linux") {
+        let cgroups = std::fs::read_to_string("/proc/self/cgroup").unwrap_or_default();
+        let (v2_dir, _) = cgroup_dirs(Path::new(CGROUP_ROOT), &cgroups);
+        vec![
+            v2_dir.join("memory.pressure"),
+            PathBuf::from("/proc/pressure/memory"),
+        ]
+    } else {
+        Vec::new()
+    }
+}
+
+/// Returns the `avg10` of the `some` line of the first readable file of
+/// `pressure_files`.
+///
+/// Returns `None` if the PSI is not available (e.g. on a platform other than Linux
+/// or a kernel without PSI).
+#[cfg(feature = "sync")]
+fn memory_pressure(pressure_files: &[PathBuf]) -> Option<f64> {
+    pressure_files
+        .iter()
+        .find_map(|path| std::fs::read_to_string(path).ok())
+        .and_then(|s| parse_memory_pressure(&s))
+}
+
+/// Parses the `avg10` of the `some` line of a PSI file, e.g.
+/// `some avg10=0.00 avg60=0.00 avg300=0.00 total=0`.
+#[cfg(feature = "sync")]
+fn parse_memory_pressure(s: &str) -> Option<f64> {
+    s.lines()
+        .find_map(|l| l.strip_prefix("some "))?
+        .split_whitespace()
+        .find_map(|field| field.strip_prefix("avg10="))?
+        .parse()
+        .ok()
+}
+
+/// Watches the memory pressure, and tells when the cache should shed or restore
+/// its capacity.
+#[cfg(feature = "sync")]
+pub(crate) struct MemoryPressureWatcher {
+    /// The fraction of the max capacity to shed under pressure.
+    shed_fraction: f64,
+    pressure_files: Vec<PathBuf>,
+    last_polled: AtomicInstant,
+    is_under_pressure: AtomicBool,
+}
+
+#[cfg(feature = "sync")]
+impl MemoryPressureWatcher {
+    pub(crate) fn new(shed_fraction: f64) -> Self {
+        Self {
+            shed_fraction,
+            pressure_files: memory_pressure_files(),
+            last_polled: AtomicInstant::default(),
+            is_under_pressure: AtomicBool::default(),
+        }
+    }
+
+    /// Checks the memory pressure if the poll interval has passed since the last
+    /// check. Returns the fraction of the max capacity to shed when the pressure
+    /// state has changed: `shed_fraction` when the pressure has risen, and `0.0`
+    /// when it has gone.
+    pub(crate) fn poll(&self, now: Instant) -> Option<f64> {
+        self.poll_with(now, || memory_pressure(&self.pressure_files))
+    }
+
+    fn poll_with(&self, now: Instant, pressure: impl FnOnce() -> Option<f64>) -> Option<f64> {
+        if let Some(last) = self.last_polled.instant() {
+            let elapsed = now.checked_duration_since(last).unwrap_or_default();
+            if elapsed < MEMORY_PRESSURE_POLL_INTERVAL {
+                return None;
+            }
+        }
+        self.last_polled.set_instant(now);
+
+        let under_pressure = pressure()? > MEMORY_PRESSURE_THRESHOLD;
+        let was_under_pressure = self
+            .is_under_pressure
+            .swap(under_pressure, Ordering::AcqRel);
+        if was_under_pressure == under_pressure {
+            None
+        } else if under_pressure {
+            Some(self.shed_fraction)
+        } else {
+            Some(0.0)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{detect, parse_cgroup_limit, parse_cgroup_paths, parse_mem_total};
+
+    #[test]
+    fn parse_files() {
+        let meminfo = "MemTotal:        6158152 kB\nMemFree:          123456 kB\n";
+        assert_eq!(parse_mem_total(meminfo), Some(6_158_152 * 1024));
+        assert_eq!(parse_mem_total("MemFree: 1 kB\n"), None);
+
+        assert_eq!(parse_cgroup_limit("1073741824\n"), Some(1 << 30));
+        assert_eq!(parse_cgroup_limit("max\n"), None);
+        assert_eq!(parse_cgroup_limit(""), None);
+
+        assert_eq!(
+            parse_cgroup_paths("0::/user.slice/app\n"),
+            (Some("/user.slice/app"), None)
+        );
+        let v1 = "12:cpu,cpuacct:/docker/abc\n5:memory:/docker/abc\n1:name=systemd:/\n";
+        assert_eq!(parse_cgroup_paths(v1), (None, Some("/docker/abc")));
+        assert_eq!(parse_cgroup_paths(""), (None, None));
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn parse_memory_pressure() {
+        use super::parse_memory_pressure;
+
+        let psi = "some avg10=12.50 avg60=3.00 avg300=0.00 total=431051\n\
+                   full avg10=1.00 avg60=0.00 avg300=0.00 total=237242\n";
+        assert_eq!(parse_memory_pressure(psi), Some(12.5));
+        assert_eq!(parse_memory_pressure("full avg10=1.00\n"), None);
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn watch_memory_pressure() {
+        use super::MemoryPressureWatcher;
+        use crate::common::time::{CheckedTimeOps, Instant};
+        use std::time::Duration;
+
+        let watcher = MemoryPressureWatcher::new(0.5);
+        let mut now = Instant::now();
+        let mut tick = |secs| {
+            now = now.checked_add(Duration::from_secs(secs)).unwrap();
+            now
+        };
+
+        // No change from the initial state.
+        assert_eq!(watcher.poll_with(tick(0), || Some(1.0)), None);
+        // The pressure rises, but the poll interval has not passed.
+        assert_eq!(watcher.poll_with(tick(0), || Some(50.0)), None);
+        assert_eq!(watcher.poll_with(tick(1), || Some(50.0)), Some(0.5));
+        assert_eq!(watcher.poll_with(tick(1), || Some(50.0)), None);
+        // The PSI is not available.
+        assert_eq!(watcher.poll_with(tick(1), || None), None);
+        // The pressure goes.
+        assert_eq!(watcher.poll_with(tick(1), || Some(0.0)), Some(0.0));
+        assert_eq!(watcher.poll_with(tick(1), || Some(0.0)), None);
+    }
+
+    #[test]
+    fn detect_memory() {
+        let dir = std::env::temp_dir().join(format!("moka-sys-memory-test-{}", std::process::id()));
+        let meminfo = dir.join("meminfo");
+        let cgroup_dir = dir.join("cgroup");
+        std::fs::create_dir_all(cgroup_dir.join("memory")).unwrap();
+        std::fs::write(&meminfo, "MemTotal: 4194304 kB\n").unwrap();
+
+        // No cgroup limit.
+        assert_eq!(detect(&meminfo, &cgroup_dir, ""), Some(4 << 30));
+
+        // cgroup v1 limit.
+        let v1 = cgroup_dir.join("memory").join("memory.limit_in_bytes");
+        std::fs::write(&v1, format!("{}\n", 1u64 << 30)).unwrap();
+        assert_eq!(detect(&meminfo, &cgroup_dir, ""), Some(1 << 30));
+
+        // cgroup v2 limit takes precedence over v1.
+        std::fs::write(cgroup_dir.join("memory.max"), "max\n").unwrap();
+        assert_eq!(detect(&meminfo, &cgroup_dir, ""), Some(4 << 30));
+        std::fs::write(cgroup_dir.join("memory.max"), format!("{}\n", 2u64 << 30)).unwrap();
+        assert_eq!(detect(&meminfo, &cgroup_dir, ""), Some(2 << 30));
+
+        // A cgroup limit larger than the physical memory is capped.
+        std::fs::write(cgroup_dir.join("memory.max"), format!("{}\n", 8u64 << 30)).unwrap();
+        assert_eq!(detect(&meminfo, &cgroup_dir, ""), Some(4 << 30));
+
+        // The limit of the nested cgroup of the process, or of its ancestor, is
+        // applied.
+        let app_dir = cgroup_dir.join("app");
+        std::fs::create_dir_all(app_dir.join("worker")).unwrap();
+        std::fs::write(app_dir.join("memory.max"), format!("{}\n", 1u64 << 30)).unwrap();
+        std::fs::write(app_dir.join("worker").join("memory.max"), "max\n").unwrap();
+        let cgroups = "0::/app/worker\n";
+        assert_eq!(detect(&meminfo, &cgroup_dir, cgroups), Some(1 << 30));
+        std::fs::write(
+            app_dir.join("worker").join("memory.max"),
+            format!("{}\n", 512u64 << 20),
+        )
+        .unwrap();
+        assert_eq!(detect(&meminfo, &cgroup_dir, cgroups), Some(512 << 20));
+
+        // The same for cgroup v1.
+        std::fs::remove_dir_all(&app_dir).unwrap();
+        std::fs::remove_file(cgroup_dir.join("memory.max")).unwrap();
+        let v1_app_dir = cgroup_dir.join("memory").join("docker").join("abc");
+        std::fs::create_dir_all(&v1_app_dir).unwrap();
+        std::fs::write(
+            v1_app_dir.join("memory.limit_in_bytes"),
+            format!("{}\n", 256u64 << 20),
+        )
+        .unwrap();
+        let cgroups = "5:memory:/docker/abc\n";
+        assert_eq!(detect(&meminfo, &cgroup_dir, cgroups), Some(256 << 20));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(detect(&meminfo, &cgroup_dir, ""), None);
+    }
+}
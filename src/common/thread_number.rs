@@ -0,0 +1,29 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// The number given to the next thread that asks for its number.
+static NEXT_THREAD_NUMBER: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    static THREAD_NUMBER: usize = NEXT_THREAD_NUMBER.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Returns the number of the current thread. The threads are numbered in the
+/// order of their first call, so the numbers of the live threads are spread
+/// evenly over the stripes or the shards selected by them.
+#[inline]
+pub(crate) fn current() -> usize {
+    THREAD_NUMBER.with(|n| *n)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::current;
+
+    #[test]
+    fn numbers_are_per_thread() {
+        let n = current();
+        assert_eq!(current(), n);
+        let other = std::thread::spawn(current).join().unwrap();
+        assert_ne!(other, n);
+    }
+}
@@ -1,4 +1,4 @@
-use std::time::Duration;
+use std::{fmt, sync::Arc, time::Duration};
 
 #[cfg_attr(feature = "quanta", path = "time/clock_quanta.rs")]
 #[cfg_attr(not(feature = "quanta"), path = "time/clock_compat.rs")]
@@ -9,6 +9,127 @@ pub(crate) use clock::Clock;
 #[cfg(test)]
 pub(crate) use clock::Mock;
 
+/// The source of the current time used by a cache for the expirations and the
+/// timing of the housekeeping tasks. Set it with the `time_source` method of the
+/// cache builder.
+///
+/// Without the `quanta` feature (enabled by default), `std::time::Instant` is
+/// used regardless of this setting, except for `WallClock`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TimeSource {
+    /// Use `quanta::Instant::now`. It is faster than `std::time::Instant::now`
+    /// on most platforms, but relies on the TSC calibration of `quanta` crate,
+    /// which can be problematic on some VMs and ARM SoCs.
+    ///
+    /// This is the default.
+    #[default]
+    Quanta,
+    /// Use `std::time::Instant::now`.
+    Std,
+    /// Use `quanta::Instant::recent`, the coarse time cached by `quanta`. It is the
+    /// cheapest one to get, but it is only updated while the application runs a
+    /// `quanta::Upkeep` thread. Otherwise, it is the same as `Quanta`.
+    Coarse,
+    /// Use the wall-clock time measured by `std::time::SystemTime::now`.
+    ///
+    /// Unlike `Instant`, which may stop while the machine is suspended on some
+    /// platforms, the wall-clock time keeps advancing during a sleep. So the
+    /// entries expire correctly after a laptop or a mobile device wakes up from
+    /// a suspend of hours. The time never goes backward; if the system time is
+    /// adjusted backward, the clock stays still until the system time catches up.
+    WallClock,
+}
+
+/// Returns the wall-clock time elapsed since the first call, measured by
+/// `SystemTime`. It never decreases, even if the system time is adjusted backward.
+pub(crate) fn wall_clock_elapsed() -> Duration {
+    use crossbeam_utils::atomic::AtomicCell;
+    use once_cell::sync::Lazy;
+    use std::time::SystemTime;
+
+    static ORIGIN: Lazy<SystemTime> = Lazy::new(SystemTime::now);
+    static LAST_NANOS: AtomicCell<u64> = AtomicCell::new(0);
+
+    let nanos = SystemTime::now()
+        .duration_since(*ORIGIN)
+        .map_or(0, |d| d.as_nanos() as u64);
+    let mut last = LAST_NANOS.load();
+    while last < nanos {
+        match LAST_NANOS.compare_exchange(last, nanos) {
+            Ok(_) => break,
+            Err(current) => last = current,
+        }
+    }
+    Duration::from_nanos(last.max(nanos))
+}
+
+/// A clock whose time advances only when [`advance`](#method.advance) is called.
+///
+/// Set it to a cache with the `clock` method of the cache builder, so that unit
+/// tests of TTL-dependent logic can expire entries by advancing the clock instead
+/// of sleeping. The clones share the same time, so keep a clone to advance the
+/// clock after building the cache.
+///
+/// The clock affects the cache expirations and the timing of the housekeeping
+/// tasks. The `std::time::Instant`s passed to an [`Expiry`][expiry] are derived
+/// from the clock too.
+///
+/// [expiry]: ./policy/trait.Expiry.html
+///
+/// # Examples
+///
+/// ```rust
+/// use moka::{sync::Cache, MockClock};
+/// use std::time::Duration;
+///
+/// let clock = MockClock::new();
+/// let cache = Cache::builder()
+///     .time_to_live(Duration::from_secs(60))
+///     .clock(clock.clone())
+///     .build();
+///
+/// cache.insert("a", 1);
+/// clock.advance(Duration::from_secs(59));
+/// assert_eq!(cache.get(&"a"), Some(1));
+///
+/// clock.advance(Duration::from_secs(1));
+/// assert_eq!(cache.get(&"a"), None);
+/// ```
+#[derive(Clone)]
+pub struct MockClock {
+    clock: Clock,
+    mock: Arc<clock::Mock>,
+}
+
+impl MockClock {
+    /// Creates a new `MockClock`.
+    pub fn new() -> Self {
+        let (clock, mock) = Clock::mock();
+        Self { clock, mock }
+    }
+
+    /// Advances the time of this clock by `duration`.
+    pub fn advance(&self, duration: Duration) {
+        self.mock.increment(duration);
+    }
+
+    pub(crate) fn clock(&self) -> Clock {
+        self.clock.clone()
+    }
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for MockClock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MockClock").finish_non_exhaustive()
+    }
+}
+
 /// a wrapper type over Instant to force checked additions and prevent
 /// unintentional overflow. The type preserve the Copy semantics for the wrapped
 #[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug)]
@@ -19,6 +140,10 @@ pub(crate) trait CheckedTimeOps {
     where
         Self: Sized;
 
+    fn checked_sub(&self, duration: Duration) -> Option<Self>
+    where
+        Self: Sized;
+
     fn checked_duration_since(&self, earlier: Self) -> Option<Duration>
     where
         Self: Sized;
@@ -44,6 +169,10 @@ impl CheckedTimeOps for Instant {
         self.0.checked_add(duration).map(Instant)
     }
 
+    fn checked_sub(&self, duration: Duration) -> Option<Instant> {
+        self.0.checked_sub(duration).map(Instant)
+    }
+
     fn checked_duration_since(&self, earlier: Self) -> Option<Duration>
     where
         Self: Sized,
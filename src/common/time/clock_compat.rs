@@ -1,29 +1,48 @@
-use std::{sync::Arc, time::Instant as StdInstant};
-
-#[cfg(test)]
-use std::time::Duration;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant as StdInstant},
+};
 
+use once_cell::sync::Lazy;
 use parking_lot::RwLock;
 
+use super::TimeSource;
+
 pub(crate) type Instant = StdInstant;
 
+#[derive(Clone)]
 pub(crate) struct Clock {
     mock: Option<Arc<Mock>>,
+    wall_clock: bool,
 }
 
 impl Clock {
-    #[cfg(test)]
     pub(crate) fn mock() -> (Clock, Arc<Mock>) {
         let mock = Arc::new(Mock::default());
         let clock = Clock {
             mock: Some(Arc::clone(&mock)),
+            wall_clock: false,
         };
         (clock, mock)
     }
 
+    /// Returns the clock for the given `TimeSource`, or `None` if the default
+    /// clock (`std::time::Instant::now`) should be used. Without the `quanta`
+    /// feature, all time sources but `WallClock` use `std::time::Instant`.
+    pub(crate) fn from_time_source(source: TimeSource) -> Option<Clock> {
+        (source == TimeSource::WallClock).then_some(Clock {
+            mock: None,
+            wall_clock: true,
+        })
+    }
+
     pub(crate) fn now(&self) -> Instant {
+        static ORIGIN: Lazy<StdInstant> = Lazy::new(StdInstant::now);
+
         if let Some(mock) = &self.mock {
             *mock.now.read()
+        } else if self.wall_clock {
+            *ORIGIN + super::wall_clock_elapsed()
         } else {
             StdInstant::now()
         }
@@ -42,7 +61,6 @@ impl Default for Mock {
     }
 }
 
-#[cfg(test)]
 impl Mock {
     pub(crate) fn increment(&self, amount: Duration) {
         *self.now.write() += amount;
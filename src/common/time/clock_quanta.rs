@@ -1,5 +1,55 @@
-pub(crate) type Clock = quanta::Clock;
-pub(crate) type Instant = quanta::Instant;
+use std::{sync::Arc, time::Instant as StdInstant};
+
+use once_cell::sync::Lazy;
+
+use super::TimeSource;
 
-#[cfg(test)]
+pub(crate) type Instant = quanta::Instant;
 pub(crate) type Mock = quanta::Mock;
+
+#[derive(Clone)]
+pub(crate) enum Clock {
+    Quanta(quanta::Clock),
+    Std,
+    Coarse,
+    Wall,
+}
+
+impl Clock {
+    pub(crate) fn mock() -> (Clock, Arc<Mock>) {
+        let (clock, mock) = quanta::Clock::mock();
+        (Clock::Quanta(clock), mock)
+    }
+
+    /// Returns the clock for the given `TimeSource`, or `None` if the default
+    /// clock (`quanta::Instant::now`) should be used.
+    pub(crate) fn from_time_source(source: TimeSource) -> Option<Clock> {
+        match source {
+            TimeSource::Quanta => None,
+            TimeSource::Std => Some(Clock::Std),
+            TimeSource::Coarse => Some(Clock::Coarse),
+            TimeSource::WallClock => Some(Clock::Wall),
+        }
+    }
+
+    pub(crate) fn now(&self) -> Instant {
+        match self {
+            Clock::Quanta(clock) => clock.now(),
+            Clock::Std => std_now(),
+            Clock::Coarse => Instant::recent(),
+            Clock::Wall => from_nanos(super::wall_clock_elapsed().as_nanos() as u64),
+        }
+    }
+}
+
+/// Returns the current time of `std::time::Instant` as a `quanta::Instant`.
+fn std_now() -> Instant {
+    static ORIGIN: Lazy<StdInstant> = Lazy::new(StdInstant::now);
+    from_nanos(ORIGIN.elapsed().as_nanos() as u64)
+}
+
+fn from_nanos(nanos: u64) -> Instant {
+    // SAFETY: `quanta::Instant` is a wrapper of a `u64` nanoseconds value. We
+    // make the same assumption in `AtomicInstant`.
+    unsafe { std::mem::transmute::<u64, Instant>(nanos) }
+}
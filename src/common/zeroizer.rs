@@ -0,0 +1,48 @@
+use std::marker::PhantomData;
+
+/// Zeroizes the values dropped by a cache built with `zeroize_values`.
+///
+/// Without the `zeroize` feature, this is a zero-sized type that does nothing, so
+/// that the entries do not pay for it.
+pub(crate) struct ValueZeroizer<V> {
+    #[cfg(feature = "zeroize")]
+    zeroize: Option<fn(&mut V)>,
+    _marker: PhantomData<fn(&mut V)>,
+}
+
+impl<V> Default for ValueZeroizer<V> {
+    fn default() -> Self {
+        Self {
+            #[cfg(feature = "zeroize")]
+            zeroize: None,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<V> Clone for ValueZeroizer<V> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<V> Copy for ValueZeroizer<V> {}
+
+#[cfg(feature = "zeroize")]
+impl<V> ValueZeroizer<V> {
+    pub(crate) fn new() -> Self
+    where
+        V: zeroize::Zeroize,
+    {
+        Self {
+            zeroize: Some(|v: &mut V| v.zeroize()),
+            _marker: PhantomData,
+        }
+    }
+
+    pub(crate) fn zeroize(&self, value: &mut V) {
+        if let Some(zeroize) = self.zeroize {
+            zeroize(value);
+        }
+    }
+}
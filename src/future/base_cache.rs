@@ -20,15 +20,17 @@ use crate::{
             WriteOp,
         },
         deque::{DeqNode, Deque},
+        error_cache::ErrorCache,
         frequency_sketch::FrequencySketch,
         time::{CheckedTimeOps, Clock, Instant},
         timer_wheel::{ReschedulingResult, TimerWheel},
         CacheRegion, HousekeeperConfig,
     },
     future::CancelGuard,
-    notification::{AsyncEvictionListener, RemovalCause},
-    policy::{EvictionPolicy, EvictionPolicyConfig, ExpirationPolicy},
+    notification::{AsyncEvictionListener, RemovalCause, RemovalCauses},
+    policy::{self, EntryStats, EvictionPolicy, EvictionPolicyConfig, ExpirationPolicy},
     sync_base::iter::ScanningGet,
+    weigher::WeighContext,
     Entry, Expiry, Policy, PredicateError,
 };
 
@@ -137,6 +139,24 @@ impl<K, V, S> BaseCache<K, V, S> {
         self.inner.notify_invalidate(key, entry)
     }
 
+    /// Notifies the eviction listener of an entry that was not stored because the
+    /// max capacity of the cache is zero, as if the entry was evicted right after
+    /// the insertion.
+    pub(crate) async fn notify_rejected(&self, key: Arc<K>, value: V)
+    where
+        K: Send + Sync + 'static,
+        V: Clone + Send + Sync + 'static,
+    {
+        if let Some(notifier) = self
+            .inner
+            .removal_notifier
+            .as_ref()
+            .filter(|n| n.is_notified(RemovalCause::Size))
+        {
+            notifier.notify(key, value, RemovalCause::Size).await;
+        }
+    }
+
     #[cfg(feature = "unstable-debug-counters")]
     pub async fn debug_stats(&self) -> CacheDebugStats {
         self.inner.debug_stats().await
@@ -169,9 +189,12 @@ where
         weigher: Option<Weigher<K, V>>,
         eviction_policy: EvictionPolicy,
         eviction_listener: Option<AsyncEvictionListener<K, V>>,
+        eviction_listener_causes: RemovalCauses,
         expiration_policy: ExpirationPolicy<K, V>,
         housekeeper_config: HousekeeperConfig,
         invalidator_enabled: bool,
+        expiration_clock: Option<Clock>,
+        error_cache: Option<Arc<ErrorCache<K, S>>>,
     ) -> Self {
         let (r_size, w_size) = if max_capacity == Some(0) {
             (0, 0)
@@ -192,11 +215,15 @@ where
             weigher,
             eviction_policy,
             eviction_listener,
+            eviction_listener_causes,
             r_rcv,
             w_rcv,
             expiration_policy,
             invalidator_enabled,
+            expiration_clock,
+            error_cache,
         ));
+        let now = inner.current_time_from_expiration_clock();
 
         Self {
             inner,
@@ -207,6 +234,7 @@ where
             housekeeper: Some(Arc::new(Housekeeper::new(
                 is_eviction_listener_enabled,
                 housekeeper_config,
+                now,
             ))),
         }
     }
@@ -285,6 +313,9 @@ where
                     None
                 } else {
                     // Valid entry.
+                    if i.counts_entry_ops() {
+                        entry.entry_info().incr_read_count();
+                    }
                     let mut is_expiry_modified = false;
 
                     // Call the user supplied `expire_after_read` method if any.
@@ -320,7 +351,9 @@ where
                         // the timer wheel will reschedule the expiration timer for the
                         // entry.
                         is_expiry_modified = Self::expire_after_read_or_update(
-                            |k, v, t, d| expiry.expire_after_read(k, v, t, d, lm),
+                            |k, v, t, d, st| {
+                                policy::expire_after_read(&**expiry, k, v, t, d, lm, st)
+                            },
                             &entry.entry_info().key_hash().key,
                             entry,
                             self.inner.expiration_policy.time_to_live(),
@@ -403,6 +436,16 @@ where
         self.inner.set_valid_after(now);
     }
 
+    pub(crate) fn invalidate_entries_older_than(&self, age: Duration) {
+        let now = self.current_time_from_expiration_clock();
+        // If the watermark is before the time the cache was created (or the clock
+        // origin), there is nothing to invalidate.
+        let Some(watermark) = now.checked_sub(age) else {
+            return;
+        };
+        self.inner.set_valid_after(watermark);
+    }
+
     pub(crate) fn invalidate_entries_if(
         &self,
         predicate: PredicateFun<K, V>,
@@ -490,8 +533,14 @@ where
         let mut op1 = None;
         let mut op2 = None;
 
-        // Lock the key for update if blocking removal notification is enabled.
-        let kl = self.maybe_key_lock(&key);
+        // Lock the key for update if blocking removal notification is enabled and
+        // the replaced entry can be notified.
+        let notifies_update = self.inner.is_update_notified();
+        let kl = if notifies_update {
+            self.maybe_key_lock(&key)
+        } else {
+            None
+        };
         let _klg = if let Some(lock) = &kl {
             Some(lock.lock().await)
         } else {
@@ -530,7 +579,11 @@ where
                 // Create this OldEntryInfo _before_ creating a new ValueEntry, so
                 // that the OldEntryInfo can preserve the old EntryInfo's
                 // last_accessed and last_modified timestamps.
-                let old_info = OldEntryInfo::new(old_entry);
+                let old_info = if notifies_update {
+                    Some(OldEntryInfo::new(old_entry))
+                } else {
+                    None
+                };
                 let (entry, gen) = self.new_value_entry_from(value.clone(), ts, weight, old_entry);
                 let upd_op = WriteOp::new_upsert(&key, hash, &entry, gen, old_weight, weight);
                 let cnt = op_cnt2.fetch_add(1, Ordering::Relaxed);
@@ -570,7 +623,7 @@ where
         &self,
         ts: Instant,
         key: Arc<K>,
-        old_info: OldEntryInfo<K, V>,
+        old_info: Option<OldEntryInfo<K, V>>,
         upd_op: WriteOp<K, V>,
         interrupted_op_ch: &'a Sender<InterruptedOp<K, V>>,
     ) -> (WriteOp<K, V>, Instant) {
@@ -580,7 +633,7 @@ where
             (&self.inner.expiration_policy.expiry(), &upd_op)
         {
             Self::expire_after_read_or_update(
-                |k, v, t, d| expiry.expire_after_update(k, v, t, d),
+                |k, v, t, d, st| policy::expire_after_update(&**expiry, k, v, t, d, st),
                 &key,
                 value_entry,
                 self.inner.expiration_policy.time_to_live(),
@@ -590,7 +643,7 @@ where
             );
         }
 
-        if self.is_removal_notifier_enabled() {
+        if let Some(old_info) = old_info {
             let future = self
                 .inner
                 .notify_upsert(
@@ -761,7 +814,11 @@ impl<K, V, S> BaseCache<K, V, S> {
         policy_weight: u32,
     ) -> (TrioArc<ValueEntry<K, V>>, u16) {
         let key_hash = KeyHash::new(Arc::clone(key), hash);
-        let info = TrioArc::new(EntryInfo::new(key_hash, timestamp, policy_weight));
+        let mut info = EntryInfo::new(key_hash, timestamp, policy_weight);
+        if self.inner.counts_entry_ops() {
+            info = info.with_op_counts(timestamp);
+        }
+        let info = TrioArc::new(info);
         let gen: u16 = info.entry_gen();
         (TrioArc::new(ValueEntry::new(value, info)), gen)
     }
@@ -778,6 +835,9 @@ impl<K, V, S> BaseCache<K, V, S> {
         // To prevent this updated ValueEntry from being evicted by an expiration
         // policy, increment the entry generation.
         let gen = info.incr_entry_gen();
+        if self.inner.counts_entry_ops() {
+            info.incr_write_count();
+        }
         info.set_last_accessed(timestamp);
         info.set_last_modified(timestamp);
         info.set_policy_weight(policy_weight);
@@ -800,7 +860,7 @@ impl<K, V, S> BaseCache<K, V, S> {
     }
 
     fn expire_after_read_or_update(
-        expiry: impl FnOnce(&K, &V, StdInstant, Option<Duration>) -> Option<Duration>,
+        expiry: impl FnOnce(&K, &V, StdInstant, Option<Duration>, &EntryStats) -> Option<Duration>,
         key: &K,
         value_entry: &ValueEntry<K, V>,
         ttl: Option<Duration>,
@@ -824,7 +884,21 @@ impl<K, V, S> BaseCache<K, V, S> {
             std_time.checked_duration_since(current_time)
         });
 
-        let duration = expiry(key, &value_entry.value, current_time, current_duration);
+        #[cfg(feature = "entry-stats")]
+        let stats = EntryStats::new(
+            clocks.to_std_instant(ei.created_at().expect("Created at is not set")),
+            ei.read_count(),
+            ei.write_count(),
+        );
+        #[cfg(not(feature = "entry-stats"))]
+        let stats = EntryStats;
+        let duration = expiry(
+            key,
+            &value_entry.value,
+            current_time,
+            current_duration,
+            &stats,
+        );
 
         if duration != current_duration {
             let expiration_time =
@@ -908,10 +982,12 @@ impl<'a, K, V> EvictionState<'a, K, V> {
         K: Send + Sync + 'static,
         V: Clone + Send + Sync + 'static,
     {
-        if let Some(notifier) = self.notifier {
-            notifier.notify(key, entry.value.clone(), cause).await;
-        } else {
-            panic!("notify_entry_removal is called when the notification is disabled");
+        match self.notifier {
+            Some(notifier) if notifier.is_notified(cause) => {
+                notifier.notify(key, entry.value.clone(), cause).await;
+            }
+            Some(_) => (),
+            None => panic!("notify_entry_removal is called when the notification is disabled"),
         }
     }
 }
@@ -1009,14 +1085,15 @@ struct Clocks {
 }
 
 impl Clocks {
-    fn new(time: Instant, std_time: StdInstant) -> Self {
+    fn new(time: Instant, std_time: StdInstant, expiration_clock: Option<Clock>) -> Self {
+        let has_expiration_clock = expiration_clock.is_some();
         Self {
             _lock: Mutex::default(),
-            has_expiration_clock: AtomicBool::default(),
-            expiration_clock: Default::default(),
+            has_expiration_clock: AtomicBool::new(has_expiration_clock),
+            expiration_clock: SyncRwLock::new(expiration_clock),
             origin: time,
             origin_std: std_time,
-            mutable_origin: Default::default(),
+            mutable_origin: SyncRwLock::new(has_expiration_clock.then_some((time, std_time))),
         }
     }
 
@@ -1059,6 +1136,9 @@ pub(crate) struct Inner<K, V, S> {
     key_locks: Option<KeyLockMap<K, S>>,
     invalidator: Option<Invalidator<K, V, S>>,
     clocks: Clocks,
+    /// The errors cached by `try_get_with` of the cache, purged of the expired ones
+    /// by the housekeeper.
+    error_cache: Option<Arc<ErrorCache<K, S>>>,
 }
 
 impl<K, V, S> Drop for Inner<K, V, S> {
@@ -1087,7 +1167,15 @@ impl<K, V, S> Inner<K, V, S> {
 
     fn policy(&self) -> Policy {
         let exp = &self.expiration_policy;
-        Policy::new(self.max_capacity, 1, exp.time_to_live(), exp.time_to_idle())
+        let mut policy = Policy::new(self.max_capacity, 1, exp.time_to_live(), exp.time_to_idle());
+        policy.eviction_policy = EvictionPolicy {
+            config: self.eviction_policy.clone(),
+        };
+        policy.has_weigher = self.weigher.is_some();
+        policy.has_expiry = exp.expiry().is_some();
+        policy.has_eviction_listener = self.is_removal_notifier_enabled();
+        policy.supports_invalidation_closures = self.invalidator.is_some();
+        policy
     }
 
     #[inline]
@@ -1105,6 +1193,18 @@ impl<K, V, S> Inner<K, V, S> {
         self.removal_notifier.is_some()
     }
 
+    /// Returns `true` if replacing an entry should be notified. The replaced entry
+    /// is notified as `Replaced`, or as `Expired` or `Explicit` when it was
+    /// already expired or invalidated.
+    #[inline]
+    fn is_update_notified(&self) -> bool {
+        self.removal_notifier.as_ref().map_or(false, |n| {
+            n.is_notified(RemovalCause::Replaced)
+                || n.is_notified(RemovalCause::Expired)
+                || n.is_notified(RemovalCause::Explicit)
+        })
+    }
+
     #[cfg(feature = "unstable-debug-counters")]
     pub async fn debug_stats(&self) -> CacheDebugStats {
         let ec = self.entry_count.load();
@@ -1176,15 +1276,25 @@ impl<K, V, S> Inner<K, V, S> {
         self.valid_after.instant()
     }
 
+    /// Moves the watermark forward to `timestamp`. It is never moved backward, as
+    /// it would revive the entries invalidated by the concurrent or earlier calls.
     #[inline]
     fn set_valid_after(&self, timestamp: Instant) {
-        self.valid_after.set_instant(timestamp);
+        self.valid_after.set_instant_max(timestamp);
     }
 
     #[inline]
     fn has_valid_after(&self) -> bool {
         self.valid_after.is_set()
     }
+
+    /// Returns `true` if the reads and writes of each entry should be counted.
+    /// The counts are needed only by the `Expiry`, which may read them through
+    /// `EntryStats`.
+    #[inline]
+    fn counts_entry_ops(&self) -> bool {
+        cfg!(feature = "entry-stats") && self.expiration_policy.expiry().is_some()
+    }
 }
 
 impl<K, V, S> Inner<K, V, S>
@@ -1204,14 +1314,34 @@ where
         weigher: Option<Weigher<K, V>>,
         eviction_policy: EvictionPolicy,
         eviction_listener: Option<AsyncEvictionListener<K, V>>,
+        eviction_listener_causes: RemovalCauses,
         read_op_ch: Receiver<ReadOp<K, V>>,
         write_op_ch: Receiver<WriteOp<K, V>>,
-        expiration_policy: ExpirationPolicy<K, V>,
+        mut expiration_policy: ExpirationPolicy<K, V>,
         invalidator_enabled: bool,
+        expiration_clock: Option<Clock>,
+        error_cache: Option<Arc<ErrorCache<K, S>>>,
     ) -> Self {
+        expiration_policy.apply_namespaces();
+
+        // A cache with zero max capacity stores nothing. The deques, the timer wheel
+        // and the frequency sketch allocate only when the entries are stored, and
+        // the channels are created with zero capacity by `BaseCache`.
+        let is_map_disabled = max_capacity == Some(0);
+
+        // If the number of entries is expected, allocate the table of the frequency
+        // sketch up front instead of when the cache becomes half full.
+        let mut frequency_sketch = FrequencySketch::default();
+        if let (Some(max_cap), Some(init_cap), None) = (max_capacity, initial_capacity, &weigher) {
+            let cap = max_cap.min(init_cap as u64);
+            if cap > 0 {
+                frequency_sketch.ensure_capacity(common::sketch_capacity(cap));
+            }
+        }
+
         // TODO: Calculate the number of segments based on the max capacity and
         // the number of CPUs.
-        let (num_segments, initial_capacity) = if max_capacity == Some(0) {
+        let (num_segments, initial_capacity) = if is_map_disabled {
             (1, 0)
         } else {
             let ic = initial_capacity
@@ -1228,14 +1358,25 @@ where
         // Assume that getting `moka::common::Instant::now` has lower latency than
         // `StdInstant::now`.
         let now_std = StdInstant::now();
-        let now = Instant::now();
-        let clocks = Clocks::new(now, now_std);
+        let now = expiration_clock
+            .as_ref()
+            .map_or_else(Instant::now, |clock| Instant::new(clock.now()));
+        let clocks = Clocks::new(now, now_std, expiration_clock);
         let timer_wheel = Mutex::new(TimerWheel::new(now));
 
         let (removal_notifier, key_locks) = if let Some(listener) = eviction_listener {
-            let rn = Arc::new(RemovalNotifier::new(listener, name.clone()));
-            let kl = KeyLockMap::with_hasher(build_hasher.clone());
-            (Some(rn), Some(kl))
+            let rn = Arc::new(RemovalNotifier::new(
+                listener,
+                eviction_listener_causes,
+                name.clone(),
+            ));
+            // The key locks are not needed when the cache stores nothing.
+            let kl = if is_map_disabled {
+                None
+            } else {
+                Some(KeyLockMap::with_hasher(build_hasher.clone()))
+            };
+            (Some(rn), kl)
         } else {
             (None, None)
         };
@@ -1254,7 +1395,7 @@ where
             build_hasher,
             deques: Mutex::default(),
             timer_wheel,
-            frequency_sketch: RwLock::new(FrequencySketch::default()),
+            frequency_sketch: RwLock::new(frequency_sketch),
             frequency_sketch_enabled: AtomicBool::default(),
             read_op_ch,
             write_op_ch,
@@ -1267,6 +1408,7 @@ where
             key_locks,
             invalidator,
             clocks,
+            error_cache,
         }
     }
 
@@ -1350,7 +1492,9 @@ where
 
     #[inline]
     fn weigh(&self, key: &K, value: &V) -> u32 {
-        self.weigher.as_ref().map_or(1, |w| w(key, value))
+        self.weigher
+            .as_ref()
+            .map_or(1, |w| w(key, value, &WeighContext::insert()))
     }
 }
 
@@ -1436,6 +1580,10 @@ where
         max_log_sync_repeats: u32,
         eviction_batch_size: u32,
     ) -> bool {
+        if let Some(ec) = &self.error_cache {
+            ec.remove_expired(self.current_time_from_expiration_clock());
+        }
+
         if self.max_capacity == Some(0) {
             return false;
         }
@@ -2649,7 +2797,11 @@ where
         entry: &TrioArc<ValueEntry<K, V>>,
         cause: RemovalCause,
     ) {
-        if let Some(notifier) = &self.removal_notifier {
+        if let Some(notifier) = self
+            .removal_notifier
+            .as_ref()
+            .filter(|n| n.is_notified(cause))
+        {
             notifier.notify(key, entry.value.clone(), cause).await;
         }
     }
@@ -2683,7 +2835,11 @@ where
             }
         }
 
-        if let Some(notifier) = &self.removal_notifier {
+        if let Some(notifier) = self
+            .removal_notifier
+            .as_ref()
+            .filter(|n| n.is_notified(cause))
+        {
             let notifier = Arc::clone(notifier);
             let value = entry.value.clone();
             async move {
@@ -2720,7 +2876,11 @@ where
             }
         }
 
-        if let Some(notifier) = &self.removal_notifier {
+        if let Some(notifier) = self
+            .removal_notifier
+            .as_ref()
+            .filter(|n| n.is_notified(cause))
+        {
             let notifier = Arc::clone(notifier);
             let key = Arc::clone(key);
             let value = entry.value.clone();
@@ -2896,6 +3056,7 @@ fn is_expired_by_ttl(
 mod tests {
     use crate::{
         common::HousekeeperConfig,
+        notification::RemovalCauses,
         policy::{EvictionPolicy, ExpirationPolicy},
     };
 
@@ -2918,9 +3079,12 @@ mod tests {
                 None,
                 EvictionPolicy::default(),
                 None,
+                RemovalCauses::default(),
                 ExpirationPolicy::default(),
                 HousekeeperConfig::default(),
                 false,
+                None,
+                None,
             );
             cache.inner.enable_frequency_sketch_for_testing().await;
             assert_eq!(
@@ -3268,6 +3432,7 @@ mod tests {
             None,
             EvictionPolicy::default(),
             None,
+            RemovalCauses::default(),
             ExpirationPolicy::new(
                 Some(Duration::from_secs(TTL)),
                 Some(Duration::from_secs(TTI)),
@@ -3275,6 +3440,8 @@ mod tests {
             ),
             HousekeeperConfig::default(),
             false,
+            None,
+            None,
         );
         cache.reconfigure_for_testing().await;
 
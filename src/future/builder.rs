@@ -1,11 +1,13 @@
 use super::{Cache, FutureExt};
 use crate::{
-    common::{builder_utils, concurrent::Weigher, HousekeeperConfig},
-    notification::{AsyncEvictionListener, ListenerFuture, RemovalCause},
+    common::{builder_utils, concurrent::Weigher, time::Clock, HousekeeperConfig},
+    notification::{AsyncEvictionListener, ListenerFuture, RemovalCause, RemovalCauses},
     policy::{EvictionPolicy, ExpirationPolicy},
-    Expiry,
+    weigher::{byte_len, ByteLength, WeighContext},
+    BuilderError, Expiry, MockClock, SeededState, TimeSource,
 };
 
+use async_lock::Semaphore;
 use std::{
     collections::hash_map::RandomState,
     hash::{BuildHasher, Hash},
@@ -59,12 +61,18 @@ pub struct CacheBuilder<K, V, C> {
     name: Option<String>,
     max_capacity: Option<u64>,
     initial_capacity: Option<usize>,
+    initial_capacity_load_factor: Option<f64>,
+    max_memory_fraction: Option<f64>,
     weigher: Option<Weigher<K, V>>,
     eviction_policy: EvictionPolicy,
     eviction_listener: Option<AsyncEvictionListener<K, V>>,
+    eviction_listener_causes: RemovalCauses,
     expiration_policy: ExpirationPolicy<K, V>,
     housekeeper_config: HousekeeperConfig,
     invalidator_enabled: bool,
+    max_concurrent_loads: Option<usize>,
+    error_time_to_live: Option<Duration>,
+    expiration_clock: Option<Clock>,
     cache_type: PhantomData<C>,
 }
 
@@ -78,12 +86,18 @@ where
             name: None,
             max_capacity: None,
             initial_capacity: None,
+            initial_capacity_load_factor: None,
+            max_memory_fraction: None,
             weigher: None,
             eviction_policy: EvictionPolicy::default(),
             eviction_listener: None,
+            eviction_listener_causes: RemovalCauses::default(),
             expiration_policy: ExpirationPolicy::default(),
             housekeeper_config: HousekeeperConfig::default(),
             invalidator_enabled: false,
+            max_concurrent_loads: None,
+            error_time_to_live: None,
+            expiration_clock: None,
             cache_type: PhantomData,
         }
     }
@@ -114,20 +128,40 @@ where
         let build_hasher = RandomState::default();
         let exp = &self.expiration_policy;
         builder_utils::ensure_expirations_or_panic(exp.time_to_live(), exp.time_to_idle());
+        let max_capacity = self.derived_max_capacity();
+        let initial_capacity = self.derived_initial_capacity();
+        let load_limiter = self.load_limiter();
         Cache::with_everything(
             self.name,
-            self.max_capacity,
-            self.initial_capacity,
+            max_capacity,
+            initial_capacity,
             build_hasher,
             self.weigher,
             self.eviction_policy,
             self.eviction_listener,
+            self.eviction_listener_causes,
             self.expiration_policy,
             self.housekeeper_config,
             self.invalidator_enabled,
+            load_limiter,
+            self.error_time_to_live,
+            self.expiration_clock,
         )
     }
 
+    /// Builds a `Cache<K, V>` after validating the configuration.
+    ///
+    /// Unlike the `build` method, this method returns an error instead of panicking
+    /// or silently creating a cache with surprising behavior, when the builder has
+    /// an invalid combination of configurations. See [`BuilderError`][builder-error]
+    /// for the rejected configurations.
+    ///
+    /// [builder-error]: ../enum.BuilderError.html
+    pub fn try_build(self) -> Result<Cache<K, V, RandomState>, BuilderError> {
+        self.validate()?;
+        Ok(self.build())
+    }
+
     /// Builds a `Cache<K, V, S>` with the given `hasher` of type `S`.
     ///
     /// # Examples
@@ -212,19 +246,54 @@ where
     {
         let exp = &self.expiration_policy;
         builder_utils::ensure_expirations_or_panic(exp.time_to_live(), exp.time_to_idle());
+        let max_capacity = self.derived_max_capacity();
+        let initial_capacity = self.derived_initial_capacity();
+        let load_limiter = self.load_limiter();
         Cache::with_everything(
             self.name,
-            self.max_capacity,
-            self.initial_capacity,
+            max_capacity,
+            initial_capacity,
             hasher,
             self.weigher,
             self.eviction_policy,
             self.eviction_listener,
+            self.eviction_listener_causes,
             self.expiration_policy,
             self.housekeeper_config,
             self.invalidator_enabled,
+            load_limiter,
+            self.error_time_to_live,
+            self.expiration_clock,
         )
     }
+
+    /// Builds a `Cache<K, V, SeededState<S>>` with the given `hasher` of type `S`
+    /// wrapped in a [`SeededState`][seeded-state] with a random seed.
+    ///
+    /// This gives the cache its own hash function even when `S` always creates
+    /// the same hash function, e.g. `BuildHasherDefault<H>`. To use a
+    /// deterministic seed in tests, call `build_with_hasher` method with
+    /// `SeededState::with_seed` instead.
+    ///
+    /// [seeded-state]: ../struct.SeededState.html
+    pub fn build_with_seeded_hasher<S>(self, hasher: S) -> Cache<K, V, SeededState<S>>
+    where
+        S: BuildHasher + Clone + Send + Sync + 'static,
+    {
+        self.build_with_hasher(SeededState::new(hasher))
+    }
+
+    /// Builds a `Cache<K, V, S>` with the given `hasher` of type `S` after validating
+    /// the configuration.
+    ///
+    /// See the `try_build` method for the validation.
+    pub fn try_build_with_hasher<S>(self, hasher: S) -> Result<Cache<K, V, S>, BuilderError>
+    where
+        S: BuildHasher + Clone + Send + Sync + 'static,
+    {
+        self.validate()?;
+        Ok(self.build_with_hasher(hasher))
+    }
 }
 
 impl<K, V, C> CacheBuilder<K, V, C> {
@@ -238,6 +307,14 @@ impl<K, V, C> CacheBuilder<K, V, C> {
     }
 
     /// Sets the max capacity of the cache.
+    ///
+    /// A max capacity of zero disables caching. Inserted entries are dropped
+    /// immediately without being stored, and the eviction listener is notified
+    /// of them with the [`Size`][removal-cause-size] cause. All lookups miss.
+    /// This can be used to turn off caching at runtime without changing the call
+    /// sites.
+    ///
+    /// [removal-cause-size]: ../notification/enum.RemovalCause.html#variant.Size
     pub fn max_capacity(self, max_capacity: u64) -> Self {
         Self {
             max_capacity: Some(max_capacity),
@@ -245,6 +322,50 @@ impl<K, V, C> CacheBuilder<K, V, C> {
         }
     }
 
+    /// Sets the max capacity of the cache to the `fraction` of the memory available
+    /// to the process, in bytes.
+    ///
+    /// The memory is detected when the cache is built. It is the total physical
+    /// memory of the machine, or the memory limit of the cgroup of the process
+    /// (e.g. the memory limit of a container) if it is smaller. This replaces the
+    /// max capacity set by the `max_capacity` method.
+    ///
+    /// If the memory could not be detected (currently, it is detected only on
+    /// Linux), the max capacity set by the `max_capacity` method is used instead.
+    /// Set it to a capacity in bytes to build the cache on any platform.
+    ///
+    /// A [`weigher`](#method.weigher) returning the approximate size of each entry
+    /// in bytes must be set. `fraction` must be greater than `0.0` and not greater
+    /// than `1.0`. Otherwise, or if the memory could not be detected and the
+    /// `max_capacity` is not set, the `build` method will panic, and the
+    /// `try_build` method will return a [`BuilderError`][builder-error].
+    ///
+    /// [builder-error]: ../enum.BuilderError.html
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use moka::future::Cache;
+    ///
+    /// let result = Cache::builder()
+    ///     .weigher(|k: &String, v: &Vec<u8>| (k.len() + v.len()).try_into().unwrap_or(u32::MAX))
+    ///     // Use up to a quarter of the memory, or 64 MiB if the memory could not
+    ///     // be detected.
+    ///     .max_memory_fraction(0.25)
+    ///     .max_capacity(64 * 1024 * 1024)
+    ///     .try_build();
+    ///
+    /// let cache = result.expect("Invalid configuration");
+    /// // The max capacity is the number of bytes.
+    /// println!("{:?}", cache.policy().max_capacity());
+    /// ```
+    pub fn max_memory_fraction(self, fraction: f64) -> Self {
+        Self {
+            max_memory_fraction: Some(fraction),
+            ..self
+        }
+    }
+
     /// Sets the initial capacity (number of entries) of the cache.
     pub fn initial_capacity(self, number_of_entries: usize) -> Self {
         Self {
@@ -253,6 +374,29 @@ impl<K, V, C> CacheBuilder<K, V, C> {
         }
     }
 
+    /// Derives the initial capacity of the cache from the max capacity, when the
+    /// initial capacity is not set by the `initial_capacity` method.
+    ///
+    /// The cache will be pre-sized to hold `max_capacity * load_factor` entries
+    /// so that a large cache does not have to grow its internal hash table many
+    /// times while it is warming up. The table of the historic popularity
+    /// estimator is also allocated up front.
+    ///
+    /// `load_factor` must be greater than `0.0` and not greater than `1.0`.
+    /// Otherwise, the `build` method will panic, and the `try_build` method will
+    /// return [`BuilderError::InvalidLoadFactor`][invalid-load-factor].
+    ///
+    /// This has no effect if the max capacity is not set, or a weigher is set as
+    /// the max capacity is not a number of entries in that case.
+    ///
+    /// [invalid-load-factor]: ../enum.BuilderError.html#variant.InvalidLoadFactor
+    pub fn auto_initial_capacity(self, load_factor: f64) -> Self {
+        Self {
+            initial_capacity_load_factor: Some(load_factor),
+            ..self
+        }
+    }
+
     /// Sets the eviction (and admission) policy of the cache.
     ///
     /// The default policy is TinyLFU. See [`EvictionPolicy`][eviction-policy] for
@@ -272,11 +416,26 @@ impl<K, V, C> CacheBuilder<K, V, C> {
     /// representing the relative size of the entry.
     pub fn weigher(self, weigher: impl Fn(&K, &V) -> u32 + Send + Sync + 'static) -> Self {
         Self {
-            weigher: Some(Arc::new(weigher)),
+            weigher: Some(Arc::new(move |k: &K, v: &V, _: &WeighContext| {
+                weigher(k, v)
+            })),
             ..self
         }
     }
 
+    /// Sets the [`byte_len`][byte-len-fn] weigher to the cache, so that each entry
+    /// is weighed by the length of its value in bytes. Combined with
+    /// `max_capacity`, it bounds the cache by the total bytes of the values.
+    ///
+    /// [byte-len-fn]: ../weigher/fn.byte_len.html
+    pub fn weigh_by_byte_len(self) -> Self
+    where
+        K: 'static,
+        V: ByteLength + 'static,
+    {
+        self.weigher(byte_len)
+    }
+
     /// Sets the eviction listener closure to the cache. The closure should take
     /// `Arc<K>`, `V` and [`RemovalCause`][removal-cause] as the arguments.
     ///
@@ -353,6 +512,55 @@ impl<K, V, C> CacheBuilder<K, V, C> {
         }
     }
 
+    /// Sets the [`RemovalCauses`][removal-causes] notified to the eviction
+    /// listener. The removals by the other causes are not notified, and the cache
+    /// does not clone their values for the listener. By default, all the causes are
+    /// notified.
+    ///
+    /// For example, a cache whose values are replaced very often can skip the
+    /// `Replaced` cause to avoid the overhead of notifying every routine
+    /// replacement. This has no effect if the eviction listener is not set.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// // Cargo.toml
+    /// //
+    /// // [dependencies]
+    /// // moka = { version = "0.12", features = ["future"] }
+    /// // tokio = { version = "1", features = ["rt-multi-thread", "macros" ] }
+    ///
+    /// use moka::{
+    ///     future::Cache,
+    ///     notification::{RemovalCause, RemovalCauses},
+    /// };
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let cache = Cache::builder()
+    ///         .max_capacity(100)
+    ///         .eviction_listener(|key, _value, cause| {
+    ///             assert_ne!(cause, RemovalCause::Replaced);
+    ///             println!("{key} was removed ({cause:?})");
+    ///         })
+    ///         .eviction_listener_causes(RemovalCauses::all().without(RemovalCause::Replaced))
+    ///         .build();
+    ///
+    ///     cache.insert(1, "one").await;
+    ///     // The listener is not called for the replaced value.
+    ///     cache.insert(1, "uno").await;
+    ///     cache.invalidate(&1).await;
+    /// }
+    /// ```
+    ///
+    /// [removal-causes]: ../notification/struct.RemovalCauses.html
+    pub fn eviction_listener_causes(self, causes: impl Into<RemovalCauses>) -> Self {
+        Self {
+            eviction_listener_causes: causes.into(),
+            ..self
+        }
+    }
+
     /// Sets the time to live of the cache.
     ///
     /// A cached entry will be expired after the specified duration past from
@@ -398,6 +606,85 @@ impl<K, V, C> CacheBuilder<K, V, C> {
         builder
     }
 
+    /// Sets the max number of `init` futures of the `get_with` family methods that
+    /// are resolved at the same time, across all keys. When the limit is reached,
+    /// the calls that need to resolve their futures wait until one of the running
+    /// futures completes.
+    ///
+    /// This protects the backing store from a flood of misses on distinct keys,
+    /// e.g. right after a cold start.
+    ///
+    /// Each `init` future holds a permit until it completes. So an `init` future
+    /// that loads other keys of the same cache (e.g. by awaiting `get_with`) waits
+    /// for a permit while holding its own, and the cache deadlocks when all
+    /// permits are held this way (always if `n` is one). Avoid such nested loads,
+    /// or set `n` to more than their max depth times the expected concurrent
+    /// loads. Unlike `sync::Cache`, the nested loads cannot be detected because a
+    /// task may move between threads.
+    ///
+    /// # Panics
+    ///
+    /// `CacheBuilder::build*` methods will panic if `n` is zero, and `try_build*`
+    /// methods will return [`BuilderError::ZeroMaxConcurrentLoads`][zero-loads].
+    ///
+    /// [zero-loads]: ../enum.BuilderError.html#variant.ZeroMaxConcurrentLoads
+    pub fn max_concurrent_loads(self, n: usize) -> Self {
+        Self {
+            max_concurrent_loads: Some(n),
+            ..self
+        }
+    }
+
+    /// Sets the time to live of the errors returned by the `init` futures of the
+    /// `try_get_with` family methods.
+    ///
+    /// When set, the error of a failed `init` future is cached for the key for the
+    /// given duration, and the subsequent `try_get_with` calls for the key return
+    /// the cached error without resolving their `init` futures, as long as they
+    /// have the same error type. Once a value is inserted for the key, the cached
+    /// error is discarded immediately.
+    ///
+    /// The expired errors are removed by the cache maintenance, like the expired
+    /// entries. Up to 4096 errors are cached at once; when that many unexpired
+    /// errors are cached, a new error replaces the oldest one.
+    pub fn error_time_to_live(self, duration: Duration) -> Self {
+        Self {
+            error_time_to_live: Some(duration),
+            ..self
+        }
+    }
+
+    /// Registers a namespace of keys, which have their own time to live and time to
+    /// idle. A key belongs to the first registered namespace whose `matches` closure
+    /// returns `true`.
+    ///
+    /// The entries in a namespace expire after `time_to_live` past from `insert`,
+    /// or after `time_to_idle` past from `get` or `insert`, whichever comes
+    /// earlier. Passing `None` disables each of them for the namespace. The entries
+    /// in no namespace follow the expiry set by `expire_after`, if any.
+    ///
+    /// Note that the `time_to_live` and `time_to_idle` of the cache still apply to
+    /// all entries. So to let a namespace live longer than others, do not set them
+    /// to the cache but register a namespace for the other keys.
+    ///
+    /// # Panics
+    ///
+    /// Panics if either `time_to_live` or `time_to_idle` is longer than 1000 years.
+    /// This is done to protect against overflow when computing key expiration.
+    pub fn namespace_expiration(
+        self,
+        matches: impl Fn(&K) -> bool + Send + Sync + 'static,
+        time_to_live: Option<Duration>,
+        time_to_idle: Option<Duration>,
+    ) -> Self {
+        builder_utils::ensure_expirations_or_panic(time_to_live, time_to_idle);
+        let mut builder = self;
+        builder
+            .expiration_policy
+            .add_namespace(Arc::new(matches), time_to_live, time_to_idle);
+        builder
+    }
+
     #[cfg(test)]
     pub(crate) fn housekeeper_config(self, conf: HousekeeperConfig) -> Self {
         Self {
@@ -419,6 +706,90 @@ impl<K, V, C> CacheBuilder<K, V, C> {
             ..self
         }
     }
+
+    /// Sets a [`MockClock`][mock-clock] as the clock of the cache, so that the
+    /// entries can be expired by advancing the clock instead of sleeping. This is
+    /// meant for unit tests.
+    ///
+    /// This replaces the time source set by the `time_source` method, and vice
+    /// versa.
+    ///
+    /// [mock-clock]: ../struct.MockClock.html
+    pub fn clock(self, clock: MockClock) -> Self {
+        Self {
+            expiration_clock: Some(clock.clock()),
+            ..self
+        }
+    }
+
+    /// Sets the source of the current time used by the cache. See
+    /// [`TimeSource`][time-source] for the choices.
+    ///
+    /// This replaces the clock set by the `clock` method, and vice versa.
+    ///
+    /// [time-source]: ../enum.TimeSource.html
+    pub fn time_source(self, source: TimeSource) -> Self {
+        Self {
+            expiration_clock: Clock::from_time_source(source),
+            ..self
+        }
+    }
+
+    fn derived_max_capacity(&self) -> Option<u64> {
+        builder_utils::derive_max_capacity(
+            self.max_capacity,
+            self.weigher.is_some(),
+            self.max_memory_fraction,
+        )
+        .unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    fn derived_initial_capacity(&self) -> Option<usize> {
+        builder_utils::derive_initial_capacity(
+            self.initial_capacity,
+            self.max_capacity,
+            self.weigher.is_some(),
+            self.initial_capacity_load_factor,
+        )
+        .unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// # Panics
+    ///
+    /// Panics if `max_concurrent_loads` is zero.
+    fn load_limiter(&self) -> Option<Arc<Semaphore>> {
+        self.max_concurrent_loads.map(|n| {
+            assert!(n > 0, "max_concurrent_loads must not be zero");
+            Arc::new(Semaphore::new(n))
+        })
+    }
+
+    fn validate(&self) -> Result<(), BuilderError> {
+        let exp = &self.expiration_policy;
+        let max_capacity = builder_utils::derive_max_capacity(
+            self.max_capacity,
+            self.weigher.is_some(),
+            self.max_memory_fraction,
+        )?;
+        builder_utils::validate_config(
+            None,
+            max_capacity,
+            self.weigher.is_some(),
+            exp.time_to_live(),
+            exp.time_to_idle(),
+        )?;
+        builder_utils::derive_initial_capacity(
+            self.initial_capacity,
+            self.max_capacity,
+            self.weigher.is_some(),
+            self.initial_capacity_load_factor,
+        )
+        .map(|_| ())?;
+        if self.max_concurrent_loads == Some(0) {
+            return Err(BuilderError::ZeroMaxConcurrentLoads);
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -477,4 +848,26 @@ mod tests {
             .time_to_idle(duration + Duration::from_secs(1))
             .build();
     }
+
+    #[tokio::test]
+    async fn try_build_cache() {
+        use crate::BuilderError;
+
+        let cache = CacheBuilder::new(100)
+            .time_to_idle(Duration::from_secs(60))
+            .try_build()
+            .unwrap();
+        cache.insert('a', "Alice").await;
+        assert_eq!(cache.get(&'a').await, Some("Alice"));
+
+        let result = CacheBuilder::<char, String, _>::new(100)
+            .time_to_live(Duration::ZERO)
+            .try_build();
+        assert_eq!(result.err(), Some(BuilderError::ZeroTimeToLive));
+
+        let result = CacheBuilder::default()
+            .weigher(|_k: &char, v: &String| v.len() as u32)
+            .try_build();
+        assert_eq!(result.err(), Some(BuilderError::WeigherWithoutMaxCapacity));
+    }
 }
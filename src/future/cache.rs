@@ -5,8 +5,8 @@ use super::{
     WriteOp,
 };
 use crate::{
-    common::{concurrent::Weigher, HousekeeperConfig},
-    notification::AsyncEvictionListener,
+    common::{concurrent::Weigher, error_cache::ErrorCache, time::Clock, HousekeeperConfig},
+    notification::{AsyncEvictionListener, RemovalCauses},
     ops::compute::{self, CompResult},
     policy::{EvictionPolicy, ExpirationPolicy},
     Entry, Policy, PredicateError,
@@ -18,12 +18,13 @@ use crate::common::concurrent::debug_counters::CacheDebugStats;
 use async_trait::async_trait;
 use std::{
     borrow::Borrow,
-    collections::hash_map::RandomState,
+    collections::{hash_map::RandomState, HashMap},
     fmt,
     future::Future,
     hash::{BuildHasher, Hash},
     pin::Pin,
     sync::Arc,
+    time::Duration,
 };
 
 #[cfg(test)]
@@ -630,6 +631,7 @@ use std::sync::atomic::{AtomicBool, Ordering};
 pub struct Cache<K, V, S = RandomState> {
     base: BaseCache<K, V, S>,
     value_initializer: Arc<ValueInitializer<K, V, S>>,
+    error_cache: Option<Arc<ErrorCache<K, S>>>,
 
     #[cfg(test)]
     schedule_write_op_should_block: AtomicBool,
@@ -663,6 +665,7 @@ impl<K, V, S> Clone for Cache<K, V, S> {
         Self {
             base: self.base.clone(),
             value_initializer: Arc::clone(&self.value_initializer),
+            error_cache: self.error_cache.clone(),
 
             #[cfg(test)]
             schedule_write_op_should_block: AtomicBool::new(
@@ -788,9 +791,13 @@ where
             None,
             EvictionPolicy::default(),
             None,
+            RemovalCauses::default(),
             ExpirationPolicy::default(),
             HousekeeperConfig::default(),
             false,
+            None,
+            None,
+            None,
         )
     }
 
@@ -819,10 +826,16 @@ where
         weigher: Option<Weigher<K, V>>,
         eviction_policy: EvictionPolicy,
         eviction_listener: Option<AsyncEvictionListener<K, V>>,
+        eviction_listener_causes: RemovalCauses,
         expiration_policy: ExpirationPolicy<K, V>,
         housekeeper_config: HousekeeperConfig,
         invalidator_enabled: bool,
+        load_limiter: Option<Arc<async_lock::Semaphore>>,
+        error_time_to_live: Option<Duration>,
+        expiration_clock: Option<Clock>,
     ) -> Self {
+        let error_cache =
+            error_time_to_live.map(|ttl| Arc::new(ErrorCache::new(ttl, build_hasher.clone())));
         Self {
             base: BaseCache::new(
                 name,
@@ -832,11 +845,15 @@ where
                 weigher,
                 eviction_policy,
                 eviction_listener,
+                eviction_listener_causes,
                 expiration_policy,
                 housekeeper_config,
                 invalidator_enabled,
+                expiration_clock,
+                error_cache.clone(),
             ),
-            value_initializer: Arc::new(ValueInitializer::with_hasher(build_hasher)),
+            value_initializer: Arc::new(ValueInitializer::new(build_hasher, load_limiter)),
+            error_cache,
 
             #[cfg(test)]
             schedule_write_op_should_block: Default::default(), // false
@@ -1388,6 +1405,17 @@ where
         self.base.invalidate_all();
     }
 
+    /// Discards all cached values that have not been inserted or updated within
+    /// the last `age`.
+    ///
+    /// Like `invalidate_all`, this method returns immediately and the invalidated
+    /// values are evicted later by the housekeeping tasks, but `get` will never
+    /// return them. It moves the watermark used by `invalidate_all` forward to
+    /// `age` before now, so it never revives the entries invalidated earlier.
+    pub fn invalidate_entries_older_than(&self, age: Duration) {
+        self.base.invalidate_entries_older_than(age);
+    }
+
     /// Discards cached values that satisfy a predicate.
     ///
     /// `invalidate_entries_if` takes a closure that returns `true` or `false`. This
@@ -1482,6 +1510,46 @@ where
         Iter::new(inner)
     }
 
+    /// Returns a `HashMap` holding the clones of the keys and values of the entries
+    /// currently in the cache.
+    ///
+    /// This is a convenient way to hand the contents of the cache to a
+    /// serialization layer or to make assertions in tests. The map is built with
+    /// the [`iter`](#method.iter) method, so it has the same guarantees: it is
+    /// weakly consistent, and the entries inserted or removed while building the
+    /// map may or may not be included. Like the `iter` method, it does not update
+    /// the historic popularity estimator or reset idle timers for keys.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// // Cargo.toml
+    /// //
+    /// // [dependencies]
+    /// // moka = { version = "0.12", features = ["future"] }
+    /// // tokio = { version = "1", features = ["rt-multi-thread", "macros" ] }
+    /// use moka::future::Cache;
+    /// use std::collections::HashMap;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let cache = Cache::new(100);
+    ///     cache.insert("Julia", 14).await;
+    ///     cache.insert("Alice", 13).await;
+    ///
+    ///     let map = cache.to_hash_map();
+    ///     assert_eq!(map, HashMap::from([("Julia", 14), ("Alice", 13)]));
+    /// }
+    /// ```
+    pub fn to_hash_map(&self) -> HashMap<K, V>
+    where
+        K: Clone,
+    {
+        let mut map = HashMap::with_capacity(self.entry_count() as usize);
+        map.extend(self.iter().map(|(k, v)| (K::clone(&k), v)));
+        map
+    }
+
     /// Performs any pending maintenance operations needed by the cache.
     pub async fn run_pending_tasks(&self) {
         if let Some(hk) = &self.base.housekeeper {
@@ -1783,6 +1851,15 @@ where
             None
         };
 
+        // If the `init` future has recently failed for the key, return the cached
+        // error without resolving it.
+        if let Some(ec) = &self.error_cache {
+            let now = self.base.current_time_from_expiration_clock();
+            if let Some(e) = ec.get::<E>(&*key, now) {
+                return Err(e);
+            }
+        }
+
         let type_id = ValueInitializer::<K, V, S>::type_id_for_try_get_with::<E>();
         let post_init = ValueInitializer::<K, V, S>::post_init_for_try_get_with;
 
@@ -1797,6 +1874,10 @@ where
             }
             InitResult::ReadExisting(v) => Ok(Entry::new(k, v, false, false)),
             InitResult::InitErr(e) => {
+                if let Some(ec) = &self.error_cache {
+                    let now = self.base.current_time_from_expiration_clock();
+                    ec.insert(Arc::clone(&key), Arc::clone(&e), now);
+                }
                 crossbeam_epoch::pin().flush();
                 Err(e)
             }
@@ -1804,7 +1885,12 @@ where
     }
 
     async fn insert_with_hash(&self, key: Arc<K>, hash: u64, value: V) {
+        if let Some(ec) = &self.error_cache {
+            ec.remove(&*key);
+        }
+
         if self.base.is_map_disabled() {
+            self.base.notify_rejected(key, value).await;
             return;
         }
 
@@ -2226,6 +2312,155 @@ mod tests {
         assert_eq!(cache.entry_count(), 0)
     }
 
+    #[tokio::test]
+    async fn max_concurrent_loads() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        const MAX_LOADS: usize = 2;
+
+        let cache = Cache::builder()
+            .max_capacity(100)
+            .max_concurrent_loads(MAX_LOADS)
+            .build();
+        let running = Arc::new(AtomicUsize::new(0));
+        let max_running = Arc::new(AtomicUsize::new(0));
+
+        let tasks = (0..8).map(|key| {
+            let (cache, running, max_running) = (
+                cache.clone(),
+                Arc::clone(&running),
+                Arc::clone(&max_running),
+            );
+            async move {
+                cache
+                    .get_with(key, async {
+                        let n = running.fetch_add(1, Ordering::AcqRel) + 1;
+                        max_running.fetch_max(n, Ordering::AcqRel);
+                        tokio::time::sleep(Duration::from_millis(50)).await;
+                        running.fetch_sub(1, Ordering::AcqRel);
+                        key * 10
+                    })
+                    .await
+            }
+        });
+
+        let values = futures_util::future::join_all(tasks).await;
+        assert_eq!(values, (0..8).map(|key| key * 10).collect::<Vec<_>>());
+        assert_eq!(max_running.load(Ordering::Acquire), MAX_LOADS);
+        assert!(cache.is_waiter_map_empty());
+    }
+
+    #[tokio::test]
+    async fn error_time_to_live() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let clock = crate::MockClock::new();
+        let cache = Cache::builder()
+            .max_capacity(100)
+            .error_time_to_live(Duration::from_secs(10))
+            .clock(clock.clone())
+            .build();
+
+        let calls = AtomicUsize::new(0);
+        let fail = || async {
+            calls.fetch_add(1, Ordering::AcqRel);
+            Err("failed")
+        };
+
+        assert_eq!(cache.try_get_with(1, fail()).await, Err(Arc::new("failed")));
+        assert_eq!(cache.try_get_with(1, fail()).await, Err(Arc::new("failed")));
+        assert_eq!(calls.load(Ordering::Acquire), 1);
+
+        clock.advance(Duration::from_secs(10));
+        assert_eq!(cache.try_get_with(1, fail()).await, Err(Arc::new("failed")));
+        assert_eq!(calls.load(Ordering::Acquire), 2);
+
+        // Inserting a value discards the cached error.
+        cache.insert(1, 10).await;
+        cache.invalidate(&1).await;
+        assert_eq!(cache.try_get_with(1, fail()).await, Err(Arc::new("failed")));
+        assert_eq!(calls.load(Ordering::Acquire), 3);
+    }
+
+    #[tokio::test]
+    async fn max_capacity_zero_with_eviction_listener() {
+        let actual = Arc::new(Mutex::new(Vec::new()));
+        let a1 = Arc::clone(&actual);
+        let listener = move |k, v, cause| -> ListenerFuture {
+            let a2 = Arc::clone(&a1);
+            async move {
+                a2.lock().await.push((k, v, cause));
+            }
+            .boxed()
+        };
+
+        let mut cache = Cache::builder()
+            .max_capacity(0)
+            .async_eviction_listener(listener)
+            .build();
+        cache.reconfigure_for_testing().await;
+
+        // Make the cache exterior immutable.
+        let cache = cache;
+
+        cache.insert("a", "alice").await;
+        assert!(cache.get(&"a").await.is_none());
+
+        // The value is returned to the caller, but is not stored.
+        assert_eq!(cache.get_with("b", async { "bob" }).await, "bob");
+        assert!(cache.get(&"b").await.is_none());
+
+        cache.run_pending_tasks().await;
+        assert_eq!(cache.entry_count(), 0);
+
+        let expected = vec![
+            (Arc::new("a"), "alice", RemovalCause::Size),
+            (Arc::new("b"), "bob", RemovalCause::Size),
+        ];
+        assert_eq!(*actual.lock().await, expected);
+    }
+
+    #[tokio::test]
+    async fn eviction_listener_causes() {
+        use crate::notification::RemovalCauses;
+
+        let actual = Arc::new(Mutex::new(Vec::new()));
+        let a1 = Arc::clone(&actual);
+        let listener = move |k, v, cause| -> ListenerFuture {
+            let a2 = Arc::clone(&a1);
+            async move {
+                a2.lock().await.push((k, v, cause));
+            }
+            .boxed()
+        };
+
+        let mut cache = Cache::builder()
+            .max_capacity(1)
+            .async_eviction_listener(listener)
+            .eviction_listener_causes(RemovalCauses::all().without(RemovalCause::Replaced))
+            .build();
+        cache.reconfigure_for_testing().await;
+
+        // Make the cache exterior immutable.
+        let cache = cache;
+
+        cache.insert("a", "alice").await;
+        cache.insert("a", "anna").await;
+        cache.run_pending_tasks().await;
+        cache.invalidate(&"a").await;
+        cache.insert("b", "bob").await;
+        cache.run_pending_tasks().await;
+        cache.insert("c", "cindy").await;
+        cache.run_pending_tasks().await;
+
+        // "alice" was replaced without a notification.
+        let expected = vec![
+            (Arc::new("a"), "anna", RemovalCause::Explicit),
+            (Arc::new("c"), "cindy", RemovalCause::Size),
+        ];
+        verify_notification_vec(&cache, actual, &expected).await;
+    }
+
     #[tokio::test]
     async fn basic_single_async_task() {
         // The following `Vec`s will hold actual and expected notifications.
@@ -71,7 +71,11 @@ pub(crate) struct Housekeeper {
 }
 
 impl Housekeeper {
-    pub(crate) fn new(is_eviction_listener_enabled: bool, config: HousekeeperConfig) -> Self {
+    pub(crate) fn new(
+        is_eviction_listener_enabled: bool,
+        config: HousekeeperConfig,
+        now: Instant,
+    ) -> Self {
         let (more_entries_to_evict, maintenance_task_timeout) = if is_eviction_listener_enabled {
             (
                 Some(AtomicBool::new(false)),
@@ -83,7 +87,7 @@ impl Housekeeper {
 
         Self {
             current_task: Mutex::default(),
-            run_after: AtomicInstant::new(Self::sync_after(Instant::now())),
+            run_after: AtomicInstant::new(Self::sync_after(now)),
             more_entries_to_evict,
             maintenance_task_timeout,
             max_log_sync_repeats: config.max_log_sync_repeats,
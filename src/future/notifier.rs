@@ -5,25 +5,38 @@ use std::sync::{
 
 use futures_util::FutureExt;
 
-use crate::notification::{AsyncEvictionListener, RemovalCause};
+use crate::notification::{AsyncEvictionListener, RemovalCause, RemovalCauses};
 
 pub(crate) struct RemovalNotifier<K, V> {
     listener: AsyncEvictionListener<K, V>,
+    causes: RemovalCauses,
     is_enabled: AtomicBool,
     #[cfg(feature = "logging")]
     cache_name: Option<String>,
 }
 
 impl<K, V> RemovalNotifier<K, V> {
-    pub(crate) fn new(listener: AsyncEvictionListener<K, V>, _cache_name: Option<String>) -> Self {
+    pub(crate) fn new(
+        listener: AsyncEvictionListener<K, V>,
+        causes: RemovalCauses,
+        _cache_name: Option<String>,
+    ) -> Self {
         Self {
             listener,
+            causes,
             is_enabled: AtomicBool::new(true),
             #[cfg(feature = "logging")]
             cache_name: _cache_name,
         }
     }
 
+    /// Returns `true` if the listener should be notified of the removals by the
+    /// `cause`.
+    #[inline]
+    pub(crate) fn is_notified(&self, cause: RemovalCause) -> bool {
+        self.causes.contains(cause)
+    }
+
     pub(crate) async fn notify(&self, key: Arc<K>, value: V, cause: RemovalCause) {
         use std::panic::{catch_unwind, AssertUnwindSafe};
 
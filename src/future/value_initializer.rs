@@ -1,4 +1,4 @@
-use async_lock::{RwLock, RwLockWriteGuard};
+use async_lock::{RwLock, RwLockWriteGuard, Semaphore};
 use async_trait::async_trait;
 use futures_util::FutureExt;
 use std::{
@@ -145,6 +145,9 @@ pub(crate) struct ValueInitializer<K, V, S> {
     // can always downcast the trait object ErrorObject (in Waiter<V>) into its
     // concrete type.
     waiters: TrioArc<WaiterMap<K, V, S>>,
+    // Bounds the number of `init` futures resolved at the same time across all
+    // keys.
+    load_limiter: Option<Arc<Semaphore>>,
 }
 
 impl<K, V, S> ValueInitializer<K, V, S>
@@ -153,12 +156,13 @@ where
     V: Clone + Send + Sync + 'static,
     S: BuildHasher + Send + Sync + 'static,
 {
-    pub(crate) fn with_hasher(hasher: S) -> Self {
+    pub(crate) fn new(hasher: S, load_limiter: Option<Arc<Semaphore>>) -> Self {
         Self {
             waiters: TrioArc::new(crate::cht::SegmentedHashMap::with_num_segments_and_hasher(
                 WAITER_MAP_NUM_SEGMENTS,
                 hasher,
             )),
+            load_limiter,
         }
     }
 
@@ -261,9 +265,16 @@ where
         }
 
         // The value still does note exist. Let's resolve the init
-        // future. Catching panic is safe here as we do not try to
-        // resolve the future again.
-        match AssertUnwindSafe(init).catch_unwind().await {
+        // future, after getting a permit if the number of concurrent
+        // loads is limited. Catching panic is safe here as we do not try
+        // to resolve the future again.
+        let permit = match &self.load_limiter {
+            Some(limiter) => Some(limiter.acquire().await),
+            None => None,
+        };
+        let init_res = AssertUnwindSafe(init).catch_unwind().await;
+        drop(permit);
+        match init_res {
             // Resolved.
             Ok(value) => match post_init(value) {
                 Ok(value) => {
@@ -0,0 +1,456 @@
+//! Provides a memory cache of HTTP responses, powered by a weigher-bounded
+//! [`future::Cache`][future-cache].
+//!
+//! To use this module, enable a crate feature called "http".
+//!
+//! [`ResponseCache`] stores the status, the headers and the body bytes of the
+//! responses, keyed by the method and the URI of the requests and by the values of
+//! the request headers named in the `Vary` header of the responses. It is cheap to
+//! clone, so it can be shared as the state of a web application (e.g. an axum
+//! `State`) and used by the handlers or a middleware.
+//!
+//! Only the responses to `GET` and `HEAD` requests with a successful status are
+//! cached. The responses with `Cache-Control: no-store` or `private`, or with
+//! `Vary: *`, are not cached.
+//!
+//! [future-cache]: ../future/struct.Cache.html
+//!
+//! # Example
+//!
+//! ```rust
+//! // Cargo.toml
+//! //
+//! // [dependencies]
+//! // moka = { version = "0.12", features = ["http"] }
+//! // http = "0.2"
+//! // bytes = "1"
+//! // tokio = { version = "1", features = ["rt-multi-thread", "macros" ] }
+//!
+//! use bytes::Bytes;
+//! use http::{header, Request, Response};
+//! use moka::http::ResponseCache;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     // Up to 32MiB of responses.
+//!     let cache = ResponseCache::new(32 * 1024 * 1024);
+//!
+//!     let req = Request::get("/hello")
+//!         .header(header::ACCEPT_LANGUAGE, "en")
+//!         .body(())
+//!         .unwrap();
+//!     assert!(cache.get(&req).await.is_none());
+//!
+//!     let resp = Response::builder()
+//!         .header(header::VARY, "accept-language")
+//!         .body(Bytes::from("Hello!"))
+//!         .unwrap();
+//!     assert!(cache.insert(&req, resp).await);
+//!
+//!     let cached = cache.get(&req).await.unwrap();
+//!     assert_eq!(cached.body(), &Bytes::from("Hello!"));
+//!
+//!     // The response varies on the language.
+//!     let req = Request::get("/hello")
+//!         .header(header::ACCEPT_LANGUAGE, "ja")
+//!         .body(())
+//!         .unwrap();
+//!     assert!(cache.get(&req).await.is_none());
+//! }
+//! ```
+
+use crate::future::{Cache, CacheBuilder};
+
+use ::http::{
+    header::{self, HeaderMap, HeaderName, HeaderValue},
+    Method, Request, Response, StatusCode, Uri,
+};
+use bytes::Bytes;
+use std::{
+    collections::hash_map::RandomState,
+    fmt,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+/// The max number of the `Vary` header name lists remembered for the pairs of a
+/// method and a URI.
+const MAX_VARY_ENTRIES: u64 = 10_000;
+
+/// The key of a cached response.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ResponseKey {
+    method: Method,
+    uri: String,
+    /// The ID of the `Variants` of the method and the URI.
+    variants_id: u64,
+    /// The values of the request headers named in the `Vary` header of the
+    /// response, in the order of the names.
+    vary: Vec<(HeaderName, Option<HeaderValue>)>,
+}
+
+impl ResponseKey {
+    fn new(method: &Method, uri: &Uri, variants: &Variants, headers: &HeaderMap) -> Self {
+        Self {
+            method: method.clone(),
+            uri: uri.to_string(),
+            variants_id: variants.id,
+            vary: variants
+                .names
+                .iter()
+                .map(|name| (name.clone(), headers.get(name).cloned()))
+                .collect(),
+        }
+    }
+
+    /// Returns the method of the request.
+    pub fn method(&self) -> &Method {
+        &self.method
+    }
+
+    /// Returns the URI of the request.
+    pub fn uri(&self) -> &str {
+        &self.uri
+    }
+}
+
+/// A cached HTTP response.
+#[derive(Clone, Debug)]
+pub struct CachedResponse {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Bytes,
+}
+
+impl CachedResponse {
+    /// Constructs a new `CachedResponse`.
+    pub fn new(status: StatusCode, headers: HeaderMap, body: Bytes) -> Self {
+        Self {
+            status,
+            headers,
+            body,
+        }
+    }
+
+    /// Returns the status of the response.
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    /// Returns the headers of the response.
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// Returns the body of the response.
+    pub fn body(&self) -> &Bytes {
+        &self.body
+    }
+
+    /// Converts this into an `http::Response`. The body is not copied.
+    pub fn into_response(self) -> Response<Bytes> {
+        let mut resp = Response::new(self.body);
+        *resp.status_mut() = self.status;
+        *resp.headers_mut() = self.headers;
+        resp
+    }
+
+    /// Returns the number of bytes of the body and the headers.
+    fn weight(&self) -> u32 {
+        let headers = self
+            .headers
+            .iter()
+            .map(|(name, value)| name.as_str().len() + value.len())
+            .sum::<usize>();
+        u32::try_from(self.body.len().saturating_add(headers)).unwrap_or(u32::MAX)
+    }
+
+    fn is_cacheable(&self) -> bool {
+        let mut cache_control = self
+            .headers
+            .get_all(header::CACHE_CONTROL)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(str::trim);
+        self.status.is_success()
+            && !cache_control
+                .any(|d| d.eq_ignore_ascii_case("no-store") || d.eq_ignore_ascii_case("private"))
+            && !vary_names(&self.headers).iter().any(|name| name == "*")
+    }
+}
+
+impl From<Response<Bytes>> for CachedResponse {
+    fn from(resp: Response<Bytes>) -> Self {
+        let (parts, body) = resp.into_parts();
+        Self::new(parts.status, parts.headers, body)
+    }
+}
+
+impl From<CachedResponse> for Response<Bytes> {
+    fn from(cached: CachedResponse) -> Self {
+        cached.into_response()
+    }
+}
+
+/// Returns the header names listed in the `Vary` headers, in lower case.
+fn vary_names(headers: &HeaderMap) -> Vec<String> {
+    headers
+        .get_all(header::VARY)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|name| name.trim().to_ascii_lowercase())
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+/// The `Vary` header names of the cached responses to the requests of a method
+/// and a URI.
+///
+/// A new ID is given when the names change or the responses are invalidated, so
+/// that the responses cached for the previous names cannot be found anymore.
+struct Variants {
+    id: u64,
+    names: Vec<HeaderName>,
+}
+
+/// A memory cache of HTTP responses.
+///
+/// See [the module level documentation](./index.html) for an example.
+#[derive(Clone)]
+pub struct ResponseCache<S = RandomState> {
+    responses: Cache<ResponseKey, CachedResponse, S>,
+    /// The `Vary` header names of the last cached response for each pair of a
+    /// method and a URI.
+    variants: Cache<(Method, String), Arc<Variants>>,
+    next_variants_id: Arc<AtomicU64>,
+}
+
+impl<S> fmt::Debug for ResponseCache<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ResponseCache").finish_non_exhaustive()
+    }
+}
+
+impl ResponseCache {
+    /// Constructs a new `ResponseCache` holding up to `max_bytes` of the bodies
+    /// and the headers of the responses.
+    pub fn new(max_bytes: u64) -> Self {
+        Self::builder(max_bytes).build().into()
+    }
+
+    /// Returns a cache builder with the weigher and the max capacity set for
+    /// `max_bytes` of responses. Use it to set other options, such as the time to
+    /// live, and convert the built cache into a `ResponseCache` with `into`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use moka::http::ResponseCache;
+    /// use std::time::Duration;
+    ///
+    /// let cache: ResponseCache = ResponseCache::builder(32 * 1024 * 1024)
+    ///     .time_to_live(Duration::from_secs(60))
+    ///     .build()
+    ///     .into();
+    /// ```
+    pub fn builder(
+        max_bytes: u64,
+    ) -> CacheBuilder<ResponseKey, CachedResponse, Cache<ResponseKey, CachedResponse>> {
+        Cache::builder()
+            .max_capacity(max_bytes)
+            .weigher(|_key, resp: &CachedResponse| resp.weight())
+    }
+}
+
+impl<S> From<Cache<ResponseKey, CachedResponse, S>> for ResponseCache<S> {
+    fn from(responses: Cache<ResponseKey, CachedResponse, S>) -> Self {
+        Self {
+            responses,
+            variants: Cache::new(MAX_VARY_ENTRIES),
+            next_variants_id: Arc::default(),
+        }
+    }
+}
+
+impl<S> ResponseCache<S>
+where
+    S: std::hash::BuildHasher + Clone + Send + Sync + 'static,
+{
+    /// Returns the cached response to the `req`, or `None` if there is no such
+    /// response or the request method is neither `GET` nor `HEAD`.
+    pub async fn get<B>(&self, req: &Request<B>) -> Option<Response<Bytes>> {
+        if !Self::is_cacheable_method(req.method()) {
+            return None;
+        }
+        let base_key = (req.method().clone(), req.uri().to_string());
+        let variants = self.variants.get(&base_key).await?;
+        let key = ResponseKey::new(req.method(), req.uri(), &variants, req.headers());
+        self.responses
+            .get(&key)
+            .await
+            .map(CachedResponse::into_response)
+    }
+
+    /// Caches the `resp` to the `req`. Returns `false` if the response is not
+    /// cacheable.
+    pub async fn insert<B>(&self, req: &Request<B>, resp: impl Into<CachedResponse>) -> bool {
+        let resp = resp.into();
+        if !Self::is_cacheable_method(req.method()) || !resp.is_cacheable() {
+            return false;
+        }
+        let names = vary_names(resp.headers())
+            .iter()
+            .filter_map(|name| HeaderName::from_bytes(name.as_bytes()).ok())
+            .collect::<Vec<_>>();
+        let base_key = (req.method().clone(), req.uri().to_string());
+        let variants = match self.variants.get(&base_key).await {
+            Some(variants) if variants.names == names => variants,
+            _ => {
+                let variants = Arc::new(Variants {
+                    id: self.next_variants_id.fetch_add(1, Ordering::Relaxed),
+                    names,
+                });
+                self.variants.insert(base_key, Arc::clone(&variants)).await;
+                variants
+            }
+        };
+        let key = ResponseKey::new(req.method(), req.uri(), &variants, req.headers());
+        self.responses.insert(key, resp).await;
+        true
+    }
+
+    /// Discards all the cached responses to the requests of the `method` and the
+    /// `uri`.
+    pub async fn invalidate(&self, method: &Method, uri: &Uri) {
+        // The responses cannot be found without their `Variants`, and will be
+        // evicted eventually.
+        let base_key = (method.clone(), uri.to_string());
+        self.variants.invalidate(&base_key).await;
+    }
+
+    /// Returns the underlying cache of the responses.
+    pub fn responses(&self) -> &Cache<ResponseKey, CachedResponse, S> {
+        &self.responses
+    }
+
+    fn is_cacheable_method(method: &Method) -> bool {
+        method == Method::GET || method == Method::HEAD
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CachedResponse, ResponseCache};
+
+    use ::http::{header, Method, Request, Response, StatusCode, Uri};
+    use bytes::Bytes;
+
+    fn response(body: &'static str) -> Response<Bytes> {
+        Response::new(Bytes::from_static(body.as_bytes()))
+    }
+
+    #[tokio::test]
+    async fn cache_responses() {
+        let cache = ResponseCache::new(1024);
+        let req = Request::get("/a?x=1").body(()).unwrap();
+
+        assert!(cache.get(&req).await.is_none());
+        assert!(cache.insert(&req, response("alice")).await);
+        let cached = cache.get(&req).await.unwrap();
+        assert_eq!(cached.status(), StatusCode::OK);
+        assert_eq!(cached.body(), "alice");
+
+        // The query is a part of the key.
+        let other = Request::get("/a?x=2").body(()).unwrap();
+        assert!(cache.get(&other).await.is_none());
+        // So is the method.
+        let head = Request::head("/a?x=1").body(()).unwrap();
+        assert!(cache.get(&head).await.is_none());
+
+        cache
+            .invalidate(&Method::GET, &Uri::from_static("/a?x=1"))
+            .await;
+        assert!(cache.get(&req).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn skip_uncacheable() {
+        let cache = ResponseCache::new(1024);
+
+        let post = Request::post("/a").body(()).unwrap();
+        assert!(!cache.insert(&post, response("alice")).await);
+
+        let req = Request::get("/a").body(()).unwrap();
+        let mut not_found = response("not found");
+        *not_found.status_mut() = StatusCode::NOT_FOUND;
+        assert!(!cache.insert(&req, not_found).await);
+
+        for (name, value) in [
+            (header::CACHE_CONTROL, "max-age=60, no-store"),
+            (header::CACHE_CONTROL, "Private"),
+            (header::VARY, "accept, *"),
+        ] {
+            let resp = Response::builder()
+                .header(name, value)
+                .body(Bytes::from_static(b"alice"))
+                .unwrap();
+            assert!(!cache.insert(&req, resp).await);
+        }
+        assert!(cache.get(&req).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn vary() {
+        let cache = ResponseCache::new(1024);
+        let req = |lang: &str| {
+            Request::get("/hello")
+                .header(header::ACCEPT_LANGUAGE, lang)
+                .header(header::USER_AGENT, lang)
+                .body(())
+                .unwrap()
+        };
+        let resp = |body: &'static str| {
+            Response::builder()
+                .header(header::VARY, "Accept-Language")
+                .body(Bytes::from_static(body.as_bytes()))
+                .unwrap()
+        };
+
+        assert!(cache.insert(&req("en"), resp("Hello")).await);
+        assert!(cache.insert(&req("ja"), resp("Konnichiwa")).await);
+        assert_eq!(cache.get(&req("en")).await.unwrap().body(), "Hello");
+        assert_eq!(cache.get(&req("ja")).await.unwrap().body(), "Konnichiwa");
+        assert!(cache.get(&req("fr")).await.is_none());
+
+        // Invalidating discards all the variants, even if one of them is cached
+        // again.
+        cache
+            .invalidate(&Method::GET, &Uri::from_static("/hello"))
+            .await;
+        assert!(cache.get(&req("en")).await.is_none());
+        assert!(cache.insert(&req("en"), resp("Hello")).await);
+        assert!(cache.get(&req("ja")).await.is_none());
+
+        // A header not in `Vary` is not a part of the key.
+        let mut en = req("en");
+        en.headers_mut()
+            .insert(header::USER_AGENT, "curl".parse().unwrap());
+        assert_eq!(cache.get(&en).await.unwrap().body(), "Hello");
+    }
+
+    #[tokio::test]
+    async fn bounded_by_bytes() {
+        let cache = ResponseCache::new(100);
+        for i in 0..10 {
+            let req = Request::get(format!("/{i}")).body(()).unwrap();
+            let resp = CachedResponse::from(Response::new(Bytes::from(vec![0; 40])));
+            assert!(cache.insert(&req, resp).await);
+        }
+        cache.responses().run_pending_tasks().await;
+        assert!(cache.responses().weighted_size() <= 100);
+    }
+}
@@ -3,7 +3,10 @@
 #[cfg(feature = "sync")]
 pub(crate) mod notifier;
 
-use std::{future::Future, pin::Pin, sync::Arc};
+use std::{fmt, future::Future, pin::Pin, sync::Arc};
+
+#[cfg(feature = "sync")]
+use std::time::Instant;
 
 /// A future returned by an eviction listener.
 ///
@@ -15,7 +18,7 @@ pub type ListenerFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
 
 #[cfg(feature = "sync")]
 pub(crate) type EvictionListener<K, V> =
-    Arc<dyn Fn(Arc<K>, V, RemovalCause) + Send + Sync + 'static>;
+    Arc<dyn Fn(Arc<K>, V, RemovalCause, &RemovalMetadata) + Send + Sync + 'static>;
 
 #[cfg(feature = "future")]
 pub(crate) type AsyncEvictionListener<K, V> =
@@ -45,3 +48,142 @@ impl RemovalCause {
         matches!(self, Self::Expired | Self::Size)
     }
 }
+
+/// A set of [`RemovalCause`]s, used to select the causes notified to the eviction
+/// listener.
+///
+/// Set it by the `eviction_listener_causes` method of the cache builder. For
+/// example, a cache updating its entries very often can skip the notifications of
+/// the `Replaced` cause, so that the cache does not clone the replaced values
+/// only to call the listener.
+///
+/// # Example
+///
+/// ```rust
+/// use moka::notification::{RemovalCause, RemovalCauses};
+///
+/// let causes = RemovalCauses::all().without(RemovalCause::Replaced);
+/// assert!(causes.contains(RemovalCause::Expired));
+/// assert!(!causes.contains(RemovalCause::Replaced));
+///
+/// let causes: RemovalCauses = [RemovalCause::Expired, RemovalCause::Size]
+///     .into_iter()
+///     .collect();
+/// assert_eq!(causes, RemovalCauses::all().without(RemovalCause::Replaced).without(RemovalCause::Explicit));
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct RemovalCauses(u8);
+
+impl RemovalCauses {
+    const CAUSES: [RemovalCause; 4] = [
+        RemovalCause::Expired,
+        RemovalCause::Explicit,
+        RemovalCause::Replaced,
+        RemovalCause::Size,
+    ];
+
+    /// Returns the set of all the causes.
+    pub fn all() -> Self {
+        Self::CAUSES.into_iter().collect()
+    }
+
+    /// Returns the empty set.
+    pub fn empty() -> Self {
+        Self(0)
+    }
+
+    /// Returns this set with the `cause` added.
+    pub fn with(self, cause: RemovalCause) -> Self {
+        Self(self.0 | Self::bit(cause))
+    }
+
+    /// Returns this set with the `cause` removed.
+    pub fn without(self, cause: RemovalCause) -> Self {
+        Self(self.0 & !Self::bit(cause))
+    }
+
+    /// Returns `true` if this set contains the `cause`.
+    pub fn contains(&self, cause: RemovalCause) -> bool {
+        self.0 & Self::bit(cause) != 0
+    }
+
+    fn bit(cause: RemovalCause) -> u8 {
+        1 << cause as u8
+    }
+}
+
+impl Default for RemovalCauses {
+    /// Returns the set of all the causes.
+    fn default() -> Self {
+        Self::all()
+    }
+}
+
+impl From<RemovalCause> for RemovalCauses {
+    fn from(cause: RemovalCause) -> Self {
+        Self::empty().with(cause)
+    }
+}
+
+impl FromIterator<RemovalCause> for RemovalCauses {
+    fn from_iter<I: IntoIterator<Item = RemovalCause>>(iter: I) -> Self {
+        iter.into_iter().fold(Self::empty(), Self::with)
+    }
+}
+
+impl fmt::Debug for RemovalCauses {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set()
+            .entries(Self::CAUSES.iter().filter(|c| self.contains(**c)))
+            .finish()
+    }
+}
+
+/// The metadata of a removed entry, passed to the eviction listener set by the
+/// [`eviction_listener_with_metadata`][listener-method] method of the cache
+/// builder.
+///
+/// [listener-method]: ../sync/struct.CacheBuilder.html#method.eviction_listener_with_metadata
+#[cfg(feature = "sync")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RemovalMetadata {
+    last_modified: Option<Instant>,
+    last_accessed: Option<Instant>,
+    policy_weight: u32,
+}
+
+#[cfg(feature = "sync")]
+impl RemovalMetadata {
+    pub(crate) fn new(
+        last_modified: Option<Instant>,
+        last_accessed: Option<Instant>,
+        policy_weight: u32,
+    ) -> Self {
+        Self {
+            last_modified,
+            last_accessed,
+            policy_weight,
+        }
+    }
+
+    /// Returns the time when the value was inserted or last updated.
+    ///
+    /// Returns `None` if the entry was rejected before it was stored, e.g. when
+    /// the max capacity of the cache is zero.
+    pub fn last_modified(&self) -> Option<Instant> {
+        self.last_modified
+    }
+
+    /// Returns the time when the entry was last read or written.
+    ///
+    /// Returns `None` if the entry was rejected before it was stored.
+    pub fn last_accessed(&self) -> Option<Instant> {
+        self.last_accessed
+    }
+
+    /// Returns the weight of the entry, which is given by the weigher of the cache,
+    /// or `1` when the cache has no weigher.
+    pub fn policy_weight(&self) -> u32 {
+        self.policy_weight
+    }
+}
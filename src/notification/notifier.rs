@@ -3,33 +3,52 @@ use std::sync::{
     Arc,
 };
 
-use crate::notification::{EvictionListener, RemovalCause};
+use crate::notification::{EvictionListener, RemovalCause, RemovalCauses, RemovalMetadata};
 
 pub(crate) struct RemovalNotifier<K, V> {
     listener: EvictionListener<K, V>,
+    causes: RemovalCauses,
     is_enabled: AtomicBool,
     #[cfg(feature = "logging")]
     cache_name: Option<String>,
 }
 
 impl<K, V> RemovalNotifier<K, V> {
-    pub(crate) fn new(listener: EvictionListener<K, V>, _cache_name: Option<String>) -> Self {
+    pub(crate) fn new(
+        listener: EvictionListener<K, V>,
+        causes: RemovalCauses,
+        _cache_name: Option<String>,
+    ) -> Self {
         Self {
             listener,
+            causes,
             is_enabled: AtomicBool::new(true),
             #[cfg(feature = "logging")]
             cache_name: _cache_name,
         }
     }
 
-    pub(crate) fn notify(&self, key: Arc<K>, value: V, cause: RemovalCause) {
+    /// Returns `true` if the listener should be notified of the removals by the
+    /// `cause`.
+    #[inline]
+    pub(crate) fn is_notified(&self, cause: RemovalCause) -> bool {
+        self.causes.contains(cause)
+    }
+
+    pub(crate) fn notify(
+        &self,
+        key: Arc<K>,
+        value: V,
+        cause: RemovalCause,
+        metadata: RemovalMetadata,
+    ) {
         use std::panic::{catch_unwind, AssertUnwindSafe};
 
         if !self.is_enabled.load(Ordering::Acquire) {
             return;
         }
 
-        let listener_clo = || (self.listener)(key, value, cause);
+        let listener_clo = || (self.listener)(key, value, cause, &metadata);
 
         // Safety: It is safe to assert unwind safety here because we will not
         // call the listener again if it has been panicked.
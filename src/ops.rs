@@ -68,4 +68,142 @@ pub mod compute {
             }
         }
     }
+
+    /// A view into a single cached entry, which is either occupied or vacant.
+    ///
+    /// `MapEntry` is given to the closure of the `and_entry_with` method while the
+    /// key-level lock is held. Calling one of the methods on the contained handle
+    /// returns the [`Op`] to perform on the cache.
+    #[derive(Debug)]
+    pub enum MapEntry<K, V> {
+        /// The key has a cached value.
+        Occupied(OccupiedEntry<K, V>),
+        /// The key has no cached value.
+        Vacant(VacantEntry<K>),
+    }
+
+    impl<K, V> MapEntry<K, V> {
+        #[cfg(feature = "sync")]
+        pub(crate) fn new(key: Arc<K>, maybe_entry: Option<Entry<K, V>>) -> Self {
+            match maybe_entry {
+                Some(entry) => Self::Occupied(OccupiedEntry { key, entry }),
+                None => Self::Vacant(VacantEntry { key }),
+            }
+        }
+
+        /// Returns a reference to the key of this entry.
+        pub fn key(&self) -> &K {
+            match self {
+                Self::Occupied(occupied) => occupied.key(),
+                Self::Vacant(vacant) => vacant.key(),
+            }
+        }
+    }
+
+    /// A handle to an occupied entry of [`MapEntry`].
+    #[derive(Debug)]
+    pub struct OccupiedEntry<K, V> {
+        key: Arc<K>,
+        entry: Entry<K, V>,
+    }
+
+    impl<K, V> OccupiedEntry<K, V> {
+        /// Returns a reference to the key of this entry.
+        pub fn key(&self) -> &K {
+            &self.key
+        }
+
+        /// Returns a reference to the cached value.
+        pub fn get(&self) -> &V {
+            self.entry.value()
+        }
+
+        /// Returns the cached value, consuming the handle.
+        pub fn into_value(self) -> V {
+            self.entry.into_value()
+        }
+
+        /// Replaces the cached value with the given one.
+        pub fn insert(self, value: V) -> Op<V> {
+            Op::Put(value)
+        }
+
+        /// Modifies the cached value with the closure `f` and stores the result.
+        pub fn modify(self, f: impl FnOnce(&mut V)) -> Op<V> {
+            let mut value = self.entry.into_value();
+            f(&mut value);
+            Op::Put(value)
+        }
+
+        /// Removes the cached entry.
+        pub fn remove(self) -> Op<V> {
+            Op::Remove
+        }
+
+        /// Leaves the cached entry as it is.
+        pub fn keep(self) -> Op<V> {
+            Op::Nop
+        }
+    }
+
+    /// A handle to a vacant entry of [`MapEntry`].
+    #[derive(Debug)]
+    pub struct VacantEntry<K> {
+        key: Arc<K>,
+    }
+
+    impl<K> VacantEntry<K> {
+        /// Returns a reference to the key of this entry.
+        pub fn key(&self) -> &K {
+            &self.key
+        }
+
+        /// Inserts the given value to the cache.
+        pub fn insert<V>(self, value: V) -> Op<V> {
+            Op::Put(value)
+        }
+
+        /// Leaves the entry vacant.
+        pub fn skip<V>(self) -> Op<V> {
+            Op::Nop
+        }
+    }
+}
+
+/// Types used by the `merge_from` method.
+pub mod merge {
+    use std::fmt;
+
+    type MergeFn<'a, K, V> = Box<dyn FnMut(&K, &V, &V) -> V + 'a>;
+
+    /// Decides which value to keep when the `merge_from` method copies an entry
+    /// whose key already exists in the destination cache.
+    pub enum MergeStrategy<'a, K, V> {
+        /// Keep the value that was inserted or updated more recently. The ages of
+        /// the entries in the source and destination caches are compared.
+        KeepNewer,
+        /// Keep the existing value in the destination cache.
+        KeepExisting,
+        /// Keep the value returned by the closure. The closure takes the key, the
+        /// existing value in the destination cache and the value in the source
+        /// cache.
+        Custom(MergeFn<'a, K, V>),
+    }
+
+    impl<'a, K, V> MergeStrategy<'a, K, V> {
+        /// Creates a `Custom` strategy from the given closure.
+        pub fn custom(f: impl FnMut(&K, &V, &V) -> V + 'a) -> Self {
+            Self::Custom(Box::new(f))
+        }
+    }
+
+    impl<K, V> fmt::Debug for MergeStrategy<'_, K, V> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                Self::KeepNewer => f.write_str("KeepNewer"),
+                Self::KeepExisting => f.write_str("KeepExisting"),
+                Self::Custom(_) => f.write_str("Custom(..)"),
+            }
+        }
+    }
 }
@@ -1,3 +1,8 @@
+#[cfg(feature = "sync")]
+use crate::common::concurrent::constants::{
+    DEFAULT_WRITE_RETRY_INTERVAL_MICROS, MAX_WRITE_RETRIES,
+};
+
 use std::{
     fmt,
     sync::Arc,
@@ -11,6 +16,11 @@ pub struct Policy {
     num_segments: usize,
     time_to_live: Option<Duration>,
     time_to_idle: Option<Duration>,
+    pub(crate) eviction_policy: EvictionPolicy,
+    pub(crate) has_weigher: bool,
+    pub(crate) has_expiry: bool,
+    pub(crate) has_eviction_listener: bool,
+    pub(crate) supports_invalidation_closures: bool,
 }
 
 impl Policy {
@@ -25,6 +35,11 @@ impl Policy {
             num_segments,
             time_to_live,
             time_to_idle,
+            eviction_policy: EvictionPolicy::default(),
+            has_weigher: false,
+            has_expiry: false,
+            has_eviction_listener: false,
+            supports_invalidation_closures: false,
         }
     }
 
@@ -57,6 +72,37 @@ impl Policy {
     pub fn time_to_idle(&self) -> Option<Duration> {
         self.time_to_idle
     }
+
+    /// Returns the eviction (and admission) policy of the cache.
+    pub fn eviction_policy(&self) -> EvictionPolicy {
+        self.eviction_policy.clone()
+    }
+
+    /// Returns `true` if the cache has a weigher, so that the `max_capacity` is
+    /// the max total weighted size of the entries rather than the max number of
+    /// them.
+    pub fn has_weigher(&self) -> bool {
+        self.has_weigher
+    }
+
+    /// Returns `true` if the cache has an [`Expiry`][expiry-trait] to calculate
+    /// the per-entry expiration times.
+    ///
+    /// [expiry-trait]: ./trait.Expiry.html
+    pub fn has_expiry(&self) -> bool {
+        self.has_expiry
+    }
+
+    /// Returns `true` if the cache has an eviction listener. The listener is
+    /// called synchronously when an entry is removed from the cache.
+    pub fn has_eviction_listener(&self) -> bool {
+        self.has_eviction_listener
+    }
+
+    /// Returns `true` if the cache supports the `invalidate_entries_if` method.
+    pub fn supports_invalidation_closures(&self) -> bool {
+        self.supports_invalidation_closures
+    }
 }
 
 /// The eviction (and admission) policy of a cache.
@@ -84,7 +130,7 @@ impl Policy {
 ///
 /// Use associate function [`EvictionPolicy::tiny_lfu`](#method.tiny_lfu) or
 /// [`EvictionPolicy::lru`](#method.lru) to obtain an instance of `EvictionPolicy`.
-#[derive(Clone, Default)]
+#[derive(Clone, Default, PartialEq, Eq)]
 pub struct EvictionPolicy {
     pub(crate) config: EvictionPolicyConfig,
 }
@@ -137,6 +183,231 @@ impl Default for EvictionPolicyConfig {
     }
 }
 
+/// The strategy of a writer to wait for a room in the write log of a cache when
+/// the log is full.
+///
+/// Every write to a cache is recorded to a bounded write log, which is drained
+/// when the pending maintenance tasks are run. When the log is full, a writer
+/// retries to record its write a few times, waiting in the way given by this
+/// strategy between the retries. If the log is still full, the writer waits for
+/// its turn to run the pending tasks by itself.
+///
+/// The default strategy sleeps 50 microseconds between the retries. Use the
+/// associate functions such as
+/// [`WriteRetryStrategy::exponential_backoff`](#method.exponential_backoff) to
+/// obtain another strategy.
+#[cfg(feature = "sync")]
+#[derive(Clone, PartialEq, Eq)]
+pub struct WriteRetryStrategy {
+    pub(crate) config: WriteRetryConfig,
+}
+
+#[cfg(feature = "sync")]
+impl Default for WriteRetryStrategy {
+    fn default() -> Self {
+        Self::sleep(Duration::from_micros(DEFAULT_WRITE_RETRY_INTERVAL_MICROS))
+    }
+}
+
+#[cfg(feature = "sync")]
+impl WriteRetryStrategy {
+    /// Returns a strategy that busy-waits with a spin loop hint between the retries.
+    ///
+    /// Suitable only when the maintenance tasks are expected to finish very soon,
+    /// as it keeps the CPU busy.
+    pub fn spin() -> Self {
+        Self {
+            config: WriteRetryConfig::Spin,
+        }
+    }
+
+    /// Returns a strategy that yields the CPU to other threads between the retries.
+    pub fn yield_now() -> Self {
+        Self {
+            config: WriteRetryConfig::Yield,
+        }
+    }
+
+    /// Returns a strategy that sleeps for the `interval` between the retries.
+    pub fn sleep(interval: Duration) -> Self {
+        Self {
+            config: WriteRetryConfig::Sleep(interval),
+        }
+    }
+
+    /// Returns a strategy that sleeps for the `initial` duration before the first
+    /// retry, and doubles the duration for each of the next retries up to `max`.
+    pub fn exponential_backoff(initial: Duration, max: Duration) -> Self {
+        Self {
+            config: WriteRetryConfig::ExponentialBackoff { initial, max },
+        }
+    }
+
+    /// Returns a strategy that does not retry but parks the writer until its turn
+    /// to run the pending tasks comes.
+    ///
+    /// Suitable when many writers contend for the write log, as the parked writers
+    /// do not use the CPU.
+    pub fn park() -> Self {
+        Self {
+            config: WriteRetryConfig::Park,
+        }
+    }
+
+    /// Returns the number of the retries before the writer waits for its turn to
+    /// run the pending tasks.
+    pub(crate) fn max_retries(&self) -> u32 {
+        match self.config {
+            WriteRetryConfig::Park => 0,
+            _ => MAX_WRITE_RETRIES,
+        }
+    }
+
+    /// Waits before the retry of the given number, starting from zero.
+    pub(crate) fn wait(&self, retry: u32) {
+        match self.config {
+            WriteRetryConfig::Spin => std::hint::spin_loop(),
+            WriteRetryConfig::Yield => std::thread::yield_now(),
+            WriteRetryConfig::Sleep(interval) => std::thread::sleep(interval),
+            WriteRetryConfig::ExponentialBackoff { initial, max } => {
+                let factor = 1u32.checked_shl(retry).unwrap_or(u32::MAX);
+                let interval = initial.checked_mul(factor).unwrap_or(max).min(max);
+                std::thread::sleep(interval);
+            }
+            WriteRetryConfig::Park => (),
+        }
+    }
+}
+
+#[cfg(feature = "sync")]
+impl fmt::Debug for WriteRetryStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.config {
+            WriteRetryConfig::Spin => write!(f, "WriteRetryStrategy::Spin"),
+            WriteRetryConfig::Yield => write!(f, "WriteRetryStrategy::Yield"),
+            WriteRetryConfig::Sleep(interval) => {
+                write!(f, "WriteRetryStrategy::Sleep({interval:?})")
+            }
+            WriteRetryConfig::ExponentialBackoff { initial, max } => write!(
+                f,
+                "WriteRetryStrategy::ExponentialBackoff {{ initial: {initial:?}, max: {max:?} }}"
+            ),
+            WriteRetryConfig::Park => write!(f, "WriteRetryStrategy::Park"),
+        }
+    }
+}
+
+#[cfg(feature = "sync")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum WriteRetryConfig {
+    Spin,
+    Yield,
+    Sleep(Duration),
+    ExponentialBackoff { initial: Duration, max: Duration },
+    Park,
+}
+
+/// The statistics of a cached entry, passed to the
+/// [`expire_after_read_with_stats`][read-with-stats] and
+/// [`expire_after_update_with_stats`][update-with-stats] methods of [`Expiry`].
+///
+/// Requires the `entry-stats` crate feature.
+///
+/// [read-with-stats]: ./trait.Expiry.html#method.expire_after_read_with_stats
+/// [update-with-stats]: ./trait.Expiry.html#method.expire_after_update_with_stats
+#[cfg(feature = "entry-stats")]
+#[cfg_attr(docsrs, doc(cfg(feature = "entry-stats")))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EntryStats {
+    created_at: Instant,
+    read_count: u32,
+    write_count: u32,
+}
+
+#[cfg(feature = "entry-stats")]
+impl EntryStats {
+    pub(crate) fn new(created_at: Instant, read_count: u32, write_count: u32) -> Self {
+        Self {
+            created_at,
+            read_count,
+            write_count,
+        }
+    }
+
+    /// Returns the time when the entry was inserted. Unlike `last_modified_at`,
+    /// it is not changed by the updates of the value.
+    pub fn created_at(&self) -> Instant {
+        self.created_at
+    }
+
+    /// Returns the number of the reads on the entry, including the current read
+    /// if called from `expire_after_read_with_stats`. The count saturates at
+    /// `u32::MAX`.
+    pub fn read_count(&self) -> u32 {
+        self.read_count
+    }
+
+    /// Returns the number of the writes on the entry, i.e. `1` for the insert plus
+    /// the number of the updates, including the current update if called from
+    /// `expire_after_update_with_stats`. The count saturates at `u32::MAX`.
+    pub fn write_count(&self) -> u32 {
+        self.write_count
+    }
+}
+
+/// The stand-in for `EntryStats` without the `entry-stats` feature. The entries
+/// have no stats then, and the caches call the `Expiry` methods without them.
+#[cfg(not(feature = "entry-stats"))]
+pub(crate) struct EntryStats;
+
+/// Calls `expire_after_read_with_stats` of the `expiry`, or `expire_after_read`
+/// without the `entry-stats` feature.
+#[cfg_attr(not(feature = "entry-stats"), allow(unused_variables))]
+pub(crate) fn expire_after_read<K, V>(
+    expiry: &(dyn Expiry<K, V> + Send + Sync),
+    key: &K,
+    value: &V,
+    read_at: Instant,
+    duration_until_expiry: Option<Duration>,
+    last_modified_at: Instant,
+    stats: &EntryStats,
+) -> Option<Duration> {
+    #[cfg(feature = "entry-stats")]
+    return expiry.expire_after_read_with_stats(
+        key,
+        value,
+        read_at,
+        duration_until_expiry,
+        last_modified_at,
+        stats,
+    );
+    #[cfg(not(feature = "entry-stats"))]
+    expiry.expire_after_read(key, value, read_at, duration_until_expiry, last_modified_at)
+}
+
+/// Calls `expire_after_update_with_stats` of the `expiry`, or
+/// `expire_after_update` without the `entry-stats` feature.
+#[cfg_attr(not(feature = "entry-stats"), allow(unused_variables))]
+pub(crate) fn expire_after_update<K, V>(
+    expiry: &(dyn Expiry<K, V> + Send + Sync),
+    key: &K,
+    value: &V,
+    updated_at: Instant,
+    duration_until_expiry: Option<Duration>,
+    stats: &EntryStats,
+) -> Option<Duration> {
+    #[cfg(feature = "entry-stats")]
+    return expiry.expire_after_update_with_stats(
+        key,
+        value,
+        updated_at,
+        duration_until_expiry,
+        stats,
+    );
+    #[cfg(not(feature = "entry-stats"))]
+    expiry.expire_after_update(key, value, updated_at, duration_until_expiry)
+}
+
 /// Calculates when cache entries expire. A single expiration time is retained on
 /// each entry so that the lifetime of an entry may be extended or reduced by
 /// subsequent evaluations.
@@ -233,6 +504,69 @@ pub trait Expiry<K, V> {
         duration_until_expiry
     }
 
+    /// Same as [`expire_after_read`](#method.expire_after_read), but also takes the
+    /// [`EntryStats`] of the entry, such as its creation time and the numbers of
+    /// the reads and writes. This is the method called by the cache, and the
+    /// default implementation calls `expire_after_read`.
+    ///
+    /// Override this method instead of `expire_after_read` when the policy depends
+    /// on the history of the entry, e.g. to extend the lifetime of an entry only
+    /// after it has been read a number of times.
+    ///
+    /// Requires the `entry-stats` crate feature.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use moka::{policy::EntryStats, Expiry};
+    /// use std::time::{Duration, Instant};
+    ///
+    /// /// Keeps the entries for a minute, or for an hour once they have been read
+    /// /// three times.
+    /// struct PromoteHotEntries;
+    ///
+    /// impl Expiry<String, String> for PromoteHotEntries {
+    ///     fn expire_after_create(
+    ///         &self,
+    ///         _key: &String,
+    ///         _value: &String,
+    ///         _created_at: Instant,
+    ///     ) -> Option<Duration> {
+    ///         Some(Duration::from_secs(60))
+    ///     }
+    ///
+    ///     fn expire_after_read_with_stats(
+    ///         &self,
+    ///         _key: &String,
+    ///         _value: &String,
+    ///         _read_at: Instant,
+    ///         duration_until_expiry: Option<Duration>,
+    ///         _last_modified_at: Instant,
+    ///         stats: &EntryStats,
+    ///     ) -> Option<Duration> {
+    ///         if stats.read_count() == 3 {
+    ///             Some(Duration::from_secs(3600))
+    ///         } else {
+    ///             duration_until_expiry
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    #[cfg(feature = "entry-stats")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "entry-stats")))]
+    #[allow(unused_variables)]
+    fn expire_after_read_with_stats(
+        &self,
+        key: &K,
+        value: &V,
+        read_at: Instant,
+        duration_until_expiry: Option<Duration>,
+        last_modified_at: Instant,
+        stats: &EntryStats,
+    ) -> Option<Duration> {
+        self.expire_after_read(key, value, read_at, duration_until_expiry, last_modified_at)
+    }
+
     /// Specifies that the entry should be automatically removed from the cache once
     /// the duration has elapsed after the replacement of its value. This method is
     /// called for cache write methods such as `insert` but only when the key is
@@ -276,12 +610,177 @@ pub trait Expiry<K, V> {
     ) -> Option<Duration> {
         duration_until_expiry
     }
+
+    /// Same as [`expire_after_update`](#method.expire_after_update), but also
+    /// takes the [`EntryStats`] of the entry. This is the method called by the
+    /// cache, and the default implementation calls `expire_after_update`.
+    ///
+    /// Requires the `entry-stats` crate feature.
+    #[cfg(feature = "entry-stats")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "entry-stats")))]
+    #[allow(unused_variables)]
+    fn expire_after_update_with_stats(
+        &self,
+        key: &K,
+        value: &V,
+        updated_at: Instant,
+        duration_until_expiry: Option<Duration>,
+        stats: &EntryStats,
+    ) -> Option<Duration> {
+        self.expire_after_update(key, value, updated_at, duration_until_expiry)
+    }
+}
+
+pub(crate) type NamespaceMatcher<K> = Arc<dyn Fn(&K) -> bool + Send + Sync + 'static>;
+
+/// A namespace of keys having its own time to live and time to idle.
+pub(crate) struct ExpirationNamespace<K> {
+    matches: NamespaceMatcher<K>,
+    time_to_live: Option<Duration>,
+    time_to_idle: Option<Duration>,
+}
+
+impl<K> Clone for ExpirationNamespace<K> {
+    fn clone(&self) -> Self {
+        Self {
+            matches: Arc::clone(&self.matches),
+            time_to_live: self.time_to_live,
+            time_to_idle: self.time_to_idle,
+        }
+    }
+}
+
+impl<K> ExpirationNamespace<K> {
+    fn expire_after_write(&self) -> Option<Duration> {
+        match (self.time_to_live, self.time_to_idle) {
+            (Some(ttl), Some(tti)) => Some(ttl.min(tti)),
+            (ttl, tti) => ttl.or(tti),
+        }
+    }
+
+    fn expire_after_read(&self, read_at: Instant, last_modified_at: Instant) -> Option<Duration> {
+        let remaining_ttl = self
+            .time_to_live
+            .map(|ttl| ttl.saturating_sub(read_at.saturating_duration_since(last_modified_at)));
+        match (remaining_ttl, self.time_to_idle) {
+            (Some(ttl), Some(tti)) => Some(ttl.min(tti)),
+            (ttl, tti) => ttl.or(tti),
+        }
+    }
+}
+
+/// An `Expiry` applying the time to live and time to idle of the first
+/// namespace matching the key, or delegating to the `fallback` expiry if no
+/// namespace matches.
+struct NamespacedExpiry<K, V> {
+    namespaces: Vec<ExpirationNamespace<K>>,
+    fallback: Option<Arc<dyn Expiry<K, V> + Send + Sync + 'static>>,
+}
+
+impl<K, V> NamespacedExpiry<K, V> {
+    fn namespace_of(&self, key: &K) -> Option<&ExpirationNamespace<K>> {
+        self.namespaces.iter().find(|ns| (ns.matches)(key))
+    }
+}
+
+impl<K, V> Expiry<K, V> for NamespacedExpiry<K, V> {
+    fn expire_after_create(&self, key: &K, value: &V, created_at: Instant) -> Option<Duration> {
+        match (self.namespace_of(key), &self.fallback) {
+            (Some(ns), _) => ns.expire_after_write(),
+            (None, Some(expiry)) => expiry.expire_after_create(key, value, created_at),
+            (None, None) => None,
+        }
+    }
+
+    fn expire_after_read(
+        &self,
+        key: &K,
+        value: &V,
+        read_at: Instant,
+        duration_until_expiry: Option<Duration>,
+        last_modified_at: Instant,
+    ) -> Option<Duration> {
+        match (self.namespace_of(key), &self.fallback) {
+            (Some(ns), _) => ns.expire_after_read(read_at, last_modified_at),
+            (None, Some(expiry)) => expiry.expire_after_read(
+                key,
+                value,
+                read_at,
+                duration_until_expiry,
+                last_modified_at,
+            ),
+            (None, None) => duration_until_expiry,
+        }
+    }
+
+    fn expire_after_update(
+        &self,
+        key: &K,
+        value: &V,
+        updated_at: Instant,
+        duration_until_expiry: Option<Duration>,
+    ) -> Option<Duration> {
+        match (self.namespace_of(key), &self.fallback) {
+            (Some(ns), _) => ns.expire_after_write(),
+            (None, Some(expiry)) => {
+                expiry.expire_after_update(key, value, updated_at, duration_until_expiry)
+            }
+            (None, None) => duration_until_expiry,
+        }
+    }
+
+    #[cfg(feature = "entry-stats")]
+    fn expire_after_read_with_stats(
+        &self,
+        key: &K,
+        value: &V,
+        read_at: Instant,
+        duration_until_expiry: Option<Duration>,
+        last_modified_at: Instant,
+        stats: &EntryStats,
+    ) -> Option<Duration> {
+        match (self.namespace_of(key), &self.fallback) {
+            (Some(ns), _) => ns.expire_after_read(read_at, last_modified_at),
+            (None, Some(expiry)) => expiry.expire_after_read_with_stats(
+                key,
+                value,
+                read_at,
+                duration_until_expiry,
+                last_modified_at,
+                stats,
+            ),
+            (None, None) => duration_until_expiry,
+        }
+    }
+
+    #[cfg(feature = "entry-stats")]
+    fn expire_after_update_with_stats(
+        &self,
+        key: &K,
+        value: &V,
+        updated_at: Instant,
+        duration_until_expiry: Option<Duration>,
+        stats: &EntryStats,
+    ) -> Option<Duration> {
+        match (self.namespace_of(key), &self.fallback) {
+            (Some(ns), _) => ns.expire_after_write(),
+            (None, Some(expiry)) => expiry.expire_after_update_with_stats(
+                key,
+                value,
+                updated_at,
+                duration_until_expiry,
+                stats,
+            ),
+            (None, None) => duration_until_expiry,
+        }
+    }
 }
 
 pub(crate) struct ExpirationPolicy<K, V> {
     time_to_live: Option<Duration>,
     time_to_idle: Option<Duration>,
     expiry: Option<Arc<dyn Expiry<K, V> + Send + Sync + 'static>>,
+    namespaces: Vec<ExpirationNamespace<K>>,
 }
 
 impl<K, V> Default for ExpirationPolicy<K, V> {
@@ -290,6 +789,7 @@ impl<K, V> Default for ExpirationPolicy<K, V> {
             time_to_live: None,
             time_to_idle: None,
             expiry: None,
+            namespaces: Vec::default(),
         }
     }
 }
@@ -300,6 +800,7 @@ impl<K, V> Clone for ExpirationPolicy<K, V> {
             time_to_live: self.time_to_live,
             time_to_idle: self.time_to_idle,
             expiry: self.expiry.clone(),
+            namespaces: self.namespaces.clone(),
         }
     }
 }
@@ -315,6 +816,7 @@ impl<K, V> ExpirationPolicy<K, V> {
             time_to_live,
             time_to_idle,
             expiry,
+            namespaces: Vec::default(),
         }
     }
 
@@ -343,6 +845,36 @@ impl<K, V> ExpirationPolicy<K, V> {
     pub(crate) fn set_expiry(&mut self, expiry: Arc<dyn Expiry<K, V> + Send + Sync + 'static>) {
         self.expiry = Some(expiry);
     }
+
+    pub(crate) fn add_namespace(
+        &mut self,
+        matches: NamespaceMatcher<K>,
+        time_to_live: Option<Duration>,
+        time_to_idle: Option<Duration>,
+    ) {
+        self.namespaces.push(ExpirationNamespace {
+            matches,
+            time_to_live,
+            time_to_idle,
+        });
+    }
+
+    /// Wraps the per-entry expiry with an expiry applying the namespaces, if any
+    /// namespace has been added.
+    pub(crate) fn apply_namespaces(&mut self)
+    where
+        K: 'static,
+        V: 'static,
+    {
+        if self.namespaces.is_empty() {
+            return;
+        }
+        let expiry = NamespacedExpiry {
+            namespaces: std::mem::take(&mut self.namespaces),
+            fallback: self.expiry.take(),
+        };
+        self.expiry = Some(Arc::new(expiry));
+    }
 }
 
 #[cfg(test)]
@@ -1,17 +1,47 @@
 //! Provides thread-safe, concurrent cache implementations.
 
 mod builder;
-mod cache;
+pub(crate) mod cache;
 mod entry_selector;
+pub(crate) mod interceptor;
+mod load_context;
+mod loader;
+mod local_sharded;
+mod map_view;
+mod scoped;
 mod segment;
+mod stream;
 mod value_initializer;
 
-pub use crate::sync_base::{iter::Iter, PredicateId};
+#[cfg(debug_assertions)]
+pub use crate::sync_base::invariants::{InvariantReport, InvariantViolation};
+#[cfg(feature = "rayon")]
+pub use crate::sync_base::iter::ParIter;
+pub use crate::sync_base::{
+    class_stats::ClassStats,
+    event_bus::{CacheEvent, EventReceiver},
+    ghost_list::GhostListStats,
+    hit_ratio::{HitRatioEstimate, HitRatioReport},
+    iter::{FrequencyIter, IntoIter, Iter, RecencyIter},
+    lifetime::{LifetimeBucket, LifetimeHistogram},
+    pinned::PinnedEntry,
+    read_sampler::ReadSamplingStats,
+    stats::{CacheStats, DefaultStatsCounter, StatsCounter},
+    victim::VictimDecision,
+    PredicateId,
+};
 pub use {
     builder::CacheBuilder,
     cache::Cache,
     entry_selector::{OwnedKeyEntrySelector, RefKeyEntrySelector},
-    segment::SegmentedCache,
+    interceptor::Interceptor,
+    load_context::{LoadProgress, LoaderContext},
+    loader::Loader,
+    local_sharded::LocalShardedCache,
+    map_view::MapView,
+    scoped::{ScopedCache, ScopedKey},
+    segment::{InvalidationHandle, SegmentedCache},
+    stream::ExportStream,
 };
 
 /// Provides extra methods that will be useful for testing.
@@ -1,9 +1,25 @@
-use super::{Cache, SegmentedCache};
+use super::{
+    interceptor::{ArcInterceptor, InterceptorChain},
+    loader::{ArcLoader, LoaderChain},
+    segment::SegmentSelector,
+    value_initializer::LoadLimiter,
+    Cache, Interceptor, Loader, LocalShardedCache, SegmentedCache,
+};
+#[cfg(feature = "zeroize")]
+use crate::common::zeroizer::ValueZeroizer;
 use crate::{
-    common::{builder_utils, concurrent::Weigher, HousekeeperConfig},
-    notification::{EvictionListener, RemovalCause},
-    policy::{EvictionPolicy, ExpirationPolicy},
-    Expiry,
+    common::{builder_utils, concurrent::Weigher, time::Clock, HousekeeperConfig},
+    notification::{EvictionListener, RemovalCause, RemovalCauses, RemovalMetadata},
+    policy::{EvictionPolicy, ExpirationPolicy, WriteRetryStrategy},
+    sync_base::{
+        hot_key::HotKeyConfig,
+        policy_config::PolicyConfig,
+        stats::StatsCounter,
+        tenant::{TenantClassifier, TenantQuotasConfig},
+        victim::VictimDecision,
+    },
+    weigher::{byte_len, ByteLength, WeighContext},
+    BuilderError, Expiry, MockClock, SeededState, TimeSource,
 };
 
 use std::{
@@ -51,13 +67,25 @@ pub struct CacheBuilder<K, V, C> {
     name: Option<String>,
     max_capacity: Option<u64>,
     initial_capacity: Option<usize>,
+    initial_capacity_load_factor: Option<f64>,
+    max_memory_fraction: Option<f64>,
     num_segments: Option<usize>,
     weigher: Option<Weigher<K, V>>,
     eviction_policy: EvictionPolicy,
     eviction_listener: Option<EvictionListener<K, V>>,
+    eviction_listener_causes: RemovalCauses,
     expiration_policy: ExpirationPolicy<K, V>,
     housekeeper_config: HousekeeperConfig,
     invalidator_enabled: bool,
+    numa_aware: bool,
+    segment_selector: Option<SegmentSelector<K>>,
+    read_through_siblings: bool,
+    loaders: Vec<ArcLoader<K, V>>,
+    max_concurrent_loads: Option<usize>,
+    error_time_to_live: Option<Duration>,
+    interceptors: Vec<ArcInterceptor<K, V>>,
+    policy_config: PolicyConfig<K, V>,
+    tenant_max_entries: Option<Vec<u64>>,
     cache_type: PhantomData<C>,
 }
 
@@ -71,13 +99,25 @@ where
             name: None,
             max_capacity: None,
             initial_capacity: None,
+            initial_capacity_load_factor: None,
+            max_memory_fraction: None,
             num_segments: None,
             weigher: None,
             eviction_listener: None,
+            eviction_listener_causes: RemovalCauses::default(),
             eviction_policy: EvictionPolicy::default(),
             expiration_policy: ExpirationPolicy::default(),
             housekeeper_config: HousekeeperConfig::default(),
             invalidator_enabled: false,
+            numa_aware: false,
+            segment_selector: None,
+            read_through_siblings: false,
+            loaders: Vec::default(),
+            max_concurrent_loads: None,
+            error_time_to_live: None,
+            interceptors: Vec::default(),
+            policy_config: PolicyConfig::default(),
+            tenant_max_entries: None,
             cache_type: PhantomData,
         }
     }
@@ -99,26 +139,76 @@ where
 
     /// Sets the number of segments of the cache.
     ///
-    /// # Panics
+    /// `num_segments` must not be zero. Otherwise, the `build` method of the
+    /// returned builder will panic, and the `try_build` method will return
+    /// [`BuilderError::ZeroSegments`][zero-segments].
     ///
-    /// Panics if `num_segments` is zero.
+    /// [zero-segments]: ../enum.BuilderError.html#variant.ZeroSegments
     pub fn segments(
         self,
         num_segments: usize,
     ) -> CacheBuilder<K, V, SegmentedCache<K, V, RandomState>> {
-        assert!(num_segments != 0);
-
         CacheBuilder {
             name: self.name,
             max_capacity: self.max_capacity,
             initial_capacity: self.initial_capacity,
+            initial_capacity_load_factor: self.initial_capacity_load_factor,
+            max_memory_fraction: self.max_memory_fraction,
             num_segments: Some(num_segments),
             weigher: self.weigher,
             eviction_policy: self.eviction_policy,
             eviction_listener: self.eviction_listener,
+            eviction_listener_causes: self.eviction_listener_causes,
+            expiration_policy: self.expiration_policy,
+            housekeeper_config: self.housekeeper_config,
+            invalidator_enabled: self.invalidator_enabled,
+            numa_aware: self.numa_aware,
+            segment_selector: self.segment_selector,
+            read_through_siblings: self.read_through_siblings,
+            loaders: self.loaders,
+            max_concurrent_loads: self.max_concurrent_loads,
+            error_time_to_live: self.error_time_to_live,
+            interceptors: self.interceptors,
+            policy_config: self.policy_config,
+            tenant_max_entries: self.tenant_max_entries,
+            cache_type: PhantomData,
+        }
+    }
+
+    /// Sets the number of shards of the cache, and returns a builder of a
+    /// [`LocalShardedCache`][local-sharded-cache].
+    ///
+    /// `num_shards` must not be zero. Otherwise, the `build` method of the
+    /// returned builder will panic.
+    ///
+    /// [local-sharded-cache]: ./struct.LocalShardedCache.html
+    pub fn local_shards(
+        self,
+        num_shards: usize,
+    ) -> CacheBuilder<K, V, LocalShardedCache<K, V, RandomState>> {
+        CacheBuilder {
+            name: self.name,
+            max_capacity: self.max_capacity,
+            initial_capacity: self.initial_capacity,
+            initial_capacity_load_factor: self.initial_capacity_load_factor,
+            max_memory_fraction: self.max_memory_fraction,
+            num_segments: Some(num_shards),
+            weigher: self.weigher,
+            eviction_policy: self.eviction_policy,
+            eviction_listener: self.eviction_listener,
+            eviction_listener_causes: self.eviction_listener_causes,
             expiration_policy: self.expiration_policy,
             housekeeper_config: self.housekeeper_config,
             invalidator_enabled: self.invalidator_enabled,
+            numa_aware: self.numa_aware,
+            segment_selector: self.segment_selector,
+            read_through_siblings: self.read_through_siblings,
+            loaders: self.loaders,
+            max_concurrent_loads: self.max_concurrent_loads,
+            error_time_to_live: self.error_time_to_live,
+            interceptors: self.interceptors,
+            policy_config: self.policy_config,
+            tenant_max_entries: self.tenant_max_entries,
             cache_type: PhantomData,
         }
     }
@@ -137,20 +227,59 @@ where
         let build_hasher = RandomState::default();
         let exp = &self.expiration_policy;
         builder_utils::ensure_expirations_or_panic(exp.time_to_live(), exp.time_to_idle());
+        let max_capacity = self.derived_max_capacity();
+        let tenant_quotas = self.validated_tenant_quotas(max_capacity);
+        let initial_capacity = self.derived_initial_capacity();
+        let load_limiter = self.load_limiter();
         Cache::with_everything(
             self.name,
-            self.max_capacity,
-            self.initial_capacity,
+            max_capacity,
+            initial_capacity,
             build_hasher,
             self.weigher,
             self.eviction_policy,
             self.eviction_listener,
+            self.eviction_listener_causes,
             self.expiration_policy,
             self.housekeeper_config,
             self.invalidator_enabled,
+            LoaderChain::new(self.loaders),
+            load_limiter,
+            self.error_time_to_live,
+            InterceptorChain::new(self.interceptors),
+            PolicyConfig {
+                tenant_quotas,
+                ..self.policy_config
+            },
         )
     }
 
+    /// Builds a `Cache<K, V>` after validating the configuration.
+    ///
+    /// Unlike the `build` method, this method returns an error instead of panicking
+    /// or silently creating a cache with surprising behavior, when the builder has
+    /// an invalid combination of configurations. See [`BuilderError`][builder-error]
+    /// for the rejected configurations.
+    ///
+    /// [builder-error]: ../enum.BuilderError.html
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use moka::{sync::Cache, BuilderError};
+    /// use std::time::Duration;
+    ///
+    /// let result = Cache::<u32, String>::builder()
+    ///     .max_capacity(100)
+    ///     .time_to_live(Duration::ZERO)
+    ///     .try_build();
+    /// assert!(matches!(result, Err(BuilderError::ZeroTimeToLive)));
+    /// ```
+    pub fn try_build(self) -> Result<Cache<K, V, RandomState>, BuilderError> {
+        self.validate()?;
+        Ok(self.build())
+    }
+
     /// Builds a `Cache<K, V, S>` with the given `hasher` of type `S`.
     ///
     /// # Examples
@@ -225,19 +354,60 @@ where
     {
         let exp = &self.expiration_policy;
         builder_utils::ensure_expirations_or_panic(exp.time_to_live(), exp.time_to_idle());
+        let max_capacity = self.derived_max_capacity();
+        let tenant_quotas = self.validated_tenant_quotas(max_capacity);
+        let initial_capacity = self.derived_initial_capacity();
+        let load_limiter = self.load_limiter();
         Cache::with_everything(
             self.name,
-            self.max_capacity,
-            self.initial_capacity,
+            max_capacity,
+            initial_capacity,
             hasher,
             self.weigher,
             self.eviction_policy,
             self.eviction_listener,
+            self.eviction_listener_causes,
             self.expiration_policy,
             self.housekeeper_config,
             self.invalidator_enabled,
+            LoaderChain::new(self.loaders),
+            load_limiter,
+            self.error_time_to_live,
+            InterceptorChain::new(self.interceptors),
+            PolicyConfig {
+                tenant_quotas,
+                ..self.policy_config
+            },
         )
     }
+
+    /// Builds a `Cache<K, V, SeededState<S>>` with the given `hasher` of type `S`
+    /// wrapped in a [`SeededState`][seeded-state] with a random seed.
+    ///
+    /// This gives the cache its own hash function even when `S` always creates
+    /// the same hash function, e.g. `BuildHasherDefault<H>`. To use a
+    /// deterministic seed in tests, call `build_with_hasher` method with
+    /// `SeededState::with_seed` instead.
+    ///
+    /// [seeded-state]: ../struct.SeededState.html
+    pub fn build_with_seeded_hasher<S>(self, hasher: S) -> Cache<K, V, SeededState<S>>
+    where
+        S: BuildHasher + Clone + Send + Sync + 'static,
+    {
+        self.build_with_hasher(SeededState::new(hasher))
+    }
+
+    /// Builds a `Cache<K, V, S>` with the given `hasher` of type `S` after validating
+    /// the configuration.
+    ///
+    /// See the `try_build` method for the validation.
+    pub fn try_build_with_hasher<S>(self, hasher: S) -> Result<Cache<K, V, S>, BuilderError>
+    where
+        S: BuildHasher + Clone + Send + Sync + 'static,
+    {
+        self.validate()?;
+        Ok(self.build_with_hasher(hasher))
+    }
 }
 
 impl<K, V> CacheBuilder<K, V, SegmentedCache<K, V, RandomState>>
@@ -255,25 +425,55 @@ where
     /// Panics if configured with either `time_to_live` or `time_to_idle` higher than
     /// 1000 years. This is done to protect against overflow when computing key
     /// expiration.
+    ///
+    /// Also panics if the number of segments is zero.
     pub fn build(self) -> SegmentedCache<K, V, RandomState> {
         let build_hasher = RandomState::default();
         let exp = &self.expiration_policy;
         builder_utils::ensure_expirations_or_panic(exp.time_to_live(), exp.time_to_idle());
+        let max_capacity = self.derived_max_capacity();
+        let tenant_quotas = self.validated_tenant_quotas(max_capacity);
+        let initial_capacity = self.derived_initial_capacity();
+        let load_limiter = self.load_limiter();
         SegmentedCache::with_everything(
             self.name,
-            self.max_capacity,
-            self.initial_capacity,
+            max_capacity,
+            initial_capacity,
             self.num_segments.unwrap(),
             build_hasher,
             self.weigher,
             self.eviction_policy,
             self.eviction_listener,
+            self.eviction_listener_causes,
             self.expiration_policy,
             self.housekeeper_config,
             self.invalidator_enabled,
+            self.numa_aware,
+            self.segment_selector,
+            LoaderChain::new(self.loaders),
+            load_limiter,
+            self.error_time_to_live,
+            InterceptorChain::new(self.interceptors),
+            PolicyConfig {
+                tenant_quotas,
+                ..self.policy_config
+            },
         )
     }
 
+    /// Builds a `SegmentedCache<K, V>` after validating the configuration.
+    ///
+    /// Unlike the `build` method, this method returns an error instead of panicking
+    /// or silently creating a cache with surprising behavior, when the builder has
+    /// an invalid combination of configurations. See [`BuilderError`][builder-error]
+    /// for the rejected configurations.
+    ///
+    /// [builder-error]: ../enum.BuilderError.html
+    pub fn try_build(self) -> Result<SegmentedCache<K, V, RandomState>, BuilderError> {
+        self.validate()?;
+        Ok(self.build())
+    }
+
     /// Builds a `SegmentedCache<K, V, S>` with the given `hasher`.
     ///
     ///
@@ -349,174 +549,1505 @@ where
     {
         let exp = &self.expiration_policy;
         builder_utils::ensure_expirations_or_panic(exp.time_to_live(), exp.time_to_idle());
+        let max_capacity = self.derived_max_capacity();
+        let tenant_quotas = self.validated_tenant_quotas(max_capacity);
+        let initial_capacity = self.derived_initial_capacity();
+        let load_limiter = self.load_limiter();
         SegmentedCache::with_everything(
             self.name,
-            self.max_capacity,
-            self.initial_capacity,
+            max_capacity,
+            initial_capacity,
             self.num_segments.unwrap(),
             hasher,
             self.weigher,
             self.eviction_policy,
             self.eviction_listener,
+            self.eviction_listener_causes,
             self.expiration_policy,
             self.housekeeper_config,
             self.invalidator_enabled,
+            self.numa_aware,
+            self.segment_selector,
+            LoaderChain::new(self.loaders),
+            load_limiter,
+            self.error_time_to_live,
+            InterceptorChain::new(self.interceptors),
+            PolicyConfig {
+                tenant_quotas,
+                ..self.policy_config
+            },
         )
     }
-}
 
-impl<K, V, C> CacheBuilder<K, V, C> {
-    /// Sets the name of the cache. Currently the name is used for identification
-    /// only in logging messages.
-    pub fn name(self, name: &str) -> Self {
+    /// Builds a `SegmentedCache<K, V, SeededState<S>>` with the given `hasher` of type `S`
+    /// wrapped in a [`SeededState`][seeded-state] with a random seed.
+    ///
+    /// This gives the cache its own hash function even when `S` always creates
+    /// the same hash function, e.g. `BuildHasherDefault<H>`. To use a
+    /// deterministic seed in tests, call `build_with_hasher` method with
+    /// `SeededState::with_seed` instead.
+    ///
+    /// [seeded-state]: ../struct.SeededState.html
+    pub fn build_with_seeded_hasher<S>(self, hasher: S) -> SegmentedCache<K, V, SeededState<S>>
+    where
+        S: BuildHasher + Clone + Send + Sync + 'static,
+    {
+        self.build_with_hasher(SeededState::new(hasher))
+    }
+
+    /// Builds a `SegmentedCache<K, V, S>` with the given `hasher` of type `S` after validating
+    /// the configuration.
+    ///
+    /// See the `try_build` method for the validation.
+    pub fn try_build_with_hasher<S>(
+        self,
+        hasher: S,
+    ) -> Result<SegmentedCache<K, V, S>, BuilderError>
+    where
+        S: BuildHasher + Clone + Send + Sync + 'static,
+    {
+        self.validate()?;
+        Ok(self.build_with_hasher(hasher))
+    }
+
+    /// Enables the NUMA-aware mode of the cache.
+    ///
+    /// When enabled and the machine has more than one NUMA node, the segments are
+    /// assigned to the nodes in round-robin order. Each segment is created on a
+    /// thread pinned to its node so that its internal data structures are allocated
+    /// in the node-local memory, and `run_pending_tasks` processes each segment on a
+    /// thread pinned to its node. The cache keeps one such worker thread per node
+    /// until it is dropped.
+    ///
+    /// Maintenance tasks that are triggered by cache reads and writes still run on
+    /// the calling thread.
+    ///
+    /// This has no effect on a machine with only one NUMA node, or on a platform
+    /// other than Linux.
+    #[cfg(feature = "numa")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "numa")))]
+    pub fn numa_aware(self) -> Self {
         Self {
-            name: Some(name.to_string()),
+            numa_aware: true,
             ..self
         }
     }
 
-    /// Sets the max capacity of the cache.
-    pub fn max_capacity(self, max_capacity: u64) -> Self {
+    /// Sets a closure to select the segment of each key, instead of selecting it
+    /// from the hash of the key.
+    ///
+    /// The closure returns an arbitrary number, and the key is stored in the segment
+    /// at that number modulo the number of the segments. This lets related keys,
+    /// e.g. the keys of the same tenant, land in the same segment, so they share the
+    /// same eviction and housekeeping. Note that the max capacity is evenly divided
+    /// among the segments, so a segment having many keys will evict more entries
+    /// than others.
+    ///
+    /// The closure must return the same number for the same key. Methods taking a
+    /// borrowed key (e.g. `get` and `invalidate`) cannot call the closure, so they
+    /// look for the segment containing the key, which costs a lookup per segment.
+    /// The `by_ref` loading methods (e.g. `get_with_by_ref`) create an owned key to
+    /// call the closure.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use moka::sync::SegmentedCache;
+    ///
+    /// // Keys are (tenant ID, item ID). Keep the keys of a tenant together.
+    /// let cache = SegmentedCache::builder(4)
+    ///     .max_capacity(100)
+    ///     .segment_selector(|&(tenant, _item): &(u32, u32)| tenant as usize)
+    ///     .build();
+    ///
+    /// cache.insert((1, 10), "a");
+    /// cache.insert((1, 20), "b");
+    /// cache.insert((2, 10), "c");
+    /// assert_eq!(cache.get(&(1, 20)), Some("b"));
+    /// ```
+    pub fn segment_selector(self, selector: impl Fn(&K) -> usize + Send + Sync + 'static) -> Self {
         Self {
-            max_capacity: Some(max_capacity),
+            segment_selector: Some(Arc::new(selector)),
             ..self
         }
     }
+}
+
+impl<K, V> CacheBuilder<K, V, LocalShardedCache<K, V, RandomState>>
+where
+    K: Eq + Hash + Send + Sync + 'static,
+    V: Clone + Send + Sync + 'static,
+{
+    /// Builds a `LocalShardedCache<K, V>`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if configured with either `time_to_live` or `time_to_idle` higher than
+    /// 1000 years. This is done to protect against overflow when computing key
+    /// expiration.
+    ///
+    /// Also panics if the number of shards is zero.
+    pub fn build(self) -> LocalShardedCache<K, V, RandomState> {
+        self.build_with_hasher(RandomState::default())
+    }
 
-    /// Sets the initial capacity (number of entries) of the cache.
-    pub fn initial_capacity(self, number_of_entries: usize) -> Self {
+    /// Builds a `LocalShardedCache<K, V, S>` with the given `hasher`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if configured with either `time_to_live` or `time_to_idle` higher than
+    /// 1000 years. This is done to protect against overflow when computing key
+    /// expiration.
+    ///
+    /// Also panics if the number of shards is zero.
+    pub fn build_with_hasher<S>(self, hasher: S) -> LocalShardedCache<K, V, S>
+    where
+        S: BuildHasher + Clone + Send + Sync + 'static,
+    {
+        let exp = &self.expiration_policy;
+        builder_utils::ensure_expirations_or_panic(exp.time_to_live(), exp.time_to_idle());
+        let max_capacity = self.derived_max_capacity();
+        let tenant_quotas = self.validated_tenant_quotas(max_capacity);
+        let initial_capacity = self.derived_initial_capacity();
+        let load_limiter = self.load_limiter();
+        LocalShardedCache::with_everything(
+            self.name,
+            max_capacity,
+            initial_capacity,
+            self.num_segments.unwrap(),
+            hasher,
+            self.weigher,
+            self.eviction_policy,
+            self.eviction_listener,
+            self.eviction_listener_causes,
+            self.expiration_policy,
+            self.housekeeper_config,
+            self.invalidator_enabled,
+            self.read_through_siblings,
+            LoaderChain::new(self.loaders),
+            load_limiter,
+            self.error_time_to_live,
+            InterceptorChain::new(self.interceptors),
+            PolicyConfig {
+                tenant_quotas,
+                ..self.policy_config
+            },
+        )
+    }
+
+    /// Makes a miss in the shard of the current thread fall back to the other
+    /// shards. When a value is found in another shard, it is copied to the shard of
+    /// the current thread.
+    pub fn read_through_siblings(self) -> Self {
         Self {
-            initial_capacity: Some(number_of_entries),
+            read_through_siblings: true,
             ..self
         }
     }
+}
 
-    /// Sets the eviction (and admission) policy of the cache.
-    ///
-    /// The default policy is TinyLFU. See [`EvictionPolicy`][eviction-policy] for
-    /// more details.
-    ///
-    /// [eviction-policy]: ../policy/struct.EvictionPolicy.html
-    pub fn eviction_policy(self, policy: EvictionPolicy) -> Self {
+impl<K, V, C> CacheBuilder<K, V, C> {
+    /// Sets the name of the cache. Currently the name is used for identification
+    /// only in logging messages.
+    pub fn name(self, name: &str) -> Self {
         Self {
-            eviction_policy: policy,
+            name: Some(name.to_string()),
             ..self
         }
     }
 
-    /// Sets the weigher closure to the cache.
+    /// Sets the max capacity of the cache.
     ///
-    /// The closure should take `&K` and `&V` as the arguments and returns a `u32`
-    /// representing the relative size of the entry.
-    pub fn weigher(self, weigher: impl Fn(&K, &V) -> u32 + Send + Sync + 'static) -> Self {
+    /// A max capacity of zero disables caching. Inserted entries are dropped
+    /// immediately without being stored, and the eviction listener is notified
+    /// of them with the [`Size`][removal-cause-size] cause. All lookups miss.
+    /// This can be used to turn off caching at runtime without changing the call
+    /// sites.
+    ///
+    /// [removal-cause-size]: ../notification/enum.RemovalCause.html#variant.Size
+    pub fn max_capacity(self, max_capacity: u64) -> Self {
         Self {
-            weigher: Some(Arc::new(weigher)),
+            max_capacity: Some(max_capacity),
             ..self
         }
     }
 
-    /// Sets the eviction listener closure to the cache.
+    /// Sets the max capacity of the cache to the `fraction` of the memory available
+    /// to the process, in bytes.
     ///
-    /// The closure should take `Arc<K>`, `V` and [`RemovalCause`][removal-cause] as
-    /// the arguments.
+    /// The memory is detected when the cache is built. It is the total physical
+    /// memory of the machine, or the memory limit of the cgroup of the process
+    /// (e.g. the memory limit of a container) if it is smaller. This replaces the
+    /// max capacity set by the `max_capacity` method.
     ///
-    /// # Panics
+    /// If the memory could not be detected (currently, it is detected only on
+    /// Linux), the max capacity set by the `max_capacity` method is used instead.
+    /// Set it to a capacity in bytes to build the cache on any platform.
     ///
-    /// It is very important to make the listener closure not to panic. Otherwise,
-    /// the cache will stop calling the listener after a panic. This is an intended
-    /// behavior because the cache cannot know whether it is memory safe or not to
-    /// call the panicked listener again.
+    /// A [`weigher`](#method.weigher) returning the approximate size of each entry
+    /// in bytes must be set. `fraction` must be greater than `0.0` and not greater
+    /// than `1.0`. Otherwise, or if the memory could not be detected and the
+    /// `max_capacity` is not set, the `build` method will panic, and the
+    /// `try_build` method will return a [`BuilderError`][builder-error].
     ///
-    /// [removal-cause]: ../notification/enum.RemovalCause.html
-    pub fn eviction_listener(
-        self,
-        listener: impl Fn(Arc<K>, V, RemovalCause) + Send + Sync + 'static,
-    ) -> Self {
+    /// [builder-error]: ../enum.BuilderError.html
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use moka::sync::Cache;
+    ///
+    /// let result = Cache::builder()
+    ///     .weigher(|k: &String, v: &Vec<u8>| (k.len() + v.len()).try_into().unwrap_or(u32::MAX))
+    ///     // Use up to a quarter of the memory, or 64 MiB if the memory could not
+    ///     // be detected.
+    ///     .max_memory_fraction(0.25)
+    ///     .max_capacity(64 * 1024 * 1024)
+    ///     .try_build();
+    ///
+    /// let cache = result.expect("Invalid configuration");
+    /// // The max capacity is the number of bytes.
+    /// println!("{:?}", cache.policy().max_capacity());
+    /// ```
+    pub fn max_memory_fraction(self, fraction: f64) -> Self {
         Self {
-            eviction_listener: Some(Arc::new(listener)),
+            max_memory_fraction: Some(fraction),
             ..self
         }
     }
 
-    /// Sets the time to live of the cache.
+    /// Makes the cache watch the memory pressure, and temporarily shed the
+    /// `fraction` of its max capacity while the memory is under pressure.
     ///
-    /// A cached entry will be expired after the specified duration past from
-    /// `insert`.
+    /// The cache checks the memory pressure stall information (PSI) of the cgroup
+    /// of the process (or of the whole system) at most once a second while
+    /// performing the pending maintenance tasks. When some tasks have been stalled
+    /// on memory for more than 10% of the time in the last 10 seconds, the cache
+    /// shrinks its effective capacity and evicts entries down to it, as
+    /// [`Cache::shed`][cache-shed] does. When the pressure goes, the capacity is
+    /// restored.
     ///
-    /// # Panics
+    /// `fraction` is clamped to the range of `[0.0, 1.0]`. The memory pressure is
+    /// available only on Linux 4.20 or newer with PSI enabled. Otherwise, this has
+    /// no effect, and the application can still call `Cache::shed` on its own
+    /// signal.
     ///
-    /// `CacheBuilder::build*` methods will panic if the given `duration` is longer
-    /// than 1000 years. This is done to protect against overflow when computing key
-    /// expiration.
-    pub fn time_to_live(self, duration: Duration) -> Self {
+    /// [cache-shed]: ./struct.Cache.html#method.shed
+    pub fn shed_on_memory_pressure(self, fraction: f64) -> Self {
         let mut builder = self;
-        builder.expiration_policy.set_time_to_live(duration);
+        builder.policy_config.memory_pressure_shed_fraction = Some(fraction);
         builder
     }
 
-    /// Sets the time to idle of the cache.
+    /// Makes the pending maintenance tasks shrink the cache first while it is over
+    /// capacity, e.g. after [`Cache::shed`][cache-shed] or on the memory pressure.
     ///
-    /// A cached entry will be expired after the specified duration past from `get`
-    /// or `insert`.
+    /// By default, the tasks apply the logged reads and writes (which may admit new
+    /// entries) before removing the expired, invalidated and excess entries. With
+    /// this option, the removals come first while the cache is over capacity, so
+    /// that the memory is released faster during an incident. If `pause_reads` is
+    /// `true`, the logged reads are not applied at all until the cache is back
+    /// within its capacity. Some of the reads may be dropped in the meantime, and
+    /// the eviction policy will not reflect them.
     ///
-    /// # Panics
+    /// [cache-shed]: ./struct.Cache.html#method.shed
+    pub fn shrink_first_when_over_capacity(self, pause_reads: bool) -> Self {
+        let mut builder = self;
+        builder.policy_config.shrink_first = true;
+        builder.policy_config.pause_reads_while_shrinking = pause_reads;
+        builder
+    }
+
+    /// Makes the cache count the reads and the writes of each entry. The counts
+    /// are returned by the [`EntryMetadata`][metadata-struct] of the `Entry`
+    /// returned by [`Cache::get_entry`][cache-get-entry], so that the application
+    /// can make its own LFU-like decisions, or find out why an entry looks cold.
     ///
-    /// `CacheBuilder::build*` methods will panic if the given `duration` is longer
-    /// than 1000 years. This is done to protect against overflow when computing key
-    /// expiration.
-    pub fn time_to_idle(self, duration: Duration) -> Self {
+    /// The counts are stored as `u32` and saturate at `u32::MAX`.
+    ///
+    /// Requires the `entry-stats` crate feature.
+    ///
+    /// [metadata-struct]: ../struct.EntryMetadata.html
+    /// [cache-get-entry]: ./struct.Cache.html#method.get_entry
+    #[cfg(feature = "entry-stats")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "entry-stats")))]
+    pub fn track_entry_counters(self) -> Self {
         let mut builder = self;
-        builder.expiration_policy.set_time_to_idle(duration);
+        builder.policy_config.entry_counters = true;
         builder
     }
 
-    /// Sets the given `expiry` to the cache.
+    /// Makes the cache record how long the entries lived until they were removed,
+    /// and whether they were ever read, into a histogram. The histogram is
+    /// returned by the [`entry_lifetime_histogram`][histogram-method] method of the
+    /// cache, to find out from the production traffic whether the time-to-live is
+    /// longer than useful.
     ///
-    /// See [the example][per-entry-expiration-example] for per-entry expiration
-    /// policy in the `Cache` documentation.
+    /// The lifetimes are recorded when the pending tasks remove the entries. The
+    /// entries replaced by an update are not recorded, as the lifetime spans the
+    /// updates of the value. This also enables counting the reads of each entry,
+    /// as [`track_entry_counters`](#method.track_entry_counters) does, but without
+    /// returning the counts in the entry metadata.
     ///
-    /// [per-entry-expiration-example]:
-    ///     ./struct.Cache.html#per-entry-expiration-policy
-    pub fn expire_after(self, expiry: impl Expiry<K, V> + Send + Sync + 'static) -> Self {
+    /// Requires the `entry-stats` crate feature.
+    ///
+    /// [histogram-method]: ./struct.Cache.html#method.entry_lifetime_histogram
+    #[cfg(feature = "entry-stats")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "entry-stats")))]
+    pub fn track_entry_lifetimes(self) -> Self {
         let mut builder = self;
-        builder.expiration_policy.set_expiry(Arc::new(expiry));
+        builder.policy_config.entry_lifetimes = true;
         builder
     }
 
-    #[cfg(test)]
-    pub(crate) fn housekeeper_config(self, conf: HousekeeperConfig) -> Self {
-        Self {
-            housekeeper_config: conf,
-            ..self
-        }
+    /// Makes the cache record the in-flight `init` closures of the `get_with`
+    /// family methods, so that [`Cache::load_progress`][load-progress] can return
+    /// their progress. Without this, `load_progress` returns only the progress of
+    /// the in-flight [`refresh`][refresh]es, and only when a loader is registered.
+    ///
+    /// Recording a load adds an insert and a remove on a shared map to every
+    /// evaluation of an `init` closure.
+    ///
+    /// [load-progress]: ./struct.Cache.html#method.load_progress
+    /// [refresh]: ./struct.Cache.html#method.refresh
+    pub fn track_load_progress(self) -> Self {
+        let mut builder = self;
+        builder.policy_config.load_progress = true;
+        builder
     }
 
-    /// Enables support for [`Cache::invalidate_entries_if`][cache-invalidate-if]
-    /// method.
+    /// Makes the cache sample the reads to record while its read log is under
+    /// pressure.
     ///
-    /// The cache will maintain additional internal data structures to support
-    /// `invalidate_entries_if` method.
+    /// The reads are recorded to a bounded log, which is applied to the eviction
+    /// policy by the pending tasks. By default, when the log is full, the reads
+    /// coming after are dropped, which skews the frequencies toward the keys read
+    /// before. With this option, the cache records one of every `N` reads instead,
+    /// so the frequencies of all keys are scaled down evenly. `N` is adapted while
+    /// the pending tasks are processed: doubled up to 64 while the log is more than
+    /// three-quarters full, and halved down to 1 while it is less than a quarter
+    /// full. The state is returned by the
+    /// [`read_sampling_stats`][read-sampling-stats] method of the cache.
     ///
-    /// [cache-invalidate-if]: ./struct.Cache.html#method.invalidate_entries_if
-    pub fn support_invalidation_closures(self) -> Self {
-        Self {
-            invalidator_enabled: true,
-            ..self
-        }
+    /// The hits counted by the hit ratio analyzer and the class stats are sampled
+    /// in the same way.
+    ///
+    /// [read-sampling-stats]: ./struct.Cache.html#method.read_sampling_stats
+    pub fn sample_reads_under_pressure(self) -> Self {
+        let mut builder = self;
+        builder.policy_config.read_sampling = true;
+        builder
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::CacheBuilder;
+    /// Splits the frequency sketch of the cache into segments, each guarded by its
+    /// own lock.
+    ///
+    /// The frequency sketch estimates how often the keys are read, and is updated
+    /// by the pending tasks for every recorded read. By default, it is a single
+    /// table behind a single lock. With this option, it is split in the same way as
+    /// the internal hash table, and a key is counted only in the segment of its
+    /// hash, so the updates and lookups on different segments do not contend with
+    /// each other on many-core machines.
+    ///
+    /// Each segment is sized to its share of the max capacity and aged on its own,
+    /// with a minimum size per segment. So a small cache uses a larger sketch in
+    /// total than without this option.
+    pub fn segment_frequency_sketch(self) -> Self {
+        let mut builder = self;
+        builder.policy_config.segmented_frequency_sketch = true;
+        builder
+    }
 
-    use std::time::Duration;
+    /// Sets the maximum time that a single run of the pending tasks may spend.
+    ///
+    /// The pending tasks, such as applying the read and write logs and evicting
+    /// the entries, are run inline by the cache operations from time to time. A
+    /// run normally continues until there is nothing left to do, so an operation
+    /// that triggers it after a burst of writes or a mass expiration may take a
+    /// while. With a budget, the run stops at the first check point after the
+    /// budget is spent, and the cache operations that follow continue the work
+    /// left over in small runs.
+    ///
+    /// The budget is checked after each round of the run, which applies the
+    /// logs and evicts a batch of entries, so a run may exceed it by up to one
+    /// round. If an eviction listener is set, its timeout (100 milliseconds) is
+    /// also applied, and the shorter of the two is used.
+    pub fn maintenance_time_budget(self, budget: Duration) -> Self {
+        let mut builder = self;
+        builder.housekeeper_config.maintenance_time_budget = Some(budget);
+        builder
+    }
 
-    #[test]
-    fn build_cache() {
-        // Cache<char, String>
-        let cache = CacheBuilder::new(100).build();
-        let policy = cache.policy();
+    /// Disables recording the stats returned by the [`stats`][stats-method]
+    /// method of the cache.
+    ///
+    /// The stats are recorded by default. The counters are striped by the calling
+    /// thread to keep the cost on the read path low, but this option removes it
+    /// for the users who do not need the stats.
+    ///
+    /// [stats-method]: ./struct.Cache.html#method.stats
+    pub fn disable_stats(self) -> Self {
+        let mut builder = self;
+        builder.policy_config.stats_disabled = true;
+        builder
+    }
+
+    /// Sets the [`StatsCounter`][stats-counter] to record the hit, miss, eviction
+    /// and load events of the cache to, instead of the built-in
+    /// [`DefaultStatsCounter`][default-counter].
+    ///
+    /// Use it to forward the events to a metrics backend. The
+    /// [`stats`][stats-method] method of the cache returns what the
+    /// `snapshot` method of the counter returns. A `SegmentedCache` records the
+    /// events of all segments to the same counter. This option has no effect if
+    /// [`disable_stats`][disable-stats] is also called.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use moka::sync::{Cache, StatsCounter};
+    /// use std::sync::atomic::{AtomicU64, Ordering};
+    ///
+    /// #[derive(Default)]
+    /// struct Misses(AtomicU64);
+    ///
+    /// impl StatsCounter for Misses {
+    ///     fn record_hit(&self) {}
+    ///     fn record_miss(&self) {
+    ///         // Forward the event to a metrics backend here.
+    ///         self.0.fetch_add(1, Ordering::Relaxed);
+    ///     }
+    ///     fn record_evictions(&self, _count: u64) {}
+    ///     fn record_load_success(&self) {}
+    ///     fn record_load_failure(&self) {}
+    /// }
+    ///
+    /// let cache: Cache<u32, u32> = Cache::builder()
+    ///     .stats_counter(Misses::default())
+    ///     .build();
+    /// assert_eq!(cache.get(&1), None);
+    /// // The counter forwards the events only, so the snapshot is all zeros.
+    /// assert_eq!(cache.stats().unwrap().miss_count(), 0);
+    /// ```
+    ///
+    /// [stats-counter]: ./trait.StatsCounter.html
+    /// [default-counter]: ./struct.DefaultStatsCounter.html
+    /// [stats-method]: ./struct.Cache.html#method.stats
+    /// [disable-stats]: #method.disable_stats
+    pub fn stats_counter(self, counter: impl StatsCounter + 'static) -> Self {
+        let mut builder = self;
+        builder.policy_config.stats_counter = Some(Arc::new(counter));
+        builder
+    }
+
+    /// Removes the expired entries eagerly, by running the pending tasks from a
+    /// background thread at the given interval.
+    ///
+    /// By default, the expired entries are never returned, but they are removed
+    /// only when the pending tasks are run, which is triggered by the cache
+    /// operations. So an idle cache may keep an expired entry (e.g. a revoked
+    /// token) in memory, and notify the eviction listener of it, long after its
+    /// expiration. With this option, an expired entry is removed within about the
+    /// given interval after its expiration, even while the cache is idle. An
+    /// interval of one second is a good starting point.
+    ///
+    /// This option has effect only when the time-to-live, the time-to-idle or the
+    /// [`expire_after`][expire-after-method] is set. The thread is named
+    /// `moka-expirer`, and exits after the cache is dropped. A `SegmentedCache`
+    /// spawns one thread for each segment.
+    ///
+    /// [expire-after-method]: #method.expire_after
+    pub fn eager_expiration(self, interval: Duration) -> Self {
+        let mut builder = self;
+        builder.housekeeper_config.eager_expiration_interval = Some(interval);
+        builder
+    }
+
+    /// Enables the hit ratio analyzer, which estimates what the hit ratio would be
+    /// at 0.5x, 1x, 2x and 4x of the max capacity. The estimates are returned by the
+    /// `hit_ratio_report` method of the cache, so the capacity can be planned on
+    /// the measured benefit of a larger or smaller cache.
+    ///
+    /// The analyzer simulates an LRU cache over a sample of the read keys (a ghost
+    /// cache) while the pending tasks are processed. It tracks at most about a
+    /// thousand keys regardless of the capacity. It has no effect when the max
+    /// capacity is not set.
+    pub fn analyze_hit_ratio(self) -> Self {
+        let mut builder = self;
+        builder.policy_config.hit_ratio_analyzer = true;
+        builder
+    }
+
+    /// Makes the cache remember the hashes of the keys recently evicted by its size
+    /// constraint (a ghost list), as many as the max capacity up to 65,536.
+    ///
+    /// The cache can then tell whether a key was recently evicted by the
+    /// `was_recently_evicted` method, and count the misses on the recently evicted
+    /// keys, returned by the `ghost_list_stats` method. Many such misses mean that
+    /// the cache is undersized, rather than the workload not repeating the keys.
+    /// It has no effect when the max capacity is not set.
+    pub fn track_recent_evictions(self) -> Self {
+        let mut builder = self;
+        builder.policy_config.ghost_list = true;
+        builder
+    }
+
+    /// Makes the cache count the hits, the misses and the evictions of each class
+    /// of the keys, given by the `classifier` closure. The counters are returned by
+    /// the [`class_stats`][class-stats] method of the cache, so that a cache
+    /// holding several types of objects can show which type benefits from it.
+    ///
+    /// Like the [cache stats][stats], the hits and the misses are counted by the
+    /// reads, so the closure is called on every read and should return quickly. The
+    /// evictions are counted when the pending tasks are processed. See
+    /// [`ClassStats`][class-stats-struct] for which misses are counted.
+    ///
+    /// [stats]: ./struct.Cache.html#method.stats
+    ///
+    /// [class-stats]: ./struct.Cache.html#method.class_stats
+    /// [class-stats-struct]: ./struct.ClassStats.html
+    pub fn class_stats(
+        self,
+        classifier: impl Fn(&K) -> &'static str + Send + Sync + 'static,
+    ) -> Self {
+        let mut builder = self;
+        builder.policy_config.key_classifier = Some(Arc::new(classifier));
+        builder
+    }
+
+    /// Gives a second chance to the keys that return soon after they were evicted
+    /// by the size constraint.
+    ///
+    /// The TinyLFU admission policy of the cache admits a new entry only when it is
+    /// read more often than the entries to evict. When the working set is slightly
+    /// larger than the cache, the entries tend to be evicted and rejected in turn.
+    /// With this option, the cache remembers the recently evicted keys as
+    /// [`track_recent_evictions`](#method.track_recent_evictions) does, and boosts
+    /// the frequency of such a key when it is inserted again, to reduce the
+    /// thrashing.
+    ///
+    /// It has no effect when the max capacity is not set, or the eviction policy is
+    /// LRU.
+    pub fn second_chance_readmission(self) -> Self {
+        let mut builder = self;
+        builder.policy_config.second_chance_readmission = true;
+        builder
+    }
+
+    /// Sets the strategy of a writer to wait for a room in the write log of the
+    /// cache when the log is full. The default strategy sleeps 50 microseconds
+    /// between the retries.
+    ///
+    /// See [`WriteRetryStrategy`][write-retry-strategy] for the available
+    /// strategies.
+    ///
+    /// [write-retry-strategy]: ../policy/struct.WriteRetryStrategy.html
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use moka::{policy::WriteRetryStrategy, sync::Cache};
+    /// use std::time::Duration;
+    ///
+    /// let cache: Cache<u32, u32> = Cache::builder()
+    ///     .max_capacity(100)
+    ///     .write_retry_strategy(WriteRetryStrategy::exponential_backoff(
+    ///         Duration::from_micros(10),
+    ///         Duration::from_millis(1),
+    ///     ))
+    ///     .build();
+    /// ```
+    pub fn write_retry_strategy(self, strategy: WriteRetryStrategy) -> Self {
+        let mut builder = self;
+        builder.housekeeper_config.write_retry_strategy = strategy;
+        builder
+    }
+
+    /// Sets the initial capacity (number of entries) of the cache.
+    pub fn initial_capacity(self, number_of_entries: usize) -> Self {
+        Self {
+            initial_capacity: Some(number_of_entries),
+            ..self
+        }
+    }
+
+    /// Derives the initial capacity of the cache from the max capacity, when the
+    /// initial capacity is not set by the `initial_capacity` method.
+    ///
+    /// The cache will be pre-sized to hold `max_capacity * load_factor` entries
+    /// so that a large cache does not have to grow its internal hash table many
+    /// times while it is warming up. The table of the historic popularity
+    /// estimator is also allocated up front.
+    ///
+    /// `load_factor` must be greater than `0.0` and not greater than `1.0`.
+    /// Otherwise, the `build` method will panic, and the `try_build` method will
+    /// return [`BuilderError::InvalidLoadFactor`][invalid-load-factor].
+    ///
+    /// This has no effect if the max capacity is not set, or a weigher is set as
+    /// the max capacity is not a number of entries in that case.
+    ///
+    /// [invalid-load-factor]: ../enum.BuilderError.html#variant.InvalidLoadFactor
+    pub fn auto_initial_capacity(self, load_factor: f64) -> Self {
+        Self {
+            initial_capacity_load_factor: Some(load_factor),
+            ..self
+        }
+    }
+
+    /// Sets the eviction (and admission) policy of the cache.
+    ///
+    /// The default policy is TinyLFU. See [`EvictionPolicy`][eviction-policy] for
+    /// more details.
+    ///
+    /// [eviction-policy]: ../policy/struct.EvictionPolicy.html
+    pub fn eviction_policy(self, policy: EvictionPolicy) -> Self {
+        Self {
+            eviction_policy: policy,
+            ..self
+        }
+    }
+
+    /// Sets the victim selector closure to the cache.
+    ///
+    /// When the TinyLFU policy selects the entries to evict to admit a new entry,
+    /// or when the cache evicts the least recently used entries to get back within
+    /// its capacity, the closure is called with the key and the value of each
+    /// candidate. It returns a [`VictimDecision`][victim-decision] to allow the
+    /// eviction, to keep the entry for now, or to defer it after the other entries.
+    /// This gives targeted control over the evictions, e.g. to protect the entries
+    /// that are being used, without replacing the whole policy.
+    ///
+    /// If the closure keeps or defers several candidates in a row, the new entry
+    /// will not be admitted. When evicting to get back within the capacity, up to
+    /// the eviction batch size of candidates are offered at each run of the
+    /// pending tasks, so the cache may stay over its capacity while the closure
+    /// keeps the entries.
+    ///
+    /// The closure is called by the housekeeper while it holds the lock of the
+    /// eviction order, so it should return quickly. It must not call the methods
+    /// of the same cache that run the pending tasks or iterate over the entries,
+    /// such as `run_pending_tasks` and `iter_by_recency`, as they would wait for
+    /// that lock forever.
+    ///
+    /// It has no effect on the evictions over a tenant quota.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use moka::sync::{Cache, VictimDecision};
+    ///
+    /// let cache = Cache::builder()
+    ///     .max_capacity(100)
+    ///     .victim_selector(|key: &String, _value: &String| {
+    ///         if key.starts_with("session/") {
+    ///             VictimDecision::Defer
+    ///         } else {
+    ///             VictimDecision::Evict
+    ///         }
+    ///     })
+    ///     .build();
+    /// # cache.insert("session/a".to_string(), "alice".to_string());
+    /// ```
+    ///
+    /// [victim-decision]: ./enum.VictimDecision.html
+    pub fn victim_selector(
+        self,
+        selector: impl Fn(&K, &V) -> VictimDecision + Send + Sync + 'static,
+    ) -> Self {
+        let mut builder = self;
+        builder.policy_config.victim_selector = Some(Arc::new(selector));
+        builder
+    }
+
+    /// Sets the closure to be called once for an entry when its key becomes hot.
+    ///
+    /// The key is hot when the estimated frequency of its reads reaches the
+    /// `threshold`. The frequency is estimated by the same count-min sketch as
+    /// the TinyLFU policy, which counts up to 15 and halves the counts
+    /// periodically, so `threshold` is clamped to the range of `1..=15`. The
+    /// closure is called with the key and the value of the entry the first time
+    /// it is hit at or over the threshold, e.g. to push the newly hot object to a
+    /// CDN or to warm up the peers. It is not called again for the same entry, but
+    /// may be called again if the key is removed and inserted back.
+    ///
+    /// The closure is called by the housekeeper while it applies the logged
+    /// reads, so it should return quickly. The frequencies are estimated only
+    /// after the cache has been filled up to the half of its max capacity, and
+    /// not at all if the max capacity is not set.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use moka::sync::Cache;
+    /// use std::sync::Arc;
+    ///
+    /// let cache = Cache::builder()
+    ///     .max_capacity(100)
+    ///     .on_hot_key(8, |key: &Arc<String>, _value: &Vec<u8>| {
+    ///         println!("{key} is hot");
+    ///     })
+    ///     .build();
+    /// # cache.insert("a".to_string(), vec![]);
+    /// ```
+    pub fn on_hot_key(
+        self,
+        threshold: u8,
+        listener: impl Fn(&Arc<K>, &V) + Send + Sync + 'static,
+    ) -> Self {
+        let mut builder = self;
+        builder.policy_config.hot_keys = Some(HotKeyConfig::new(threshold, Arc::new(listener)));
+        builder
+    }
+
+    /// Sets the weigher closure to the cache.
+    ///
+    /// The closure should take `&K` and `&V` as the arguments and returns a `u32`
+    /// representing the relative size of the entry.
+    pub fn weigher(self, weigher: impl Fn(&K, &V) -> u32 + Send + Sync + 'static) -> Self {
+        self.weigher_with_context(move |k, v, _ctx| weigher(k, v))
+    }
+
+    /// Sets the weigher closure receiving the context of the insertion to the
+    /// cache.
+    ///
+    /// The closure should take `&K`, `&V` and [`&WeighContext`][weigh-context] as
+    /// the arguments and returns a `u32` representing the relative size of the
+    /// entry. When the value replaces an existing entry, the context carries the
+    /// weight of the replaced entry, so the weight of a large value can be
+    /// adjusted from the old one instead of being measured from scratch.
+    ///
+    /// [weigh-context]: ../weigher/struct.WeighContext.html
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use moka::{sync::Cache, weigher::WeighContext};
+    ///
+    /// let cache = Cache::builder()
+    ///     .max_capacity(100)
+    ///     .weigher_with_context(|_k: &u32, v: &Vec<String>, ctx: &WeighContext| {
+    ///         match ctx.old_weight() {
+    ///             // Each update appends one line to the value, so only the last
+    ///             // line is measured.
+    ///             Some(old) => old + v.last().map_or(0, |l| l.len() as u32),
+    ///             None => v.iter().map(|l| l.len() as u32).sum(),
+    ///         }
+    ///     })
+    ///     .build();
+    ///
+    /// let mut lines = vec!["hello".to_string()];
+    /// cache.insert(0, lines.clone());
+    /// lines.push("world!".to_string());
+    /// cache.insert(0, lines);
+    /// cache.run_pending_tasks();
+    /// assert_eq!(cache.weighted_size(), 11);
+    /// ```
+    pub fn weigher_with_context(
+        self,
+        weigher: impl Fn(&K, &V, &WeighContext) -> u32 + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            weigher: Some(Arc::new(weigher)),
+            ..self
+        }
+    }
+
+    /// Sets the [`byte_len`][byte-len-fn] weigher to the cache, so that each entry
+    /// is weighed by the length of its value in bytes. Combined with
+    /// `max_capacity`, it bounds the cache by the total bytes of the values.
+    ///
+    /// [byte-len-fn]: ../weigher/fn.byte_len.html
+    pub fn weigh_by_byte_len(self) -> Self
+    where
+        K: 'static,
+        V: ByteLength + 'static,
+    {
+        self.weigher(byte_len)
+    }
+
+    /// Sets the eviction listener closure to the cache.
+    ///
+    /// The closure should take `Arc<K>`, `V` and [`RemovalCause`][removal-cause] as
+    /// the arguments.
+    ///
+    /// # Panics
+    ///
+    /// It is very important to make the listener closure not to panic. Otherwise,
+    /// the cache will stop calling the listener after a panic. This is an intended
+    /// behavior because the cache cannot know whether it is memory safe or not to
+    /// call the panicked listener again.
+    ///
+    /// [removal-cause]: ../notification/enum.RemovalCause.html
+    pub fn eviction_listener(
+        self,
+        listener: impl Fn(Arc<K>, V, RemovalCause) + Send + Sync + 'static,
+    ) -> Self {
+        self.eviction_listener_with_metadata(move |k, v, cause, _metadata| listener(k, v, cause))
+    }
+
+    /// Sets the eviction listener closure to the cache, which is also given the
+    /// [`RemovalMetadata`][removal-metadata] of the removed entry, such as the time
+    /// it was last modified and accessed and its weight.
+    ///
+    /// This is useful to log the lifecycle of the entries, or to reconcile the
+    /// state of an external system. Otherwise, it works the same as the
+    /// [`eviction_listener`](#method.eviction_listener) method; the listener set
+    /// by the last call of either method is used.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use moka::sync::Cache;
+    ///
+    /// let cache = Cache::builder()
+    ///     .max_capacity(100)
+    ///     .eviction_listener_with_metadata(|key, _value, cause, metadata| {
+    ///         println!(
+    ///             "{key} was removed ({cause:?}); weight: {}, last accessed: {:?}",
+    ///             metadata.policy_weight(),
+    ///             metadata.last_accessed()
+    ///         );
+    ///     })
+    ///     .build();
+    ///
+    /// cache.insert(1, "one");
+    /// cache.invalidate(&1);
+    /// ```
+    ///
+    /// [removal-metadata]: ../notification/struct.RemovalMetadata.html
+    pub fn eviction_listener_with_metadata(
+        self,
+        listener: impl Fn(Arc<K>, V, RemovalCause, &RemovalMetadata) + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            eviction_listener: Some(Arc::new(listener)),
+            ..self
+        }
+    }
+
+    /// Sets the [`RemovalCauses`][removal-causes] notified to the eviction
+    /// listener. The removals by the other causes are not notified, and the cache
+    /// does not clone their values for the listener. By default, all the causes are
+    /// notified.
+    ///
+    /// For example, a cache whose values are replaced very often can skip the
+    /// `Replaced` cause to avoid the overhead of notifying every routine
+    /// replacement. This has no effect if the eviction listener is not set.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use moka::{
+    ///     notification::{RemovalCause, RemovalCauses},
+    ///     sync::Cache,
+    /// };
+    ///
+    /// let cache = Cache::builder()
+    ///     .max_capacity(100)
+    ///     .eviction_listener(|key, _value, cause| {
+    ///         assert_ne!(cause, RemovalCause::Replaced);
+    ///         println!("{key} was removed ({cause:?})");
+    ///     })
+    ///     .eviction_listener_causes(RemovalCauses::all().without(RemovalCause::Replaced))
+    ///     .build();
+    ///
+    /// cache.insert(1, "one");
+    /// // The listener is not called for the replaced value.
+    /// cache.insert(1, "uno");
+    /// cache.invalidate(&1);
+    /// ```
+    ///
+    /// [removal-causes]: ../notification/struct.RemovalCauses.html
+    pub fn eviction_listener_causes(self, causes: impl Into<RemovalCauses>) -> Self {
+        Self {
+            eviction_listener_causes: causes.into(),
+            ..self
+        }
+    }
+
+    /// Sets the time to live of the cache.
+    ///
+    /// A cached entry will be expired after the specified duration past from
+    /// `insert`.
+    ///
+    /// # Panics
+    ///
+    /// `CacheBuilder::build*` methods will panic if the given `duration` is longer
+    /// than 1000 years. This is done to protect against overflow when computing key
+    /// expiration.
+    pub fn time_to_live(self, duration: Duration) -> Self {
+        let mut builder = self;
+        builder.expiration_policy.set_time_to_live(duration);
+        builder
+    }
+
+    /// Sets the time to idle of the cache.
+    ///
+    /// A cached entry will be expired after the specified duration past from `get`
+    /// or `insert`.
+    ///
+    /// # Panics
+    ///
+    /// `CacheBuilder::build*` methods will panic if the given `duration` is longer
+    /// than 1000 years. This is done to protect against overflow when computing key
+    /// expiration.
+    pub fn time_to_idle(self, duration: Duration) -> Self {
+        let mut builder = self;
+        builder.expiration_policy.set_time_to_idle(duration);
+        builder
+    }
+
+    /// Sets the given `expiry` to the cache.
+    ///
+    /// See [the example][per-entry-expiration-example] for per-entry expiration
+    /// policy in the `Cache` documentation.
+    ///
+    /// [per-entry-expiration-example]:
+    ///     ./struct.Cache.html#per-entry-expiration-policy
+    pub fn expire_after(self, expiry: impl Expiry<K, V> + Send + Sync + 'static) -> Self {
+        let mut builder = self;
+        builder.expiration_policy.set_expiry(Arc::new(expiry));
+        builder
+    }
+
+    /// Appends a [`Loader`][loader-trait] to the chain of the loaders called by
+    /// `get_or_load` method of the cache on a miss.
+    ///
+    /// The loaders are called in the order they were appended, until one of them
+    /// returns a value. The value is inserted to the cache, and passed to the
+    /// `store` method of the loaders before the one returned it.
+    ///
+    /// The plain `get` method does not call the loaders. It takes any borrowed
+    /// form `&Q` of the key, which cannot always be turned into the owned key `K`
+    /// that the loaders take and the cache stores. `get_or_load` requires
+    /// `Q: ToOwned<Owned = K>` for this, which `get` cannot add without breaking
+    /// the callers whose key types are not `Clone`.
+    ///
+    /// [loader-trait]: ./trait.Loader.html
+    pub fn loader(self, loader: impl Loader<K, V> + Send + Sync + 'static) -> Self {
+        let mut builder = self;
+        builder.loaders.push(Arc::new(loader));
+        builder
+    }
+
+    /// Appends an [`Interceptor`][interceptor-trait] to the chain of the
+    /// interceptors, whose hooks are called around the `get`, `insert` and
+    /// `invalidate` operations of the cache.
+    ///
+    /// The hooks are called in the order the interceptors were appended. See the
+    /// document of the trait for details.
+    ///
+    /// [interceptor-trait]: ./trait.Interceptor.html
+    pub fn interceptor(self, interceptor: impl Interceptor<K, V> + Send + Sync + 'static) -> Self {
+        let mut builder = self;
+        builder.interceptors.push(Arc::new(interceptor));
+        builder
+    }
+
+    /// Makes the cache zeroize its copies of the values when they are dropped, e.g.
+    /// after they are evicted, expired, invalidated or replaced, before their
+    /// memory is released. This is for caching secrets such as keys and tokens.
+    ///
+    /// The values returned by the cache (e.g. by `get`) and passed to the eviction
+    /// listener are clones owned by the callers, and not zeroized by the cache. To
+    /// zeroize them too, store the values wrapped in
+    /// [`zeroize::Zeroizing`][zeroizing] instead.
+    ///
+    /// Requires the `zeroize` crate feature.
+    ///
+    /// [zeroizing]: https://docs.rs/zeroize/latest/zeroize/struct.Zeroizing.html
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use moka::sync::Cache;
+    ///
+    /// let cache = Cache::builder()
+    ///     .max_capacity(100)
+    ///     .zeroize_values()
+    ///     .build();
+    ///
+    /// cache.insert("token:alice", "secret".to_string());
+    /// // The cached copy of "secret" is zeroized when it is dropped.
+    /// cache.invalidate(&"token:alice");
+    /// ```
+    #[cfg(feature = "zeroize")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "zeroize")))]
+    pub fn zeroize_values(self) -> Self
+    where
+        V: zeroize::Zeroize,
+    {
+        let mut builder = self;
+        builder.policy_config.value_zeroizer = ValueZeroizer::new();
+        builder
+    }
+
+    /// Divides the max capacity of the cache between tenants, so that a tenant
+    /// inserting a flood of entries cannot evict the entries of the other tenants.
+    ///
+    /// `quotas` are the max weighted sizes of the tenants, and `classifier` returns
+    /// the index of the tenant of a key in `quotas`. The keys for which
+    /// `classifier` returns `None` or an index out of range share the remaining
+    /// capacity, i.e. the max capacity minus the sum of the quotas.
+    ///
+    /// A new entry is admitted as long as it fits in the quota of its tenant. Once
+    /// the tenant is full, the new entry is selected by the cache policy against
+    /// the least recently used entries of the same tenant, and never against the
+    /// entries of the other tenants. When a tenant exceeds its quota, its least
+    /// recently used entries are evicted with
+    /// [`RemovalCause::Size`][removal-cause-size] until it fits in its quota. For
+    /// a `SegmentedCache`, the quotas are divided between the segments in the same
+    /// way as the max capacity.
+    ///
+    /// # Panics
+    ///
+    /// `CacheBuilder::build*` methods will panic if the max capacity is not set or
+    /// the sum of the quotas is greater than the max capacity, and `try_build*`
+    /// methods will return [`BuilderError::TenantQuotasWithoutMaxCapacity`] or
+    /// [`BuilderError::TenantQuotasExceedMaxCapacity`] respectively.
+    ///
+    /// [removal-cause-size]: ../notification/enum.RemovalCause.html#variant.Size
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use moka::sync::Cache;
+    ///
+    /// // Tenant 0 ("a:" keys) may use up to 60 entries, and tenant 1 ("b:" keys)
+    /// // up to 30 entries. The other keys share the remaining 10 entries.
+    /// let cache = Cache::builder()
+    ///     .max_capacity(100)
+    ///     .tenant_quotas([60, 30], |key: &String| {
+    ///         if key.starts_with("a:") {
+    ///             Some(0)
+    ///         } else if key.starts_with("b:") {
+    ///             Some(1)
+    ///         } else {
+    ///             None
+    ///         }
+    ///     })
+    ///     .build();
+    ///
+    /// cache.insert("a:1".to_string(), 1);
+    /// cache.run_pending_tasks();
+    /// assert_eq!(cache.tenant_weighted_size(0), Some(1));
+    /// assert_eq!(cache.tenant_weighted_size(1), Some(0));
+    /// ```
+    pub fn tenant_quotas(
+        self,
+        quotas: impl IntoIterator<Item = u64>,
+        classifier: impl Fn(&K) -> Option<usize> + Send + Sync + 'static,
+    ) -> Self {
+        let classifier: TenantClassifier<K> = Arc::new(classifier);
+        let mut builder = self;
+        builder.policy_config.tenant_quotas = Some(TenantQuotasConfig::new(
+            classifier,
+            quotas.into_iter().collect(),
+        ));
+        builder
+    }
+
+    /// Sets the max numbers of entries of the tenants given by
+    /// [`tenant_quotas`](#method.tenant_quotas), so that a tenant inserting a flood
+    /// of tiny entries cannot take the space of the hash table and the deques used
+    /// by the entries of the other tenants.
+    ///
+    /// `max_entries` are indexed in the same way as the quotas. The tenants without
+    /// a max number of entries given, including the keys sharing the remaining
+    /// capacity, are limited only by their quotas. When a tenant exceeds its max
+    /// number of entries, its least recently used entries are evicted with
+    /// [`RemovalCause::Size`][removal-cause-size]. For a `SegmentedCache`, the max
+    /// numbers of entries are divided between the segments in the same way as the
+    /// max capacity.
+    ///
+    /// # Panics
+    ///
+    /// `CacheBuilder::build*` methods will panic if the tenant quotas are not set,
+    /// and `try_build*` methods will return
+    /// [`BuilderError::TenantMaxEntriesWithoutQuotas`].
+    ///
+    /// [removal-cause-size]: ../notification/enum.RemovalCause.html#variant.Size
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use moka::sync::Cache;
+    ///
+    /// // Tenant 0 may use up to 1,000,000 bytes but no more than 100 entries.
+    /// let cache = Cache::builder()
+    ///     .max_capacity(2_000_000)
+    ///     .weigher(|_key: &u32, value: &Vec<u8>| value.len() as u32)
+    ///     .tenant_quotas([1_000_000], |key: &u32| (*key < 1000).then_some(0))
+    ///     .tenant_max_entries([100])
+    ///     .build();
+    ///
+    /// for key in 0..200 {
+    ///     cache.insert(key, vec![0u8; 10]);
+    /// }
+    /// cache.run_pending_tasks();
+    /// assert_eq!(cache.tenant_entry_count(0), Some(100));
+    /// ```
+    pub fn tenant_max_entries(self, max_entries: impl IntoIterator<Item = u64>) -> Self {
+        Self {
+            tenant_max_entries: Some(max_entries.into_iter().collect()),
+            ..self
+        }
+    }
+
+    /// Sets the max number of `init` closures of the `get_with` family methods and
+    /// `get_or_load` that are evaluated at the same time, across all keys. When the
+    /// limit is reached, the calls that need to evaluate their closures block until
+    /// one of the running closures completes.
+    ///
+    /// This protects the backing store from a flood of misses on distinct keys,
+    /// e.g. right after a cold start. For a `SegmentedCache`, the limit is shared by
+    /// all segments.
+    ///
+    /// A closure that loads other keys of the same cache (e.g. by calling
+    /// `get_with` from the closure) does not take another permit, as it already
+    /// holds one. So the nested loads do not deadlock even if `n` is one. However,
+    /// the nested loads on a _different_ cache with its own limit do take its
+    /// permits, so two caches whose closures load from each other can still
+    /// deadlock when both limits are reached.
+    ///
+    /// # Panics
+    ///
+    /// `CacheBuilder::build*` methods will panic if `n` is zero, and `try_build*`
+    /// methods will return [`BuilderError::ZeroMaxConcurrentLoads`][zero-loads].
+    ///
+    /// [zero-loads]: ../enum.BuilderError.html#variant.ZeroMaxConcurrentLoads
+    pub fn max_concurrent_loads(self, n: usize) -> Self {
+        Self {
+            max_concurrent_loads: Some(n),
+            ..self
+        }
+    }
+
+    /// Sets the time to live of the errors returned by the `init` closures of the
+    /// `try_get_with` family methods.
+    ///
+    /// When set, the error of a failed `init` closure is cached for the key for the
+    /// given duration, and the subsequent `try_get_with` calls for the key return
+    /// the cached error without calling their `init` closures, as long as they
+    /// have the same error type. This lets repeated failures on the same key back
+    /// off automatically. Once a value is inserted for the key, the cached error is
+    /// discarded immediately.
+    ///
+    /// The cached errors are not cache entries; they are not counted by
+    /// `entry_count`, and are not visible to `get` or `contains_key`. The expired
+    /// errors are removed by the cache maintenance, like the expired entries. Up to
+    /// 4096 errors are cached at once; when that many unexpired errors are cached,
+    /// a new error replaces the oldest one.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use moka::sync::Cache;
+    /// use std::{
+    ///     sync::atomic::{AtomicUsize, Ordering},
+    ///     time::Duration,
+    /// };
+    ///
+    /// let cache: Cache<u32, String> = Cache::builder()
+    ///     .error_time_to_live(Duration::from_secs(30))
+    ///     .build();
+    ///
+    /// let calls = AtomicUsize::new(0);
+    /// let fail = || {
+    ///     calls.fetch_add(1, Ordering::Relaxed);
+    ///     Err("backend unavailable")
+    /// };
+    ///
+    /// assert!(cache.try_get_with(1, fail).is_err());
+    /// // The error is returned from the error cache without calling `fail` again.
+    /// assert!(cache.try_get_with(1, fail).is_err());
+    /// assert_eq!(calls.load(Ordering::Relaxed), 1);
+    ///
+    /// // Inserting a value discards the cached error.
+    /// cache.insert(1, "value".to_string());
+    /// assert_eq!(cache.try_get_with(1, fail), Ok("value".to_string()));
+    /// ```
+    pub fn error_time_to_live(self, duration: Duration) -> Self {
+        Self {
+            error_time_to_live: Some(duration),
+            ..self
+        }
+    }
+
+    /// Registers a namespace of keys, which have their own time to live and time to
+    /// idle. A key belongs to the first registered namespace whose `matches` closure
+    /// returns `true`.
+    ///
+    /// The entries in a namespace expire after `time_to_live` past from `insert`,
+    /// or after `time_to_idle` past from `get` or `insert`, whichever comes
+    /// earlier. Passing `None` disables each of them for the namespace. The entries
+    /// in no namespace follow the expiry set by `expire_after`, if any.
+    ///
+    /// Note that the `time_to_live` and `time_to_idle` of the cache still apply to
+    /// all entries. So to let a namespace live longer than others, do not set them
+    /// to the cache but register a namespace for the other keys.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use moka::sync::Cache;
+    /// use std::time::Duration;
+    ///
+    /// let cache: Cache<String, String> = Cache::builder()
+    ///     .max_capacity(10_000)
+    ///     // Tokens live for 5 minutes.
+    ///     .namespace_expiration(
+    ///         |key: &String| key.starts_with("token:"),
+    ///         Some(Duration::from_secs(5 * 60)),
+    ///         None,
+    ///     )
+    ///     // Metadata lives for a day, unless it has not been read for an hour.
+    ///     .namespace_expiration(
+    ///         |key: &String| key.starts_with("meta:"),
+    ///         Some(Duration::from_secs(24 * 60 * 60)),
+    ///         Some(Duration::from_secs(60 * 60)),
+    ///     )
+    ///     .build();
+    ///
+    /// cache.insert("token:alice".to_string(), "secret".to_string());
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if either `time_to_live` or `time_to_idle` is longer than 1000 years.
+    /// This is done to protect against overflow when computing key expiration.
+    pub fn namespace_expiration(
+        self,
+        matches: impl Fn(&K) -> bool + Send + Sync + 'static,
+        time_to_live: Option<Duration>,
+        time_to_idle: Option<Duration>,
+    ) -> Self {
+        builder_utils::ensure_expirations_or_panic(time_to_live, time_to_idle);
+        let mut builder = self;
+        builder
+            .expiration_policy
+            .add_namespace(Arc::new(matches), time_to_live, time_to_idle);
+        builder
+    }
+
+    #[cfg(test)]
+    pub(crate) fn housekeeper_config(self, conf: HousekeeperConfig) -> Self {
+        Self {
+            housekeeper_config: conf,
+            ..self
+        }
+    }
+
+    /// Enables support for [`Cache::invalidate_entries_if`][cache-invalidate-if]
+    /// method.
+    ///
+    /// The cache will maintain additional internal data structures to support
+    /// `invalidate_entries_if` method.
+    ///
+    /// [cache-invalidate-if]: ./struct.Cache.html#method.invalidate_entries_if
+    pub fn support_invalidation_closures(self) -> Self {
+        Self {
+            invalidator_enabled: true,
+            ..self
+        }
+    }
+
+    /// Enables support for [`Cache::insert_if_version`][cache-insert-if-version]
+    /// method.
+    ///
+    /// The cache will give a new version to every inserted or updated value, which
+    /// is returned by [`EntryMetadata::version`][entry-metadata-version]. The
+    /// versions are drawn from a counter shared by all writes to the cache, so
+    /// leave this disabled if `insert_if_version` is not used.
+    ///
+    /// Requires the `entry-versions` crate feature.
+    ///
+    /// [cache-insert-if-version]: ./struct.Cache.html#method.insert_if_version
+    /// [entry-metadata-version]: ../struct.EntryMetadata.html#method.version
+    #[cfg(feature = "entry-versions")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "entry-versions")))]
+    pub fn support_entry_versions(self) -> Self {
+        let mut builder = self;
+        builder.policy_config.entry_versions = true;
+        builder
+    }
+
+    /// Sets a [`MockClock`][mock-clock] as the clock of the cache, so that the
+    /// entries can be expired by advancing the clock instead of sleeping. This is
+    /// meant for unit tests.
+    ///
+    /// This replaces the time source set by the `time_source` method, and vice
+    /// versa.
+    ///
+    /// [mock-clock]: ../struct.MockClock.html
+    pub fn clock(self, clock: MockClock) -> Self {
+        let mut builder = self;
+        builder.policy_config.expiration_clock = Some(clock.clock());
+        builder
+    }
+
+    /// Sets the source of the current time used by the cache. See
+    /// [`TimeSource`][time-source] for the choices.
+    ///
+    /// This replaces the clock set by the `clock` method, and vice versa.
+    ///
+    /// [time-source]: ../enum.TimeSource.html
+    pub fn time_source(self, source: TimeSource) -> Self {
+        let mut builder = self;
+        builder.policy_config.expiration_clock = Clock::from_time_source(source);
+        builder
+    }
+
+    /// Puts the cache into the deterministic mode, so that property-based and
+    /// model-checking tests of the code built on the cache are reproducible. This
+    /// is meant for tests.
+    ///
+    /// In this mode:
+    ///
+    /// - The cache uses the given [`MockClock`][mock-clock] as its clock, as the
+    ///   `clock` method does. The time advances only when the clock is advanced.
+    /// - The cache operations do not run the pending maintenance tasks (applying
+    ///   the recorded reads and writes, and evicting entries). They run only when
+    ///   you call `run_pending_tasks`, so you can step the cache explicitly. The
+    ///   only exception is a write that finds the write log full; it runs the tasks
+    ///   inline on the calling thread instead of waiting for other threads.
+    /// - The `shed_on_memory_pressure` option has no effect.
+    ///
+    /// The admission and eviction decisions still depend on the hash values of the
+    /// keys. For fully reproducible results, build the cache with a hasher with
+    /// fixed keys, such as a [`SeededState`][seeded-state] with a fixed seed, and
+    /// perform the operations from a single thread.
+    ///
+    /// [mock-clock]: ../struct.MockClock.html
+    /// [seeded-state]: ../struct.SeededState.html
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use moka::{sync::Cache, MockClock, SeededState};
+    /// use std::{collections::hash_map::DefaultHasher, hash::BuildHasherDefault};
+    /// use std::time::Duration;
+    ///
+    /// type FixedState = BuildHasherDefault<DefaultHasher>;
+    ///
+    /// let clock = MockClock::new();
+    /// let cache: Cache<u32, u32, _> = Cache::builder()
+    ///     .max_capacity(100)
+    ///     .time_to_live(Duration::from_secs(10))
+    ///     .deterministic(clock.clone())
+    ///     .build_with_hasher(SeededState::with_seed(FixedState::default(), 42));
+    ///
+    /// for i in 0..200 {
+    ///     cache.insert(i, i);
+    /// }
+    /// // Nothing has been evicted yet.
+    /// assert_eq!(cache.iter().count(), 200);
+    ///
+    /// cache.run_pending_tasks();
+    /// assert_eq!(cache.entry_count(), 100);
+    ///
+    /// clock.advance(Duration::from_secs(10));
+    /// cache.run_pending_tasks();
+    /// assert_eq!(cache.entry_count(), 0);
+    /// ```
+    pub fn deterministic(self, clock: MockClock) -> Self {
+        let mut builder = self.clock(clock);
+        builder.housekeeper_config.manual_maintenance = true;
+        builder
+    }
+
+    fn derived_max_capacity(&self) -> Option<u64> {
+        builder_utils::derive_max_capacity(
+            self.max_capacity,
+            self.weigher.is_some(),
+            self.max_memory_fraction,
+        )
+        .unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// # Panics
+    ///
+    /// Panics if the tenant quotas are set without the max capacity, their sum is
+    /// greater than the max capacity, or the tenant max entries are set without the
+    /// tenant quotas.
+    fn validated_tenant_quotas(&self, max_capacity: Option<u64>) -> Option<TenantQuotasConfig<K>> {
+        self.validate_tenant_quotas(max_capacity)
+            .unwrap_or_else(|e| panic!("{e}"));
+        let quotas = self.policy_config.tenant_quotas.clone()?;
+        match &self.tenant_max_entries {
+            Some(max_entries) => Some(quotas.with_max_entries(max_entries.clone())),
+            None => Some(quotas),
+        }
+    }
+
+    fn validate_tenant_quotas(&self, max_capacity: Option<u64>) -> Result<(), BuilderError> {
+        match (&self.policy_config.tenant_quotas, &self.tenant_max_entries) {
+            (Some(quotas), _) => {
+                builder_utils::validate_tenant_quotas(max_capacity, quotas.total())
+            }
+            (None, Some(_)) => Err(BuilderError::TenantMaxEntriesWithoutQuotas),
+            (None, None) => Ok(()),
+        }
+    }
+
+    fn derived_initial_capacity(&self) -> Option<usize> {
+        builder_utils::derive_initial_capacity(
+            self.initial_capacity,
+            self.max_capacity,
+            self.weigher.is_some(),
+            self.initial_capacity_load_factor,
+        )
+        .unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// # Panics
+    ///
+    /// Panics if `max_concurrent_loads` is zero.
+    fn load_limiter(&self) -> Option<Arc<LoadLimiter>> {
+        self.max_concurrent_loads
+            .map(|n| Arc::new(LoadLimiter::new(n)))
+    }
+
+    fn validate(&self) -> Result<(), BuilderError> {
+        let exp = &self.expiration_policy;
+        let max_capacity = builder_utils::derive_max_capacity(
+            self.max_capacity,
+            self.weigher.is_some(),
+            self.max_memory_fraction,
+        )?;
+        builder_utils::validate_config(
+            self.num_segments,
+            max_capacity,
+            self.weigher.is_some(),
+            exp.time_to_live(),
+            exp.time_to_idle(),
+        )?;
+        builder_utils::derive_initial_capacity(
+            self.initial_capacity,
+            self.max_capacity,
+            self.weigher.is_some(),
+            self.initial_capacity_load_factor,
+        )
+        .map(|_| ())?;
+        if self.max_concurrent_loads == Some(0) {
+            return Err(BuilderError::ZeroMaxConcurrentLoads);
+        }
+        self.validate_tenant_quotas(max_capacity)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CacheBuilder;
+
+    use std::time::Duration;
+
+    #[test]
+    fn build_cache() {
+        // Cache<char, String>
+        let cache = CacheBuilder::new(100).build();
+        let policy = cache.policy();
 
         assert_eq!(policy.max_capacity(), Some(100));
         assert_eq!(policy.time_to_live(), None);
@@ -603,4 +2134,152 @@ mod tests {
             .time_to_idle(duration + Duration::from_secs(1))
             .build();
     }
+
+    #[test]
+    fn try_build_cache() {
+        use crate::BuilderError;
+
+        let cache = CacheBuilder::new(100)
+            .time_to_live(Duration::from_secs(60))
+            .try_build()
+            .unwrap();
+        cache.insert('a', "Alice");
+        assert_eq!(cache.get(&'a'), Some("Alice"));
+
+        let builder = || CacheBuilder::<char, String, _>::new(100);
+        let zero = Duration::ZERO;
+        let too_long = Duration::from_secs(1000 * 365 * 24 * 3600 + 1);
+
+        let result = builder().time_to_live(zero).try_build();
+        assert_eq!(result.err(), Some(BuilderError::ZeroTimeToLive));
+        let result = builder().time_to_idle(zero).try_build();
+        assert_eq!(result.err(), Some(BuilderError::ZeroTimeToIdle));
+        let result = builder().time_to_live(too_long).try_build();
+        assert_eq!(result.err(), Some(BuilderError::TimeToLiveTooLong));
+        let result = builder().time_to_idle(too_long).try_build();
+        assert_eq!(result.err(), Some(BuilderError::TimeToIdleTooLong));
+        let result = builder().segments(0).try_build();
+        assert_eq!(result.err(), Some(BuilderError::ZeroSegments));
+
+        let result = CacheBuilder::default()
+            .weigher(|_k: &char, v: &String| v.len() as u32)
+            .try_build();
+        assert_eq!(result.err(), Some(BuilderError::WeigherWithoutMaxCapacity));
+
+        let result = builder().auto_initial_capacity(0.0).try_build();
+        assert_eq!(result.err(), Some(BuilderError::InvalidLoadFactor));
+        let result = builder().auto_initial_capacity(1.5).try_build();
+        assert_eq!(result.err(), Some(BuilderError::InvalidLoadFactor));
+        assert!(builder().auto_initial_capacity(0.5).try_build().is_ok());
+
+        let result = builder().max_concurrent_loads(0).try_build();
+        assert_eq!(result.err(), Some(BuilderError::ZeroMaxConcurrentLoads));
+
+        let result = builder().max_memory_fraction(0.5).try_build();
+        assert_eq!(
+            result.err(),
+            Some(BuilderError::MemoryFractionWithoutWeigher)
+        );
+        let weighted = || builder().weigher(|_k, v| v.len() as u32);
+        let result = weighted().max_memory_fraction(1.5).try_build();
+        assert_eq!(result.err(), Some(BuilderError::InvalidMemoryFraction));
+        // The max capacity 100 is used if the memory could not be detected.
+        let cache = weighted().max_memory_fraction(0.5).try_build().unwrap();
+        assert!(cache.policy().max_capacity().unwrap() >= 100);
+        let result = CacheBuilder::<char, String, _>::default()
+            .weigher(|_k, v| v.len() as u32)
+            .max_memory_fraction(0.5)
+            .try_build();
+        if crate::common::sys_memory::available_memory().is_none() {
+            assert_eq!(result.err(), Some(BuilderError::MemoryNotDetected));
+        }
+
+        let result = CacheBuilder::<char, String, _>::default()
+            .tenant_quotas([10], |_| Some(0))
+            .try_build();
+        assert_eq!(
+            result.err(),
+            Some(BuilderError::TenantQuotasWithoutMaxCapacity)
+        );
+        let result = builder().tenant_quotas([60, 50], |_| None).try_build();
+        assert_eq!(
+            result.err(),
+            Some(BuilderError::TenantQuotasExceedMaxCapacity {
+                total: 110,
+                max_capacity: 100
+            })
+        );
+        let result = builder().tenant_max_entries([10]).try_build();
+        assert_eq!(
+            result.err(),
+            Some(BuilderError::TenantMaxEntriesWithoutQuotas)
+        );
+    }
+
+    #[test]
+    fn derive_memory_fraction() {
+        use crate::common::builder_utils::memory_fraction_of;
+        use crate::BuilderError;
+
+        let gib = 1u64 << 30;
+        assert_eq!(memory_fraction_of(Some(4 * gib), None, true, 0.25), Ok(gib));
+        assert_eq!(
+            memory_fraction_of(Some(4 * gib), None, true, 1.0),
+            Ok(4 * gib)
+        );
+        assert_eq!(
+            memory_fraction_of(Some(gib), None, true, 0.0),
+            Err(BuilderError::InvalidMemoryFraction)
+        );
+        assert_eq!(
+            memory_fraction_of(Some(gib), None, true, f64::NAN),
+            Err(BuilderError::InvalidMemoryFraction)
+        );
+        assert_eq!(
+            memory_fraction_of(Some(gib), None, false, 0.5),
+            Err(BuilderError::MemoryFractionWithoutWeigher)
+        );
+        assert_eq!(
+            memory_fraction_of(None, None, true, 0.5),
+            Err(BuilderError::MemoryNotDetected)
+        );
+
+        // The detected memory takes precedence over the fallback max capacity.
+        assert_eq!(
+            memory_fraction_of(Some(4 * gib), Some(100), true, 0.25),
+            Ok(gib)
+        );
+        // Fall back to the max capacity if the memory could not be detected.
+        assert_eq!(memory_fraction_of(None, Some(100), true, 0.5), Ok(100));
+        assert_eq!(
+            memory_fraction_of(None, Some(100), false, 0.5),
+            Err(BuilderError::MemoryFractionWithoutWeigher)
+        );
+    }
+
+    #[test]
+    fn derive_initial_capacity() {
+        use crate::common::builder_utils::derive_initial_capacity;
+
+        // Derived from the max capacity.
+        let ic = derive_initial_capacity(None, Some(1000), false, Some(0.5));
+        assert_eq!(ic, Ok(Some(500)));
+        // Not derived when the initial capacity is set, a weigher is set, or the
+        // max capacity is not set.
+        let ic = derive_initial_capacity(Some(10), Some(1000), false, Some(0.5));
+        assert_eq!(ic, Ok(Some(10)));
+        let ic = derive_initial_capacity(None, Some(1000), true, Some(0.5));
+        assert_eq!(ic, Ok(None));
+        let ic = derive_initial_capacity(None, None, false, Some(0.5));
+        assert_eq!(ic, Ok(None));
+        let ic = derive_initial_capacity(None, Some(1000), false, None);
+        assert_eq!(ic, Ok(None));
+    }
+
+    #[test]
+    #[should_panic(expected = "The load factor of auto_initial_capacity")]
+    fn build_cache_invalid_load_factor() {
+        let builder: CacheBuilder<char, String, _> = CacheBuilder::new(100);
+        builder.auto_initial_capacity(f64::NAN).build();
+    }
 }
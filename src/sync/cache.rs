@@ -1,35 +1,51 @@
 use super::{
-    value_initializer::{GetOrInsert, InitResult, ValueInitializer},
+    interceptor::InterceptorChain,
+    loader::LoaderChain,
+    map_view::MapView,
+    stream,
+    value_initializer::{GetOrInsert, InitResult, LoadLimiter, ValueInitializer},
     CacheBuilder, OwnedKeyEntrySelector, RefKeyEntrySelector,
 };
 use crate::{
     common::{
-        concurrent::{
-            constants::WRITE_RETRY_INTERVAL_MICROS, housekeeper::InnerSync, Weigher, WriteOp,
-        },
+        concurrent::{housekeeper::InnerSync, ValueEntry, Weigher, WriteOp},
+        error_cache::ErrorCache,
         time::Instant,
         HousekeeperConfig,
     },
-    notification::EvictionListener,
-    ops::compute::{self, CompResult},
-    policy::{EvictionPolicy, ExpirationPolicy},
-    sync::{Iter, PredicateId},
+    notification::{EvictionListener, RemovalCauses},
+    ops::{
+        compute::{self, CompResult},
+        merge::MergeStrategy,
+    },
+    policy::{EvictionPolicy, ExpirationPolicy, WriteRetryStrategy},
+    sync::{
+        CacheStats, ClassStats, EventReceiver, ExportStream, FrequencyIter, GhostListStats,
+        HitRatioReport, IntoIter, Iter, LoadProgress, LoaderContext, PinnedEntry, PredicateId,
+        ReadSamplingStats, RecencyIter,
+    },
     sync_base::{
         base_cache::{BaseCache, HouseKeeperArc},
         iter::ScanningGet,
+        policy_config::PolicyConfig,
     },
-    Entry, Policy, PredicateError,
+    Entry, EntryMetadata, Policy, PredicateError,
 };
 
+#[cfg(debug_assertions)]
+use crate::sync::InvariantReport;
+
 use crossbeam_channel::{Sender, TrySendError};
 use std::{
-    borrow::Borrow,
-    collections::hash_map::RandomState,
+    borrow::{Borrow, Cow},
+    collections::{hash_map::RandomState, HashMap, HashSet},
     fmt,
     hash::{BuildHasher, Hash},
+    io,
     sync::Arc,
-    time::Duration,
+    time::{Duration, Instant as StdInstant},
 };
+use triomphe::Arc as TrioArc;
 
 /// A thread-safe concurrent synchronous in-memory cache.
 ///
@@ -574,6 +590,30 @@ use std::{
 pub struct Cache<K, V, S = RandomState> {
     base: BaseCache<K, V, S>,
     value_initializer: Arc<ValueInitializer<K, V, S>>,
+    loader_chain: Option<Arc<LoaderChain<K, V>>>,
+    error_cache: Option<Arc<ErrorCache<K, S>>>,
+}
+
+/// The configuration this cache was built with. Kept once in the inner cache
+/// shared by the clones, and used by `snapshot_clone` to build an independent
+/// cache with the same configuration.
+pub(crate) struct Config<K, V, S> {
+    name: Option<String>,
+    max_capacity: Option<u64>,
+    initial_capacity: Option<usize>,
+    build_hasher: S,
+    weigher: Option<Weigher<K, V>>,
+    eviction_policy: EvictionPolicy,
+    eviction_listener: Option<EvictionListener<K, V>>,
+    eviction_listener_causes: RemovalCauses,
+    expiration_policy: ExpirationPolicy<K, V>,
+    housekeeper_config: HousekeeperConfig,
+    invalidator_enabled: bool,
+    loader_chain: Option<Arc<LoaderChain<K, V>>>,
+    max_concurrent_loads: Option<usize>,
+    error_time_to_live: Option<Duration>,
+    interceptors: Option<Arc<InterceptorChain<K, V>>>,
+    policy_config: PolicyConfig<K, V>,
 }
 
 // TODO: https://github.com/moka-rs/moka/issues/54
@@ -604,6 +644,8 @@ impl<K, V, S> Clone for Cache<K, V, S> {
         Self {
             base: self.base.clone(),
             value_initializer: Arc::clone(&self.value_initializer),
+            loader_chain: self.loader_chain.clone(),
+            error_cache: self.error_cache.clone(),
         }
     }
 }
@@ -686,6 +728,232 @@ impl<K, V, S> Cache<K, V, S> {
     pub fn weighted_size(&self) -> u64 {
         self.base.weighted_size()
     }
+
+    /// Returns an approximate weighted size of the entries of the `tenant` in this
+    /// cache, or `None` if the cache was not built with
+    /// [`tenant_quotas`][tenant-quotas] or the `tenant` is out of range.
+    ///
+    /// Like `weighted_size`, the value returned is _an estimate_ until the pending
+    /// tasks are processed.
+    ///
+    /// [tenant-quotas]: ./struct.CacheBuilder.html#method.tenant_quotas
+    pub fn tenant_weighted_size(&self, tenant: usize) -> Option<u64> {
+        self.base.tenant_weighted_size(tenant)
+    }
+
+    /// Returns an approximate number of the entries of the `tenant` in this cache,
+    /// or `None` if the cache was not built with [`tenant_quotas`][tenant-quotas] or
+    /// the `tenant` is out of range.
+    ///
+    /// Like `entry_count`, the value returned is _an estimate_ until the pending
+    /// tasks are processed.
+    ///
+    /// [tenant-quotas]: ./struct.CacheBuilder.html#method.tenant_quotas
+    pub fn tenant_entry_count(&self, tenant: usize) -> Option<u64> {
+        self.base.tenant_entry_count(tenant)
+    }
+
+    /// Returns the report of the hit ratio analyzer, or `None` if the cache was not
+    /// built with [`analyze_hit_ratio`][analyze-hit-ratio] or without a max
+    /// capacity.
+    ///
+    /// The report has the observed hit ratio and the estimated hit ratios at 0.5x,
+    /// 1x, 2x and 4x of the max capacity. See [`HitRatioReport`] for details. Like
+    /// `entry_count`, the reads are counted when the pending tasks are processed.
+    ///
+    /// [analyze-hit-ratio]: ./struct.CacheBuilder.html#method.analyze_hit_ratio
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use moka::sync::Cache;
+    ///
+    /// let cache = Cache::builder()
+    ///     .max_capacity(100)
+    ///     .analyze_hit_ratio()
+    ///     .build();
+    ///
+    /// for i in 0..1000 {
+    ///     let key = i % 150;
+    ///     cache.get_with(key, || key);
+    /// }
+    /// cache.run_pending_tasks();
+    ///
+    /// let report = cache.hit_ratio_report().unwrap();
+    /// assert_eq!(report.reads(), 1000);
+    /// for estimate in report.estimates() {
+    ///     println!(
+    ///         "{:>4} entries: {:.2}",
+    ///         estimate.capacity(),
+    ///         estimate.hit_ratio()
+    ///     );
+    /// }
+    /// ```
+    pub fn hit_ratio_report(&self) -> Option<HitRatioReport> {
+        self.base.hit_ratio_report()
+    }
+
+    /// Returns the counters of the misses on the recently evicted keys, or `None`
+    /// if the cache was not built with [`track_recent_evictions`][track-recent-evictions]
+    /// or without a max capacity. See [`GhostListStats`] for details.
+    ///
+    /// [track-recent-evictions]: ./struct.CacheBuilder.html#method.track_recent_evictions
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use moka::sync::Cache;
+    ///
+    /// let cache = Cache::builder()
+    ///     .max_capacity(100)
+    ///     .track_recent_evictions()
+    ///     .build();
+    ///
+    /// for round in 0..3 {
+    ///     for key in 0..200 {
+    ///         cache.get_with(key, || round);
+    ///     }
+    ///     cache.run_pending_tasks();
+    /// }
+    ///
+    /// let stats = cache.ghost_list_stats().unwrap();
+    /// // Many misses are on the keys evicted recently, so the cache is undersized
+    /// // for the workload.
+    /// println!("{:.2}", stats.recent_eviction_miss_ratio());
+    /// assert!(stats.recent_eviction_misses() > 0);
+    /// ```
+    pub fn ghost_list_stats(&self) -> Option<GhostListStats> {
+        self.base.ghost_list_stats()
+    }
+
+    /// Returns the counters of the hits, the misses and the evictions of each class
+    /// of the keys, or `None` if the cache was not built with
+    /// [`class_stats`][class-stats].
+    ///
+    /// The hits and the misses are counted by the reads, and the evictions are
+    /// counted when the pending tasks are processed.
+    ///
+    /// [class-stats]: ./struct.CacheBuilder.html#method.class_stats
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use moka::sync::Cache;
+    ///
+    /// let cache = Cache::builder()
+    ///     .max_capacity(100)
+    ///     .class_stats(|key: &String| {
+    ///         if key.starts_with("user/") {
+    ///             "user"
+    ///         } else {
+    ///             "other"
+    ///         }
+    ///     })
+    ///     .build();
+    ///
+    /// cache.get_with("user/alice".to_string(), || 1);
+    /// cache.insert("page/index".to_string(), 2);
+    /// cache.get("user/alice");
+    ///
+    /// let stats = cache.class_stats().unwrap();
+    /// assert_eq!(stats["user"].hits(), 1);
+    /// assert_eq!(stats["user"].misses(), 1);
+    /// // A plain `insert` is not a miss.
+    /// assert!(!stats.contains_key("other"));
+    /// ```
+    pub fn class_stats(&self) -> Option<HashMap<&'static str, ClassStats>> {
+        self.base.class_stats()
+    }
+
+    /// Returns the histogram of the lifetimes of the entries removed from the
+    /// cache, or `None` if the cache was not built with
+    /// [`track_entry_lifetimes`][track-entry-lifetimes].
+    ///
+    /// Requires the `entry-stats` crate feature.
+    ///
+    /// [track-entry-lifetimes]: ./struct.CacheBuilder.html#method.track_entry_lifetimes
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use moka::sync::Cache;
+    /// use std::time::Duration;
+    ///
+    /// let cache = Cache::builder()
+    ///     .max_capacity(100)
+    ///     .time_to_live(Duration::from_secs(3600))
+    ///     .track_entry_lifetimes()
+    ///     .build();
+    ///
+    /// cache.insert(0, "zero");
+    /// cache.insert(1, "one");
+    /// cache.get(&0);
+    /// cache.invalidate(&0);
+    /// cache.invalidate(&1);
+    /// cache.run_pending_tasks();
+    ///
+    /// let histogram = cache.entry_lifetime_histogram().unwrap();
+    /// assert_eq!(histogram.removed(), 2);
+    /// assert_eq!(histogram.never_hit(), 1);
+    /// for bucket in histogram.buckets().iter().filter(|b| b.removed() > 0) {
+    ///     println!("< {:?}: {} removed", bucket.upper_bound(), bucket.removed());
+    /// }
+    /// ```
+    #[cfg(feature = "entry-stats")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "entry-stats")))]
+    pub fn entry_lifetime_histogram(&self) -> Option<crate::sync::LifetimeHistogram> {
+        self.base.entry_lifetime_histogram()
+    }
+
+    /// Returns the state of the sampling of the reads, such as the current
+    /// sampling interval, or `None` if the cache was not built with
+    /// [`sample_reads_under_pressure`][sample-reads].
+    ///
+    /// [sample-reads]: ./struct.CacheBuilder.html#method.sample_reads_under_pressure
+    pub fn read_sampling_stats(&self) -> Option<ReadSamplingStats> {
+        self.base.read_sampling_stats()
+    }
+
+    /// Returns a snapshot of the hit, miss, eviction and load counts of the cache,
+    /// or `None` if the cache was built with [`disable_stats`][disable-stats].
+    ///
+    /// The hits and misses are counted by the reads such as `get` and `get_with`,
+    /// and the evictions are counted when the pending tasks evict or expire the
+    /// entries. See [`CacheStats`][cache-stats] for the details.
+    ///
+    /// [disable-stats]: ./struct.CacheBuilder.html#method.disable_stats
+    /// [cache-stats]: ./struct.CacheStats.html
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use moka::sync::Cache;
+    ///
+    /// let cache = Cache::new(100);
+    /// cache.insert("a", 1);
+    /// assert_eq!(cache.get(&"a"), Some(1));
+    /// assert_eq!(cache.get(&"b"), None);
+    /// assert_eq!(cache.get_with("b", || 2), 2);
+    ///
+    /// let stats = cache.stats().unwrap();
+    /// // `get_with` looked up the key "b" once more before loading it.
+    /// assert_eq!((stats.hit_count(), stats.miss_count()), (1, 2));
+    /// assert_eq!(stats.load_success_count(), 1);
+    /// ```
+    pub fn stats(&self) -> Option<CacheStats> {
+        self.base.stats()
+    }
+
+    /// Returns `true` if the cache has lost the internal channels to its
+    /// housekeeper, e.g. in a race on shutdown.
+    ///
+    /// Instead of panicking, such a cache keeps working as a plain concurrent hash
+    /// map: the reads and writes are not recorded, so the entries are no longer
+    /// evicted or expired by the policies, and `entry_count` and `weighted_size`
+    /// are not updated. Monitor this to detect the condition.
+    pub fn is_degraded(&self) -> bool {
+        self.base.is_degraded()
+    }
 }
 
 impl<K, V> Cache<K, V, RandomState>
@@ -709,9 +977,15 @@ where
             None,
             EvictionPolicy::default(),
             None,
+            RemovalCauses::default(),
             ExpirationPolicy::default(),
             HousekeeperConfig::default(),
             false,
+            None,
+            None,
+            None,
+            None,
+            PolicyConfig::default(),
         )
     }
 
@@ -740,24 +1014,65 @@ where
         weigher: Option<Weigher<K, V>>,
         eviction_policy: EvictionPolicy,
         eviction_listener: Option<EvictionListener<K, V>>,
+        eviction_listener_causes: RemovalCauses,
         expiration_policy: ExpirationPolicy<K, V>,
         housekeeper_config: HousekeeperConfig,
         invalidator_enabled: bool,
+        loader_chain: Option<Arc<LoaderChain<K, V>>>,
+        load_limiter: Option<Arc<LoadLimiter>>,
+        error_time_to_live: Option<Duration>,
+        interceptors: Option<Arc<InterceptorChain<K, V>>>,
+        policy_config: PolicyConfig<K, V>,
     ) -> Self {
+        let config = Config {
+            name: name.clone(),
+            max_capacity,
+            initial_capacity,
+            build_hasher: build_hasher.clone(),
+            weigher: weigher.clone(),
+            eviction_policy: eviction_policy.clone(),
+            eviction_listener: eviction_listener.clone(),
+            eviction_listener_causes,
+            expiration_policy: expiration_policy.clone(),
+            housekeeper_config: housekeeper_config.clone(),
+            invalidator_enabled,
+            loader_chain: loader_chain.clone(),
+            max_concurrent_loads: load_limiter.as_ref().map(|l| l.max_concurrent_loads()),
+            error_time_to_live,
+            interceptors: interceptors.clone(),
+            policy_config: policy_config.clone(),
+        };
+        // The refreshes need the in-flight loads to run only one at a time for a
+        // key.
+        let tracks_loads = policy_config.load_progress || loader_chain.is_some();
+        let error_cache =
+            error_time_to_live.map(|ttl| Arc::new(ErrorCache::new(ttl, build_hasher.clone())));
+        let mut base = BaseCache::new(
+            name,
+            max_capacity,
+            initial_capacity,
+            build_hasher.clone(),
+            weigher,
+            eviction_policy,
+            eviction_listener,
+            eviction_listener_causes,
+            expiration_policy,
+            housekeeper_config,
+            invalidator_enabled,
+            policy_config,
+            Some(config),
+            error_cache.clone(),
+        );
+        base.interceptors = interceptors;
         Self {
-            base: BaseCache::new(
-                name,
-                max_capacity,
-                initial_capacity,
+            base,
+            value_initializer: Arc::new(ValueInitializer::new(
                 build_hasher.clone(),
-                weigher,
-                eviction_policy,
-                eviction_listener,
-                expiration_policy,
-                housekeeper_config,
-                invalidator_enabled,
-            ),
-            value_initializer: Arc::new(ValueInitializer::with_hasher(build_hasher)),
+                load_limiter,
+                tracks_loads,
+            )),
+            loader_chain,
+            error_cache,
         }
     }
 
@@ -777,6 +1092,24 @@ where
         self.base.contains_key_with_hash(key, self.base.hash(key))
     }
 
+    /// Returns `true` if the key was recently evicted by the size constraint of the
+    /// cache. Always returns `false` if the cache was not built with
+    /// [`track_recent_evictions`][track-recent-evictions].
+    ///
+    /// Only the hashes of the evicted keys are remembered, so this method can
+    /// return `true` for a key that has the same hash as an evicted key. The
+    /// evictions are recorded when the pending tasks are processed.
+    ///
+    /// [track-recent-evictions]: ./struct.CacheBuilder.html#method.track_recent_evictions
+    pub fn was_recently_evicted<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let hash = self.base.hash(key);
+        self.base.was_recently_evicted_with_hash(hash)
+    }
+
     pub(crate) fn contains_key_with_hash<Q>(&self, key: &Q, hash: u64) -> bool
     where
         K: Borrow<Q>,
@@ -794,17 +1127,125 @@ where
     /// The key may be any borrowed form of the cache's key type, but `Hash` and `Eq`
     /// on the borrowed form _must_ match those for the key type.
     ///
+    /// This method does not call the loaders registered by
+    /// [`CacheBuilder::loader`][builder-loader] on a miss. Use
+    /// [`get_or_load`](#method.get_or_load) to read through them.
+    ///
     /// [rustdoc-std-arc]: https://doc.rust-lang.org/stable/std/sync/struct.Arc.html
+    /// [builder-loader]: ./struct.CacheBuilder.html#method.loader
     pub fn get<Q>(&self, key: &Q) -> Option<V>
     where
         K: Borrow<Q>,
         Q: Hash + Eq + ?Sized,
     {
-        self.base
-            .get_with_hash(key, self.base.hash(key), false)
+        self.get_with_hash(key, self.base.hash(key), false)
             .map(Entry::into_value)
     }
 
+    /// Returns an [`Entry`][entry-struct] holding a clone of the value and the key
+    /// corresponding to the key, and the [`EntryMetadata`][metadata-struct] of the
+    /// entry, such as its age and the time until it expires.
+    ///
+    /// Like `get`, this method is considered a cache read operation.
+    ///
+    /// [entry-struct]: ../struct.Entry.html
+    /// [metadata-struct]: ../struct.EntryMetadata.html
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use moka::sync::Cache;
+    /// use std::time::Duration;
+    ///
+    /// let cache = Cache::builder()
+    ///     .time_to_live(Duration::from_secs(60))
+    ///     .build();
+    /// cache.insert("/index.html", "<html></html>");
+    ///
+    /// let entry = cache.get_entry(&"/index.html").unwrap();
+    /// assert_eq!(entry.key(), &"/index.html");
+    /// assert_eq!(entry.value(), &"<html></html>");
+    ///
+    /// let metadata = entry.metadata().unwrap();
+    /// // e.g. `Age: 0` and `Cache-Control: max-age=59`.
+    /// assert!(metadata.age() < Duration::from_secs(1));
+    /// assert!(metadata.expires_in().unwrap() <= Duration::from_secs(60));
+    /// ```
+    pub fn get_entry<Q>(&self, key: &Q) -> Option<Entry<K, V>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get_entry_with_hash(key, self.base.hash(key))
+    }
+
+    pub(crate) fn get_entry_with_hash<Q>(&self, key: &Q, hash: u64) -> Option<Entry<K, V>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.base.get_entry_with_hash(key, hash)
+    }
+
+    /// Returns a [`PinnedEntry`][pinned-entry-struct] guard holding a clone of the
+    /// value corresponding to the key. The entry will not be evicted or expired
+    /// while the guard lives.
+    ///
+    /// This is useful when a long computation borrowed a cached artifact and you do
+    /// not want it to be evicted and recomputed by other threads in the meantime.
+    /// Note that the pinned entries still count towards the max capacity of the
+    /// cache, so pinning too many entries will make the cache exceed its capacity.
+    ///
+    /// Like `get`, this method is considered a cache read operation.
+    ///
+    /// [pinned-entry-struct]: ./struct.PinnedEntry.html
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use moka::sync::Cache;
+    /// use std::time::Duration;
+    ///
+    /// let cache = Cache::builder()
+    ///     .time_to_live(Duration::from_millis(100))
+    ///     .build();
+    /// cache.insert("model", vec![0u8; 1024]);
+    ///
+    /// {
+    ///     let model = cache.get_pinned(&"model").unwrap();
+    ///     std::thread::sleep(Duration::from_millis(200));
+    ///     // The entry has not expired as it is pinned.
+    ///     assert!(cache.contains_key(&"model"));
+    ///     assert_eq!(model.len(), 1024);
+    /// }
+    ///
+    /// // The guard has been dropped, so the entry has expired.
+    /// assert!(!cache.contains_key(&"model"));
+    /// ```
+    pub fn get_pinned<Q>(&self, key: &Q) -> Option<PinnedEntry<K, V>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get_pinned_with_hash(key, self.base.hash(key))
+    }
+
+    pub(crate) fn get_pinned_with_hash<Q>(&self, key: &Q, hash: u64) -> Option<PinnedEntry<K, V>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.base.get_pinned_with_hash(key, hash)
+    }
+
+    pub(crate) fn hash<Q>(&self, key: &Q) -> u64
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.base.hash(key)
+    }
+
     pub(crate) fn get_with_hash<Q>(&self, key: &Q, hash: u64, need_key: bool) -> Option<Entry<K, V>>
     where
         K: Borrow<Q>,
@@ -813,6 +1254,86 @@ where
         self.base.get_with_hash(key, hash, need_key)
     }
 
+    /// Similar to `get_with_hash`, but counts a miss on the class of the key if the
+    /// cache was built with `class_stats`. Used by the methods that insert the
+    /// value on a miss.
+    fn get_with_owned_key_and_hash(
+        &self,
+        key: &K,
+        hash: u64,
+        need_key: bool,
+    ) -> Option<Entry<K, V>> {
+        let ignore_if = None as Option<&mut fn(&V) -> bool>;
+        self.base
+            .get_with_hash_and_ignore_if(key, hash, ignore_if, need_key, |cs| cs.record_miss(key))
+    }
+
+    /// Similar to `get_with_owned_key_and_hash`, but clones the key on a miss to
+    /// count it on the class of the key.
+    fn get_with_key_ref_and_hash<Q>(
+        &self,
+        key: &Q,
+        hash: u64,
+        need_key: bool,
+    ) -> Option<Entry<K, V>>
+    where
+        K: Borrow<Q>,
+        Q: ToOwned<Owned = K> + Hash + Eq + ?Sized,
+    {
+        let ignore_if = None as Option<&mut fn(&V) -> bool>;
+        self.base
+            .get_with_hash_and_ignore_if(key, hash, ignore_if, need_key, |cs| {
+                cs.record_miss(&key.to_owned())
+            })
+    }
+
+    pub(crate) fn get_and_map<Q, T>(&self, key: &Q, map: impl FnOnce(&V) -> T) -> Option<T>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.base
+            .get_with_hash_and_map(key, self.base.hash(key), map)
+    }
+
+    /// Returns a read-only view of this cache, whose values are the parts of the
+    /// cached values selected by the `project` closure.
+    ///
+    /// The view shares the entries with this cache; it does not copy the cached
+    /// values. Its `get` method clones only the selected part of the value. So a
+    /// cache of large values can be handed to subsystems that only need a field of
+    /// the values, without exposing the whole values or the write methods.
+    ///
+    /// Reading an entry through the view is considered a cache read operation of
+    /// this cache, e.g. it resets the idle timer for the key.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use moka::sync::Cache;
+    ///
+    /// #[derive(Clone)]
+    /// struct User {
+    ///     name: String,
+    ///     avatar: Vec<u8>,
+    /// }
+    ///
+    /// let cache = Cache::new(100);
+    /// cache.insert(1, User { name: "alice".to_string(), avatar: vec![0; 1024] });
+    ///
+    /// // A view that exposes only the names of the users.
+    /// let names = cache.map_view(|user: &User| &user.name);
+    /// assert_eq!(names.get(&1), Some("alice".to_string()));
+    /// assert_eq!(names.get(&2), None);
+    /// ```
+    pub fn map_view<U, F>(&self, project: F) -> MapView<K, V, U, S>
+    where
+        U: ?Sized,
+        F: Fn(&V) -> &U + Send + Sync + 'static,
+    {
+        MapView::new(self.clone(), project)
+    }
+
     /// Takes a key `K` and returns an [`OwnedKeyEntrySelector`] that can be used to
     /// select or insert an entry.
     ///
@@ -972,33 +1493,221 @@ where
             .into_value()
     }
 
-    /// TODO: Remove this in v0.13.0.
-    /// Deprecated, replaced with
-    /// [`entry()::or_insert_with_if()`](./struct.OwnedKeyEntrySelector.html#method.or_insert_with_if)
-    #[deprecated(since = "0.10.0", note = "Replaced with `entry().or_insert_with_if()`")]
-    pub fn get_with_if(
+    /// Returns clones of the values corresponding to the `keys`. The values missing
+    /// in the cache are loaded at once by the `init` closure, e.g. by one query to
+    /// a database.
+    ///
+    /// The `init` closure is called with the missing keys and returns a map of the
+    /// values it found for them. The values are inserted into the cache per key.
+    /// The keys it did not return a value for are neither cached nor included in
+    /// the returned map.
+    ///
+    /// # Concurrent calls on the same keys
+    ///
+    /// Like [`optionally_get_with`](#method.optionally_get_with), concurrent calls
+    /// on the same missing key are coalesced. If some of the missing keys are being
+    /// loaded by other `get_all_with` or `optionally_get_with` calls, the `init`
+    /// closure is called only with the rest of the keys, and this method waits for
+    /// the other calls to get the values of those keys.
+    ///
+    /// The `init` closure is not called if there is no key left to load. It may be
+    /// called again with the keys whose `init` closure of another call panicked.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use moka::sync::Cache;
+    /// use std::collections::HashMap;
+    ///
+    /// let cache = Cache::new(100);
+    /// cache.insert(1, "one".to_string());
+    ///
+    /// let values = cache.get_all_with([1, 2, 3], |missing| {
+    ///     // Load the values of the missing keys by one query.
+    ///     assert_eq!(missing.len(), 2);
+    ///     missing
+    ///         .iter()
+    ///         .filter(|&&key| key != 3)
+    ///         .map(|&key| (key, format!("value of {key}")))
+    ///         .collect::<HashMap<_, _>>()
+    /// });
+    ///
+    /// assert_eq!(values.len(), 2);
+    /// assert_eq!(values[&1], "one");
+    /// assert_eq!(values[&2], "value of 2");
+    /// assert_eq!(cache.get(&2), Some("value of 2".to_string()));
+    /// assert!(!cache.contains_key(&3));
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// This method panics when the `init` closure has panicked. The other calls
+    /// waiting for the keys being loaded by the closure will retry loading them.
+    pub fn get_all_with<I>(
         &self,
-        key: K,
-        init: impl FnOnce() -> V,
-        replace_if: impl FnMut(&V) -> bool,
-    ) -> V {
-        let hash = self.base.hash(&key);
-        let key = Arc::new(key);
-        self.get_or_insert_with_hash_and_fun(key, hash, init, Some(replace_if), false)
-            .into_value()
+        keys: I,
+        mut init: impl FnMut(&[K]) -> HashMap<K, V>,
+    ) -> HashMap<K, V>
+    where
+        I: IntoIterator<Item = K>,
+        K: Clone,
+    {
+        let mut values = HashMap::new();
+        let mut missing = Vec::new();
+        for key in keys.into_iter().collect::<HashSet<_>>() {
+            let hash = self.base.hash(&key);
+            match self.get_with_owned_key_and_hash(&key, hash, false) {
+                Some(entry) => {
+                    values.insert(key, entry.into_value());
+                }
+                None => missing.push((Arc::new(key), hash)),
+            }
+        }
+        if missing.is_empty() {
+            return values;
+        }
+
+        let get = |key: &Arc<K>, hash| {
+            self.base
+                .get_with_hash_without_recording(key, hash, None::<&mut fn(&V) -> bool>)
+        };
+        let init = |keys: &[&Arc<K>]| {
+            let owned_keys = keys.iter().map(|k| K::clone(k)).collect::<Vec<_>>();
+            let mut loaded = init(&owned_keys);
+            keys.iter().map(|k| loaded.remove(&***k)).collect()
+        };
+        let insert =
+            |key: &Arc<K>, hash, value| self.insert_with_hash(Arc::clone(key), hash, value);
+
+        let loaded = self
+            .value_initializer
+            .init_all_or_read(&missing, get, init, insert);
+        for ((key, _), value) in missing.into_iter().zip(loaded) {
+            if let Some(value) = value {
+                values.insert(K::clone(&key), value);
+            }
+        }
+        values
     }
 
-    pub(crate) fn get_or_insert_with_hash_and_fun(
-        &self,
-        key: Arc<K>,
-        hash: u64,
+    /// Similar to [`get_with`](#method.get_with), but the `init` closure takes a
+    /// [`LoaderContext`][loader-context-struct]. A long running `init` closure can
+    /// call its `touch` method to publish a heartbeat, and other threads can see
+    /// how long the load has been running and when it was touched last by the
+    /// [`load_progress`](#method.load_progress) method, when the cache is built
+    /// with [`track_load_progress`][track-load-progress].
+    ///
+    /// If a [`refresh`](#method.refresh) of the key, or another `init` closure for
+    /// the key with a different return type, is already in flight, `load_progress`
+    /// returns the progress of that one. In that case, `init` is given a context
+    /// that is not recorded, so its `touch` calls are not visible to
+    /// `load_progress`.
+    ///
+    /// [loader-context-struct]: ./struct.LoaderContext.html
+    /// [track-load-progress]: ./struct.CacheBuilder.html#method.track_load_progress
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use moka::sync::Cache;
+    /// use std::{sync::Arc, thread, time::Duration};
+    ///
+    /// let cache = Arc::new(Cache::builder().track_load_progress().build());
+    ///
+    /// let loader = {
+    ///     let cache = Arc::clone(&cache);
+    ///     thread::spawn(move || {
+    ///         cache.get_with_context("report", |ctx| {
+    ///             for _ in 0..5 {
+    ///                 // Render a part of the report.
+    ///                 thread::sleep(Duration::from_millis(20));
+    ///                 ctx.touch();
+    ///             }
+    ///             "done"
+    ///         })
+    ///     })
+    /// };
+    ///
+    /// thread::sleep(Duration::from_millis(50));
+    /// // Decide whether to wait for the load by its progress.
+    /// if let Some(progress) = cache.load_progress(&"report") {
+    ///     println!("Loading the report for {:?}.", progress.elapsed());
+    ///     if progress.idle_time() > Duration::from_secs(10) {
+    ///         // The load seems stuck. Give up waiting.
+    ///     }
+    /// }
+    ///
+    /// assert_eq!(loader.join().unwrap(), "done");
+    /// assert!(cache.load_progress(&"report").is_none());
+    /// ```
+    pub fn get_with_context(&self, key: K, init: impl FnOnce(&LoaderContext) -> V) -> V {
+        let hash = self.base.hash(&key);
+        let key = Arc::new(key);
+        let mut replace_if = None as Option<fn(&V) -> bool>;
+        self.base
+            .get_with_hash_and_ignore_if(&key, hash, replace_if.as_mut(), false, |cs| {
+                cs.record_miss(&key)
+            })
+            .unwrap_or_else(|| self.insert_with_hash_and_fun(key, hash, init, replace_if, false))
+            .into_value()
+    }
+
+    /// Returns the progress of the in-flight `init` closure of the `get_with`
+    /// family of methods for the key, or `None` if the value is not being loaded.
+    ///
+    /// The `init` closures are recorded only when the cache is built with
+    /// [`track_load_progress`][track-load-progress]. Otherwise, this returns
+    /// only the progress of an in-flight [`refresh`](#method.refresh).
+    ///
+    /// The callers waiting for the load can use it to decide whether to keep
+    /// waiting or to time out, or to show the progress. The `init` closure passed
+    /// to [`get_with_context`](#method.get_with_context) can update the time it was
+    /// touched last.
+    ///
+    /// The key may be any borrowed form of the cache's key type, but `Hash` and `Eq`
+    /// on the borrowed form _must_ match those for the key type.
+    ///
+    /// [track-load-progress]: ./struct.CacheBuilder.html#method.track_load_progress
+    pub fn load_progress<Q>(&self, key: &Q) -> Option<LoadProgress>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let hash = self.base.hash(key);
+        self.value_initializer.load_progress(key, hash)
+    }
+
+    /// TODO: Remove this in v0.13.0.
+    /// Deprecated, replaced with
+    /// [`entry()::or_insert_with_if()`](./struct.OwnedKeyEntrySelector.html#method.or_insert_with_if)
+    #[deprecated(since = "0.10.0", note = "Replaced with `entry().or_insert_with_if()`")]
+    pub fn get_with_if(
+        &self,
+        key: K,
+        init: impl FnOnce() -> V,
+        replace_if: impl FnMut(&V) -> bool,
+    ) -> V {
+        let hash = self.base.hash(&key);
+        let key = Arc::new(key);
+        self.get_or_insert_with_hash_and_fun(key, hash, init, Some(replace_if), false)
+            .into_value()
+    }
+
+    pub(crate) fn get_or_insert_with_hash_and_fun(
+        &self,
+        key: Arc<K>,
+        hash: u64,
         init: impl FnOnce() -> V,
         mut replace_if: Option<impl FnMut(&V) -> bool>,
         need_key: bool,
     ) -> Entry<K, V> {
         self.base
-            .get_with_hash_and_ignore_if(&key, hash, replace_if.as_mut(), need_key)
-            .unwrap_or_else(|| self.insert_with_hash_and_fun(key, hash, init, replace_if, need_key))
+            .get_with_hash_and_ignore_if(&key, hash, replace_if.as_mut(), need_key, |cs| {
+                cs.record_miss(&key)
+            })
+            .unwrap_or_else(|| {
+                self.insert_with_hash_and_fun(key, hash, |_| init(), replace_if, need_key)
+            })
     }
 
     // Need to create new function instead of using the existing
@@ -1019,10 +1728,12 @@ where
         Q: ToOwned<Owned = K> + Hash + Eq + ?Sized,
     {
         self.base
-            .get_with_hash_and_ignore_if(key, hash, replace_if.as_mut(), need_key)
+            .get_with_hash_and_ignore_if(key, hash, replace_if.as_mut(), need_key, |cs| {
+                cs.record_miss(&key.to_owned())
+            })
             .unwrap_or_else(|| {
                 let key = Arc::new(key.to_owned());
-                self.insert_with_hash_and_fun(key, hash, init, replace_if, need_key)
+                self.insert_with_hash_and_fun(key, hash, |_| init(), replace_if, need_key)
             })
     }
 
@@ -1030,7 +1741,7 @@ where
         &self,
         key: Arc<K>,
         hash: u64,
-        init: impl FnOnce() -> V,
+        init: impl FnOnce(&LoaderContext) -> V,
         mut replace_if: Option<impl FnMut(&V) -> bool>,
         need_key: bool,
     ) -> Entry<K, V> {
@@ -1051,9 +1762,10 @@ where
 
         match self
             .value_initializer
-            .try_init_or_read(&key, type_id, get, init, insert, post_init)
+            .try_init_or_read(&key, hash, type_id, get, init, insert, post_init)
         {
             InitResult::Initialized(v) => {
+                self.base.record_load_success();
                 crossbeam_epoch::pin().flush();
                 Entry::new(k, v, true, false)
             }
@@ -1068,10 +1780,11 @@ where
         hash: u64,
         init: impl FnOnce() -> V,
     ) -> Entry<K, V> {
-        match self.base.get_with_hash(&key, hash, true) {
+        match self.get_with_owned_key_and_hash(&key, hash, true) {
             Some(entry) => entry,
             None => {
                 let value = init();
+                self.base.record_load_success();
                 self.insert_with_hash(Arc::clone(&key), hash, value.clone());
                 Entry::new(Some(key), value, true, false)
             }
@@ -1088,7 +1801,7 @@ where
         K: Borrow<Q>,
         Q: ToOwned<Owned = K> + Hash + Eq + ?Sized,
     {
-        match self.base.get_with_hash(key, hash, true) {
+        match self.get_with_key_ref_and_hash(key, hash, true) {
             Some(entry) => entry,
             None => {
                 let key = Arc::new(key.to_owned());
@@ -1208,6 +1921,156 @@ where
             .map(Entry::into_value)
     }
 
+    /// Returns a _clone_ of the value corresponding to the key. If the value does
+    /// not exist, calls the loaders registered by
+    /// [`CacheBuilder::loader`][builder-loader] in order, and inserts the value
+    /// returned by one of them. Returns `None` if none of the loaders has the value.
+    ///
+    /// Like `optionally_get_with`, concurrent calls on the same not-existing key
+    /// are coalesced into one call of the loaders. If no loader is registered, this
+    /// method works like `get`.
+    ///
+    /// Note that `get` method does not call the loaders, because its borrowed key
+    /// cannot always be turned into the owned key that the loaders take (see
+    /// [`CacheBuilder::loader`][builder-loader]).
+    ///
+    /// [builder-loader]: ./struct.CacheBuilder.html#method.loader
+    pub fn get_or_load<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: ToOwned<Owned = K> + Hash + Eq + ?Sized,
+    {
+        let hash = self.base.hash(key);
+        self.get_or_load_with_hash(key, hash)
+    }
+
+    pub(crate) fn get_or_load_with_hash<Q>(&self, key: &Q, hash: u64) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: ToOwned<Owned = K> + Hash + Eq + ?Sized,
+    {
+        let entry = match &self.loader_chain {
+            Some(chain) => {
+                let init = || chain.load(&key.to_owned());
+                self.get_or_optionally_insert_with_hash_by_ref_and_fun(key, hash, init, false)
+            }
+            None => self.get_with_hash(key, hash, false),
+        };
+        entry.map(Entry::into_value)
+    }
+
+    /// Reloads the value for an existing key in a background thread by calling the
+    /// loaders registered by [`CacheBuilder::loader`][builder-loader], and replaces
+    /// the cached value with the loaded one when it is ready. The current value
+    /// keeps being returned to the readers in the meantime.
+    ///
+    /// This is useful to refresh a key ahead of its expiration, or when you know
+    /// its value has just changed in the upstream. Returns `true` if a refresh has
+    /// started. Returns `false` if no loader is registered, the key does not exist
+    /// in the cache, a load or refresh for the key is already in flight, or too
+    /// many refreshes are waiting. The refreshes are run by a small pool of
+    /// background threads shared by the cache.
+    ///
+    /// If none of the loaders has the value, or the entry has been removed or
+    /// updated while reloading, the cache is not modified. While the refresh is in
+    /// flight, its
+    /// progress is returned by the [`load_progress`](#method.load_progress)
+    /// method.
+    ///
+    /// [builder-loader]: ./struct.CacheBuilder.html#method.loader
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use moka::sync::{Cache, Loader};
+    /// use std::sync::atomic::{AtomicU32, Ordering};
+    ///
+    /// #[derive(Default)]
+    /// struct Upstream(AtomicU32);
+    ///
+    /// impl Loader<&'static str, u32> for Upstream {
+    ///     fn load(&self, _key: &&'static str) -> Option<u32> {
+    ///         Some(self.0.fetch_add(1, Ordering::AcqRel))
+    ///     }
+    /// }
+    ///
+    /// let cache = Cache::builder().loader(Upstream::default()).build();
+    /// assert_eq!(cache.get_or_load(&"version"), Some(0));
+    ///
+    /// assert!(cache.refresh(&"version"));
+    /// // Wait for the refresh to finish.
+    /// while cache.load_progress(&"version").is_some() {
+    ///     std::thread::yield_now();
+    /// }
+    /// assert_eq!(cache.get(&"version"), Some(1));
+    /// ```
+    pub fn refresh<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let Some(chain) = &self.loader_chain else {
+            return false;
+        };
+        let hash = self.base.hash(key);
+        let Some(key) = self.base.get_key_with_hash(key, hash) else {
+            return false;
+        };
+        // Hold the current entry, so that the refresh can tell if it has been
+        // replaced in the meantime.
+        let Some(original) = self.base.scanning_get_value_entry(&key) else {
+            return false;
+        };
+        let Some(ctx) = self.value_initializer.start_load(&key, hash) else {
+            return false;
+        };
+
+        let cache = self.clone();
+        let (key1, ctx1) = (Arc::clone(&key), Arc::clone(&ctx));
+        let started = chain.spawn_refresh(move || {
+            use std::panic::{catch_unwind, resume_unwind, AssertUnwindSafe};
+
+            let (key, ctx) = (key1, ctx1);
+            let result = catch_unwind(AssertUnwindSafe(|| {
+                let value = cache.loader_chain.as_ref().and_then(|c| c.load(&key));
+                if let Some(value) = value {
+                    cache.replace_with_hash_if_unchanged(Arc::clone(&key), hash, value, &original);
+                }
+            }));
+            cache.value_initializer.finish_load(&key, hash, &ctx);
+            if let Err(payload) = result {
+                resume_unwind(payload);
+            }
+        });
+
+        if !started {
+            self.value_initializer.finish_load(&key, hash, &ctx);
+        }
+        started
+    }
+
+    /// Replaces the value of the entry for the key with `value` if the entry is
+    /// still `original`. Holding `original` keeps its address from being reused by
+    /// another entry, so comparing the addresses is enough.
+    fn replace_with_hash_if_unchanged(
+        &self,
+        key: Arc<K>,
+        hash: u64,
+        value: V,
+        original: &TrioArc<ValueEntry<K, V>>,
+    ) {
+        let Some(value) = self.intercept_insert(&key, value) else {
+            return;
+        };
+        let replace_if = |e: &ValueEntry<K, V>| std::ptr::eq(e, &**original);
+        if let Some((op, now)) =
+            self.base
+                .do_replace_with_hash_if(Arc::clone(&key), hash, value, replace_if)
+        {
+            self.finish_upsert(&key, op, now);
+        }
+    }
+
     pub(super) fn get_or_optionally_insert_with_hash_and_fun<F>(
         &self,
         key: Arc<K>,
@@ -1218,7 +2081,7 @@ where
     where
         F: FnOnce() -> Option<V>,
     {
-        let entry = self.get_with_hash(&key, hash, need_key);
+        let entry = self.get_with_owned_key_and_hash(&key, hash, need_key);
         if entry.is_some() {
             return entry;
         }
@@ -1238,7 +2101,7 @@ where
         K: Borrow<Q>,
         Q: ToOwned<Owned = K> + Hash + Eq + ?Sized,
     {
-        let entry = self.get_with_hash(key, hash, need_key);
+        let entry = self.get_with_key_ref_and_hash(key, hash, need_key);
         if entry.is_some() {
             return entry;
         }
@@ -1273,16 +2136,23 @@ where
         let type_id = ValueInitializer::<K, V, S>::type_id_for_optionally_get_with();
         let post_init = ValueInitializer::<K, V, S>::post_init_for_optionally_get_with;
 
-        match self
-            .value_initializer
-            .try_init_or_read(&key, type_id, get, init, insert, post_init)
-        {
+        match self.value_initializer.try_init_or_read(
+            &key,
+            hash,
+            type_id,
+            get,
+            |_| init(),
+            insert,
+            post_init,
+        ) {
             InitResult::Initialized(v) => {
+                self.base.record_load_success();
                 crossbeam_epoch::pin().flush();
                 Some(Entry::new(k, v, true, false))
             }
             InitResult::ReadExisting(v) => Some(Entry::new(k, v, false, false)),
             InitResult::InitErr(_) => {
+                self.base.record_load_failure();
                 crossbeam_epoch::pin().flush();
                 None
             }
@@ -1412,7 +2282,7 @@ where
         F: FnOnce() -> Result<V, E>,
         E: Send + Sync + 'static,
     {
-        if let Some(entry) = self.get_with_hash(&key, hash, need_key) {
+        if let Some(entry) = self.get_with_owned_key_and_hash(&key, hash, need_key) {
             return Ok(entry);
         }
 
@@ -1432,7 +2302,7 @@ where
         K: Borrow<Q>,
         Q: ToOwned<Owned = K> + Hash + Eq + ?Sized,
     {
-        if let Some(entry) = self.get_with_hash(key, hash, false) {
+        if let Some(entry) = self.get_with_key_ref_and_hash(key, hash, false) {
             return Ok(entry);
         }
 
@@ -1464,19 +2334,39 @@ where
             None
         };
 
+        // If the `init` closure has recently failed for the key, return the cached
+        // error without calling it again.
+        if let Some(ec) = &self.error_cache {
+            let now = self.base.current_time_from_expiration_clock();
+            if let Some(e) = ec.get::<E>(&*key, now) {
+                return Err(e);
+            }
+        }
+
         let type_id = ValueInitializer::<K, V, S>::type_id_for_try_get_with::<E>();
         let post_init = ValueInitializer::<K, V, S>::post_init_for_try_get_with;
 
-        match self
-            .value_initializer
-            .try_init_or_read(&key, type_id, get, init, insert, post_init)
-        {
+        match self.value_initializer.try_init_or_read(
+            &key,
+            hash,
+            type_id,
+            get,
+            |_| init(),
+            insert,
+            post_init,
+        ) {
             InitResult::Initialized(v) => {
+                self.base.record_load_success();
                 crossbeam_epoch::pin().flush();
                 Ok(Entry::new(k, v, true, false))
             }
             InitResult::ReadExisting(v) => Ok(Entry::new(k, v, false, false)),
             InitResult::InitErr(e) => {
+                self.base.record_load_failure();
+                if let Some(ec) = &self.error_cache {
+                    let now = self.base.current_time_from_expiration_clock();
+                    ec.insert(Arc::clone(&key), Arc::clone(&e), now);
+                }
                 crossbeam_epoch::pin().flush();
                 Err(e)
             }
@@ -1492,12 +2382,267 @@ where
         self.insert_with_hash(key, hash, value);
     }
 
-    pub(crate) fn insert_with_hash(&self, key: Arc<K>, hash: u64, value: V) {
-        if self.base.is_map_disabled() {
-            return;
+    /// Inserts a key-value pair into the cache, with the expiration computed by the
+    /// `expire_after` closure for this insert only.
+    ///
+    /// The closure is called with the key, the value and the current time, and
+    /// returns the duration until the entry expires, or `None` if it does not
+    /// expire by itself. This is useful when the lifetime of a value is known only
+    /// at the time it was fetched, e.g. from the `max-age` of an HTTP response,
+    /// and there is no need to register an [`Expiry`][expiry-trait] to the cache.
+    ///
+    /// The returned duration overrides the one from the `Expiry` of the cache, if
+    /// any, for this insert. The time-to-live and time-to-idle of the cache still
+    /// apply. Later updates of the entry by other methods are handled by the
+    /// `Expiry` as usual, and otherwise keep the expiration time set here.
+    ///
+    /// [expiry-trait]: ../policy/trait.Expiry.html
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use moka::sync::Cache;
+    /// use std::time::Duration;
+    ///
+    /// struct Response {
+    ///     body: String,
+    ///     max_age: Duration,
+    /// }
+    ///
+    /// let cache: Cache<&str, std::sync::Arc<Response>> = Cache::new(100);
+    /// let response = Response {
+    ///     body: "hello".to_string(),
+    ///     max_age: Duration::from_secs(60),
+    /// };
+    /// cache.insert_with_expiry("/hello", response.into(), |_key, res, _now| {
+    ///     Some(res.max_age)
+    /// });
+    /// assert!(cache.contains_key(&"/hello"));
+    /// ```
+    pub fn insert_with_expiry(
+        &self,
+        key: K,
+        value: V,
+        expire_after: impl FnOnce(&K, &V, StdInstant) -> Option<Duration>,
+    ) {
+        let hash = self.base.hash(&key);
+        let key = Arc::new(key);
+        self.insert_with_hash_and_expiry(key, hash, value, Some(expire_after));
+    }
+
+    /// Inserts a key-value pair into the cache only if the cache does not have a
+    /// live entry for the key. An existing entry is never replaced unless it has
+    /// been expired or invalidated.
+    ///
+    /// Returns `None` if the value was inserted, or `Some` with a clone of the
+    /// existing value if it was kept. The check and the insert are done
+    /// atomically, so when multiple threads race to insert the same key, exactly
+    /// one of them gets `None`. This is useful for "first writer wins" semantics
+    /// such as leader claims and deduplication windows.
+    ///
+    /// Unlike [`get_with`](#method.get_with), this method takes an already
+    /// computed value and does not wait for other threads.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use moka::sync::Cache;
+    ///
+    /// let cache = Cache::new(100);
+    ///
+    /// assert_eq!(cache.insert_if_absent("leader", "node-1"), None);
+    /// assert_eq!(cache.insert_if_absent("leader", "node-2"), Some("node-1"));
+    /// assert_eq!(cache.get(&"leader"), Some("node-1"));
+    /// ```
+    pub fn insert_if_absent(&self, key: K, value: V) -> Option<V> {
+        let hash = self.base.hash(&key);
+        let key = Arc::new(key);
+        self.insert_with_hash_unless(key, hash, value, |_| true)
+    }
+
+    /// Inserts a key-value pair into the cache unless the cache has a live entry
+    /// for the key and `predicate` returns `true` for its value.
+    ///
+    /// Returns `None` if the value was inserted, or `Some` with a clone of the
+    /// existing value if it was kept. The predicate is evaluated and the value is
+    /// inserted atomically, so no other insert to the key can happen in between.
+    /// This is useful for the conflict resolution such as "last writer wins" with
+    /// versioned values.
+    ///
+    /// The predicate is not called if the key does not have a live entry. It may be
+    /// called more than once when the insert conflicts with other concurrent
+    /// updates to the internal hash table, so it should not have side effects.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use moka::sync::Cache;
+    ///
+    /// #[derive(Clone, Debug, PartialEq)]
+    /// struct Versioned {
+    ///     version: u64,
+    ///     data: &'static str,
+    /// }
+    ///
+    /// let cache = Cache::new(100);
+    /// let write = |version, data| {
+    ///     cache.insert_unless("key", Versioned { version, data }, |current| {
+    ///         current.version >= version
+    ///     })
+    /// };
+    ///
+    /// assert_eq!(write(2, "two"), None);
+    /// // The older write is rejected.
+    /// assert_eq!(write(1, "one").map(|v| v.version), Some(2));
+    /// assert_eq!(write(3, "three"), None);
+    /// assert_eq!(cache.get(&"key").unwrap().data, "three");
+    /// ```
+    pub fn insert_unless(&self, key: K, value: V, predicate: impl FnMut(&V) -> bool) -> Option<V> {
+        let hash = self.base.hash(&key);
+        let key = Arc::new(key);
+        self.insert_with_hash_unless(key, hash, value, predicate)
+    }
+
+    /// Inserts a key-value pair into the cache only if the current version of the
+    /// entry is `expected_version`, or if the cache has no live entry for the key
+    /// when `expected_version` is `None`.
+    ///
+    /// Every insert or update gives the value a new version, which can be read by
+    /// [`EntryMetadata::version`][version] from the metadata returned by the
+    /// [`get_entry`](#method.get_entry) method. This enables optimistic
+    /// concurrency: read the entry, compute the new value, and write it back with
+    /// the version read. If someone else has updated the entry in between, the
+    /// write is rejected.
+    ///
+    /// Returns `Ok` with the version of the inserted value, or `Err` with the
+    /// current version of the entry (`None` if the cache has no live entry) if the
+    /// versions did not match. Also returns `Err` with `expected_version` if the
+    /// value was rejected by an interceptor.
+    ///
+    /// Note that if the entry is invalidated or evicted right after its version was
+    /// checked, the value may be inserted as a new entry.
+    ///
+    /// [version]: ../struct.EntryMetadata.html#method.version
+    ///
+    /// # Panics
+    ///
+    /// Panics if the cache was not built with the
+    /// [`support_entry_versions`][support-entry-versions] method of the builder.
+    ///
+    /// [support-entry-versions]: ./struct.CacheBuilder.html#method.support_entry_versions
+    ///
+    /// Requires the `entry-versions` crate feature.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use moka::sync::Cache;
+    ///
+    /// let cache = Cache::builder()
+    ///     .max_capacity(100)
+    ///     .support_entry_versions()
+    ///     .build();
+    ///
+    /// let v1 = cache.insert_if_version("counter", None, 1).unwrap();
+    /// // The key now exists, so inserting it as a new entry fails.
+    /// assert_eq!(cache.insert_if_version("counter", None, 10), Err(Some(v1)));
+    ///
+    /// let entry = cache.get_entry(&"counter").unwrap();
+    /// let version = entry.metadata().unwrap().version();
+    /// assert_eq!(version, v1);
+    ///
+    /// let v2 = cache
+    ///     .insert_if_version("counter", Some(version), entry.value() + 1)
+    ///     .unwrap();
+    /// assert!(v2 > v1);
+    ///
+    /// // A writer holding the stale version is rejected.
+    /// assert_eq!(cache.insert_if_version("counter", Some(v1), 100), Err(Some(v2)));
+    /// assert_eq!(cache.get(&"counter"), Some(2));
+    /// ```
+    #[cfg(feature = "entry-versions")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "entry-versions")))]
+    pub fn insert_if_version(
+        &self,
+        key: K,
+        expected_version: Option<u64>,
+        value: V,
+    ) -> Result<u64, Option<u64>> {
+        assert!(
+            self.base.is_entry_versions_enabled(),
+            "insert_if_version requires a cache built with support_entry_versions"
+        );
+        let hash = self.base.hash(&key);
+        let key = Arc::new(key);
+
+        // The internal hash table cannot refuse to insert a key that is not present,
+        // so check the absent case here.
+        if expected_version.is_some() {
+            let current = self.base.scanning_get_version(&key);
+            if current != expected_version {
+                return Err(current);
+            }
+        }
+
+        let keep_if = |e: &ValueEntry<K, V>| Some(e.version()) != expected_version;
+        match self.insert_with_hash_unless_entry(key, hash, value, keep_if) {
+            Ok(Some(version)) => Ok(version),
+            Ok(None) => Err(expected_version),
+            Err(existing) => Err(Some(existing.version())),
         }
+    }
+
+    /// Pushes out the expiration of the entry for the key by `duration` without
+    /// rewriting its value. This is useful for validation-style caches, where a
+    /// cheap revalidation of the cached value should renew the entry.
+    ///
+    /// Returns `true` if the entry was extended, or `false` if the cache has no
+    /// live entry for the key.
+    ///
+    /// The per-entry expiration time set by the [`Expiry`][expiry-trait] or the
+    /// [`insert_with_expiry`](#method.insert_with_expiry) method is pushed out by
+    /// `duration`. The time-to-live is counted from the last write, so the entry is
+    /// treated as if it was written `duration` later, but not later than now. In
+    /// other words, the time-to-live deadline is pushed out at most to a full
+    /// time-to-live from now. The time-to-idle is not changed.
+    ///
+    /// Unlike updating the entry, this method does not give the value a new
+    /// version, nor notify the eviction listener.
+    ///
+    /// [expiry-trait]: ../trait.Expiry.html
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use moka::sync::Cache;
+    /// use std::time::Duration;
+    ///
+    /// let cache = Cache::builder()
+    ///     .time_to_live(Duration::from_secs(60))
+    ///     .build();
+    ///
+    /// cache.insert("etag", "abc".to_string());
+    /// // The origin server confirmed that the cached value is still valid.
+    /// assert!(cache.extend_ttl(&"etag", Duration::from_secs(60)));
+    /// assert!(!cache.extend_ttl(&"unknown", Duration::from_secs(60)));
+    /// ```
+    pub fn extend_ttl<Q>(&self, key: &Q, duration: Duration) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let hash = self.base.hash(key);
+        self.extend_ttl_with_hash(key, hash, duration)
+    }
 
-        let (op, now) = self.base.do_insert_with_hash(key, hash, value);
+    pub(crate) fn extend_ttl_with_hash<Q>(&self, key: &Q, hash: u64, duration: Duration) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let Some((op, now)) = self.base.do_extend_ttl_with_hash(key, hash, duration) else {
+            return false;
+        };
         let hk = self.base.housekeeper.as_ref();
         Self::schedule_write_op(
             self.base.inner.as_ref(),
@@ -1506,21 +2651,144 @@ where
             now,
             hk,
         )
-        .expect("Failed to insert");
+        .unwrap_or_else(|_| self.base.degrade());
+        true
     }
 
-    pub(crate) fn compute_with_hash_and_fun<F>(
+    /// Passes the value to insert to the interceptors, if any. Returns `None` if
+    /// the insert was vetoed.
+    fn intercept_insert(&self, key: &K, value: V) -> Option<V> {
+        match &self.base.interceptors {
+            Some(icpt) => icpt.before_insert(key, value),
+            None => Some(value),
+        }
+    }
+
+    fn intercept_after_insert(&self, key: &K, op: &WriteOp<K, V>) {
+        if let (Some(icpt), WriteOp::Upsert { value_entry, .. }) = (&self.base.interceptors, op) {
+            icpt.after_insert(key, &value_entry.value);
+        }
+    }
+
+    pub(crate) fn insert_with_hash(&self, key: Arc<K>, hash: u64, value: V) {
+        let expire_after = None as Option<fn(&K, &V, StdInstant) -> Option<Duration>>;
+        self.insert_with_hash_and_expiry(key, hash, value, expire_after);
+    }
+
+    pub(crate) fn insert_with_hash_and_expiry<F>(
         &self,
         key: Arc<K>,
         hash: u64,
-        f: F,
-    ) -> compute::CompResult<K, V>
-    where
-        F: FnOnce(Option<Entry<K, V>>) -> compute::Op<V>,
+        value: V,
+        expire_after: Option<F>,
+    ) where
+        F: FnOnce(&K, &V, StdInstant) -> Option<Duration>,
     {
-        let post_init = ValueInitializer::<K, V, S>::post_init_for_compute_with;
-        match self
-            .value_initializer
+        let Some(value) = self.intercept_insert(&key, value) else {
+            return;
+        };
+
+        if let Some(ec) = &self.error_cache {
+            ec.remove(&*key);
+        }
+
+        if self.base.is_map_disabled() {
+            self.base.notify_rejected(key, value);
+            return;
+        }
+
+        let (op, now) = self.base.do_insert_with_hash(Arc::clone(&key), hash, value);
+        if let Some(expire_after) = expire_after {
+            self.base.set_expiration_time_for_op(&op, now, expire_after);
+        }
+        self.intercept_after_insert(&key, &op);
+        let hk = self.base.housekeeper.as_ref();
+        Self::schedule_write_op(
+            self.base.inner.as_ref(),
+            &self.base.write_op_ch,
+            op,
+            now,
+            hk,
+        )
+        .unwrap_or_else(|_| self.base.degrade());
+    }
+
+    /// Inserts the value unless the cache has a live entry for the key whose value
+    /// satisfies `keep_if`. Returns a clone of the existing value if it was kept.
+    pub(crate) fn insert_with_hash_unless(
+        &self,
+        key: Arc<K>,
+        hash: u64,
+        value: V,
+        mut keep_if: impl FnMut(&V) -> bool,
+    ) -> Option<V> {
+        match self.insert_with_hash_unless_entry(key, hash, value, |e| keep_if(&e.value)) {
+            Ok(_) => None,
+            Err(existing) => Some(existing.value.clone()),
+        }
+    }
+
+    /// Inserts the value unless the cache has a live entry for the key that
+    /// satisfies `keep_if`. Returns the version of the inserted value, or `None` if
+    /// the value was rejected by an interceptor or the disabled map. Returns the
+    /// existing entry as `Err` if it was kept.
+    fn insert_with_hash_unless_entry(
+        &self,
+        key: Arc<K>,
+        hash: u64,
+        value: V,
+        keep_if: impl FnMut(&ValueEntry<K, V>) -> bool,
+    ) -> Result<Option<u64>, TrioArc<ValueEntry<K, V>>> {
+        let Some(value) = self.intercept_insert(&key, value) else {
+            return Ok(None);
+        };
+
+        if self.base.is_map_disabled() {
+            self.base.notify_rejected(key, value);
+            return Ok(None);
+        }
+
+        let (op, now) =
+            self.base
+                .do_insert_with_hash_unless(Arc::clone(&key), hash, value, Some(keep_if))?;
+        let version = match &op {
+            WriteOp::Upsert { value_entry, .. } => Some(value_entry.version()),
+            _ => None,
+        };
+        self.finish_upsert(&key, op, now);
+        Ok(version)
+    }
+
+    /// Runs the interceptors, clears the cached error and schedules the write
+    /// operation after a value has been inserted or replaced.
+    fn finish_upsert(&self, key: &Arc<K>, op: WriteOp<K, V>, now: Instant) {
+        self.intercept_after_insert(key, &op);
+        if let Some(ec) = &self.error_cache {
+            ec.remove(&**key);
+        }
+        let hk = self.base.housekeeper.as_ref();
+        Self::schedule_write_op(
+            self.base.inner.as_ref(),
+            &self.base.write_op_ch,
+            op,
+            now,
+            hk,
+        )
+        .unwrap_or_else(|_| self.base.degrade());
+    }
+
+    pub(crate) fn compute_with_hash_and_fun<F>(
+        &self,
+        key: Arc<K>,
+        hash: u64,
+        f: F,
+    ) -> compute::CompResult<K, V>
+    where
+        F: FnOnce(Option<Entry<K, V>>) -> compute::Op<V>,
+    {
+        let post_init = ValueInitializer::<K, V, S>::post_init_for_compute_with;
+        match self
+            .value_initializer
             .try_compute(key, hash, self, f, post_init, true)
         {
             Ok(result) => result,
@@ -1589,10 +2857,100 @@ where
         self.invalidate_with_hash(key, hash, true)
     }
 
+    /// Discards the cached value for the key and returns it, only if the
+    /// `predicate` returns `true` for the value. Otherwise, returns `None` and
+    /// keeps the entry.
+    ///
+    /// The predicate is evaluated and the entry is removed atomically, so two
+    /// threads calling this method for the same key never get the same value. This
+    /// is useful for single-use values like tokens, where `get` followed by
+    /// `invalidate` would race. Expired entries are never returned.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use moka::sync::Cache;
+    ///
+    /// #[derive(Clone)]
+    /// struct Token {
+    ///     user: &'static str,
+    /// }
+    ///
+    /// let cache = Cache::new(100);
+    /// cache.insert("t1", Token { user: "alice" });
+    ///
+    /// // The token is not for bob, so it is not consumed.
+    /// assert!(cache.get_and_invalidate_if(&"t1", |t| t.user == "bob").is_none());
+    /// assert!(cache.contains_key(&"t1"));
+    ///
+    /// // The token is consumed by alice, and cannot be used again.
+    /// assert!(cache.get_and_invalidate_if(&"t1", |t| t.user == "alice").is_some());
+    /// assert!(cache.get_and_invalidate_if(&"t1", |t| t.user == "alice").is_none());
+    /// ```
+    pub fn get_and_invalidate_if<Q>(&self, key: &Q, predicate: impl FnMut(&V) -> bool) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let hash = self.base.hash(key);
+        self.invalidate_with_hash_if(key, hash, true, Some(predicate))
+    }
+
+    /// Discards the cached value for the key and returns it, if the entry exists
+    /// and is neither expired nor invalidated.
+    ///
+    /// Unlike the [`remove`](#method.remove) method, this method does not return
+    /// the value of an expired entry; it is left to the housekeeping to remove it
+    /// and notify the eviction listener with the `Expired` cause. The entry is
+    /// checked and removed atomically, and the eviction listener is notified only
+    /// once with the `Explicit` cause, so the caller can both purge the entry and
+    /// consume its final value.
+    ///
+    /// The key may be any borrowed form of the cache's key type, but `Hash` and `Eq`
+    /// on the borrowed form _must_ match those for the key type.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use moka::sync::Cache;
+    ///
+    /// let cache = Cache::new(100);
+    /// cache.insert("session", String::from("cart: 3 items"));
+    ///
+    /// assert_eq!(
+    ///     cache.invalidate_and_take(&"session"),
+    ///     Some(String::from("cart: 3 items"))
+    /// );
+    /// assert_eq!(cache.invalidate_and_take(&"session"), None);
+    /// ```
+    pub fn invalidate_and_take<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get_and_invalidate_if(key, |_| true)
+    }
+
     pub(crate) fn invalidate_with_hash<Q>(&self, key: &Q, hash: u64, need_value: bool) -> Option<V>
     where
         K: Borrow<Q>,
         Q: Hash + Eq + ?Sized,
+    {
+        let condition = None as Option<fn(&V) -> bool>;
+        self.invalidate_with_hash_if(key, hash, need_value, condition)
+    }
+
+    pub(crate) fn invalidate_with_hash_if<Q, C>(
+        &self,
+        key: &Q,
+        hash: u64,
+        need_value: bool,
+        condition: Option<C>,
+    ) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        C: FnMut(&V) -> bool,
     {
         // Lock the key for removal if blocking removal notification is enabled.
         let mut kl = None;
@@ -1614,7 +2972,24 @@ where
             }
         }
 
-        match self.base.remove_entry(key, hash) {
+        let mut was_live = true;
+        let maybe_kv = match (condition, &self.base.interceptors) {
+            (None, None) => self.base.remove_entry(key, hash),
+            // The interceptors may keep a live entry, but an expired entry is
+            // removed (and returned) in the same way as without them.
+            (None, Some(icpt)) => self
+                .base
+                .remove_entry_unless_live_and(key, hash, |k, v| !icpt.before_invalidate(k, v))
+                .map(|(kv, is_live)| {
+                    was_live = is_live;
+                    kv
+                }),
+            (Some(mut condition), icpt) => self.base.remove_entry_if(key, hash, |k, v| {
+                condition(v) && icpt.as_ref().map_or(true, |i| i.before_invalidate(k, v))
+            }),
+        };
+
+        match maybe_kv {
             None => None,
             Some(kv) => {
                 let now = self.base.current_time_from_expiration_clock();
@@ -1632,8 +3007,12 @@ where
                 std::mem::drop(klg);
                 std::mem::drop(kl);
 
+                if let (Some(icpt), true) = (&self.base.interceptors, was_live) {
+                    icpt.after_invalidate(&kv.key, &kv.entry.value);
+                }
+
                 let maybe_v = if need_value {
-                    Some(kv.entry.value.clone())
+                    self.base.intercept_get(&kv.entry).map(Cow::into_owned)
                 } else {
                     None
                 };
@@ -1650,7 +3029,7 @@ where
                     now,
                     hk,
                 )
-                .expect("Failed to remove");
+                .unwrap_or_else(|_| self.base.degrade());
                 crossbeam_epoch::pin().flush();
                 maybe_v
             }
@@ -1671,6 +3050,38 @@ where
         self.base.invalidate_all();
     }
 
+    /// Discards all cached values that have not been inserted or updated within
+    /// the last `age`.
+    ///
+    /// Like `invalidate_all`, this method returns immediately and the invalidated
+    /// values are evicted later by the housekeeping tasks, but `get` will never
+    /// return them. Unlike `invalidate_entries_if`, it does not need a predicate
+    /// closure, nor the `support_invalidation_closures` option, and does not
+    /// wait for the entries to be expired lazily.
+    ///
+    /// Internally, this moves the watermark used by `invalidate_all` forward to
+    /// `age` before now. So the entries written before the last `invalidate_all`
+    /// remain invalidated even if a longer `age` is given.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use moka::sync::Cache;
+    /// use std::time::Duration;
+    ///
+    /// let cache = Cache::new(100);
+    /// cache.insert("old", 1);
+    /// std::thread::sleep(Duration::from_millis(50));
+    /// cache.insert("new", 2);
+    ///
+    /// cache.invalidate_entries_older_than(Duration::from_millis(25));
+    /// assert_eq!(cache.get(&"old"), None);
+    /// assert_eq!(cache.get(&"new"), Some(2));
+    /// ```
+    pub fn invalidate_entries_older_than(&self, age: Duration) {
+        self.base.invalidate_entries_older_than(age);
+    }
+
     /// Discards cached values that satisfy a predicate.
     ///
     /// `invalidate_entries_if` takes a closure that returns `true` or `false`. This
@@ -1713,6 +3124,42 @@ where
         self.base.invalidate_entries_if(predicate)
     }
 
+    pub(crate) fn is_invalidation_predicate_registered(&self, id: &PredicateId) -> bool {
+        self.base.is_invalidation_predicate_registered(id)
+    }
+
+    /// Removes all entries from the cache, and returns an iterator yielding their
+    /// keys and values in arbitrary order.
+    ///
+    /// Unlike `invalidate_all`, the entries are removed before this method returns,
+    /// so the contents can be persisted before the cache is rebuilt. If an eviction
+    /// listener is set, it is called for each removed entry with the `Explicit`
+    /// cause. Expired or invalidated entries are notified with their original
+    /// cause, and are not yielded.
+    ///
+    /// Entries inserted by other threads while this method is running may or may
+    /// not be removed.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use moka::sync::Cache;
+    ///
+    /// let cache = Cache::new(100);
+    /// cache.insert("Julia", 14);
+    /// cache.insert("Mike", 26);
+    ///
+    /// let mut entries = cache.drain().map(|(k, v)| (*k, v)).collect::<Vec<_>>();
+    /// entries.sort_unstable();
+    /// assert_eq!(entries, vec![("Julia", 14), ("Mike", 26)]);
+    ///
+    /// assert!(cache.get(&"Julia").is_none());
+    /// assert_eq!(cache.entry_count(), 0);
+    /// ```
+    pub fn drain(&self) -> IntoIter<K, V> {
+        IntoIter::new(self.take_all_entries(true))
+    }
+
     /// Creates an iterator visiting all key-value pairs in arbitrary order. The
     /// iterator element type is `(Arc<K>, V)`, where `V` is a clone of a stored
     /// value.
@@ -1764,64 +3211,746 @@ where
         Iter::with_single_cache_segment(&self.base, self.num_cht_segments())
     }
 
-    /// Performs any pending maintenance operations needed by the cache.
-    pub fn run_pending_tasks(&self) {
-        if let Some(hk) = &self.base.housekeeper {
-            hk.run_pending_tasks(&*self.base.inner);
-        }
+    /// Creates a parallel iterator visiting all key-value pairs in arbitrary order.
+    /// The iterator element type is `(Arc<K>, V)`, where `V` is a clone of a stored
+    /// value.
+    ///
+    /// The segments of the internal concurrent hash table are scanned in parallel
+    /// on the rayon thread pool, so bulk exports and scans of large caches can use
+    /// all available cores. It gives the same guarantees as [`iter`](#method.iter)
+    /// method, and does not update the historic popularity estimator or reset idle
+    /// timers for keys either.
+    ///
+    /// Requires the `rayon` crate feature.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use moka::sync::Cache;
+    /// use rayon::prelude::*;
+    ///
+    /// let cache = Cache::new(100);
+    /// cache.insert(1, 10);
+    /// cache.insert(2, 20);
+    ///
+    /// let sum: i32 = cache.par_iter().map(|(_k, v)| v).sum();
+    /// assert_eq!(sum, 30);
+    /// ```
+    ///
+    #[cfg(feature = "rayon")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "rayon")))]
+    pub fn par_iter(&self) -> crate::sync::ParIter<'_, K, V> {
+        crate::sync::ParIter::new(Box::new([self]))
     }
-}
-
-impl<'a, K, V, S> IntoIterator for &'a Cache<K, V, S>
-where
-    K: Hash + Eq + Send + Sync + 'static,
-    V: Clone + Send + Sync + 'static,
-    S: BuildHasher + Clone + Send + Sync + 'static,
-{
-    type Item = (Arc<K>, V);
-
-    type IntoIter = Iter<'a, K, V>;
 
-    fn into_iter(self) -> Self::IntoIter {
-        self.iter()
+    /// Creates a stream of the length-prefixed records of the entries, to replicate
+    /// the contents of the cache to another cache, e.g. over the network, without
+    /// holding them all in memory.
+    ///
+    /// The `encode` closure is called for each entry when the stream is read, and
+    /// should append the bytes of the entry to the given buffer. Each record is a
+    /// 4-byte big-endian length followed by those bytes. The entries are visited in
+    /// the same way as [`iter`](#method.iter) does, so the stream reflects the
+    /// entries inserted or removed while it is read, or not. See
+    /// [`ExportStream`][export-stream] for how to take the records.
+    ///
+    /// The records are read back by [`import_stream`](#method.import_stream).
+    ///
+    /// # Panics
+    ///
+    /// Reading the stream panics if the `encode` closure writes 4 GiB or more for a
+    /// single entry.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use moka::sync::Cache;
+    /// use std::io;
+    ///
+    /// let cache = Cache::new(100);
+    /// cache.insert(1u32, "alice".to_string());
+    ///
+    /// let mut bytes = Vec::new();
+    /// let mut stream = cache.export_stream(|key, value, buf| {
+    ///     buf.extend_from_slice(&key.to_be_bytes());
+    ///     buf.extend_from_slice(value.as_bytes());
+    /// });
+    /// io::copy(&mut stream, &mut bytes).unwrap();
+    /// assert_eq!(bytes, b"\0\0\0\x09\0\0\0\x01alice");
+    ///
+    /// let replica: Cache<u32, String> = Cache::new(100);
+    /// let count = replica.import_stream(&bytes[..], |record| {
+    ///     let (key, value) = record.split_at(4);
+    ///     let value = String::from_utf8(value.to_vec())
+    ///         .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    ///     Ok((u32::from_be_bytes(key.try_into().unwrap()), value))
+    /// });
+    /// assert_eq!(count.unwrap(), 1);
+    /// assert_eq!(replica.get(&1), Some("alice".to_string()));
+    /// ```
+    ///
+    /// [export-stream]: ./struct.ExportStream.html
+    pub fn export_stream<F>(&self, encode: F) -> ExportStream<'_, K, V, F>
+    where
+        F: FnMut(&K, &V, &mut Vec<u8>),
+    {
+        ExportStream::new(self.iter(), encode)
     }
-}
 
-//
-// Iterator support
-//
-impl<K, V, S> ScanningGet<K, V> for Cache<K, V, S>
-where
-    K: Hash + Eq + Send + Sync + 'static,
-    V: Clone + Send + Sync + 'static,
-    S: BuildHasher + Clone + Send + Sync + 'static,
-{
-    fn num_cht_segments(&self) -> usize {
-        self.base.num_cht_segments()
+    /// Reads the records written by an [`ExportStream`][export-stream] from the
+    /// `reader` until its end, and inserts the entries decoded by the `decode`
+    /// closure. Returns the number of the inserted entries.
+    ///
+    /// The `decode` closure is given the bytes of a record without the length
+    /// prefix. The entries are inserted as they are read, so the entries before an
+    /// error stay in the cache. An error is returned if the `reader` or the
+    /// `decode` closure fails, or the `reader` ends in the middle of a record.
+    ///
+    /// The entries are inserted as new ones, so their expiration and eviction
+    /// states on the exporting cache are not carried over.
+    ///
+    /// See [`export_stream`](#method.export_stream) for an example.
+    ///
+    /// [export-stream]: ./struct.ExportStream.html
+    pub fn import_stream<R, F>(&self, reader: R, decode: F) -> io::Result<u64>
+    where
+        R: io::Read,
+        F: FnMut(&[u8]) -> io::Result<(K, V)>,
+    {
+        stream::import_records(reader, decode, |key, value| self.insert(key, value))
     }
 
-    fn scanning_get(&self, key: &Arc<K>) -> Option<V> {
-        self.base.scanning_get(key)
+    /// Returns a `HashMap` holding the clones of the keys and values of the entries
+    /// currently in the cache.
+    ///
+    /// This is a convenient way to hand the contents of the cache to a
+    /// serialization layer or to make assertions in tests. The map is built with
+    /// the [`iter`](#method.iter) method, so it has the same guarantees: it is
+    /// weakly consistent, and the entries inserted or removed while building the
+    /// map may or may not be included. Like the `iter` method, it does not update
+    /// the historic popularity estimator or reset idle timers for keys.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use moka::sync::Cache;
+    /// use std::collections::HashMap;
+    ///
+    /// let cache = Cache::new(100);
+    /// cache.insert("Julia", 14);
+    /// cache.insert("Alice", 13);
+    ///
+    /// let map = cache.to_hash_map();
+    /// assert_eq!(map, HashMap::from([("Julia", 14), ("Alice", 13)]));
+    /// ```
+    pub fn to_hash_map(&self) -> HashMap<K, V>
+    where
+        K: Clone,
+    {
+        let mut map = HashMap::with_capacity(self.entry_count() as usize);
+        map.extend(self.iter().map(|(k, v)| (K::clone(&k), v)));
+        map
     }
 
-    fn keys(&self, cht_segment: usize) -> Option<Vec<Arc<K>>> {
-        self.base.keys(cht_segment)
+    /// Creates an independent copy of this cache. The copy has the same
+    /// configuration as this cache, and holds the clones of the keys and values of
+    /// the entries currently in this cache.
+    ///
+    /// Unlike the cheap [`clone`](#method.clone), which returns another handle
+    /// sharing the internal data structures, the inserts and removals on the copy
+    /// do not affect this cache, and vice versa. This is useful for what-if testing
+    /// and for forking a cache into a separate request context.
+    ///
+    /// The copy shares the user-supplied callbacks of this cache, such as the
+    /// eviction listener, the weigher, the loaders and the interceptors. The
+    /// interceptors are not called while copying the entries. The entries are
+    /// visited with the [`iter`](#method.iter) method, so the entries inserted or
+    /// removed in this cache while copying may or may not be included. The copied
+    /// entries are treated as newly inserted; their ages and the time-to-live and
+    /// time-to-idle timers start over. The historic popularity estimates and the
+    /// errors cached by `error_time_to_live` are not copied.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use moka::sync::Cache;
+    ///
+    /// let cache = Cache::new(100);
+    /// cache.insert("a", 1);
+    ///
+    /// let copy = cache.snapshot_clone();
+    /// copy.insert("b", 2);
+    /// cache.invalidate(&"a");
+    ///
+    /// assert_eq!(copy.get(&"a"), Some(1));
+    /// assert!(!cache.contains_key(&"b"));
+    /// assert_eq!(copy.policy().max_capacity(), Some(100));
+    /// ```
+    pub fn snapshot_clone(&self) -> Self {
+        let c = self
+            .base
+            .config()
+            .expect("the cache should be built with its configuration");
+        let mut snapshot = Self::with_everything(
+            c.name.clone(),
+            c.max_capacity,
+            c.initial_capacity,
+            c.build_hasher.clone(),
+            c.weigher.clone(),
+            c.eviction_policy.clone(),
+            c.eviction_listener.clone(),
+            c.eviction_listener_causes,
+            c.expiration_policy.clone(),
+            c.housekeeper_config.clone(),
+            c.invalidator_enabled,
+            c.loader_chain.clone(),
+            c.max_concurrent_loads
+                .map(|n| Arc::new(LoadLimiter::new(n))),
+            c.error_time_to_live,
+            // Do not let the interceptors see the copied entries.
+            None,
+            c.policy_config.clone(),
+        );
+        // Copy the values as they are stored, not as the interceptors return them.
+        let source = self.base.without_interceptors();
+        for (key, value) in Iter::with_single_cache_segment(&source, self.num_cht_segments()) {
+            let hash = snapshot.base.hash(&key);
+            snapshot.insert_with_hash(key, hash, value);
+        }
+        snapshot.base.interceptors = c.interceptors.clone();
+        snapshot
     }
-}
 
-//
-// private methods
-//
-impl<K, V, S> Cache<K, V, S>
-where
-    K: Hash + Eq + Send + Sync + 'static,
-    V: Clone + Send + Sync + 'static,
-    S: BuildHasher + Clone + Send + Sync + 'static,
-{
-    // TODO: Like future::Cache, move this method to BaseCache.
-    #[inline]
-    fn schedule_write_op(
-        inner: &impl InnerSync,
+    /// Copies the entries in the `other` cache to this cache, and returns the
+    /// number of the entries inserted or replaced in this cache.
+    ///
+    /// When a key already exists in this cache, the `strategy` decides which value
+    /// to keep. See [`MergeStrategy`][merge-strategy-enum] for the details. This is
+    /// useful for blue/green deployments, where the new cache instance inherits the
+    /// contents of the old one.
+    ///
+    /// The entries of `other` are visited with the [`iter`](#method.iter) method,
+    /// so the entries inserted or removed in `other` while merging may or may not
+    /// be copied. The copied entries are treated as newly inserted to this cache;
+    /// their ages and the time-to-live and time-to-idle timers start over, and the
+    /// eviction listener of this cache is notified for the replaced values. Each
+    /// key is updated with the same key-level lock as
+    /// [`and_compute_with`][compute-with-method], so concurrent writes on the same
+    /// key are not lost.
+    ///
+    /// [merge-strategy-enum]: ../ops/merge/enum.MergeStrategy.html
+    /// [compute-with-method]: ./struct.OwnedKeyEntrySelector.html#method.and_compute_with
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use moka::{ops::merge::MergeStrategy, sync::Cache};
+    ///
+    /// let old = Cache::new(100);
+    /// old.insert("a", 1);
+    /// old.insert("b", 2);
+    ///
+    /// let new = Cache::new(100);
+    /// new.insert("b", 20);
+    ///
+    /// assert_eq!(new.merge_from(&old, MergeStrategy::KeepExisting), 1);
+    /// assert_eq!(new.get(&"a"), Some(1));
+    /// assert_eq!(new.get(&"b"), Some(20));
+    ///
+    /// // Add up the values.
+    /// let sum = MergeStrategy::custom(|_k, existing, incoming| existing + incoming);
+    /// assert_eq!(new.merge_from(&old, sum), 2);
+    /// assert_eq!(new.get(&"a"), Some(2));
+    /// assert_eq!(new.get(&"b"), Some(22));
+    /// ```
+    pub fn merge_from<S1>(
+        &self,
+        other: &Cache<K, V, S1>,
+        mut strategy: MergeStrategy<'_, K, V>,
+    ) -> usize
+    where
+        S1: BuildHasher + Clone + Send + Sync + 'static,
+    {
+        let mut merged = 0;
+        for (key, incoming) in other.iter() {
+            let incoming_age = match strategy {
+                MergeStrategy::KeepNewer => other
+                    .base
+                    .scanning_get_entry(&key)
+                    .and_then(|e| e.metadata().map(EntryMetadata::age)),
+                _ => None,
+            };
+            let hash = self.base.hash(&key);
+            let result = self.compute_with_hash_and_fun(Arc::clone(&key), hash, |existing| {
+                let Some(existing) = existing else {
+                    return compute::Op::Put(incoming);
+                };
+                match &mut strategy {
+                    MergeStrategy::KeepExisting => compute::Op::Nop,
+                    MergeStrategy::KeepNewer => {
+                        let existing_age = self
+                            .base
+                            .scanning_get_entry(&key)
+                            .and_then(|e| e.metadata().map(EntryMetadata::age));
+                        match (incoming_age, existing_age) {
+                            (Some(i), Some(e)) if i >= e => compute::Op::Nop,
+                            // The source entry has expired or been removed.
+                            (None, _) => compute::Op::Nop,
+                            _ => compute::Op::Put(incoming),
+                        }
+                    }
+                    MergeStrategy::Custom(f) => {
+                        compute::Op::Put(f(&key, existing.value(), &incoming))
+                    }
+                }
+            });
+            if matches!(
+                result,
+                CompResult::Inserted(_) | CompResult::ReplacedWith(_)
+            ) {
+                merged += 1;
+            }
+        }
+        merged
+    }
+
+    /// Creates an iterator visiting key-value pairs from the least recently used
+    /// (LRU) entry to the most recently used (MRU) entry. The iterator element type
+    /// is `(Arc<K>, V)`, where `V` is a clone of a stored value.
+    ///
+    /// This can be used to debug scan pollution problems, or to export the coldest
+    /// entries of the cache.
+    ///
+    /// The iterator walks a snapshot of the access order queue of the cache. The
+    /// snapshot is taken in bounded chunks, so the iterator does not block the
+    /// cache maintenance for a long time. The recency order reflects the reads and
+    /// writes that have been applied by the pending maintenance tasks; call
+    /// `run_pending_tasks` before this method to have all of them applied.
+    ///
+    /// # Guarantees
+    ///
+    /// The iterator does _not_ guarantee the following:
+    ///
+    /// - It does not guarantee to return all entries. If the entry at the end of
+    ///   the last chunk has been removed before the next chunk is taken, the
+    ///   iterator will stop early.
+    /// - It does not guarantee the order of the entries that have been read or
+    ///   updated while the iterator is alive.
+    ///
+    /// Like the `iter` method, it does not return expired or invalidated entries,
+    /// and does not update the historic popularity estimator or reset idle timers
+    /// for keys.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use moka::sync::Cache;
+    ///
+    /// let cache = Cache::new(100);
+    /// cache.insert("a", 1);
+    /// cache.insert("b", 2);
+    /// cache.insert("c", 3);
+    /// cache.run_pending_tasks();
+    ///
+    /// // Read "a" to make it the most recently used.
+    /// cache.get(&"a");
+    /// cache.run_pending_tasks();
+    ///
+    /// let keys = cache.iter_by_recency().map(|(k, _v)| *k).collect::<Vec<_>>();
+    /// assert_eq!(keys, vec!["b", "c", "a"]);
+    /// ```
+    pub fn iter_by_recency(&self) -> RecencyIter<'_, K, V, S> {
+        RecencyIter::new(&self.base)
+    }
+
+    /// Creates an iterator visiting key-value pairs from the most frequently
+    /// accessed entry to the least frequently accessed one. The iterator element
+    /// type is `(Arc<K>, V)`, where `V` is a clone of a stored value.
+    ///
+    /// This can be used to transfer the hottest entries first when priming a new
+    /// cache instance.
+    ///
+    /// The ranking is approximate. It uses the access frequencies estimated by the
+    /// historic popularity estimator (a Count-Min Sketch) of the TinyLFU policy.
+    /// The estimator is enabled when the cache becomes half full, so until then,
+    /// and when the cache uses the LRU policy or has no max capacity, the entries
+    /// are visited in arbitrary order.
+    ///
+    /// A snapshot of all keys is taken when this method is called. Entries
+    /// inserted after that are not visited, and entries removed after that are
+    /// skipped. Like the `iter` method, visiting entries does not update the
+    /// estimator or reset idle timers for keys.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use moka::sync::Cache;
+    ///
+    /// let cache = Cache::new(4);
+    /// cache.insert("a", 1);
+    /// cache.insert("b", 2);
+    /// cache.insert("c", 3);
+    /// cache.run_pending_tasks();
+    ///
+    /// for _ in 0..3 {
+    ///     cache.get(&"b");
+    /// }
+    /// cache.run_pending_tasks();
+    ///
+    /// let (hottest_key, _v) = cache.iter_by_estimated_frequency().next().unwrap();
+    /// assert_eq!(*hottest_key, "b");
+    /// ```
+    pub fn iter_by_estimated_frequency(&self) -> FrequencyIter<'_, K, V, S> {
+        FrequencyIter::new(&self.base)
+    }
+
+    /// Returns up to `n` key-value pairs uniformly sampled from the live entries
+    /// of the cache, without replacement. The element type is `(Arc<K>, V)`, where
+    /// `V` is a clone of a stored value.
+    ///
+    /// This can be used to take statistical estimates (e.g. the average size of
+    /// the values) on a large cache without visiting all entries. `rng` is called
+    /// to get random `u64` numbers; moka does not depend on a random number
+    /// generator crate, so pass a closure calling the one of your choice.
+    ///
+    /// When `n` is small compared to [`entry_count`](#method.entry_count), the
+    /// entries are picked by probing random slots of the internal hash table, so
+    /// the cost is proportional to `n` rather than to the number of entries.
+    /// Otherwise, all keys are visited once to do reservoir sampling.
+    ///
+    /// Fewer than `n` entries are returned if the cache has fewer live entries, or
+    /// if entries are removed or expire concurrently. Like the `iter` method,
+    /// sampling does not update the historic popularity estimator or reset idle
+    /// timers for keys.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use moka::sync::Cache;
+    ///
+    /// let cache = Cache::new(1000);
+    /// for i in 0..1000u64 {
+    ///     cache.insert(i, vec![0u8; (i % 10) as usize]);
+    /// }
+    ///
+    /// // A simple xorshift generator. Use a proper one like `rand` crate in
+    /// // practice.
+    /// let mut state = 0x2545_f491_4f6c_dd1d_u64;
+    /// let rng = move || {
+    ///     state ^= state << 13;
+    ///     state ^= state >> 7;
+    ///     state ^= state << 17;
+    ///     state
+    /// };
+    ///
+    /// let samples = cache.sample_entries(100, rng);
+    /// assert_eq!(samples.len(), 100);
+    ///
+    /// let avg_len = samples.iter().map(|(_k, v)| v.len()).sum::<usize>() as f64 / 100.0;
+    /// assert!(avg_len < 10.0);
+    /// ```
+    pub fn sample_entries(&self, n: usize, rng: impl FnMut() -> u64) -> Vec<(Arc<K>, V)> {
+        self.base
+            .sample_keys(n, rng)
+            .into_iter()
+            .filter_map(|key| self.base.scanning_get(&key).map(|value| (key, value)))
+            .collect()
+    }
+
+    /// Returns the keys of the entries that will expire within `within` from now,
+    /// ordered by their expiration time from the soonest. The expiration time of
+    /// an entry is the earliest of the ones set by the time-to-live, time-to-idle
+    /// and [`Expiry`][expiry-trait] policies. Entries already expired and pinned
+    /// entries are not returned.
+    ///
+    /// This is useful for a pre-warming job refreshing the entries before they
+    /// expire, so that the users do not see cache misses. The returned keys are a
+    /// snapshot; an entry may be updated, read or removed before the caller
+    /// refreshes it. The entries written since the last
+    /// [`run_pending_tasks`](#method.run_pending_tasks) call may not be returned
+    /// when only the time-to-live is configured.
+    ///
+    /// Like the `iter` method, this does not update the historic popularity
+    /// estimator or reset idle timers for keys.
+    ///
+    /// [expiry-trait]: ../policy/trait.Expiry.html
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use moka::sync::Cache;
+    /// use std::time::Duration;
+    ///
+    /// let cache = Cache::builder()
+    ///     .time_to_live(Duration::from_secs(60))
+    ///     .build();
+    /// cache.insert("a", 1);
+    /// cache.run_pending_tasks();
+    ///
+    /// assert!(cache.entries_expiring_within(Duration::from_secs(1)).is_empty());
+    /// let keys = cache.entries_expiring_within(Duration::from_secs(120));
+    /// assert_eq!(keys.len(), 1);
+    /// assert_eq!(*keys[0], "a");
+    /// ```
+    pub fn entries_expiring_within(&self, within: Duration) -> Vec<Arc<K>> {
+        self.base.keys_expiring_within(within)
+    }
+
+    /// Subscribes to the events of this cache, and returns an
+    /// [`EventReceiver`][event-receiver-struct] delivering a bounded stream of
+    /// [`CacheEvent`][cache-event-enum]s, such as inserts, updates and removals of
+    /// the entries.
+    ///
+    /// This is useful when you want to mirror or audit the cache activity in other
+    /// places, without wrapping every call site of the cache. The cache never blocks
+    /// on a slow receiver; if its channel is full, the new events for the receiver
+    /// are discarded and counted by its `missed_count` method. Dropping the receiver
+    /// unsubscribes it.
+    ///
+    /// Like the eviction listener, the `Evict` events for expired or evicted
+    /// entries are sent during the maintenance tasks, which may run in the calling
+    /// thread of the cache methods.
+    ///
+    /// [event-receiver-struct]: ./struct.EventReceiver.html
+    /// [cache-event-enum]: ./enum.CacheEvent.html
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use moka::{notification::RemovalCause, sync::{Cache, CacheEvent}};
+    /// use std::sync::Arc;
+    ///
+    /// let cache = Cache::new(100);
+    /// let events = cache.subscribe();
+    ///
+    /// cache.insert("a", 1);
+    /// cache.insert("a", 2);
+    /// cache.invalidate(&"a");
+    ///
+    /// let key = Arc::new("a");
+    /// let received = events.try_iter().collect::<Vec<_>>();
+    /// assert_eq!(
+    ///     received,
+    ///     vec![
+    ///         CacheEvent::Insert { key: key.clone(), value: 1 },
+    ///         CacheEvent::Update { key: key.clone(), value: 2 },
+    ///         CacheEvent::Evict { key: key.clone(), value: 1, cause: RemovalCause::Replaced },
+    ///         CacheEvent::Evict { key, value: 2, cause: RemovalCause::Explicit },
+    ///     ]
+    /// );
+    /// ```
+    pub fn subscribe(&self) -> EventReceiver<K, V> {
+        self.base.subscribe()
+    }
+
+    /// Releases the memory that the cache keeps allocated for the entries it no
+    /// longer holds.
+    ///
+    /// The internal hash table grows as entries are inserted, and the internal
+    /// deques keep some node allocations for reuse. After a large number of
+    /// entries are evicted or invalidated, this method shrinks the hash table to
+    /// fit the live entries and deallocates the kept nodes. The pending maintenance
+    /// tasks are performed first, so that the entries invalidated by
+    /// `invalidate_all` or `invalidate_entries_if` are removed.
+    ///
+    /// The maintenance tasks also shrink the parts of the hash table that have
+    /// become much larger than needed, so calling this method is usually not
+    /// necessary. Neither of them shrinks the hash table below the initial
+    /// capacity set to the builder.
+    pub fn purge_unused_memory(&self) {
+        self.run_pending_tasks();
+        self.base.purge_unused_memory();
+    }
+
+    /// Checks the consistency between the internal data structures of the cache:
+    /// the hash table, the access order and write order deques, the recorded
+    /// entry count and weighted size, and the admitted flags of the entries.
+    /// Returns an [`InvariantReport`][invariant-report] listing the
+    /// inconsistencies found.
+    ///
+    /// The pending maintenance tasks are performed first. Call this method while
+    /// no other thread is updating the cache; otherwise the concurrent updates may
+    /// be reported as inconsistencies.
+    ///
+    /// This method is available only in debug builds.
+    ///
+    /// [invariant-report]: ./struct.InvariantReport.html
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use moka::sync::Cache;
+    ///
+    /// let cache = Cache::new(100);
+    /// for i in 0..200 {
+    ///     cache.insert(i, i);
+    /// }
+    ///
+    /// # #[cfg(debug_assertions)]
+    /// # {
+    /// let report = cache.validate_invariants();
+    /// assert!(report.is_ok(), "{:?}", report.violations);
+    /// assert_eq!(report.entry_count, 100);
+    /// # }
+    /// ```
+    #[cfg(debug_assertions)]
+    pub fn validate_invariants(&self) -> InvariantReport<K> {
+        self.run_pending_tasks();
+        self.base.validate_invariants()
+    }
+
+    /// Temporarily shrinks the effective max capacity of the cache by the
+    /// `fraction` of the max capacity, and evicts entries down to it.
+    ///
+    /// This is a way for the application to make the cache cooperate with the rest
+    /// of the process under memory stress. The effective capacity stays shrunk
+    /// until [`restore_capacity`](#method.restore_capacity) is called, or `shed` is
+    /// called again with a different `fraction`. `fraction` is clamped to the range
+    /// of `[0.0, 1.0]`. This has no effect if the cache has no max capacity.
+    ///
+    /// To shrink the capacity automatically on the memory pressure of the system,
+    /// see [`CacheBuilder::shed_on_memory_pressure`][shed-on-pressure]. Note that
+    /// the watcher will override the fraction set by this method when the memory
+    /// pressure changes.
+    ///
+    /// [shed-on-pressure]: ./struct.CacheBuilder.html#method.shed_on_memory_pressure
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use moka::sync::Cache;
+    ///
+    /// let cache = Cache::new(100);
+    /// for i in 0..100 {
+    ///     cache.insert(i, i);
+    /// }
+    /// cache.run_pending_tasks();
+    /// assert_eq!(cache.entry_count(), 100);
+    ///
+    /// // Shed a quarter of the capacity.
+    /// cache.shed(0.25);
+    /// assert_eq!(cache.entry_count(), 75);
+    ///
+    /// // The cache can hold 100 entries again.
+    /// cache.restore_capacity();
+    /// ```
+    pub fn shed(&self, fraction: f64) {
+        self.base.set_shed_fraction(fraction);
+        self.run_pending_tasks();
+    }
+
+    /// Restores the effective max capacity shrunk by the [`shed`](#method.shed)
+    /// method.
+    pub fn restore_capacity(&self) {
+        self.base.set_shed_fraction(0.0);
+    }
+
+    /// Performs any pending maintenance operations needed by the cache.
+    pub fn run_pending_tasks(&self) {
+        if let Some(hk) = &self.base.housekeeper {
+            hk.run_pending_tasks(&*self.base.inner);
+        }
+    }
+
+    pub(crate) fn take_all_entries(&self, notify: bool) -> Vec<(Arc<K>, V)> {
+        self.base.take_all_entries(notify)
+    }
+}
+
+impl<'a, K, V, S> IntoIterator for &'a Cache<K, V, S>
+where
+    K: Hash + Eq + Send + Sync + 'static,
+    V: Clone + Send + Sync + 'static,
+    S: BuildHasher + Clone + Send + Sync + 'static,
+{
+    type Item = (Arc<K>, V);
+
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<K, V, S> IntoIterator for Cache<K, V, S>
+where
+    K: Hash + Eq + Send + Sync + 'static,
+    V: Clone + Send + Sync + 'static,
+    S: BuildHasher + Clone + Send + Sync + 'static,
+{
+    type Item = (Arc<K>, V);
+
+    type IntoIter = IntoIter<K, V>;
+
+    /// Removes all entries from the cache and creates an iterator yielding their
+    /// keys and values in arbitrary order. This is useful to move the contents of a
+    /// cache being decommissioned to a replacement cache.
+    ///
+    /// The values are moved out of the cache without cloning where possible. A
+    /// value is cloned if it is still referenced by a concurrent reader, or by the
+    /// internal hash table until its memory reclamation runs. Expired or invalidated entries are not
+    /// yielded, and they are notified to the eviction listener if one is set. The
+    /// live entries are _not_ notified.
+    ///
+    /// Note that other clones of this cache share the same internal data
+    /// structures, so they will see the cache emptied.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use moka::sync::Cache;
+    ///
+    /// let cache = Cache::new(100);
+    /// cache.insert("Julia", 14);
+    ///
+    /// let new_cache = Cache::new(200);
+    /// for (k, v) in cache {
+    ///     // k: Arc<&str>, v: i32
+    ///     new_cache.insert(*k, v);
+    /// }
+    ///
+    /// assert_eq!(new_cache.get(&"Julia"), Some(14));
+    /// ```
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter::new(self.take_all_entries(false))
+    }
+}
+
+//
+// Iterator support
+//
+impl<K, V, S> ScanningGet<K, V> for Cache<K, V, S>
+where
+    K: Hash + Eq + Send + Sync + 'static,
+    V: Clone + Send + Sync + 'static,
+    S: BuildHasher + Clone + Send + Sync + 'static,
+{
+    fn num_cht_segments(&self) -> usize {
+        self.base.num_cht_segments()
+    }
+
+    fn scanning_get(&self, key: &Arc<K>) -> Option<V> {
+        self.base.scanning_get(key)
+    }
+
+    fn keys(&self, cht_segment: usize) -> Option<Vec<Arc<K>>> {
+        self.base.keys(cht_segment)
+    }
+}
+
+//
+// private methods
+//
+impl<K, V, S> Cache<K, V, S>
+where
+    K: Hash + Eq + Send + Sync + 'static,
+    V: Clone + Send + Sync + 'static,
+    S: BuildHasher + Clone + Send + Sync + 'static,
+{
+    // TODO: Like future::Cache, move this method to BaseCache.
+    #[inline]
+    fn schedule_write_op(
+        inner: &impl InnerSync,
         ch: &Sender<WriteOp<K, V>>,
         op: WriteOp<K, V>,
         now: Instant,
@@ -1829,6 +3958,23 @@ where
     ) -> Result<(), TrySendError<WriteOp<K, V>>> {
         let mut op = op;
 
+        // Try to send the op right away, unless other writers are already waiting
+        // for a room in the full channel.
+        if housekeeper.map_or(true, |hk| !hk.has_queued_writers()) {
+            BaseCache::<K, V, S>::apply_reads_writes_if_needed(inner, ch, now, housekeeper);
+            match ch.try_send(op) {
+                Ok(()) => return Ok(()),
+                Err(TrySendError::Full(op1)) => op = op1,
+                Err(e @ TrySendError::Disconnected(_)) => return Err(e),
+            }
+        }
+
+        // The channel is full. Wait for our turn behind the writers queued earlier,
+        // so that the rooms made in the channel are taken in FIFO order and no
+        // writer is starved by the others.
+        let _turn = housekeeper.map(|hk| hk.wait_for_write_turn());
+        let mut retries = 0;
+
         // NOTES:
         // - This will block when the channel is full.
         // - We are doing a busy-loop here. We were originally calling `ch.send(op)?`,
@@ -1839,7 +3985,23 @@ where
                 Ok(()) => break,
                 Err(TrySendError::Full(op1)) => {
                     op = op1;
-                    std::thread::sleep(Duration::from_micros(WRITE_RETRY_INTERVAL_MICROS));
+                    match housekeeper {
+                        // Nobody else will make a room in the channel when the
+                        // pending tasks are not run automatically.
+                        Some(hk) if !hk.is_auto_run_enabled() => hk.run_pending_tasks(inner),
+                        // Other writers keep filling the channel. Instead of retrying
+                        // further, wait for our turn to run the pending tasks, which
+                        // makes a room in the channel for this writer.
+                        Some(hk) if retries >= hk.write_retry_strategy().max_retries() => {
+                            hk.run_pending_tasks(inner);
+                            retries = 0;
+                        }
+                        Some(hk) => {
+                            hk.write_retry_strategy().wait(retries);
+                            retries += 1;
+                        }
+                        None => WriteRetryStrategy::default().wait(retries),
+                    }
                 }
                 Err(e @ TrySendError::Disconnected(_)) => return Err(e),
             }
@@ -1857,7 +4019,7 @@ where
     fn get_entry(&self, key: &Arc<K>, hash: u64) -> Option<Entry<K, V>> {
         let ignore_if = None as Option<&mut fn(&V) -> bool>;
         self.base
-            .get_with_hash_and_ignore_if(key, hash, ignore_if, true)
+            .get_with_hash_and_ignore_if(key, hash, ignore_if, true, |_| ())
     }
 
     fn insert(&self, key: Arc<K>, hash: u64, value: V) {
@@ -1912,7 +4074,7 @@ mod tests {
     use crate::{
         common::{time::Clock, HousekeeperConfig},
         notification::RemovalCause,
-        policy::{test_utils::ExpiryCallCounters, EvictionPolicy},
+        policy::{test_utils::ExpiryCallCounters, EvictionPolicy, WriteRetryStrategy},
         Expiry,
     };
 
@@ -1920,7 +4082,7 @@ mod tests {
     use std::{
         convert::Infallible,
         sync::{
-            atomic::{AtomicU8, Ordering},
+            atomic::{AtomicU32, AtomicU8, Ordering},
             Arc,
         },
         time::{Duration, Instant as StdInstant},
@@ -1945,18 +4107,13 @@ mod tests {
     }
 
     #[test]
-    fn basic_single_thread() {
-        // The following `Vec`s will hold actual and expected notifications.
+    fn max_capacity_zero_with_eviction_listener() {
         let actual = Arc::new(Mutex::new(Vec::new()));
-        let mut expected = Vec::new();
-
-        // Create an eviction listener.
         let a1 = Arc::clone(&actual);
         let listener = move |k, v, cause| a1.lock().push((k, v, cause));
 
-        // Create a cache with the eviction listener.
         let mut cache = Cache::builder()
-            .max_capacity(3)
+            .max_capacity(0)
             .eviction_listener(listener)
             .build();
         cache.reconfigure_for_testing();
@@ -1965,15 +4122,86 @@ mod tests {
         let cache = cache;
 
         cache.insert("a", "alice");
-        cache.insert("b", "bob");
-        assert_eq!(cache.get(&"a"), Some("alice"));
-        assert!(cache.contains_key(&"a"));
-        assert!(cache.contains_key(&"b"));
-        assert_eq!(cache.get(&"b"), Some("bob"));
-        cache.run_pending_tasks();
-        // counts: a -> 1, b -> 1
+        assert!(cache.get(&"a").is_none());
 
-        cache.insert("c", "cindy");
+        // The value is returned to the caller, but is not stored.
+        assert_eq!(cache.get_with("b", || "bob"), "bob");
+        assert!(cache.get(&"b").is_none());
+
+        cache.run_pending_tasks();
+        assert_eq!(cache.entry_count(), 0);
+
+        let expected = vec![
+            (Arc::new("a"), "alice", RemovalCause::Size),
+            (Arc::new("b"), "bob", RemovalCause::Size),
+        ];
+        assert_eq!(*actual.lock(), expected);
+    }
+
+    #[test]
+    fn eviction_listener_causes() {
+        use crate::notification::RemovalCauses;
+
+        let actual = Arc::new(Mutex::new(Vec::new()));
+        let a1 = Arc::clone(&actual);
+        let listener = move |k, v, cause| a1.lock().push((k, v, cause));
+
+        let mut cache = Cache::builder()
+            .max_capacity(1)
+            .eviction_listener(listener)
+            .eviction_listener_causes(RemovalCauses::all().without(RemovalCause::Replaced))
+            .build();
+        cache.reconfigure_for_testing();
+
+        // Make the cache exterior immutable.
+        let cache = cache;
+
+        cache.insert("a", "alice");
+        cache.insert("a", "anna");
+        cache.run_pending_tasks();
+        cache.invalidate(&"a");
+        cache.insert("b", "bob");
+        cache.run_pending_tasks();
+        cache.insert("c", "cindy");
+
+        // "alice" was replaced without a notification.
+        let expected = vec![
+            (Arc::new("a"), "anna", RemovalCause::Explicit),
+            (Arc::new("c"), "cindy", RemovalCause::Size),
+        ];
+        verify_notification_vec(&cache, actual, &expected);
+    }
+
+    #[test]
+    fn basic_single_thread() {
+        // The following `Vec`s will hold actual and expected notifications.
+        let actual = Arc::new(Mutex::new(Vec::new()));
+        let mut expected = Vec::new();
+
+        // Create an eviction listener.
+        let a1 = Arc::clone(&actual);
+        let listener = move |k, v, cause| a1.lock().push((k, v, cause));
+
+        // Create a cache with the eviction listener.
+        let mut cache = Cache::builder()
+            .max_capacity(3)
+            .eviction_listener(listener)
+            .build();
+        cache.reconfigure_for_testing();
+
+        // Make the cache exterior immutable.
+        let cache = cache;
+
+        cache.insert("a", "alice");
+        cache.insert("b", "bob");
+        assert_eq!(cache.get(&"a"), Some("alice"));
+        assert!(cache.contains_key(&"a"));
+        assert!(cache.contains_key(&"b"));
+        assert_eq!(cache.get(&"b"), Some("bob"));
+        cache.run_pending_tasks();
+        // counts: a -> 1, b -> 1
+
+        cache.insert("c", "cindy");
         assert_eq!(cache.get(&"c"), Some("cindy"));
         assert!(cache.contains_key(&"c"));
         // counts: a -> 1, b -> 1, c -> 1
@@ -2276,758 +4504,3581 @@ mod tests {
     }
 
     #[test]
-    fn invalidate_all() {
-        // The following `Vec`s will hold actual and expected notifications.
-        let actual = Arc::new(Mutex::new(Vec::new()));
-        let mut expected = Vec::new();
+    fn writers_make_progress_when_write_log_is_saturated() {
+        let strategies = [
+            WriteRetryStrategy::default(),
+            WriteRetryStrategy::spin(),
+            WriteRetryStrategy::yield_now(),
+            WriteRetryStrategy::exponential_backoff(
+                Duration::from_micros(10),
+                Duration::from_millis(1),
+            ),
+            WriteRetryStrategy::park(),
+        ];
+        for strategy in strategies {
+            let num_threads = 8u32;
+            let num_inserts = 2_000u32;
+            // A slow eviction listener keeps the write log near its capacity.
+            let cache = Cache::builder()
+                .max_capacity(100)
+                .eviction_listener(|_k, _v, _cause| {
+                    std::thread::sleep(Duration::from_micros(1));
+                })
+                .write_retry_strategy(strategy.clone())
+                .build();
+
+            // The numbers of the inserts done by each writer, and their snapshot
+            // taken when the first writer finishes.
+            let progress = Arc::new(
+                (0..num_threads)
+                    .map(|_| AtomicU32::new(0))
+                    .collect::<Vec<_>>(),
+            );
+            let snapshot = Arc::new(Mutex::new(None));
+            let barrier = Arc::new(std::sync::Barrier::new(num_threads as usize));
+
+            #[allow(clippy::needless_collect)]
+            let handles = (0..num_threads)
+                .map(|id| {
+                    let cache = cache.clone();
+                    let progress = Arc::clone(&progress);
+                    let snapshot = Arc::clone(&snapshot);
+                    let barrier = Arc::clone(&barrier);
+                    std::thread::spawn(move || {
+                        barrier.wait();
+                        for i in 0..num_inserts {
+                            cache.insert(id * num_inserts + i, i);
+                            progress[id as usize].fetch_add(1, Ordering::Relaxed);
+                        }
+                        snapshot.lock().get_or_insert_with(|| {
+                            progress
+                                .iter()
+                                .map(|p| p.load(Ordering::Relaxed))
+                                .collect::<Vec<_>>()
+                        });
+                    })
+                })
+                .collect::<Vec<_>>();
 
-        // Create an eviction listener.
-        let a1 = Arc::clone(&actual);
-        let listener = move |k, v, cause| a1.lock().push((k, v, cause));
+            handles.into_iter().for_each(|h| h.join().expect("Failed"));
 
-        // Create a cache with the eviction listener.
-        let mut cache = Cache::builder()
+            cache.run_pending_tasks();
+            assert!(cache.entry_count() <= 100);
+
+            // No writer was starved: when the first writer finished, every other
+            // writer had done at least half of its inserts.
+            let snapshot = snapshot.lock().take().unwrap();
+            let slowest = snapshot.iter().min().copied().unwrap();
+            assert!(
+                slowest >= num_inserts / 2,
+                "{strategy:?}: inserts done by the writers: {snapshot:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn invalidate_entries_older_than() {
+        let clock = crate::MockClock::new();
+        let cache = Cache::builder()
             .max_capacity(100)
-            .eviction_listener(listener)
+            .clock(clock.clone())
             .build();
-        cache.reconfigure_for_testing();
-
-        // Make the cache exterior immutable.
-        let cache = cache;
 
         cache.insert("a", "alice");
+        clock.advance(Duration::from_secs(10));
         cache.insert("b", "bob");
+        clock.advance(Duration::from_secs(10));
         cache.insert("c", "cindy");
+        // Reading "a" does not keep it from being invalidated.
         assert_eq!(cache.get(&"a"), Some("alice"));
+
+        cache.invalidate_entries_older_than(Duration::from_secs(15));
+        assert_eq!(cache.get(&"a"), None);
         assert_eq!(cache.get(&"b"), Some("bob"));
         assert_eq!(cache.get(&"c"), Some("cindy"));
-        assert!(cache.contains_key(&"a"));
-        assert!(cache.contains_key(&"b"));
-        assert!(cache.contains_key(&"c"));
 
-        // `cache.run_pending_tasks()` is no longer needed here before invalidating. The last
-        // modified timestamp of the entries were updated when they were inserted.
-        // https://github.com/moka-rs/moka/issues/155
-
-        cache.invalidate_all();
-        expected.push((Arc::new("a"), "alice", RemovalCause::Explicit));
-        expected.push((Arc::new("b"), "bob", RemovalCause::Explicit));
-        expected.push((Arc::new("c"), "cindy", RemovalCause::Explicit));
-        cache.run_pending_tasks();
+        // A longer age does not revive "a".
+        cache.invalidate_entries_older_than(Duration::from_secs(100));
+        assert_eq!(cache.get(&"a"), None);
+        assert_eq!(cache.get(&"b"), Some("bob"));
 
-        cache.insert("d", "david");
+        clock.advance(Duration::from_secs(1));
+        cache.invalidate_entries_older_than(Duration::ZERO);
         cache.run_pending_tasks();
-
-        assert!(cache.get(&"a").is_none());
-        assert!(cache.get(&"b").is_none());
-        assert!(cache.get(&"c").is_none());
-        assert_eq!(cache.get(&"d"), Some("david"));
-        assert!(!cache.contains_key(&"a"));
-        assert!(!cache.contains_key(&"b"));
-        assert!(!cache.contains_key(&"c"));
-        assert!(cache.contains_key(&"d"));
-
-        verify_notification_vec(&cache, actual, &expected);
+        assert_eq!(cache.entry_count(), 0);
     }
 
     #[test]
-    fn invalidate_entries_if() -> Result<(), Box<dyn std::error::Error>> {
-        use std::collections::HashSet;
-
-        // The following `Vec`s will hold actual and expected notifications.
+    fn get_and_invalidate_if() {
         let actual = Arc::new(Mutex::new(Vec::new()));
-        let mut expected = Vec::new();
-
-        // Create an eviction listener.
         let a1 = Arc::clone(&actual);
         let listener = move |k, v, cause| a1.lock().push((k, v, cause));
 
-        // Create a cache with the eviction listener.
-        let mut cache = Cache::builder()
+        let clock = crate::MockClock::new();
+        let cache = Cache::builder()
             .max_capacity(100)
-            .support_invalidation_closures()
+            .time_to_live(Duration::from_secs(10))
             .eviction_listener(listener)
+            .clock(clock.clone())
             .build();
-        cache.reconfigure_for_testing();
 
-        let (clock, mock) = Clock::mock();
-        cache.set_expiration_clock(Some(clock));
+        cache.insert("a", 1);
+        cache.insert("b", 2);
 
-        // Make the cache exterior immutable.
-        let cache = cache;
+        assert_eq!(cache.get_and_invalidate_if(&"a", |v| *v > 1), None);
+        assert_eq!(cache.get(&"a"), Some(1));
+        assert_eq!(cache.get_and_invalidate_if(&"a", |v| *v == 1), Some(1));
+        assert_eq!(cache.get(&"a"), None);
+        assert_eq!(cache.get_and_invalidate_if(&"a", |_| true), None);
 
-        cache.insert(0, "alice");
-        cache.insert(1, "bob");
-        cache.insert(2, "alex");
-        cache.run_pending_tasks();
+        // An expired entry is never returned.
+        clock.advance(Duration::from_secs(10));
+        assert_eq!(cache.get_and_invalidate_if(&"b", |_| true), None);
 
-        mock.increment(Duration::from_secs(5)); // 5 secs from the start.
         cache.run_pending_tasks();
+        assert_eq!(cache.entry_count(), 0);
+        let actual = actual.lock();
+        assert_eq!(actual[0], (Arc::new("a"), 1, RemovalCause::Explicit));
+        assert_eq!(actual[1], (Arc::new("b"), 2, RemovalCause::Expired));
+    }
 
-        assert_eq!(cache.get(&0), Some("alice"));
-        assert_eq!(cache.get(&1), Some("bob"));
-        assert_eq!(cache.get(&2), Some("alex"));
-        assert!(cache.contains_key(&0));
-        assert!(cache.contains_key(&1));
-        assert!(cache.contains_key(&2));
-
-        let names = ["alice", "alex"].iter().cloned().collect::<HashSet<_>>();
-        cache.invalidate_entries_if(move |_k, &v| names.contains(v))?;
-        assert_eq!(cache.base.invalidation_predicate_count(), 1);
-        expected.push((Arc::new(0), "alice", RemovalCause::Explicit));
-        expected.push((Arc::new(2), "alex", RemovalCause::Explicit));
-
-        mock.increment(Duration::from_secs(5)); // 10 secs from the start.
-
-        cache.insert(3, "alice");
-
-        // Run the invalidation task and wait for it to finish. (TODO: Need a better way than sleeping)
-        cache.run_pending_tasks(); // To submit the invalidation task.
-        std::thread::sleep(Duration::from_millis(200));
-        cache.run_pending_tasks(); // To process the task result.
-        std::thread::sleep(Duration::from_millis(200));
-
-        assert!(cache.get(&0).is_none());
-        assert!(cache.get(&2).is_none());
-        assert_eq!(cache.get(&1), Some("bob"));
-        // This should survive as it was inserted after calling invalidate_entries_if.
-        assert_eq!(cache.get(&3), Some("alice"));
-
-        assert!(!cache.contains_key(&0));
-        assert!(cache.contains_key(&1));
-        assert!(!cache.contains_key(&2));
-        assert!(cache.contains_key(&3));
-
-        assert_eq!(cache.entry_count(), 2);
-        assert_eq!(cache.invalidation_predicate_count(), 0);
-
-        mock.increment(Duration::from_secs(5)); // 15 secs from the start.
-
-        cache.invalidate_entries_if(|_k, &v| v == "alice")?;
-        cache.invalidate_entries_if(|_k, &v| v == "bob")?;
-        assert_eq!(cache.invalidation_predicate_count(), 2);
-        // key 1 was inserted before key 3.
-        expected.push((Arc::new(1), "bob", RemovalCause::Explicit));
-        expected.push((Arc::new(3), "alice", RemovalCause::Explicit));
-
-        // Run the invalidation task and wait for it to finish. (TODO: Need a better way than sleeping)
-        cache.run_pending_tasks(); // To submit the invalidation task.
-        std::thread::sleep(Duration::from_millis(200));
-        cache.run_pending_tasks(); // To process the task result.
-        std::thread::sleep(Duration::from_millis(200));
+    #[test]
+    fn insert_if_absent() {
+        let clock = crate::MockClock::new();
+        let cache = Cache::builder()
+            .max_capacity(100)
+            .time_to_live(Duration::from_secs(10))
+            .clock(clock.clone())
+            .build();
 
-        assert!(cache.get(&1).is_none());
-        assert!(cache.get(&3).is_none());
+        assert_eq!(cache.insert_if_absent("a", 1), None);
+        assert_eq!(cache.insert_if_absent("a", 2), Some(1));
+        assert_eq!(cache.get(&"a"), Some(1));
 
-        assert!(!cache.contains_key(&1));
-        assert!(!cache.contains_key(&3));
+        // An expired entry is replaced.
+        clock.advance(Duration::from_secs(10));
+        assert_eq!(cache.insert_if_absent("a", 3), None);
+        assert_eq!(cache.get(&"a"), Some(3));
 
-        assert_eq!(cache.entry_count(), 0);
-        assert_eq!(cache.invalidation_predicate_count(), 0);
+        // An invalidated entry is replaced.
+        clock.advance(Duration::from_secs(1));
+        cache.invalidate_all();
+        assert_eq!(cache.insert_if_absent("a", 4), None);
+        assert_eq!(cache.get(&"a"), Some(4));
 
-        verify_notification_vec(&cache, actual, &expected);
+        cache.run_pending_tasks();
+        assert_eq!(cache.entry_count(), 1);
 
-        Ok(())
+        // Only one of the racing threads wins.
+        let handles = (0..8)
+            .map(|i| {
+                let cache = cache.clone();
+                std::thread::spawn(move || cache.insert_if_absent("b", i).is_none())
+            })
+            .collect::<Vec<_>>();
+        let winners = handles
+            .into_iter()
+            .map(|h| h.join().expect("Failed to join"))
+            .filter(|inserted| *inserted)
+            .count();
+        assert_eq!(winners, 1);
     }
 
     #[test]
-    fn time_to_live() {
-        // The following `Vec`s will hold actual and expected notifications.
-        let actual = Arc::new(Mutex::new(Vec::new()));
-        let mut expected = Vec::new();
-
-        // Create an eviction listener.
-        let a1 = Arc::clone(&actual);
-        let listener = move |k, v, cause| a1.lock().push((k, v, cause));
-
-        // Create a cache with the eviction listener.
+    fn get_pinned() {
+        let clock = crate::MockClock::new();
         let mut cache = Cache::builder()
-            .max_capacity(100)
+            .max_capacity(2)
             .time_to_live(Duration::from_secs(10))
-            .eviction_listener(listener)
+            .clock(clock.clone())
             .build();
         cache.reconfigure_for_testing();
 
-        let (clock, mock) = Clock::mock();
-        cache.set_expiration_clock(Some(clock));
-
         // Make the cache exterior immutable.
         let cache = cache;
 
         cache.insert("a", "alice");
+        cache.insert("b", "bob");
         cache.run_pending_tasks();
 
-        mock.increment(Duration::from_secs(5)); // 5 secs from the start.
+        let pinned = cache.get_pinned(&"a").unwrap();
+        assert_eq!(pinned.key(), &Arc::new("a"));
+        assert_eq!(*pinned, "alice");
+
+        // "a" is at the LRU position, but it is pinned, so "b" should be evicted
+        // to admit "c".
+        for _ in 0..3 {
+            assert_eq!(cache.get(&"c"), None);
+        }
+        cache.insert("c", "cindy");
         cache.run_pending_tasks();
+        assert!(cache.contains_key(&"a"));
+        assert!(!cache.contains_key(&"b"));
+        assert!(cache.contains_key(&"c"));
 
+        // The pinned entry should not expire.
+        clock.advance(Duration::from_secs(10));
         assert_eq!(cache.get(&"a"), Some("alice"));
-        assert!(cache.contains_key(&"a"));
+        assert_eq!(cache.get(&"c"), None);
+        cache.run_pending_tasks();
+        assert_eq!(cache.entry_count(), 1);
 
-        mock.increment(Duration::from_secs(5)); // 10 secs.
-        expected.push((Arc::new("a"), "alice", RemovalCause::Expired));
+        // Unpin the entry, then it should expire.
+        drop(pinned);
         assert_eq!(cache.get(&"a"), None);
-        assert!(!cache.contains_key(&"a"));
-
-        assert_eq!(cache.iter().count(), 0);
-
         cache.run_pending_tasks();
-        assert!(cache.is_table_empty());
+        assert_eq!(cache.entry_count(), 0);
+    }
 
-        cache.insert("b", "bob");
-        cache.run_pending_tasks();
+    #[test]
+    fn policy() {
+        struct NoExpiry;
+        impl Expiry<u32, u32> for NoExpiry {}
+
+        let cache: Cache<u32, u32> = Cache::new(100);
+        let policy = cache.policy();
+        assert_eq!(policy.eviction_policy(), EvictionPolicy::tiny_lfu());
+        assert!(!policy.has_weigher());
+        assert!(!policy.has_expiry());
+        assert!(!policy.has_eviction_listener());
+        assert!(!policy.supports_invalidation_closures());
 
-        assert_eq!(cache.entry_count(), 1);
+        let cache = Cache::builder()
+            .max_capacity(100)
+            .eviction_policy(EvictionPolicy::lru())
+            .weigher(|_k: &u32, v: &u32| *v)
+            .expire_after(NoExpiry)
+            .eviction_listener(|_k, _v, _cause| {})
+            .support_invalidation_closures()
+            .build();
+        let policy = cache.policy();
+        assert_eq!(policy.eviction_policy(), EvictionPolicy::lru());
+        assert!(policy.has_weigher());
+        assert!(policy.has_expiry());
+        assert!(policy.has_eviction_listener());
+        assert!(policy.supports_invalidation_closures());
+    }
 
-        mock.increment(Duration::from_secs(5)); // 15 secs.
+    #[test]
+    fn tenant_quotas() {
+        let cache = Cache::builder()
+            .max_capacity(100)
+            .tenant_quotas([20, 30], |key: &u32| match key {
+                0..=999 => Some(0),
+                1000..=1999 => Some(1),
+                _ => None,
+            })
+            .build();
+
+        for key in 1000..1030 {
+            cache.insert(key, key);
+        }
         cache.run_pending_tasks();
+        assert_eq!(cache.tenant_weighted_size(1), Some(30));
 
-        assert_eq!(cache.get(&"b"), Some("bob"));
-        assert!(cache.contains_key(&"b"));
-        assert_eq!(cache.entry_count(), 1);
+        // Tenant 0 floods the cache, but only its own entries are evicted.
+        for key in 0..500 {
+            cache.insert(key, key);
+            cache.get(&key);
+        }
+        cache.run_pending_tasks();
+        assert_eq!(cache.tenant_weighted_size(0), Some(20));
+        assert_eq!(cache.tenant_weighted_size(1), Some(30));
+        assert_eq!(cache.tenant_weighted_size(2), None);
+        assert!((1000..1030).all(|key| cache.contains_key(&key)));
+
+        // Once tenant 0 is full, its candidates compete with its own entries by
+        // the cache policy. A key never read loses, and a popular key wins over an
+        // entry of tenant 0.
+        cache.insert(701, 701);
+        cache.run_pending_tasks();
+        assert!(!cache.contains_key(&701));
+        for _ in 0..5 {
+            cache.get(&700);
+        }
+        cache.insert(700, 700);
+        cache.run_pending_tasks();
+        assert!(cache.contains_key(&700));
+        assert_eq!(cache.tenant_weighted_size(0), Some(20));
+        assert_eq!(cache.tenant_weighted_size(1), Some(30));
 
-        cache.insert("b", "bill");
-        expected.push((Arc::new("b"), "bob", RemovalCause::Replaced));
+        // The unclassified keys share the remaining capacity.
+        for key in 2000..2100 {
+            cache.insert(key, key);
+        }
         cache.run_pending_tasks();
+        assert_eq!(cache.entry_count(), 100);
+        assert!((1000..1030).all(|key| cache.contains_key(&key)));
 
-        mock.increment(Duration::from_secs(5)); // 20 secs
+        cache.invalidate(&1000);
         cache.run_pending_tasks();
+        assert_eq!(cache.tenant_weighted_size(1), Some(29));
+    }
 
-        assert_eq!(cache.get(&"b"), Some("bill"));
-        assert!(cache.contains_key(&"b"));
-        assert_eq!(cache.entry_count(), 1);
+    #[test]
+    fn tenant_quotas_behind_other_tenants() {
+        let cache = Cache::builder()
+            .max_capacity(3000)
+            .weigher(|_k, v: &u32| *v)
+            .tenant_quotas([100, 2000], |key: &u32| match key {
+                0..=999 => Some(0),
+                1000..=2999 => Some(1),
+                _ => None,
+            })
+            .build();
 
-        mock.increment(Duration::from_secs(5)); // 25 secs
-        expected.push((Arc::new("b"), "bill", RemovalCause::Expired));
+        for key in 1000..3000 {
+            cache.insert(key, 1);
+        }
+        for key in 0..50 {
+            cache.insert(key, 1);
+        }
+        cache.run_pending_tasks();
+        assert_eq!(cache.tenant_weighted_size(0), Some(50));
 
-        assert_eq!(cache.get(&"a"), None);
-        assert_eq!(cache.get(&"b"), None);
-        assert!(!cache.contains_key(&"a"));
-        assert!(!cache.contains_key(&"b"));
+        // The entries of tenant 0 are behind more than `MAX_TENANT_WALK` entries of
+        // tenant 1 at the LRU position, but they are still found and evicted.
+        for key in 0..50 {
+            cache.insert(key, 4);
+        }
+        cache.run_pending_tasks();
+        cache.run_pending_tasks();
+        assert!(cache.tenant_weighted_size(0).unwrap() <= 100);
+        assert_eq!(cache.tenant_weighted_size(1), Some(2000));
+    }
 
-        assert_eq!(cache.iter().count(), 0);
+    #[test]
+    fn tenant_quota_holds_when_no_victims_are_found() {
+        let cache = Cache::builder()
+            .max_capacity(3000)
+            .tenant_quotas([10, 2000], |key: &u32| match key {
+                0..=999 => Some(0),
+                1000..=2999 => Some(1),
+                _ => None,
+            })
+            .build();
 
+        for key in 0..10 {
+            cache.insert(key, key);
+        }
+        for key in 1000..3000 {
+            cache.insert(key, key);
+        }
         cache.run_pending_tasks();
-        assert!(cache.is_table_empty());
-
-        verify_notification_vec(&cache, actual, &expected);
+        assert_eq!(cache.tenant_weighted_size(0), Some(10));
+
+        // The entries of tenant 0 are behind more than `MAX_TENANT_WALK` entries of
+        // tenant 1 at the LRU position. Its popular candidates cannot be compared
+        // with them, so they are rejected instead of exceeding the quota.
+        for key in 10..60 {
+            for _ in 0..5 {
+                cache.get(&key);
+            }
+            cache.insert(key, key);
+            cache.run_pending_tasks();
+            assert!(cache.tenant_weighted_size(0).unwrap() <= 10);
+        }
+        assert_eq!(cache.tenant_weighted_size(1), Some(2000));
+        assert!((1000..3000).all(|key| cache.contains_key(&key)));
     }
 
     #[test]
-    fn time_to_idle() {
-        // The following `Vec`s will hold actual and expected notifications.
-        let actual = Arc::new(Mutex::new(Vec::new()));
-        let mut expected = Vec::new();
+    fn tenant_max_entries() {
+        let cache = Cache::builder()
+            .max_capacity(10_000)
+            .weigher(|_k, v: &Vec<u8>| v.len() as u32)
+            .tenant_quotas([5_000, 3_000], |key: &u32| match key {
+                0..=999 => Some(0),
+                1000..=1999 => Some(1),
+                _ => None,
+            })
+            .tenant_max_entries([10])
+            .build();
 
-        // Create an eviction listener.
-        let a1 = Arc::clone(&actual);
-        let listener = move |k, v, cause| a1.lock().push((k, v, cause));
+        for key in 1000..1100 {
+            cache.insert(key, vec![0; 20]);
+        }
+        cache.run_pending_tasks();
+        assert_eq!(cache.tenant_entry_count(1), Some(100));
 
-        // Create a cache with the eviction listener.
-        let mut cache = Cache::builder()
+        // Tenant 0 floods the cache with tiny entries, but only keeps 10 entries.
+        for key in 0..500 {
+            cache.insert(key, vec![0; 1]);
+            cache.get(&key);
+        }
+        cache.run_pending_tasks();
+        assert_eq!(cache.tenant_entry_count(0), Some(10));
+        assert_eq!(cache.tenant_weighted_size(0), Some(10));
+        assert_eq!(cache.tenant_entry_count(1), Some(100));
+        assert_eq!(cache.tenant_entry_count(2), None);
+
+        // Updating an entry does not change the number of entries.
+        let key = (0..500).find(|key| cache.contains_key(key)).unwrap();
+        cache.insert(key, vec![0; 50]);
+        cache.run_pending_tasks();
+        assert_eq!(cache.tenant_entry_count(0), Some(10));
+        assert_eq!(cache.tenant_weighted_size(0), Some(59));
+    }
+
+    #[test]
+    fn shed() {
+        let cache = Cache::builder()
             .max_capacity(100)
-            .time_to_idle(Duration::from_secs(10))
-            .eviction_listener(listener)
+            .weigher(|_k: &u32, v: &u32| *v)
             .build();
-        cache.reconfigure_for_testing();
-
-        let (clock, mock) = Clock::mock();
-        cache.set_expiration_clock(Some(clock));
+        for i in 0..10 {
+            cache.insert(i, 10);
+        }
+        cache.run_pending_tasks();
+        assert_eq!(cache.weighted_size(), 100);
 
-        // Make the cache exterior immutable.
-        let cache = cache;
+        // Shed 40% of the capacity.
+        cache.shed(0.4);
+        assert_eq!(cache.weighted_size(), 60);
+        assert_eq!(cache.entry_count(), 6);
+        assert_eq!(cache.policy().max_capacity(), Some(100));
 
-        cache.insert("a", "alice");
+        // No more room while shedding.
+        cache.insert(10, 10);
         cache.run_pending_tasks();
+        assert_eq!(cache.weighted_size(), 60);
 
-        mock.increment(Duration::from_secs(5)); // 5 secs from the start.
+        // The fraction is clamped.
+        cache.shed(2.0);
+        assert_eq!(cache.entry_count(), 0);
+
+        cache.restore_capacity();
+        for i in 0..10 {
+            cache.insert(i, 10);
+        }
         cache.run_pending_tasks();
+        assert_eq!(cache.weighted_size(), 100);
+    }
 
-        assert_eq!(cache.get(&"a"), Some("alice"));
+    #[test]
+    fn purge_unused_memory() {
+        let mut cache = Cache::new(100_000);
+        cache.reconfigure_for_testing();
 
-        mock.increment(Duration::from_secs(5)); // 10 secs.
-        cache.run_pending_tasks();
+        // Make the cache thread-safe.
+        let cache = cache;
 
-        cache.insert("b", "bob");
+        for i in 0..50_000 {
+            cache.insert(i, i);
+            if i % 100 == 0 {
+                cache.run_pending_tasks();
+            }
+        }
         cache.run_pending_tasks();
+        let grown_capacity = cache.base.table_capacity();
 
-        assert_eq!(cache.entry_count(), 2);
-
-        mock.increment(Duration::from_secs(2)); // 12 secs.
+        // The maintenance tasks shrink the hash table after a mass invalidation.
+        cache.invalidate_all();
         cache.run_pending_tasks();
+        assert_eq!(cache.entry_count(), 0);
+        let shrunk_capacity = cache.base.table_capacity();
+        assert!(shrunk_capacity < grown_capacity / 4);
+        assert!(cache.base.free_deque_node_count() > 0);
 
-        // contains_key does not reset the idle timer for the key.
-        assert!(cache.contains_key(&"a"));
-        assert!(cache.contains_key(&"b"));
+        // The rest of the memory is released on demand.
+        cache.insert(0, 0);
         cache.run_pending_tasks();
+        cache.purge_unused_memory();
+        assert!(cache.base.table_capacity() < shrunk_capacity);
+        assert_eq!(cache.base.free_deque_node_count(), 0);
+        assert_eq!(cache.get(&0), Some(0));
+    }
 
-        assert_eq!(cache.entry_count(), 2);
+    #[test]
+    fn validate_invariants() {
+        use crate::sync::InvariantViolation;
 
-        mock.increment(Duration::from_secs(3)); // 15 secs.
-        expected.push((Arc::new("a"), "alice", RemovalCause::Expired));
+        let cache = Cache::builder()
+            .max_capacity(100)
+            .weigher(|_k: &u32, v: &u32| *v)
+            .time_to_live(Duration::from_secs(60))
+            .build();
+        for i in 0..100 {
+            cache.insert(i, i % 3 + 1);
+        }
+        cache.get(&0);
+        cache.invalidate(&1);
 
-        assert_eq!(cache.get(&"a"), None);
-        assert_eq!(cache.get(&"b"), Some("bob"));
-        assert!(!cache.contains_key(&"a"));
-        assert!(cache.contains_key(&"b"));
+        let report = cache.validate_invariants();
+        assert!(report.is_ok(), "{:?}", report.violations);
+        assert_eq!(report.entry_count, report.admitted_count);
+        assert_eq!(report.table_len as u64, report.admitted_count);
 
-        assert_eq!(cache.iter().count(), 1);
+        // Break the accounting.
+        let ws = report.weighted_size;
+        cache.base.set_weighted_size_for_testing(ws - 5);
+        cache.base.unlink_access_order_node_for_testing(&0);
 
-        cache.run_pending_tasks();
-        assert_eq!(cache.entry_count(), 1);
+        let report = cache.validate_invariants();
+        assert!(!report.is_ok());
+        assert_eq!(
+            report.violations,
+            vec![
+                InvariantViolation::NotInAccessOrderDeque { key: Arc::new(0) },
+                InvariantViolation::WeightedSizeMismatch {
+                    recorded: ws - 5,
+                    actual: ws,
+                },
+            ]
+        );
+    }
 
-        mock.increment(Duration::from_secs(10)); // 25 secs
-        expected.push((Arc::new("b"), "bob", RemovalCause::Expired));
+    #[test]
+    fn deterministic() {
+        use crate::SeededState;
+        use std::{collections::hash_map::DefaultHasher, hash::BuildHasherDefault};
+
+        type FixedState = BuildHasherDefault<DefaultHasher>;
+
+        let run = || {
+            let clock = crate::MockClock::new();
+            let cache: Cache<u32, u32, _> = Cache::builder()
+                .max_capacity(100)
+                .time_to_idle(Duration::from_secs(30))
+                .deterministic(clock.clone())
+                .build_with_hasher(SeededState::with_seed(FixedState::default(), 7));
+
+            // The pending tasks are not run by the time or the number of the
+            // operations.
+            for i in 0..200 {
+                cache.insert(i, i);
+                clock.advance(Duration::from_millis(100));
+            }
+            assert_eq!(cache.entry_count(), 0);
 
-        assert_eq!(cache.get(&"a"), None);
-        assert_eq!(cache.get(&"b"), None);
-        assert!(!cache.contains_key(&"a"));
-        assert!(!cache.contains_key(&"b"));
+            // More writes than the write log channel can hold. They must not block.
+            for i in 0..1000 {
+                cache.insert(i % 300, i);
+                cache.get(&(i % 7));
+                clock.advance(Duration::from_millis(100));
+            }
+            cache.run_pending_tasks();
+            assert_eq!(cache.entry_count(), 100);
+            let mut keys = cache.iter().map(|(k, _v)| *k).collect::<Vec<_>>();
+            keys.sort_unstable();
+            keys
+        };
 
-        assert_eq!(cache.iter().count(), 0);
+        let keys = run();
+        for i in 0..7 {
+            assert!(keys.contains(&i));
+        }
+        assert_eq!(run(), keys);
+    }
+
+    #[cfg(feature = "zeroize")]
+    #[test]
+    fn zeroize_values() {
+        #[derive(Clone)]
+        struct Secret(Vec<u8>, Arc<AtomicU8>);
+
+        impl zeroize::Zeroize for Secret {
+            fn zeroize(&mut self) {
+                self.0.zeroize();
+                self.1.fetch_add(1, Ordering::AcqRel);
+            }
+        }
 
+        let zeroized = Arc::new(AtomicU8::default());
+        let secret = |s: &str| Secret(s.as_bytes().to_vec(), Arc::clone(&zeroized));
+
+        let mut cache = Cache::builder().max_capacity(100).zeroize_values().build();
+        cache.reconfigure_for_testing();
+
+        // Make the cache thread-safe.
+        let cache = cache;
+
+        cache.insert("a", secret("alice"));
+        cache.insert("b", secret("bob"));
         cache.run_pending_tasks();
-        assert!(cache.is_table_empty());
 
-        verify_notification_vec(&cache, actual, &expected);
+        // The clones returned by the cache are not zeroized.
+        assert_eq!(cache.get(&"a").map(|s| s.0), Some(b"alice".to_vec()));
+        assert_eq!(zeroized.load(Ordering::Acquire), 0);
+
+        // The replaced value is zeroized.
+        cache.insert("a", secret("anna"));
+        cache.run_pending_tasks();
+        assert_eq!(zeroized.load(Ordering::Acquire), 1);
+
+        cache.invalidate(&"a");
+        cache.invalidate(&"b");
+        cache.run_pending_tasks();
+        assert_eq!(cache.entry_count(), 0);
+        assert_eq!(zeroized.load(Ordering::Acquire), 3);
+
+        // A cache without `zeroize_values` does not zeroize the values.
+        let cache = Cache::new(100);
+        cache.insert("a", secret("alice"));
+        cache.invalidate(&"a");
+        assert_eq!(zeroized.load(Ordering::Acquire), 3);
     }
 
-    // https://github.com/moka-rs/moka/issues/359
     #[test]
-    fn ensure_access_time_is_updated_immediately_after_read() {
+    fn subscribe() {
+        use crate::{notification::RemovalCause, sync::CacheEvent};
+
+        let clock = crate::MockClock::new();
         let mut cache = Cache::builder()
-            .max_capacity(10)
-            .time_to_idle(Duration::from_secs(5))
+            .max_capacity(100)
+            .time_to_live(Duration::from_secs(10))
+            .support_invalidation_closures()
+            .clock(clock.clone())
             .build();
         cache.reconfigure_for_testing();
 
-        let (clock, mock) = Clock::mock();
-        cache.set_expiration_clock(Some(clock));
-
         // Make the cache exterior immutable.
         let cache = cache;
 
-        cache.insert(1, 1);
-
-        mock.increment(Duration::from_secs(4));
-        assert_eq!(cache.get(&1), Some(1));
+        let events1 = cache.subscribe();
+        let events2 = cache.subscribe();
 
-        mock.increment(Duration::from_secs(2));
-        assert_eq!(cache.get(&1), Some(1));
+        cache.insert("a", "alice");
+        clock.advance(Duration::from_secs(10));
         cache.run_pending_tasks();
-        assert_eq!(cache.get(&1), Some(1));
+        cache.invalidate_all();
+        let id = cache.invalidate_entries_if(|_, _| false).unwrap();
+
+        let expected = vec![
+            CacheEvent::Insert {
+                key: Arc::new("a"),
+                value: "alice",
+            },
+            CacheEvent::Evict {
+                key: Arc::new("a"),
+                value: "alice",
+                cause: RemovalCause::Expired,
+            },
+            CacheEvent::InvalidateAll,
+            CacheEvent::PredicateRegistered { id },
+        ];
+        assert_eq!(events1.try_iter().collect::<Vec<_>>(), expected);
+        assert_eq!(events2.try_iter().collect::<Vec<_>>(), expected);
+
+        // A dropped receiver is unsubscribed, and a full receiver misses events.
+        drop(events2);
+        let ch_size = crate::sync_base::event_bus::EVENT_CH_SIZE;
+        // An `Insert` event, then pairs of `Update` and `Evict` events.
+        for _ in 0..=(ch_size / 2) {
+            cache.insert("b", "bob");
+            cache.run_pending_tasks();
+        }
+        assert_eq!(events1.len(), ch_size);
+        assert_eq!(events1.missed_count(), 1);
+        assert!(cache.base.is_removal_notifier_enabled());
+        drop(events1);
+        cache.insert("b", "bill");
+        assert!(!cache.base.is_removal_notifier_enabled());
     }
 
     #[test]
-    fn time_to_live_by_expiry_type() {
-        // Define an expiry type.
-        struct MyExpiry {
-            counters: Arc<ExpiryCallCounters>,
-        }
+    fn interceptor() {
+        use crate::sync::Interceptor;
 
-        impl MyExpiry {
-            fn new(counters: Arc<ExpiryCallCounters>) -> Self {
-                Self { counters }
-            }
-        }
+        #[derive(Default)]
+        struct Recorder(Mutex<Vec<String>>);
 
-        impl Expiry<&str, &str> for MyExpiry {
-            fn expire_after_create(
-                &self,
-                _key: &&str,
-                _value: &&str,
-                _current_time: StdInstant,
-            ) -> Option<Duration> {
-                self.counters.incl_actual_creations();
-                Some(Duration::from_secs(10))
+        impl Interceptor<u32, u32> for Arc<Recorder> {
+            fn after_get(&self, key: &u32, value: u32) -> Option<u32> {
+                // Hide the odd keys.
+                (key % 2 == 0).then_some(value)
             }
 
-            fn expire_after_update(
-                &self,
-                _key: &&str,
-                _value: &&str,
-                _current_time: StdInstant,
-                _current_duration: Option<Duration>,
-            ) -> Option<Duration> {
-                self.counters.incl_actual_updates();
-                Some(Duration::from_secs(10))
+            fn before_insert(&self, key: &u32, value: u32) -> Option<u32> {
+                // Discard the keys >= 100, and double the values.
+                (*key < 100).then_some(value * 2)
             }
-        }
 
-        // The following `Vec`s will hold actual and expected notifications.
-        let actual = Arc::new(Mutex::new(Vec::new()));
-        let mut expected = Vec::new();
+            fn after_insert(&self, key: &u32, value: &u32) {
+                self.0.lock().push(format!("insert {key} {value}"));
+            }
 
-        // Create expiry counters and the expiry.
-        let expiry_counters = Arc::new(ExpiryCallCounters::default());
-        let expiry = MyExpiry::new(Arc::clone(&expiry_counters));
+            fn before_invalidate(&self, key: &u32, _value: &u32) -> bool {
+                // Keep the key 0.
+                *key != 0
+            }
 
-        // Create an eviction listener.
-        let a1 = Arc::clone(&actual);
-        let listener = move |k, v, cause| a1.lock().push((k, v, cause));
+            fn after_invalidate(&self, key: &u32, value: &u32) {
+                self.0.lock().push(format!("invalidate {key} {value}"));
+            }
+        }
 
-        // Create a cache with the eviction listener.
-        let mut cache = Cache::builder()
+        let recorder = Arc::new(Recorder::default());
+        let cache = Cache::builder()
             .max_capacity(100)
-            .expire_after(expiry)
-            .eviction_listener(listener)
+            .interceptor(Arc::clone(&recorder))
             .build();
-        cache.reconfigure_for_testing();
 
-        let (clock, mock) = Clock::mock();
-        cache.set_expiration_clock(Some(clock));
+        cache.insert(0, 1);
+        cache.insert(1, 1);
+        cache.insert(100, 1);
+        assert_eq!(cache.get(&0), Some(2));
+        assert_eq!(cache.get(&1), None);
+        assert!(cache.contains_key(&1));
+        assert!(!cache.contains_key(&100));
 
-        // Make the cache exterior immutable.
-        let cache = cache;
+        // The inserts by `get_with` are also intercepted.
+        assert_eq!(cache.get_with(2, || 3), 3);
+        assert_eq!(cache.get(&2), Some(6));
 
-        cache.insert("a", "alice");
-        expiry_counters.incl_expected_creations();
-        cache.run_pending_tasks();
+        assert_eq!(cache.remove(&0), None);
+        assert_eq!(cache.get(&0), Some(2));
+        cache.invalidate(&1);
+        assert!(!cache.contains_key(&1));
 
-        mock.increment(Duration::from_secs(5)); // 5 secs from the start.
-        cache.run_pending_tasks();
+        assert_eq!(
+            *recorder.0.lock(),
+            ["insert 0 2", "insert 1 2", "insert 2 6", "invalidate 1 2"]
+        );
+    }
 
-        assert_eq!(cache.get(&"a"), Some("alice"));
-        assert!(cache.contains_key(&"a"));
+    #[test]
+    fn interceptor_on_every_read() {
+        use crate::sync::Interceptor;
 
-        mock.increment(Duration::from_secs(5)); // 10 secs.
-        expected.push((Arc::new("a"), "alice", RemovalCause::Expired));
-        assert_eq!(cache.get(&"a"), None);
-        assert!(!cache.contains_key(&"a"));
+        struct AddTen;
 
-        assert_eq!(cache.iter().count(), 0);
+        impl Interceptor<u32, u32> for AddTen {
+            fn after_get(&self, key: &u32, value: u32) -> Option<u32> {
+                // Hide the odd keys, and add 10 to the values.
+                (key % 2 == 0).then_some(value + 10)
+            }
+
+            fn before_invalidate(&self, _key: &u32, _value: &u32) -> bool {
+                false
+            }
+        }
+
+        let clock = crate::MockClock::new();
+        let removed = Arc::new(Mutex::new(Vec::new()));
+        let removed1 = Arc::clone(&removed);
+        let cache = Cache::builder()
+            .max_capacity(100)
+            .time_to_live(Duration::from_secs(10))
+            .deterministic(clock.clone())
+            .interceptor(AddTen)
+            .eviction_listener(move |k, _v, cause| removed1.lock().push((*k, cause)))
+            .build();
+
+        cache.insert(0, 1);
+        cache.insert(1, 1);
+
+        assert_eq!(cache.get_entry(&0).map(|e| e.into_value()), Some(11));
+        assert!(cache.get_entry(&1).is_none());
+        assert_eq!(cache.get_pinned(&0).map(|e| *e), Some(11));
+        assert!(cache.get_pinned(&1).is_none());
+        assert_eq!(cache.get_with(0, || unreachable!()), 11);
+        assert_eq!(
+            cache.iter().map(|(k, v)| (*k, v)).collect::<Vec<_>>(),
+            [(0, 11)]
+        );
+        assert_eq!(cache.to_hash_map(), [(0, 11)].into_iter().collect());
+
+        // A live entry is kept by `before_invalidate`.
+        cache.invalidate(&0);
+        assert!(removed.lock().is_empty());
+
+        // An expired entry is removed without calling `before_invalidate`.
+        clock.advance(Duration::from_secs(10));
+        cache.invalidate(&0);
+        assert_eq!(*removed.lock(), [(0, RemovalCause::Expired)]);
+    }
+
+    #[test]
+    fn insert_unless() {
+        let clock = crate::MockClock::new();
+        let cache = Cache::builder()
+            .max_capacity(100)
+            .time_to_live(Duration::from_secs(10))
+            .clock(clock.clone())
+            .build();
+
+        // The predicate is not called for an absent key.
+        assert_eq!(cache.insert_unless("a", 2, |_| unreachable!()), None);
+        assert_eq!(cache.insert_unless("a", 1, |v| *v >= 1), Some(2));
+        assert_eq!(cache.get(&"a"), Some(2));
+        assert_eq!(cache.insert_unless("a", 3, |v| *v >= 3), None);
+        assert_eq!(cache.get(&"a"), Some(3));
+
+        // The predicate is not called for an expired entry.
+        clock.advance(Duration::from_secs(10));
+        assert_eq!(cache.insert_unless("a", 1, |_| unreachable!()), None);
+        assert_eq!(cache.get(&"a"), Some(1));
 
         cache.run_pending_tasks();
-        assert!(cache.is_table_empty());
+        assert_eq!(cache.entry_count(), 1);
+    }
 
-        cache.insert("b", "bob");
-        expiry_counters.incl_expected_creations();
+    #[cfg(feature = "entry-versions")]
+    #[test]
+    fn insert_if_version() {
+        let clock = crate::MockClock::new();
+        let cache = Cache::builder()
+            .max_capacity(100)
+            .time_to_live(Duration::from_secs(10))
+            .support_entry_versions()
+            .clock(clock.clone())
+            .build();
+
+        let version = |key| {
+            cache
+                .get_entry(key)
+                .map(|e| e.metadata().unwrap().version())
+        };
+
+        assert_eq!(cache.insert_if_version("a", Some(1), 1), Err(None));
+        let v1 = cache.insert_if_version("a", None, 1).unwrap();
+        assert_eq!(version(&"a"), Some(v1));
+        assert_eq!(cache.insert_if_version("a", None, 2), Err(Some(v1)));
+
+        // Every update gives a new version, including plain inserts.
+        cache.insert("a", 3);
+        let v2 = version(&"a").unwrap();
+        assert!(v2 > v1);
+        assert_eq!(cache.insert_if_version("a", Some(v1), 4), Err(Some(v2)));
+        let v3 = cache.insert_if_version("a", Some(v2), 4).unwrap();
+        assert!(v3 > v2);
+        assert_eq!(cache.get(&"a"), Some(4));
+
+        // An expired entry has no version.
+        clock.advance(Duration::from_secs(10));
+        assert_eq!(cache.insert_if_version("a", Some(v3), 5), Err(None));
+        let v4 = cache.insert_if_version("a", None, 5).unwrap();
+        assert!(v4 > v3);
+        assert_eq!(cache.get(&"a"), Some(5));
+    }
+
+    #[cfg(feature = "entry-versions")]
+    #[test]
+    #[should_panic(expected = "support_entry_versions")]
+    fn insert_if_version_without_entry_versions() {
+        let cache = Cache::new(100);
+        cache.insert("a", 1);
+        // Without the support, the versions are not given.
+        assert_eq!(
+            cache.get_entry(&"a").unwrap().metadata().unwrap().version(),
+            0
+        );
+        let _ = cache.insert_if_version("a", Some(0), 2);
+    }
+
+    #[test]
+    fn extend_ttl() {
+        let clock = crate::MockClock::new();
+        let builder = Cache::builder()
+            .max_capacity(100)
+            .time_to_live(Duration::from_secs(10))
+            .clock(clock.clone());
+        #[cfg(feature = "entry-versions")]
+        let builder = builder.support_entry_versions();
+        let cache = builder.build();
+
+        assert!(!cache.extend_ttl(&"a", Duration::from_secs(3)));
+
+        // The time-to-live deadline is pushed out from 10 to 13.
+        cache.insert("a", 1);
+        let version = cache.get_entry(&"a").unwrap().metadata().unwrap().version();
+        clock.advance(Duration::from_secs(6)); // 6 secs.
+        assert!(cache.extend_ttl(&"a", Duration::from_secs(3)));
+        let entry = cache.get_entry(&"a").unwrap();
+        assert_eq!(entry.metadata().unwrap().version(), version);
+        assert_eq!(
+            entry.metadata().unwrap().expires_in(),
+            Some(Duration::from_secs(7))
+        );
+        clock.advance(Duration::from_secs(5)); // 11 secs.
+                                               // The housekeeper does not expire the entry either.
         cache.run_pending_tasks();
+        assert_eq!(cache.get(&"a"), Some(1));
+        clock.advance(Duration::from_secs(2)); // 13 secs.
+        assert_eq!(cache.get(&"a"), None);
+        assert!(!cache.extend_ttl(&"a", Duration::from_secs(3)));
+
+        // The time-to-live deadline is never pushed out past a full time-to-live
+        // from now.
+        cache.insert("b", 2);
+        clock.advance(Duration::from_secs(5)); // 18 secs.
+        assert!(cache.extend_ttl(&"b", Duration::from_secs(100)));
+        clock.advance(Duration::from_secs(9)); // 27 secs.
+        assert_eq!(cache.get(&"b"), Some(2));
+        clock.advance(Duration::from_secs(1)); // 28 secs.
+        assert_eq!(cache.get(&"b"), None);
 
+        // The per-entry deadline is pushed out from 33 to 38.
+        cache.insert_with_expiry("c", 3, |_, _, _| Some(Duration::from_secs(5)));
+        clock.advance(Duration::from_secs(4)); // 32 secs.
+        assert!(cache.extend_ttl(&"c", Duration::from_secs(5)));
+        cache.run_pending_tasks();
+        clock.advance(Duration::from_secs(5)); // 37 secs.
+        assert_eq!(cache.get(&"c"), Some(3));
+        cache.run_pending_tasks();
         assert_eq!(cache.entry_count(), 1);
+        clock.advance(Duration::from_secs(1)); // 38 secs.
+        assert_eq!(cache.get(&"c"), None);
 
-        mock.increment(Duration::from_secs(5)); // 15 secs.
         cache.run_pending_tasks();
+        assert_eq!(cache.entry_count(), 0);
+    }
 
-        assert_eq!(cache.get(&"b"), Some("bob"));
-        assert!(cache.contains_key(&"b"));
-        assert_eq!(cache.entry_count(), 1);
+    #[test]
+    fn track_recent_evictions() {
+        let mut cache = Cache::builder()
+            .max_capacity(10)
+            .eviction_policy(EvictionPolicy::lru())
+            .track_recent_evictions()
+            .build();
+        cache.reconfigure_for_testing();
 
-        cache.insert("b", "bill");
-        expected.push((Arc::new("b"), "bob", RemovalCause::Replaced));
-        expiry_counters.incl_expected_updates();
+        // Make the cache exterior immutable.
+        let cache = cache;
+
+        for key in 0..15 {
+            cache.insert(key, key);
+        }
         cache.run_pending_tasks();
+        assert!((0..5).all(|key| cache.was_recently_evicted(&key)));
+        assert!(!(5..15).any(|key| cache.was_recently_evicted(&key)));
 
-        mock.increment(Duration::from_secs(5)); // 20 secs
+        assert_eq!(cache.get(&0), None);
+        assert_eq!(cache.get(&1), None);
+        assert_eq!(cache.get(&20), None);
+        assert_eq!(cache.get(&5), Some(5));
         cache.run_pending_tasks();
 
-        assert_eq!(cache.get(&"b"), Some("bill"));
-        assert!(cache.contains_key(&"b"));
-        assert_eq!(cache.entry_count(), 1);
+        let stats = cache.ghost_list_stats().unwrap();
+        assert_eq!(stats.misses(), 3);
+        assert_eq!(stats.recent_eviction_misses(), 2);
+    }
 
-        mock.increment(Duration::from_secs(5)); // 25 secs
-        expected.push((Arc::new("b"), "bill", RemovalCause::Expired));
+    #[test]
+    fn second_chance_readmission() {
+        let run = |second_chance: bool| {
+            let mut builder = Cache::builder().max_capacity(10);
+            if second_chance {
+                builder = builder.second_chance_readmission();
+            }
+            let mut cache = builder.build();
+            cache.reconfigure_for_testing();
 
-        assert_eq!(cache.get(&"a"), None);
-        assert_eq!(cache.get(&"b"), None);
-        assert!(!cache.contains_key(&"a"));
-        assert!(!cache.contains_key(&"b"));
+            // Make the cache exterior immutable.
+            let cache = cache;
 
-        assert_eq!(cache.iter().count(), 0);
+            for key in 0..10 {
+                cache.insert(key, key);
+            }
+            cache.run_pending_tasks();
+            for _ in 0..2 {
+                for key in 0..10 {
+                    assert_eq!(cache.get(&key), Some(key));
+                }
+            }
 
-        cache.run_pending_tasks();
-        assert!(cache.is_table_empty());
+            // The new key is rejected as it has never been read.
+            cache.insert(100, 100);
+            cache.run_pending_tasks();
+            assert_eq!(cache.get(&100), None);
 
-        expiry_counters.verify();
-        verify_notification_vec(&cache, actual, &expected);
+            // It is still read less often than the others, so it is admitted only
+            // with the second chance.
+            cache.insert(100, 100);
+            cache.run_pending_tasks();
+            cache.contains_key(&100)
+        };
+
+        assert!(!run(false));
+        assert!(run(true));
     }
 
     #[test]
-    fn time_to_idle_by_expiry_type() {
-        // Define an expiry type.
-        struct MyExpiry {
-            counters: Arc<ExpiryCallCounters>,
-        }
+    fn victim_selector() {
+        use crate::sync::VictimDecision;
 
-        impl MyExpiry {
-            fn new(counters: Arc<ExpiryCallCounters>) -> Self {
-                Self { counters }
+        let run = |selector: fn(&u32, &u32) -> VictimDecision| {
+            let mut cache = Cache::builder()
+                .max_capacity(10)
+                .victim_selector(selector)
+                .build();
+            cache.reconfigure_for_testing();
+
+            // Make the cache exterior immutable.
+            let cache = cache;
+
+            for key in 0..10 {
+                cache.insert(key, key);
+            }
+            cache.run_pending_tasks();
+            for key in 0..10 {
+                assert_eq!(cache.get(&key), Some(key));
             }
-        }
 
-        impl Expiry<&str, &str> for MyExpiry {
-            fn expire_after_read(
-                &self,
-                _key: &&str,
-                _value: &&str,
-                _current_time: StdInstant,
-                _current_duration: Option<Duration>,
-                _last_modified_at: StdInstant,
-            ) -> Option<Duration> {
-                self.counters.incl_actual_reads();
-                Some(Duration::from_secs(10))
+            // Make the new key more popular than the others.
+            for _ in 0..5 {
+                assert_eq!(cache.get(&100), None);
             }
-        }
+            cache.insert(100, 100);
+            cache.run_pending_tasks();
+            (0..10)
+                .chain([100])
+                .filter(|key| cache.contains_key(key))
+                .collect::<Vec<_>>()
+        };
 
-        // The following `Vec`s will hold actual and expected notifications.
-        let actual = Arc::new(Mutex::new(Vec::new()));
-        let mut expected = Vec::new();
+        // Without a veto, the LRU entry is evicted.
+        assert_eq!(
+            run(|_, _| VictimDecision::Evict),
+            [1, 2, 3, 4, 5, 6, 7, 8, 9, 100]
+        );
 
-        // Create expiry counters and the expiry.
-        let expiry_counters = Arc::new(ExpiryCallCounters::default());
-        let expiry = MyExpiry::new(Arc::clone(&expiry_counters));
+        let selector = |key: &u32, _: &u32| match key {
+            0 => VictimDecision::Defer,
+            1 => VictimDecision::Keep,
+            _ => VictimDecision::Evict,
+        };
+        assert_eq!(run(selector), [0, 1, 3, 4, 5, 6, 7, 8, 9, 100]);
 
-        // Create an eviction listener.
-        let a1 = Arc::clone(&actual);
-        let listener = move |k, v, cause| a1.lock().push((k, v, cause));
+        // The new entry is rejected if no victim is found.
+        assert_eq!(
+            run(|_, _| VictimDecision::Keep),
+            (0..10).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn victim_selector_on_lru_evictions() {
+        use crate::sync::VictimDecision;
 
-        // Create a cache with the eviction listener.
         let mut cache = Cache::builder()
-            .max_capacity(100)
-            .expire_after(expiry)
-            .eviction_listener(listener)
+            .max_capacity(10)
+            .eviction_policy(EvictionPolicy::lru())
+            .victim_selector(|key: &u32, _: &u32| match key {
+                0 => VictimDecision::Keep,
+                1 => VictimDecision::Defer,
+                _ => VictimDecision::Evict,
+            })
             .build();
         cache.reconfigure_for_testing();
 
-        let (clock, mock) = Clock::mock();
-        cache.set_expiration_clock(Some(clock));
-
         // Make the cache exterior immutable.
         let cache = cache;
 
-        cache.insert("a", "alice");
+        for key in 0..10 {
+            cache.insert(key, key);
+        }
         cache.run_pending_tasks();
 
-        mock.increment(Duration::from_secs(5)); // 5 secs from the start.
+        // The entries over the capacity are evicted in the LRU order, but 0 is kept
+        // and 1 is deferred after the others.
+        cache.insert(10, 10);
+        cache.insert(11, 11);
         cache.run_pending_tasks();
+        let keys = (0..12)
+            .filter(|key| cache.contains_key(key))
+            .collect::<Vec<_>>();
+        assert_eq!(keys, [0, 1, 4, 5, 6, 7, 8, 9, 10, 11]);
 
-        assert_eq!(cache.get(&"a"), Some("alice"));
-        expiry_counters.incl_expected_reads();
-
-        mock.increment(Duration::from_secs(5)); // 10 secs.
+        // 0 is still kept at the LRU position, and 1 is now after 11, so the
+        // entries from 4 to 11 are evicted first.
+        for key in 12..20 {
+            cache.insert(key, key);
+        }
         cache.run_pending_tasks();
+        let keys = (0..20)
+            .filter(|key| cache.contains_key(key))
+            .collect::<Vec<_>>();
+        assert_eq!(keys, [0, 1, 12, 13, 14, 15, 16, 17, 18, 19]);
+    }
 
-        cache.insert("b", "bob");
+    #[test]
+    fn degraded_on_disconnected_channels() {
+        let mut cache = Cache::new(100);
+        cache.reconfigure_for_testing();
+        cache.insert(0, "a");
         cache.run_pending_tasks();
+        assert!(!cache.is_degraded());
+        assert!(!cache.stats().unwrap().is_degraded());
 
-        assert_eq!(cache.entry_count(), 2);
+        cache.base.disconnect_channels_for_testing();
 
-        mock.increment(Duration::from_secs(2)); // 12 secs.
-        cache.run_pending_tasks();
+        // Make the cache exterior immutable.
+        let cache = cache;
 
-        // contains_key does not reset the idle timer for the key.
-        assert!(cache.contains_key(&"a"));
-        assert!(cache.contains_key(&"b"));
+        // The operations do not panic, but the cache is degraded.
+        assert_eq!(cache.get(&0), Some("a"));
+        assert!(cache.is_degraded());
+        cache.insert(1, "b");
+        assert_eq!(cache.get(&1), Some("b"));
+        assert_eq!(cache.remove(&0), Some("a"));
+        assert!(!cache.contains_key(&0));
+
+        // The writes are no longer recorded for the policies.
         cache.run_pending_tasks();
+        assert_eq!(cache.entry_count(), 1);
 
-        assert_eq!(cache.entry_count(), 2);
+        // The stats count the two reads and the two writes not recorded.
+        let stats = cache.stats().unwrap();
+        assert!(stats.is_degraded());
+        assert_eq!(stats.degraded_op_count(), 4);
+    }
 
-        mock.increment(Duration::from_secs(3)); // 15 secs.
-        expected.push((Arc::new("a"), "alice", RemovalCause::Expired));
+    #[test]
+    fn shrink_first_when_over_capacity() {
+        // Returns the surviving keys and the number of the pending reads after
+        // shedding the half of the capacity with the pending reads on the LRU keys.
+        let run = |shrink_first: Option<bool>| {
+            let mut builder = Cache::builder()
+                .max_capacity(100)
+                .eviction_policy(EvictionPolicy::lru());
+            if let Some(pause_reads) = shrink_first {
+                builder = builder.shrink_first_when_over_capacity(pause_reads);
+            }
+            let mut cache = builder.build();
+            cache.reconfigure_for_testing();
 
-        assert_eq!(cache.get(&"a"), None);
-        assert_eq!(cache.get(&"b"), Some("bob"));
-        expiry_counters.incl_expected_reads();
-        assert!(!cache.contains_key(&"a"));
-        assert!(cache.contains_key(&"b"));
+            // Make the cache exterior immutable.
+            let cache = cache;
 
-        assert_eq!(cache.iter().count(), 1);
+            for key in 0..100 {
+                cache.insert(key, key);
+            }
+            cache.run_pending_tasks();
+            for key in 0..10 {
+                assert_eq!(cache.get(&key), Some(key));
+            }
 
-        cache.run_pending_tasks();
-        assert_eq!(cache.entry_count(), 1);
+            cache.shed(0.5);
+            assert_eq!(cache.entry_count(), 50);
+            let survivors = (0..100).filter(|key| cache.contains_key(key)).count();
+            let lru_survivors = (0..10).filter(|key| cache.contains_key(key)).count();
+            assert_eq!(survivors, 50);
+            (lru_survivors, cache.base.pending_read_count())
+        };
 
-        mock.increment(Duration::from_secs(10)); // 25 secs
-        expected.push((Arc::new("b"), "bob", RemovalCause::Expired));
+        // By default, the reads are applied first, so the read keys survive.
+        assert_eq!(run(None), (10, 0));
+        // The LRU keys are evicted before the reads are applied.
+        assert_eq!(run(Some(false)), (0, 0));
+        // The reads are not applied while the cache is over capacity.
+        assert_eq!(run(Some(true)), (0, 10));
+    }
 
-        assert_eq!(cache.get(&"a"), None);
-        assert_eq!(cache.get(&"b"), None);
-        assert!(!cache.contains_key(&"a"));
-        assert!(!cache.contains_key(&"b"));
+    #[test]
+    fn weigher_with_context() {
+        use crate::weigher::WeighContext;
 
-        assert_eq!(cache.iter().count(), 0);
+        let contexts = Arc::new(Mutex::new(Vec::new()));
+        let contexts1 = Arc::clone(&contexts);
+        let cache = Cache::builder()
+            .max_capacity(100)
+            .weigher_with_context(move |_k: &u32, v: &Vec<u8>, ctx: &WeighContext| {
+                contexts1.lock().push(*ctx);
+                // Each update appends 15 bytes to the value.
+                ctx.old_weight().map_or(v.len() as u32, |old| old + 15)
+            })
+            .build();
 
+        cache.insert(0, vec![0; 10]);
+        cache.insert(0, vec![0; 25]);
         cache.run_pending_tasks();
-        assert!(cache.is_table_empty());
+        assert_eq!(cache.weighted_size(), 25);
 
-        expiry_counters.verify();
-        verify_notification_vec(&cache, actual, &expected);
+        let contexts = contexts.lock();
+        assert_eq!(contexts.len(), 2);
+        assert!(!contexts[0].is_update());
+        assert_eq!(contexts[0].old_weight(), None);
+        assert!(contexts[1].is_update());
+        assert_eq!(contexts[1].old_weight(), Some(10));
     }
 
-    /// Verify that the `Expiry::expire_after_read()` method is called in `get_with`
-    /// only when the key was already present in the cache.
+    #[cfg(feature = "entry-stats")]
     #[test]
-    fn test_expiry_using_get_with() {
-        // Define an expiry type, which always return `None`.
-        struct NoExpiry {
-            counters: Arc<ExpiryCallCounters>,
-        }
+    fn track_entry_counters() {
+        let counts = |cache: &Cache<u32, u32>, key| {
+            let metadata = cache.get_entry(&key).and_then(|e| e.metadata().cloned());
+            metadata.map(|m| (m.read_count(), m.write_count()))
+        };
 
-        impl NoExpiry {
-            fn new(counters: Arc<ExpiryCallCounters>) -> Self {
-                Self { counters }
-            }
-        }
+        // Disabled by default.
+        let cache = Cache::new(100);
+        cache.insert(0, 0);
+        assert_eq!(counts(&cache, 0), Some((None, None)));
 
-        impl Expiry<&str, &str> for NoExpiry {
-            fn expire_after_create(
-                &self,
-                _key: &&str,
-                _value: &&str,
-                _current_time: StdInstant,
-            ) -> Option<Duration> {
-                self.counters.incl_actual_creations();
-                None
-            }
+        let cache = Cache::builder()
+            .max_capacity(100)
+            .track_entry_counters()
+            .build();
+        cache.insert(0, 0);
+        cache.insert(1, 1);
+        assert_eq!(counts(&cache, 0), Some((Some(1), Some(1))));
+
+        cache.get(&0);
+        cache.insert(0, 10);
+        cache.insert(0, 20);
+        assert_eq!(counts(&cache, 0), Some((Some(3), Some(3))));
+        assert_eq!(counts(&cache, 1), Some((Some(1), Some(1))));
+        assert_eq!(counts(&cache, 2), None);
+    }
 
-            fn expire_after_read(
-                &self,
-                _key: &&str,
-                _value: &&str,
-                _current_time: StdInstant,
-                _current_duration: Option<Duration>,
-                _last_modified_at: StdInstant,
-            ) -> Option<Duration> {
-                self.counters.incl_actual_reads();
-                None
-            }
+    #[test]
+    fn on_hot_key() {
+        let hot_keys = Arc::new(Mutex::new(Vec::new()));
+        let hot_keys1 = Arc::clone(&hot_keys);
+        let mut cache = Cache::builder()
+            .max_capacity(100)
+            .on_hot_key(3, move |k: &Arc<u32>, v: &u32| {
+                hot_keys1.lock().push((**k, *v))
+            })
+            .build();
+        cache.reconfigure_for_testing();
 
-            fn expire_after_update(
-                &self,
-                _key: &&str,
-                _value: &&str,
-                _current_time: StdInstant,
-                _current_duration: Option<Duration>,
-            ) -> Option<Duration> {
-                unreachable!("The `expire_after_update()` method should not be called.");
+        // Make the cache exterior immutable.
+        let cache = cache;
+
+        cache.insert(0, 10);
+        cache.insert(1, 11);
+        cache.run_pending_tasks();
+
+        for _ in 0..2 {
+            cache.get(&0);
+            cache.get(&1);
+        }
+        cache.run_pending_tasks();
+        assert!(hot_keys.lock().is_empty());
+
+        // The third read makes the key 0 hot.
+        cache.get(&0);
+        cache.run_pending_tasks();
+        assert_eq!(*hot_keys.lock(), vec![(0, 10)]);
+
+        // Not called again for the same entry.
+        for _ in 0..5 {
+            cache.get(&0);
+        }
+        cache.run_pending_tasks();
+        assert_eq!(*hot_keys.lock(), vec![(0, 10)]);
+    }
+
+    #[test]
+    fn class_stats() {
+        let mut cache = Cache::builder()
+            .max_capacity(3)
+            .eviction_policy(EvictionPolicy::lru())
+            .class_stats(|key: &u32| if key % 2 == 0 { "even" } else { "odd" })
+            .build();
+        cache.reconfigure_for_testing();
+
+        // Make the cache exterior immutable.
+        let cache = cache;
+
+        assert!(Cache::<u32, u32>::new(3).class_stats().is_none());
+
+        for key in 0..3 {
+            cache.insert(key, key);
+        }
+        cache.run_pending_tasks();
+        for _ in 0..3 {
+            cache.get(&1);
+        }
+        cache.get(&2);
+        // Evicts the key 0.
+        cache.insert(3, 3);
+        cache.run_pending_tasks();
+
+        // The inserts are not misses.
+        let stats = cache.class_stats().unwrap();
+        let (even, odd) = (&stats["even"], &stats["odd"]);
+        assert_eq!((even.hits(), even.misses(), even.evictions()), (1, 0, 1));
+        assert_eq!((odd.hits(), odd.misses(), odd.evictions()), (3, 0, 0));
+
+        // The miss of `get` cannot be classified, but the ones of the methods
+        // taking an owned key (or cloning it) are.
+        assert_eq!(cache.get(&4), None);
+        assert_eq!(cache.get_with(4, || 4), 4);
+        assert_eq!(cache.get_with_by_ref(&5, || 5), 5);
+        assert_eq!(cache.get_with(1, || unreachable!()), 1);
+
+        let stats = cache.class_stats().unwrap();
+        assert_eq!(stats.len(), 2);
+        let (even, odd) = (&stats["even"], &stats["odd"]);
+        assert_eq!((even.hits(), even.misses(), even.evictions()), (1, 1, 1));
+        assert_eq!((odd.hits(), odd.misses(), odd.evictions()), (4, 1, 0));
+        assert_eq!(odd.hit_ratio(), 0.8);
+    }
+
+    #[cfg(feature = "entry-stats")]
+    #[test]
+    fn entry_lifetime_histogram() {
+        let mut cache = Cache::builder()
+            .max_capacity(100)
+            .time_to_live(Duration::from_secs(10))
+            .track_entry_lifetimes()
+            .build();
+        cache.reconfigure_for_testing();
+
+        let (clock, mock) = Clock::mock();
+        cache.set_expiration_clock(Some(clock));
+
+        // Make the cache exterior immutable.
+        let cache = cache;
+
+        assert!(Cache::<u32, u32>::new(100)
+            .entry_lifetime_histogram()
+            .is_none());
+
+        cache.insert(0, 0);
+        cache.insert(1, 1);
+        cache.insert(2, 2);
+        cache.run_pending_tasks();
+        cache.get(&1);
+
+        // Removed after 3 seconds.
+        mock.increment(Duration::from_secs(3));
+        cache.invalidate(&0);
+        cache.run_pending_tasks();
+
+        // Expired after 10 seconds.
+        mock.increment(Duration::from_secs(7));
+        cache.run_pending_tasks();
+        assert_eq!(cache.entry_count(), 0);
+
+        let histogram = cache.entry_lifetime_histogram().unwrap();
+        assert_eq!(histogram.removed(), 3);
+        assert_eq!(histogram.never_hit(), 2);
+
+        let buckets = histogram.buckets();
+        assert_eq!(buckets[0].upper_bound(), Some(Duration::from_secs(1)));
+        assert_eq!(buckets.last().unwrap().upper_bound(), None);
+        // 3 seconds is in [2, 4).
+        assert_eq!(buckets[2].upper_bound(), Some(Duration::from_secs(4)));
+        assert_eq!((buckets[2].removed(), buckets[2].never_hit()), (1, 1));
+        // 10 seconds is in [8, 16).
+        assert_eq!(buckets[4].upper_bound(), Some(Duration::from_secs(16)));
+        assert_eq!((buckets[4].removed(), buckets[4].never_hit()), (2, 1));
+    }
+
+    #[test]
+    fn sample_reads_under_pressure() {
+        use crate::common::concurrent::constants::READ_LOG_CH_SIZE;
+
+        let mut cache = Cache::builder()
+            .max_capacity(100)
+            .sample_reads_under_pressure()
+            .build();
+        cache.reconfigure_for_testing();
+
+        // Make the cache exterior immutable.
+        let cache = cache;
+
+        cache.insert(0, 0);
+        cache.run_pending_tasks();
+
+        // Fill up the read log. The reads exceeding it are dropped.
+        for _ in 0..(READ_LOG_CH_SIZE + 10) {
+            cache.get(&0);
+        }
+        let stats = cache.read_sampling_stats().unwrap();
+        assert_eq!(
+            (stats.interval(), stats.sampled_out(), stats.dropped()),
+            (1, 0, 10)
+        );
+
+        // The full log doubles the interval.
+        cache.run_pending_tasks();
+        for _ in 0..100 {
+            cache.get(&0);
+        }
+        let stats = cache.read_sampling_stats().unwrap();
+        assert_eq!(
+            (stats.interval(), stats.sampled_out(), stats.dropped()),
+            (2, 50, 10)
+        );
+        assert_eq!(cache.base.pending_read_count(), 50);
+
+        // The almost empty log halves the interval.
+        cache.run_pending_tasks();
+        assert_eq!(cache.read_sampling_stats().unwrap().interval(), 1);
+    }
+
+    #[test]
+    fn segment_frequency_sketch() {
+        let mut cache = Cache::builder()
+            .max_capacity(3)
+            .segment_frequency_sketch()
+            .build();
+        cache.reconfigure_for_testing();
+
+        // Make the cache exterior immutable.
+        let cache = cache;
+
+        cache.insert("a", "alice");
+        cache.insert("b", "bob");
+        cache.insert("c", "cindy");
+        cache.run_pending_tasks();
+
+        assert_eq!(cache.get(&"c"), Some("cindy"));
+        for _ in 0..2 {
+            assert_eq!(cache.get(&"a"), Some("alice"));
+            assert_eq!(cache.get(&"b"), Some("bob"));
+        }
+        cache.run_pending_tasks();
+        // counts: a -> 2, b -> 2, c -> 1
+
+        // "d" should not be admitted because its frequency is too low.
+        cache.insert("d", "david");
+        cache.run_pending_tasks();
+        assert!(!cache.contains_key(&"d"));
+
+        // Raise the frequency of "d" above "c".
+        for _ in 0..2 {
+            assert_eq!(cache.get(&"d"), None);
+        }
+        cache.run_pending_tasks();
+        // counts: a -> 2, b -> 2, c -> 1, d -> 2
+
+        // "d" should be admitted and "c" should be evicted.
+        cache.insert("d", "dennis");
+        cache.run_pending_tasks();
+        assert!(cache.contains_key(&"a"));
+        assert!(cache.contains_key(&"b"));
+        assert!(!cache.contains_key(&"c"));
+        assert_eq!(cache.get(&"d"), Some("dennis"));
+    }
+
+    #[test]
+    fn maintenance_time_budget() {
+        use crate::common::concurrent::constants::DEFAULT_EVICTION_BATCH_SIZE;
+
+        const NUM_ENTRIES: u32 = DEFAULT_EVICTION_BATCH_SIZE * 2 + 10;
+
+        let mut cache = Cache::builder()
+            .time_to_live(Duration::from_secs(10))
+            // The mock clock does not advance during a run, so a zero budget stops
+            // every run after its first round.
+            .maintenance_time_budget(Duration::ZERO)
+            .build();
+        cache.reconfigure_for_testing();
+        let (clock, mock) = Clock::mock();
+        cache.set_expiration_clock(Some(clock));
+
+        // Make the cache exterior immutable.
+        let cache = cache;
+
+        for i in 0..NUM_ENTRIES {
+            cache.insert(i, i);
+            if i % 100 == 99 {
+                cache.run_pending_tasks();
             }
         }
+        cache.run_pending_tasks();
+        assert_eq!(cache.entry_count(), NUM_ENTRIES as u64);
 
-        // Create expiry counters and the expiry.
-        let expiry_counters = Arc::new(ExpiryCallCounters::default());
-        let expiry = NoExpiry::new(Arc::clone(&expiry_counters));
+        // Expire all entries. Each run removes only one batch of them.
+        mock.increment(Duration::from_secs(11));
+        cache.run_pending_tasks();
+        assert_eq!(
+            cache.entry_count(),
+            (NUM_ENTRIES - DEFAULT_EVICTION_BATCH_SIZE) as u64
+        );
+        cache.run_pending_tasks();
+        assert_eq!(cache.entry_count(), 10);
+        cache.run_pending_tasks();
+        assert_eq!(cache.entry_count(), 0);
+    }
+
+    #[test]
+    fn invalidate_entries_if_without_deques_lock() {
+        use std::sync::atomic::{AtomicU32, Ordering};
 
-        // Create a cache with the expiry and eviction listener.
         let mut cache = Cache::builder()
             .max_capacity(100)
-            .expire_after(expiry)
+            .support_invalidation_closures()
             .build();
         cache.reconfigure_for_testing();
 
         // Make the cache exterior immutable.
         let cache = cache;
 
-        // The key is not present.
-        cache.get_with("a", || "alice");
-        expiry_counters.incl_expected_creations();
+        for i in 0..10u32 {
+            cache.insert(i, i);
+        }
+        cache.run_pending_tasks();
+
+        // Count the calls of the predicate while the deques are locked.
+        let calls_with_lock = Arc::new(AtomicU32::default());
+        let predicate = {
+            let cache = cache.clone();
+            let calls_with_lock = Arc::clone(&calls_with_lock);
+            move |_k: &u32, v: &u32| {
+                // Capture the whole `Cache`, which is `Send`, not only its `base`.
+                let cache = &cache;
+                if cache.base.is_deques_locked() {
+                    calls_with_lock.fetch_add(1, Ordering::Relaxed);
+                }
+                v % 2 == 0
+            }
+        };
+        cache.invalidate_entries_if(predicate).unwrap();
+        cache.run_pending_tasks();
+
+        assert_eq!(calls_with_lock.load(Ordering::Relaxed), 0);
+        assert_eq!(cache.invalidation_predicate_count(), 0);
+        assert_eq!(cache.entry_count(), 5);
+        for i in 0..10u32 {
+            assert_eq!(cache.contains_key(&i), i % 2 == 1);
+        }
+    }
+
+    #[test]
+    fn stats() {
+        let mut cache = Cache::builder()
+            .max_capacity(3)
+            .time_to_live(Duration::from_secs(10))
+            .build();
+        cache.reconfigure_for_testing();
+        let (clock, mock) = Clock::mock();
+        cache.set_expiration_clock(Some(clock));
+
+        // Make the cache exterior immutable.
+        let cache = cache;
+
+        assert!(Cache::<u32, u32>::builder()
+            .disable_stats()
+            .build()
+            .stats()
+            .is_none());
+
+        cache.insert(0, 0);
+        assert_eq!(cache.get(&0), Some(0));
+        assert_eq!(cache.get(&1), None);
+        assert_eq!(cache.get_with(1, || 1), 1);
+        assert_eq!(cache.get_with(1, || unreachable!()), 1);
+        assert_eq!(cache.optionally_get_with(2, || None), None);
+        assert!(cache.try_get_with(2, || Err("failed")).is_err());
+        assert_eq!(cache.try_get_with(2, || Ok::<_, ()>(2)), Ok(2));
         cache.run_pending_tasks();
 
-        // The key is present.
-        cache.get_with("a", || "alex");
-        expiry_counters.incl_expected_reads();
-        cache.run_pending_tasks();
+        let stats = cache.stats().unwrap();
+        assert_eq!((stats.hit_count(), stats.miss_count()), (2, 5));
+        assert_eq!(stats.request_count(), 7);
+        assert_eq!(
+            (stats.load_success_count(), stats.load_failure_count()),
+            (2, 2)
+        );
+        assert_eq!(stats.eviction_count(), 0);
+
+        // Read the entries to make them stay, and then evict "3" for the capacity.
+        cache.get(&0);
+        cache.get(&1);
+        cache.get(&2);
+        cache.run_pending_tasks();
+        cache.insert(3, 3);
+        cache.run_pending_tasks();
+        assert!(!cache.contains_key(&3));
+        assert_eq!(cache.stats().unwrap().eviction_count(), 1);
+
+        // Expire the rest. An explicit removal is not an eviction.
+        cache.invalidate(&0);
+        mock.increment(Duration::from_secs(11));
+        cache.run_pending_tasks();
+        assert_eq!(cache.entry_count(), 0);
+        let stats = cache.stats().unwrap();
+        assert_eq!(stats.eviction_count(), 3);
+        assert_eq!(stats.hit_count(), 5);
+    }
+
+    #[test]
+    fn eager_expiration() {
+        let actual = Arc::new(Mutex::new(Vec::new()));
+        let a1 = Arc::clone(&actual);
+        let listener = move |k, v, cause| a1.lock().push((k, v, cause));
+
+        let mut cache = Cache::builder()
+            .time_to_live(Duration::from_secs(10))
+            .eager_expiration(Duration::from_millis(10))
+            .eviction_listener(listener)
+            .build();
+        cache.reconfigure_for_testing();
+        let (clock, mock) = Clock::mock();
+        cache.set_expiration_clock(Some(clock));
+
+        // Make the cache exterior immutable.
+        let cache = cache;
+
+        cache.insert('a', "alice");
+        cache.run_pending_tasks();
+        assert_eq!(cache.entry_count(), 1);
+
+        // Expire the entry, and wait for the background thread to remove it
+        // without calling any cache operation that runs the pending tasks.
+        mock.increment(Duration::from_secs(11));
+        for _ in 0..500 {
+            if cache.entry_count() == 0 {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(cache.entry_count(), 0);
+        assert_eq!(
+            *actual.lock(),
+            vec![(Arc::new('a'), "alice", RemovalCause::Expired)]
+        );
+    }
+
+    #[test]
+    fn custom_stats_counter() {
+        use crate::sync::{CacheStats, SegmentedCache, StatsCounter};
+
+        #[derive(Clone, Default)]
+        struct Recorder(Arc<Mutex<Vec<&'static str>>>);
+
+        impl StatsCounter for Recorder {
+            fn record_hit(&self) {
+                self.0.lock().push("hit");
+            }
+            fn record_miss(&self) {
+                self.0.lock().push("miss");
+            }
+            fn record_evictions(&self, count: u64) {
+                assert!(count > 0);
+                self.0
+                    .lock()
+                    .extend(std::iter::repeat("eviction").take(count as usize));
+            }
+            fn record_load_success(&self) {
+                self.0.lock().push("load_success");
+            }
+            fn record_load_failure(&self) {
+                self.0.lock().push("load_failure");
+            }
+            fn snapshot(&self) -> CacheStats {
+                let events = self.0.lock();
+                let count = |event| events.iter().filter(|e| **e == event).count() as u64;
+                CacheStats::new(
+                    count("hit"),
+                    count("miss"),
+                    count("eviction"),
+                    count("load_success"),
+                    count("load_failure"),
+                )
+            }
+        }
+
+        let recorder = Recorder::default();
+        let mut cache = Cache::builder()
+            .time_to_live(Duration::from_secs(10))
+            .stats_counter(recorder.clone())
+            .build();
+        cache.reconfigure_for_testing();
+        let (clock, mock) = Clock::mock();
+        cache.set_expiration_clock(Some(clock));
+
+        // Make the cache exterior immutable.
+        let cache = cache;
+
+        assert_eq!(cache.get(&0), None);
+        assert_eq!(cache.get_with(0, || 0), 0);
+        assert_eq!(cache.get(&0), Some(0));
+        assert_eq!(cache.optionally_get_with(1, || None), None);
+        mock.increment(Duration::from_secs(11));
+        cache.run_pending_tasks();
+        assert_eq!(
+            *recorder.0.lock(),
+            vec![
+                "miss",
+                "miss",
+                "load_success",
+                "hit",
+                "miss",
+                "load_failure",
+                "eviction"
+            ]
+        );
+        let stats = cache.stats().unwrap();
+        assert_eq!((stats.hit_count(), stats.miss_count()), (1, 3));
+        assert_eq!(stats.eviction_count(), 1);
+
+        // The segments record to the same counter, so the events are not counted
+        // more than once.
+        let recorder = Recorder::default();
+        let cache = SegmentedCache::builder(4)
+            .stats_counter(recorder.clone())
+            .build();
+        for i in 0..8 {
+            cache.get(&i);
+            cache.insert(i, i);
+            cache.get(&i);
+        }
+        let stats = cache.stats().unwrap();
+        assert_eq!((stats.hit_count(), stats.miss_count()), (8, 8));
+        assert_eq!(recorder.0.lock().len(), 16);
+    }
+
+    #[test]
+    fn export_and_import_stream() {
+        use std::io::{self, Read};
+
+        let encode = |key: &u32, value: &u32, buf: &mut Vec<u8>| {
+            buf.extend_from_slice(&key.to_be_bytes());
+            buf.extend_from_slice(&value.to_be_bytes());
+        };
+        let decode = |record: &[u8]| {
+            if record.len() != 8 {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "bad record"));
+            }
+            let (key, value) = record.split_at(4);
+            Ok((
+                u32::from_be_bytes(key.try_into().unwrap()),
+                u32::from_be_bytes(value.try_into().unwrap()),
+            ))
+        };
+
+        let cache = Cache::new(100);
+        for i in 0..10 {
+            cache.insert(i, i * 10);
+        }
+
+        // Each record is yielded with its length prefix.
+        let records = cache.export_stream(encode).collect::<Vec<_>>();
+        assert_eq!(records.len(), 10);
+        assert!(records
+            .iter()
+            .all(|r| r.len() == 12 && r[..4] == [0, 0, 0, 8]));
+
+        // Read the stream in small chunks to split the records.
+        let mut bytes = Vec::new();
+        let mut stream = cache.export_stream(encode);
+        let mut chunk = [0; 5];
+        loop {
+            match stream.read(&mut chunk).unwrap() {
+                0 => break,
+                n => bytes.extend_from_slice(&chunk[..n]),
+            }
+        }
+        assert_eq!(bytes.len(), 120);
+
+        let replica = Cache::new(100);
+        assert_eq!(replica.import_stream(&bytes[..], decode).unwrap(), 10);
+        for i in 0..10 {
+            assert_eq!(replica.get(&i), Some(i * 10));
+        }
+
+        // The entries before a truncated record are still inserted.
+        let replica = Cache::new(100);
+        let err = replica.import_stream(&bytes[..30], decode).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        assert_eq!(replica.iter().count(), 2);
+
+        // An error of the decode closure is returned as is.
+        let err = replica
+            .import_stream(&[0, 0, 0, 1, 42][..], decode)
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn entry_with_occupied_and_vacant() {
+        use crate::ops::compute::{CompResult, MapEntry};
+        use std::thread;
+
+        let cache: Cache<u32, u32> = Cache::new(100);
+        const KEY: u32 = 0;
+
+        // Concurrent increments on the same key must not be lost.
+        let handles = (0..8)
+            .map(|_| {
+                let cache = cache.clone();
+                thread::spawn(move || {
+                    for _ in 0..100 {
+                        cache.entry(KEY).and_entry_with(|entry| match entry {
+                            MapEntry::Occupied(occupied) => occupied.modify(|v| *v += 1),
+                            MapEntry::Vacant(vacant) => vacant.insert(1),
+                        });
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        handles.into_iter().for_each(|h| h.join().expect("Failed"));
+
+        assert_eq!(cache.get(&KEY), Some(800));
+        cache.run_pending_tasks();
+        assert_eq!(cache.entry_count(), 1);
+
+        let result = cache.entry_by_ref(&KEY).and_entry_with(|entry| {
+            assert_eq!(entry.key(), &KEY);
+            let MapEntry::Occupied(occupied) = entry else {
+                panic!("The entry should be occupied");
+            };
+            assert_eq!(occupied.get(), &800);
+            occupied.keep()
+        });
+        assert!(matches!(result, CompResult::Unchanged(_)));
+
+        let result = cache.entry(KEY).and_entry_with(|entry| match entry {
+            MapEntry::Occupied(occupied) => occupied.remove(),
+            MapEntry::Vacant(vacant) => vacant.skip(),
+        });
+        assert!(matches!(result, CompResult::Removed(_)));
+
+        let result = cache.entry(KEY).and_entry_with(|entry| match entry {
+            MapEntry::Occupied(occupied) => occupied.remove(),
+            MapEntry::Vacant(vacant) => vacant.skip(),
+        });
+        assert!(matches!(result, CompResult::StillNone(_)));
+        cache.run_pending_tasks();
+        assert_eq!(cache.entry_count(), 0);
+
+        assert!(cache.is_waiter_map_empty());
+    }
+
+    #[test]
+    fn invalidate_all() {
+        // The following `Vec`s will hold actual and expected notifications.
+        let actual = Arc::new(Mutex::new(Vec::new()));
+        let mut expected = Vec::new();
+
+        // Create an eviction listener.
+        let a1 = Arc::clone(&actual);
+        let listener = move |k, v, cause| a1.lock().push((k, v, cause));
+
+        // Create a cache with the eviction listener.
+        let mut cache = Cache::builder()
+            .max_capacity(100)
+            .eviction_listener(listener)
+            .build();
+        cache.reconfigure_for_testing();
+
+        // Make the cache exterior immutable.
+        let cache = cache;
+
+        cache.insert("a", "alice");
+        cache.insert("b", "bob");
+        cache.insert("c", "cindy");
+        assert_eq!(cache.get(&"a"), Some("alice"));
+        assert_eq!(cache.get(&"b"), Some("bob"));
+        assert_eq!(cache.get(&"c"), Some("cindy"));
+        assert!(cache.contains_key(&"a"));
+        assert!(cache.contains_key(&"b"));
+        assert!(cache.contains_key(&"c"));
+
+        // `cache.run_pending_tasks()` is no longer needed here before invalidating. The last
+        // modified timestamp of the entries were updated when they were inserted.
+        // https://github.com/moka-rs/moka/issues/155
+
+        cache.invalidate_all();
+        expected.push((Arc::new("a"), "alice", RemovalCause::Explicit));
+        expected.push((Arc::new("b"), "bob", RemovalCause::Explicit));
+        expected.push((Arc::new("c"), "cindy", RemovalCause::Explicit));
+        cache.run_pending_tasks();
+
+        cache.insert("d", "david");
+        cache.run_pending_tasks();
+
+        assert!(cache.get(&"a").is_none());
+        assert!(cache.get(&"b").is_none());
+        assert!(cache.get(&"c").is_none());
+        assert_eq!(cache.get(&"d"), Some("david"));
+        assert!(!cache.contains_key(&"a"));
+        assert!(!cache.contains_key(&"b"));
+        assert!(!cache.contains_key(&"c"));
+        assert!(cache.contains_key(&"d"));
+
+        verify_notification_vec(&cache, actual, &expected);
+    }
+
+    #[test]
+    fn invalidate_entries_if() -> Result<(), Box<dyn std::error::Error>> {
+        use std::collections::HashSet;
+
+        // The following `Vec`s will hold actual and expected notifications.
+        let actual = Arc::new(Mutex::new(Vec::new()));
+        let mut expected = Vec::new();
+
+        // Create an eviction listener.
+        let a1 = Arc::clone(&actual);
+        let listener = move |k, v, cause| a1.lock().push((k, v, cause));
+
+        // Create a cache with the eviction listener.
+        let mut cache = Cache::builder()
+            .max_capacity(100)
+            .support_invalidation_closures()
+            .eviction_listener(listener)
+            .build();
+        cache.reconfigure_for_testing();
+
+        let (clock, mock) = Clock::mock();
+        cache.set_expiration_clock(Some(clock));
+
+        // Make the cache exterior immutable.
+        let cache = cache;
+
+        cache.insert(0, "alice");
+        cache.insert(1, "bob");
+        cache.insert(2, "alex");
+        cache.run_pending_tasks();
+
+        mock.increment(Duration::from_secs(5)); // 5 secs from the start.
+        cache.run_pending_tasks();
+
+        assert_eq!(cache.get(&0), Some("alice"));
+        assert_eq!(cache.get(&1), Some("bob"));
+        assert_eq!(cache.get(&2), Some("alex"));
+        assert!(cache.contains_key(&0));
+        assert!(cache.contains_key(&1));
+        assert!(cache.contains_key(&2));
+
+        let names = ["alice", "alex"].iter().cloned().collect::<HashSet<_>>();
+        cache.invalidate_entries_if(move |_k, &v| names.contains(v))?;
+        assert_eq!(cache.base.invalidation_predicate_count(), 1);
+        expected.push((Arc::new(0), "alice", RemovalCause::Explicit));
+        expected.push((Arc::new(2), "alex", RemovalCause::Explicit));
+
+        mock.increment(Duration::from_secs(5)); // 10 secs from the start.
+
+        cache.insert(3, "alice");
+
+        // Run the invalidation task and wait for it to finish. (TODO: Need a better way than sleeping)
+        cache.run_pending_tasks(); // To submit the invalidation task.
+        std::thread::sleep(Duration::from_millis(200));
+        cache.run_pending_tasks(); // To process the task result.
+        std::thread::sleep(Duration::from_millis(200));
+
+        assert!(cache.get(&0).is_none());
+        assert!(cache.get(&2).is_none());
+        assert_eq!(cache.get(&1), Some("bob"));
+        // This should survive as it was inserted after calling invalidate_entries_if.
+        assert_eq!(cache.get(&3), Some("alice"));
+
+        assert!(!cache.contains_key(&0));
+        assert!(cache.contains_key(&1));
+        assert!(!cache.contains_key(&2));
+        assert!(cache.contains_key(&3));
+
+        assert_eq!(cache.entry_count(), 2);
+        assert_eq!(cache.invalidation_predicate_count(), 0);
+
+        mock.increment(Duration::from_secs(5)); // 15 secs from the start.
+
+        cache.invalidate_entries_if(|_k, &v| v == "alice")?;
+        cache.invalidate_entries_if(|_k, &v| v == "bob")?;
+        assert_eq!(cache.invalidation_predicate_count(), 2);
+        // key 1 was inserted before key 3.
+        expected.push((Arc::new(1), "bob", RemovalCause::Explicit));
+        expected.push((Arc::new(3), "alice", RemovalCause::Explicit));
+
+        // Run the invalidation task and wait for it to finish. (TODO: Need a better way than sleeping)
+        cache.run_pending_tasks(); // To submit the invalidation task.
+        std::thread::sleep(Duration::from_millis(200));
+        cache.run_pending_tasks(); // To process the task result.
+        std::thread::sleep(Duration::from_millis(200));
+
+        assert!(cache.get(&1).is_none());
+        assert!(cache.get(&3).is_none());
+
+        assert!(!cache.contains_key(&1));
+        assert!(!cache.contains_key(&3));
+
+        assert_eq!(cache.entry_count(), 0);
+        assert_eq!(cache.invalidation_predicate_count(), 0);
+
+        verify_notification_vec(&cache, actual, &expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn time_to_live_with_builder_clock() {
+        let actual = Arc::new(Mutex::new(Vec::new()));
+        let a1 = Arc::clone(&actual);
+        let listener = move |k, v, cause| a1.lock().push((k, v, cause));
+
+        let clock = crate::MockClock::new();
+        let cache = Cache::builder()
+            .max_capacity(100)
+            .time_to_live(Duration::from_secs(10))
+            .eviction_listener(listener)
+            .clock(clock.clone())
+            .build();
+
+        cache.insert("a", "alice");
+        cache.run_pending_tasks();
+        assert_eq!(cache.entry_count(), 1);
+
+        clock.advance(Duration::from_secs(9));
+        assert_eq!(cache.get(&"a"), Some("alice"));
+
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(cache.get(&"a"), None);
+
+        cache.run_pending_tasks();
+        assert_eq!(cache.entry_count(), 0);
+        assert_eq!(
+            *actual.lock(),
+            vec![(Arc::new("a"), "alice", RemovalCause::Expired)]
+        );
+    }
+
+    #[test]
+    fn max_concurrent_loads() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        const MAX_LOADS: usize = 2;
+
+        let cache = Cache::builder()
+            .max_capacity(100)
+            .max_concurrent_loads(MAX_LOADS)
+            .build();
+        let running = Arc::new(AtomicUsize::new(0));
+        let max_running = Arc::new(AtomicUsize::new(0));
+
+        let threads = (0..8)
+            .map(|key| {
+                let (cache, running, max_running) = (
+                    cache.clone(),
+                    Arc::clone(&running),
+                    Arc::clone(&max_running),
+                );
+                std::thread::spawn(move || {
+                    cache.get_with(key, || {
+                        let n = running.fetch_add(1, Ordering::AcqRel) + 1;
+                        max_running.fetch_max(n, Ordering::AcqRel);
+                        std::thread::sleep(Duration::from_millis(50));
+                        running.fetch_sub(1, Ordering::AcqRel);
+                        key * 10
+                    })
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for (key, t) in threads.into_iter().enumerate() {
+            assert_eq!(t.join().unwrap(), key * 10);
+        }
+        assert_eq!(max_running.load(Ordering::Acquire), MAX_LOADS);
+        assert!(cache.is_waiter_map_empty());
+    }
+
+    #[test]
+    fn max_concurrent_loads_nested() {
+        let cache = Cache::builder()
+            .max_capacity(100)
+            .max_concurrent_loads(1)
+            .build();
+
+        // The nested loads must not wait for the permit held by the outer one.
+        let value = cache.get_with(1, || {
+            let inner = cache.get_with(2, || cache.get_with(3, || 30) + 20);
+            inner + 10
+        });
+        assert_eq!(value, 60);
+        assert_eq!(cache.get(&2), Some(50));
+
+        // The permit is returned after the outer load, so other threads can load.
+        let t = {
+            let cache = cache.clone();
+            std::thread::spawn(move || cache.get_with(4, || 40))
+        };
+        assert_eq!(t.join().unwrap(), 40);
+    }
+
+    #[test]
+    fn get_or_load() {
+        use crate::sync::Loader;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        struct CountingLoader {
+            max_key: u32,
+            calls: Arc<AtomicUsize>,
+        }
+
+        impl Loader<u32, String> for CountingLoader {
+            fn load(&self, key: &u32) -> Option<String> {
+                self.calls.fetch_add(1, Ordering::AcqRel);
+                (*key <= self.max_key).then(|| format!("{key} from {}", self.max_key))
+            }
+        }
+
+        let (calls1, calls2) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+        let cache = Cache::builder()
+            .max_capacity(100)
+            .loader(CountingLoader {
+                max_key: 10,
+                calls: Arc::clone(&calls1),
+            })
+            .loader(CountingLoader {
+                max_key: 20,
+                calls: Arc::clone(&calls2),
+            })
+            .build();
+
+        assert_eq!(cache.get_or_load(&5), Some("5 from 10".into()));
+        assert_eq!(cache.get_or_load(&15), Some("15 from 20".into()));
+        assert_eq!(cache.get_or_load(&25), None);
+        assert_eq!(calls1.load(Ordering::Acquire), 3);
+        assert_eq!(calls2.load(Ordering::Acquire), 2);
+
+        // The loaded values are cached.
+        assert_eq!(cache.get(&15), Some("15 from 20".into()));
+        assert_eq!(cache.get_or_load(&5), Some("5 from 10".into()));
+        assert_eq!(calls1.load(Ordering::Acquire), 3);
+        assert!(!cache.contains_key(&25));
+
+        // Without loaders, get_or_load works like get.
+        let cache: Cache<u32, String> = Cache::new(100);
+        assert_eq!(cache.get_or_load(&5), None);
+    }
+
+    #[test]
+    fn namespace_expiration() {
+        let clock = crate::MockClock::new();
+        let cache = Cache::builder()
+            .max_capacity(100)
+            .namespace_expiration(
+                |k: &&str| k.starts_with("token:"),
+                Some(Duration::from_secs(10)),
+                None,
+            )
+            .namespace_expiration(
+                |k: &&str| k.starts_with("meta:"),
+                Some(Duration::from_secs(30)),
+                Some(Duration::from_secs(5)),
+            )
+            .clock(clock.clone())
+            .build();
+
+        cache.insert("token:a", "alice");
+        cache.insert("meta:b", "bob");
+        cache.insert("other:c", "cindy");
+
+        // The idle timer of "meta:b" is reset by the get.
+        clock.advance(Duration::from_secs(4));
+        assert_eq!(cache.get(&"meta:b"), Some("bob"));
+        clock.advance(Duration::from_secs(4));
+        assert_eq!(cache.get(&"meta:b"), Some("bob"));
+        assert_eq!(cache.get(&"token:a"), Some("alice"));
+
+        // "token:a" lives for 10 seconds.
+        clock.advance(Duration::from_secs(2));
+        assert_eq!(cache.get(&"token:a"), None);
+        assert_eq!(cache.get(&"meta:b"), Some("bob"));
+
+        // "meta:b" has not been read for 5 seconds.
+        clock.advance(Duration::from_secs(5));
+        assert_eq!(cache.get(&"meta:b"), None);
+
+        // "other:c" is in no namespace, so it never expires.
+        clock.advance(Duration::from_secs(60));
+        assert_eq!(cache.get(&"other:c"), Some("cindy"));
+
+        cache.run_pending_tasks();
+        assert_eq!(cache.entry_count(), 1);
+    }
+
+    #[test]
+    fn error_time_to_live() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let clock = crate::MockClock::new();
+        let cache = Cache::builder()
+            .max_capacity(100)
+            .error_time_to_live(Duration::from_secs(10))
+            .clock(clock.clone())
+            .build();
+
+        let calls = AtomicUsize::new(0);
+        let fail = || {
+            calls.fetch_add(1, Ordering::AcqRel);
+            Err("failed")
+        };
+
+        assert_eq!(cache.try_get_with(1, fail), Err(Arc::new("failed")));
+        assert_eq!(calls.load(Ordering::Acquire), 1);
+
+        // The cached error is returned without calling the init closure.
+        clock.advance(Duration::from_secs(5));
+        assert_eq!(cache.try_get_with(1, fail), Err(Arc::new("failed")));
+        assert_eq!(calls.load(Ordering::Acquire), 1);
+
+        // A call with a different error type does not get the cached error.
+        let res: Result<_, Arc<String>> = cache.try_get_with(1, || Ok(10));
+        assert_eq!(res, Ok(10));
+
+        // The success has replaced the cached error.
+        cache.invalidate(&1);
+        assert_eq!(cache.try_get_with(1, || Ok::<_, &str>(11)), Ok(11));
+
+        // The cached error expires after its time to live.
+        assert_eq!(cache.try_get_with(2, fail), Err(Arc::new("failed")));
+        assert_eq!(calls.load(Ordering::Acquire), 2);
+        clock.advance(Duration::from_secs(10));
+        assert_eq!(cache.try_get_with(2, fail), Err(Arc::new("failed")));
+        assert_eq!(calls.load(Ordering::Acquire), 3);
+
+        // Inserting a value discards the cached error.
+        cache.insert(2, 20);
+        cache.invalidate(&2);
+        assert_eq!(cache.try_get_with(2, fail), Err(Arc::new("failed")));
+        assert_eq!(calls.load(Ordering::Acquire), 4);
+    }
+
+    #[test]
+    fn expired_errors_are_purged_by_housekeeper() {
+        let clock = crate::MockClock::new();
+        let cache = Cache::builder()
+            .max_capacity(100)
+            .error_time_to_live(Duration::from_secs(10))
+            .clock(clock.clone())
+            .build();
+
+        for key in 0..10 {
+            assert!(cache.try_get_with(key, || Err("failed")).is_err());
+        }
+        let errors = cache.error_cache.as_ref().unwrap();
+        assert!(!errors.is_empty());
+
+        // A write after the expiration runs the maintenance, which purges the
+        // expired errors without an explicit call to `run_pending_tasks`.
+        clock.advance(Duration::from_secs(10));
+        cache.insert(100, 100);
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn get_entry() {
+        let clock = crate::MockClock::new();
+        let cache = Cache::builder()
+            .max_capacity(100)
+            .time_to_live(Duration::from_secs(60))
+            .time_to_idle(Duration::from_secs(30))
+            .clock(clock.clone())
+            .build();
+
+        cache.insert("a", "alice");
+        assert!(cache.get_entry(&"b").is_none());
+
+        clock.advance(Duration::from_secs(10));
+        let entry = cache.get_entry(&"a").unwrap();
+        assert_eq!(entry.key(), &"a");
+        assert_eq!(entry.value(), &"alice");
+        let metadata = entry.metadata().unwrap();
+        assert_eq!(metadata.age(), Duration::from_secs(10));
+        // The idle timer was reset, so the time-to-idle expires 30 seconds later.
+        assert_eq!(metadata.expires_in(), Some(Duration::from_secs(30)));
+
+        clock.advance(Duration::from_secs(25));
+        let metadata = cache.get_entry(&"a").unwrap().metadata().cloned().unwrap();
+        assert_eq!(metadata.age(), Duration::from_secs(35));
+        // Now the time-to-live expires earlier.
+        assert_eq!(metadata.expires_in(), Some(Duration::from_secs(25)));
+
+        // Updating the value resets the age.
+        cache.insert("a", "anna");
+        let metadata = cache.get_entry(&"a").unwrap().metadata().cloned().unwrap();
+        assert_eq!(metadata.age(), Duration::ZERO);
+
+        // Other methods do not return the metadata.
+        assert!(cache.entry("a").or_insert("amy").metadata().is_none());
+
+        let cache = Cache::new(100);
+        cache.insert(1, 1);
+        let entry = cache.get_entry(&1).unwrap();
+        assert_eq!(entry.metadata().unwrap().expires_in(), None);
+    }
+
+    #[test]
+    fn eviction_listener_with_metadata() {
+        let clock = crate::MockClock::new();
+        let removed = Arc::new(Mutex::new(Vec::new()));
+        let removed1 = Arc::clone(&removed);
+        let cache = Cache::builder()
+            .max_capacity(100)
+            .weigher(|_k: &u32, v: &String| v.len() as u32)
+            .eviction_listener_with_metadata(move |k, _v, cause, metadata| {
+                removed1.lock().push((*k, cause, metadata.clone()));
+            })
+            .clock(clock.clone())
+            .build();
+
+        cache.insert(1, "alice".to_string());
+        clock.advance(Duration::from_secs(5));
+        assert!(cache.get(&1).is_some());
+        clock.advance(Duration::from_secs(5));
+        cache.invalidate(&1);
+
+        let removed = removed.lock();
+        assert_eq!(removed.len(), 1);
+        let (key, cause, metadata) = &removed[0];
+        assert_eq!((*key, *cause), (1, RemovalCause::Explicit));
+        assert_eq!(metadata.policy_weight(), 5);
+        let (lm, la) = (metadata.last_modified(), metadata.last_accessed());
+        assert_eq!(la.unwrap() - lm.unwrap(), Duration::from_secs(5));
+    }
+
+    #[test]
+    fn insert_with_expiry() {
+        let clock = crate::MockClock::new();
+        let cache = Cache::builder()
+            .max_capacity(100)
+            .clock(clock.clone())
+            .build();
+
+        cache.insert_with_expiry("a", 10, |_k, v, _now| Some(Duration::from_secs(*v as u64)));
+        cache.insert_with_expiry("b", 20, |_k, _v, _now| None);
+        cache.insert("c", 30);
+        cache.run_pending_tasks();
+
+        clock.advance(Duration::from_secs(9));
+        assert_eq!(cache.get(&"a"), Some(10));
+
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(cache.get(&"a"), None);
+        assert_eq!(cache.get(&"b"), Some(20));
+        assert_eq!(cache.get(&"c"), Some(30));
+
+        // Updating an entry with a new one-shot expiry.
+        cache.insert_with_expiry("b", 21, |_k, _v, _now| Some(Duration::from_secs(5)));
+        clock.advance(Duration::from_secs(5));
+        assert_eq!(cache.get(&"b"), None);
+
+        cache.run_pending_tasks();
+        assert_eq!(cache.entry_count(), 1);
+    }
+
+    #[test]
+    fn time_to_live_with_time_sources() {
+        use crate::TimeSource;
+
+        for source in [
+            TimeSource::Quanta,
+            TimeSource::Std,
+            TimeSource::Coarse,
+            TimeSource::WallClock,
+        ] {
+            let cache = Cache::builder()
+                .max_capacity(100)
+                .time_to_live(Duration::from_millis(100))
+                .time_source(source)
+                .build();
+
+            cache.insert("a", "alice");
+            assert_eq!(cache.get(&"a"), Some("alice"), "{source:?}");
+
+            std::thread::sleep(Duration::from_millis(150));
+            assert_eq!(cache.get(&"a"), None, "{source:?}");
+        }
+    }
+
+    #[test]
+    fn time_to_live() {
+        // The following `Vec`s will hold actual and expected notifications.
+        let actual = Arc::new(Mutex::new(Vec::new()));
+        let mut expected = Vec::new();
+
+        // Create an eviction listener.
+        let a1 = Arc::clone(&actual);
+        let listener = move |k, v, cause| a1.lock().push((k, v, cause));
+
+        // Create a cache with the eviction listener.
+        let mut cache = Cache::builder()
+            .max_capacity(100)
+            .time_to_live(Duration::from_secs(10))
+            .eviction_listener(listener)
+            .build();
+        cache.reconfigure_for_testing();
+
+        let (clock, mock) = Clock::mock();
+        cache.set_expiration_clock(Some(clock));
+
+        // Make the cache exterior immutable.
+        let cache = cache;
+
+        cache.insert("a", "alice");
+        cache.run_pending_tasks();
+
+        mock.increment(Duration::from_secs(5)); // 5 secs from the start.
+        cache.run_pending_tasks();
+
+        assert_eq!(cache.get(&"a"), Some("alice"));
+        assert!(cache.contains_key(&"a"));
+
+        mock.increment(Duration::from_secs(5)); // 10 secs.
+        expected.push((Arc::new("a"), "alice", RemovalCause::Expired));
+        assert_eq!(cache.get(&"a"), None);
+        assert!(!cache.contains_key(&"a"));
+
+        assert_eq!(cache.iter().count(), 0);
+
+        cache.run_pending_tasks();
+        assert!(cache.is_table_empty());
+
+        cache.insert("b", "bob");
+        cache.run_pending_tasks();
+
+        assert_eq!(cache.entry_count(), 1);
+
+        mock.increment(Duration::from_secs(5)); // 15 secs.
+        cache.run_pending_tasks();
+
+        assert_eq!(cache.get(&"b"), Some("bob"));
+        assert!(cache.contains_key(&"b"));
+        assert_eq!(cache.entry_count(), 1);
+
+        cache.insert("b", "bill");
+        expected.push((Arc::new("b"), "bob", RemovalCause::Replaced));
+        cache.run_pending_tasks();
+
+        mock.increment(Duration::from_secs(5)); // 20 secs
+        cache.run_pending_tasks();
+
+        assert_eq!(cache.get(&"b"), Some("bill"));
+        assert!(cache.contains_key(&"b"));
+        assert_eq!(cache.entry_count(), 1);
+
+        mock.increment(Duration::from_secs(5)); // 25 secs
+        expected.push((Arc::new("b"), "bill", RemovalCause::Expired));
+
+        assert_eq!(cache.get(&"a"), None);
+        assert_eq!(cache.get(&"b"), None);
+        assert!(!cache.contains_key(&"a"));
+        assert!(!cache.contains_key(&"b"));
+
+        assert_eq!(cache.iter().count(), 0);
+
+        cache.run_pending_tasks();
+        assert!(cache.is_table_empty());
+
+        verify_notification_vec(&cache, actual, &expected);
+    }
+
+    #[test]
+    fn time_to_idle() {
+        // The following `Vec`s will hold actual and expected notifications.
+        let actual = Arc::new(Mutex::new(Vec::new()));
+        let mut expected = Vec::new();
+
+        // Create an eviction listener.
+        let a1 = Arc::clone(&actual);
+        let listener = move |k, v, cause| a1.lock().push((k, v, cause));
+
+        // Create a cache with the eviction listener.
+        let mut cache = Cache::builder()
+            .max_capacity(100)
+            .time_to_idle(Duration::from_secs(10))
+            .eviction_listener(listener)
+            .build();
+        cache.reconfigure_for_testing();
+
+        let (clock, mock) = Clock::mock();
+        cache.set_expiration_clock(Some(clock));
+
+        // Make the cache exterior immutable.
+        let cache = cache;
+
+        cache.insert("a", "alice");
+        cache.run_pending_tasks();
+
+        mock.increment(Duration::from_secs(5)); // 5 secs from the start.
+        cache.run_pending_tasks();
+
+        assert_eq!(cache.get(&"a"), Some("alice"));
+
+        mock.increment(Duration::from_secs(5)); // 10 secs.
+        cache.run_pending_tasks();
+
+        cache.insert("b", "bob");
+        cache.run_pending_tasks();
+
+        assert_eq!(cache.entry_count(), 2);
+
+        mock.increment(Duration::from_secs(2)); // 12 secs.
+        cache.run_pending_tasks();
+
+        // contains_key does not reset the idle timer for the key.
+        assert!(cache.contains_key(&"a"));
+        assert!(cache.contains_key(&"b"));
+        cache.run_pending_tasks();
+
+        assert_eq!(cache.entry_count(), 2);
+
+        mock.increment(Duration::from_secs(3)); // 15 secs.
+        expected.push((Arc::new("a"), "alice", RemovalCause::Expired));
+
+        assert_eq!(cache.get(&"a"), None);
+        assert_eq!(cache.get(&"b"), Some("bob"));
+        assert!(!cache.contains_key(&"a"));
+        assert!(cache.contains_key(&"b"));
+
+        assert_eq!(cache.iter().count(), 1);
+
+        cache.run_pending_tasks();
+        assert_eq!(cache.entry_count(), 1);
+
+        mock.increment(Duration::from_secs(10)); // 25 secs
+        expected.push((Arc::new("b"), "bob", RemovalCause::Expired));
+
+        assert_eq!(cache.get(&"a"), None);
+        assert_eq!(cache.get(&"b"), None);
+        assert!(!cache.contains_key(&"a"));
+        assert!(!cache.contains_key(&"b"));
+
+        assert_eq!(cache.iter().count(), 0);
+
+        cache.run_pending_tasks();
+        assert!(cache.is_table_empty());
+
+        verify_notification_vec(&cache, actual, &expected);
+    }
+
+    // https://github.com/moka-rs/moka/issues/359
+    #[test]
+    fn ensure_access_time_is_updated_immediately_after_read() {
+        let mut cache = Cache::builder()
+            .max_capacity(10)
+            .time_to_idle(Duration::from_secs(5))
+            .build();
+        cache.reconfigure_for_testing();
+
+        let (clock, mock) = Clock::mock();
+        cache.set_expiration_clock(Some(clock));
+
+        // Make the cache exterior immutable.
+        let cache = cache;
+
+        cache.insert(1, 1);
+
+        mock.increment(Duration::from_secs(4));
+        assert_eq!(cache.get(&1), Some(1));
+
+        mock.increment(Duration::from_secs(2));
+        assert_eq!(cache.get(&1), Some(1));
+        cache.run_pending_tasks();
+        assert_eq!(cache.get(&1), Some(1));
+    }
+
+    #[test]
+    fn time_to_live_by_expiry_type() {
+        // Define an expiry type.
+        struct MyExpiry {
+            counters: Arc<ExpiryCallCounters>,
+        }
+
+        impl MyExpiry {
+            fn new(counters: Arc<ExpiryCallCounters>) -> Self {
+                Self { counters }
+            }
+        }
+
+        impl Expiry<&str, &str> for MyExpiry {
+            fn expire_after_create(
+                &self,
+                _key: &&str,
+                _value: &&str,
+                _current_time: StdInstant,
+            ) -> Option<Duration> {
+                self.counters.incl_actual_creations();
+                Some(Duration::from_secs(10))
+            }
+
+            fn expire_after_update(
+                &self,
+                _key: &&str,
+                _value: &&str,
+                _current_time: StdInstant,
+                _current_duration: Option<Duration>,
+            ) -> Option<Duration> {
+                self.counters.incl_actual_updates();
+                Some(Duration::from_secs(10))
+            }
+        }
+
+        // The following `Vec`s will hold actual and expected notifications.
+        let actual = Arc::new(Mutex::new(Vec::new()));
+        let mut expected = Vec::new();
+
+        // Create expiry counters and the expiry.
+        let expiry_counters = Arc::new(ExpiryCallCounters::default());
+        let expiry = MyExpiry::new(Arc::clone(&expiry_counters));
+
+        // Create an eviction listener.
+        let a1 = Arc::clone(&actual);
+        let listener = move |k, v, cause| a1.lock().push((k, v, cause));
+
+        // Create a cache with the eviction listener.
+        let mut cache = Cache::builder()
+            .max_capacity(100)
+            .expire_after(expiry)
+            .eviction_listener(listener)
+            .build();
+        cache.reconfigure_for_testing();
+
+        let (clock, mock) = Clock::mock();
+        cache.set_expiration_clock(Some(clock));
+
+        // Make the cache exterior immutable.
+        let cache = cache;
+
+        cache.insert("a", "alice");
+        expiry_counters.incl_expected_creations();
+        cache.run_pending_tasks();
+
+        mock.increment(Duration::from_secs(5)); // 5 secs from the start.
+        cache.run_pending_tasks();
+
+        assert_eq!(cache.get(&"a"), Some("alice"));
+        assert!(cache.contains_key(&"a"));
+
+        mock.increment(Duration::from_secs(5)); // 10 secs.
+        expected.push((Arc::new("a"), "alice", RemovalCause::Expired));
+        assert_eq!(cache.get(&"a"), None);
+        assert!(!cache.contains_key(&"a"));
+
+        assert_eq!(cache.iter().count(), 0);
+
+        cache.run_pending_tasks();
+        assert!(cache.is_table_empty());
+
+        cache.insert("b", "bob");
+        expiry_counters.incl_expected_creations();
+        cache.run_pending_tasks();
+
+        assert_eq!(cache.entry_count(), 1);
+
+        mock.increment(Duration::from_secs(5)); // 15 secs.
+        cache.run_pending_tasks();
+
+        assert_eq!(cache.get(&"b"), Some("bob"));
+        assert!(cache.contains_key(&"b"));
+        assert_eq!(cache.entry_count(), 1);
+
+        cache.insert("b", "bill");
+        expected.push((Arc::new("b"), "bob", RemovalCause::Replaced));
+        expiry_counters.incl_expected_updates();
+        cache.run_pending_tasks();
+
+        mock.increment(Duration::from_secs(5)); // 20 secs
+        cache.run_pending_tasks();
+
+        assert_eq!(cache.get(&"b"), Some("bill"));
+        assert!(cache.contains_key(&"b"));
+        assert_eq!(cache.entry_count(), 1);
+
+        mock.increment(Duration::from_secs(5)); // 25 secs
+        expected.push((Arc::new("b"), "bill", RemovalCause::Expired));
+
+        assert_eq!(cache.get(&"a"), None);
+        assert_eq!(cache.get(&"b"), None);
+        assert!(!cache.contains_key(&"a"));
+        assert!(!cache.contains_key(&"b"));
+
+        assert_eq!(cache.iter().count(), 0);
+
+        cache.run_pending_tasks();
+        assert!(cache.is_table_empty());
+
+        expiry_counters.verify();
+        verify_notification_vec(&cache, actual, &expected);
+    }
+
+    #[cfg(feature = "entry-stats")]
+    #[test]
+    fn expiry_with_entry_stats() {
+        use crate::policy::EntryStats;
+
+        // Keeps the entries for 10 seconds, or for 100 seconds once they have been
+        // read three times.
+        struct MyExpiry {
+            stats: Arc<Mutex<Vec<(u32, u32, StdInstant)>>>,
+        }
+
+        impl Expiry<&str, &str> for MyExpiry {
+            fn expire_after_create(
+                &self,
+                _key: &&str,
+                _value: &&str,
+                _current_time: StdInstant,
+            ) -> Option<Duration> {
+                Some(Duration::from_secs(10))
+            }
+
+            fn expire_after_read_with_stats(
+                &self,
+                _key: &&str,
+                _value: &&str,
+                _current_time: StdInstant,
+                current_duration: Option<Duration>,
+                _last_modified_at: StdInstant,
+                stats: &EntryStats,
+            ) -> Option<Duration> {
+                self.stats.lock().push((
+                    stats.read_count(),
+                    stats.write_count(),
+                    stats.created_at(),
+                ));
+                if stats.read_count() == 3 {
+                    Some(Duration::from_secs(100))
+                } else {
+                    current_duration
+                }
+            }
+
+            fn expire_after_update_with_stats(
+                &self,
+                _key: &&str,
+                _value: &&str,
+                _current_time: StdInstant,
+                current_duration: Option<Duration>,
+                stats: &EntryStats,
+            ) -> Option<Duration> {
+                self.stats.lock().push((
+                    stats.read_count(),
+                    stats.write_count(),
+                    stats.created_at(),
+                ));
+                current_duration
+            }
+        }
+
+        let stats = Arc::new(Mutex::new(Vec::new()));
+        let mut cache = Cache::builder()
+            .max_capacity(100)
+            .expire_after(MyExpiry {
+                stats: Arc::clone(&stats),
+            })
+            .build();
+        cache.reconfigure_for_testing();
+
+        let (clock, mock) = Clock::mock();
+        cache.set_expiration_clock(Some(clock));
+
+        // Make the cache exterior immutable.
+        let cache = cache;
+
+        cache.insert("a", "alice");
+        cache.run_pending_tasks();
+
+        mock.increment(Duration::from_secs(1));
+        cache.insert("a", "anna");
+        cache.run_pending_tasks();
+
+        for _ in 0..3 {
+            mock.increment(Duration::from_secs(1));
+            assert_eq!(cache.get(&"a"), Some("anna"));
+        }
+        cache.run_pending_tasks();
+
+        {
+            let stats = stats.lock();
+            let created_at = stats[0].2;
+            assert_eq!(
+                *stats,
+                vec![
+                    (0, 2, created_at),
+                    (1, 2, created_at),
+                    (2, 2, created_at),
+                    (3, 2, created_at),
+                ]
+            );
+        }
+
+        // Promoted by the third read, so the entry outlives the initial 10 seconds.
+        mock.increment(Duration::from_secs(20));
+        assert_eq!(cache.get(&"a"), Some("anna"));
+    }
+
+    #[test]
+    fn time_to_idle_by_expiry_type() {
+        // Define an expiry type.
+        struct MyExpiry {
+            counters: Arc<ExpiryCallCounters>,
+        }
+
+        impl MyExpiry {
+            fn new(counters: Arc<ExpiryCallCounters>) -> Self {
+                Self { counters }
+            }
+        }
+
+        impl Expiry<&str, &str> for MyExpiry {
+            fn expire_after_read(
+                &self,
+                _key: &&str,
+                _value: &&str,
+                _current_time: StdInstant,
+                _current_duration: Option<Duration>,
+                _last_modified_at: StdInstant,
+            ) -> Option<Duration> {
+                self.counters.incl_actual_reads();
+                Some(Duration::from_secs(10))
+            }
+        }
+
+        // The following `Vec`s will hold actual and expected notifications.
+        let actual = Arc::new(Mutex::new(Vec::new()));
+        let mut expected = Vec::new();
+
+        // Create expiry counters and the expiry.
+        let expiry_counters = Arc::new(ExpiryCallCounters::default());
+        let expiry = MyExpiry::new(Arc::clone(&expiry_counters));
+
+        // Create an eviction listener.
+        let a1 = Arc::clone(&actual);
+        let listener = move |k, v, cause| a1.lock().push((k, v, cause));
+
+        // Create a cache with the eviction listener.
+        let mut cache = Cache::builder()
+            .max_capacity(100)
+            .expire_after(expiry)
+            .eviction_listener(listener)
+            .build();
+        cache.reconfigure_for_testing();
+
+        let (clock, mock) = Clock::mock();
+        cache.set_expiration_clock(Some(clock));
+
+        // Make the cache exterior immutable.
+        let cache = cache;
+
+        cache.insert("a", "alice");
+        cache.run_pending_tasks();
+
+        mock.increment(Duration::from_secs(5)); // 5 secs from the start.
+        cache.run_pending_tasks();
+
+        assert_eq!(cache.get(&"a"), Some("alice"));
+        expiry_counters.incl_expected_reads();
+
+        mock.increment(Duration::from_secs(5)); // 10 secs.
+        cache.run_pending_tasks();
+
+        cache.insert("b", "bob");
+        cache.run_pending_tasks();
+
+        assert_eq!(cache.entry_count(), 2);
+
+        mock.increment(Duration::from_secs(2)); // 12 secs.
+        cache.run_pending_tasks();
+
+        // contains_key does not reset the idle timer for the key.
+        assert!(cache.contains_key(&"a"));
+        assert!(cache.contains_key(&"b"));
+        cache.run_pending_tasks();
+
+        assert_eq!(cache.entry_count(), 2);
+
+        mock.increment(Duration::from_secs(3)); // 15 secs.
+        expected.push((Arc::new("a"), "alice", RemovalCause::Expired));
+
+        assert_eq!(cache.get(&"a"), None);
+        assert_eq!(cache.get(&"b"), Some("bob"));
+        expiry_counters.incl_expected_reads();
+        assert!(!cache.contains_key(&"a"));
+        assert!(cache.contains_key(&"b"));
+
+        assert_eq!(cache.iter().count(), 1);
+
+        cache.run_pending_tasks();
+        assert_eq!(cache.entry_count(), 1);
+
+        mock.increment(Duration::from_secs(10)); // 25 secs
+        expected.push((Arc::new("b"), "bob", RemovalCause::Expired));
+
+        assert_eq!(cache.get(&"a"), None);
+        assert_eq!(cache.get(&"b"), None);
+        assert!(!cache.contains_key(&"a"));
+        assert!(!cache.contains_key(&"b"));
+
+        assert_eq!(cache.iter().count(), 0);
+
+        cache.run_pending_tasks();
+        assert!(cache.is_table_empty());
+
+        expiry_counters.verify();
+        verify_notification_vec(&cache, actual, &expected);
+    }
+
+    /// Verify that the `Expiry::expire_after_read()` method is called in `get_with`
+    /// only when the key was already present in the cache.
+    #[test]
+    fn test_expiry_using_get_with() {
+        // Define an expiry type, which always return `None`.
+        struct NoExpiry {
+            counters: Arc<ExpiryCallCounters>,
+        }
+
+        impl NoExpiry {
+            fn new(counters: Arc<ExpiryCallCounters>) -> Self {
+                Self { counters }
+            }
+        }
+
+        impl Expiry<&str, &str> for NoExpiry {
+            fn expire_after_create(
+                &self,
+                _key: &&str,
+                _value: &&str,
+                _current_time: StdInstant,
+            ) -> Option<Duration> {
+                self.counters.incl_actual_creations();
+                None
+            }
+
+            fn expire_after_read(
+                &self,
+                _key: &&str,
+                _value: &&str,
+                _current_time: StdInstant,
+                _current_duration: Option<Duration>,
+                _last_modified_at: StdInstant,
+            ) -> Option<Duration> {
+                self.counters.incl_actual_reads();
+                None
+            }
+
+            fn expire_after_update(
+                &self,
+                _key: &&str,
+                _value: &&str,
+                _current_time: StdInstant,
+                _current_duration: Option<Duration>,
+            ) -> Option<Duration> {
+                unreachable!("The `expire_after_update()` method should not be called.");
+            }
+        }
+
+        // Create expiry counters and the expiry.
+        let expiry_counters = Arc::new(ExpiryCallCounters::default());
+        let expiry = NoExpiry::new(Arc::clone(&expiry_counters));
+
+        // Create a cache with the expiry and eviction listener.
+        let mut cache = Cache::builder()
+            .max_capacity(100)
+            .expire_after(expiry)
+            .build();
+        cache.reconfigure_for_testing();
+
+        // Make the cache exterior immutable.
+        let cache = cache;
+
+        // The key is not present.
+        cache.get_with("a", || "alice");
+        expiry_counters.incl_expected_creations();
+        cache.run_pending_tasks();
+
+        // The key is present.
+        cache.get_with("a", || "alex");
+        expiry_counters.incl_expected_reads();
+        cache.run_pending_tasks();
+
+        // The key is not present.
+        cache.invalidate("a");
+        cache.get_with("a", || "amanda");
+        expiry_counters.incl_expected_creations();
+        cache.run_pending_tasks();
+
+        expiry_counters.verify();
+    }
+
+    // https://github.com/moka-rs/moka/issues/345
+    #[test]
+    fn test_race_between_updating_entry_and_processing_its_write_ops() {
+        let cache = Cache::builder()
+            .max_capacity(2)
+            .time_to_idle(Duration::from_secs(1))
+            .build();
+        let (clock, mock) = Clock::mock();
+        cache.set_expiration_clock(Some(clock));
+
+        cache.insert("a", "alice");
+        cache.insert("b", "bob");
+        cache.insert("c", "cathy"); // c1
+        mock.increment(Duration::from_secs(2));
+
+        // The following `insert` will do the followings:
+        // 1. Replaces current "c" (c1) in the concurrent hash table (cht).
+        // 2. Runs the pending tasks implicitly.
+        //    (1) "a" will be admitted.
+        //    (2) "b" will be admitted.
+        //    (3) c1 will be evicted by size constraint.
+        //    (4) "a" will be evicted due to expiration.
+        //    (5) "b" will be evicted due to expiration.
+        // 3. Send its `WriteOp` log to the channel.
+        cache.insert("c", "cindy"); // c2
+
+        // Remove "c" (c2) from the cht.
+        assert_eq!(cache.remove(&"c"), Some("cindy")); // c-remove
+
+        mock.increment(Duration::from_secs(2));
+
+        // The following `run_pending_tasks` will do the followings:
+        // 1. Admits "c" (c2) to the cache. (Create a node in the LRU deque)
+        // 2. Because of c-remove, removes c2's node from the LRU deque.
+        cache.run_pending_tasks();
+        assert_eq!(cache.entry_count(), 0);
+    }
+
+    #[test]
+    fn test_race_between_recreating_entry_and_processing_its_write_ops() {
+        let cache = Cache::builder().max_capacity(2).build();
+
+        cache.insert('a', "a");
+        cache.insert('b', "b");
+        cache.run_pending_tasks();
+
+        cache.insert('c', "c1"); // (a) `EntryInfo` 1, gen: 1
+        assert!(cache.remove(&'a').is_some()); // (b)
+        assert!(cache.remove(&'b').is_some()); // (c)
+        assert!(cache.remove(&'c').is_some()); // (d) `EntryInfo` 1, gen: 2
+        cache.insert('c', "c2"); // (e) `EntryInfo` 2, gen: 1
+
+        // Now the `write_op_ch` channel contains the following `WriteOp`s:
+        //
+        // - 0: (a) insert "c1" (`EntryInfo` 1, gen: 1)
+        // - 1: (b) remove "a"
+        // - 2: (c) remove "b"
+        // - 3: (d) remove "c1" (`EntryInfo` 1, gen: 2)
+        // - 4: (e) insert "c2" (`EntryInfo` 2, gen: 1)
+        //
+        // 0 for "c1" is going to be rejected because the cache is full. Let's ensure
+        // processing 0 must not remove "c2" from the concurrent hash table. (Their
+        // gen are the same, but `EntryInfo`s are different)
+        cache.run_pending_tasks();
+        assert_eq!(cache.get(&'c'), Some("c2"));
+    }
+
+    #[test]
+    fn test_iter() {
+        const NUM_KEYS: usize = 50;
+
+        fn make_value(key: usize) -> String {
+            format!("val: {key}")
+        }
+
+        let cache = Cache::builder()
+            .max_capacity(100)
+            .time_to_idle(Duration::from_secs(10))
+            .build();
+
+        for key in 0..NUM_KEYS {
+            cache.insert(key, make_value(key));
+        }
+
+        let mut key_set = std::collections::HashSet::new();
+
+        for (key, value) in &cache {
+            assert_eq!(value, make_value(*key));
+
+            key_set.insert(*key);
+        }
+
+        // Ensure there are no missing or duplicate keys in the iteration.
+        assert_eq!(key_set.len(), NUM_KEYS);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_par_iter() {
+        use rayon::prelude::*;
+
+        const NUM_KEYS: usize = 1000;
+
+        fn make_value(key: usize) -> String {
+            format!("val: {key}")
+        }
+
+        let cache = Cache::builder()
+            .max_capacity(NUM_KEYS as u64 * 2)
+            .time_to_idle(Duration::from_secs(10))
+            .build();
+
+        for key in 0..NUM_KEYS {
+            cache.insert(key, make_value(key));
+        }
+        cache.invalidate(&0);
+
+        let entries = cache.par_iter().collect::<Vec<_>>();
+        for (key, value) in &entries {
+            assert_eq!(*value, make_value(**key));
+        }
+
+        // Ensure there are no missing or duplicate keys in the iteration.
+        let key_set = entries
+            .iter()
+            .map(|(k, _)| **k)
+            .collect::<std::collections::HashSet<_>>();
+        assert_eq!(entries.len(), NUM_KEYS - 1);
+        assert_eq!(key_set.len(), NUM_KEYS - 1);
+        assert!(!key_set.contains(&0));
+    }
+
+    #[test]
+    fn test_iter_by_recency() {
+        // Use more keys than the chunk size of the snapshot.
+        const NUM_KEYS: usize = 1200;
+
+        let mut cache = Cache::new(NUM_KEYS as u64);
+        cache.reconfigure_for_testing();
+
+        // Make the cache exterior immutable.
+        let cache = cache;
+
+        for key in 0..NUM_KEYS {
+            cache.insert(key, key * 10);
+            // Do not let the write op channel get full.
+            if key % 100 == 0 {
+                cache.run_pending_tasks();
+            }
+        }
+        cache.run_pending_tasks();
+
+        // Read some keys to move them to the MRU end.
+        for key in [5, 700, 3] {
+            assert_eq!(cache.get(&key), Some(key * 10));
+        }
+        // Invalidate a key. It should not be returned.
+        cache.invalidate(&10);
+        cache.run_pending_tasks();
+
+        let mut expected = (0..NUM_KEYS)
+            .filter(|k| ![3, 5, 10, 700].contains(k))
+            .collect::<Vec<_>>();
+        expected.extend([5, 700, 3]);
+
+        let (keys, values): (Vec<_>, Vec<_>) =
+            cache.iter_by_recency().map(|(k, v)| (*k, v)).unzip();
+        assert_eq!(keys, expected);
+        assert!(keys.iter().zip(values).all(|(k, v)| v == k * 10));
+    }
+
+    #[test]
+    fn test_iter_by_estimated_frequency() {
+        let mut cache = Cache::new(100);
+        cache.reconfigure_for_testing();
+
+        // Make the cache exterior immutable.
+        let cache = cache;
+
+        for key in 0..10 {
+            cache.insert(key, key * 10);
+        }
+        cache.run_pending_tasks();
+
+        // Read the keys 3, 7 and 1 for different times.
+        for (key, times) in [(3, 9), (7, 6), (1, 3)] {
+            for _ in 0..times {
+                assert_eq!(cache.get(&key), Some(key * 10));
+            }
+        }
+        cache.invalidate(&7);
+        cache.run_pending_tasks();
+
+        let entries = cache.iter_by_estimated_frequency().collect::<Vec<_>>();
+        assert_eq!(entries.len(), 9);
+        assert_eq!(*entries[0].0, 3);
+        assert_eq!(entries[0].1, 30);
+        assert_eq!(*entries[1].0, 1);
+        assert!(entries.iter().all(|(k, v)| *v == **k * 10));
+    }
+
+    #[test]
+    fn test_sample_entries() {
+        const NUM_KEYS: u64 = 1000;
+
+        let cache = Cache::builder()
+            .max_capacity(NUM_KEYS * 2)
+            .time_to_idle(Duration::from_secs(10))
+            .build();
+        for i in 0..NUM_KEYS {
+            cache.insert(i, i * 10);
+        }
+
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        let mut rng = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+
+        let mut counts = vec![0u32; NUM_KEYS as usize];
+
+        // Sparse sampling (probing random buckets) and dense sampling (reservoir
+        // sampling).
+        for n in [10, 100, 800] {
+            for _ in 0..20 {
+                let samples = cache.sample_entries(n, &mut rng);
+                assert_eq!(samples.len(), n);
+
+                let mut keys = std::collections::HashSet::new();
+                for (k, v) in samples {
+                    assert_eq!(v, *k * 10);
+                    // No duplicates.
+                    assert!(keys.insert(*k));
+                    counts[*k as usize] += 1;
+                }
+            }
+        }
+
+        // All keys should have been picked at least once (in average, each key
+        // is picked 18.2 times).
+        assert!(counts.iter().all(|&c| c > 0));
+
+        // Requesting more than the number of entries returns all entries.
+        assert_eq!(
+            cache.sample_entries(2000, &mut rng).len(),
+            NUM_KEYS as usize
+        );
+        assert!(cache.sample_entries(0, &mut rng).is_empty());
+
+        cache.invalidate_all();
+        assert!(cache.sample_entries(10, &mut rng).is_empty());
+    }
+
+    #[test]
+    fn entries_expiring_within() {
+        let secs = Duration::from_secs;
+
+        // Time-to-live only. The write order deque is walked.
+        let mut cache = Cache::builder().time_to_live(secs(10)).build();
+        cache.reconfigure_for_testing();
+        let (clock, mock) = Clock::mock();
+        cache.set_expiration_clock(Some(clock));
+        let cache = cache;
+
+        cache.insert("a", 1);
+        mock.increment(secs(3));
+        cache.insert("b", 2);
+        mock.increment(secs(3)); // 6 secs from the start.
+        cache.insert("c", 3);
+        cache.run_pending_tasks();
+
+        // "a" expires at 10 secs, "b" at 13 and "c" at 16.
+        assert!(cache.entries_expiring_within(secs(3)).is_empty());
+        assert_eq!(cache.entries_expiring_within(secs(4)), vec![Arc::new("a")]);
+        assert_eq!(
+            cache.entries_expiring_within(secs(7)),
+            vec![Arc::new("a"), Arc::new("b")]
+        );
+
+        // Updating "a" moves its deadline to 18 secs.
+        mock.increment(secs(2)); // 8 secs.
+        cache.insert("a", 4);
+        cache.run_pending_tasks();
+        assert_eq!(
+            cache.entries_expiring_within(secs(100)),
+            vec![Arc::new("b"), Arc::new("c"), Arc::new("a")]
+        );
+
+        // Expired entries are not returned.
+        mock.increment(secs(6)); // 14 secs.
+        assert_eq!(cache.entries_expiring_within(secs(3)), vec![Arc::new("c")]);
+
+        // Time-to-idle. All entries are checked.
+        let mut cache = Cache::builder()
+            .time_to_live(secs(10))
+            .time_to_idle(secs(5))
+            .build();
+        cache.reconfigure_for_testing();
+        let (clock, mock) = Clock::mock();
+        cache.set_expiration_clock(Some(clock));
+        let cache = cache;
+
+        cache.insert("a", 1);
+        cache.insert("b", 2);
+        cache.run_pending_tasks();
+        mock.increment(secs(4));
+        assert_eq!(cache.get(&"b"), Some(2));
+        cache.run_pending_tasks();
+
+        // "a" expires at 5 secs by the TTI, and "b" at 9 secs.
+        assert_eq!(cache.entries_expiring_within(secs(2)), vec![Arc::new("a")]);
+        assert_eq!(
+            cache.entries_expiring_within(secs(5)),
+            vec![Arc::new("a"), Arc::new("b")]
+        );
+
+        // No expiration.
+        let cache = Cache::new(10);
+        cache.insert("a", 1);
+        cache.run_pending_tasks();
+        assert!(cache.entries_expiring_within(secs(100)).is_empty());
+    }
+
+    #[test]
+    fn to_hash_map() {
+        use std::collections::HashMap;
+
+        let cache = Cache::builder()
+            .max_capacity(100)
+            .time_to_live(Duration::from_secs(10))
+            .build();
+        let (clock, mock) = Clock::mock();
+        cache.set_expiration_clock(Some(clock));
+
+        cache.insert("a", 1);
+        cache.insert("b", 2);
+        mock.increment(Duration::from_secs(5));
+        cache.insert("c", 3);
+        cache.invalidate(&"b");
+        assert_eq!(cache.to_hash_map(), HashMap::from([("a", 1), ("c", 3)]));
+
+        // Expired entries are not included.
+        mock.increment(Duration::from_secs(5));
+        assert_eq!(cache.to_hash_map(), HashMap::from([("c", 3)]));
+    }
+
+    #[test]
+    fn invalidate_and_take() {
+        let actual = Arc::new(Mutex::new(Vec::new()));
+        let mut expected = Vec::new();
+        let a1 = Arc::clone(&actual);
+        let listener = move |k, v, cause| a1.lock().push((k, v, cause));
+
+        let mut cache = Cache::builder()
+            .max_capacity(100)
+            .time_to_live(Duration::from_secs(10))
+            .eviction_listener(listener)
+            .build();
+        cache.reconfigure_for_testing();
+        let (clock, mock) = Clock::mock();
+        cache.set_expiration_clock(Some(clock));
+        let cache = cache;
+
+        cache.insert("a", "alice");
+        cache.insert("b", "bob");
+        cache.run_pending_tasks();
+
+        assert_eq!(cache.invalidate_and_take(&"a"), Some("alice"));
+        expected.push((Arc::new("a"), "alice", RemovalCause::Explicit));
+        assert_eq!(cache.invalidate_and_take(&"a"), None);
+        cache.run_pending_tasks();
+        assert_eq!(*actual.lock(), expected);
+
+        // The value of an expired entry is not returned, and the entry is removed
+        // by the housekeeping with the `Expired` cause.
+        mock.increment(Duration::from_secs(10));
+        assert_eq!(cache.invalidate_and_take(&"b"), None);
+        expected.push((Arc::new("b"), "bob", RemovalCause::Expired));
+        cache.run_pending_tasks();
+        assert_eq!(*actual.lock(), expected);
+        assert!(cache.is_table_empty());
+    }
+
+    #[test]
+    fn snapshot_clone() {
+        let actual = Arc::new(Mutex::new(Vec::new()));
+        let a1 = Arc::clone(&actual);
+        let listener = move |k, v, cause| a1.lock().push((k, v, cause));
+
+        let cache = Cache::builder()
+            .max_capacity(100)
+            .time_to_live(Duration::from_secs(10))
+            .eviction_listener(listener)
+            .build();
+        cache.insert("a", "alice");
+        cache.insert("b", "bob");
+
+        let copy = cache.snapshot_clone();
+        assert_eq!(copy.policy().max_capacity(), Some(100));
+        assert_eq!(copy.policy().time_to_live(), Some(Duration::from_secs(10)));
+        assert_eq!(copy.to_hash_map(), cache.to_hash_map());
 
-        // The key is not present.
-        cache.invalidate("a");
-        cache.get_with("a", || "amanda");
-        expiry_counters.incl_expected_creations();
+        // The copy does not share the entries with the original cache.
+        copy.insert("c", "cindy");
+        cache.invalidate(&"b");
+        assert_eq!(copy.get(&"b"), Some("bob"));
+        assert!(!cache.contains_key(&"c"));
         cache.run_pending_tasks();
+        copy.run_pending_tasks();
+        assert_eq!(cache.entry_count(), 1);
+        assert_eq!(copy.entry_count(), 3);
 
-        expiry_counters.verify();
+        // But shares the eviction listener.
+        copy.invalidate(&"a");
+        assert_eq!(
+            *actual.lock(),
+            vec![
+                (Arc::new("b"), "bob", RemovalCause::Explicit),
+                (Arc::new("a"), "alice", RemovalCause::Explicit),
+            ]
+        );
     }
 
-    // https://github.com/moka-rs/moka/issues/345
     #[test]
-    fn test_race_between_updating_entry_and_processing_its_write_ops() {
-        let cache = Cache::builder()
-            .max_capacity(2)
-            .time_to_idle(Duration::from_secs(1))
-            .build();
-        let (clock, mock) = Clock::mock();
-        cache.set_expiration_clock(Some(clock));
+    fn merge_from_keep_newer() {
+        use crate::ops::merge::MergeStrategy;
 
-        cache.insert("a", "alice");
-        cache.insert("b", "bob");
-        cache.insert("c", "cathy"); // c1
-        mock.increment(Duration::from_secs(2));
+        let secs = Duration::from_secs;
 
-        // The following `insert` will do the followings:
-        // 1. Replaces current "c" (c1) in the concurrent hash table (cht).
-        // 2. Runs the pending tasks implicitly.
-        //    (1) "a" will be admitted.
-        //    (2) "b" will be admitted.
-        //    (3) c1 will be evicted by size constraint.
-        //    (4) "a" will be evicted due to expiration.
-        //    (5) "b" will be evicted due to expiration.
-        // 3. Send its `WriteOp` log to the channel.
-        cache.insert("c", "cindy"); // c2
+        let (clock1, mock1) = Clock::mock();
+        let dest = Cache::new(100);
+        dest.set_expiration_clock(Some(clock1));
 
-        // Remove "c" (c2) from the cht.
-        assert_eq!(cache.remove(&"c"), Some("cindy")); // c-remove
+        let (clock2, mock2) = Clock::mock();
+        let src = Cache::new(100);
+        src.set_expiration_clock(Some(clock2));
 
-        mock.increment(Duration::from_secs(2));
+        dest.insert("a", 10);
+        mock1.increment(secs(10));
+        dest.insert("c", 30);
 
-        // The following `run_pending_tasks` will do the followings:
-        // 1. Admits "c" (c2) to the cache. (Create a node in the LRU deque)
-        // 2. Because of c-remove, removes c2's node from the LRU deque.
-        cache.run_pending_tasks();
-        assert_eq!(cache.entry_count(), 0);
+        src.insert("a", 1);
+        src.insert("b", 2);
+        src.insert("c", 3);
+        mock2.increment(secs(5));
+
+        // "a" is newer in `src` (5 secs old vs. 10 secs), and "c" is newer in
+        // `dest` (0 secs old vs. 5 secs).
+        assert_eq!(dest.merge_from(&src, MergeStrategy::KeepNewer), 2);
+        assert_eq!(
+            dest.to_hash_map(),
+            std::collections::HashMap::from([("a", 1), ("b", 2), ("c", 30)])
+        );
+
+        // Expired entries in `src` are not copied.
+        let src = Cache::builder().time_to_live(secs(1)).build();
+        let (clock3, mock3) = Clock::mock();
+        src.set_expiration_clock(Some(clock3));
+        src.insert("d", 4);
+        mock3.increment(secs(1));
+        assert_eq!(dest.merge_from(&src, MergeStrategy::KeepNewer), 0);
+        assert!(!dest.contains_key(&"d"));
     }
 
     #[test]
-    fn test_race_between_recreating_entry_and_processing_its_write_ops() {
-        let cache = Cache::builder().max_capacity(2).build();
+    fn test_into_iter() {
+        // A value that counts how many times it has been cloned.
+        #[derive(Debug)]
+        struct Value(u32, Arc<AtomicU8>);
 
-        cache.insert('a', "a");
-        cache.insert('b', "b");
-        cache.run_pending_tasks();
+        impl Clone for Value {
+            fn clone(&self) -> Self {
+                self.1.fetch_add(1, Ordering::AcqRel);
+                Self(self.0, Arc::clone(&self.1))
+            }
+        }
 
-        cache.insert('c', "c1"); // (a) `EntryInfo` 1, gen: 1
-        assert!(cache.remove(&'a').is_some()); // (b)
-        assert!(cache.remove(&'b').is_some()); // (c)
-        assert!(cache.remove(&'c').is_some()); // (d) `EntryInfo` 1, gen: 2
-        cache.insert('c', "c2"); // (e) `EntryInfo` 2, gen: 1
+        impl PartialEq for Value {
+            fn eq(&self, other: &Self) -> bool {
+                self.0 == other.0
+            }
+        }
 
-        // Now the `write_op_ch` channel contains the following `WriteOp`s:
-        //
-        // - 0: (a) insert "c1" (`EntryInfo` 1, gen: 1)
-        // - 1: (b) remove "a"
-        // - 2: (c) remove "b"
-        // - 3: (d) remove "c1" (`EntryInfo` 1, gen: 2)
-        // - 4: (e) insert "c2" (`EntryInfo` 2, gen: 1)
-        //
-        // 0 for "c1" is going to be rejected because the cache is full. Let's ensure
-        // processing 0 must not remove "c2" from the concurrent hash table. (Their
-        // gen are the same, but `EntryInfo`s are different)
+        impl Eq for Value {}
+
+        let clone_count = Arc::new(AtomicU8::new(0));
+
+        // The following `Vec`s will hold actual and expected notifications.
+        let actual = Arc::new(Mutex::new(Vec::new()));
+        let mut expected = Vec::new();
+
+        // Create an eviction listener.
+        let a1 = Arc::clone(&actual);
+        let listener = move |k, v, cause| a1.lock().push((k, v, cause));
+
+        let mut cache = Cache::builder()
+            .max_capacity(100)
+            .time_to_live(Duration::from_secs(10))
+            .eviction_listener(listener)
+            .build();
+        cache.reconfigure_for_testing();
+
+        let (clock, mock) = Clock::mock();
+        cache.set_expiration_clock(Some(clock));
+
+        // Make the cache exterior immutable.
+        let cache = cache;
+
+        cache.insert(0, Value(0, Arc::clone(&clone_count)));
         cache.run_pending_tasks();
-        assert_eq!(cache.get(&'c'), Some("c2"));
+        mock.increment(Duration::from_secs(5)); // 5 secs from the start.
+
+        for key in 1..4 {
+            cache.insert(key, Value(key, Arc::clone(&clone_count)));
+        }
+        // Do not call run_pending_tasks here, so that `into_iter` will have to
+        // apply the pending writes.
+
+        mock.increment(Duration::from_secs(5)); // 10 secs.
+        expected.push((
+            Arc::new(0),
+            Value(0, Arc::clone(&clone_count)),
+            RemovalCause::Expired,
+        ));
+
+        // Keep a clone to check the cache after `into_iter` consumes the cache.
+        let cache2 = cache.clone();
+        let clone_count_before = clone_count.load(Ordering::Acquire);
+
+        let mut entries = cache
+            .into_iter()
+            .map(|(k, v)| (*k, v.0))
+            .collect::<Vec<_>>();
+        entries.sort_unstable();
+        assert_eq!(entries, vec![(1, 1), (2, 2), (3, 3)]);
+
+        // The expired value was cloned once for the eviction listener. The live
+        // values are moved out without cloning, unless crossbeam-epoch has not
+        // collected the removed buckets yet, which depends on the other threads.
+        let clones = clone_count.load(Ordering::Acquire) - clone_count_before;
+        assert!((1..=4).contains(&clones), "clones: {clones}");
+
+        assert_eq!(cache2.entry_count(), 0);
+        assert_eq!(cache2.weighted_size(), 0);
+        assert!(cache2.is_table_empty());
+        assert!(cache2.get(&1).is_none());
+
+        verify_notification_vec(&cache2, actual, &expected);
     }
 
     #[test]
-    fn test_iter() {
-        const NUM_KEYS: usize = 50;
+    fn test_drain() {
+        // The following `Vec` will hold actual notifications.
+        let actual = Arc::new(Mutex::new(Vec::new()));
 
-        fn make_value(key: usize) -> String {
-            format!("val: {key}")
-        }
+        // Create an eviction listener.
+        let a1 = Arc::clone(&actual);
+        let listener = move |k, v, cause| a1.lock().push((k, v, cause));
 
-        let cache = Cache::builder()
+        let mut cache = Cache::builder()
             .max_capacity(100)
-            .time_to_idle(Duration::from_secs(10))
+            .eviction_listener(listener)
             .build();
+        cache.reconfigure_for_testing();
 
-        for key in 0..NUM_KEYS {
-            cache.insert(key, make_value(key));
-        }
+        // Make the cache exterior immutable.
+        let cache = cache;
 
-        let mut key_set = std::collections::HashSet::new();
+        cache.insert("a", "alice");
+        cache.insert("b", "bob");
+        cache.run_pending_tasks();
+        cache.insert("c", "cindy");
 
-        for (key, value) in &cache {
-            assert_eq!(value, make_value(*key));
+        let mut entries = cache.drain().map(|(k, v)| (*k, v)).collect::<Vec<_>>();
+        entries.sort_unstable();
+        assert_eq!(entries, vec![("a", "alice"), ("b", "bob"), ("c", "cindy")]);
 
-            key_set.insert(*key);
-        }
+        assert_eq!(cache.entry_count(), 0);
+        assert!(cache.is_table_empty());
+        assert!(cache.get(&"a").is_none());
 
-        // Ensure there are no missing or duplicate keys in the iteration.
-        assert_eq!(key_set.len(), NUM_KEYS);
+        let mut notifications = actual.lock().clone();
+        notifications.sort_unstable_by_key(|(k, _, _)| **k);
+        assert_eq!(
+            notifications,
+            vec![
+                (Arc::new("a"), "alice", RemovalCause::Explicit),
+                (Arc::new("b"), "bob", RemovalCause::Explicit),
+                (Arc::new("c"), "cindy", RemovalCause::Explicit),
+            ]
+        );
+
+        // The cache should still be usable.
+        cache.insert("d", "david");
+        cache.run_pending_tasks();
+        assert_eq!(cache.get(&"d"), Some("david"));
+        assert_eq!(cache.entry_count(), 1);
     }
 
     /// Runs 16 threads at the same time and ensures no deadlock occurs.
@@ -3103,6 +8154,185 @@ mod tests {
         assert_eq!(key_set.len(), NUM_KEYS);
     }
 
+    #[test]
+    fn refresh() {
+        use crate::sync::Loader;
+        use std::sync::mpsc::{self, Receiver};
+
+        struct BlockingLoader(Mutex<Receiver<&'static str>>);
+
+        impl Loader<&'static str, &'static str> for BlockingLoader {
+            fn load(&self, _key: &&'static str) -> Option<&'static str> {
+                self.0.lock().recv().ok()
+            }
+        }
+
+        let (tx, rx) = mpsc::channel();
+        let cache = Cache::builder()
+            .loader(BlockingLoader(Mutex::new(rx)))
+            .build();
+
+        // The key does not exist.
+        assert!(!cache.refresh(&"a"));
+
+        cache.insert("a", "alice");
+        assert!(cache.refresh(&"a"));
+        // The refresh is in flight.
+        assert!(!cache.refresh(&"a"));
+        assert!(cache.load_progress(&"a").is_some());
+        assert_eq!(cache.get(&"a"), Some("alice"));
+
+        tx.send("anna").unwrap();
+        while cache.load_progress(&"a").is_some() {
+            std::thread::yield_now();
+        }
+        assert_eq!(cache.get(&"a"), Some("anna"));
+
+        // The entry removed while reloading is not inserted back.
+        assert!(cache.refresh(&"a"));
+        cache.invalidate(&"a");
+        tx.send("amy").unwrap();
+        while cache.load_progress(&"a").is_some() {
+            std::thread::yield_now();
+        }
+        assert!(!cache.contains_key(&"a"));
+
+        // The value inserted while reloading is not overwritten.
+        cache.insert("a", "alice");
+        assert!(cache.refresh(&"a"));
+        cache.insert("a", "annie");
+        tx.send("amy").unwrap();
+        while cache.load_progress(&"a").is_some() {
+            std::thread::yield_now();
+        }
+        assert_eq!(cache.get(&"a"), Some("annie"));
+
+        // No loader is registered.
+        let cache = Cache::new(10);
+        cache.insert("a", "alice");
+        assert!(!cache.refresh(&"a"));
+    }
+
+    #[test]
+    fn refresh_does_not_insert_back_removed_key() {
+        use crate::sync::{Interceptor, Loader};
+        use std::sync::mpsc::{self, Receiver};
+
+        type Slot = Arc<Mutex<Option<Cache<&'static str, &'static str>>>>;
+
+        struct BlockingLoader(Mutex<Receiver<&'static str>>);
+
+        impl Loader<&'static str, &'static str> for BlockingLoader {
+            fn load(&self, _key: &&'static str) -> Option<&'static str> {
+                self.0.lock().recv().ok()
+            }
+        }
+
+        // Removes the key right before the loaded value is stored, i.e. after the
+        // refresh has found the entry.
+        struct RemoveBeforeInsert(Slot);
+
+        impl Interceptor<&'static str, &'static str> for RemoveBeforeInsert {
+            fn before_insert(
+                &self,
+                key: &&'static str,
+                value: &'static str,
+            ) -> Option<&'static str> {
+                if let Some(cache) = &*self.0.lock() {
+                    cache.invalidate(key);
+                }
+                Some(value)
+            }
+        }
+
+        let (tx, rx) = mpsc::channel();
+        let slot = Slot::default();
+        let cache = Cache::builder()
+            .loader(BlockingLoader(Mutex::new(rx)))
+            .interceptor(RemoveBeforeInsert(Arc::clone(&slot)))
+            .build();
+        let wait_for_refresh = || {
+            while cache.load_progress(&"a").is_some() {
+                std::thread::yield_now();
+            }
+        };
+
+        // Removed while the loader is blocked.
+        cache.insert("a", "alice");
+        assert!(cache.refresh(&"a"));
+        cache.invalidate(&"a");
+        tx.send("amy").unwrap();
+        wait_for_refresh();
+        cache.run_pending_tasks();
+        assert!(!cache.contains_key(&"a"));
+        assert_eq!(cache.entry_count(), 0);
+
+        // Removed after the loader has returned.
+        cache.insert("a", "alice");
+        *slot.lock() = Some(cache.clone());
+        assert!(cache.refresh(&"a"));
+        tx.send("amy").unwrap();
+        wait_for_refresh();
+        cache.run_pending_tasks();
+        assert!(!cache.contains_key(&"a"));
+        assert_eq!(cache.entry_count(), 0);
+
+        // Break the reference cycle between the cache and the interceptor.
+        slot.lock().take();
+    }
+
+    #[test]
+    fn get_with_context() {
+        use std::sync::mpsc;
+
+        let cache = Arc::new(Cache::builder().track_load_progress().build());
+        let (to_main, from_loader) = mpsc::channel();
+        let (to_loader, from_main) = mpsc::channel();
+
+        assert!(cache.load_progress(&"a").is_none());
+
+        let loader = {
+            let cache = Arc::clone(&cache);
+            std::thread::spawn(move || {
+                cache.get_with_context("a", |ctx| {
+                    to_main.send(()).unwrap();
+                    from_main.recv().unwrap();
+                    ctx.touch();
+                    to_main.send(()).unwrap();
+                    from_main.recv().unwrap();
+                    "alice"
+                })
+            })
+        };
+
+        from_loader.recv().unwrap();
+        let progress = cache.load_progress(&"a").unwrap();
+        assert_eq!(progress.touched_at(), None);
+        assert!(progress.idle_time() <= progress.elapsed());
+
+        to_loader.send(()).unwrap();
+        from_loader.recv().unwrap();
+        let progress = cache.load_progress(&"a").unwrap();
+        assert!(progress.touched_at().unwrap() >= progress.started_at());
+
+        to_loader.send(()).unwrap();
+        assert_eq!(loader.join().unwrap(), "alice");
+        assert!(cache.load_progress(&"a").is_none());
+
+        // The value exists, so the `init` closure is not called.
+        assert_eq!(cache.get_with_context("a", |_| unreachable!()), "alice");
+        assert!(cache.is_waiter_map_empty());
+
+        // The loads are not recorded unless the cache tracks them.
+        let cache = Cache::new(100);
+        let v = cache.get_with_context("a", |ctx| {
+            ctx.touch();
+            assert!(cache.load_progress(&"a").is_none());
+            "alice"
+        });
+        assert_eq!(v, "alice");
+    }
+
     #[test]
     fn get_with() {
         use std::thread::{sleep, spawn};
@@ -3965,6 +9195,81 @@ mod tests {
         assert!(cache.is_waiter_map_empty());
     }
 
+    #[test]
+    fn get_all_with() {
+        use std::{
+            collections::HashMap,
+            thread::{sleep, spawn},
+        };
+
+        let cache = Cache::new(100);
+        cache.insert(0, "cached");
+
+        // This test will run three threads:
+        //
+        // Thread1 will be the first thread to load the keys 1, 2 and 3. Its init
+        // closure will be called with all of them, and will not find a value for
+        // the key 3.
+        let thread1 = {
+            let cache1 = cache.clone();
+            spawn(move || {
+                let values = cache1.get_all_with([0, 1, 2, 3], |keys| {
+                    let mut keys = keys.to_vec();
+                    keys.sort_unstable();
+                    assert_eq!(keys, [1, 2, 3]);
+                    // Wait for 300 ms and return the values.
+                    sleep(Duration::from_millis(300));
+                    HashMap::from([(1, "thread1"), (2, "thread1")])
+                });
+                assert_eq!(values.len(), 3);
+                assert_eq!(values[&0], "cached");
+                assert_eq!(values[&1], "thread1");
+                assert_eq!(values[&2], "thread1");
+            })
+        };
+
+        // Thread2 will load the overlapping keys while thread1's init closure is
+        // running. Its init closure will be called only with the key 4, and it will
+        // get the values of the other keys from thread1.
+        let thread2 = {
+            let cache2 = cache.clone();
+            spawn(move || {
+                sleep(Duration::from_millis(100));
+                let values = cache2.get_all_with([2, 3, 4, 4], |keys| {
+                    assert_eq!(keys, [4]);
+                    HashMap::from([(4, "thread2")])
+                });
+                assert_eq!(values.len(), 2);
+                assert_eq!(values[&2], "thread1");
+                assert_eq!(values[&4], "thread2");
+            })
+        };
+
+        // Thread3 will call `optionally_get_with` for the key 3 while thread1's init
+        // closure is running. It will get `None` from thread1.
+        let thread3 = {
+            let cache3 = cache.clone();
+            spawn(move || {
+                sleep(Duration::from_millis(100));
+                let v = cache3.optionally_get_with(3, || unreachable!());
+                assert!(v.is_none());
+            })
+        };
+
+        for t in [thread1, thread2, thread3] {
+            t.join().expect("Failed to join");
+        }
+
+        assert_eq!(cache.get(&1), Some("thread1"));
+        assert_eq!(cache.get(&4), Some("thread2"));
+        assert!(!cache.contains_key(&3));
+        assert!(cache.is_waiter_map_empty());
+
+        // No key is missing.
+        let values = cache.get_all_with([1, 2], |_| unreachable!());
+        assert_eq!(values.len(), 2);
+    }
+
     #[test]
     fn optionally_get_with_by_ref() {
         use std::thread::{sleep, spawn};
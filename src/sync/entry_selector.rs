@@ -217,6 +217,62 @@ where
         self.cache.try_compute_with_hash_and_fun(key, self.hash, f)
     }
 
+    /// Performs a compute operation on a cached entry by giving the closure `f` an
+    /// occupied or vacant handle to the entry, like the `entry` method of
+    /// `std::collections::HashMap`.
+    ///
+    /// The closure should consume the handle by calling one of its methods, such
+    /// as `insert`, `modify` or `remove`, and return the resulting
+    /// `ops::compute::Op<V>`. The op is then executed and its result is returned
+    /// in the same way as [`and_compute_with`]. A put goes through the regular
+    /// insert path, so the write is recorded by the cache policy as usual.
+    ///
+    /// [`and_compute_with`]: #method.and_compute_with
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use moka::{
+    ///     ops::compute::{CompResult, MapEntry},
+    ///     sync::Cache,
+    /// };
+    ///
+    /// let cache: Cache<String, u32> = Cache::new(100);
+    /// let key = "key1".to_string();
+    ///
+    /// let bump = |cache: &Cache<String, u32>| {
+    ///     cache.entry(key.clone()).and_entry_with(|entry| match entry {
+    ///         MapEntry::Occupied(occupied) if *occupied.get() >= 2 => occupied.remove(),
+    ///         MapEntry::Occupied(occupied) => occupied.modify(|v| *v += 1),
+    ///         MapEntry::Vacant(vacant) => vacant.insert(1),
+    ///     })
+    /// };
+    ///
+    /// assert!(matches!(bump(&cache), CompResult::Inserted(_)));
+    /// assert!(matches!(bump(&cache), CompResult::ReplacedWith(_)));
+    /// assert_eq!(cache.get(&key), Some(2));
+    /// assert!(matches!(bump(&cache), CompResult::Removed(_)));
+    /// assert!(!cache.contains_key(&key));
+    /// ```
+    ///
+    /// # Concurrent calls on the same key
+    ///
+    /// This method guarantees that concurrent calls on the same key are executed
+    /// serially. That is, `and_entry_with` calls on the same key never run
+    /// concurrently. The calls are serialized by the order of their invocation. It
+    /// uses a key-level lock to achieve this.
+    pub fn and_entry_with<F>(self, f: F) -> compute::CompResult<K, V>
+    where
+        F: FnOnce(compute::MapEntry<K, V>) -> compute::Op<V>,
+    {
+        let key = Arc::new(self.owned_key);
+        let entry_key = Arc::clone(&key);
+        self.cache
+            .compute_with_hash_and_fun(key, self.hash, |maybe_entry| {
+                f(compute::MapEntry::new(entry_key, maybe_entry))
+            })
+    }
+
     /// Performs an upsert of an [`Entry`] by using the given closure `f`. The word
     /// "upsert" here means "update" or "insert".
     ///
@@ -759,6 +815,62 @@ where
         self.cache.try_compute_with_hash_and_fun(key, self.hash, f)
     }
 
+    /// Performs a compute operation on a cached entry by giving the closure `f` an
+    /// occupied or vacant handle to the entry, like the `entry` method of
+    /// `std::collections::HashMap`.
+    ///
+    /// The closure should consume the handle by calling one of its methods, such
+    /// as `insert`, `modify` or `remove`, and return the resulting
+    /// `ops::compute::Op<V>`. The op is then executed and its result is returned
+    /// in the same way as [`and_compute_with`]. A put goes through the regular
+    /// insert path, so the write is recorded by the cache policy as usual.
+    ///
+    /// [`and_compute_with`]: #method.and_compute_with
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use moka::{
+    ///     ops::compute::{CompResult, MapEntry},
+    ///     sync::Cache,
+    /// };
+    ///
+    /// let cache: Cache<String, u32> = Cache::new(100);
+    /// let key = "key1".to_string();
+    ///
+    /// let bump = |cache: &Cache<String, u32>| {
+    ///     cache.entry_by_ref(&key).and_entry_with(|entry| match entry {
+    ///         MapEntry::Occupied(occupied) if *occupied.get() >= 2 => occupied.remove(),
+    ///         MapEntry::Occupied(occupied) => occupied.modify(|v| *v += 1),
+    ///         MapEntry::Vacant(vacant) => vacant.insert(1),
+    ///     })
+    /// };
+    ///
+    /// assert!(matches!(bump(&cache), CompResult::Inserted(_)));
+    /// assert!(matches!(bump(&cache), CompResult::ReplacedWith(_)));
+    /// assert_eq!(cache.get(&key), Some(2));
+    /// assert!(matches!(bump(&cache), CompResult::Removed(_)));
+    /// assert!(!cache.contains_key(&key));
+    /// ```
+    ///
+    /// # Concurrent calls on the same key
+    ///
+    /// This method guarantees that concurrent calls on the same key are executed
+    /// serially. That is, `and_entry_with` calls on the same key never run
+    /// concurrently. The calls are serialized by the order of their invocation. It
+    /// uses a key-level lock to achieve this.
+    pub fn and_entry_with<F>(self, f: F) -> compute::CompResult<K, V>
+    where
+        F: FnOnce(compute::MapEntry<K, V>) -> compute::Op<V>,
+    {
+        let key = Arc::new(self.ref_key.to_owned());
+        let entry_key = Arc::clone(&key);
+        self.cache
+            .compute_with_hash_and_fun(key, self.hash, |maybe_entry| {
+                f(compute::MapEntry::new(entry_key, maybe_entry))
+            })
+    }
+
     /// Performs an upsert of an [`Entry`] by using the given closure `f`. The word
     /// "upsert" here means "update" or "insert".
     ///
@@ -0,0 +1,193 @@
+use std::sync::Arc;
+
+/// Hooks called around the cache operations, to layer cross-cutting concerns such
+/// as authorization, tracing or dual-writes without changing the call sites of the
+/// cache.
+///
+/// Interceptors are registered to the cache by
+/// [`CacheBuilder::interceptor`][builder-interceptor] in order. The hooks of the
+/// interceptors are called in the registered order. When a hook vetoes an
+/// operation, the hooks of the later interceptors are not called.
+///
+/// The hooks are called for the following methods, and the variants of them (e.g.
+/// `insert_with_expiry`, `remove` and the `get_with` family methods):
+///
+/// - `after_get`: every method returning a cached value, when there is a live
+///   entry for the key. This includes the `get`, `get_entry` and `get_pinned`
+///   methods, the hits of the `get_with` family methods and the `entry` API, the
+///   map views, `iter` and `to_hash_map`, and the values returned by `remove`,
+///   `invalidate_and_take` and `get_and_invalidate_if`. It is not called on a
+///   miss. When it returns `None`, the `get_with` family methods treat the read as
+///   a miss and insert a new value.
+/// - `before_insert` and `after_insert`: `insert` family methods, and the inserts
+///   of the values computed by the `get_with` family methods.
+/// - `before_invalidate` and `after_invalidate`: `invalidate` and `remove`
+///   methods, when there is a live entry for the key. An expired entry is removed
+///   without calling them.
+///
+/// They are not called for the removals by the eviction and expiration, or by the
+/// `invalidate_all` and `invalidate_entries_if` methods. Use the eviction listener
+/// for them.
+///
+/// Note that the `get_with` family methods return the value computed by the init
+/// closure as is, even if `before_insert` transformed or discarded it.
+///
+/// [builder-interceptor]: ./struct.CacheBuilder.html#method.interceptor
+///
+/// # Examples
+///
+/// ```rust
+/// use moka::sync::{Cache, Interceptor};
+///
+/// // An interceptor to hide the values of the keys starting with "secret/", and
+/// // to trim the values to insert.
+/// struct Guard;
+///
+/// impl Interceptor<String, String> for Guard {
+///     fn after_get(&self, key: &String, value: String) -> Option<String> {
+///         (!key.starts_with("secret/")).then_some(value)
+///     }
+///
+///     fn before_insert(&self, _key: &String, value: String) -> Option<String> {
+///         Some(value.trim().to_string())
+///     }
+/// }
+///
+/// let cache = Cache::builder()
+///     .max_capacity(100)
+///     .interceptor(Guard)
+///     .build();
+///
+/// cache.insert("public/a".to_string(), " alice ".to_string());
+/// cache.insert("secret/b".to_string(), "bob".to_string());
+///
+/// assert_eq!(cache.get("public/a"), Some("alice".to_string()));
+/// assert_eq!(cache.get("secret/b"), None);
+/// // The value is still cached.
+/// assert!(cache.contains_key("secret/b"));
+/// ```
+pub trait Interceptor<K, V> {
+    /// Called with the value read from the cache for the `key`. Returns the value
+    /// to return to the caller, which may be transformed, or `None` to treat the
+    /// read as a miss.
+    ///
+    /// The default implementation returns the `value` as is.
+    #[allow(unused_variables)]
+    fn after_get(&self, key: &K, value: V) -> Option<V> {
+        Some(value)
+    }
+
+    /// Called before the `value` is inserted for the `key`. Returns the value to
+    /// insert, which may be transformed, or `None` to discard the insert.
+    ///
+    /// The default implementation returns the `value` as is.
+    #[allow(unused_variables)]
+    fn before_insert(&self, key: &K, value: V) -> Option<V> {
+        Some(value)
+    }
+
+    /// Called after the `value` is inserted for the `key`.
+    ///
+    /// The default implementation does nothing.
+    #[allow(unused_variables)]
+    fn after_insert(&self, key: &K, value: &V) {}
+
+    /// Called before the entry for the `key` is invalidated. Returns `false` to keep
+    /// the entry.
+    ///
+    /// This is called while the entry is being removed atomically, and may be
+    /// called more than once when the removal conflicts with other concurrent
+    /// updates to the internal hash table, so it should not have side effects.
+    ///
+    /// The default implementation returns `true`.
+    #[allow(unused_variables)]
+    fn before_invalidate(&self, key: &K, value: &V) -> bool {
+        true
+    }
+
+    /// Called after the entry for the `key` is invalidated.
+    ///
+    /// The default implementation does nothing.
+    #[allow(unused_variables)]
+    fn after_invalidate(&self, key: &K, value: &V) {}
+}
+
+pub(crate) type ArcInterceptor<K, V> = Arc<dyn Interceptor<K, V> + Send + Sync + 'static>;
+
+/// The interceptors registered to a cache, in the registered order.
+pub(crate) struct InterceptorChain<K, V> {
+    interceptors: Vec<ArcInterceptor<K, V>>,
+}
+
+impl<K, V> InterceptorChain<K, V> {
+    /// Returns `None` if `interceptors` is empty.
+    pub(crate) fn new(interceptors: Vec<ArcInterceptor<K, V>>) -> Option<Arc<Self>> {
+        (!interceptors.is_empty()).then(|| Arc::new(Self { interceptors }))
+    }
+
+    pub(crate) fn after_get(&self, key: &K, value: V) -> Option<V> {
+        self.interceptors
+            .iter()
+            .try_fold(value, |v, icpt| icpt.after_get(key, v))
+    }
+
+    pub(crate) fn before_insert(&self, key: &K, value: V) -> Option<V> {
+        self.interceptors
+            .iter()
+            .try_fold(value, |v, icpt| icpt.before_insert(key, v))
+    }
+
+    pub(crate) fn after_insert(&self, key: &K, value: &V) {
+        for icpt in &self.interceptors {
+            icpt.after_insert(key, value);
+        }
+    }
+
+    pub(crate) fn before_invalidate(&self, key: &K, value: &V) -> bool {
+        self.interceptors
+            .iter()
+            .all(|icpt| icpt.before_invalidate(key, value))
+    }
+
+    pub(crate) fn after_invalidate(&self, key: &K, value: &V) {
+        for icpt in &self.interceptors {
+            icpt.after_invalidate(key, value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Interceptor, InterceptorChain};
+    use std::sync::Arc;
+
+    struct Add(u32);
+
+    impl Interceptor<u32, u32> for Add {
+        fn after_get(&self, _key: &u32, value: u32) -> Option<u32> {
+            value.checked_add(self.0)
+        }
+
+        fn before_insert(&self, key: &u32, value: u32) -> Option<u32> {
+            (*key != self.0).then_some(value + self.0)
+        }
+
+        fn before_invalidate(&self, key: &u32, _value: &u32) -> bool {
+            *key != self.0
+        }
+    }
+
+    #[test]
+    fn hooks_are_called_in_order() {
+        let chain = InterceptorChain::new(vec![Arc::new(Add(1)), Arc::new(Add(10))]).unwrap();
+        assert_eq!(chain.after_get(&0, 100), Some(111));
+        assert_eq!(chain.after_get(&0, u32::MAX - 1), None);
+        assert_eq!(chain.before_insert(&0, 100), Some(111));
+        assert_eq!(chain.before_insert(&1, 100), None);
+        assert_eq!(chain.before_insert(&10, 100), None);
+        assert!(chain.before_invalidate(&0, &100));
+        assert!(!chain.before_invalidate(&10, &100));
+
+        assert!(InterceptorChain::<u32, u32>::new(vec![]).is_none());
+    }
+}
@@ -0,0 +1,79 @@
+use parking_lot::Mutex;
+use std::time::{Duration, Instant};
+
+/// Passed to the `init` closure of the
+/// [`Cache::get_with_context`][get-with-context] method, so that a long running
+/// load can publish a heartbeat.
+///
+/// Other threads can see how long the load has been running and when it was
+/// touched last by the [`Cache::load_progress`][load-progress] method.
+///
+/// [get-with-context]: ./struct.Cache.html#method.get_with_context
+/// [load-progress]: ./struct.Cache.html#method.load_progress
+#[derive(Debug)]
+pub struct LoaderContext {
+    started_at: Instant,
+    touched_at: Mutex<Option<Instant>>,
+}
+
+impl LoaderContext {
+    pub(crate) fn new() -> Self {
+        Self {
+            started_at: Instant::now(),
+            touched_at: Mutex::new(None),
+        }
+    }
+
+    /// Records that the load is still making progress.
+    pub fn touch(&self) {
+        *self.touched_at.lock() = Some(Instant::now());
+    }
+
+    /// Returns the time elapsed since the load started.
+    pub fn elapsed(&self) -> Duration {
+        self.started_at.elapsed()
+    }
+
+    pub(crate) fn progress(&self) -> LoadProgress {
+        LoadProgress {
+            started_at: self.started_at,
+            touched_at: *self.touched_at.lock(),
+        }
+    }
+}
+
+/// A snapshot of an in-flight load, returned by the
+/// [`Cache::load_progress`][load-progress] method.
+///
+/// [load-progress]: ./struct.Cache.html#method.load_progress
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LoadProgress {
+    started_at: Instant,
+    touched_at: Option<Instant>,
+}
+
+impl LoadProgress {
+    /// Returns the time when the load started.
+    pub fn started_at(&self) -> Instant {
+        self.started_at
+    }
+
+    /// Returns the time elapsed since the load started.
+    pub fn elapsed(&self) -> Duration {
+        self.started_at.elapsed()
+    }
+
+    /// Returns the time when the loader called
+    /// [`LoaderContext::touch`](./struct.LoaderContext.html#method.touch) last, or
+    /// `None` if it has never called it.
+    pub fn touched_at(&self) -> Option<Instant> {
+        self.touched_at
+    }
+
+    /// Returns the time elapsed since the loader called `touch` last, or since the
+    /// load started if it has never called it. A long idle time suggests the load
+    /// is stuck.
+    pub fn idle_time(&self) -> Duration {
+        self.touched_at.unwrap_or(self.started_at).elapsed()
+    }
+}
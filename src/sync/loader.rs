@@ -0,0 +1,278 @@
+use crossbeam_channel::{Receiver, RecvTimeoutError, Sender};
+use std::{
+    panic::{catch_unwind, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+/// A source of the values for the keys missing in the cache.
+///
+/// Loaders are registered to the cache by
+/// [`CacheBuilder::loader`][builder-loader] in order, e.g. a local file and then a
+/// remote service. On a miss, `get_or_load` method of the cache calls the loaders
+/// in the registered order until one of them returns a value, and inserts the
+/// value to the cache.
+///
+/// [builder-loader]: ./struct.CacheBuilder.html#method.loader
+///
+/// # Examples
+///
+/// ```rust
+/// use moka::sync::{Cache, Loader};
+/// use std::{collections::HashMap, sync::Mutex};
+///
+/// // A loader backed by a local store, which also saves the values loaded by
+/// // the later loaders.
+/// #[derive(Default)]
+/// struct LocalStore(Mutex<HashMap<u32, String>>);
+///
+/// impl Loader<u32, String> for LocalStore {
+///     fn load(&self, key: &u32) -> Option<String> {
+///         self.0.lock().unwrap().get(key).cloned()
+///     }
+///
+///     fn store(&self, key: &u32, value: &String) {
+///         self.0.lock().unwrap().insert(*key, value.clone());
+///     }
+/// }
+///
+/// // A loader backed by a remote service.
+/// struct Remote;
+///
+/// impl Loader<u32, String> for Remote {
+///     fn load(&self, key: &u32) -> Option<String> {
+///         (*key < 100).then(|| format!("value of {key}"))
+///     }
+/// }
+///
+/// let cache = Cache::builder()
+///     .max_capacity(100)
+///     .loader(LocalStore::default())
+///     .loader(Remote)
+///     .build();
+///
+/// assert_eq!(cache.get_or_load(&1), Some("value of 1".to_string()));
+/// assert_eq!(cache.get(&1), Some("value of 1".to_string()));
+/// assert_eq!(cache.get_or_load(&100), None);
+/// ```
+pub trait Loader<K, V> {
+    /// Loads the value for the `key`. Returns `None` if this loader does not have
+    /// the value, so the next loader is tried.
+    fn load(&self, key: &K) -> Option<V>;
+
+    /// Called when one of the loaders registered after this loader has loaded the
+    /// `value` for the `key`, so this loader can save it for the next time.
+    ///
+    /// The default implementation does nothing.
+    #[allow(unused_variables)]
+    fn store(&self, key: &K, value: &V) {}
+}
+
+pub(crate) type ArcLoader<K, V> = Arc<dyn Loader<K, V> + Send + Sync + 'static>;
+
+/// The loaders registered to a cache, in the registered order.
+pub(crate) struct LoaderChain<K, V> {
+    loaders: Vec<ArcLoader<K, V>>,
+    refresh_workers: RefreshWorkers,
+}
+
+impl<K, V> LoaderChain<K, V> {
+    /// Returns `None` if `loaders` is empty.
+    pub(crate) fn new(loaders: Vec<ArcLoader<K, V>>) -> Option<Arc<Self>> {
+        (!loaders.is_empty()).then(|| {
+            Arc::new(Self {
+                loaders,
+                refresh_workers: RefreshWorkers::new(),
+            })
+        })
+    }
+
+    /// Calls the loaders in order until one of them returns a value, and then
+    /// stores the value to the loaders before it.
+    pub(crate) fn load(&self, key: &K) -> Option<V> {
+        self.loaders.iter().enumerate().find_map(|(i, loader)| {
+            let value = loader.load(key)?;
+            for earlier in &self.loaders[..i] {
+                earlier.store(key, &value);
+            }
+            Some(value)
+        })
+    }
+
+    /// Runs the `refresh` job on a background worker. Returns `false` if too many
+    /// refreshes are already queued.
+    pub(crate) fn spawn_refresh(&self, refresh: impl FnOnce() + Send + 'static) -> bool {
+        self.refresh_workers.submit(Box::new(refresh))
+    }
+}
+
+/// The max number of the worker threads running the refreshes of a cache.
+const MAX_REFRESH_WORKERS: usize = 4;
+/// The max number of the refreshes waiting for a worker.
+const REFRESH_QUEUE_CAPACITY: usize = 1024;
+/// How long an idle worker waits for a refresh before it exits.
+const REFRESH_WORKER_IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+
+type RefreshJob = Box<dyn FnOnce() + Send + 'static>;
+
+/// A bounded pool of the threads running the refreshes. The workers are started
+/// on demand, up to `MAX_REFRESH_WORKERS`, and exit when they have been idle for a
+/// while or the cache has been dropped.
+struct RefreshWorkers {
+    jobs: Sender<RefreshJob>,
+    queue: Receiver<RefreshJob>,
+    num_workers: Arc<AtomicUsize>,
+}
+
+impl RefreshWorkers {
+    fn new() -> Self {
+        let (jobs, queue) = crossbeam_channel::bounded(REFRESH_QUEUE_CAPACITY);
+        Self {
+            jobs,
+            queue,
+            num_workers: Arc::default(),
+        }
+    }
+
+    fn submit(&self, job: RefreshJob) -> bool {
+        if self.jobs.try_send(job).is_err() {
+            return false;
+        }
+
+        // Start a worker unless the running ones can take the job.
+        let mut n = self.num_workers.load(Ordering::Acquire);
+        while n < MAX_REFRESH_WORKERS && (n == 0 || !self.queue.is_empty()) {
+            match self.num_workers.compare_exchange_weak(
+                n,
+                n + 1,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => {
+                    if !self.spawn_worker() {
+                        self.num_workers.fetch_sub(1, Ordering::AcqRel);
+                        // The OS refused to start a thread. If no worker is left,
+                        // run the queued jobs here rather than leaving them behind.
+                        if self.num_workers.load(Ordering::Acquire) == 0 {
+                            while let Ok(job) = self.queue.try_recv() {
+                                run_job(job);
+                            }
+                        }
+                    }
+                    break;
+                }
+                Err(current) => n = current,
+            }
+        }
+        true
+    }
+
+    fn spawn_worker(&self) -> bool {
+        let queue = self.queue.clone();
+        let num_workers = Arc::clone(&self.num_workers);
+        std::thread::Builder::new()
+            .name("moka-refresh".into())
+            .spawn(move || run_worker(&queue, &num_workers))
+            .is_ok()
+    }
+}
+
+fn run_worker(queue: &Receiver<RefreshJob>, num_workers: &AtomicUsize) {
+    loop {
+        match queue.recv_timeout(REFRESH_WORKER_IDLE_TIMEOUT) {
+            Ok(job) => run_job(job),
+            Err(RecvTimeoutError::Timeout) => {
+                num_workers.fetch_sub(1, Ordering::AcqRel);
+                // A job may have been queued while this worker was still counted.
+                // Take it over unless another worker has been started.
+                if queue.is_empty()
+                    || num_workers
+                        .compare_exchange(0, 1, Ordering::AcqRel, Ordering::Acquire)
+                        .is_err()
+                {
+                    return;
+                }
+            }
+            Err(RecvTimeoutError::Disconnected) => {
+                num_workers.fetch_sub(1, Ordering::AcqRel);
+                return;
+            }
+        }
+    }
+}
+
+fn run_job(job: RefreshJob) {
+    // The panic has already been reported by the panic hook. Keep the worker for
+    // the other refreshes.
+    let _ = catch_unwind(AssertUnwindSafe(job));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Loader, LoaderChain, RefreshWorkers, MAX_REFRESH_WORKERS};
+    use parking_lot::Mutex;
+    use std::{
+        collections::HashMap,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+    };
+
+    #[derive(Default)]
+    struct MapLoader(Mutex<HashMap<u32, u32>>);
+
+    impl Loader<u32, u32> for MapLoader {
+        fn load(&self, key: &u32) -> Option<u32> {
+            self.0.lock().get(key).copied()
+        }
+
+        fn store(&self, key: &u32, value: &u32) {
+            self.0.lock().insert(*key, *value);
+        }
+    }
+
+    #[test]
+    fn load_populates_earlier_loaders() {
+        let (l1, l2, l3) = (
+            Arc::new(MapLoader::default()),
+            Arc::new(MapLoader::default()),
+            Arc::new(MapLoader::default()),
+        );
+        l2.0.lock().insert(2, 20);
+        l3.0.lock().insert(3, 30);
+
+        let chain = LoaderChain::new(vec![l1.clone(), l2.clone(), l3.clone()]).unwrap();
+        assert_eq!(chain.load(&3), Some(30));
+        assert_eq!(chain.load(&2), Some(20));
+        assert_eq!(chain.load(&4), None);
+
+        // The value loaded by l3 is stored to l1 and l2, and the value loaded by l2
+        // is stored to l1 only.
+        assert_eq!(*l1.0.lock(), [(2, 20), (3, 30)].into_iter().collect());
+        assert_eq!(*l2.0.lock(), [(2, 20), (3, 30)].into_iter().collect());
+        assert_eq!(*l3.0.lock(), [(3, 30)].into_iter().collect());
+
+        assert!(LoaderChain::<u32, u32>::new(vec![]).is_none());
+    }
+
+    #[test]
+    fn refresh_workers_are_bounded() {
+        let workers = RefreshWorkers::new();
+        let done = Arc::new(AtomicUsize::new(0));
+        for _ in 0..100 {
+            let done = Arc::clone(&done);
+            assert!(workers.submit(Box::new(move || {
+                std::thread::sleep(std::time::Duration::from_millis(1));
+                done.fetch_add(1, Ordering::AcqRel);
+            })));
+            assert!(workers.num_workers.load(Ordering::Acquire) <= MAX_REFRESH_WORKERS);
+        }
+        while done.load(Ordering::Acquire) < 100 {
+            std::thread::yield_now();
+        }
+    }
+}
@@ -0,0 +1,567 @@
+use super::{
+    cache::Cache, interceptor::InterceptorChain, loader::LoaderChain,
+    value_initializer::LoadLimiter, CacheBuilder,
+};
+use crate::{
+    common::{concurrent::Weigher, thread_number, HousekeeperConfig},
+    notification::{EvictionListener, RemovalCauses},
+    policy::{EvictionPolicy, ExpirationPolicy},
+    sync_base::policy_config::PolicyConfig,
+    Entry, Policy,
+};
+
+use std::{
+    borrow::Borrow,
+    collections::hash_map::RandomState,
+    fmt,
+    hash::{BuildHasher, Hash},
+    sync::Arc,
+    time::Duration,
+};
+
+/// A thread-safe in-memory cache, where each thread reads and writes its own
+/// shard.
+///
+/// `LocalShardedCache` has multiple internal [`Cache`][cache-struct] instances
+/// called shards. Unlike [`SegmentedCache`][seg-cache-struct], which selects the
+/// segment by the hash of the key, `LocalShardedCache` selects the shard by the
+/// calling thread. Threads are assigned to the shards in round-robin order when
+/// they access a `LocalShardedCache` for the first time. So as long as the
+/// number of the worker threads is not greater than the number of the shards,
+/// each thread owns a shard and never contends with other threads on the hot
+/// path.
+///
+/// As each shard is independent, the same key may be cached in more than one
+/// shard, and the max capacity is evenly divided among the shards. This suits
+/// read-heavy workloads where every thread reads a similar set of keys, e.g. a
+/// per-core DNS cache. Methods discarding entries (e.g. `invalidate`) apply to
+/// all the shards.
+///
+/// When [`CacheBuilder::read_through_siblings`][read-through] is enabled, a miss
+/// in the shard of the current thread falls back to the other shards, and the
+/// found value is copied to the shard of the current thread.
+///
+/// # Examples
+///
+/// ```rust
+/// use moka::sync::LocalShardedCache;
+///
+/// let cache = LocalShardedCache::new(100, 4);
+/// cache.insert("a", 1);
+/// assert_eq!(cache.get(&"a"), Some(1));
+///
+/// // The shard of another thread does not have the entry.
+/// let c = cache.clone();
+/// std::thread::spawn(move || assert_eq!(c.get(&"a"), None))
+///     .join()
+///     .unwrap();
+/// ```
+///
+/// [cache-struct]: ./struct.Cache.html
+/// [seg-cache-struct]: ./struct.SegmentedCache.html
+/// [read-through]: ./struct.CacheBuilder.html#method.read_through_siblings
+pub struct LocalShardedCache<K, V, S = RandomState> {
+    inner: Arc<Inner<K, V, S>>,
+}
+
+impl<K, V, S> Clone for LocalShardedCache<K, V, S> {
+    /// Makes a clone of this shared cache.
+    ///
+    /// This operation is cheap as it only creates thread-safe reference counted
+    /// pointers to the shared internal data structures.
+    fn clone(&self) -> Self {
+        Self {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+}
+
+impl<K, V, S> fmt::Debug for LocalShardedCache<K, V, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LocalShardedCache")
+            .field("shard_count", &self.inner.shards.len())
+            .field("read_through_siblings", &self.inner.read_through_siblings)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<K, V> LocalShardedCache<K, V, RandomState>
+where
+    K: Hash + Eq + Send + Sync + 'static,
+    V: Clone + Send + Sync + 'static,
+{
+    /// Constructs a new `LocalShardedCache<K, V>` that has `num_shards` internal
+    /// shards and will store up to the `max_capacity` in total.
+    ///
+    /// To adjust various configuration knobs such as `initial_capacity` or
+    /// `time_to_live`, use the [`CacheBuilder`][builder-struct].
+    ///
+    /// [builder-struct]: ./struct.CacheBuilder.html
+    ///
+    /// # Panics
+    ///
+    /// Panics if `num_shards` is 0.
+    pub fn new(max_capacity: u64, num_shards: usize) -> Self {
+        let build_hasher = RandomState::default();
+        Self::with_everything(
+            None,
+            Some(max_capacity),
+            None,
+            num_shards,
+            build_hasher,
+            None,
+            EvictionPolicy::default(),
+            None,
+            RemovalCauses::default(),
+            ExpirationPolicy::default(),
+            HousekeeperConfig::default(),
+            false,
+            false,
+            None,
+            None,
+            None,
+            None,
+            PolicyConfig::default(),
+        )
+    }
+
+    /// Returns a [`CacheBuilder`][builder-struct], which can builds a
+    /// `LocalShardedCache` with various configuration knobs.
+    ///
+    /// [builder-struct]: ./struct.CacheBuilder.html
+    pub fn builder(num_shards: usize) -> CacheBuilder<K, V, LocalShardedCache<K, V, RandomState>> {
+        CacheBuilder::default().local_shards(num_shards)
+    }
+}
+
+impl<K, V, S> LocalShardedCache<K, V, S> {
+    /// Returns cache’s name.
+    pub fn name(&self) -> Option<&str> {
+        self.inner.shards[0].name()
+    }
+
+    /// Returns a read-only cache policy of this cache.
+    ///
+    /// At this time, cache policy cannot be modified after cache creation.
+    /// A future version may support to modify it.
+    pub fn policy(&self) -> Policy {
+        let mut policy = self.inner.shards[0].policy();
+        policy.set_max_capacity(self.inner.desired_capacity);
+        policy.set_num_segments(self.inner.shards.len());
+        policy
+    }
+
+    /// Returns an approximate number of entries in all the shards. A key cached
+    /// in more than one shard is counted for each shard.
+    ///
+    /// See [`Cache::entry_count`][entry-count] for the inaccuracy of the value.
+    ///
+    /// [entry-count]: ./struct.Cache.html#method.entry_count
+    pub fn entry_count(&self) -> u64 {
+        self.inner.shards.iter().map(|s| s.entry_count()).sum()
+    }
+
+    /// Returns an approximate total weighted size of entries in all the shards.
+    pub fn weighted_size(&self) -> u64 {
+        self.inner.shards.iter().map(|s| s.weighted_size()).sum()
+    }
+
+    /// Returns the number of internal shards of this cache.
+    pub fn shard_count(&self) -> usize {
+        self.inner.shards.len()
+    }
+}
+
+impl<K, V, S> LocalShardedCache<K, V, S>
+where
+    K: Hash + Eq + Send + Sync + 'static,
+    V: Clone + Send + Sync + 'static,
+    S: BuildHasher + Clone + Send + Sync + 'static,
+{
+    /// # Panics
+    ///
+    /// Panics if `num_shards` is 0.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn with_everything(
+        name: Option<String>,
+        max_capacity: Option<u64>,
+        initial_capacity: Option<usize>,
+        num_shards: usize,
+        build_hasher: S,
+        weigher: Option<Weigher<K, V>>,
+        eviction_policy: EvictionPolicy,
+        eviction_listener: Option<EvictionListener<K, V>>,
+        eviction_listener_causes: RemovalCauses,
+        expiration_policy: ExpirationPolicy<K, V>,
+        housekeeper_config: HousekeeperConfig,
+        invalidator_enabled: bool,
+        read_through_siblings: bool,
+        loader_chain: Option<Arc<LoaderChain<K, V>>>,
+        load_limiter: Option<Arc<LoadLimiter>>,
+        error_time_to_live: Option<Duration>,
+        interceptors: Option<Arc<InterceptorChain<K, V>>>,
+        policy_config: PolicyConfig<K, V>,
+    ) -> Self {
+        assert!(num_shards > 0);
+
+        let shard_max_capacity = max_capacity.map(|n| (n as f64 / num_shards as f64).ceil() as u64);
+        let shard_init_capacity =
+            initial_capacity.map(|cap| (cap as f64 / num_shards as f64).ceil() as usize);
+        let mut shard_policy_config = policy_config;
+        shard_policy_config.tenant_quotas = shard_policy_config
+            .tenant_quotas
+            .map(|quotas| quotas.split(num_shards));
+
+        // NOTE: We cannot initialize the shards as `vec![cache; num_shards]`
+        // because Cache::clone() does not clone its inner but shares the same inner.
+        let shards = (0..num_shards)
+            .map(|_| {
+                Cache::with_everything(
+                    name.clone(),
+                    shard_max_capacity,
+                    shard_init_capacity,
+                    build_hasher.clone(),
+                    weigher.clone(),
+                    eviction_policy.clone(),
+                    eviction_listener.clone(),
+                    eviction_listener_causes,
+                    expiration_policy.clone(),
+                    housekeeper_config.clone(),
+                    invalidator_enabled,
+                    loader_chain.clone(),
+                    load_limiter.clone(),
+                    error_time_to_live,
+                    interceptors.clone(),
+                    shard_policy_config.clone(),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        Self {
+            inner: Arc::new(Inner {
+                desired_capacity: max_capacity,
+                shards: shards.into_boxed_slice(),
+                read_through_siblings,
+            }),
+        }
+    }
+
+    /// Returns `true` if the shard of the current thread contains a value for the
+    /// key. Other shards are not looked up even when `read_through_siblings` is
+    /// enabled.
+    ///
+    /// Unlike the `get` method, this method is not considered a cache read operation,
+    /// so it does not update the historic popularity estimator or reset the idle
+    /// timer for the key.
+    ///
+    /// The key may be any borrowed form of the cache's key type, but `Hash` and `Eq`
+    /// on the borrowed form _must_ match those for the key type.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.inner.local_shard().contains_key(key)
+    }
+
+    /// Returns a _clone_ of the value corresponding to the key in the shard of the
+    /// current thread.
+    ///
+    /// If `read_through_siblings` is enabled and the shard does not have the key,
+    /// this method looks up the other shards, and copies the found value to the
+    /// shard of the current thread.
+    ///
+    /// The key may be any borrowed form of the cache's key type, but `Hash` and `Eq`
+    /// on the borrowed form _must_ match those for the key type.
+    pub fn get<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let index = self.inner.local_shard_index();
+        let local = &self.inner.shards[index];
+        let hash = local.hash(key);
+        if let Some(entry) = local.get_with_hash(key, hash, false) {
+            return Some(entry.into_value());
+        }
+        // Share the key of the sibling's entry, as the key cannot be cloned from
+        // its borrowed form.
+        let (shared_key, value) = self.inner.get_from_siblings(index, key)?;
+        local.insert_with_hash(shared_key, hash, value.clone());
+        Some(value)
+    }
+
+    /// Returns a _clone_ of the value corresponding to the key. If the value does
+    /// not exist in the shard of the current thread, evaluates the `init` closure
+    /// and inserts the output to the shard.
+    ///
+    /// If `read_through_siblings` is enabled, the other shards are looked up
+    /// before evaluating the `init` closure.
+    ///
+    /// Concurrent calls on the same not-existing key are coalesced into one
+    /// evaluation of the `init` closure only when they are made by the threads
+    /// sharing the same shard.
+    pub fn get_with(&self, key: K, init: impl FnOnce() -> V) -> V {
+        let index = self.inner.local_shard_index();
+        let local = &self.inner.shards[index];
+        if self.inner.read_through_siblings && !local.contains_key(&key) {
+            if let Some((_, value)) = self.inner.get_from_siblings(index, &key) {
+                local.insert(key, value.clone());
+                return value;
+            }
+        }
+        local.get_with(key, init)
+    }
+
+    /// Similar to [`get_with`](#method.get_with), but instead of passing an owned
+    /// key, you can pass a reference to the key. The key is cloned only when the
+    /// value is copied from a sibling shard or inserted by the `init` closure.
+    pub fn get_with_by_ref<Q>(&self, key: &Q, init: impl FnOnce() -> V) -> V
+    where
+        K: Borrow<Q>,
+        Q: ToOwned<Owned = K> + Hash + Eq + ?Sized,
+    {
+        let index = self.inner.local_shard_index();
+        let local = &self.inner.shards[index];
+        if self.inner.read_through_siblings && !local.contains_key(key) {
+            if let Some((_, value)) = self.inner.get_from_siblings(index, key) {
+                local.insert(key.to_owned(), value.clone());
+                return value;
+            }
+        }
+        local.get_with_by_ref(key, init)
+    }
+
+    /// Returns a _clone_ of the value corresponding to the key in the shard of the
+    /// current thread. If the value does not exist, calls the loaders registered by
+    /// [`CacheBuilder::loader`][builder-loader] in order, and inserts the value
+    /// returned by one of them to the shard. See
+    /// [`Cache::get_or_load`][get-or-load] for more details.
+    ///
+    /// If `read_through_siblings` is enabled, the other shards are looked up
+    /// before calling the loaders.
+    ///
+    /// [builder-loader]: ./struct.CacheBuilder.html#method.loader
+    /// [get-or-load]: ./struct.Cache.html#method.get_or_load
+    pub fn get_or_load<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: ToOwned<Owned = K> + Hash + Eq + ?Sized,
+    {
+        let index = self.inner.local_shard_index();
+        let local = &self.inner.shards[index];
+        if self.inner.read_through_siblings && !local.contains_key(key) {
+            if let Some((_, value)) = self.inner.get_from_siblings(index, key) {
+                local.insert(key.to_owned(), value.clone());
+                return Some(value);
+            }
+        }
+        local.get_or_load(key)
+    }
+
+    /// Inserts a key-value pair into the shard of the current thread.
+    ///
+    /// If the shard has this key present, the value is updated. Other shards are
+    /// not updated, so they may keep returning their old values for the key until
+    /// the key is invalidated.
+    pub fn insert(&self, key: K, value: V) {
+        self.inner.local_shard().insert(key, value);
+    }
+
+    /// Discards any cached value for the key from all the shards.
+    ///
+    /// The key may be any borrowed form of the cache's key type, but `Hash` and `Eq`
+    /// on the borrowed form _must_ match those for the key type.
+    pub fn invalidate<Q>(&self, key: &Q)
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        for shard in self.inner.shards.iter() {
+            shard.invalidate(key);
+        }
+    }
+
+    /// Discards all cached values in all the shards.
+    ///
+    /// See [`Cache::invalidate_all`][invalidate-all] for details.
+    ///
+    /// [invalidate-all]: ./struct.Cache.html#method.invalidate_all
+    pub fn invalidate_all(&self) {
+        for shard in self.inner.shards.iter() {
+            shard.invalidate_all();
+        }
+    }
+
+    /// Performs any pending maintenance operations needed by the cache.
+    pub fn run_pending_tasks(&self) {
+        for shard in self.inner.shards.iter() {
+            shard.run_pending_tasks();
+        }
+    }
+}
+
+struct Inner<K, V, S> {
+    desired_capacity: Option<u64>,
+    shards: Box<[Cache<K, V, S>]>,
+    read_through_siblings: bool,
+}
+
+impl<K, V, S> Inner<K, V, S> {
+    #[inline]
+    fn local_shard_index(&self) -> usize {
+        thread_number::current() % self.shards.len()
+    }
+
+    #[inline]
+    fn local_shard(&self) -> &Cache<K, V, S> {
+        &self.shards[self.local_shard_index()]
+    }
+}
+
+impl<K, V, S> Inner<K, V, S>
+where
+    K: Hash + Eq + Send + Sync + 'static,
+    V: Clone + Send + Sync + 'static,
+    S: BuildHasher + Clone + Send + Sync + 'static,
+{
+    /// Looks up the shards other than the shard at `index`, if
+    /// `read_through_siblings` is enabled.
+    /// Returns the key and a clone of the value of the first sibling shard that
+    /// has the key, if `read_through_siblings` is enabled.
+    fn get_from_siblings<Q>(&self, index: usize, key: &Q) -> Option<(Arc<K>, V)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        if !self.read_through_siblings {
+            return None;
+        }
+        self.shards
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| *i != index)
+            .find_map(|(_, shard)| shard.get_with_hash(key, shard.hash(key), true))
+            .map(Entry::into_shared_key_and_value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LocalShardedCache;
+
+    /// Runs `f` on a spawned thread whose shard is not the shard of the current
+    /// thread. Threads are spawned until one is assigned to another shard, as
+    /// other tests running in parallel also take shards.
+    fn run_on_other_shard<K, V>(
+        cache: &LocalShardedCache<K, V>,
+        f: impl Fn(&LocalShardedCache<K, V>) + Clone + Send + 'static,
+    ) where
+        K: Send + Sync + 'static,
+        V: Send + Sync + 'static,
+    {
+        let current = cache.inner.local_shard_index();
+        loop {
+            let (c, f) = (cache.clone(), f.clone());
+            let done = std::thread::spawn(move || {
+                let other = c.inner.local_shard_index() != current;
+                if other {
+                    f(&c);
+                }
+                other
+            })
+            .join()
+            .unwrap();
+            if done {
+                break;
+            }
+        }
+    }
+
+    #[test]
+    fn shards_are_local_to_threads() {
+        let cache = LocalShardedCache::new(100, 4);
+        assert_eq!(cache.shard_count(), 4);
+
+        cache.insert("a", 1);
+        assert_eq!(cache.get(&"a"), Some(1));
+
+        // Another shard can cache its own value.
+        run_on_other_shard(&cache, |c| {
+            assert_eq!(c.get(&"a"), None);
+            c.insert("a", 2);
+            assert_eq!(c.get(&"a"), Some(2));
+        });
+
+        assert_eq!(cache.get(&"a"), Some(1));
+        cache.run_pending_tasks();
+        assert_eq!(cache.entry_count(), 2);
+
+        // invalidate discards the key from all shards.
+        cache.invalidate(&"a");
+        cache.run_pending_tasks();
+        assert_eq!(cache.entry_count(), 0);
+    }
+
+    #[test]
+    fn read_through_siblings() {
+        let cache = LocalShardedCache::builder(4)
+            .max_capacity(100)
+            .read_through_siblings()
+            .build();
+        cache.insert("a", 1);
+
+        run_on_other_shard(&cache, |c| {
+            assert!(!c.contains_key(&"a"));
+            // Found in the shard of the main thread, and copied to the local shard.
+            assert_eq!(c.get(&"a"), Some(1));
+            assert!(c.contains_key(&"a"));
+            assert_eq!(c.get_with("b", || 2), 2);
+        });
+
+        assert_eq!(cache.get_with("b", || unreachable!()), 2);
+        cache.run_pending_tasks();
+        assert_eq!(cache.entry_count(), 4);
+    }
+
+    #[test]
+    fn get_with_by_ref() {
+        let cache: LocalShardedCache<String, u32> = LocalShardedCache::builder(4)
+            .max_capacity(100)
+            .read_through_siblings()
+            .build();
+        assert_eq!(cache.get_with_by_ref("a", || 1), 1);
+        assert_eq!(cache.get_with_by_ref("a", || unreachable!()), 1);
+
+        run_on_other_shard(&cache, |c| {
+            assert_eq!(c.get_with_by_ref("a", || unreachable!()), 1);
+            assert_eq!(c.get_with_by_ref("b", || 2), 2);
+        });
+
+        assert_eq!(cache.get(&"b".to_string()), Some(2));
+    }
+
+    #[test]
+    fn get_by_borrowed_key() {
+        use std::sync::Arc;
+
+        // `str` is not `ToOwned<Owned = Arc<str>>`, so the key copied from a
+        // sibling shard is shared with the sibling.
+        let cache: LocalShardedCache<Arc<str>, u32> = LocalShardedCache::builder(4)
+            .max_capacity(100)
+            .read_through_siblings()
+            .build();
+        cache.insert(Arc::from("a"), 1);
+        assert_eq!(cache.get("a"), Some(1));
+
+        run_on_other_shard(&cache, |c| {
+            assert!(!c.contains_key("a"));
+            assert_eq!(c.get("a"), Some(1));
+            assert!(c.contains_key("a"));
+            assert_eq!(c.get("b"), None);
+        });
+
+        cache.run_pending_tasks();
+        assert_eq!(cache.entry_count(), 2);
+    }
+}
@@ -0,0 +1,169 @@
+use super::Cache;
+
+use std::{
+    borrow::Borrow,
+    collections::hash_map::RandomState,
+    fmt,
+    hash::{BuildHasher, Hash},
+    sync::Arc,
+};
+
+type Projection<V, U> = Arc<dyn Fn(&V) -> &U + Send + Sync + 'static>;
+
+/// A read-only view of a [`Cache`][cache-struct], whose values are the parts of
+/// the cached values selected by a projection closure.
+///
+/// A `MapView` is created by the [`map_view`][map-view-method] method of `Cache`.
+/// It shares the entries with the cache, so the inserts and invalidations to the
+/// cache are visible through the view immediately.
+///
+/// Cloning a `MapView` is cheap as it only clones the reference counted pointers
+/// to the cache and the projection closure.
+///
+/// [cache-struct]: ./struct.Cache.html
+/// [map-view-method]: ./struct.Cache.html#method.map_view
+pub struct MapView<K, V, U: ?Sized, S = RandomState> {
+    cache: Cache<K, V, S>,
+    project: Projection<V, U>,
+}
+
+impl<K, V, U: ?Sized, S> Clone for MapView<K, V, U, S> {
+    fn clone(&self) -> Self {
+        Self {
+            cache: self.cache.clone(),
+            project: Arc::clone(&self.project),
+        }
+    }
+}
+
+impl<K, V, U: ?Sized, S> fmt::Debug for MapView<K, V, U, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MapView")
+            .field("name", &self.cache.name())
+            .field("entry_count", &self.cache.entry_count())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<K, V, U: ?Sized, S> MapView<K, V, U, S> {
+    /// Returns the name of the underlying cache.
+    pub fn name(&self) -> Option<&str> {
+        self.cache.name()
+    }
+
+    /// Returns an approximate number of entries in the underlying cache.
+    ///
+    /// See [`Cache::entry_count`](./struct.Cache.html#method.entry_count) for
+    /// details.
+    pub fn entry_count(&self) -> u64 {
+        self.cache.entry_count()
+    }
+
+    /// Returns an approximate total weighted size of entries in the underlying
+    /// cache.
+    pub fn weighted_size(&self) -> u64 {
+        self.cache.weighted_size()
+    }
+}
+
+impl<K, V, U, S> MapView<K, V, U, S>
+where
+    K: Hash + Eq + Send + Sync + 'static,
+    V: Clone + Send + Sync + 'static,
+    U: ?Sized,
+    S: BuildHasher + Clone + Send + Sync + 'static,
+{
+    pub(crate) fn new<F>(cache: Cache<K, V, S>, project: F) -> Self
+    where
+        F: Fn(&V) -> &U + Send + Sync + 'static,
+    {
+        Self {
+            cache,
+            project: Arc::new(project),
+        }
+    }
+
+    /// Returns `true` if the underlying cache contains a value for the key.
+    ///
+    /// Like `Cache::contains_key`, this method is not considered a cache read
+    /// operation.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.cache.contains_key(key)
+    }
+
+    /// Returns an owned copy of the selected part of the value corresponding to
+    /// the key. The rest of the value is not cloned.
+    pub fn get<Q>(&self, key: &Q) -> Option<U::Owned>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        U: ToOwned,
+    {
+        self.get_and(key, U::to_owned)
+    }
+
+    /// Calls the closure `f` with a reference to the selected part of the value
+    /// corresponding to the key, and returns its result. Nothing is cloned.
+    pub fn get_and<Q, T>(&self, key: &Q, f: impl FnOnce(&U) -> T) -> Option<T>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.cache.get_and_map(key, |v| f((self.project)(v)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Cache;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Profile {
+        name: String,
+        tags: Vec<String>,
+        visits: u32,
+    }
+
+    #[test]
+    fn map_view() {
+        let cache = Cache::new(100);
+        let view = cache.map_view(|p: &Profile| p.name.as_str());
+        let visits = cache.map_view(|p: &Profile| &p.visits);
+
+        cache.insert(
+            1,
+            Profile {
+                name: "alice".to_string(),
+                tags: vec!["admin".to_string()],
+                visits: 3,
+            },
+        );
+        assert!(view.contains_key(&1));
+        assert_eq!(view.get(&1), Some("alice".to_string()));
+        assert_eq!(view.get_and(&1, str::len), Some(5));
+        assert_eq!(visits.get(&1), Some(3));
+        assert_eq!(view.get(&2), None);
+
+        // Updates to the cache are visible through the views.
+        cache.insert(
+            1,
+            Profile {
+                name: "bob".to_string(),
+                tags: vec![],
+                visits: 4,
+            },
+        );
+        assert_eq!(view.clone().get(&1), Some("bob".to_string()));
+        assert_eq!(visits.get(&1), Some(4));
+
+        cache.invalidate(&1);
+        assert_eq!(view.get(&1), None);
+
+        cache.run_pending_tasks();
+        assert_eq!(view.entry_count(), 0);
+    }
+}
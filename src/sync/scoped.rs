@@ -0,0 +1,328 @@
+use super::Cache;
+
+use std::{
+    borrow::Borrow,
+    collections::hash_map::RandomState,
+    fmt,
+    hash::{BuildHasher, Hash, Hasher},
+    sync::Arc,
+};
+
+/// The key type of a [`Cache`][cache-struct] shared by [`ScopedCache`]s. It pairs
+/// the name of a scope with the key given to the scoped cache.
+///
+/// [cache-struct]: ./struct.Cache.html
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ScopedKey<K> {
+    scope: Arc<str>,
+    key: K,
+}
+
+impl<K> ScopedKey<K> {
+    /// Creates a key for the `key` in the `scope`.
+    pub fn new(scope: impl Into<Arc<str>>, key: K) -> Self {
+        Self {
+            scope: scope.into(),
+            key,
+        }
+    }
+
+    /// Returns the name of the scope.
+    pub fn scope(&self) -> &str {
+        &self.scope
+    }
+
+    /// Returns a reference to the key in the scope.
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Consumes this `ScopedKey`, returning the key in the scope.
+    pub fn into_key(self) -> K {
+        self.key
+    }
+}
+
+/// A scope name and a key, either owned by a `ScopedKey` or borrowed. Looking up
+/// the parent cache by `&dyn ScopedKeyRef` lets a `ScopedCache` find an entry
+/// without cloning the key and the scope name.
+///
+/// This trait is public only to be used in the `Borrow` implementation below. It
+/// is not reachable from outside of the crate.
+pub trait ScopedKeyRef<K> {
+    fn scope(&self) -> &str;
+    fn key(&self) -> &K;
+}
+
+impl<K> ScopedKeyRef<K> for ScopedKey<K> {
+    fn scope(&self) -> &str {
+        &self.scope
+    }
+
+    fn key(&self) -> &K {
+        &self.key
+    }
+}
+
+struct BorrowedScopedKey<'a, K> {
+    scope: &'a str,
+    key: &'a K,
+}
+
+impl<K> ScopedKeyRef<K> for BorrowedScopedKey<'_, K> {
+    fn scope(&self) -> &str {
+        self.scope
+    }
+
+    fn key(&self) -> &K {
+        self.key
+    }
+}
+
+impl<'a, K: 'a> Borrow<dyn ScopedKeyRef<K> + 'a> for ScopedKey<K> {
+    fn borrow(&self) -> &(dyn ScopedKeyRef<K> + 'a) {
+        self
+    }
+}
+
+// Must hash in the same way as the derived `Hash` of `ScopedKey`. `Arc<str>`
+// hashes in the same way as `str`.
+impl<K: Hash> Hash for dyn ScopedKeyRef<K> + '_ {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.scope().hash(state);
+        self.key().hash(state);
+    }
+}
+
+impl<K: PartialEq> PartialEq for dyn ScopedKeyRef<K> + '_ {
+    fn eq(&self, other: &Self) -> bool {
+        self.scope() == other.scope() && self.key() == other.key()
+    }
+}
+
+impl<K: Eq> Eq for dyn ScopedKeyRef<K> + '_ {}
+
+/// A named child handle of a [`Cache`][cache-struct], which stores its entries in
+/// the parent cache under its own scope.
+///
+/// A `ScopedCache` is created by the [`scoped`][scoped-method] method of a `Cache`
+/// whose key type is [`ScopedKey`]. The keys of different scopes never collide, so
+/// each subsystem of an application can have its own scope, while all scopes share
+/// the capacity, the eviction and expiration policies and the housekeeper of the
+/// parent cache.
+///
+/// Cloning a `ScopedCache` is cheap as it only clones the reference counted
+/// pointers to the parent cache and the scope name.
+///
+/// [cache-struct]: ./struct.Cache.html
+/// [scoped-method]: ./struct.Cache.html#method.scoped
+pub struct ScopedCache<K, V, S = RandomState> {
+    cache: Cache<ScopedKey<K>, V, S>,
+    scope: Arc<str>,
+}
+
+impl<K, V, S> Clone for ScopedCache<K, V, S> {
+    fn clone(&self) -> Self {
+        Self {
+            cache: self.cache.clone(),
+            scope: Arc::clone(&self.scope),
+        }
+    }
+}
+
+impl<K, V, S> fmt::Debug for ScopedCache<K, V, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ScopedCache")
+            .field("name", &self.cache.name())
+            .field("scope", &self.scope)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<K, V, S> ScopedCache<K, V, S> {
+    /// Returns the name of the scope.
+    pub fn scope(&self) -> &str {
+        &self.scope
+    }
+
+    /// Returns a reference to the parent cache.
+    pub fn parent(&self) -> &Cache<ScopedKey<K>, V, S> {
+        &self.cache
+    }
+}
+
+impl<K, V, S> Cache<ScopedKey<K>, V, S>
+where
+    K: Hash + Eq + Send + Sync + 'static,
+    V: Clone + Send + Sync + 'static,
+    S: BuildHasher + Clone + Send + Sync + 'static,
+{
+    /// Returns a [`ScopedCache`] that stores its entries in this cache under the
+    /// `scope`. Scoped caches with the same scope name share the same entries.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use moka::sync::{Cache, ScopedKey};
+    ///
+    /// let cache: Cache<ScopedKey<u32>, String> = Cache::new(100);
+    /// let search = cache.scoped("search");
+    /// let users = cache.scoped("users");
+    ///
+    /// search.insert(1, "rust".to_string());
+    /// users.insert(1, "alice".to_string());
+    /// assert_eq!(search.get(&1), Some("rust".to_string()));
+    /// assert_eq!(users.get(&1), Some("alice".to_string()));
+    ///
+    /// // The entries are stored in the parent cache.
+    /// assert!(cache.contains_key(&ScopedKey::new("users", 1)));
+    /// ```
+    pub fn scoped(&self, scope: &str) -> ScopedCache<K, V, S> {
+        ScopedCache {
+            cache: self.clone(),
+            scope: scope.into(),
+        }
+    }
+}
+
+impl<K, V, S> ScopedCache<K, V, S>
+where
+    K: Hash + Eq + Send + Sync + 'static,
+    V: Clone + Send + Sync + 'static,
+    S: BuildHasher + Clone + Send + Sync + 'static,
+{
+    /// Returns `true` if the scope contains a value for the key.
+    ///
+    /// Like `Cache::contains_key`, this method is not considered a cache read
+    /// operation.
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.cache.contains_key(self.borrowed_key(key).as_dyn())
+    }
+
+    /// Returns a clone of the value corresponding to the key in the scope.
+    pub fn get(&self, key: &K) -> Option<V> {
+        self.cache.get(self.borrowed_key(key).as_dyn())
+    }
+
+    /// Returns a clone of the value corresponding to the key in the scope. If the
+    /// value does not exist, evaluates the `init` closure and inserts its output.
+    ///
+    /// See [`Cache::get_with`](./struct.Cache.html#method.get_with) for details.
+    pub fn get_with(&self, key: K, init: impl FnOnce() -> V) -> V {
+        self.cache.get_with(self.scoped_key(key), init)
+    }
+
+    /// Inserts a key-value pair into the scope.
+    pub fn insert(&self, key: K, value: V) {
+        self.cache.insert(self.scoped_key(key), value);
+    }
+
+    /// Discards the cached value for the key in the scope.
+    pub fn invalidate(&self, key: &K) {
+        self.cache.invalidate(self.borrowed_key(key).as_dyn());
+    }
+
+    /// Discards the cached value for the key in the scope and returns a clone of
+    /// the value.
+    pub fn remove(&self, key: &K) -> Option<V> {
+        self.cache.remove(self.borrowed_key(key).as_dyn())
+    }
+
+    /// Discards all cached values in the scope. The entries of the other scopes are
+    /// kept.
+    ///
+    /// Unlike `Cache::invalidate_all`, this method iterates over all entries of the
+    /// parent cache, so it takes time proportional to the size of the parent cache.
+    pub fn invalidate_all(&self) {
+        for (key, _) in self.cache.iter() {
+            if key.scope == self.scope {
+                self.cache.invalidate(&*key);
+            }
+        }
+    }
+
+    fn scoped_key(&self, key: K) -> ScopedKey<K> {
+        ScopedKey {
+            scope: Arc::clone(&self.scope),
+            key,
+        }
+    }
+
+    fn borrowed_key<'a>(&'a self, key: &'a K) -> BorrowedScopedKey<'a, K> {
+        BorrowedScopedKey {
+            scope: &self.scope,
+            key,
+        }
+    }
+}
+
+impl<'a, K> BorrowedScopedKey<'a, K> {
+    fn as_dyn(&self) -> &(dyn ScopedKeyRef<K> + 'a) {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Cache, ScopedKey};
+
+    #[test]
+    fn scoped() {
+        let cache: Cache<ScopedKey<u32>, &str> = Cache::new(100);
+        let search = cache.scoped("search");
+        let users = cache.scoped("users");
+
+        search.insert(1, "rust");
+        search.insert(2, "moka");
+        users.insert(1, "alice");
+        assert_eq!(search.get(&1), Some("rust"));
+        assert_eq!(users.get(&1), Some("alice"));
+        assert!(!users.contains_key(&2));
+        assert_eq!(users.get_with(2, || "bob"), "bob");
+
+        // A scoped cache with the same name shares the entries.
+        assert_eq!(cache.scoped("search").get(&2), Some("moka"));
+        assert_eq!(cache.get(&ScopedKey::new("users", 2)), Some("bob"));
+
+        cache.run_pending_tasks();
+        assert_eq!(cache.entry_count(), 4);
+
+        assert_eq!(users.remove(&1), Some("alice"));
+        assert_eq!(search.get(&1), Some("rust"));
+
+        search.invalidate_all();
+        assert_eq!(search.get(&1), None);
+        assert_eq!(search.get(&2), None);
+        assert_eq!(users.get(&2), Some("bob"));
+
+        cache.run_pending_tasks();
+        assert_eq!(cache.entry_count(), 1);
+    }
+
+    #[test]
+    fn lookup_without_cloning_keys() {
+        // A key that cannot be cloned.
+        #[derive(Debug, PartialEq, Eq, Hash)]
+        struct Key(String);
+
+        let cache: Cache<ScopedKey<Key>, u32> = Cache::new(100);
+        let users = cache.scoped("users");
+        users.insert(Key("alice".into()), 1);
+        users.insert(Key("bob".into()), 2);
+
+        let alice = Key("alice".into());
+        assert!(users.contains_key(&alice));
+        assert_eq!(users.get(&alice), Some(1));
+        assert!(!cache.scoped("search").contains_key(&alice));
+        // The borrowed key finds the same entry as the owned one.
+        assert_eq!(
+            cache.get(&ScopedKey::new("users", Key("bob".into()))),
+            Some(2)
+        );
+
+        users.invalidate(&alice);
+        assert_eq!(users.get(&alice), None);
+        assert_eq!(users.remove(&Key("bob".into())), Some(2));
+        assert!(!users.contains_key(&Key("bob".into())));
+    }
+}
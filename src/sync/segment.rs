@@ -1,21 +1,38 @@
-use super::{cache::Cache, CacheBuilder, OwnedKeyEntrySelector, RefKeyEntrySelector};
+use super::{
+    cache::Cache, interceptor::InterceptorChain, loader::LoaderChain, stream,
+    value_initializer::LoadLimiter, CacheBuilder, OwnedKeyEntrySelector, RefKeyEntrySelector,
+};
 use crate::common::concurrent::Weigher;
+#[cfg(feature = "numa")]
+use crate::common::numa::{NodeWorkers, NumaTopology};
 use crate::{
     common::HousekeeperConfig,
-    notification::EvictionListener,
+    notification::{EvictionListener, RemovalCauses},
     policy::{EvictionPolicy, ExpirationPolicy},
-    sync_base::iter::{Iter, ScanningGet},
+    sync::{
+        CacheStats, ClassStats, ExportStream, GhostListStats, HitRatioReport, ReadSamplingStats,
+    },
+    sync_base::{
+        iter::{IntoIter, Iter, ScanningGet},
+        pinned::PinnedEntry,
+        PredicateId,
+    },
+    sync_base::{policy_config::PolicyConfig, stats::DefaultStatsCounter},
     Entry, Policy, PredicateError,
 };
 
 use std::{
     borrow::Borrow,
-    collections::hash_map::RandomState,
+    collections::{hash_map::RandomState, HashMap},
     fmt,
     hash::{BuildHasher, Hash, Hasher},
+    io,
     sync::Arc,
+    time::{Duration, Instant as StdInstant},
 };
 
+pub(crate) type SegmentSelector<K> = Arc<dyn Fn(&K) -> usize + Send + Sync + 'static>;
+
 /// A thread-safe concurrent in-memory cache, with multiple internal segments.
 ///
 /// `SegmentedCache` has multiple internal [`Cache`][cache-struct] instances for
@@ -30,6 +47,27 @@ pub struct SegmentedCache<K, V, S = RandomState> {
     inner: Arc<Inner<K, V, S>>,
 }
 
+/// The handle of a predicate registered to all segments of a
+/// [`SegmentedCache`][segmented-cache] by its `invalidate_entries_if` method.
+///
+/// Pass it to [`SegmentedCache::is_invalidation_finished`][is-finished] to check
+/// if the invalidation has been completed in every segment.
+///
+/// [segmented-cache]: ./struct.SegmentedCache.html
+/// [is-finished]: ./struct.SegmentedCache.html#method.is_invalidation_finished
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InvalidationHandle {
+    predicate_ids: Vec<PredicateId>,
+}
+
+impl InvalidationHandle {
+    /// Returns the IDs of the predicate registered to the segments, ordered by
+    /// the segment index.
+    pub fn predicate_ids(&self) -> &[PredicateId] {
+        &self.predicate_ids
+    }
+}
+
 // TODO: https://github.com/moka-rs/moka/issues/54
 #[allow(clippy::non_send_fields_in_send_ty)]
 unsafe impl<K, V, S> Send for SegmentedCache<K, V, S>
@@ -105,9 +143,17 @@ where
             None,
             EvictionPolicy::default(),
             None,
+            RemovalCauses::default(),
             ExpirationPolicy::default(),
             HousekeeperConfig::default(),
             false,
+            false,
+            None,
+            None,
+            None,
+            None,
+            None,
+            PolicyConfig::default(),
         )
     }
 
@@ -191,6 +237,147 @@ impl<K, V, S> SegmentedCache<K, V, S> {
             .map(|seg| seg.weighted_size())
             .sum()
     }
+
+    /// Returns an approximate weighted size of the entries of the `tenant` in this
+    /// cache, or `None` if the cache was not built with
+    /// [`tenant_quotas`][tenant-quotas] or the `tenant` is out of range.
+    ///
+    /// [tenant-quotas]: ./struct.CacheBuilder.html#method.tenant_quotas
+    pub fn tenant_weighted_size(&self, tenant: usize) -> Option<u64> {
+        self.inner
+            .segments
+            .iter()
+            .map(|seg| seg.tenant_weighted_size(tenant))
+            .sum()
+    }
+
+    /// Returns an approximate number of the entries of the `tenant` in this cache,
+    /// or `None` if the cache was not built with [`tenant_quotas`][tenant-quotas] or
+    /// the `tenant` is out of range.
+    ///
+    /// [tenant-quotas]: ./struct.CacheBuilder.html#method.tenant_quotas
+    pub fn tenant_entry_count(&self, tenant: usize) -> Option<u64> {
+        self.inner
+            .segments
+            .iter()
+            .map(|seg| seg.tenant_entry_count(tenant))
+            .sum()
+    }
+
+    /// Returns the report of the hit ratio analyzer, merged across the segments, or
+    /// `None` if the cache was not built with
+    /// [`analyze_hit_ratio`][analyze-hit-ratio] or without a max capacity.
+    ///
+    /// [analyze-hit-ratio]: ./struct.CacheBuilder.html#method.analyze_hit_ratio
+    pub fn hit_ratio_report(&self) -> Option<HitRatioReport> {
+        let mut reports = self.inner.segments.iter().map(Cache::hit_ratio_report);
+        let mut merged = reports.next()??;
+        for report in reports.flatten() {
+            merged.merge(&report);
+        }
+        Some(merged)
+    }
+
+    /// Returns the counters of the misses on the recently evicted keys, summed
+    /// across the segments, or `None` if the cache was not built with
+    /// [`track_recent_evictions`][track-recent-evictions] or without a max capacity.
+    ///
+    /// [track-recent-evictions]: ./struct.CacheBuilder.html#method.track_recent_evictions
+    pub fn ghost_list_stats(&self) -> Option<GhostListStats> {
+        let mut stats = self.inner.segments.iter().map(Cache::ghost_list_stats);
+        let mut merged = stats.next()??;
+        for s in stats.flatten() {
+            merged.merge(&s);
+        }
+        Some(merged)
+    }
+
+    /// Returns the counters of each class of the keys, merged over the segments,
+    /// or `None` if the cache was not built with [`class_stats`][class-stats].
+    ///
+    /// [class-stats]: ./struct.CacheBuilder.html#method.class_stats
+    pub fn class_stats(&self) -> Option<HashMap<&'static str, ClassStats>> {
+        let mut stats = self.inner.segments.iter().map(Cache::class_stats);
+        let mut merged = stats.next()??;
+        for s in stats.flatten() {
+            for (class, counters) in s {
+                merged.entry(class).or_default().merge(&counters);
+            }
+        }
+        Some(merged)
+    }
+
+    /// Returns the histogram of the lifetimes of the entries removed from the
+    /// cache, merged over the segments, or `None` if the cache was not built with
+    /// [`track_entry_lifetimes`][track-entry-lifetimes].
+    ///
+    /// Requires the `entry-stats` crate feature.
+    ///
+    /// [track-entry-lifetimes]: ./struct.CacheBuilder.html#method.track_entry_lifetimes
+    #[cfg(feature = "entry-stats")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "entry-stats")))]
+    pub fn entry_lifetime_histogram(&self) -> Option<crate::sync::LifetimeHistogram> {
+        let mut histograms = self
+            .inner
+            .segments
+            .iter()
+            .map(Cache::entry_lifetime_histogram);
+        let mut merged = histograms.next()??;
+        for h in histograms.flatten() {
+            merged.merge(&h);
+        }
+        Some(merged)
+    }
+
+    /// Returns the state of the sampling of the reads, merged over the segments,
+    /// or `None` if the cache was not built with
+    /// [`sample_reads_under_pressure`][sample-reads]. The interval is the largest
+    /// one of the segments.
+    ///
+    /// [sample-reads]: ./struct.CacheBuilder.html#method.sample_reads_under_pressure
+    pub fn read_sampling_stats(&self) -> Option<ReadSamplingStats> {
+        let mut stats = self.inner.segments.iter().map(Cache::read_sampling_stats);
+        let mut merged = stats.next()??;
+        for s in stats.flatten() {
+            merged.merge(&s);
+        }
+        Some(merged)
+    }
+
+    /// Returns a snapshot of the hit, miss, eviction and load counts of all the
+    /// segments, or `None` if the cache was built with
+    /// [`disable_stats`][disable-stats].
+    ///
+    /// [disable-stats]: ./struct.CacheBuilder.html#method.disable_stats
+    pub fn stats(&self) -> Option<CacheStats> {
+        // The segments share the same stats counter.
+        self.inner.segments[0].stats()
+    }
+
+    /// Returns `true` if any segment of the cache has lost the internal channels
+    /// to its housekeeper. See [`Cache::is_degraded`][cache-is-degraded].
+    ///
+    /// [cache-is-degraded]: ./struct.Cache.html#method.is_degraded
+    pub fn is_degraded(&self) -> bool {
+        self.inner.segments.iter().any(Cache::is_degraded)
+    }
+
+    /// Returns the number of internal segments of this cache.
+    ///
+    /// The number is the `num_segments` given to the builder rounded up to the
+    /// next power of two.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use moka::sync::SegmentedCache;
+    ///
+    /// let cache: SegmentedCache<i32, i32> = SegmentedCache::new(100, 3);
+    /// assert_eq!(cache.segment_count(), 4);
+    /// ```
+    pub fn segment_count(&self) -> usize {
+        self.inner.segments.len()
+    }
 }
 
 impl<K, V, S> SegmentedCache<K, V, S>
@@ -212,9 +399,17 @@ where
         weigher: Option<Weigher<K, V>>,
         eviction_policy: EvictionPolicy,
         eviction_listener: Option<EvictionListener<K, V>>,
+        eviction_listener_causes: RemovalCauses,
         expiration_policy: ExpirationPolicy<K, V>,
         housekeeper_config: HousekeeperConfig,
         invalidator_enabled: bool,
+        numa_aware: bool,
+        segment_selector: Option<SegmentSelector<K>>,
+        loader_chain: Option<Arc<LoaderChain<K, V>>>,
+        load_limiter: Option<Arc<LoadLimiter>>,
+        error_time_to_live: Option<Duration>,
+        interceptors: Option<Arc<InterceptorChain<K, V>>>,
+        policy_config: PolicyConfig<K, V>,
     ) -> Self {
         Self {
             inner: Arc::new(Inner::new(
@@ -226,9 +421,17 @@ where
                 weigher,
                 eviction_policy,
                 eviction_listener,
+                eviction_listener_causes,
                 expiration_policy,
                 housekeeper_config,
                 invalidator_enabled,
+                numa_aware,
+                segment_selector,
+                loader_chain,
+                load_limiter,
+                error_time_to_live,
+                interceptors,
+                policy_config,
             )),
         }
     }
@@ -247,7 +450,24 @@ where
         Q: Hash + Eq + ?Sized,
     {
         let hash = self.inner.hash(key);
-        self.inner.select(hash).contains_key_with_hash(key, hash)
+        self.inner
+            .select_by_borrowed_key(key, hash)
+            .contains_key_with_hash(key, hash)
+    }
+
+    /// Returns `true` if the key was recently evicted by the size constraint of the
+    /// cache. See
+    /// [`Cache::was_recently_evicted`](./struct.Cache.html#method.was_recently_evicted)
+    /// for details.
+    pub fn was_recently_evicted<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let hash = self.inner.hash(key);
+        self.inner
+            .select_by_borrowed_key(key, hash)
+            .was_recently_evicted(key)
     }
 
     /// Returns a _clone_ of the value corresponding to the key.
@@ -259,7 +479,12 @@ where
     /// The key may be any borrowed form of the cache's key type, but `Hash` and `Eq`
     /// on the borrowed form _must_ match those for the key type.
     ///
+    /// This method does not call the loaders registered by
+    /// [`CacheBuilder::loader`][builder-loader] on a miss. Use
+    /// [`get_or_load`](#method.get_or_load) to read through them.
+    ///
     /// [rustdoc-std-arc]: https://doc.rust-lang.org/stable/std/sync/struct.Arc.html
+    /// [builder-loader]: ./struct.CacheBuilder.html#method.loader
     pub fn get<Q>(&self, key: &Q) -> Option<V>
     where
         K: Borrow<Q>,
@@ -267,17 +492,45 @@ where
     {
         let hash = self.inner.hash(key);
         self.inner
-            .select(hash)
+            .select_by_borrowed_key(key, hash)
             .get_with_hash(key, hash, false)
             .map(Entry::into_value)
     }
 
+    /// Returns an `Entry` holding a clone of the value and the key corresponding to
+    /// the key, and the metadata of the entry. See
+    /// [`Cache::get_entry`](./struct.Cache.html#method.get_entry) for details.
+    pub fn get_entry<Q>(&self, key: &Q) -> Option<Entry<K, V>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let hash = self.inner.hash(key);
+        self.inner
+            .select_by_borrowed_key(key, hash)
+            .get_entry_with_hash(key, hash)
+    }
+
+    /// Returns a `PinnedEntry` guard holding a clone of the value corresponding to
+    /// the key. The entry will not be evicted or expired while the guard lives. See
+    /// [`Cache::get_pinned`](./struct.Cache.html#method.get_pinned) for details.
+    pub fn get_pinned<Q>(&self, key: &Q) -> Option<PinnedEntry<K, V>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let hash = self.inner.hash(key);
+        self.inner
+            .select_by_borrowed_key(key, hash)
+            .get_pinned_with_hash(key, hash)
+    }
+
     pub fn entry(&self, key: K) -> OwnedKeyEntrySelector<'_, K, V, S>
     where
         K: Hash + Eq,
     {
         let hash = self.inner.hash(&key);
-        let cache = self.inner.select(hash);
+        let cache = self.inner.select_by_key(&key, hash);
         OwnedKeyEntrySelector::new(key, hash, cache)
     }
 
@@ -287,7 +540,7 @@ where
         Q: ToOwned<Owned = K> + Hash + Eq + ?Sized,
     {
         let hash = self.inner.hash(key);
-        let cache = self.inner.select(hash);
+        let cache = self.inner.select_by_owned_ref(key, hash);
         RefKeyEntrySelector::new(key, hash, cache)
     }
 
@@ -325,7 +578,7 @@ where
         let key = Arc::new(key);
         let replace_if = None as Option<fn(&V) -> bool>;
         self.inner
-            .select(hash)
+            .select_by_key(&key, hash)
             .get_or_insert_with_hash_and_fun(key, hash, init, replace_if, false)
             .into_value()
     }
@@ -341,7 +594,7 @@ where
         let hash = self.inner.hash(key);
         let replace_if = None as Option<fn(&V) -> bool>;
         self.inner
-            .select(hash)
+            .select_by_owned_ref(key, hash)
             .get_or_insert_with_hash_by_ref_and_fun(key, hash, init, replace_if, false)
             .into_value()
     }
@@ -363,7 +616,7 @@ where
         let hash = self.inner.hash(&key);
         let key = Arc::new(key);
         self.inner
-            .select(hash)
+            .select_by_key(&key, hash)
             .get_or_insert_with_hash_and_fun(key, hash, init, Some(replace_if), false)
             .into_value()
     }
@@ -388,7 +641,7 @@ where
         let hash = self.inner.hash(&key);
         let key = Arc::new(key);
         self.inner
-            .select(hash)
+            .select_by_key(&key, hash)
             .get_or_optionally_insert_with_hash_and_fun(key, hash, init, false)
             .map(Entry::into_value)
     }
@@ -405,11 +658,30 @@ where
     {
         let hash = self.inner.hash(key);
         self.inner
-            .select(hash)
+            .select_by_owned_ref(key, hash)
             .get_or_optionally_insert_with_hash_by_ref_and_fun(key, hash, init, false)
             .map(Entry::into_value)
     }
 
+    /// Returns a _clone_ of the value corresponding to the key. If the value does
+    /// not exist, calls the loaders registered by
+    /// [`CacheBuilder::loader`][builder-loader] in order, and inserts the value
+    /// returned by one of them. See [`Cache::get_or_load`][get-or-load] for more
+    /// details.
+    ///
+    /// [builder-loader]: ./struct.CacheBuilder.html#method.loader
+    /// [get-or-load]: ./struct.Cache.html#method.get_or_load
+    pub fn get_or_load<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: ToOwned<Owned = K> + Hash + Eq + ?Sized,
+    {
+        let hash = self.inner.hash(key);
+        self.inner
+            .select_by_owned_ref(key, hash)
+            .get_or_load_with_hash(key, hash)
+    }
+
     /// Returns a _clone_ of the value corresponding to the key. If the value does
     /// not exist, evaluates the `init` closure, and inserts the value if `Ok(value)`
     /// was returned. If `Err(_)` was returned from the closure, this method does not
@@ -434,7 +706,7 @@ where
         let hash = self.inner.hash(&key);
         let key = Arc::new(key);
         self.inner
-            .select(hash)
+            .select_by_key(&key, hash)
             .get_or_try_insert_with_hash_and_fun(key, hash, init, false)
             .map(Entry::into_value)
     }
@@ -451,7 +723,7 @@ where
     {
         let hash = self.inner.hash(key);
         self.inner
-            .select(hash)
+            .select_by_owned_ref(key, hash)
             .get_or_try_insert_with_hash_by_ref_and_fun(key, hash, init, false)
             .map(Entry::into_value)
     }
@@ -462,7 +734,56 @@ where
     pub fn insert(&self, key: K, value: V) {
         let hash = self.inner.hash(&key);
         let key = Arc::new(key);
-        self.inner.select(hash).insert_with_hash(key, hash, value);
+        self.inner
+            .select_by_key(&key, hash)
+            .insert_with_hash(key, hash, value);
+    }
+
+    /// Inserts a key-value pair into the cache, with the expiration computed by the
+    /// `expire_after` closure for this insert only. See
+    /// [`Cache::insert_with_expiry`](./struct.Cache.html#method.insert_with_expiry)
+    /// for details.
+    pub fn insert_with_expiry(
+        &self,
+        key: K,
+        value: V,
+        expire_after: impl FnOnce(&K, &V, StdInstant) -> Option<Duration>,
+    ) {
+        let hash = self.inner.hash(&key);
+        let key = Arc::new(key);
+        self.inner
+            .select_by_key(&key, hash)
+            .insert_with_hash_and_expiry(key, hash, value, Some(expire_after));
+    }
+
+    /// Inserts a key-value pair into the cache only if the cache does not have a
+    /// live entry for the key.
+    ///
+    /// Returns `None` if the value was inserted, or `Some` with a clone of the
+    /// existing value if it was kept. See
+    /// [`Cache::insert_if_absent`](./struct.Cache.html#method.insert_if_absent)
+    /// for details.
+    pub fn insert_if_absent(&self, key: K, value: V) -> Option<V> {
+        let hash = self.inner.hash(&key);
+        let key = Arc::new(key);
+        self.inner
+            .select_by_key(&key, hash)
+            .insert_with_hash_unless(key, hash, value, |_| true)
+    }
+
+    /// Inserts a key-value pair into the cache unless the cache has a live entry
+    /// for the key and `predicate` returns `true` for its value.
+    ///
+    /// Returns `None` if the value was inserted, or `Some` with a clone of the
+    /// existing value if it was kept. See
+    /// [`Cache::insert_unless`](./struct.Cache.html#method.insert_unless) for
+    /// details.
+    pub fn insert_unless(&self, key: K, value: V, predicate: impl FnMut(&V) -> bool) -> Option<V> {
+        let hash = self.inner.hash(&key);
+        let key = Arc::new(key);
+        self.inner
+            .select_by_key(&key, hash)
+            .insert_with_hash_unless(key, hash, value, predicate)
     }
 
     /// Discards any cached value for the key.
@@ -479,7 +800,7 @@ where
     {
         let hash = self.inner.hash(key);
         self.inner
-            .select(hash)
+            .select_by_borrowed_key(key, hash)
             .invalidate_with_hash(key, hash, false);
     }
 
@@ -497,10 +818,51 @@ where
     {
         let hash = self.inner.hash(key);
         self.inner
-            .select(hash)
+            .select_by_borrowed_key(key, hash)
             .invalidate_with_hash(key, hash, true)
     }
 
+    /// Pushes out the expiration of the entry for the key by `duration` without
+    /// rewriting its value. See
+    /// [`Cache::extend_ttl`](./struct.Cache.html#method.extend_ttl) for details.
+    pub fn extend_ttl<Q>(&self, key: &Q, duration: Duration) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let hash = self.inner.hash(key);
+        self.inner
+            .select_by_borrowed_key(key, hash)
+            .extend_ttl_with_hash(key, hash, duration)
+    }
+
+    /// Discards the cached value for the key and returns it, only if the
+    /// `predicate` returns `true` for the value. See
+    /// [`Cache::get_and_invalidate_if`](./struct.Cache.html#method.get_and_invalidate_if)
+    /// for details.
+    pub fn get_and_invalidate_if<Q>(&self, key: &Q, predicate: impl FnMut(&V) -> bool) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let hash = self.inner.hash(key);
+        self.inner
+            .select_by_borrowed_key(key, hash)
+            .invalidate_with_hash_if(key, hash, true, Some(predicate))
+    }
+
+    /// Discards the cached value for the key and returns it, if the entry exists
+    /// and is neither expired nor invalidated. See
+    /// [`Cache::invalidate_and_take`](./struct.Cache.html#method.invalidate_and_take)
+    /// for details.
+    pub fn invalidate_and_take<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get_and_invalidate_if(key, |_| true)
+    }
+
     /// Discards all cached values.
     ///
     /// This method returns immediately and a background thread will evict all the
@@ -517,6 +879,43 @@ where
         }
     }
 
+    /// Releases the memory that the segments keep allocated for the entries they
+    /// no longer hold. See
+    /// [`Cache::purge_unused_memory`](./struct.Cache.html#method.purge_unused_memory)
+    /// for details.
+    pub fn purge_unused_memory(&self) {
+        for segment in self.inner.segments.iter() {
+            segment.purge_unused_memory();
+        }
+    }
+
+    /// Temporarily shrinks the effective max capacity of each segment by the
+    /// `fraction` of its max capacity, and evicts entries down to it. See
+    /// [`Cache::shed`](./struct.Cache.html#method.shed) for details.
+    pub fn shed(&self, fraction: f64) {
+        for segment in self.inner.segments.iter() {
+            segment.shed(fraction);
+        }
+    }
+
+    /// Restores the effective max capacity shrunk by the [`shed`](#method.shed)
+    /// method.
+    pub fn restore_capacity(&self) {
+        for segment in self.inner.segments.iter() {
+            segment.restore_capacity();
+        }
+    }
+
+    /// Discards all cached values that have not been inserted or updated within
+    /// the last `age`. See
+    /// [`Cache::invalidate_entries_older_than`](./struct.Cache.html#method.invalidate_entries_older_than)
+    /// for details.
+    pub fn invalidate_entries_older_than(&self, age: Duration) {
+        for segment in self.inner.segments.iter() {
+            segment.invalidate_entries_older_than(age);
+        }
+    }
+
     /// Discards cached values that satisfy a predicate.
     ///
     /// `invalidate_entries_if` takes a closure that returns `true` or `false`. This
@@ -540,17 +939,71 @@ where
     /// popularity estimator of keys so that it retains the client activities of
     /// trying to retrieve an item.
     ///
+    /// The closure is registered to every segment, and each segment applies it to
+    /// its own entries while running its pending tasks. The returned
+    /// [`InvalidationHandle`][invalidation-handle] can be passed to
+    /// [`is_invalidation_finished`](#method.is_invalidation_finished) to check if
+    /// all the segments have done so.
+    ///
     /// [support-invalidation-closures]: ./struct.CacheBuilder.html#method.support_invalidation_closures
     /// [invalidation-disabled-error]: ../enum.PredicateError.html#variant.InvalidationClosuresDisabled
-    pub fn invalidate_entries_if<F>(&self, predicate: F) -> Result<(), PredicateError>
+    /// [invalidation-handle]: ./struct.InvalidationHandle.html
+    pub fn invalidate_entries_if<F>(
+        &self,
+        predicate: F,
+    ) -> Result<InvalidationHandle, PredicateError>
     where
         F: Fn(&K, &V) -> bool + Send + Sync + 'static,
     {
         let pred = Arc::new(predicate);
-        for segment in self.inner.segments.iter() {
-            segment.invalidate_entries_with_arc_fun(Arc::clone(&pred))?;
-        }
-        Ok(())
+        let predicate_ids = self
+            .inner
+            .segments
+            .iter()
+            .map(|segment| segment.invalidate_entries_with_arc_fun(Arc::clone(&pred)))
+            .collect::<Result<_, _>>()?;
+        Ok(InvalidationHandle { predicate_ids })
+    }
+
+    /// Returns `true` if all the segments have applied the predicate of the
+    /// `handle` to their entries, and the entries satisfying it have been
+    /// invalidated.
+    ///
+    /// The predicates are applied by the pending tasks of the segments, so this
+    /// method will keep returning `false` until `run_pending_tasks` has been
+    /// called enough times on the cache (or the background housekeeping has
+    /// done so).
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use moka::sync::SegmentedCache;
+    ///
+    /// let cache = SegmentedCache::builder(4)
+    ///     .max_capacity(100)
+    ///     .support_invalidation_closures()
+    ///     .build();
+    /// cache.insert(1, "alice");
+    /// cache.insert(2, "bob");
+    ///
+    /// let handle = cache
+    ///     .invalidate_entries_if(|_k, &v| v == "alice")
+    ///     .expect("invalidation closures should be enabled");
+    /// assert_eq!(handle.predicate_ids().len(), cache.segment_count());
+    ///
+    /// while !cache.is_invalidation_finished(&handle) {
+    ///     cache.run_pending_tasks();
+    ///     std::thread::sleep(std::time::Duration::from_millis(10));
+    /// }
+    /// assert!(!cache.contains_key(&1));
+    /// assert!(cache.contains_key(&2));
+    /// ```
+    pub fn is_invalidation_finished(&self, handle: &InvalidationHandle) -> bool {
+        self.inner
+            .segments
+            .iter()
+            .zip(handle.predicate_ids.iter())
+            .all(|(segment, id)| !segment.is_invalidation_predicate_registered(id))
     }
 
     /// Creates an iterator visiting all key-value pairs in arbitrary order. The
@@ -612,8 +1065,171 @@ where
         Iter::with_multiple_cache_segments(segments, num_cht_segments)
     }
 
+    /// Creates a parallel iterator visiting all key-value pairs in arbitrary order.
+    /// The iterator element type is `(Arc<K>, V)`, where `V` is a clone of a stored
+    /// value.
+    ///
+    /// The segments of the internal concurrent hash tables of all the cache
+    /// segments are scanned in parallel on the rayon thread pool. See
+    /// [`Cache::par_iter`](./struct.Cache.html#method.par_iter) for more details.
+    ///
+    /// Requires the `rayon` crate feature.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use moka::sync::SegmentedCache;
+    /// use rayon::prelude::*;
+    ///
+    /// let cache = SegmentedCache::new(100, 4);
+    /// cache.insert(1, 10);
+    /// cache.insert(2, 20);
+    ///
+    /// let sum: i32 = cache.par_iter().map(|(_k, v)| v).sum();
+    /// assert_eq!(sum, 30);
+    /// ```
+    ///
+    #[cfg(feature = "rayon")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "rayon")))]
+    pub fn par_iter(&self) -> crate::sync::ParIter<'_, K, V> {
+        let segments = self
+            .inner
+            .segments
+            .iter()
+            .map(|c| c as &(dyn ScanningGet<_, _> + Sync))
+            .collect::<Vec<_>>()
+            .into_boxed_slice();
+        crate::sync::ParIter::new(segments)
+    }
+
+    /// Creates a stream of the length-prefixed records of the entries of all the
+    /// segments. See [`Cache::export_stream`][export-stream-method] for the
+    /// details.
+    ///
+    /// [export-stream-method]: ./struct.Cache.html#method.export_stream
+    pub fn export_stream<F>(&self, encode: F) -> ExportStream<'_, K, V, F>
+    where
+        F: FnMut(&K, &V, &mut Vec<u8>),
+    {
+        ExportStream::new(self.iter(), encode)
+    }
+
+    /// Reads the records written by an `ExportStream` from the `reader` until its
+    /// end, and inserts the entries decoded by the `decode` closure. See
+    /// [`Cache::import_stream`][import-stream-method] for the details.
+    ///
+    /// [import-stream-method]: ./struct.Cache.html#method.import_stream
+    pub fn import_stream<R, F>(&self, reader: R, decode: F) -> io::Result<u64>
+    where
+        R: io::Read,
+        F: FnMut(&[u8]) -> io::Result<(K, V)>,
+    {
+        stream::import_records(reader, decode, |key, value| self.insert(key, value))
+    }
+
+    /// Returns a `HashMap` holding the clones of the keys and values of the entries
+    /// currently in the cache.
+    ///
+    /// This is a convenient way to hand the contents of the cache to a
+    /// serialization layer or to make assertions in tests. The map is built with
+    /// the [`iter`](#method.iter) method, so it has the same guarantees: it is
+    /// weakly consistent, and the entries inserted or removed while building the
+    /// map may or may not be included. Like the `iter` method, it does not update
+    /// the historic popularity estimator or reset idle timers for keys.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use moka::sync::SegmentedCache;
+    /// use std::collections::HashMap;
+    ///
+    /// let cache = SegmentedCache::new(100, 4);
+    /// cache.insert("Julia", 14);
+    /// cache.insert("Alice", 13);
+    ///
+    /// let map = cache.to_hash_map();
+    /// assert_eq!(map, HashMap::from([("Julia", 14), ("Alice", 13)]));
+    /// ```
+    pub fn to_hash_map(&self) -> HashMap<K, V>
+    where
+        K: Clone,
+    {
+        let mut map = HashMap::with_capacity(self.entry_count() as usize);
+        map.extend(self.iter().map(|(k, v)| (K::clone(&k), v)));
+        map
+    }
+
+    /// Removes all entries from the cache, and returns an iterator yielding their
+    /// keys and values in arbitrary order.
+    ///
+    /// See the `drain` method of [`Cache`](./struct.Cache.html) for more details.
+    pub fn drain(&self) -> IntoIter<K, V> {
+        let entries = self
+            .inner
+            .segments
+            .iter()
+            .flat_map(|seg| seg.take_all_entries(true))
+            .collect();
+        IntoIter::new(entries)
+    }
+
+    /// Creates an iterator visiting all key-value pairs in the segment at `index`,
+    /// in arbitrary order. The iterator element type is `(Arc<K>, V)`, where `V` is
+    /// a clone of a stored value.
+    ///
+    /// This is useful to process the cache one segment at a time with bounded
+    /// memory, e.g. when re-sharding the contents. The iterator has the same
+    /// guarantees as the one returned by the [`iter`](#method.iter) method.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is not less than [`segment_count`](#method.segment_count).
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use moka::sync::SegmentedCache;
+    ///
+    /// let cache = SegmentedCache::new(100, 4);
+    /// cache.insert("Julia", 14);
+    /// cache.insert("Mike", 26);
+    ///
+    /// let mut count = 0;
+    /// for i in 0..cache.segment_count() {
+    ///     for (_k, _v) in cache.iter_segment(i) {
+    ///         count += 1;
+    ///     }
+    /// }
+    /// assert_eq!(count, 2);
+    /// ```
+    pub fn iter_segment(&self, index: usize) -> Iter<'_, K, V> {
+        let segment = &self.inner.segments[index];
+        Iter::with_single_cache_segment(segment, segment.num_cht_segments())
+    }
+
     /// Performs any pending maintenance operations needed by the cache.
+    ///
+    /// If the cache is in the NUMA-aware mode, the segments are processed on
+    /// worker threads pinned to their NUMA nodes.
     pub fn run_pending_tasks(&self) {
+        #[cfg(feature = "numa")]
+        if let Some(workers) = &self.inner.numa_workers {
+            let inner = Arc::clone(&self.inner);
+            workers.run_on_nodes(move |node| {
+                let Some(workers) = &inner.numa_workers else {
+                    return;
+                };
+                let topology = workers.topology();
+                inner
+                    .segments
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, _)| topology.node_of_segment(*i) == node)
+                    .for_each(|(_, segment)| segment.run_pending_tasks());
+            });
+            return;
+        }
+
         for segment in self.inner.segments.iter() {
             segment.run_pending_tasks();
         }
@@ -629,6 +1245,32 @@ where
     // }
 }
 
+impl<K, V, S> IntoIterator for SegmentedCache<K, V, S>
+where
+    K: Hash + Eq + Send + Sync + 'static,
+    V: Clone + Send + Sync + 'static,
+    S: BuildHasher + Clone + Send + Sync + 'static,
+{
+    type Item = (Arc<K>, V);
+
+    type IntoIter = IntoIter<K, V>;
+
+    /// Removes all entries from the cache and creates an iterator yielding their
+    /// keys and values in arbitrary order.
+    ///
+    /// See the `into_iter` method of [`Cache`](./struct.Cache.html) for more
+    /// details.
+    fn into_iter(self) -> Self::IntoIter {
+        let entries = self
+            .inner
+            .segments
+            .iter()
+            .flat_map(|seg| seg.take_all_entries(false))
+            .collect();
+        IntoIter::new(entries)
+    }
+}
+
 impl<'a, K, V, S> IntoIterator for &'a SegmentedCache<K, V, S>
 where
     K: Hash + Eq + Send + Sync + 'static,
@@ -717,6 +1359,9 @@ struct Inner<K, V, S> {
     segments: Box<[Cache<K, V, S>]>,
     build_hasher: S,
     segment_shift: u32,
+    segment_selector: Option<SegmentSelector<K>>,
+    #[cfg(feature = "numa")]
+    numa_workers: Option<NodeWorkers>,
 }
 
 impl<K, V, S> Inner<K, V, S>
@@ -738,9 +1383,17 @@ where
         weigher: Option<Weigher<K, V>>,
         eviction_policy: EvictionPolicy,
         eviction_listener: Option<EvictionListener<K, V>>,
+        eviction_listener_causes: RemovalCauses,
         expiration_policy: ExpirationPolicy<K, V>,
         housekeeper_config: HousekeeperConfig,
         invalidator_enabled: bool,
+        numa_aware: bool,
+        segment_selector: Option<SegmentSelector<K>>,
+        loader_chain: Option<Arc<LoaderChain<K, V>>>,
+        load_limiter: Option<Arc<LoadLimiter>>,
+        error_time_to_live: Option<Duration>,
+        interceptors: Option<Arc<InterceptorChain<K, V>>>,
+        policy_config: PolicyConfig<K, V>,
     ) -> Self {
         assert!(num_segments > 0);
 
@@ -750,23 +1403,63 @@ where
             max_capacity.map(|n| (n as f64 / actual_num_segments as f64).ceil() as u64);
         let seg_init_capacity =
             initial_capacity.map(|cap| (cap as f64 / actual_num_segments as f64).ceil() as usize);
+        let mut seg_policy_config = policy_config;
+        seg_policy_config.tenant_quotas = seg_policy_config
+            .tenant_quotas
+            .map(|quotas| quotas.split(actual_num_segments));
+        // Share a single stats counter among the segments, so that a custom counter
+        // sees the events of the whole cache, and `stats` returns its snapshot
+        // as is.
+        if !seg_policy_config.stats_disabled {
+            seg_policy_config
+                .stats_counter
+                .get_or_insert_with(|| Arc::new(DefaultStatsCounter::new()));
+        }
+
+        let new_segment = || {
+            Cache::with_everything(
+                name.clone(),
+                seg_max_capacity,
+                seg_init_capacity,
+                build_hasher.clone(),
+                weigher.clone(),
+                eviction_policy.clone(),
+                eviction_listener.clone(),
+                eviction_listener_causes,
+                expiration_policy.clone(),
+                housekeeper_config.clone(),
+                invalidator_enabled,
+                loader_chain.clone(),
+                load_limiter.clone(),
+                error_time_to_live,
+                interceptors.clone(),
+                seg_policy_config.clone(),
+            )
+        };
+
+        #[cfg(feature = "numa")]
+        let numa_topology = if numa_aware {
+            NumaTopology::detect()
+        } else {
+            None
+        };
+        #[cfg(not(feature = "numa"))]
+        let _ = numa_aware;
+
         // NOTE: We cannot initialize the segments as `vec![cache; actual_num_segments]`
         // because Cache::clone() does not clone its inner but shares the same inner.
+        #[cfg(feature = "numa")]
+        let segments = match &numa_topology {
+            Some(topology) => {
+                Self::new_numa_local_segments(topology, actual_num_segments, new_segment)
+            }
+            None => (0..actual_num_segments).map(|_| new_segment()).collect(),
+        };
+        #[cfg(feature = "numa")]
+        let numa_workers = numa_topology.map(NodeWorkers::spawn);
+        #[cfg(not(feature = "numa"))]
         let segments = (0..actual_num_segments)
-            .map(|_| {
-                Cache::with_everything(
-                    name.clone(),
-                    seg_max_capacity,
-                    seg_init_capacity,
-                    build_hasher.clone(),
-                    weigher.clone(),
-                    eviction_policy.clone(),
-                    eviction_listener.clone(),
-                    expiration_policy.clone(),
-                    housekeeper_config.clone(),
-                    invalidator_enabled,
-                )
-            })
+            .map(|_| new_segment())
             .collect::<Vec<_>>();
 
         Self {
@@ -774,9 +1467,39 @@ where
             segments: segments.into_boxed_slice(),
             build_hasher,
             segment_shift,
+            segment_selector,
+            #[cfg(feature = "numa")]
+            numa_workers,
         }
     }
 
+    /// Creates each segment on a thread pinned to its NUMA node.
+    #[cfg(feature = "numa")]
+    fn new_numa_local_segments(
+        topology: &NumaTopology,
+        num_segments: usize,
+        new_segment: impl Fn() -> Cache<K, V, S> + Sync,
+    ) -> Vec<Cache<K, V, S>> {
+        let mut segments_per_node = topology
+            .map_nodes(|node| {
+                (0..num_segments)
+                    .filter(|&i| topology.node_of_segment(i) == node)
+                    .map(|_| new_segment())
+                    .collect::<Vec<_>>()
+                    .into_iter()
+            })
+            .into_iter()
+            .collect::<Vec<_>>();
+
+        (0..num_segments)
+            .map(|i| {
+                segments_per_node[topology.node_of_segment(i)]
+                    .next()
+                    .expect("Missing a segment")
+            })
+            .collect()
+    }
+
     #[inline]
     fn hash<Q>(&self, key: &Q) -> u64
     where
@@ -794,6 +1517,48 @@ where
         &self.segments[index]
     }
 
+    /// Selects the segment for the owned `key`.
+    #[inline]
+    fn select_by_key(&self, key: &K, hash: u64) -> &Cache<K, V, S> {
+        match &self.segment_selector {
+            Some(selector) => &self.segments[selector(key) % self.segments.len()],
+            None => self.select(hash),
+        }
+    }
+
+    /// Selects the segment for the borrowed `key`. As the segment selector cannot
+    /// be called with a borrowed key, this returns the segment containing the key,
+    /// or the segment from the hash if no segment contains it.
+    #[inline]
+    fn select_by_borrowed_key<Q>(&self, key: &Q, hash: u64) -> &Cache<K, V, S>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        if self.segment_selector.is_none() {
+            return self.select(hash);
+        }
+        self.segments
+            .iter()
+            .find(|segment| segment.contains_key_with_hash(key, hash))
+            .unwrap_or_else(|| self.select(hash))
+    }
+
+    /// Selects the segment for the borrowed `key` that may be inserted to the
+    /// segment. If there is a segment selector, this creates an owned key to call
+    /// it.
+    #[inline]
+    fn select_by_owned_ref<Q>(&self, key: &Q, hash: u64) -> &Cache<K, V, S>
+    where
+        K: Borrow<Q>,
+        Q: ToOwned<Owned = K> + Hash + Eq + ?Sized,
+    {
+        match &self.segment_selector {
+            Some(selector) => &self.segments[selector(&key.to_owned()) % self.segments.len()],
+            None => self.select(hash),
+        }
+    }
+
     #[inline]
     fn segment_index_from_hash(&self, hash: u64) -> usize {
         if self.segment_shift == 64 {
@@ -829,6 +1594,43 @@ mod tests {
         assert_eq!(cache.entry_count(), 0)
     }
 
+    #[cfg(feature = "numa")]
+    #[test]
+    fn numa_aware() {
+        use super::Inner;
+        use crate::{
+            common::numa::{NodeWorkers, NumaTopology},
+            sync::Cache,
+        };
+        use std::collections::hash_map::RandomState;
+
+        // Pretend that the machine has two NUMA nodes.
+        let topology = NumaTopology::with_node_cpus(vec![vec![0], vec![0]]);
+
+        let segments =
+            Inner::<u32, u32, RandomState>::new_numa_local_segments(&topology, 4, || {
+                Cache::new(25)
+            });
+        assert_eq!(segments.len(), 4);
+
+        let mut cache = SegmentedCache::builder(4)
+            .max_capacity(100)
+            .numa_aware()
+            .build();
+        cache.reconfigure_for_testing();
+        Arc::get_mut(&mut cache.inner).unwrap().numa_workers = Some(NodeWorkers::spawn(topology));
+
+        // Make the cache exterior immutable.
+        let cache = cache;
+
+        for i in 0..10 {
+            cache.insert(i, i);
+        }
+        cache.run_pending_tasks();
+        assert_eq!(cache.entry_count(), 10);
+        assert_eq!(cache.get(&3), Some(3));
+    }
+
     #[test]
     fn basic_single_thread() {
         // The following `Vec`s will hold actual and expected notifications.
@@ -1197,8 +1999,10 @@ mod tests {
         assert!(cache.contains_key(&2));
 
         let names = ["alice", "alex"].iter().cloned().collect::<HashSet<_>>();
-        cache.invalidate_entries_if(move |_k, &v| names.contains(v))?;
+        let handle = cache.invalidate_entries_if(move |_k, &v| names.contains(v))?;
         assert_eq!(cache.invalidation_predicate_count(), SEGMENTS);
+        assert_eq!(handle.predicate_ids().len(), SEGMENTS);
+        assert!(!cache.is_invalidation_finished(&handle));
         expected.insert(Arc::new(0), ("alice", RemovalCause::Explicit));
         expected.insert(Arc::new(2), ("alex", RemovalCause::Explicit));
 
@@ -1225,12 +2029,16 @@ mod tests {
 
         assert_eq!(cache.entry_count(), 2);
         assert_eq!(cache.invalidation_predicate_count(), 0);
+        assert!(cache.is_invalidation_finished(&handle));
 
         mock.increment(Duration::from_secs(5)); // 15 secs from the start.
 
-        cache.invalidate_entries_if(|_k, &v| v == "alice")?;
-        cache.invalidate_entries_if(|_k, &v| v == "bob")?;
+        let handle1 = cache.invalidate_entries_if(|_k, &v| v == "alice")?;
+        let handle2 = cache.invalidate_entries_if(|_k, &v| v == "bob")?;
         assert_eq!(cache.invalidation_predicate_count(), SEGMENTS * 2);
+        assert_ne!(handle1, handle2);
+        assert!(!cache.is_invalidation_finished(&handle1));
+        assert!(!cache.is_invalidation_finished(&handle2));
         expected.insert(Arc::new(1), ("bob", RemovalCause::Explicit));
         expected.insert(Arc::new(3), ("alice", RemovalCause::Explicit));
 
@@ -1248,6 +2056,8 @@ mod tests {
 
         assert_eq!(cache.entry_count(), 0);
         assert_eq!(cache.invalidation_predicate_count(), 0);
+        assert!(cache.is_invalidation_finished(&handle1));
+        assert!(cache.is_invalidation_finished(&handle2));
 
         verify_notification_map(&cache, actual, &expected);
 
@@ -1284,6 +2094,181 @@ mod tests {
         assert_eq!(key_set.len(), NUM_KEYS);
     }
 
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_par_iter() {
+        use rayon::prelude::*;
+
+        const NUM_KEYS: usize = 1000;
+
+        fn make_value(key: usize) -> String {
+            format!("val: {key}")
+        }
+
+        let cache = SegmentedCache::builder(4)
+            .max_capacity(NUM_KEYS as u64 * 2)
+            .time_to_idle(Duration::from_secs(10))
+            .build();
+
+        for key in 0..NUM_KEYS {
+            cache.insert(key, make_value(key));
+        }
+        cache.invalidate(&0);
+
+        let entries = cache.par_iter().collect::<Vec<_>>();
+        for (key, value) in &entries {
+            assert_eq!(*value, make_value(**key));
+        }
+
+        // Ensure there are no missing or duplicate keys in the iteration.
+        let key_set = entries
+            .iter()
+            .map(|(k, _)| **k)
+            .collect::<std::collections::HashSet<_>>();
+        assert_eq!(entries.len(), NUM_KEYS - 1);
+        assert_eq!(key_set.len(), NUM_KEYS - 1);
+        assert!(!key_set.contains(&0));
+    }
+
+    #[test]
+    fn time_to_idle_with_builder_clock() {
+        let clock = crate::MockClock::new();
+        let cache = SegmentedCache::builder(4)
+            .max_capacity(100)
+            .time_to_idle(Duration::from_secs(10))
+            .clock(clock.clone())
+            .build();
+
+        // The keys should be spread over the segments.
+        for key in 0..20 {
+            cache.insert(key, key);
+        }
+
+        clock.advance(Duration::from_secs(5));
+        assert_eq!(cache.get(&0), Some(0));
+
+        clock.advance(Duration::from_secs(5));
+        assert_eq!(cache.get(&0), Some(0));
+        for key in 1..20 {
+            assert_eq!(cache.get(&key), None);
+        }
+
+        cache.run_pending_tasks();
+        assert_eq!(cache.entry_count(), 1);
+    }
+
+    #[test]
+    fn test_iter_segment() {
+        const NUM_KEYS: usize = 50;
+
+        fn make_value(key: usize) -> String {
+            format!("val: {key}")
+        }
+
+        let cache = SegmentedCache::builder(3)
+            .max_capacity(100)
+            .time_to_idle(Duration::from_secs(10))
+            .build();
+        assert_eq!(cache.segment_count(), 4);
+
+        for key in 0..NUM_KEYS {
+            cache.insert(key, make_value(key));
+        }
+
+        let mut key_set = std::collections::HashSet::new();
+
+        for i in 0..cache.segment_count() {
+            for (key, value) in cache.iter_segment(i) {
+                assert_eq!(value, make_value(*key));
+                // The key should belong to the segment.
+                let hash = cache.inner.hash(&*key);
+                assert_eq!(cache.inner.segment_index_from_hash(hash), i);
+
+                key_set.insert(*key);
+            }
+        }
+
+        // Ensure there are no missing or duplicate keys in the iteration.
+        assert_eq!(key_set.len(), NUM_KEYS);
+    }
+
+    #[test]
+    #[should_panic(expected = "index out of bounds")]
+    fn test_iter_segment_out_of_bounds() {
+        let cache: SegmentedCache<u32, u32> = SegmentedCache::new(100, 4);
+        let _ = cache.iter_segment(4);
+    }
+
+    #[test]
+    fn segment_selector() {
+        const SEGMENTS: usize = 4;
+
+        // Keys are (tenant, item).
+        let cache = SegmentedCache::builder(SEGMENTS)
+            .max_capacity(100)
+            .segment_selector(|&(tenant, _item): &(u32, u32)| tenant as usize)
+            .build();
+
+        for tenant in 0..8 {
+            for item in 0..5 {
+                cache.insert((tenant, item), tenant * 10 + item);
+            }
+        }
+        assert_eq!(cache.get_with((9, 0), || 90), 90);
+        assert_eq!(cache.get_with_by_ref(&(9, 1), || 91), 91);
+        cache.run_pending_tasks();
+
+        // All keys of a tenant should be in the segment selected by the tenant.
+        for index in 0..SEGMENTS {
+            for (key, _) in cache.iter_segment(index) {
+                assert_eq!(key.0 as usize % SEGMENTS, index);
+            }
+        }
+
+        // The methods taking a borrowed key should find the keys.
+        assert_eq!(cache.get(&(3, 4)), Some(34));
+        assert!(cache.contains_key(&(9, 1)));
+        assert_eq!(cache.remove(&(3, 4)), Some(34));
+        cache.invalidate(&(5, 0));
+        assert!(!cache.contains_key(&(3, 4)));
+        assert_eq!(cache.get(&(5, 0)), None);
+        assert_eq!(cache.get(&(5, 1)), Some(51));
+    }
+
+    #[test]
+    fn test_into_iter() {
+        const NUM_KEYS: usize = 50;
+
+        fn make_value(key: usize) -> String {
+            format!("val: {key}")
+        }
+
+        let cache = SegmentedCache::builder(4)
+            .max_capacity(100)
+            .time_to_idle(Duration::from_secs(10))
+            .build();
+
+        for key in 0..NUM_KEYS {
+            cache.insert(key, make_value(key));
+        }
+
+        let cache2 = cache.clone();
+        let mut key_set = std::collections::HashSet::new();
+
+        for (key, value) in cache {
+            assert_eq!(value, make_value(*key));
+
+            key_set.insert(*key);
+        }
+
+        // Ensure there are no missing or duplicate keys in the iteration.
+        assert_eq!(key_set.len(), NUM_KEYS);
+
+        // Ensure all entries have been removed.
+        assert_eq!(cache2.entry_count(), 0);
+        assert!(cache2.iter().next().is_none());
+    }
+
     /// Runs 16 threads at the same time and ensures no deadlock occurs.
     ///
     /// - Eight of the threads will update key-values in the cache.
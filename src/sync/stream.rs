@@ -0,0 +1,157 @@
+use super::Iter;
+
+use std::{
+    fmt,
+    hash::Hash,
+    io::{self, Read},
+};
+
+/// The size of the length prefix of a record.
+const LEN_PREFIX_SIZE: usize = 4;
+
+/// A stream of the length-prefixed records of the entries of a cache.
+///
+/// An `ExportStream` is created by the [`export_stream`][export-stream-method]
+/// method of `Cache` or `SegmentedCache`. Each record is a 4-byte big-endian
+/// length followed by the bytes written by the `encode` closure for an entry.
+/// The entries are visited in the same way as [`Iter`][iter-struct] does, and
+/// encoded one by one when the stream is read, so the whole contents of the
+/// cache are never held in memory.
+///
+/// The records can be taken either by iterating the stream, which yields one
+/// record at a time, or by reading it as an `std::io::Read`, e.g. with
+/// `std::io::copy` to a socket. Do not mix the two on the same stream, as the
+/// rest of a partially read record is discarded by `next`.
+///
+/// [export-stream-method]: ./struct.Cache.html#method.export_stream
+/// [iter-struct]: ./struct.Iter.html
+pub struct ExportStream<'i, K, V, F> {
+    iter: Iter<'i, K, V>,
+    encode: F,
+    record: Vec<u8>,
+    pos: usize,
+}
+
+impl<'i, K, V, F> ExportStream<'i, K, V, F>
+where
+    K: Eq + Hash + Send + Sync + 'static,
+    V: Clone + Send + Sync + 'static,
+    F: FnMut(&K, &V, &mut Vec<u8>),
+{
+    pub(crate) fn new(iter: Iter<'i, K, V>, encode: F) -> Self {
+        Self {
+            iter,
+            encode,
+            record: Vec::default(),
+            pos: 0,
+        }
+    }
+
+    /// Encodes the next entry to `self.record`. Returns `false` if there is no
+    /// entry left.
+    fn encode_next(&mut self) -> bool {
+        let Some((key, value)) = self.iter.next() else {
+            return false;
+        };
+        self.record.clear();
+        self.record.extend_from_slice(&[0; LEN_PREFIX_SIZE]);
+        (self.encode)(&key, &value, &mut self.record);
+        let len = u32::try_from(self.record.len() - LEN_PREFIX_SIZE)
+            .expect("The encoded entry must be smaller than 4 GiB");
+        self.record[..LEN_PREFIX_SIZE].copy_from_slice(&len.to_be_bytes());
+        self.pos = 0;
+        true
+    }
+}
+
+impl<'i, K, V, F> Iterator for ExportStream<'i, K, V, F>
+where
+    K: Eq + Hash + Send + Sync + 'static,
+    V: Clone + Send + Sync + 'static,
+    F: FnMut(&K, &V, &mut Vec<u8>),
+{
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.encode_next() {
+            Some(std::mem::take(&mut self.record))
+        } else {
+            None
+        }
+    }
+}
+
+impl<'i, K, V, F> Read for ExportStream<'i, K, V, F>
+where
+    K: Eq + Hash + Send + Sync + 'static,
+    V: Clone + Send + Sync + 'static,
+    F: FnMut(&K, &V, &mut Vec<u8>),
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos >= self.record.len() {
+            if !self.encode_next() {
+                return Ok(0);
+            }
+        }
+        let rest = &self.record[self.pos..];
+        let len = rest.len().min(buf.len());
+        buf[..len].copy_from_slice(&rest[..len]);
+        self.pos += len;
+        Ok(len)
+    }
+}
+
+impl<'i, K, V, F> fmt::Debug for ExportStream<'i, K, V, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ExportStream").finish_non_exhaustive()
+    }
+}
+
+/// Reads the length-prefixed records written by an `ExportStream` from the
+/// `reader` until its end, and passes the entries decoded by `decode` to
+/// `insert`. Returns the number of the entries.
+pub(crate) fn import_records<K, V>(
+    mut reader: impl Read,
+    mut decode: impl FnMut(&[u8]) -> io::Result<(K, V)>,
+    mut insert: impl FnMut(K, V),
+) -> io::Result<u64> {
+    let mut payload = Vec::new();
+    let mut count = 0;
+    while let Some(len) = read_len_prefix(&mut reader)? {
+        payload.clear();
+        // Do not allocate the buffer for the length up front, as the length may
+        // be corrupted.
+        let read = (&mut reader).take(len as u64).read_to_end(&mut payload)?;
+        if read < len as usize {
+            return Err(unexpected_eof());
+        }
+        let (key, value) = decode(&payload)?;
+        insert(key, value);
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Reads the length prefix of the next record. Returns `None` if the `reader` is
+/// at its end.
+fn read_len_prefix(reader: &mut impl Read) -> io::Result<Option<u32>> {
+    let mut buf = [0; LEN_PREFIX_SIZE];
+    let mut filled = 0;
+    while filled < LEN_PREFIX_SIZE {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) if filled == 0 => return Ok(None),
+            Ok(0) => return Err(unexpected_eof()),
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(Some(u32::from_be_bytes(buf)))
+}
+
+fn unexpected_eof() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::UnexpectedEof,
+        "The stream ended in the middle of a record",
+    )
+}
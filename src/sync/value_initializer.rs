@@ -1,6 +1,7 @@
-use parking_lot::RwLock;
+use parking_lot::{Condvar, Mutex, RwLock};
 use std::{
     any::{Any, TypeId},
+    cell::RefCell,
     fmt,
     hash::{BuildHasher, Hash},
     sync::Arc,
@@ -12,10 +13,15 @@ use crate::{
     Entry,
 };
 
-use super::{ComputeNone, OptionallyNone};
+use super::{ComputeNone, LoadProgress, LoaderContext, OptionallyNone};
 
 const WAITER_MAP_NUM_SEGMENTS: usize = 64;
 
+thread_local! {
+    /// The addresses of the `LoadLimiter`s the current thread holds a permit of.
+    static HELD_LIMITERS: RefCell<Vec<usize>> = const { RefCell::new(Vec::new()) };
+}
+
 pub(crate) trait GetOrInsert<K, V> {
     /// Gets an entry for the given key _with_ recording the access to the cache
     /// policies.
@@ -60,39 +66,166 @@ pub(crate) enum InitResult<V, E> {
     InitErr(Arc<E>),
 }
 
+/// Bounds the number of `init` closures evaluated at the same time across all keys.
+/// It is shared by all segments of a `SegmentedCache`.
+pub(crate) struct LoadLimiter {
+    max_concurrent_loads: usize,
+    available_permits: Mutex<usize>,
+    released: Condvar,
+}
+
+impl LoadLimiter {
+    /// # Panics
+    ///
+    /// Panics if `max_concurrent_loads` is zero.
+    pub(crate) fn new(max_concurrent_loads: usize) -> Self {
+        assert!(
+            max_concurrent_loads > 0,
+            "max_concurrent_loads must not be zero"
+        );
+        Self {
+            max_concurrent_loads,
+            available_permits: Mutex::new(max_concurrent_loads),
+            released: Condvar::new(),
+        }
+    }
+
+    pub(crate) fn max_concurrent_loads(&self) -> usize {
+        self.max_concurrent_loads
+    }
+
+    /// Blocks until a permit becomes available.
+    ///
+    /// If the current thread already holds a permit, i.e. this is a nested load
+    /// from an `init` closure, returns at once without taking another one.
+    /// Otherwise, a nested load would wait for its own caller to release the
+    /// permit, and deadlock when all permits are held this way.
+    fn acquire(&self) -> LoadPermit<'_> {
+        let addr = self as *const Self as usize;
+        if HELD_LIMITERS.with(|held| held.borrow().contains(&addr)) {
+            return LoadPermit {
+                limiter: self,
+                nested: true,
+            };
+        }
+
+        let mut permits = self.available_permits.lock();
+        while *permits == 0 {
+            self.released.wait(&mut permits);
+        }
+        *permits -= 1;
+        drop(permits);
+
+        HELD_LIMITERS.with(|held| held.borrow_mut().push(addr));
+        LoadPermit {
+            limiter: self,
+            nested: false,
+        }
+    }
+}
+
+/// Returns the permit to the `LoadLimiter` when dropped, unless it was given to a
+/// nested load.
+struct LoadPermit<'a> {
+    limiter: &'a LoadLimiter,
+    nested: bool,
+}
+
+impl Drop for LoadPermit<'_> {
+    fn drop(&mut self) {
+        if self.nested {
+            return;
+        }
+        let addr = self.limiter as *const LoadLimiter as usize;
+        HELD_LIMITERS.with(|held| held.borrow_mut().retain(|a| *a != addr));
+        *self.limiter.available_permits.lock() += 1;
+        self.limiter.released.notify_one();
+    }
+}
+
 pub(crate) struct ValueInitializer<K, V, S> {
     // TypeId is the type ID of the concrete error type of generic type E in the
     // try_get_with method. We use the type ID as a part of the key to ensure that
     // we can always downcast the trait object ErrorObject (in Waiter<V>) into
     // its concrete type.
     waiters: crate::cht::SegmentedHashMap<(Arc<K>, TypeId), Waiter<V>, S>,
+    // The contexts of the in-flight `init` closures, or `None` if the loads are
+    // not tracked. They are stored with the hash of the key computed by the cache,
+    // so that they can be looked up by a borrowed key.
+    loads: Option<crate::cht::SegmentedHashMap<Arc<K>, Arc<LoaderContext>, S>>,
+    load_limiter: Option<Arc<LoadLimiter>>,
 }
 
 impl<K, V, S> ValueInitializer<K, V, S>
 where
     K: Eq + Hash,
     V: Clone,
-    S: BuildHasher,
+    S: BuildHasher + Clone,
 {
-    pub(crate) fn with_hasher(hasher: S) -> Self {
+    pub(crate) fn new(
+        hasher: S,
+        load_limiter: Option<Arc<LoadLimiter>>,
+        tracks_loads: bool,
+    ) -> Self {
+        let loads = tracks_loads.then(|| {
+            crate::cht::SegmentedHashMap::with_num_segments_and_hasher(
+                WAITER_MAP_NUM_SEGMENTS,
+                hasher.clone(),
+            )
+        });
         Self {
             waiters: crate::cht::SegmentedHashMap::with_num_segments_and_hasher(
                 WAITER_MAP_NUM_SEGMENTS,
                 hasher,
             ),
+            loads,
+            load_limiter,
         }
     }
 
+    /// Registers an in-flight load for the key, unless another one is already in
+    /// flight or the loads are not tracked. The returned context must be passed to
+    /// `finish_load` when the load is done.
+    pub(crate) fn start_load(&self, key: &Arc<K>, hash: u64) -> Option<Arc<LoaderContext>> {
+        let ctx = Arc::new(LoaderContext::new());
+        self.loads
+            .as_ref()?
+            .insert_if_not_present(Arc::clone(key), hash, Arc::clone(&ctx))
+            .is_none()
+            .then_some(ctx)
+    }
+
+    pub(crate) fn finish_load(&self, key: &Arc<K>, hash: u64, ctx: &Arc<LoaderContext>) {
+        if let Some(loads) = &self.loads {
+            loads.remove_if(hash, |k| k == key, |_, c| Arc::ptr_eq(c, ctx));
+        }
+    }
+
+    /// Returns the progress of the in-flight `init` closure for the key, if any.
+    /// `hash` must be the hash of the key computed by the cache.
+    pub(crate) fn load_progress<Q>(&self, key: &Q, hash: u64) -> Option<LoadProgress>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: Eq + ?Sized,
+    {
+        self.loads
+            .as_ref()?
+            .get(hash, |k| (**k).borrow() == key)
+            .map(|ctx| ctx.progress())
+    }
+
     /// # Panics
     /// Panics if the `init` closure has been panicked.
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn try_init_or_read<O, E>(
         &self,
         key: &Arc<K>,
+        hash: u64,
         type_id: TypeId,
         // Closure to get an existing value from cache.
         mut get: impl FnMut() -> Option<V>,
         // Closure to initialize a new value.
-        init: impl FnOnce() -> O,
+        init: impl FnOnce(&LoaderContext) -> O,
         // Closure to insert a new value into cache.
         mut insert: impl FnMut(V),
         // Function to convert a value O, returned from the init future, into
@@ -157,9 +290,23 @@ where
         }
 
         // The value still does note exist. Let's evaluate the init
-        // closure. Catching panic is safe here as we do not try to
-        // evaluate the closure again.
-        match catch_unwind(AssertUnwindSafe(init)) {
+        // closure, after getting a permit if the number of concurrent
+        // loads is limited. Catching panic is safe here as we do not try
+        // to evaluate the closure again.
+        let permit = self.load_limiter.as_deref().map(LoadLimiter::acquire);
+        // Another `init` closure for the key with a different return type, or a
+        // refresh, may be in flight. In that case, only that one is visible, and
+        // our `init` closure gets a context that is not visible to anyone.
+        let ctx = self.start_load(key, hash);
+        let init_res = match &ctx {
+            Some(ctx) => catch_unwind(AssertUnwindSafe(|| init(ctx))),
+            None => catch_unwind(AssertUnwindSafe(|| init(&LoaderContext::new()))),
+        };
+        if let Some(ctx) = &ctx {
+            self.finish_load(key, hash, ctx);
+        }
+        drop(permit);
+        match init_res {
             // Evaluated.
             Ok(value) => {
                 let init_res = match post_init(value) {
@@ -188,6 +335,147 @@ where
         // The write lock will be unlocked here.
     }
 
+    /// Similar to `try_init_or_read`, but for many keys at once. The `init` closure
+    /// is called once with the keys that are not being initialized by other calls,
+    /// and returns their values in the same order. The values of the other keys are
+    /// read from the waiters of those calls.
+    ///
+    /// The waiters are shared with the `optionally_get_with` method of cache.
+    /// Returns the values in the order of the `keys`, or `None` for the keys that
+    /// the `init` closure did not return a value for.
+    ///
+    /// # Panics
+    /// Panics if the `init` closure has been panicked.
+    pub(crate) fn init_all_or_read(
+        &self,
+        keys: &[(Arc<K>, u64)],
+        // Closure to get an existing value from cache.
+        mut get: impl FnMut(&Arc<K>, u64) -> Option<V>,
+        // Closure to initialize the values for the keys.
+        mut init: impl FnMut(&[&Arc<K>]) -> Vec<Option<V>>,
+        // Closure to insert a new value into cache.
+        mut insert: impl FnMut(&Arc<K>, u64, V),
+    ) -> Vec<Option<V>> {
+        use std::panic::{catch_unwind, resume_unwind, AssertUnwindSafe};
+
+        const MAX_RETRIES: usize = 200;
+        let mut retries = 0;
+
+        let type_id = Self::type_id_for_optionally_get_with();
+        let mut values = vec![None; keys.len()];
+        // The indices of the keys whose values are not resolved yet.
+        let mut pending = (0..keys.len()).collect::<Vec<_>>();
+
+        while !pending.is_empty() {
+            let w_keys = pending
+                .iter()
+                .map(|&i| self.waiter_key_hash(&keys[i].0, type_id))
+                .collect::<Vec<_>>();
+            let waiters = pending
+                .iter()
+                .map(|_| TrioArc::new(RwLock::new(WaiterValue::Computing)))
+                .collect::<Vec<Waiter<V>>>();
+
+            // Insert our waiters. We do not wait for the existing waiters of other
+            // calls until our waiters are resolved, so that overlapping batches do
+            // not deadlock.
+            let mut ours = Vec::new();
+            let mut theirs = Vec::new();
+            for (j, ((w_key, w_hash), waiter)) in w_keys.iter().zip(&waiters).enumerate() {
+                let lock = waiter.write();
+                match self.try_insert_waiter(w_key.clone(), *w_hash, waiter) {
+                    None => ours.push((j, lock)),
+                    Some(existing_waiter) => theirs.push((j, existing_waiter)),
+                }
+            }
+
+            // Check if the values have already been inserted by other threads.
+            let mut to_init = Vec::new();
+            for (j, mut lock) in ours {
+                let (key, hash) = &keys[pending[j]];
+                if let Some(value) = get(key, *hash) {
+                    *lock = WaiterValue::Ready(Ok(value.clone()));
+                    values[pending[j]] = Some(value);
+                    let (w_key, w_hash) = &w_keys[j];
+                    self.remove_waiter(w_key.clone(), *w_hash);
+                } else {
+                    to_init.push((j, lock));
+                }
+            }
+
+            if !to_init.is_empty() {
+                let init_keys = to_init
+                    .iter()
+                    .map(|(j, _)| &keys[pending[*j]].0)
+                    .collect::<Vec<_>>();
+                let permit = self.load_limiter.as_deref().map(LoadLimiter::acquire);
+                let init_res = catch_unwind(AssertUnwindSafe(|| init(&init_keys)));
+                drop(permit);
+
+                match init_res {
+                    // Evaluated.
+                    Ok(init_values) => {
+                        debug_assert_eq!(init_values.len(), to_init.len());
+                        for ((j, mut lock), value) in to_init.into_iter().zip(init_values) {
+                            let (key, hash) = &keys[pending[j]];
+                            *lock = match &value {
+                                Some(v) => {
+                                    insert(key, *hash, v.clone());
+                                    WaiterValue::Ready(Ok(v.clone()))
+                                }
+                                None => {
+                                    let err: ErrorObject = Arc::new(Arc::new(OptionallyNone));
+                                    WaiterValue::Ready(Err(err))
+                                }
+                            };
+                            values[pending[j]] = value;
+                            let (w_key, w_hash) = &w_keys[j];
+                            self.remove_waiter(w_key.clone(), *w_hash);
+                        }
+                    }
+                    // Panicked.
+                    Err(payload) => {
+                        for (j, mut lock) in to_init {
+                            *lock = WaiterValue::InitClosurePanicked;
+                            // Remove the waiter so that others can retry.
+                            let (w_key, w_hash) = &w_keys[j];
+                            self.remove_waiter(w_key.clone(), *w_hash);
+                        }
+                        resume_unwind(payload);
+                    }
+                }
+            }
+
+            // Wait for the values of the other calls.
+            let mut retry = Vec::new();
+            for (j, existing_waiter) in theirs {
+                match &*existing_waiter.read() {
+                    WaiterValue::Ready(Ok(value)) => values[pending[j]] = Some(value.clone()),
+                    WaiterValue::Ready(Err(_)) => (),
+                    // Somebody else's init closure has been panicked.
+                    WaiterValue::InitClosurePanicked => retry.push(pending[j]),
+                    // Unexpected state.
+                    s @ (WaiterValue::Computing | WaiterValue::ReadyNone) => panic!(
+                        "Got unexpected state `{s:?}` after resolving `init` closure. \
+                        This might be a bug in Moka"
+                    ),
+                }
+            }
+
+            if !retry.is_empty() {
+                retries += 1;
+                assert!(
+                    retries < MAX_RETRIES,
+                    "Too many retries. Tried to read the return value from the `init` \
+                    closure but failed {retries} times. Maybe the `init` kept panicking?"
+                );
+            }
+            pending = retry;
+        }
+
+        values
+    }
+
     /// # Panics
     /// Panics if the `init` closure has been panicked.
     pub(crate) fn try_compute<'a, C, F, O, E>(
@@ -3,12 +3,54 @@ pub(crate) mod iter;
 #[cfg(feature = "sync")]
 pub(crate) mod base_cache;
 
+#[cfg(feature = "sync")]
+pub(crate) mod class_stats;
+
+#[cfg(feature = "sync")]
+pub(crate) mod event_bus;
+
+#[cfg(feature = "sync")]
+pub(crate) mod ghost_list;
+
+#[cfg(feature = "sync")]
+pub(crate) mod hit_ratio;
+
+#[cfg(feature = "sync")]
+pub(crate) mod hot_key;
+
+#[cfg(all(feature = "sync", debug_assertions))]
+pub(crate) mod invariants;
+
 #[cfg(feature = "sync")]
 mod invalidator;
 
 #[cfg(feature = "sync")]
 mod key_lock;
 
+#[cfg(feature = "sync")]
+pub(crate) mod lifetime;
+
+#[cfg(feature = "sync")]
+pub(crate) mod pinned;
+
+#[cfg(feature = "sync")]
+pub(crate) mod policy_config;
+
+#[cfg(feature = "sync")]
+pub(crate) mod read_sampler;
+
+#[cfg(feature = "sync")]
+mod segmented_sketch;
+
+#[cfg(feature = "sync")]
+pub(crate) mod stats;
+
+#[cfg(feature = "sync")]
+pub(crate) mod tenant;
+
+#[cfg(feature = "sync")]
+pub(crate) mod victim;
+
 /// The type of the unique ID to identify a predicate used by
 /// [`Cache::invalidate_entries_if`][invalidate-if] method.
 ///
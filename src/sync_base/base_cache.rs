@@ -1,7 +1,20 @@
 use super::{
+    class_stats::{ClassStats, ClassStatsRecorder},
+    event_bus::{CacheEvent, EventBus, EventReceiver},
+    ghost_list::{GhostList, GhostListStats},
+    hit_ratio::{HitRatioAnalyzer, HitRatioReport},
+    hot_key::HotKeyConfig,
     invalidator::{GetOrRemoveEntry, Invalidator, KeyDateLite, PredicateFun},
     iter::ScanningGet,
     key_lock::{KeyLock, KeyLockMap},
+    lifetime::EntryLifetimes,
+    pinned::PinnedEntry,
+    policy_config::PolicyConfig,
+    read_sampler::{ReadSampler, ReadSamplingStats},
+    segmented_sketch::SegmentedFrequencySketch,
+    stats::{CacheStats, DefaultStatsCounter, StatsCounterArc},
+    tenant::TenantQuotas,
+    victim::{VictimDecision, VictimSelector},
     PredicateId,
 };
 
@@ -11,36 +24,48 @@ use crate::{
         concurrent::{
             atomic_time::AtomicInstant,
             constants::{
-                READ_LOG_CH_SIZE, READ_LOG_FLUSH_POINT, WRITE_LOG_CH_SIZE, WRITE_LOG_FLUSH_POINT,
+                AUTO_SHRINK_FACTOR, MAX_SAMPLE_ATTEMPTS_PER_KEY, MAX_TENANT_WALK, READ_LOG_CH_SIZE,
+                READ_LOG_FLUSH_POINT, SECOND_CHANCE_FREQUENCY_BOOST, WRITE_LOG_CH_SIZE,
+                WRITE_LOG_FLUSH_POINT,
             },
             deques::Deques,
             entry_info::EntryInfo,
             housekeeper::{Housekeeper, InnerSync},
-            AccessTime, KeyHash, KeyHashDate, KvEntry, OldEntryInfo, ReadOp, ValueEntry, Weigher,
-            WriteOp,
+            AccessTime, DeqNodes, KeyHash, KeyHashDate, KvEntry, OldEntryInfo, ReadOp, ValueEntry,
+            Weigher, WriteOp,
         },
         deque::{DeqNode, Deque},
-        frequency_sketch::FrequencySketch,
+        error_cache::ErrorCache,
+        sys_memory::MemoryPressureWatcher,
         time::{CheckedTimeOps, Clock, Instant},
         timer_wheel::{ReschedulingResult, TimerWheel},
+        zeroizer::ValueZeroizer,
         CacheRegion, HousekeeperConfig,
     },
-    notification::{notifier::RemovalNotifier, EvictionListener, RemovalCause},
-    policy::{EvictionPolicy, EvictionPolicyConfig, ExpirationPolicy},
-    Entry, Expiry, Policy, PredicateError,
+    notification::{
+        notifier::RemovalNotifier, EvictionListener, RemovalCause, RemovalCauses, RemovalMetadata,
+    },
+    policy::{self, EntryStats, EvictionPolicy, EvictionPolicyConfig, ExpirationPolicy},
+    sync::{cache::Config, interceptor::InterceptorChain},
+    weigher::WeighContext,
+    Entry, EntryMetadata, Expiry, Policy, PredicateError,
 };
 
+#[cfg(debug_assertions)]
+use super::invariants::{InvariantReport, InvariantViolation};
+
 use crossbeam_channel::{Receiver, Sender, TrySendError};
 use crossbeam_utils::atomic::AtomicCell;
 use parking_lot::{Mutex, RwLock};
 use smallvec::SmallVec;
 use std::{
-    borrow::Borrow,
-    collections::hash_map::RandomState,
+    borrow::{Borrow, Cow},
+    collections::{hash_map::RandomState, HashMap},
     hash::{BuildHasher, Hash, Hasher},
+    ptr::NonNull,
     rc::Rc,
     sync::{
-        atomic::{AtomicBool, AtomicU8, Ordering},
+        atomic::{AtomicBool, AtomicU64, AtomicU8, Ordering},
         Arc,
     },
     time::{Duration, Instant as StdInstant},
@@ -54,6 +79,9 @@ pub(crate) struct BaseCache<K, V, S = RandomState> {
     read_op_ch: Sender<ReadOp<K, V>>,
     pub(crate) write_op_ch: Sender<WriteOp<K, V>>,
     pub(crate) housekeeper: Option<HouseKeeperArc>,
+    /// The interceptors of the cache. They are held here rather than in the inner,
+    /// so that a cache can copy its entries without them (see `without_interceptors`).
+    pub(crate) interceptors: Option<Arc<InterceptorChain<K, V>>>,
 }
 
 impl<K, V, S> Clone for BaseCache<K, V, S> {
@@ -67,10 +95,51 @@ impl<K, V, S> Clone for BaseCache<K, V, S> {
             read_op_ch: self.read_op_ch.clone(),
             write_op_ch: self.write_op_ch.clone(),
             housekeeper: self.housekeeper.clone(),
+            interceptors: self.interceptors.clone(),
         }
     }
 }
 
+/// The weak references to the cache, held by the thread spawned by
+/// `spawn_eager_expirer`.
+struct ExpirerHandle<K, V, S> {
+    inner: std::sync::Weak<Inner<K, V, S>>,
+    housekeeper: std::sync::Weak<Housekeeper>,
+}
+
+// TODO: https://github.com/moka-rs/moka/issues/54
+// `Inner` is not `Send` without the `future` feature because its deques and timer
+// wheel have `NonNull` pointers. They are accessed only under their locks, which is
+// what makes `sync::Cache` `Send` and `Sync` too.
+#[allow(clippy::non_send_fields_in_send_ty)]
+unsafe impl<K, V, S> Send for ExpirerHandle<K, V, S>
+where
+    K: Send + Sync,
+    V: Send + Sync,
+    S: Send + Sync,
+{
+}
+
+impl<K, V, S> ExpirerHandle<K, V, S>
+where
+    K: Hash + Eq + Send + Sync + 'static,
+    V: Clone + Send + Sync + 'static,
+    S: BuildHasher + Clone + Send + Sync + 'static,
+{
+    /// Sleeps for the `interval` and runs the pending tasks. Returns `false`
+    /// without running them if the cache has been dropped.
+    fn run_pending_tasks_after(&self, interval: Duration) -> bool {
+        std::thread::sleep(interval);
+        let (Some(hk), Some(inner)) = (self.housekeeper.upgrade(), self.inner.upgrade()) else {
+            return false;
+        };
+        hk.run_pending_tasks(&*inner);
+        // The housekeeper needs to be dropped before the inner is dropped.
+        std::mem::drop(hk);
+        true
+    }
+}
+
 impl<K, V, S> Drop for BaseCache<K, V, S> {
     fn drop(&mut self) {
         // The housekeeper needs to be dropped before the inner is dropped.
@@ -95,10 +164,110 @@ impl<K, V, S> BaseCache<K, V, S> {
         self.inner.weighted_size()
     }
 
+    /// Returns the configuration the cache was built with, or `None` if the base
+    /// cache was not built by a `Cache`.
+    pub(crate) fn config(&self) -> Option<&Config<K, V, S>> {
+        self.inner.config.as_ref()
+    }
+
+    /// Returns the report of the hit ratio analyzer, or `None` if it is not
+    /// enabled.
+    pub(crate) fn hit_ratio_report(&self) -> Option<HitRatioReport> {
+        self.inner
+            .hit_ratio_analyzer
+            .as_ref()
+            .map(HitRatioAnalyzer::report)
+    }
+
+    /// Returns `true` if the key with the `hash` was recently evicted by the size
+    /// constraint. Always returns `false` if the ghost list is not enabled.
+    pub(crate) fn was_recently_evicted_with_hash(&self, hash: u64) -> bool {
+        self.inner.was_recently_evicted(hash)
+    }
+
+    pub(crate) fn ghost_list_stats(&self) -> Option<GhostListStats> {
+        self.inner.ghost_list.as_ref().map(GhostList::stats)
+    }
+
+    pub(crate) fn class_stats(&self) -> Option<HashMap<&'static str, ClassStats>> {
+        self.inner
+            .class_stats
+            .as_ref()
+            .map(ClassStatsRecorder::stats)
+    }
+
+    #[cfg(feature = "entry-stats")]
+    pub(crate) fn entry_lifetime_histogram(&self) -> Option<super::lifetime::LifetimeHistogram> {
+        self.inner
+            .entry_lifetimes
+            .as_ref()
+            .map(EntryLifetimes::histogram)
+    }
+
+    pub(crate) fn read_sampling_stats(&self) -> Option<ReadSamplingStats> {
+        self.inner.read_sampler.as_ref().map(ReadSampler::stats)
+    }
+
+    pub(crate) fn stats(&self) -> Option<CacheStats> {
+        let degraded_op_count = self.inner.degraded_op_count.load(Ordering::Relaxed);
+        self.inner
+            .stats
+            .as_ref()
+            .map(|stats| stats.snapshot().with_degraded_op_count(degraded_op_count))
+    }
+
+    pub(crate) fn record_load_success(&self) {
+        if let Some(stats) = &self.inner.stats {
+            stats.record_load_success();
+        }
+    }
+
+    pub(crate) fn record_load_failure(&self) {
+        if let Some(stats) = &self.inner.stats {
+            stats.record_load_failure();
+        }
+    }
+
+    pub(crate) fn is_degraded(&self) -> bool {
+        self.inner.is_degraded()
+    }
+
+    /// Stops recording the reads and writes as the channels to the housekeeper
+    /// are disconnected, e.g. in a race on shutdown. The cache keeps working
+    /// without the policies instead of panicking. Counts the operation that could
+    /// not be recorded.
+    pub(crate) fn degrade(&self) {
+        self.inner.degraded.store(true, Ordering::Relaxed);
+        self.inner.degraded_op_count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Returns the weighted size of the `tenant`, or `None` if the cache has no
+    /// such tenant.
+    pub(crate) fn tenant_weighted_size(&self, tenant: usize) -> Option<u64> {
+        self.inner
+            .tenant_quotas
+            .as_ref()
+            .and_then(|quotas| quotas.weighted_size(tenant))
+    }
+
+    /// Returns the number of the entries of the `tenant`, or `None` if the cache has
+    /// no such tenant.
+    pub(crate) fn tenant_entry_count(&self, tenant: usize) -> Option<u64> {
+        self.inner
+            .tenant_quotas
+            .as_ref()
+            .and_then(|quotas| quotas.entry_count(tenant))
+    }
+
     pub(crate) fn is_map_disabled(&self) -> bool {
         self.inner.max_capacity == Some(0)
     }
 
+    #[cfg(feature = "entry-versions")]
+    pub(crate) fn is_entry_versions_enabled(&self) -> bool {
+        self.inner.last_version.is_some()
+    }
+
     #[inline]
     pub(crate) fn is_removal_notifier_enabled(&self) -> bool {
         self.inner.is_removal_notifier_enabled()
@@ -116,6 +285,35 @@ impl<K, V, S> BaseCache<K, V, S> {
     {
         self.inner.notify_invalidate(key, entry);
     }
+
+    /// Notifies the eviction listener of an entry that was not stored because the
+    /// max capacity of the cache is zero, as if the entry was evicted right after
+    /// the insertion.
+    pub(crate) fn notify_rejected(&self, key: Arc<K>, value: V)
+    where
+        K: Send + Sync + 'static,
+        V: Clone + Send + Sync + 'static,
+    {
+        self.inner.event_bus.publish(|| CacheEvent::Evict {
+            key: Arc::clone(&key),
+            value: value.clone(),
+            cause: RemovalCause::Size,
+        });
+        if let Some(notifier) = self
+            .inner
+            .removal_notifier
+            .as_ref()
+            .filter(|n| n.is_notified(RemovalCause::Size))
+        {
+            let weight = self
+                .inner
+                .weigher
+                .as_ref()
+                .map_or(1, |w| w(&key, &value, &WeighContext::insert()));
+            let metadata = RemovalMetadata::new(None, None, weight);
+            notifier.notify(key, value, RemovalCause::Size, metadata);
+        }
+    }
 }
 
 impl<K, V, S> BaseCache<K, V, S>
@@ -144,9 +342,13 @@ where
         weigher: Option<Weigher<K, V>>,
         eviction_policy: EvictionPolicy,
         eviction_listener: Option<EvictionListener<K, V>>,
+        eviction_listener_causes: RemovalCauses,
         expiration_policy: ExpirationPolicy<K, V>,
         housekeeper_config: HousekeeperConfig,
         invalidator_enabled: bool,
+        mut policy_config: PolicyConfig<K, V>,
+        config: Option<Config<K, V, S>>,
+        error_cache: Option<Arc<ErrorCache<K, S>>>,
     ) -> Self {
         let (r_size, w_size) = if max_capacity == Some(0) {
             (0, 0)
@@ -154,6 +356,24 @@ where
             (READ_LOG_CH_SIZE, WRITE_LOG_CH_SIZE)
         };
         let is_eviction_listener_enabled = eviction_listener.is_some();
+        // A cache storing nothing has nothing to expire.
+        let eager_expiration_interval = housekeeper_config.eager_expiration_interval.filter(|_| {
+            max_capacity != Some(0)
+                && (expiration_policy.time_to_live().is_some()
+                    || expiration_policy.time_to_idle().is_some()
+                    || expiration_policy.expiry().is_some())
+        });
+
+        if policy_config.stats_disabled {
+            policy_config.stats_counter = None;
+        } else {
+            policy_config
+                .stats_counter
+                .get_or_insert_with(|| Arc::new(DefaultStatsCounter::new()));
+        }
+        if housekeeper_config.manual_maintenance {
+            policy_config.memory_pressure_shed_fraction = None;
+        }
 
         let (r_snd, r_rcv) = crossbeam_channel::bounded(r_size);
         let (w_snd, w_rcv) = crossbeam_channel::bounded(w_size);
@@ -166,23 +386,60 @@ where
             weigher,
             eviction_policy,
             eviction_listener,
+            eviction_listener_causes,
             r_rcv,
             w_rcv,
             expiration_policy,
             invalidator_enabled,
+            policy_config,
+            config,
+            error_cache,
         ));
+        let now = inner.current_time_from_expiration_clock();
+        let housekeeper = Arc::new(Housekeeper::new(
+            is_eviction_listener_enabled,
+            housekeeper_config,
+            now,
+        ));
+        if let Some(interval) = eager_expiration_interval {
+            Self::spawn_eager_expirer(&inner, &housekeeper, interval);
+        }
 
         Self {
             inner,
             read_op_ch: r_snd,
             write_op_ch: w_snd,
-            housekeeper: Some(Arc::new(Housekeeper::new(
-                is_eviction_listener_enabled,
-                housekeeper_config,
-            ))),
+            housekeeper: Some(housekeeper),
+            interceptors: None,
         }
     }
 
+    /// Spawns a thread that runs the pending tasks at the given interval, so that
+    /// the expired entries are removed (and the eviction listener is notified)
+    /// without waiting for the cache operations to trigger the run.
+    ///
+    /// The thread holds only weak references between the runs, and exits at the
+    /// first tick after the cache is dropped.
+    fn spawn_eager_expirer(
+        inner: &Arc<Inner<K, V, S>>,
+        housekeeper: &HouseKeeperArc,
+        interval: Duration,
+    ) {
+        let handle = ExpirerHandle {
+            inner: Arc::downgrade(inner),
+            housekeeper: Arc::downgrade(housekeeper),
+        };
+        // If the thread cannot be spawned, the expired entries are still removed
+        // by the runs triggered by the cache operations.
+        let _ = std::thread::Builder::new()
+            .name("moka-expirer".into())
+            .spawn(move || {
+                // Move the whole handle into the closure, not its fields.
+                let handle = handle;
+                while handle.run_pending_tasks_after(interval) {}
+            });
+    }
+
     #[inline]
     pub(crate) fn hash<Q>(&self, key: &Q) -> u64
     where
@@ -218,20 +475,21 @@ where
         Q: Hash + Eq + ?Sized,
     {
         // Define a closure to record a read op.
-        let record = |op, now| {
-            self.record_read_op(op, now)
-                .expect("Failed to record a get op");
-        };
+        let record = |op, now| self.record_read_op(op, now, |_| ());
         let ignore_if = None as Option<&mut fn(&V) -> bool>;
         self.do_get_with_hash(key, hash, record, ignore_if, need_key)
     }
 
+    /// Similar to `get_with_hash`, but ignores the entry whose value satisfies
+    /// `ignore_if`. On a miss, `record_class_miss` is called to count the miss on
+    /// the class of the key, as only the caller may have the owned key.
     pub(crate) fn get_with_hash_and_ignore_if<Q, I>(
         &self,
         key: &Q,
         hash: u64,
         ignore_if: Option<&mut I>,
         need_key: bool,
+        record_class_miss: impl Fn(&ClassStatsRecorder<K>),
     ) -> Option<Entry<K, V>>
     where
         K: Borrow<Q>,
@@ -239,10 +497,7 @@ where
         I: FnMut(&V) -> bool,
     {
         // Define a closure to record a read op.
-        let record = |op, now| {
-            self.record_read_op(op, now)
-                .expect("Failed to record a get op");
-        };
+        let record = |op, now| self.record_read_op(op, now, &record_class_miss);
         self.do_get_with_hash(key, hash, record, ignore_if, need_key)
     }
 
@@ -263,14 +518,245 @@ where
             .map(Entry::into_value)
     }
 
+    /// Similar to `get_with_hash`, but instead of cloning the value, returns the
+    /// result of the `map` closure called with a reference to the value.
+    pub(crate) fn get_with_hash_and_map<Q, T>(
+        &self,
+        key: &Q,
+        hash: u64,
+        map: impl FnOnce(&V) -> T,
+    ) -> Option<T>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        // Define a closure to record a read op.
+        let record = |op, now| self.record_read_op(op, now, |_| ());
+        let ignore_if = None as Option<&mut fn(&V) -> bool>;
+        self.do_get_with_hash_and_map(key, hash, record, ignore_if, false, |_, _, value, _| {
+            map(&value)
+        })
+    }
+
+    /// Similar to `get_with_hash`, but the returned `Entry` also carries the
+    /// metadata of the entry.
+    pub(crate) fn get_entry_with_hash<Q>(&self, key: &Q, hash: u64) -> Option<Entry<K, V>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        // Define a closure to record a read op.
+        let record = |op, now| self.record_read_op(op, now, |_| ());
+        let ignore_if = None as Option<&mut fn(&V) -> bool>;
+        self.do_get_with_hash_and_map(
+            key,
+            hash,
+            record,
+            ignore_if,
+            true,
+            |k, entry, value, now| {
+                let metadata = self.entry_metadata(entry, now);
+                Entry::new(k, value.into_owned(), false, false).with_metadata(metadata)
+            },
+        )
+    }
+
+    /// Similar to `get_with_hash`, but the returned guard pins the entry.
+    pub(crate) fn get_pinned_with_hash<Q>(&self, key: &Q, hash: u64) -> Option<PinnedEntry<K, V>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        // Define a closure to record a read op.
+        let record = |op, now| self.record_read_op(op, now, |_| ());
+        let ignore_if = None as Option<&mut fn(&V) -> bool>;
+        self.do_get_with_hash_and_map(key, hash, record, ignore_if, false, |_, entry, value, _| {
+            PinnedEntry::new(entry.entry_info(), value.into_owned())
+        })
+    }
+
+    /// Similar to `get_entry_with_hash`, but does not record the read, so the
+    /// historic popularity estimator and the idle timer of the entry are not
+    /// updated.
+    pub(crate) fn scanning_get_entry(&self, key: &Arc<K>) -> Option<Entry<K, V>> {
+        self.scanning_get_and_then(key, |k, entry, now| {
+            let value = self.intercept_get(entry)?;
+            let metadata = self.entry_metadata(entry, now);
+            Some(
+                Entry::new(Some(Arc::clone(k)), value.into_owned(), false, false)
+                    .with_metadata(metadata),
+            )
+        })
+    }
+
+    /// Passes the value of the entry to the `after_get` hooks of the interceptors,
+    /// if any. Returns `None` if an interceptor treated the read as a miss.
+    pub(crate) fn intercept_get<'a>(&self, entry: &'a ValueEntry<K, V>) -> Option<Cow<'a, V>> {
+        match &self.interceptors {
+            None => Some(Cow::Borrowed(&entry.value)),
+            Some(icpt) => icpt
+                .after_get(&entry.entry_info().key_hash().key, entry.value.clone())
+                .map(Cow::Owned),
+        }
+    }
+
+    /// Returns a handle of this cache that does not call the interceptors. Used to
+    /// copy the entries as they are stored.
+    pub(crate) fn without_interceptors(&self) -> Self {
+        let mut base = self.clone();
+        base.interceptors = None;
+        base
+    }
+
+    /// Pushes out the expiration of the live entry for the key by `duration` without
+    /// updating its value. Returns the write op to reorder the entry in the
+    /// deques, or `None` if there is no such entry.
+    ///
+    /// The per-entry expiration time is pushed out by `duration`. The time-to-live
+    /// is counted from the last modified time, so that time is moved forward
+    /// instead, but not past the current time. Otherwise, the entry could survive
+    /// a later `invalidate_all` call.
+    ///
+    /// Like an update of the value, the entry generation is incremented so that
+    /// the housekeeper does not act on the entry until the write op is applied.
+    pub(crate) fn do_extend_ttl_with_hash<Q>(
+        &self,
+        key: &Q,
+        hash: u64,
+        duration: Duration,
+    ) -> Option<(WriteOp<K, V>, Instant)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        if self.is_map_disabled() {
+            return None;
+        }
+
+        let now = self.current_time_from_expiration_clock();
+        let (key, entry) = self.inner.get_key_value_and_then(key, hash, |k, entry| {
+            self.inner
+                .is_live_entry(k, entry, now)
+                .then(|| (Arc::clone(k), TrioArc::clone(entry)))
+        })?;
+
+        let info = entry.entry_info();
+        let gen = info.incr_entry_gen();
+        if self.inner.time_to_live().is_some() {
+            if let Some(lm) = info.last_modified() {
+                let new_lm = lm.checked_add(duration).map_or(now, |ts| ts.min(now));
+                if new_lm > lm {
+                    info.set_last_modified(new_lm);
+                }
+            }
+        }
+        if let Some(ts) = info.expiration_time() {
+            info.set_expiration_time(ts.checked_add(duration));
+        }
+
+        let weight = info.policy_weight();
+        let op = WriteOp::new_upsert(&key, hash, &entry, gen, weight, weight);
+        Some((op, now))
+    }
+
+    /// Returns the live entry for the key without recording a read.
+    pub(crate) fn scanning_get_value_entry(
+        &self,
+        key: &Arc<K>,
+    ) -> Option<TrioArc<ValueEntry<K, V>>> {
+        self.scanning_get_and_then(key, |_k, entry, _now| Some(TrioArc::clone(entry)))
+    }
+
+    /// Returns the version of the live entry for the key without recording a
+    /// read.
+    #[cfg(feature = "entry-versions")]
+    pub(crate) fn scanning_get_version(&self, key: &Arc<K>) -> Option<u64> {
+        self.scanning_get_and_then(key, |_k, entry, _now| Some(entry.version()))
+    }
+
+    fn scanning_get_and_then<T>(
+        &self,
+        key: &Arc<K>,
+        and_then: impl FnOnce(&Arc<K>, &TrioArc<ValueEntry<K, V>>, Instant) -> Option<T>,
+    ) -> Option<T> {
+        let hash = self.hash(key);
+        self.inner.get_key_value_and_then(key, hash, |k, entry| {
+            let i = &self.inner;
+            let (ttl, tti, va) = (&i.time_to_live(), &i.time_to_idle(), &i.valid_after());
+            let now = self.current_time_from_expiration_clock();
+
+            if is_expired_by_per_entry_ttl(entry.entry_info(), now)
+                || is_expired_entry_wo(ttl, va, entry, now)
+                || is_expired_entry_ao(tti, va, entry, now)
+                || i.is_invalidated_entry(k, entry)
+            {
+                // Expired or invalidated entry.
+                None
+            } else {
+                // Valid entry.
+                and_then(k, entry, now)
+            }
+        })
+    }
+
+    fn entry_metadata(&self, entry: &TrioArc<ValueEntry<K, V>>, now: Instant) -> EntryMetadata {
+        let i = &self.inner;
+        let last_modified = entry.last_modified().unwrap_or(now);
+        let age = now
+            .checked_duration_since(last_modified)
+            .unwrap_or_default();
+        // The entry expires at the earliest of the per-entry expiration time, the
+        // time-to-live and the time-to-idle. The idle timer has just been reset.
+        let expires_at = [
+            entry.entry_info().expiration_time(),
+            i.time_to_live()
+                .and_then(|ttl| last_modified.checked_add(ttl)),
+            i.time_to_idle().and_then(|tti| now.checked_add(tti)),
+        ]
+        .into_iter()
+        .flatten()
+        .min();
+        let expires_in = expires_at.map(|ts| ts.checked_duration_since(now).unwrap_or_default());
+        let counts = i.entry_counters.then(|| {
+            let info = entry.entry_info();
+            (info.read_count(), info.write_count())
+        });
+        EntryMetadata::new(age, expires_in, entry.version(), counts)
+    }
+
     fn do_get_with_hash<Q, R, I>(
         &self,
         key: &Q,
         hash: u64,
         read_recorder: R,
-        mut ignore_if: Option<&mut I>,
+        ignore_if: Option<&mut I>,
         need_key: bool,
     ) -> Option<Entry<K, V>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        R: Fn(ReadOp<K, V>, Instant),
+        I: FnMut(&V) -> bool,
+    {
+        self.do_get_with_hash_and_map(
+            key,
+            hash,
+            read_recorder,
+            ignore_if,
+            need_key,
+            |k, _, value, _| Entry::new(k, value.into_owned(), false, false),
+        )
+    }
+
+    fn do_get_with_hash_and_map<Q, R, I, T>(
+        &self,
+        key: &Q,
+        hash: u64,
+        read_recorder: R,
+        mut ignore_if: Option<&mut I>,
+        need_key: bool,
+        map: impl FnOnce(Option<Arc<K>>, &TrioArc<ValueEntry<K, V>>, Cow<'_, V>, Instant) -> T,
+    ) -> Option<T>
     where
         K: Borrow<Q>,
         Q: Hash + Eq + ?Sized,
@@ -311,6 +797,9 @@ where
             });
 
         if let Some((maybe_key, entry)) = maybe_entry {
+            if self.inner.counts_entry_ops() {
+                entry.entry_info().incr_read_count();
+            }
             let mut is_expiry_modified = false;
 
             // Call the user supplied `expire_after_read` method if any.
@@ -346,7 +835,7 @@ where
                 // the timer wheel will reschedule the expiration timer for the
                 // entry.
                 is_expiry_modified = Self::expire_after_read_or_update(
-                    |k, v, t, d| expiry.expire_after_read(k, v, t, d, lm),
+                    |k, v, t, d, st| policy::expire_after_read(&**expiry, k, v, t, d, lm, st),
                     &entry.entry_info().key_hash().key,
                     &entry,
                     self.inner.expiration_policy.time_to_live(),
@@ -358,13 +847,17 @@ where
 
             entry.set_last_accessed(now);
 
-            let v = entry.value.clone();
+            // The read is recorded as a hit even if an interceptor treats it as a
+            // miss, as the entry is still cached.
+            let v = self
+                .intercept_get(&entry)
+                .map(|value| map(maybe_key, &entry, value, now));
             let op = ReadOp::Hit {
                 value_entry: entry,
                 is_expiry_modified,
             };
             read_recorder(op, now);
-            Some(Entry::new(maybe_key, v, false, false))
+            v
         } else {
             read_recorder(ReadOp::Miss(hash), now);
             None
@@ -389,6 +882,48 @@ where
         self.inner.remove_entry(key, hash)
     }
 
+    /// Removes the entry for the key only if it is neither expired nor invalidated,
+    /// and the `condition` returns `true` for its key and value. The condition is
+    /// checked and the entry is removed atomically.
+    pub(crate) fn remove_entry_if<Q>(
+        &self,
+        key: &Q,
+        hash: u64,
+        mut condition: impl FnMut(&K, &V) -> bool,
+    ) -> Option<KvEntry<K, V>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let now = self.current_time_from_expiration_clock();
+        self.inner.remove_entry_if(key, hash, |k, entry| {
+            self.inner.is_live_entry(k, entry, now) && condition(k, &entry.value)
+        })
+    }
+
+    /// Removes the entry for the key, unless it is neither expired nor invalidated
+    /// and `keep` returns `true` for its key and value. Returns the removed entry
+    /// and whether it was live.
+    pub(crate) fn remove_entry_unless_live_and<Q>(
+        &self,
+        key: &Q,
+        hash: u64,
+        mut keep: impl FnMut(&K, &V) -> bool,
+    ) -> Option<(KvEntry<K, V>, bool)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let now = self.current_time_from_expiration_clock();
+        let mut is_live = false;
+        self.inner
+            .remove_entry_if(key, hash, |k, entry| {
+                is_live = self.inner.is_live_entry(k, entry, now);
+                !(is_live && keep(k, &entry.value))
+            })
+            .map(|kv| (kv, is_live))
+    }
+
     #[inline]
     pub(crate) fn apply_reads_writes_if_needed(
         inner: &impl InnerSync,
@@ -405,9 +940,23 @@ where
         }
     }
 
-    pub(crate) fn invalidate_all(&self) {
+    pub(crate) fn invalidate_all(&self)
+    where
+        V: Clone,
+    {
         let now = self.current_time_from_expiration_clock();
         self.inner.set_valid_after(now);
+        self.inner.event_bus.publish(|| CacheEvent::InvalidateAll);
+    }
+
+    pub(crate) fn invalidate_entries_older_than(&self, age: Duration) {
+        let now = self.current_time_from_expiration_clock();
+        // If the watermark is before the time the cache was created (or the clock
+        // origin), there is nothing to invalidate.
+        let Some(watermark) = now.checked_sub(age) else {
+            return;
+        };
+        self.inner.set_valid_after(watermark);
     }
 
     pub(crate) fn invalidate_entries_if(
@@ -415,41 +964,104 @@ where
         predicate: PredicateFun<K, V>,
     ) -> Result<PredicateId, PredicateError> {
         let now = self.current_time_from_expiration_clock();
-        self.inner.register_invalidation_predicate(predicate, now)
+        let id = self.inner.register_invalidation_predicate(predicate, now)?;
+        self.inner
+            .event_bus
+            .publish(|| CacheEvent::PredicateRegistered { id: id.clone() });
+        Ok(id)
     }
-}
 
-//
-// Iterator support
-//
-impl<K, V, S> ScanningGet<K, V> for BaseCache<K, V, S>
-where
-    K: Hash + Eq + Send + Sync + 'static,
-    V: Clone + Send + Sync + 'static,
-    S: BuildHasher + Clone + Send + Sync + 'static,
-{
-    fn num_cht_segments(&self) -> usize {
-        self.inner.num_cht_segments()
+    /// Releases the memory kept at the high-watermark.
+    pub(crate) fn purge_unused_memory(&self) {
+        self.inner.purge_unused_memory();
     }
 
-    fn scanning_get(&self, key: &Arc<K>) -> Option<V> {
-        let hash = self.hash(key);
-        self.inner.get_key_value_and_then(key, hash, |k, entry| {
-            let i = &self.inner;
-            let (ttl, tti, va) = (&i.time_to_live(), &i.time_to_idle(), &i.valid_after());
-            let now = self.current_time_from_expiration_clock();
+    #[cfg(debug_assertions)]
+    pub(crate) fn validate_invariants(&self) -> InvariantReport<K> {
+        self.inner.validate_invariants()
+    }
 
-            if is_expired_by_per_entry_ttl(entry.entry_info(), now)
-                || is_expired_entry_wo(ttl, va, entry, now)
-                || is_expired_entry_ao(tti, va, entry, now)
-                || i.is_invalidated_entry(k, entry)
-            {
-                // Expired or invalidated entry.
-                None
-            } else {
-                // Valid entry.
-                Some(entry.value.clone())
+    /// Sheds the `fraction` of the max capacity. `0.0` restores the full capacity.
+    pub(crate) fn set_shed_fraction(&self, fraction: f64) {
+        self.inner.set_shed_fraction(fraction);
+    }
+
+    /// Returns a receiver of the events of this cache.
+    pub(crate) fn subscribe(&self) -> EventReceiver<K, V> {
+        self.inner.event_bus.subscribe()
+    }
+
+    pub(crate) fn is_invalidation_predicate_registered(&self, id: &PredicateId) -> bool {
+        self.inner.is_invalidation_predicate_registered(id)
+    }
+
+    pub(crate) fn next_keys_by_recency(
+        &self,
+        cursor: &mut RecencyCursor<K>,
+        max_keys: usize,
+    ) -> Vec<Arc<K>> {
+        self.inner.next_keys_by_recency(cursor, max_keys)
+    }
+
+    pub(crate) fn keys_by_estimated_frequency(&self) -> Vec<Arc<K>> {
+        self.inner.keys_by_estimated_frequency()
+    }
+
+    pub(crate) fn sample_keys(&self, n: usize, mut rng: impl FnMut() -> u64) -> Vec<Arc<K>> {
+        self.inner.sample_keys(n, &mut rng)
+    }
+
+    pub(crate) fn keys_expiring_within(&self, within: Duration) -> Vec<Arc<K>> {
+        self.inner.keys_expiring_within(within)
+    }
+
+    /// Removes all entries from the cache and returns the keys and values of the
+    /// live (not expired nor invalidated) ones. If `notify` is `true`, the live
+    /// entries are also notified to the eviction listener with the `Explicit`
+    /// cause.
+    ///
+    /// The values are moved out of the cache without cloning unless they are still
+    /// referenced by somebody else (e.g. a concurrent reader, or a removed bucket
+    /// of the `cht` that crossbeam-epoch has not collected yet).
+    pub(crate) fn take_all_entries(&self, notify: bool) -> Vec<(Arc<K>, V)> {
+        let notify = notify && self.is_removal_notifier_enabled();
+        let mut entries = Vec::with_capacity(self.entry_count() as usize);
+        self.inner.remove_all_entries(|key, entry| {
+            if notify {
+                self.inner
+                    .notify_single_removal(Arc::clone(&key), &entry, RemovalCause::Explicit);
             }
+            entries.push((key, entry));
+        });
+
+        // The removed buckets in the `cht` still hold references to the entries
+        // until crossbeam-epoch collects them. Give it a chance to collect them so
+        // that more values can be moved out. The rest will be cloned.
+        crossbeam_epoch::pin().flush();
+
+        entries
+            .into_iter()
+            .map(|(key, entry)| (key, ValueEntry::into_value(entry)))
+            .collect()
+    }
+}
+
+//
+// Iterator support
+//
+impl<K, V, S> ScanningGet<K, V> for BaseCache<K, V, S>
+where
+    K: Hash + Eq + Send + Sync + 'static,
+    V: Clone + Send + Sync + 'static,
+    S: BuildHasher + Clone + Send + Sync + 'static,
+{
+    fn num_cht_segments(&self) -> usize {
+        self.inner.num_cht_segments()
+    }
+
+    fn scanning_get(&self, key: &Arc<K>) -> Option<V> {
+        self.scanning_get_and_then(key, |_k, entry, _now| {
+            self.intercept_get(entry).map(Cow::into_owned)
         })
     }
 
@@ -467,18 +1079,51 @@ where
     V: Clone + Send + Sync + 'static,
     S: BuildHasher + Clone + Send + Sync + 'static,
 {
+    /// Records the read op. The hits and the misses are counted here rather than
+    /// by the housekeeper, as the read ops may be dropped or sampled out before they
+    /// are applied.
     #[inline]
     fn record_read_op(
         &self,
         op: ReadOp<K, V>,
         now: Instant,
-    ) -> Result<(), TrySendError<ReadOp<K, V>>> {
+        record_class_miss: impl FnOnce(&ClassStatsRecorder<K>),
+    ) {
+        if let Some(stats) = &self.inner.stats {
+            match &op {
+                ReadOp::Hit { .. } => stats.record_hit(),
+                ReadOp::Miss(_) => stats.record_miss(),
+            }
+        }
+        if let Some(class_stats) = &self.inner.class_stats {
+            match &op {
+                ReadOp::Hit { value_entry, .. } => {
+                    class_stats.record_hit(&value_entry.entry_info().key_hash().key)
+                }
+                ReadOp::Miss(_) => record_class_miss(class_stats),
+            }
+        }
+        if self.inner.is_degraded() {
+            self.inner.degraded_op_count.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
         self.apply_reads_if_needed(&self.inner, now);
+        let sampler = self.inner.read_sampler.as_ref();
+        if let Some(sampler) = sampler {
+            if !sampler.should_record() {
+                return;
+            }
+        }
         let ch = &self.read_op_ch;
         match ch.try_send(op) {
+            Ok(()) => (),
             // Discard the ReadOp when the channel is full.
-            Ok(()) | Err(TrySendError::Full(_)) => Ok(()),
-            Err(e @ TrySendError::Disconnected(_)) => Err(e),
+            Err(TrySendError::Full(_)) => {
+                if let Some(sampler) = sampler {
+                    sampler.record_dropped();
+                }
+            }
+            Err(TrySendError::Disconnected(_)) => self.degrade(),
         }
     }
 
@@ -489,14 +1134,40 @@ where
         hash: u64,
         value: V,
     ) -> (WriteOp<K, V>, Instant) {
-        let weight = self.inner.weigh(&key, &value);
+        let keep_if = None as Option<fn(&ValueEntry<K, V>) -> bool>;
+        match self.do_insert_with_hash_unless(key, hash, value, keep_if) {
+            Ok(op_and_ts) => op_and_ts,
+            Err(_) => unreachable!(),
+        }
+    }
+
+    /// Inserts the value for the key unless the cache has a live (neither expired
+    /// nor invalidated) entry for the key and `keep_if` returns `true` for it. The
+    /// check and the insert are done atomically. If the existing entry is kept,
+    /// returns it as `Err`.
+    // https://rust-lang.github.io/rust-clippy/master/index.html#type_complexity
+    #[allow(clippy::type_complexity)]
+    pub(crate) fn do_insert_with_hash_unless(
+        &self,
+        key: Arc<K>,
+        hash: u64,
+        value: V,
+        mut keep_if: Option<impl FnMut(&ValueEntry<K, V>) -> bool>,
+    ) -> Result<(WriteOp<K, V>, Instant), TrioArc<ValueEntry<K, V>>> {
         let op_cnt1 = Rc::new(AtomicU8::new(0));
         let op_cnt2 = Rc::clone(&op_cnt1);
         let mut op1 = None;
         let mut op2 = None;
+        let mut kept = None;
 
-        // Lock the key for update if blocking removal notification is enabled.
-        let kl = self.maybe_key_lock(&key);
+        // Lock the key for update if blocking removal notification is enabled and
+        // the replaced entry can be notified.
+        let notifies_update = self.inner.is_update_notified();
+        let kl = if notifies_update {
+            self.maybe_key_lock(&key)
+        } else {
+            None
+        };
         let _klg = &kl.as_ref().map(|kl| kl.lock());
 
         let ts = self.current_time_from_expiration_clock();
@@ -518,6 +1189,7 @@ where
             hash,
             // on_insert
             || {
+                let weight = self.inner.weigh(&key, &value, &WeighContext::insert());
                 let (entry, gen) = self.new_value_entry(&key, hash, value.clone(), ts, weight);
                 let ins_op = WriteOp::new_upsert(&key, hash, &entry, gen, 0, weight);
                 let cnt = op_cnt1.fetch_add(1, Ordering::Relaxed);
@@ -525,13 +1197,29 @@ where
                 entry
             },
             // on_modify
-            |_k, old_entry| {
+            |k, old_entry| {
+                if let Some(keep_if) = &mut keep_if {
+                    if self.inner.is_live_entry(k, old_entry, ts) && keep_if(old_entry) {
+                        // Keep the existing entry by putting it back as is.
+                        let cnt = op_cnt2.fetch_add(1, Ordering::Relaxed);
+                        kept = Some((cnt, TrioArc::clone(old_entry)));
+                        return TrioArc::clone(old_entry);
+                    }
+                }
+
                 let old_weight = old_entry.policy_weight();
+                let weight = self
+                    .inner
+                    .weigh(&key, &value, &WeighContext::update(old_weight));
 
                 // Create this OldEntryInfo _before_ creating a new ValueEntry, so
                 // that the OldEntryInfo can preserve the old EntryInfo's
                 // last_accessed and last_modified timestamps.
-                let old_info = OldEntryInfo::new(old_entry);
+                let old_info = if notifies_update {
+                    Some(OldEntryInfo::new(old_entry))
+                } else {
+                    None
+                };
                 let (entry, gen) = self.new_value_entry_from(value.clone(), ts, weight, old_entry);
                 let upd_op = WriteOp::new_upsert(&key, hash, &entry, gen, old_weight, weight);
                 let cnt = op_cnt2.fetch_add(1, Ordering::Relaxed);
@@ -540,18 +1228,114 @@ where
             },
         );
 
+        // Discard the results of the closure calls that were not the last one.
+        let last_cnt = [
+            op1.as_ref().map(|(cnt, _)| *cnt),
+            op2.as_ref().map(|(cnt, ..)| *cnt),
+            kept.as_ref().map(|(cnt, _)| *cnt),
+        ]
+        .into_iter()
+        .flatten()
+        .max();
+        if let Some((cnt, entry)) = kept {
+            if Some(cnt) == last_cnt {
+                return Err(entry);
+            }
+        }
+
         match (op1, op2) {
-            (Some((_cnt, ins_op)), None) => self.do_post_insert_steps(ts, &key, ins_op),
+            (Some((_cnt, ins_op)), None) => Ok(self.do_post_insert_steps(ts, &key, ins_op)),
             (Some((cnt1, ins_op)), Some((cnt2, ..))) if cnt1 > cnt2 => {
-                self.do_post_insert_steps(ts, &key, ins_op)
+                Ok(self.do_post_insert_steps(ts, &key, ins_op))
             }
             (_, Some((_cnt, old_info, upd_op))) => {
-                self.do_post_update_steps(ts, key, old_info, upd_op)
+                Ok(self.do_post_update_steps(ts, key, old_info, upd_op))
             }
             (None, None) => unreachable!(),
         }
     }
 
+    /// Replaces the entry for the key with the value if the key is present and
+    /// `replace_if` returns `true` for its entry. Unlike `do_insert_with_hash_unless`,
+    /// the check and the replacement are done atomically in the hash table, so the
+    /// key is never inserted back after it has been removed. Returns `None` if the
+    /// entry was not replaced.
+    pub(crate) fn do_replace_with_hash_if(
+        &self,
+        key: Arc<K>,
+        hash: u64,
+        value: V,
+        mut replace_if: impl FnMut(&ValueEntry<K, V>) -> bool,
+    ) -> Option<(WriteOp<K, V>, Instant)> {
+        let mut op = None;
+
+        let notifies_update = self.inner.is_update_notified();
+        let kl = if notifies_update {
+            self.maybe_key_lock(&key)
+        } else {
+            None
+        };
+        let _klg = &kl.as_ref().map(|kl| kl.lock());
+
+        let ts = self.current_time_from_expiration_clock();
+
+        // The modifier may be called more than once if it conflicts with other
+        // concurrent hash table operations. Only the result of the last call is
+        // stored, so keep only the last WriteOp.
+        let replaced = self.inner.cache.modify_entry_if_and(
+            hash,
+            |k| k == &key,
+            |_k, old_entry| {
+                if !replace_if(old_entry) {
+                    op = None;
+                    return None;
+                }
+                let old_weight = old_entry.policy_weight();
+                let weight = self
+                    .inner
+                    .weigh(&key, &value, &WeighContext::update(old_weight));
+                let old_info = if notifies_update {
+                    Some(OldEntryInfo::new(old_entry))
+                } else {
+                    None
+                };
+                let (entry, gen) = self.new_value_entry_from(value.clone(), ts, weight, old_entry);
+                let upd_op = WriteOp::new_upsert(&key, hash, &entry, gen, old_weight, weight);
+                op = Some((old_info, upd_op));
+                Some(entry)
+            },
+            |_k, _v| (),
+        );
+
+        let (old_info, upd_op) = replaced.and(op)?;
+        Some(self.do_post_update_steps(ts, key, old_info, upd_op))
+    }
+
+    /// Sets the expiration time of the entry inserted or updated by `op` to the
+    /// duration returned by `expire_after` past from `ts`. This overrides the
+    /// expiration time set by the `Expiry` of the cache, if any.
+    pub(crate) fn set_expiration_time_for_op(
+        &self,
+        op: &WriteOp<K, V>,
+        ts: Instant,
+        expire_after: impl FnOnce(&K, &V, StdInstant) -> Option<Duration>,
+    ) {
+        if let WriteOp::Upsert {
+            key_hash,
+            value_entry,
+            ..
+        } = op
+        {
+            let current_time = self.inner.clocks().to_std_instant(ts);
+            let duration = expire_after(&key_hash.key, &value_entry.value, current_time);
+            let expiration_time =
+                duration.map(|duration| ts.checked_add(duration).expect("Overflow"));
+            value_entry
+                .entry_info()
+                .set_expiration_time(expiration_time);
+        }
+    }
+
     fn do_post_insert_steps(
         &self,
         ts: Instant,
@@ -563,6 +1347,12 @@ where
         {
             Self::expire_after_create(expiry, key, value_entry, ts, self.inner.clocks());
         }
+        if let WriteOp::Upsert { value_entry, .. } = &ins_op {
+            self.inner.event_bus.publish(|| CacheEvent::Insert {
+                key: Arc::clone(key),
+                value: value_entry.value.clone(),
+            });
+        }
         (ins_op, ts)
     }
 
@@ -570,14 +1360,14 @@ where
         &self,
         ts: Instant,
         key: Arc<K>,
-        old_info: OldEntryInfo<K, V>,
+        old_info: Option<OldEntryInfo<K, V>>,
         upd_op: WriteOp<K, V>,
     ) -> (WriteOp<K, V>, Instant) {
         if let (Some(expiry), WriteOp::Upsert { value_entry, .. }) =
             (&self.inner.expiration_policy.expiry(), &upd_op)
         {
             Self::expire_after_read_or_update(
-                |k, v, t, d| expiry.expire_after_update(k, v, t, d),
+                |k, v, t, d, st| policy::expire_after_update(&**expiry, k, v, t, d, st),
                 &key,
                 value_entry,
                 self.inner.expiration_policy.time_to_live(),
@@ -587,7 +1377,14 @@ where
             );
         }
 
-        if self.is_removal_notifier_enabled() {
+        if let WriteOp::Upsert { value_entry, .. } = &upd_op {
+            self.inner.event_bus.publish(|| CacheEvent::Update {
+                key: Arc::clone(&key),
+                value: value_entry.value.clone(),
+            });
+        }
+
+        if let Some(old_info) = old_info {
             self.inner.notify_upsert(
                 key,
                 &old_info.entry,
@@ -632,9 +1429,16 @@ impl<K, V, S> BaseCache<K, V, S> {
         policy_weight: u32,
     ) -> (TrioArc<ValueEntry<K, V>>, u16) {
         let key_hash = KeyHash::new(Arc::clone(key), hash);
-        let info = TrioArc::new(EntryInfo::new(key_hash, timestamp, policy_weight));
+        let mut info = EntryInfo::new(key_hash, timestamp, policy_weight);
+        if self.inner.counts_entry_ops() {
+            info = info.with_op_counts(timestamp);
+        }
+        let info = TrioArc::new(info);
         let gen: u16 = info.entry_gen();
-        (TrioArc::new(ValueEntry::new(value, info)), gen)
+        let entry = ValueEntry::new(value, info)
+            .with_zeroizer(self.inner.value_zeroizer)
+            .with_version(self.inner.next_version());
+        (TrioArc::new(entry), gen)
     }
 
     #[inline]
@@ -649,10 +1453,15 @@ impl<K, V, S> BaseCache<K, V, S> {
         // To prevent this updated ValueEntry from being evicted by an expiration
         // policy, increment the entry generation.
         let gen = info.incr_entry_gen();
+        if self.inner.counts_entry_ops() {
+            info.incr_write_count();
+        }
         info.set_last_accessed(timestamp);
         info.set_last_modified(timestamp);
         info.set_policy_weight(policy_weight);
-        (TrioArc::new(ValueEntry::new_from(value, info, other)), gen)
+        let entry =
+            ValueEntry::new_from(value, info, other).with_version(self.inner.next_version());
+        (TrioArc::new(entry), gen)
     }
 
     fn expire_after_create(
@@ -671,7 +1480,7 @@ impl<K, V, S> BaseCache<K, V, S> {
     }
 
     fn expire_after_read_or_update(
-        expiry: impl FnOnce(&K, &V, StdInstant, Option<Duration>) -> Option<Duration>,
+        expiry: impl FnOnce(&K, &V, StdInstant, Option<Duration>, &EntryStats) -> Option<Duration>,
         key: &K,
         value_entry: &ValueEntry<K, V>,
         ttl: Option<Duration>,
@@ -695,7 +1504,21 @@ impl<K, V, S> BaseCache<K, V, S> {
             std_time.checked_duration_since(current_time)
         });
 
-        let duration = expiry(key, &value_entry.value, current_time, current_duration);
+        #[cfg(feature = "entry-stats")]
+        let stats = EntryStats::new(
+            clocks.to_std_instant(ei.created_at().expect("Created at is not set")),
+            ei.read_count(),
+            ei.write_count(),
+        );
+        #[cfg(not(feature = "entry-stats"))]
+        let stats = EntryStats;
+        let duration = expiry(
+            key,
+            &value_entry.value,
+            current_time,
+            current_duration,
+            &stats,
+        );
 
         if duration != current_duration {
             let expiration_time =
@@ -734,6 +1557,17 @@ where
         }
     }
 
+    pub(crate) fn pending_read_count(&self) -> usize {
+        self.read_op_ch.len()
+    }
+
+    /// Replaces the senders of the read and write channels with the ones whose
+    /// receivers are already dropped.
+    pub(crate) fn disconnect_channels_for_testing(&mut self) {
+        self.read_op_ch = crossbeam_channel::bounded(1).0;
+        self.write_op_ch = crossbeam_channel::bounded(1).0;
+    }
+
     pub(crate) fn set_expiration_clock(&self, clock: Option<Clock>) {
         self.inner.set_expiration_clock(clock);
         if let Some(hk) = &self.housekeeper {
@@ -745,11 +1579,48 @@ where
     pub(crate) fn key_locks_map_is_empty(&self) -> bool {
         self.inner.key_locks_map_is_empty()
     }
+
+    pub(crate) fn table_capacity(&self) -> usize {
+        self.inner.cache.capacity()
+    }
+
+    pub(crate) fn is_deques_locked(&self) -> bool {
+        self.inner.deques.is_locked()
+    }
+
+    pub(crate) fn free_deque_node_count(&self) -> usize {
+        let deqs = self.inner.deques.lock();
+        [
+            &deqs.window,
+            &deqs.probation,
+            &deqs.protected,
+            &deqs.write_order,
+        ]
+        .iter()
+        .map(|deq| deq.free_node_count())
+        .sum()
+    }
+
+    pub(crate) fn set_weighted_size_for_testing(&self, size: u64) {
+        self.inner.weighted_size.store(size);
+    }
+
+    pub(crate) fn unlink_access_order_node_for_testing(&self, key: &K)
+    where
+        K: Hash + Eq,
+        S: BuildHasher,
+    {
+        let hash = self.hash(key);
+        let entry = self.inner.cache.get(hash, |k| &**k == key).unwrap();
+        self.inner.deques.lock().unlink_ao(&entry);
+    }
 }
 
 struct EvictionState<'a, K, V> {
-    counters: EvictionCounters,
+    counters: EvictionCounters<'a, K>,
     notifier: Option<&'a RemovalNotifier<K, V>>,
+    event_bus: &'a EventBus<K, V>,
+    clocks: &'a Clocks,
     more_entries_to_evict: bool,
 }
 
@@ -758,16 +1629,22 @@ impl<'a, K, V> EvictionState<'a, K, V> {
         entry_count: u64,
         weighted_size: u64,
         notifier: Option<&'a RemovalNotifier<K, V>>,
+        event_bus: &'a EventBus<K, V>,
+        clocks: &'a Clocks,
+        tenant_quotas: Option<&'a TenantQuotas<K>>,
+        lifetimes: Option<(&'a EntryLifetimes, Instant)>,
     ) -> Self {
         Self {
-            counters: EvictionCounters::new(entry_count, weighted_size),
+            counters: EvictionCounters::new(entry_count, weighted_size, tenant_quotas, lifetimes),
             notifier,
+            event_bus,
+            clocks,
             more_entries_to_evict: false,
         }
     }
 
     fn is_notifier_enabled(&self) -> bool {
-        self.notifier.is_some()
+        self.notifier.is_some() || self.event_bus.has_subscribers()
     }
 
     fn notify_entry_removal(
@@ -779,27 +1656,82 @@ impl<'a, K, V> EvictionState<'a, K, V> {
         K: Send + Sync + 'static,
         V: Clone + Send + Sync + 'static,
     {
-        if let Some(notifier) = self.notifier {
-            notifier.notify(key, entry.value.clone(), cause);
-        } else {
+        if !self.is_notifier_enabled() {
             panic!("notify_entry_removal is called when the notification is disabled");
         }
+
+        self.event_bus.publish(|| CacheEvent::Evict {
+            key: Arc::clone(&key),
+            value: entry.value.clone(),
+            cause,
+        });
+        if let Some(notifier) = self.notifier.filter(|n| n.is_notified(cause)) {
+            let metadata = removal_metadata(self.clocks, entry);
+            notifier.notify(key, entry.value.clone(), cause, metadata);
+        }
     }
 }
 
-struct EvictionCounters {
+struct EvictionCounters<'a, K> {
     entry_count: u64,
     weighted_size: u64,
     eviction_count: u64,
+    tenant_quotas: Option<&'a TenantQuotas<K>>,
+    /// The recorder of the entry lifetimes and the current time.
+    lifetimes: Option<(&'a EntryLifetimes, Instant)>,
 }
 
-impl EvictionCounters {
+impl<'a, K> EvictionCounters<'a, K> {
     #[inline]
-    fn new(entry_count: u64, weighted_size: u64) -> Self {
+    fn new(
+        entry_count: u64,
+        weighted_size: u64,
+        tenant_quotas: Option<&'a TenantQuotas<K>>,
+        lifetimes: Option<(&'a EntryLifetimes, Instant)>,
+    ) -> Self {
         Self {
             entry_count,
             weighted_size,
             eviction_count: 0,
+            tenant_quotas,
+            lifetimes,
+        }
+    }
+
+    /// Records the lifetime of a removed entry, if the histogram is enabled.
+    #[inline]
+    fn record_lifetime(&self, info: &EntryInfo<K>) {
+        if let Some((lifetimes, now)) = self.lifetimes {
+            let lifetime = info
+                .created_at()
+                .and_then(|created_at| now.checked_duration_since(created_at))
+                .unwrap_or_default();
+            lifetimes.record(lifetime, info.read_count() > 0);
+        }
+    }
+
+    /// Adds an entry of the `key` to the counters of its tenant.
+    #[inline]
+    fn add_tenant_entry(&self, key: &K, weight: u32) {
+        if let Some(quotas) = self.tenant_quotas {
+            quotas.add(key, weight);
+        }
+    }
+
+    /// Removes an entry of the `key` from the counters of its tenant.
+    #[inline]
+    fn sub_tenant_entry(&self, key: &K, weight: u32) {
+        if let Some(quotas) = self.tenant_quotas {
+            quotas.sub(key, weight);
+        }
+    }
+
+    /// Updates the weighted size of the tenant of the `key` from `old_weight` to
+    /// `new_weight`.
+    #[inline]
+    fn update_tenant_weight(&self, key: &K, old_weight: u32, new_weight: u32) {
+        if let Some(quotas) = self.tenant_quotas {
+            quotas.update_weight(key, old_weight, new_weight);
         }
     }
 
@@ -842,7 +1774,7 @@ impl EntrySizeAndFrequency {
         self.policy_weight += weight as u64;
     }
 
-    fn add_frequency(&mut self, freq: &FrequencySketch, hash: u64) {
+    fn add_frequency(&mut self, freq: &SegmentedFrequencySketch, hash: u64) {
         self.freq += freq.frequency(hash) as u32;
     }
 }
@@ -878,13 +1810,14 @@ struct Clocks {
 }
 
 impl Clocks {
-    fn new(time: Instant, std_time: StdInstant) -> Self {
+    fn new(time: Instant, std_time: StdInstant, expiration_clock: Option<Clock>) -> Self {
+        let has_expiration_clock = expiration_clock.is_some();
         Self {
-            has_expiration_clock: AtomicBool::default(),
-            expiration_clock: Default::default(),
+            has_expiration_clock: AtomicBool::new(has_expiration_clock),
+            expiration_clock: RwLock::new(expiration_clock),
             origin: time,
             origin_std: std_time,
-            mutable_origin: Default::default(),
+            mutable_origin: RwLock::new(has_expiration_clock.then_some((time, std_time))),
         }
     }
 
@@ -905,17 +1838,53 @@ impl Clocks {
     }
 }
 
+/// A position in the access order deque, used to take a snapshot of the deque in
+/// chunks without holding the lock between the chunks.
+pub(crate) enum RecencyCursor<K> {
+    Start,
+    /// Points to the node right after the node of the given entry.
+    After(TrioArc<Mutex<DeqNodes<K>>>),
+    Done,
+}
+
 pub(crate) struct Inner<K, V, S> {
     name: Option<String>,
     max_capacity: Option<u64>,
+    /// The weight shed from the max capacity by the `shed` method or the memory
+    /// pressure watcher.
+    shed_weight: AtomicCell<u64>,
+    memory_pressure: Option<MemoryPressureWatcher>,
+    hit_ratio_analyzer: Option<HitRatioAnalyzer>,
+    ghost_list: Option<GhostList>,
+    second_chance_readmission: bool,
+    shrink_first: bool,
+    pause_reads_while_shrinking: bool,
+    entry_counters: bool,
+    entry_lifetimes: Option<EntryLifetimes>,
+    read_sampler: Option<ReadSampler>,
+    stats: Option<StatsCounterArc>,
     entry_count: AtomicCell<u64>,
+    /// The max entry count since the hash table was last shrunk. Updated by the
+    /// pending tasks.
+    entry_count_high_watermark: AtomicCell<u64>,
     weighted_size: AtomicCell<u64>,
+    /// The last version given to an inserted or updated value. `None` unless the
+    /// cache was built with `support_entry_versions`, so that the writes do not
+    /// contend on a single counter when the versions are not used.
+    last_version: Option<AtomicCell<u64>>,
     cache: CacheStore<K, V, S>,
     build_hasher: S,
     deques: Mutex<Deques<K>>,
     timer_wheel: Mutex<TimerWheel<K>>,
-    frequency_sketch: RwLock<FrequencySketch>,
+    frequency_sketch: SegmentedFrequencySketch,
     frequency_sketch_enabled: AtomicBool,
+    /// Set when the channels to the housekeeper were found disconnected. The cache
+    /// then stops recording the reads and writes, and works as a plain concurrent
+    /// hash map without the policies.
+    degraded: AtomicBool,
+    /// The number of the reads and writes not recorded since the cache was
+    /// degraded.
+    degraded_op_count: AtomicU64,
     read_op_ch: Receiver<ReadOp<K, V>>,
     write_op_ch: Receiver<WriteOp<K, V>>,
     eviction_policy: EvictionPolicyConfig,
@@ -923,9 +1892,21 @@ pub(crate) struct Inner<K, V, S> {
     valid_after: AtomicInstant,
     weigher: Option<Weigher<K, V>>,
     removal_notifier: Option<RemovalNotifier<K, V>>,
+    event_bus: EventBus<K, V>,
     key_locks: Option<KeyLockMap<K, S>>,
     invalidator: Option<Invalidator<K, V, S>>,
+    value_zeroizer: ValueZeroizer<V>,
+    tenant_quotas: Option<TenantQuotas<K>>,
+    victim_selector: Option<VictimSelector<K, V>>,
+    hot_keys: Option<HotKeyConfig<K, V>>,
+    class_stats: Option<ClassStatsRecorder<K>>,
     clocks: Clocks,
+    /// The configuration the cache was built with. `None` when the base cache was
+    /// not built by a `Cache`.
+    config: Option<Config<K, V, S>>,
+    /// The errors cached by `try_get_with` of the cache, purged of the expired ones
+    /// by the housekeeper.
+    error_cache: Option<Arc<ErrorCache<K, S>>>,
 }
 
 impl<K, V, S> Drop for Inner<K, V, S> {
@@ -952,9 +1933,56 @@ impl<K, V, S> Inner<K, V, S> {
         self.name.as_deref()
     }
 
+    fn was_recently_evicted(&self, hash: u64) -> bool {
+        self.ghost_list
+            .as_ref()
+            .map_or(false, |ghosts| ghosts.contains(hash))
+    }
+
+    /// Adds the hash of a key evicted by the size constraint to the ghost list, and
+    /// counts the eviction in the class stats, if enabled.
+    fn remember_evicted(&self, entry: &ValueEntry<K, V>) {
+        let kh = entry.entry_info().key_hash();
+        if let Some(ghosts) = &self.ghost_list {
+            ghosts.push(kh.hash);
+        }
+        if let Some(class_stats) = &self.class_stats {
+            class_stats.record_eviction(&kh.key);
+        }
+    }
+
+    fn is_degraded(&self) -> bool {
+        self.degraded.load(Ordering::Relaxed)
+    }
+
+    /// Returns `true` if the reads and writes of each entry should be counted.
+    /// The counts are also needed by the `Expiry`, which may read them through
+    /// `EntryStats`, and by the histogram of the entry lifetimes.
+    #[inline]
+    fn counts_entry_ops(&self) -> bool {
+        cfg!(feature = "entry-stats")
+            && (self.entry_counters
+                || self.entry_lifetimes.is_some()
+                || self.expiration_policy.expiry().is_some())
+    }
+
+    /// Returns a new version for an inserted or updated value. Versions start at
+    /// one, or are always `0` if the entry versions are not enabled.
+    fn next_version(&self) -> u64 {
+        self.last_version.as_ref().map_or(0, |v| v.fetch_add(1) + 1)
+    }
+
     fn policy(&self) -> Policy {
         let exp = &self.expiration_policy;
-        Policy::new(self.max_capacity, 1, exp.time_to_live(), exp.time_to_idle())
+        let mut policy = Policy::new(self.max_capacity, 1, exp.time_to_live(), exp.time_to_idle());
+        policy.eviction_policy = EvictionPolicy {
+            config: self.eviction_policy.clone(),
+        };
+        policy.has_weigher = self.weigher.is_some();
+        policy.has_expiry = exp.expiry().is_some();
+        policy.has_eviction_listener = self.is_removal_notifier_enabled();
+        policy.supports_invalidation_closures = self.invalidator.is_some();
+        policy
     }
 
     #[inline]
@@ -969,7 +1997,20 @@ impl<K, V, S> Inner<K, V, S> {
 
     #[inline]
     fn is_removal_notifier_enabled(&self) -> bool {
-        self.removal_notifier.is_some()
+        self.removal_notifier.is_some() || self.event_bus.has_subscribers()
+    }
+
+    /// Returns `true` if replacing an entry should be notified. The replaced entry
+    /// is notified as `Replaced`, or as `Expired` or `Explicit` when it was
+    /// already expired or invalidated.
+    #[inline]
+    fn is_update_notified(&self) -> bool {
+        self.event_bus.has_subscribers()
+            || self.removal_notifier.as_ref().map_or(false, |n| {
+                n.is_notified(RemovalCause::Replaced)
+                    || n.is_notified(RemovalCause::Expired)
+                    || n.is_notified(RemovalCause::Explicit)
+            })
     }
 
     fn maybe_key_lock(&self, key: &Arc<K>) -> Option<KeyLock<'_, K, S>>
@@ -1020,6 +2061,21 @@ impl<K, V, S> Inner<K, V, S> {
         exp.time_to_live().is_some() || exp.time_to_idle().is_some()
     }
 
+    /// Returns `true` if the entry count has dropped enough below its
+    /// high-watermark for the hash table to be worth shrinking, resetting the
+    /// high-watermark if so. This keeps the maintenance from scanning the segments
+    /// of the hash table on every run.
+    fn should_shrink_to_fit(&self, entry_count: u64) -> bool {
+        let high_watermark = self.entry_count_high_watermark.load().max(entry_count);
+        if entry_count.saturating_mul(AUTO_SHRINK_FACTOR as u64) < high_watermark {
+            self.entry_count_high_watermark.store(entry_count);
+            true
+        } else {
+            self.entry_count_high_watermark.store(high_watermark);
+            false
+        }
+    }
+
     #[inline]
     fn is_write_order_queue_enabled(&self) -> bool {
         self.expiration_policy.time_to_live().is_some() || self.invalidator.is_some()
@@ -1030,9 +2086,11 @@ impl<K, V, S> Inner<K, V, S> {
         self.valid_after.instant()
     }
 
+    /// Moves the watermark forward to `timestamp`. It is never moved backward, as
+    /// it would revive the entries invalidated by the concurrent or earlier calls.
     #[inline]
     fn set_valid_after(&self, timestamp: Instant) {
-        self.valid_after.set_instant(timestamp);
+        self.valid_after.set_instant_max(timestamp);
     }
 
     #[inline]
@@ -1058,14 +2116,50 @@ where
         weigher: Option<Weigher<K, V>>,
         eviction_policy: EvictionPolicy,
         eviction_listener: Option<EvictionListener<K, V>>,
+        eviction_listener_causes: RemovalCauses,
         read_op_ch: Receiver<ReadOp<K, V>>,
         write_op_ch: Receiver<WriteOp<K, V>>,
-        expiration_policy: ExpirationPolicy<K, V>,
+        mut expiration_policy: ExpirationPolicy<K, V>,
         invalidator_enabled: bool,
+        policy_config: PolicyConfig<K, V>,
+        config: Option<Config<K, V, S>>,
+        error_cache: Option<Arc<ErrorCache<K, S>>>,
     ) -> Self {
+        let PolicyConfig {
+            memory_pressure_shed_fraction,
+            hit_ratio_analyzer,
+            ghost_list,
+            second_chance_readmission,
+            shrink_first,
+            pause_reads_while_shrinking,
+            entry_counters,
+            entry_lifetimes,
+            read_sampling,
+            segmented_frequency_sketch,
+            stats_disabled: _,
+            entry_versions,
+            // Used by the `ValueInitializer` of the cache.
+            load_progress: _,
+            stats_counter: stats,
+            value_zeroizer,
+            tenant_quotas,
+            victim_selector,
+            hot_keys,
+            key_classifier,
+            expiration_clock,
+        } = policy_config;
+        expiration_policy.apply_namespaces();
+
+        // A cache with zero max capacity stores nothing, so it does not create the
+        // policy structures that are allocated up front. The deques, the timer
+        // wheel and the frequency sketch allocate only when the entries are
+        // stored, and the channels are created with zero capacity by `BaseCache`.
+        let is_map_disabled = max_capacity == Some(0);
+        let max_capacity_enabled = max_capacity.filter(|_| !is_map_disabled);
+
         // TODO: Calculate the number of segments based on the max capacity and the
         // number of CPUs.
-        let (num_segments, initial_capacity) = if max_capacity == Some(0) {
+        let (num_segments, cht_initial_capacity) = if is_map_disabled {
             (1, 0)
         } else {
             let ic = initial_capacity
@@ -1075,21 +2169,45 @@ where
         };
         let cache = crate::cht::SegmentedHashMap::with_num_segments_capacity_and_hasher(
             num_segments,
-            initial_capacity,
+            cht_initial_capacity,
             build_hasher.clone(),
         );
 
+        // Split the frequency sketch in the same way as the hash table if asked,
+        // so that the updates of the sketch on different segments do not contend
+        // for a single lock.
+        let frequency_sketch = SegmentedFrequencySketch::new(if segmented_frequency_sketch {
+            num_segments
+        } else {
+            1
+        });
+        // If the number of entries is expected, allocate the table of the frequency
+        // sketch up front instead of when the cache becomes half full.
+        if let (Some(max_cap), Some(init_cap), None) = (max_capacity, initial_capacity, &weigher) {
+            let cap = max_cap.min(init_cap as u64);
+            if cap > 0 {
+                frequency_sketch.ensure_capacity(common::sketch_capacity(cap));
+            }
+        }
+
         // Assume that getting `moka::common::Instant::now` has lower latency than
         // `StdInstant::now`.
         let now_std = StdInstant::now();
-        let now = Instant::now();
-        let clocks = Clocks::new(now, now_std);
+        let now = expiration_clock
+            .as_ref()
+            .map_or_else(Instant::now, |clock| Instant::new(clock.now()));
+        let clocks = Clocks::new(now, now_std, expiration_clock);
         let timer_wheel = Mutex::new(TimerWheel::new(now));
 
         let (removal_notifier, key_locks) = if let Some(listener) = eviction_listener {
-            let rn = RemovalNotifier::new(listener, name.clone());
-            let kl = KeyLockMap::with_hasher(build_hasher.clone());
-            (Some(rn), Some(kl))
+            let rn = RemovalNotifier::new(listener, eviction_listener_causes, name.clone());
+            // The key locks are not needed when the cache stores nothing.
+            let kl = if is_map_disabled {
+                None
+            } else {
+                Some(KeyLockMap::with_hasher(build_hasher.clone()))
+            };
+            (Some(rn), kl)
         } else {
             (None, None)
         };
@@ -1103,14 +2221,35 @@ where
         Self {
             name,
             max_capacity,
+            shed_weight: AtomicCell::default(),
+            memory_pressure: memory_pressure_shed_fraction
+                .filter(|_| !is_map_disabled)
+                .map(MemoryPressureWatcher::new),
+            hit_ratio_analyzer: max_capacity_enabled
+                .filter(|_| hit_ratio_analyzer)
+                .map(HitRatioAnalyzer::new),
+            ghost_list: max_capacity_enabled
+                .filter(|_| ghost_list || second_chance_readmission)
+                .map(GhostList::new),
+            second_chance_readmission,
+            shrink_first,
+            pause_reads_while_shrinking,
+            entry_counters,
+            entry_lifetimes: entry_lifetimes.then(EntryLifetimes::new),
+            read_sampler: read_sampling.then(ReadSampler::new),
+            stats,
             entry_count: AtomicCell::default(),
+            entry_count_high_watermark: AtomicCell::default(),
             weighted_size: AtomicCell::default(),
+            last_version: entry_versions.then(AtomicCell::default),
             cache,
             build_hasher,
             deques: Mutex::default(),
             timer_wheel,
-            frequency_sketch: RwLock::new(FrequencySketch::default()),
+            frequency_sketch,
             frequency_sketch_enabled: AtomicBool::default(),
+            degraded: AtomicBool::default(),
+            degraded_op_count: AtomicU64::default(),
             read_op_ch,
             write_op_ch,
             eviction_policy: eviction_policy.config,
@@ -1118,9 +2257,19 @@ where
             valid_after: AtomicInstant::default(),
             weigher,
             removal_notifier,
+            event_bus: EventBus::default(),
             key_locks,
             invalidator,
+            value_zeroizer,
+            tenant_quotas: tenant_quotas
+                .zip(max_capacity_enabled)
+                .map(|(config, max_cap)| TenantQuotas::new(config, max_cap)),
+            victim_selector,
+            hot_keys,
+            class_stats: key_classifier.map(ClassStatsRecorder::new),
             clocks,
+            config,
+            error_cache,
         }
     }
 
@@ -1168,6 +2317,285 @@ where
             .map(|(key, entry)| KvEntry::new(key, entry))
     }
 
+    fn remove_entry_if<Q>(
+        &self,
+        key: &Q,
+        hash: u64,
+        condition: impl FnMut(&Arc<K>, &TrioArc<ValueEntry<K, V>>) -> bool,
+    ) -> Option<KvEntry<K, V>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.cache.remove_entry_if_and(
+            hash,
+            |k| (k as &K).borrow() == key,
+            condition,
+            |k, entry| KvEntry::new(Arc::clone(k), TrioArc::clone(entry)),
+        )
+    }
+
+    /// Returns up to `max_keys` keys in the access order deque from the position
+    /// of the `cursor`, and advances the `cursor`.
+    ///
+    /// If the entry that the `cursor` points to has been removed from the deque
+    /// since the last call, the snapshot cannot be continued and an empty `Vec` is
+    /// returned.
+    fn next_keys_by_recency(&self, cursor: &mut RecencyCursor<K>, max_keys: usize) -> Vec<Arc<K>>
+    where
+        K: Hash + Eq,
+        S: BuildHasher,
+    {
+        // All entries are currently kept in the probation deque.
+        const CACHE_REGION: CacheRegion = CacheRegion::MainProbation;
+
+        let deqs = self.deques.lock();
+        let deq = &deqs.probation;
+
+        let mut next = match std::mem::replace(cursor, RecencyCursor::Done) {
+            RecencyCursor::Start => deq.peek_front_ptr(),
+            RecencyCursor::After(nodes) => match nodes.lock().access_order_q_node() {
+                // While we hold the deques lock, the node is alive as long as the
+                // entry points to it; the pointer is cleared when the node is
+                // unlinked.
+                Some(tagged_node) => {
+                    let (node, tag) = tagged_node.decompose();
+                    if CacheRegion::from(tag) != CACHE_REGION {
+                        return Vec::new();
+                    }
+                    DeqNode::next_node_ptr(node)
+                }
+                None => return Vec::new(),
+            },
+            RecencyCursor::Done => return Vec::new(),
+        };
+
+        let mut keys = Vec::with_capacity(max_keys.min(deq.len()));
+        let mut last = None;
+        while let Some(node) = next {
+            if keys.len() >= max_keys {
+                break;
+            }
+            let element = &unsafe { node.as_ref() }.element;
+            keys.push(Arc::clone(element.key()));
+            last = Some(element);
+            next = DeqNode::next_node_ptr(node);
+        }
+
+        if let (Some(_), Some(last)) = (next, last) {
+            let key = last.key();
+            if let Some(entry) = self.cache.get(last.hash(), |k| k == key) {
+                *cursor = RecencyCursor::After(TrioArc::clone(entry.deq_nodes()));
+            }
+        }
+
+        keys
+    }
+
+    /// Returns all keys in the cache, ordered by their estimated access frequency
+    /// from the highest to the lowest.
+    fn keys_by_estimated_frequency(&self) -> Vec<Arc<K>>
+    where
+        K: Hash + Eq,
+        S: BuildHasher,
+    {
+        let keys = (0..self.num_cht_segments())
+            .filter_map(|seg| self.cache.keys(seg, |k| (Arc::clone(k), self.hash(k))))
+            .flatten()
+            .collect::<Vec<_>>();
+
+        // Do not hold the lock of the frequency sketch while scanning the `cht`.
+        let mut keys_with_freq = {
+            let freq = &self.frequency_sketch;
+            keys.into_iter()
+                .map(|(key, hash)| (freq.frequency(hash), key))
+                .collect::<Vec<_>>()
+        };
+        keys_with_freq.sort_by(|(f1, _), (f2, _)| f2.cmp(f1));
+        keys_with_freq.into_iter().map(|(_, key)| key).collect()
+    }
+
+    /// Returns up to `n` keys uniformly sampled from the cache without
+    /// replacement. `rng` is called to get random numbers.
+    ///
+    /// When `n` is small compared to the number of entries, this probes random
+    /// buckets of the `cht` instead of scanning it: a `cht` segment is picked in
+    /// proportion to its number of buckets, and then a random bucket in it, so
+    /// every bucket has the same chance to be probed. Since a bucket holds at most
+    /// one entry and the empty buckets are skipped, every entry has the same
+    /// chance to be picked, as long as the `cht` is not resized while sampling.
+    fn sample_keys(&self, n: usize, rng: &mut impl FnMut() -> u64) -> Vec<Arc<K>>
+    where
+        K: Hash + Eq,
+        S: BuildHasher,
+    {
+        let total = self.cache.len();
+        if n == 0 || total == 0 {
+            return Vec::new();
+        }
+
+        // Rejecting the keys already picked will be slow when `n` is close to
+        // `total`. Do reservoir sampling over all keys instead.
+        if n.saturating_mul(2) > total {
+            let mut reservoir = Vec::with_capacity(n);
+            let mut seen = 0u64;
+            for seg in 0..self.num_cht_segments() {
+                for key in self.cache.keys(seg, Arc::clone).unwrap_or_default() {
+                    seen += 1;
+                    if reservoir.len() < n {
+                        reservoir.push(key);
+                    } else {
+                        let i = random_below(rng, seen) as usize;
+                        if i < n {
+                            reservoir[i] = key;
+                        }
+                    }
+                }
+            }
+            return reservoir;
+        }
+
+        let seg_caps = (0..self.num_cht_segments())
+            .map(|seg| self.cache.segment_capacity(seg).unwrap_or_default())
+            .collect::<Vec<_>>();
+        let total_cap = seg_caps.iter().sum::<usize>();
+        if total_cap == 0 {
+            return Vec::new();
+        }
+
+        let mut keys = Vec::with_capacity(n);
+        let mut picked = std::collections::HashSet::with_capacity(n);
+        // The load factor of the `cht` is kept low, so most of the probes should
+        // find an entry. Bound the attempts anyway as other threads may remove
+        // the entries while sampling.
+        let max_attempts = n.saturating_mul(MAX_SAMPLE_ATTEMPTS_PER_KEY);
+        for _ in 0..max_attempts {
+            if keys.len() >= n {
+                break;
+            }
+            let mut r = random_below(rng, total_cap as u64) as usize;
+            let seg = seg_caps
+                .iter()
+                .position(|&cap| {
+                    if r < cap {
+                        true
+                    } else {
+                        r -= cap;
+                        false
+                    }
+                })
+                .unwrap_or_default();
+            // The number of the buckets is a power of two, so taking the modulo of a
+            // random number is not biased.
+            if let Some(key) = self.cache.key_at(seg, rng() as usize, Arc::clone) {
+                if picked.insert(Arc::as_ptr(&key)) {
+                    keys.push(key);
+                }
+            }
+        }
+        keys
+    }
+
+    /// Returns the keys of the entries that are not expired yet but will expire
+    /// within `within` from now, ordered by their expiration time from the
+    /// soonest. Pinned entries never expire and are not returned.
+    ///
+    /// When only the time-to-live is configured, the write order deque is walked
+    /// from the oldest entry and the walk stops at the first entry expiring after
+    /// the window. Otherwise, every entry in the `cht` is checked.
+    fn keys_expiring_within(&self, within: Duration) -> Vec<Arc<K>>
+    where
+        K: Hash + Eq,
+        S: BuildHasher,
+    {
+        let now = self.current_time_from_expiration_clock();
+        let Some(until) = now.checked_add(within) else {
+            return Vec::new();
+        };
+
+        let mut keys_with_deadline =
+            if self.time_to_idle().is_none() && self.expiration_policy.expiry().is_none() {
+                match self.time_to_live() {
+                    Some(ttl) => self.keys_expiring_by_write_order(ttl, now, until),
+                    None => return Vec::new(),
+                }
+            } else {
+                self.cache
+                    .iter()
+                    .filter_map(|(key, entry)| {
+                        self.expiration_deadline(&entry)
+                            .filter(|d| now < *d && *d <= until)
+                            .map(|d| (d, key))
+                    })
+                    .collect()
+            };
+
+        keys_with_deadline.sort_by_key(|(d, _)| *d);
+        keys_with_deadline.into_iter().map(|(_, key)| key).collect()
+    }
+
+    fn keys_expiring_by_write_order(
+        &self,
+        ttl: Duration,
+        now: Instant,
+        until: Instant,
+    ) -> Vec<(Instant, Arc<K>)>
+    where
+        K: Hash + Eq,
+        S: BuildHasher,
+    {
+        let deqs = self.deques.lock();
+        let mut keys = Vec::new();
+        let mut next = deqs.write_order.peek_front_ptr();
+        while let Some(node) = next {
+            next = DeqNode::next_node_ptr(node);
+            let element = &unsafe { node.as_ref() }.element;
+            let Some(lm) = element.last_modified() else {
+                continue;
+            };
+            let Some(deadline) = lm.checked_add(ttl) else {
+                break;
+            };
+            // A dirty node has been updated since it was pushed to the deque, so
+            // its position does not reflect the last modified time. Do not stop at
+            // it but check the entry in the `cht`.
+            if !element.is_dirty() && deadline > until {
+                break;
+            }
+            let key = element.key();
+            if let Some(entry) = self.cache.get(element.hash(), |k| k == key) {
+                if let Some(d) = self.expiration_deadline(&entry) {
+                    if now < d && d <= until {
+                        keys.push((d, Arc::clone(key)));
+                    }
+                }
+            }
+        }
+        keys
+    }
+
+    /// Returns the time when the entry will expire by the TTL, TTI or per-entry
+    /// expiration, whichever comes first. Returns `None` if the entry is pinned or
+    /// never expires.
+    fn expiration_deadline(&self, entry: &TrioArc<ValueEntry<K, V>>) -> Option<Instant> {
+        let info = entry.entry_info();
+        if info.is_pinned() {
+            return None;
+        }
+        let by_ttl = self
+            .time_to_live()
+            .zip(entry.last_modified())
+            .and_then(|(ttl, lm)| lm.checked_add(ttl));
+        let by_tti = self
+            .time_to_idle()
+            .zip(entry.last_accessed())
+            .and_then(|(tti, la)| la.checked_add(tti));
+        [info.expiration_time(), by_ttl, by_tti]
+            .into_iter()
+            .flatten()
+            .min()
+    }
+
     fn keys(&self, cht_segment: usize) -> Option<Vec<Arc<K>>> {
         // Do `Arc::clone` instead of `Arc::downgrade`. Updating existing entry
         // in the cht with a new value replaces the key in the cht even though the
@@ -1190,6 +2618,13 @@ where
         }
     }
 
+    #[inline]
+    fn is_invalidation_predicate_registered(&self, id: &PredicateId) -> bool {
+        self.invalidator
+            .as_ref()
+            .map_or(false, |inv| inv.is_predicate_registered(id))
+    }
+
     /// Returns `true` if the entry is invalidated by `invalidate_entries_if` method.
     #[inline]
     fn is_invalidated_entry(&self, key: &Arc<K>, entry: &TrioArc<ValueEntry<K, V>>) -> bool
@@ -1202,9 +2637,26 @@ where
         false
     }
 
+    /// Returns `true` if the entry is neither expired nor invalidated at `now`.
+    #[inline]
+    fn is_live_entry(&self, key: &Arc<K>, entry: &TrioArc<ValueEntry<K, V>>, now: Instant) -> bool
+    where
+        V: Clone,
+    {
+        let (ttl, tti, va) = (
+            &self.time_to_live(),
+            &self.time_to_idle(),
+            &self.valid_after(),
+        );
+        !is_expired_by_per_entry_ttl(entry.entry_info(), now)
+            && !is_expired_entry_wo(ttl, va, entry, now)
+            && !is_expired_entry_ao(tti, va, entry, now)
+            && !self.is_invalidated_entry(key, entry)
+    }
+
     #[inline]
-    fn weigh(&self, key: &K, value: &V) -> u32 {
-        self.weigher.as_ref().map_or(1, |w| w(key, value))
+    fn weigh(&self, key: &K, value: &V, ctx: &WeighContext) -> u32 {
+        self.weigher.as_ref().map_or(1, |w| w(key, value, ctx))
     }
 }
 
@@ -1273,10 +2725,20 @@ where
         max_log_sync_repeats: u32,
         eviction_batch_size: u32,
     ) -> bool {
+        if let Some(ec) = &self.error_cache {
+            ec.remove_expired(self.current_time_from_expiration_clock());
+        }
+
         if self.max_capacity == Some(0) {
             return false;
         }
 
+        if let Some(watcher) = &self.memory_pressure {
+            if let Some(fraction) = watcher.poll(self.current_time_from_expiration_clock()) {
+                self.set_shed_fraction(fraction);
+            }
+        }
+
         // Acquire some locks.
         let mut deqs = self.deques.lock();
         let mut timer_wheel = self.timer_wheel.lock();
@@ -1288,15 +2750,46 @@ where
         };
         let mut should_process_logs = true;
         let mut calls = 0u32;
+
+        // Adapt the sampling interval to the pressure on the read log at the start
+        // of this run.
+        if let Some(sampler) = &self.read_sampler {
+            sampler.adapt(self.read_op_ch.len(), READ_LOG_CH_SIZE);
+        }
+
         let current_ec = self.entry_count.load();
         let current_ws = self.weighted_size.load();
-        let mut eviction_state =
-            EvictionState::new(current_ec, current_ws, self.removal_notifier.as_ref());
+        let mut eviction_state = EvictionState::new(
+            current_ec,
+            current_ws,
+            self.removal_notifier.as_ref(),
+            &self.event_bus,
+            self.clocks(),
+            self.tenant_quotas.as_ref(),
+            self.entry_lifetimes
+                .as_ref()
+                .map(|l| (l, self.current_time_from_expiration_clock())),
+        );
 
         loop {
+            // While the cache is over capacity, remove the expired, invalidated and
+            // excess entries before admitting the new entries, to shrink the cache
+            // faster.
+            let shrink_first =
+                self.shrink_first && self.weights_to_evict(&eviction_state.counters) > 0;
+            let last_eviction_count = eviction_state.counters.eviction_count;
+            if shrink_first {
+                self.evict_entries(
+                    &mut deqs,
+                    &mut timer_wheel,
+                    eviction_batch_size,
+                    &mut eviction_state,
+                );
+            }
+
             if should_process_logs {
                 let r_len = self.read_op_ch.len();
-                if r_len > 0 {
+                if r_len > 0 && !(shrink_first && self.pause_reads_while_shrinking) {
                     self.apply_reads(&mut deqs, &mut timer_wheel, r_len);
                 }
 
@@ -1314,55 +2807,11 @@ where
                 calls += 1;
             }
 
-            // Set this flag to `false`. The `evict_*` and `invalidate_*` methods
-            // below may set it to `true` if there are more entries to evict in next
-            // loop.
-            eviction_state.more_entries_to_evict = false;
-            let last_eviction_count = eviction_state.counters.eviction_count;
-
-            // Evict entries if there are any expired entries in the hierarchical
-            // timer wheels.
-            if timer_wheel.is_enabled() {
-                self.evict_expired_entries_using_timers(
-                    &mut timer_wheel,
-                    &mut deqs,
-                    &mut eviction_state,
-                );
-            }
-
-            // Evict entries if there are any expired entries in the write order or
-            // access order deques.
-            if self.has_expiry() || self.has_valid_after() {
-                self.evict_expired_entries_using_deqs(
-                    &mut deqs,
-                    &mut timer_wheel,
-                    eviction_batch_size,
-                    &mut eviction_state,
-                );
-            }
-
-            // Evict entries if there are any invalidation predicates set by the
-            // `invalidate_entries_if` method.
-            if let Some(invalidator) = &self.invalidator {
-                if !invalidator.is_empty() {
-                    self.invalidate_entries(
-                        invalidator,
-                        &mut deqs,
-                        &mut timer_wheel,
-                        eviction_batch_size,
-                        &mut eviction_state,
-                    );
-                }
-            }
-
-            // Evict if this cache has more entries than its capacity.
-            let weights_to_evict = self.weights_to_evict(&eviction_state.counters);
-            if weights_to_evict > 0 {
-                self.evict_lru_entries(
+            if !shrink_first {
+                self.evict_entries(
                     &mut deqs,
                     &mut timer_wheel,
                     eviction_batch_size,
-                    weights_to_evict,
                     &mut eviction_state,
                 );
             }
@@ -1400,13 +2849,305 @@ where
         self.entry_count.store(eviction_state.counters.entry_count);
         self.weighted_size
             .store(eviction_state.counters.weighted_size);
+        if let Some(stats) = &self.stats {
+            let count = eviction_state.counters.eviction_count;
+            if count > 0 {
+                stats.record_evictions(count);
+            }
+        }
+
+        // Release the memory left at the high-watermark after a mass removal.
+        if self.should_shrink_to_fit(eviction_state.counters.entry_count)
+            && self.cache.shrink_to_fit(AUTO_SHRINK_FACTOR) > 0
+        {
+            deqs.release_free_nodes();
+        }
 
         crossbeam_epoch::pin().flush();
 
         // Ensure the deqs lock is held until here.
         drop(deqs);
+        drop(timer_wheel);
 
-        eviction_state.more_entries_to_evict
+        let mut more_entries_to_evict = eviction_state.more_entries_to_evict;
+
+        // Evict entries if there are any invalidation predicates set by the
+        // `invalidate_entries_if` method. This takes the locks by itself, so that
+        // they are not held while the predicates are applied.
+        if let Some(invalidator) = &self.invalidator {
+            if !invalidator.is_empty()
+                && self.invalidate_entries(invalidator, eviction_batch_size, timeout, started_at)
+            {
+                more_entries_to_evict = true;
+            }
+        }
+
+        more_entries_to_evict
+    }
+
+    /// Evicts the expired and invalidated entries, and the entries over the tenant
+    /// quotas and the capacity, up to `eviction_batch_size` for each.
+    fn evict_entries(
+        &self,
+        deqs: &mut Deques<K>,
+        timer_wheel: &mut TimerWheel<K>,
+        eviction_batch_size: u32,
+        eviction_state: &mut EvictionState<'_, K, V>,
+    ) where
+        V: Clone,
+    {
+        // Set this flag to `false`. The `evict_*` and `invalidate_*` methods
+        // below may set it to `true` if there are more entries to evict in next
+        // loop.
+        eviction_state.more_entries_to_evict = false;
+
+        // Evict entries if there are any expired entries in the hierarchical
+        // timer wheels.
+        if timer_wheel.is_enabled() {
+            self.evict_expired_entries_using_timers(timer_wheel, deqs, eviction_state);
+        }
+
+        // Evict entries if there are any expired entries in the write order or
+        // access order deques.
+        if self.has_expiry() || self.has_valid_after() {
+            self.evict_expired_entries_using_deqs(
+                deqs,
+                timer_wheel,
+                eviction_batch_size,
+                eviction_state,
+            );
+        }
+
+        // Evict if some tenants have more entries than their quotas.
+        if let Some(quotas) = &self.tenant_quotas {
+            self.evict_tenant_lru_entries(
+                quotas,
+                deqs,
+                timer_wheel,
+                eviction_batch_size,
+                eviction_state,
+            );
+        }
+
+        // Evict if this cache has more entries than its capacity.
+        let weights_to_evict = self.weights_to_evict(&eviction_state.counters);
+        if weights_to_evict > 0 {
+            self.evict_lru_entries(
+                deqs,
+                timer_wheel,
+                eviction_batch_size,
+                weights_to_evict,
+                eviction_state,
+            );
+        }
+    }
+
+    /// Shrinks the internal hash table to fit the number of the entries, and
+    /// deallocates the deque nodes kept for reuse.
+    fn purge_unused_memory(&self) {
+        let mut deqs = self.deques.lock();
+        self.cache.shrink_to_fit(1);
+        deqs.release_free_nodes();
+        drop(deqs);
+
+        crossbeam_epoch::pin().flush();
+    }
+
+    /// Checks the consistency between the `cht`, the deques and the counters.
+    #[cfg(debug_assertions)]
+    fn validate_invariants(&self) -> InvariantReport<K> {
+        let deqs = self.deques.lock();
+        let entry_count = self.entry_count.load();
+        let weighted_size = self.weighted_size.load();
+
+        let mut violations = Vec::new();
+        let mut table_len = 0;
+        let mut admitted_count = 0u64;
+        let mut admitted_weight = 0u64;
+        let mut ao_linked = 0u64;
+        let mut wo_linked = 0u64;
+
+        for (key, entry) in self.cache.iter() {
+            table_len += 1;
+            // While we hold the deques lock, the nodes are alive as long as the
+            // entry points to them.
+            let ao_node = entry.access_order_q_node().filter(|tagged_node| {
+                let (node, tag) = tagged_node.decompose();
+                let deq = match CacheRegion::from(tag) {
+                    CacheRegion::Window => &deqs.window,
+                    CacheRegion::MainProbation => &deqs.probation,
+                    CacheRegion::MainProtected => &deqs.protected,
+                    CacheRegion::Other => return false,
+                };
+                let node = unsafe { node.as_ref() };
+                deq.contains(node) && node.element.key() == &key
+            });
+            if ao_node.is_some() {
+                ao_linked += 1;
+            }
+
+            if entry.is_admitted() {
+                admitted_count += 1;
+                admitted_weight += entry.policy_weight() as u64;
+                if ao_node.is_none() {
+                    violations.push(InvariantViolation::NotInAccessOrderDeque {
+                        key: Arc::clone(&key),
+                    });
+                }
+            } else if ao_node.is_some() {
+                violations.push(InvariantViolation::UnadmittedInAccessOrderDeque {
+                    key: Arc::clone(&key),
+                });
+            }
+
+            if let Some(node) = entry.write_order_q_node() {
+                let node = unsafe { node.as_ref() };
+                if deqs.write_order.contains(node) && node.element.key() == &key {
+                    wo_linked += 1;
+                }
+            }
+        }
+
+        if entry_count != admitted_count {
+            violations.push(InvariantViolation::EntryCountMismatch {
+                recorded: entry_count,
+                actual: admitted_count,
+            });
+        }
+        if weighted_size != admitted_weight {
+            violations.push(InvariantViolation::WeightedSizeMismatch {
+                recorded: weighted_size,
+                actual: admitted_weight,
+            });
+        }
+        let ao_len = deqs.window.len() + deqs.probation.len() + deqs.protected.len();
+        if ao_len as u64 != ao_linked {
+            violations.push(InvariantViolation::AccessOrderDequeLenMismatch {
+                deque_len: ao_len,
+                linked: ao_linked,
+            });
+        }
+        if deqs.write_order.len() as u64 != wo_linked {
+            violations.push(InvariantViolation::WriteOrderDequeLenMismatch {
+                deque_len: deqs.write_order.len(),
+                linked: wo_linked,
+            });
+        }
+
+        InvariantReport {
+            entry_count,
+            weighted_size,
+            table_len,
+            admitted_count,
+            violations,
+        }
+    }
+}
+
+impl<K, V, S> Inner<K, V, S>
+where
+    K: Hash + Eq + Send + Sync + 'static,
+    V: Clone + Send + Sync + 'static,
+    S: BuildHasher + Clone + Send + Sync + 'static,
+{
+    /// Removes all entries from the cache, and calls `f` with each live entry.
+    ///
+    /// The pending read and write operations are applied first, so that the
+    /// deques and the timer wheel reflect all entries, and the channels no longer
+    /// hold references to them. Entries that have already expired or been
+    /// invalidated are not passed to `f` but notified to the eviction listener.
+    fn remove_all_entries(&self, mut f: impl FnMut(Arc<K>, TrioArc<ValueEntry<K, V>>)) {
+        // Acquire some locks.
+        let mut deqs = self.deques.lock();
+        let mut timer_wheel = self.timer_wheel.lock();
+
+        let current_ec = self.entry_count.load();
+        let current_ws = self.weighted_size.load();
+        let mut eviction_state = EvictionState::new(
+            current_ec,
+            current_ws,
+            self.removal_notifier.as_ref(),
+            &self.event_bus,
+            self.clocks(),
+            self.tenant_quotas.as_ref(),
+            self.entry_lifetimes
+                .as_ref()
+                .map(|l| (l, self.current_time_from_expiration_clock())),
+        );
+
+        let r_len = self.read_op_ch.len();
+        if r_len > 0 {
+            self.apply_reads(&mut deqs, &mut timer_wheel, r_len);
+        }
+
+        let w_len = self.write_op_ch.len();
+        if w_len > 0 {
+            self.apply_writes(&mut deqs, &mut timer_wheel, w_len, &mut eviction_state);
+        }
+
+        let now = self.current_time_from_expiration_clock();
+        let (ttl, tti, va) = (
+            &self.time_to_live(),
+            &self.time_to_idle(),
+            &self.valid_after(),
+        );
+
+        for cht_seg in 0..self.num_cht_segments() {
+            let Some(keys) = self.keys(cht_seg) else {
+                continue;
+            };
+
+            for key in keys {
+                let hash = self.hash(&key);
+
+                // Lock the key for removal if blocking removal notification is
+                // enabled.
+                let kl = self.maybe_key_lock(&key);
+                let _klg = &kl.as_ref().map(|kl| kl.lock());
+
+                let Some((key, entry)) = self.cache.remove_entry(hash, |k| k == &key) else {
+                    continue;
+                };
+
+                let cause = if is_expired_by_per_entry_ttl(entry.entry_info(), now)
+                    || is_expired_entry_wo(ttl, va, &entry, now)
+                    || is_expired_entry_ao(tti, va, &entry, now)
+                {
+                    Some(RemovalCause::Expired)
+                } else if self.is_invalidated_entry(&key, &entry) {
+                    Some(RemovalCause::Explicit)
+                } else {
+                    None
+                };
+
+                Self::handle_remove(
+                    &mut deqs,
+                    &mut timer_wheel,
+                    TrioArc::clone(&entry),
+                    None,
+                    &mut eviction_state.counters,
+                );
+
+                match cause {
+                    None => f(key, entry),
+                    Some(cause) if eviction_state.is_notifier_enabled() => {
+                        eviction_state.notify_entry_removal(key, &entry, cause);
+                    }
+                    Some(_) => (),
+                }
+            }
+        }
+
+        debug_assert_eq!(self.entry_count.load(), current_ec);
+        debug_assert_eq!(self.weighted_size.load(), current_ws);
+        self.entry_count.store(eviction_state.counters.entry_count);
+        self.weighted_size
+            .store(eviction_state.counters.weighted_size);
+
+        crossbeam_epoch::pin().flush();
+
+        // Ensure the deqs lock is held until here.
+        drop(deqs);
     }
 }
 
@@ -1419,20 +3160,39 @@ where
     V: Send + Sync + 'static,
     S: BuildHasher + Clone + Send + Sync + 'static,
 {
-    fn has_enough_capacity(&self, candidate_weight: u32, counters: &EvictionCounters) -> bool {
-        self.max_capacity.map_or(true, |limit| {
+    /// Returns the max capacity minus the weight shed by the `shed` method or the
+    /// memory pressure watcher.
+    fn effective_max_capacity(&self) -> Option<u64> {
+        self.max_capacity
+            .map(|max_cap| max_cap.saturating_sub(self.shed_weight.load()))
+    }
+
+    fn has_enough_capacity(
+        &self,
+        candidate_weight: u32,
+        counters: &EvictionCounters<'_, K>,
+    ) -> bool {
+        self.effective_max_capacity().map_or(true, |limit| {
             counters.weighted_size + candidate_weight as u64 <= limit
         })
     }
 
-    fn weights_to_evict(&self, counters: &EvictionCounters) -> u64 {
-        self.max_capacity
+    fn weights_to_evict(&self, counters: &EvictionCounters<'_, K>) -> u64 {
+        self.effective_max_capacity()
             .map(|limit| counters.weighted_size.saturating_sub(limit))
             .unwrap_or_default()
     }
 
+    /// Sheds the `fraction` of the max capacity. `0.0` restores the full capacity.
+    fn set_shed_fraction(&self, fraction: f64) {
+        if let Some(max_cap) = self.max_capacity {
+            let fraction = fraction.clamp(0.0, 1.0);
+            self.shed_weight.store((max_cap as f64 * fraction) as u64);
+        }
+    }
+
     #[inline]
-    fn should_enable_frequency_sketch(&self, counters: &EvictionCounters) -> bool {
+    fn should_enable_frequency_sketch(&self, counters: &EvictionCounters<'_, K>) -> bool {
         match self.max_capacity {
             None | Some(0) => false,
             Some(max_cap) => {
@@ -1446,7 +3206,7 @@ where
     }
 
     #[inline]
-    fn enable_frequency_sketch(&self, counters: &EvictionCounters) {
+    fn enable_frequency_sketch(&self, counters: &EvictionCounters<'_, K>) {
         if let Some(max_cap) = self.max_capacity {
             let c = counters;
             let cap = if self.weigher.is_none() {
@@ -1468,13 +3228,13 @@ where
     #[inline]
     fn do_enable_frequency_sketch(&self, cache_capacity: u64) {
         let skt_capacity = common::sketch_capacity(cache_capacity);
-        self.frequency_sketch.write().ensure_capacity(skt_capacity);
+        self.frequency_sketch.ensure_capacity(skt_capacity);
         self.frequency_sketch_enabled.store(true, Ordering::Release);
     }
 
     fn apply_reads(&self, deqs: &mut Deques<K>, timer_wheel: &mut TimerWheel<K>, count: usize) {
         use ReadOp::{Hit, Miss};
-        let mut freq = self.frequency_sketch.write();
+        let freq = &self.frequency_sketch;
         let ch = &self.read_op_ch;
         for _ in 0..count {
             match ch.try_recv() {
@@ -1484,12 +3244,26 @@ where
                 }) => {
                     let kh = value_entry.entry_info().key_hash();
                     freq.increment(kh.hash);
+                    if let Some(hot_keys) = &self.hot_keys {
+                        hot_keys.notify_if_hot(&value_entry, freq.frequency(kh.hash));
+                    }
+                    if let Some(analyzer) = &self.hit_ratio_analyzer {
+                        analyzer.record_read(kh.hash, true);
+                    }
                     if is_expiry_modified {
                         self.update_timer_wheel(&value_entry, timer_wheel);
                     }
                     deqs.move_to_back_ao(&value_entry);
                 }
-                Ok(Miss(hash)) => freq.increment(hash),
+                Ok(Miss(hash)) => {
+                    freq.increment(hash);
+                    if let Some(analyzer) = &self.hit_ratio_analyzer {
+                        analyzer.record_read(hash, false);
+                    }
+                    if let Some(ghosts) = &self.ghost_list {
+                        ghosts.record_miss(hash);
+                    }
+                }
                 Err(_) => break,
             }
         }
@@ -1505,7 +3279,7 @@ where
         V: Clone,
     {
         use WriteOp::{Remove, Upsert};
-        let freq = self.frequency_sketch.read();
+        let freq = &self.frequency_sketch;
         let ch = &self.write_op_ch;
 
         for _ in 0..count {
@@ -1524,7 +3298,7 @@ where
                     new_weight,
                     deqs,
                     timer_wheel,
-                    &freq,
+                    freq,
                     eviction_state,
                 ),
                 Ok(Remove {
@@ -1554,7 +3328,7 @@ where
         new_weight: u32,
         deqs: &mut Deques<K>,
         timer_wheel: &mut TimerWheel<K>,
-        freq: &FrequencySketch,
+        freq: &SegmentedFrequencySketch,
         eviction_state: &mut EvictionState<'_, K, V>,
     ) where
         V: Clone,
@@ -1566,6 +3340,7 @@ where
                 // The entry has been already admitted, so treat this as an update.
                 counters.saturating_sub(0, old_weight);
                 counters.saturating_add(0, new_weight);
+                counters.update_tenant_weight(&kh.key, old_weight, new_weight);
                 self.update_timer_wheel(&entry, timer_wheel);
                 deqs.move_to_back_ao(&entry);
                 deqs.move_to_back_wo(&entry);
@@ -1573,7 +3348,9 @@ where
                 return;
             }
 
-            if self.has_enough_capacity(new_weight, counters) {
+            // With the tenant quotas, the room of the tenant is checked below
+            // instead.
+            if self.tenant_quotas.is_none() && self.has_enough_capacity(new_weight, counters) {
                 // There are enough room in the cache (or the cache is unbounded).
                 // Add the candidate to the deques.
                 self.handle_admit(&entry, new_weight, deqs, timer_wheel, counters);
@@ -1582,7 +3359,11 @@ where
             }
         }
 
-        if let Some(max) = self.max_capacity {
+        let max_weight = match &self.tenant_quotas {
+            Some(quotas) => Some(quotas.quota_of(&kh.key)),
+            None => self.max_capacity,
+        };
+        if let Some(max) = max_weight {
             if new_weight as u64 > max {
                 // The candidate is too big to fit in the cache. Reject it.
 
@@ -1604,6 +3385,7 @@ where
                         eviction_state.notify_entry_removal(key, &entry, RemovalCause::Size);
                     }
                     eviction_state.counters.incr_eviction_count();
+                    self.remember_evicted(&entry);
                 }
                 entry.entry_info().set_policy_gen(gen);
                 return;
@@ -1614,13 +3396,28 @@ where
         // https://github.com/moka-rs/moka/issues/389
 
         // Try to admit the candidate.
-        let admission_result = match &self.eviction_policy {
-            EvictionPolicyConfig::TinyLfu => {
+        let admission_result = match (&self.eviction_policy, &self.tenant_quotas) {
+            (EvictionPolicyConfig::TinyLfu, quotas) => {
                 let mut candidate = EntrySizeAndFrequency::new(new_weight);
                 candidate.add_frequency(freq, kh.hash);
-                Self::admit(&candidate, &self.cache, deqs, freq)
+                if self.second_chance_readmission && self.was_recently_evicted(kh.hash) {
+                    // The candidate has returned soon after it was evicted. Favor it
+                    // over the victims, which may be evicted and return in turn when
+                    // the working set is slightly larger than the cache.
+                    candidate.freq += SECOND_CHANCE_FREQUENCY_BOOST;
+                }
+                match quotas {
+                    Some(quotas) => Self::admit_to_tenant(&candidate, &kh.key, quotas, deqs, freq),
+                    None => Self::admit(
+                        &candidate,
+                        &self.cache,
+                        deqs,
+                        freq,
+                        self.victim_selector.as_ref(),
+                    ),
+                }
             }
-            EvictionPolicyConfig::Lru => AdmissionResult::Admitted {
+            (EvictionPolicyConfig::Lru, _) => AdmissionResult::Admitted {
                 victim_keys: SmallVec::default(),
             },
         };
@@ -1650,6 +3447,7 @@ where
                             );
                         }
                         eviction_state.counters.incr_eviction_count();
+                        self.remember_evicted(&vic_entry);
                         // And then remove the victim from the deques.
                         Self::handle_remove(
                             deqs,
@@ -1702,6 +3500,7 @@ where
                         eviction_state.notify_entry_removal(key, &entry, RemovalCause::Size);
                     }
                     eviction_state.counters.incr_eviction_count();
+                    self.remember_evicted(&entry);
                 }
             }
         };
@@ -1729,7 +3528,8 @@ where
         candidate: &EntrySizeAndFrequency,
         cache: &CacheStore<K, V, S>,
         deqs: &mut Deques<K>,
-        freq: &FrequencySketch,
+        freq: &SegmentedFrequencySketch,
+        victim_selector: Option<&VictimSelector<K, V>>,
     ) -> AdmissionResult<K> {
         const MAX_CONSECUTIVE_RETRIES: usize = 5;
         let mut retries = 0;
@@ -1754,8 +3554,9 @@ where
             next_victim = DeqNode::next_node_ptr(victim);
 
             let vic_elem = &unsafe { victim.as_ref() }.element;
-            if vic_elem.is_dirty() {
-                // Skip this node as its ValueEntry have been updated or invalidated.
+            if vic_elem.is_dirty() || vic_elem.entry_info().is_pinned() {
+                // Skip this node as its ValueEntry have been updated or invalidated,
+                // or it is pinned.
                 unsafe { deq.move_to_back(victim) };
                 retries += 1;
                 continue;
@@ -1766,6 +3567,22 @@ where
             let last_accessed = vic_elem.entry_info().last_accessed();
 
             if let Some(vic_entry) = cache.get(hash, |k| k == key) {
+                match victim_selector.map_or(VictimDecision::Evict, |select| {
+                    select(key, &vic_entry.value)
+                }) {
+                    VictimDecision::Evict => (),
+                    VictimDecision::Keep => {
+                        // Skip this node but leave it at its position.
+                        retries += 1;
+                        continue;
+                    }
+                    VictimDecision::Defer => {
+                        // Skip this node and move it to the MRU position.
+                        unsafe { deq.move_to_back(victim) };
+                        retries += 1;
+                        continue;
+                    }
+                }
                 victims.add_policy_weight(vic_entry.policy_weight());
                 victims.add_frequency(freq, hash);
                 victim_keys.push((KeyHash::new(Arc::clone(key), hash), last_accessed));
@@ -1791,15 +3608,78 @@ where
         }
     }
 
+    /// Performs the admission of a candidate to its tenant. The candidate is
+    /// admitted as is while its tenant has room for it. Otherwise, it has to have a
+    /// higher frequency than the least recently used entries of its tenant, so
+    /// that the entries of the other tenants are never evicted for it. The victims
+    /// are evicted later by `evict_tenant_lru_entries`, so no victim keys are
+    /// returned.
+    ///
+    /// Up to `MAX_TENANT_WALK` nodes at the LRU positions of the probation, window
+    /// and protected regions are examined in this order. If not enough victims of
+    /// the tenant are found there, the candidate is rejected, so that the tenant
+    /// never grows past its quota.
+    #[inline]
+    fn admit_to_tenant(
+        candidate: &EntrySizeAndFrequency,
+        key: &K,
+        quotas: &TenantQuotas<K>,
+        deqs: &Deques<K>,
+        freq: &SegmentedFrequencySketch,
+    ) -> AdmissionResult<K> {
+        let admitted = AdmissionResult::Admitted {
+            victim_keys: SmallVec::default(),
+        };
+        let tenant = quotas.tenant_of(key);
+        if quotas.has_room_for(tenant, candidate.policy_weight) {
+            return admitted;
+        }
+
+        let mut victims = EntrySizeAndFrequency::default();
+        let mut walked = 0;
+        'regions: for deq in [&deqs.probation, &deqs.window, &deqs.protected] {
+            let mut next = deq.peek_front_ptr();
+            while victims.policy_weight < candidate.policy_weight && victims.freq <= candidate.freq
+            {
+                if walked >= MAX_TENANT_WALK {
+                    break 'regions;
+                }
+                let Some(node) = next else {
+                    break;
+                };
+                next = DeqNode::next_node_ptr(node);
+                walked += 1;
+
+                let element = &unsafe { node.as_ref() }.element;
+                let info = element.entry_info();
+                if info.is_dirty() || info.is_pinned() || quotas.tenant_of(element.key()) != tenant
+                {
+                    continue;
+                }
+                victims.add_policy_weight(info.policy_weight());
+                victims.add_frequency(freq, element.hash());
+            }
+        }
+
+        // Reject the candidate also when not enough victims of the tenant were
+        // found, rather than letting the tenant take the room of the others.
+        if victims.policy_weight >= candidate.policy_weight && candidate.freq > victims.freq {
+            admitted
+        } else {
+            AdmissionResult::Rejected
+        }
+    }
+
     fn handle_admit(
         &self,
         entry: &TrioArc<ValueEntry<K, V>>,
         policy_weight: u32,
         deqs: &mut Deques<K>,
         timer_wheel: &mut TimerWheel<K>,
-        counters: &mut EvictionCounters,
+        counters: &mut EvictionCounters<'_, K>,
     ) {
         counters.saturating_add(1, policy_weight);
+        counters.add_tenant_entry(&entry.entry_info().key_hash().key, policy_weight);
 
         self.update_timer_wheel(entry, timer_wheel);
 
@@ -1869,7 +3749,7 @@ where
         timer_wheel: &mut TimerWheel<K>,
         entry: TrioArc<ValueEntry<K, V>>,
         gen: Option<u16>,
-        counters: &mut EvictionCounters,
+        counters: &mut EvictionCounters<'_, K>,
     ) {
         if let Some(timer_node) = entry.take_timer_node() {
             timer_wheel.deschedule(timer_node);
@@ -1881,11 +3761,13 @@ where
         deqs: &mut Deques<K>,
         entry: TrioArc<ValueEntry<K, V>>,
         gen: Option<u16>,
-        counters: &mut EvictionCounters,
+        counters: &mut EvictionCounters<'_, K>,
     ) {
         if entry.is_admitted() {
             entry.set_admitted(false);
             counters.saturating_sub(1, entry.policy_weight());
+            counters.sub_tenant_entry(&entry.entry_info().key_hash().key, entry.policy_weight());
+            counters.record_lifetime(entry.entry_info());
             // The following two unlink_* functions will unset the deq nodes.
             deqs.unlink_ao(&entry);
             Deques::unlink_wo(&mut deqs.write_order, &entry);
@@ -1903,7 +3785,7 @@ where
         wo_deq: &mut Deque<KeyHashDate<K>>,
         timer_wheel: &mut TimerWheel<K>,
         entry: TrioArc<ValueEntry<K, V>>,
-        counters: &mut EvictionCounters,
+        counters: &mut EvictionCounters<'_, K>,
     ) {
         if let Some(timer) = entry.take_timer_node() {
             timer_wheel.deschedule(timer);
@@ -1911,6 +3793,8 @@ where
         if entry.is_admitted() {
             entry.set_admitted(false);
             counters.saturating_sub(1, entry.policy_weight());
+            counters.sub_tenant_entry(&entry.entry_info().key_hash().key, entry.policy_weight());
+            counters.record_lifetime(entry.entry_info());
             // The following two unlink_* functions will unset the deq nodes.
             Deques::unlink_ao_from_deque(ao_deq_name, ao_deq, &entry);
             Deques::unlink_wo(wo_deq, &entry);
@@ -1942,6 +3826,9 @@ where
         //      described above.
         //    - When necessary, a new timer node will be recreated for the current or
         //      new `ValueEntry` when its `WriteOp` or `ReadOp` is processed.
+        // 3. If an entry is pinned, we will reschedule it so that it will be checked
+        //    again after it is unpinned.
+        let mut pinned_entries = Vec::new();
         for event in timer_wheel.advance(now) {
             // We do not have to do anything if event is `TimerEvent::Descheduled(_)`
             // or `TimerEvent::Rescheduled(_)`.
@@ -1958,6 +3845,13 @@ where
                 let key = &kh.key;
                 let hash = kh.hash;
 
+                if entry_info.is_pinned() {
+                    if let Some(entry) = self.cache.get(hash, |k| k == key) {
+                        pinned_entries.push(entry);
+                    }
+                    continue;
+                }
+
                 // Lock the key for removal if blocking removal notification is
                 // enabled.
                 let kl = self.maybe_key_lock(key);
@@ -1988,6 +3882,10 @@ where
                 }
             }
         }
+
+        for entry in pinned_entries {
+            self.update_timer_wheel(&entry, timer_wheel);
+        }
     }
 
     fn evict_expired_entries_using_deqs(
@@ -2139,6 +4037,29 @@ where
         }
     }
 
+    /// Similar to `skip_updated_entry_ao`, but the node of the entry does not
+    /// have to be at the front of the deque.
+    fn skip_updated_node_ao(
+        cache: &CacheStore<K, V, S>,
+        key: &K,
+        hash: u64,
+        node: NonNull<DeqNode<KeyHashDate<K>>>,
+        deq_name: &str,
+        deq: &mut Deque<KeyHashDate<K>>,
+        write_order_deq: &mut Deque<KeyHashDate<K>>,
+    ) {
+        if let Some(entry) = cache.get(hash, |k| (k.borrow() as &K) == key) {
+            Deques::move_to_back_ao_in_deque(deq_name, deq, &entry);
+            if entry.is_dirty() {
+                Deques::move_to_back_wo_in_deque(write_order_deq, &entry);
+            }
+        } else {
+            // The invalidated ValueEntry still has a pointer to this node, so move
+            // the node instead of dropping it.
+            unsafe { deq.move_to_back(node) };
+        }
+    }
+
     #[inline]
     fn skip_updated_entry_wo(&self, key: &K, hash: u64, deqs: &mut Deques<K>) {
         if let Some(entry) = self.cache.get(hash, |k| (k.borrow() as &K) == key) {
@@ -2234,66 +4155,124 @@ where
         }
     }
 
+    /// Applies the invalidation predicates to the entries, `batch_size` entries at
+    /// a time. Returns `true` if there are predicates left to apply.
+    ///
+    /// Only the snapshot of the candidate keys and their last modified times is
+    /// taken while holding the deques lock. The predicates are applied with the
+    /// deques and timer wheel locks released, so a long scan does not block the
+    /// other users of the deques. The invalidated entries have been removed from
+    /// the hash table by then, so they are only unlinked from the deques and the
+    /// timer wheel afterwards.
     fn invalidate_entries(
         &self,
         invalidator: &Invalidator<K, V, S>,
-        deqs: &mut Deques<K>,
-        timer_wheel: &mut TimerWheel<K>,
         batch_size: u32,
-        eviction_state: &mut EvictionState<'_, K, V>,
-    ) where
+        timeout: Option<Duration>,
+        started_at: Option<Instant>,
+    ) -> bool
+    where
         V: Clone,
     {
-        let now = self.current_time_from_expiration_clock();
+        while let Some((candidates, is_truncated)) =
+            self.invalidation_candidates(invalidator, batch_size)
+        {
+            let (invalidated, is_done) =
+                invalidator.scan_and_invalidate(self, candidates, is_truncated);
+            let invalidated_any = !invalidated.is_empty();
+            self.remove_invalidated_entries(invalidated, is_done);
+
+            // Continue only while the predicates are invalidating some entries, in
+            // the same way as the eviction loop of `do_run_pending_tasks`.
+            if invalidator.is_empty() || !invalidated_any {
+                break;
+            }
+            if let (Some(to), Some(started)) = (timeout, started_at) {
+                let elapsed = self
+                    .current_time_from_expiration_clock()
+                    .checked_duration_since(started)
+                    .expect("Arithmetic overflow occurred on calculating the elapse time");
+                if elapsed >= to {
+                    break;
+                }
+            }
+        }
+
+        !invalidator.is_empty()
+    }
+
+    /// Takes a snapshot of up to `batch_size` keys to apply the invalidation
+    /// predicates, from the write order deque. Returns `None` if there are no
+    /// candidates. Otherwise, returns the candidates and whether there are more
+    /// entries after them.
+    fn invalidation_candidates(
+        &self,
+        invalidator: &Invalidator<K, V, S>,
+        batch_size: u32,
+    ) -> Option<(Vec<KeyDateLite<K>>, bool)>
+    where
+        V: Clone,
+    {
+        let mut deqs = self.deques.lock();
 
         // If the write order queue is empty, we are done and can remove the predicates
         // that have been registered by now.
         if deqs.write_order.len() == 0 {
+            let now = self.current_time_from_expiration_clock();
             invalidator.remove_predicates_registered_before(now);
-            return;
+            return None;
         }
 
         let mut candidates = Vec::new();
         let mut len = 0;
-        let has_next;
-        {
-            let iter = &mut deqs.write_order.peekable();
-
-            while len < batch_size {
-                if let Some(kd) = iter.next() {
-                    if !kd.is_dirty() {
-                        if let Some(ts) = kd.last_modified() {
-                            let key = kd.key();
-                            let hash = self.hash(key);
-                            candidates.push(KeyDateLite::new(key, hash, ts));
-                            len += 1;
-                        }
+        let iter = &mut deqs.write_order.peekable();
+
+        while len < batch_size {
+            if let Some(kd) = iter.next() {
+                if !kd.is_dirty() {
+                    if let Some(ts) = kd.last_modified() {
+                        let key = kd.key();
+                        let hash = self.hash(key);
+                        candidates.push(KeyDateLite::new(key, hash, ts));
+                        len += 1;
                     }
-                } else {
-                    break;
                 }
+            } else {
+                break;
             }
-
-            has_next = iter.peek().is_some();
         }
 
         if len == 0 {
-            return;
+            return None;
         }
 
-        let is_truncated = len == batch_size && has_next;
-        let (invalidated, is_done) =
-            invalidator.scan_and_invalidate(self, candidates, is_truncated);
+        let is_truncated = len == batch_size && iter.peek().is_some();
+        Some((candidates, is_truncated))
+    }
+
+    /// Unlinks the entries invalidated by the predicates from the deques and the
+    /// timer wheel, and updates the entry count and weighted size.
+    fn remove_invalidated_entries(&self, invalidated: Vec<KvEntry<K, V>>, is_done: bool) {
+        let mut deqs = self.deques.lock();
+        let mut timer_wheel = self.timer_wheel.lock();
 
+        let mut counters = EvictionCounters::new(
+            self.entry_count.load(),
+            self.weighted_size.load(),
+            self.tenant_quotas.as_ref(),
+            self.entry_lifetimes
+                .as_ref()
+                .map(|l| (l, self.current_time_from_expiration_clock())),
+        );
         for KvEntry { key: _key, entry } in invalidated {
-            Self::handle_remove(deqs, timer_wheel, entry, None, &mut eviction_state.counters);
+            Self::handle_remove(&mut deqs, &mut timer_wheel, entry, None, &mut counters);
         }
         if is_done {
             deqs.write_order.reset_cursor();
         }
-        if !invalidator.is_empty() {
-            eviction_state.more_entries_to_evict = true;
-        }
+
+        self.entry_count.store(counters.entry_count);
+        self.weighted_size.store(counters.weighted_size);
     }
 
     fn evict_lru_entries(
@@ -2312,44 +4291,74 @@ where
         let mut evicted = 0u64;
         let mut more_to_evict = true;
 
+        // Walk the deque from the LRU position instead of always taking the front
+        // node, so that the entries kept by the victim selector stay at their
+        // positions. The other skipped entries are moved to the back.
+        let mut next_node = ao_deq.peek_front_ptr();
+
         for _ in 0..batch_size {
             if evicted >= weights_to_evict {
                 more_to_evict = false;
                 break;
             }
 
-            let maybe_key_hash_ts = ao_deq.peek_front().map(|node| {
-                let entry_info = node.element.entry_info();
-                (
-                    Arc::clone(node.element.key()),
-                    node.element.hash(),
-                    entry_info.is_dirty(),
-                    entry_info.last_accessed(),
-                )
-            });
+            let Some(node) = next_node.take() else {
+                more_to_evict = false;
+                break;
+            };
+            next_node = DeqNode::next_node_ptr(node);
+
+            let element = &unsafe { node.as_ref() }.element;
+            let entry_info = element.entry_info();
+            if entry_info.is_pinned() {
+                // Skip this entry as it is pinned.
+                unsafe { ao_deq.move_to_back(node) };
+                continue;
+            }
 
-            let (key, hash, ts) = match maybe_key_hash_ts {
-                Some((key, hash, false, Some(ts))) => (key, hash, ts),
-                // TODO: Remove the second pattern `Some((_key, false, None))` once we change
+            let key = Arc::clone(element.key());
+            let hash = element.hash();
+            let ts = match (entry_info.is_dirty(), entry_info.last_accessed()) {
+                (false, Some(ts)) => ts,
+                // TODO: Remove the second pattern `(false, None)` once we change
                 // `last_modified` and `last_accessed` in `EntryInfo` from `Option<Instant>` to
                 // `Instant`.
-                Some((key, hash, true, _) | (key, hash, false, None)) => {
+                (true, _) | (false, None) => {
                     // `is_dirty` is true or `last_modified` is None. Skip this entry
                     // as it may have been updated by this or other async task but
                     // its `WriteOp` is not processed yet.
-                    self.skip_updated_entry_ao(&key, hash, deq_name, ao_deq, wo_deq);
+                    Self::skip_updated_node_ao(
+                        &self.cache,
+                        &key,
+                        hash,
+                        node,
+                        deq_name,
+                        ao_deq,
+                        wo_deq,
+                    );
                     // Set `more_to_evict` to `false` to make `run_pending_tasks` to
                     // return early. This will help that `schedule_write_op` to send
                     // the `WriteOp` to the write op channel.
                     more_to_evict = false;
                     continue;
                 }
-                None => {
-                    more_to_evict = false;
-                    break;
-                }
             };
 
+            if let Some(select) = &self.victim_selector {
+                if let Some(entry) = self.cache.get(hash, |k| k == &key) {
+                    match select(&key, &entry.value) {
+                        VictimDecision::Evict => (),
+                        // Skip this entry but leave it at its position.
+                        VictimDecision::Keep => continue,
+                        VictimDecision::Defer => {
+                            // Skip this entry and move it to the MRU position.
+                            unsafe { ao_deq.move_to_back(node) };
+                            continue;
+                        }
+                    }
+                }
+            }
+
             // Lock the key for removal if blocking removal notification is enabled.
             let kl = self.maybe_key_lock(&key);
             let _klg = &kl.as_ref().map(|kl| kl.lock());
@@ -2359,7 +4368,7 @@ where
                 |k| k == &key,
                 |_, v| {
                     if let Some(la) = v.last_accessed() {
-                        la == ts
+                        la == ts && !v.entry_info().is_pinned()
                     } else {
                         false
                     }
@@ -2371,6 +4380,7 @@ where
                     eviction_state.notify_entry_removal(key, &entry, RemovalCause::Size);
                 }
                 eviction_state.counters.incr_eviction_count();
+                self.remember_evicted(&entry);
                 let weight = entry.policy_weight();
                 Self::handle_remove_with_deques(
                     deq_name,
@@ -2382,7 +4392,7 @@ where
                 );
                 evicted = evicted.saturating_add(weight as u64);
             } else {
-                self.skip_updated_entry_ao(&key, hash, deq_name, ao_deq, wo_deq);
+                Self::skip_updated_node_ao(&self.cache, &key, hash, node, deq_name, ao_deq, wo_deq);
                 more_to_evict = false;
             }
         }
@@ -2391,6 +4401,111 @@ where
             eviction_state.more_entries_to_evict = true;
         }
     }
+
+    /// Evicts the least recently used entries of the tenants exceeding their
+    /// quotas or max numbers of entries, without touching the entries of the other
+    /// tenants.
+    fn evict_tenant_lru_entries(
+        &self,
+        quotas: &TenantQuotas<K>,
+        deqs: &mut Deques<K>,
+        timer_wheel: &mut TimerWheel<K>,
+        batch_size: u32,
+        eviction_state: &mut EvictionState<'_, K, V>,
+    ) where
+        V: Clone,
+    {
+        // The excess weights and the excess numbers of entries of the tenants.
+        let mut excesses = (0..quotas.num_tenants())
+            .map(|tenant| (quotas.excess(tenant), quotas.excess_entries(tenant)))
+            .collect::<Vec<_>>();
+        let is_over = |&(weight, count): &(u64, u64)| weight > 0 || count > 0;
+        if !excesses.iter().any(is_over) {
+            return;
+        }
+
+        // Collect the victims from the LRU positions of the probation, window and
+        // protected regions in this order. Unlike `evict_lru_entries`, we walk the
+        // deques instead of rotating them, so that the order of the entries of the
+        // other tenants is not changed. The walk is bounded by `MAX_TENANT_WALK`
+        // nodes in total.
+        let mut victims = Vec::new();
+        // The numbers of the nodes skipped at the front of each region.
+        let mut skipped = [0; 3];
+        let mut has_more = false;
+        'regions: for (i, deq) in [&deqs.probation, &deqs.window, &deqs.protected]
+            .into_iter()
+            .enumerate()
+        {
+            let mut next = deq.peek_front_ptr();
+            while let Some(node) = next {
+                if victims.len() >= batch_size as usize
+                    || victims.len() + skipped.iter().sum::<usize>() >= MAX_TENANT_WALK
+                {
+                    has_more = true;
+                    break 'regions;
+                }
+                next = DeqNode::next_node_ptr(node);
+
+                let element = &unsafe { node.as_ref() }.element;
+                let entry_info = element.entry_info();
+                let tenant = quotas.tenant_of(element.key());
+                match entry_info.last_accessed() {
+                    Some(ts)
+                        if !entry_info.is_dirty()
+                            && !entry_info.is_pinned()
+                            && is_over(&excesses[tenant]) =>
+                    {
+                        let (weight, count) = &mut excesses[tenant];
+                        *weight = weight.saturating_sub(entry_info.policy_weight() as u64);
+                        *count = count.saturating_sub(1);
+                        victims.push((Arc::clone(element.key()), element.hash(), ts));
+                    }
+                    _ => skipped[i] += 1,
+                }
+            }
+        }
+        let more_to_evict = excesses.iter().any(is_over) && has_more;
+        let num_walked = victims.len() + skipped.iter().sum::<usize>();
+
+        for (key, hash, ts) in victims {
+            // Lock the key for removal if blocking removal notification is enabled.
+            let kl = self.maybe_key_lock(&key);
+            let _klg = &kl.as_ref().map(|kl| kl.lock());
+
+            let maybe_entry = self.cache.remove_if(
+                hash,
+                |k| k == &key,
+                |_, v| v.last_accessed() == Some(ts) && !v.entry_info().is_pinned(),
+            );
+
+            if let Some(entry) = maybe_entry {
+                if eviction_state.is_notifier_enabled() {
+                    eviction_state.notify_entry_removal(key, &entry, RemovalCause::Size);
+                }
+                eviction_state.counters.incr_eviction_count();
+                self.remember_evicted(&entry);
+                Self::handle_remove(deqs, timer_wheel, entry, None, &mut eviction_state.counters);
+            }
+        }
+
+        if more_to_evict && num_walked >= MAX_TENANT_WALK {
+            // The walk has reached its bound. Move the skipped nodes at the front to
+            // the back, so that the next walk starts further along the deques.
+            for (deq, skipped) in [&mut deqs.probation, &mut deqs.window, &mut deqs.protected]
+                .into_iter()
+                .zip(skipped)
+            {
+                for _ in 0..skipped.min(deq.len()) {
+                    deq.move_front_to_back();
+                }
+            }
+        }
+
+        if more_to_evict {
+            eviction_state.more_entries_to_evict = true;
+        }
+    }
 }
 
 impl<K, V, S> Inner<K, V, S>
@@ -2404,8 +4519,18 @@ where
         entry: &TrioArc<ValueEntry<K, V>>,
         cause: RemovalCause,
     ) {
-        if let Some(notifier) = &self.removal_notifier {
-            notifier.notify(key, entry.value.clone(), cause);
+        self.event_bus.publish(|| CacheEvent::Evict {
+            key: Arc::clone(&key),
+            value: entry.value.clone(),
+            cause,
+        });
+        if let Some(notifier) = self
+            .removal_notifier
+            .as_ref()
+            .filter(|n| n.is_notified(cause))
+        {
+            let metadata = removal_metadata(self.clocks(), entry);
+            notifier.notify(key, entry.value.clone(), cause, metadata);
         }
     }
 
@@ -2511,10 +4636,21 @@ where
 // private free-standing functions
 //
 
-/// Returns `true` if this entry is expired by its per-entry TTL.
+fn removal_metadata<K, V>(clocks: &Clocks, entry: &TrioArc<ValueEntry<K, V>>) -> RemovalMetadata {
+    RemovalMetadata::new(
+        entry.last_modified().map(|ts| clocks.to_std_instant(ts)),
+        entry.last_accessed().map(|ts| clocks.to_std_instant(ts)),
+        entry.policy_weight(),
+    )
+}
+
+/// Returns `true` if this entry is expired by its per-entry TTL. A pinned entry
+/// never expires.
 #[inline]
 fn is_expired_by_per_entry_ttl<K>(entry_info: &TrioArc<EntryInfo<K>>, now: Instant) -> bool {
-    if let Some(ts) = entry_info.expiration_time() {
+    if entry_info.is_pinned() {
+        false
+    } else if let Some(ts) = entry_info.expiration_time() {
         ts <= now
     } else {
         false
@@ -2523,17 +4659,19 @@ fn is_expired_by_per_entry_ttl<K>(entry_info: &TrioArc<EntryInfo<K>>, now: Insta
 
 /// Returns `true` when one of the followings conditions is met:
 ///
-/// - This entry is expired by the time-to-idle config of this cache instance.
+/// - This entry is not pinned and expired by the time-to-idle config of this cache
+///   instance.
 /// - Or, it is invalidated by the `invalidate_all` method.
 #[inline]
-fn is_expired_entry_ao(
+fn is_expired_entry_ao<K, V>(
     time_to_idle: &Option<Duration>,
     valid_after: &Option<Instant>,
-    entry: &impl AccessTime,
+    entry: &TrioArc<ValueEntry<K, V>>,
     now: Instant,
 ) -> bool {
     if let Some(ts) = entry.last_accessed() {
-        is_invalid_entry(valid_after, ts) || is_expired_by_tti(time_to_idle, ts, now)
+        is_invalid_entry(valid_after, ts)
+            || (!entry.entry_info().is_pinned() && is_expired_by_tti(time_to_idle, ts, now))
     } else {
         false
     }
@@ -2541,17 +4679,19 @@ fn is_expired_entry_ao(
 
 /// Returns `true` when one of the following conditions is met:
 ///
-/// - This entry is expired by the time-to-live (TTL) config of this cache instance.
+/// - This entry is not pinned and expired by the time-to-live (TTL) config of this
+///   cache instance.
 /// - Or, it is invalidated by the `invalidate_all` method.
 #[inline]
-fn is_expired_entry_wo(
+fn is_expired_entry_wo<K, V>(
     time_to_live: &Option<Duration>,
     valid_after: &Option<Instant>,
-    entry: &impl AccessTime,
+    entry: &TrioArc<ValueEntry<K, V>>,
     now: Instant,
 ) -> bool {
     if let Some(ts) = entry.last_modified() {
-        is_invalid_entry(valid_after, ts) || is_expired_by_ttl(time_to_live, ts, now)
+        is_invalid_entry(valid_after, ts)
+            || (!entry.entry_info().is_pinned() && is_expired_by_ttl(time_to_live, ts, now))
     } else {
         false
     }
@@ -2620,15 +4760,210 @@ fn is_expired_by_ttl(
     }
 }
 
+/// Returns a random number uniformly distributed in `0..bound`. The numbers from
+/// `rng` falling in the incomplete last range are rejected, so that the smaller
+/// numbers are not favored. `bound` must not be zero.
+fn random_below(rng: &mut impl FnMut() -> u64, bound: u64) -> u64 {
+    debug_assert!(bound > 0);
+    let limit = u64::MAX - u64::MAX % bound;
+    loop {
+        let r = rng();
+        if r < limit {
+            return r % bound;
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::{
         common::HousekeeperConfig,
+        notification::RemovalCauses,
         policy::{EvictionPolicy, ExpirationPolicy},
+        sync_base::policy_config::PolicyConfig,
     };
 
     use super::BaseCache;
 
+    #[test]
+    fn test_random_below_rejects_biased_numbers() {
+        use super::random_below;
+
+        // `u64::MAX` is a multiple of 3, so only `u64::MAX` itself falls in the
+        // incomplete last range.
+        let mut numbers = [u64::MAX, u64::MAX - 1, 5].into_iter();
+        let mut rng = || numbers.next().unwrap();
+        assert_eq!(random_below(&mut rng, 3), (u64::MAX - 1) % 3);
+        assert_eq!(random_below(&mut rng, 3), 2);
+
+        let mut numbers = [u64::MAX, 7].into_iter();
+        let mut rng = || numbers.next().unwrap();
+        assert_eq!(random_below(&mut rng, 4), 3);
+    }
+
+    #[test]
+    fn admit_to_tenant_finds_victims_in_protected_region() {
+        use super::{AdmissionResult, EntrySizeAndFrequency, Inner};
+        use crate::{
+            common::{
+                concurrent::{
+                    deques::Deques, entry_info::EntryInfo, KeyHash, KeyHashDate, ValueEntry,
+                },
+                time::Instant,
+                CacheRegion,
+            },
+            sync_base::{
+                segmented_sketch::SegmentedFrequencySketch,
+                tenant::{TenantQuotas, TenantQuotasConfig},
+            },
+        };
+        use std::{collections::hash_map::RandomState, sync::Arc};
+        use triomphe::Arc as TrioArc;
+
+        type TestInner = Inner<u32, u32, RandomState>;
+
+        // Keys below 10 belong to tenant 0, which has a quota of 2.
+        let classifier = Arc::new(|key: &u32| (*key < 10).then_some(0));
+        let quotas = TenantQuotas::new(TenantQuotasConfig::new(classifier, vec![2]), 100);
+        let freq = SegmentedFrequencySketch::new(1);
+        freq.ensure_capacity(100);
+
+        // Fill the quota of tenant 0 with entries in the protected region only.
+        let mut deqs = Deques::default();
+        let mut entries = Vec::new();
+        for key in 0..2u32 {
+            let hash = key as u64;
+            let info = EntryInfo::new(KeyHash::new(Arc::new(key), hash), Instant::now(), 1);
+            info.set_policy_gen(info.entry_gen());
+            let entry = TrioArc::new(ValueEntry::new(key, TrioArc::new(info)));
+            deqs.push_back_ao(
+                CacheRegion::MainProtected,
+                KeyHashDate::new(entry.entry_info()),
+                &entry,
+            );
+            quotas.add(&key, 1);
+            freq.increment(hash);
+            entries.push(entry);
+        }
+
+        // A candidate more popular than a victim in the protected region should be
+        // admitted.
+        let mut candidate = EntrySizeAndFrequency::new(1);
+        candidate.freq = 5;
+        assert!(matches!(
+            TestInner::admit_to_tenant(&candidate, &5, &quotas, &deqs, &freq),
+            AdmissionResult::Admitted { .. }
+        ));
+
+        // A less popular one should be rejected.
+        candidate.freq = 0;
+        assert!(matches!(
+            TestInner::admit_to_tenant(&candidate, &5, &quotas, &deqs, &freq),
+            AdmissionResult::Rejected
+        ));
+
+        // Without any entry of the tenant in the deques, the candidate should be
+        // rejected rather than overflowing the quota.
+        let empty = Deques::default();
+        candidate.freq = 5;
+        assert!(matches!(
+            TestInner::admit_to_tenant(&candidate, &5, &quotas, &empty, &freq),
+            AdmissionResult::Rejected
+        ));
+
+        for entry in &entries {
+            deqs.unlink_ao(entry);
+        }
+    }
+
+    #[test]
+    fn test_skt_presized_by_initial_capacity() {
+        use std::collections::hash_map::RandomState;
+
+        let sketch_len = |max_capacity, initial_capacity| {
+            let cache = BaseCache::<u8, u8>::new(
+                None,
+                max_capacity,
+                initial_capacity,
+                RandomState::default(),
+                None,
+                EvictionPolicy::default(),
+                None,
+                RemovalCauses::default(),
+                ExpirationPolicy::default(),
+                HousekeeperConfig::default(),
+                false,
+                PolicyConfig::default(),
+                None,
+                None,
+            );
+            cache.inner.frequency_sketch.table_len()
+        };
+
+        assert_eq!(sketch_len(Some(4096), Some(1000)), 1024);
+        assert_eq!(sketch_len(Some(1000), Some(4096)), 1024);
+        // Not allocated.
+        assert_eq!(sketch_len(Some(4096), None), 0);
+        assert_eq!(sketch_len(None, Some(1000)), 0);
+        assert_eq!(sketch_len(Some(0), Some(1000)), 0);
+    }
+
+    #[test]
+    fn zero_capacity_creates_no_policy_structures() {
+        use crate::sync_base::tenant::TenantQuotasConfig;
+        use std::{collections::hash_map::RandomState, sync::Arc, time::Duration};
+
+        let policy_config = PolicyConfig {
+            hit_ratio_analyzer: true,
+            ghost_list: true,
+            memory_pressure_shed_fraction: Some(0.5),
+            tenant_quotas: Some(TenantQuotasConfig::new(Arc::new(|_: &u8| Some(0)), vec![1])),
+            ..Default::default()
+        };
+        let cache = BaseCache::<u8, u8>::new(
+            None,
+            Some(0),
+            Some(1000),
+            RandomState::default(),
+            None,
+            EvictionPolicy::default(),
+            Some(Arc::new(|_, _, _, _| {})),
+            RemovalCauses::default(),
+            ExpirationPolicy::new(Some(Duration::from_secs(1)), None, None),
+            HousekeeperConfig::default(),
+            false,
+            policy_config,
+            None,
+            None,
+        );
+        cache
+            .housekeeper
+            .as_ref()
+            .unwrap()
+            .run_pending_tasks(&*cache.inner);
+
+        let inner = &cache.inner;
+        assert_eq!(cache.read_op_ch.capacity(), Some(0));
+        assert_eq!(cache.write_op_ch.capacity(), Some(0));
+        assert_eq!(inner.cache.num_segments(), 1);
+        assert_eq!(inner.frequency_sketch.table_len(), 0);
+        assert!(!inner.timer_wheel.lock().is_enabled());
+        let deqs = inner.deques.lock();
+        for deq in [
+            &deqs.window,
+            &deqs.probation,
+            &deqs.protected,
+            &deqs.write_order,
+        ] {
+            assert_eq!((deq.len(), deq.free_node_count()), (0, 0));
+        }
+        assert!(inner.key_locks.is_none());
+        assert!(inner.memory_pressure.is_none());
+        assert!(inner.hit_ratio_analyzer.is_none());
+        assert!(inner.ghost_list.is_none());
+        assert!(inner.tenant_quotas.is_none());
+    }
+
     #[cfg_attr(target_pointer_width = "16", ignore)]
     #[test]
     fn test_skt_capacity_will_not_overflow() {
@@ -2646,13 +4981,17 @@ mod tests {
                 None,
                 EvictionPolicy::default(),
                 None,
+                RemovalCauses::default(),
                 ExpirationPolicy::default(),
                 HousekeeperConfig::default(),
                 false,
+                PolicyConfig::default(),
+                None,
+                None,
             );
             cache.inner.enable_frequency_sketch_for_testing();
             assert_eq!(
-                cache.inner.frequency_sketch.read().table_len(),
+                cache.inner.frequency_sketch.table_len(),
                 len as usize,
                 "{name}"
             );
@@ -2993,6 +5332,7 @@ mod tests {
             None,
             EvictionPolicy::default(),
             None,
+            RemovalCauses::default(),
             ExpirationPolicy::new(
                 Some(Duration::from_secs(TTL)),
                 Some(Duration::from_secs(TTI)),
@@ -3000,6 +5340,9 @@ mod tests {
             ),
             HousekeeperConfig::default(),
             false,
+            PolicyConfig::default(),
+            None,
+            None,
         );
         cache.reconfigure_for_testing();
 
@@ -0,0 +1,129 @@
+use parking_lot::RwLock;
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+/// Returns the name of the class of a key, e.g. the type of the cached object.
+pub(crate) type KeyClassifier<K> = Arc<dyn Fn(&K) -> &'static str + Send + Sync + 'static>;
+
+/// Counts the hits, the misses and the evictions of each class of the keys given
+/// by the classifier set by the `class_stats` method of the cache builder.
+///
+/// The hits and the misses are recorded by the reading threads, so the counters
+/// are atomic, and the lock is taken for writing only when a new class is seen.
+pub(crate) struct ClassStatsRecorder<K> {
+    classifier: KeyClassifier<K>,
+    counters: RwLock<HashMap<&'static str, ClassCounters>>,
+}
+
+#[derive(Default)]
+struct ClassCounters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+}
+
+impl<K> ClassStatsRecorder<K> {
+    pub(crate) fn new(classifier: KeyClassifier<K>) -> Self {
+        Self {
+            classifier,
+            counters: RwLock::default(),
+        }
+    }
+
+    pub(crate) fn record_hit(&self, key: &K) {
+        self.update(key, |c| c.hits.fetch_add(1, Ordering::Relaxed));
+    }
+
+    pub(crate) fn record_miss(&self, key: &K) {
+        self.update(key, |c| c.misses.fetch_add(1, Ordering::Relaxed));
+    }
+
+    pub(crate) fn record_eviction(&self, key: &K) {
+        self.update(key, |c| c.evictions.fetch_add(1, Ordering::Relaxed));
+    }
+
+    pub(crate) fn stats(&self) -> HashMap<&'static str, ClassStats> {
+        self.counters
+            .read()
+            .iter()
+            .map(|(class, c)| {
+                let stats = ClassStats {
+                    hits: c.hits.load(Ordering::Relaxed),
+                    misses: c.misses.load(Ordering::Relaxed),
+                    evictions: c.evictions.load(Ordering::Relaxed),
+                };
+                (*class, stats)
+            })
+            .collect()
+    }
+
+    fn update(&self, key: &K, f: impl FnOnce(&ClassCounters) -> u64) {
+        let class = (self.classifier)(key);
+        if let Some(c) = self.counters.read().get(class) {
+            f(c);
+            return;
+        }
+        f(self.counters.write().entry(class).or_default());
+    }
+}
+
+/// The counters of a class of the keys, returned by the
+/// [`Cache::class_stats`][stats-method] method.
+///
+/// When a cache holds several types of objects, the counters show which type
+/// benefits from the cache and which only takes up the space: a class with many
+/// evictions and few hits is likely not worth caching.
+///
+/// [stats-method]: ./struct.Cache.html#method.class_stats
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ClassStats {
+    hits: u64,
+    misses: u64,
+    evictions: u64,
+}
+
+impl ClassStats {
+    /// Returns the number of the reads that hit an entry of the class.
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    /// Returns the number of the misses on the keys of the class.
+    ///
+    /// The misses are counted by the reads that have an owned key to classify,
+    /// i.e. the `get_with` family methods, `get_or_load` and the `entry` API (the
+    /// `by_ref` variants clone the key on a miss for this). A miss of the `get`
+    /// method is not counted, as its borrowed key cannot be classified.
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+
+    /// Returns the number of the entries of the class evicted by the size
+    /// constraint of the cache.
+    pub fn evictions(&self) -> u64 {
+        self.evictions
+    }
+
+    /// Returns the ratio of the hits to the hits and the misses, or `0.0` if there
+    /// has been no read.
+    pub fn hit_ratio(&self) -> f64 {
+        let reads = self.hits + self.misses;
+        if reads == 0 {
+            0.0
+        } else {
+            self.hits as f64 / reads as f64
+        }
+    }
+
+    /// Adds the counters of another segment of the same cache to this.
+    pub(crate) fn merge(&mut self, other: &Self) {
+        self.hits += other.hits;
+        self.misses += other.misses;
+        self.evictions += other.evictions;
+    }
+}
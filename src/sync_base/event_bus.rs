@@ -0,0 +1,203 @@
+use super::PredicateId;
+use crate::notification::RemovalCause;
+
+use crossbeam_channel::{Receiver, Sender, TrySendError};
+use parking_lot::RwLock;
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+/// The capacity of the channel of each event subscriber.
+pub(crate) const EVENT_CH_SIZE: usize = 1024;
+
+/// An event of a cache activity, delivered to the [`EventReceiver`]s returned by
+/// the [`subscribe`][subscribe-method] method of the cache.
+///
+/// [subscribe-method]: ./struct.Cache.html#method.subscribe
+#[derive(Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum CacheEvent<K, V> {
+    /// A value was inserted for a key, which did not have an entry.
+    Insert { key: Arc<K>, value: V },
+    /// The value of an existing entry was replaced with a new value.
+    Update { key: Arc<K>, value: V },
+    /// An entry was removed from the cache for the `cause`.
+    Evict {
+        key: Arc<K>,
+        value: V,
+        cause: RemovalCause,
+    },
+    /// All entries were invalidated by the `invalidate_all` method.
+    InvalidateAll,
+    /// A predicate was registered by the `invalidate_entries_if` method.
+    PredicateRegistered { id: PredicateId },
+}
+
+impl<K, V: Clone> Clone for CacheEvent<K, V> {
+    fn clone(&self) -> Self {
+        match self {
+            Self::Insert { key, value } => Self::Insert {
+                key: Arc::clone(key),
+                value: value.clone(),
+            },
+            Self::Update { key, value } => Self::Update {
+                key: Arc::clone(key),
+                value: value.clone(),
+            },
+            Self::Evict { key, value, cause } => Self::Evict {
+                key: Arc::clone(key),
+                value: value.clone(),
+                cause: *cause,
+            },
+            Self::InvalidateAll => Self::InvalidateAll,
+            Self::PredicateRegistered { id } => Self::PredicateRegistered { id: id.clone() },
+        }
+    }
+}
+
+/// A receiving side of a bounded stream of [`CacheEvent`]s.
+///
+/// An `EventReceiver` is returned by the [`subscribe`][subscribe-method] method of
+/// the cache. The cache never blocks on a slow subscriber; if the channel of the
+/// receiver is full, new events for it are discarded and counted by the
+/// [`missed_count`](#method.missed_count) method.
+///
+/// Dropping the receiver unsubscribes it from the cache.
+///
+/// [subscribe-method]: ./struct.Cache.html#method.subscribe
+pub struct EventReceiver<K, V> {
+    rx: Receiver<CacheEvent<K, V>>,
+    missed: Arc<AtomicU64>,
+}
+
+impl<K, V> fmt::Debug for EventReceiver<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EventReceiver")
+            .field("len", &self.rx.len())
+            .field("missed_count", &self.missed_count())
+            .finish()
+    }
+}
+
+impl<K, V> EventReceiver<K, V> {
+    /// Blocks the current thread until an event is available, and returns it.
+    ///
+    /// Returns `None` if the cache has been dropped and there are no more events.
+    pub fn recv(&self) -> Option<CacheEvent<K, V>> {
+        self.rx.recv().ok()
+    }
+
+    /// Returns an event if one is available, without blocking.
+    pub fn try_recv(&self) -> Option<CacheEvent<K, V>> {
+        self.rx.try_recv().ok()
+    }
+
+    /// Waits for an event for at most `timeout`, and returns it if available.
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<CacheEvent<K, V>> {
+        self.rx.recv_timeout(timeout).ok()
+    }
+
+    /// Returns an iterator over the events that are currently available, without
+    /// blocking.
+    pub fn try_iter(&self) -> impl Iterator<Item = CacheEvent<K, V>> + '_ {
+        self.rx.try_iter()
+    }
+
+    /// Returns the number of events that are currently waiting in the channel.
+    pub fn len(&self) -> usize {
+        self.rx.len()
+    }
+
+    /// Returns `true` if there is no event waiting in the channel.
+    pub fn is_empty(&self) -> bool {
+        self.rx.is_empty()
+    }
+
+    /// Returns the number of events discarded because the channel was full.
+    pub fn missed_count(&self) -> u64 {
+        self.missed.load(Ordering::Acquire)
+    }
+}
+
+struct Subscriber<K, V> {
+    tx: Sender<CacheEvent<K, V>>,
+    missed: Arc<AtomicU64>,
+    is_disconnected: AtomicBool,
+}
+
+/// Delivers `CacheEvent`s to the subscribers.
+pub(crate) struct EventBus<K, V> {
+    subscribers: RwLock<Vec<Subscriber<K, V>>>,
+    num_subscribers: AtomicUsize,
+}
+
+impl<K, V> Default for EventBus<K, V> {
+    fn default() -> Self {
+        Self {
+            subscribers: RwLock::default(),
+            num_subscribers: AtomicUsize::default(),
+        }
+    }
+}
+
+impl<K, V> EventBus<K, V> {
+    pub(crate) fn subscribe(&self) -> EventReceiver<K, V> {
+        let (tx, rx) = crossbeam_channel::bounded(EVENT_CH_SIZE);
+        let missed = Arc::new(AtomicU64::default());
+        let mut subscribers = self.subscribers.write();
+        subscribers.push(Subscriber {
+            tx,
+            missed: Arc::clone(&missed),
+            is_disconnected: AtomicBool::default(),
+        });
+        self.num_subscribers
+            .store(subscribers.len(), Ordering::Release);
+        EventReceiver { rx, missed }
+    }
+
+    #[inline]
+    pub(crate) fn has_subscribers(&self) -> bool {
+        self.num_subscribers.load(Ordering::Acquire) > 0
+    }
+
+    /// Sends an event created by `make_event` to all subscribers. `make_event` is
+    /// not called when there is no subscriber.
+    pub(crate) fn publish(&self, make_event: impl FnOnce() -> CacheEvent<K, V>)
+    where
+        V: Clone,
+    {
+        if !self.has_subscribers() {
+            return;
+        }
+
+        let event = make_event();
+        let mut has_disconnected = false;
+        {
+            let subscribers = self.subscribers.read();
+            for sub in subscribers.iter() {
+                match sub.tx.try_send(event.clone()) {
+                    Ok(()) => (),
+                    Err(TrySendError::Full(_)) => {
+                        sub.missed.fetch_add(1, Ordering::AcqRel);
+                    }
+                    Err(TrySendError::Disconnected(_)) => {
+                        sub.is_disconnected.store(true, Ordering::Release);
+                        has_disconnected = true;
+                    }
+                }
+            }
+        }
+
+        if has_disconnected {
+            let mut subscribers = self.subscribers.write();
+            subscribers.retain(|sub| !sub.is_disconnected.load(Ordering::Acquire));
+            self.num_subscribers
+                .store(subscribers.len(), Ordering::Release);
+        }
+    }
+}
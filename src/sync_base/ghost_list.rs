@@ -0,0 +1,158 @@
+use parking_lot::Mutex;
+use std::collections::{HashMap, VecDeque};
+
+/// The max number of the evicted keys to remember.
+const MAX_GHOSTS: u64 = 1 << 16;
+
+/// Remembers the hashes of the keys recently evicted by the size constraint of the
+/// cache (the ghosts), and counts the cache misses on them.
+///
+/// Only the hashes are stored, so a key with the same hash as an evicted key is
+/// also considered evicted.
+pub(crate) struct GhostList {
+    inner: Mutex<Ghosts>,
+}
+
+struct Ghosts {
+    capacity: usize,
+    /// The hashes in the order of the evictions.
+    queue: VecDeque<u64>,
+    /// The number of occurrences of each hash in the queue.
+    counts: HashMap<u64, u32>,
+    misses: u64,
+    recent_eviction_misses: u64,
+}
+
+impl GhostList {
+    pub(crate) fn new(max_capacity: u64) -> Self {
+        let capacity = max_capacity.clamp(1, MAX_GHOSTS) as usize;
+        Self {
+            inner: Mutex::new(Ghosts {
+                capacity,
+                queue: VecDeque::with_capacity(capacity),
+                counts: HashMap::with_capacity(capacity),
+                misses: 0,
+                recent_eviction_misses: 0,
+            }),
+        }
+    }
+
+    /// Remembers the hash of an evicted key, forgetting the oldest one if the list
+    /// is full.
+    pub(crate) fn push(&self, hash: u64) {
+        let mut ghosts = self.inner.lock();
+        if ghosts.queue.len() == ghosts.capacity {
+            if let Some(oldest) = ghosts.queue.pop_front() {
+                ghosts.forget(oldest);
+            }
+        }
+        ghosts.queue.push_back(hash);
+        *ghosts.counts.entry(hash).or_default() += 1;
+    }
+
+    pub(crate) fn contains(&self, hash: u64) -> bool {
+        self.inner.lock().counts.contains_key(&hash)
+    }
+
+    /// Counts a cache miss on the key with the `hash`.
+    pub(crate) fn record_miss(&self, hash: u64) {
+        let mut ghosts = self.inner.lock();
+        ghosts.misses += 1;
+        if ghosts.counts.contains_key(&hash) {
+            ghosts.recent_eviction_misses += 1;
+        }
+    }
+
+    pub(crate) fn stats(&self) -> GhostListStats {
+        let ghosts = self.inner.lock();
+        GhostListStats {
+            misses: ghosts.misses,
+            recent_eviction_misses: ghosts.recent_eviction_misses,
+        }
+    }
+}
+
+impl Ghosts {
+    fn forget(&mut self, hash: u64) {
+        if let Some(count) = self.counts.get_mut(&hash) {
+            *count -= 1;
+            if *count == 0 {
+                self.counts.remove(&hash);
+            }
+        }
+    }
+}
+
+/// The counters of the ghost list of the recently evicted keys, returned by the
+/// [`Cache::ghost_list_stats`][stats-method] method.
+///
+/// If a large part of the misses are on the recently evicted keys, the cache is
+/// likely undersized. If not, the workload simply does not repeat the keys often
+/// enough, and a larger cache would not help much.
+///
+/// [stats-method]: ./struct.Cache.html#method.ghost_list_stats
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GhostListStats {
+    misses: u64,
+    recent_eviction_misses: u64,
+}
+
+impl GhostListStats {
+    /// Returns the number of the cache misses.
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+
+    /// Returns the number of the cache misses on the keys that were recently
+    /// evicted by the size constraint of the cache.
+    pub fn recent_eviction_misses(&self) -> u64 {
+        self.recent_eviction_misses
+    }
+
+    /// Returns the ratio of the misses on the recently evicted keys to all misses,
+    /// or `0.0` if there has been no miss.
+    pub fn recent_eviction_miss_ratio(&self) -> f64 {
+        if self.misses == 0 {
+            0.0
+        } else {
+            self.recent_eviction_misses as f64 / self.misses as f64
+        }
+    }
+
+    /// Adds the counters of another segment of the same cache to this.
+    pub(crate) fn merge(&mut self, other: &Self) {
+        self.misses += other.misses;
+        self.recent_eviction_misses += other.recent_eviction_misses;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GhostList;
+
+    #[test]
+    fn ghost_list() {
+        let ghosts = GhostList::new(3);
+        for hash in [1, 2, 3, 1] {
+            ghosts.push(hash);
+        }
+        assert!(ghosts.contains(1));
+        assert!(ghosts.contains(2));
+
+        // The oldest hashes are forgotten, but 1 is still remembered as it was
+        // evicted again.
+        ghosts.push(4);
+        ghosts.push(5);
+        assert!(ghosts.contains(1));
+        assert!(!ghosts.contains(2));
+        assert!(!ghosts.contains(3));
+
+        for hash in [1, 2, 4, 6] {
+            ghosts.record_miss(hash);
+        }
+        let stats = ghosts.stats();
+        assert_eq!(stats.misses(), 4);
+        assert_eq!(stats.recent_eviction_misses(), 2);
+        assert_eq!(stats.recent_eviction_miss_ratio(), 0.5);
+    }
+}
@@ -0,0 +1,264 @@
+use parking_lot::Mutex;
+use std::collections::{BTreeMap, HashMap};
+
+/// The capacities, relative to the max capacity of the cache, to estimate the hit
+/// ratios at.
+const CAPACITY_FACTORS: [f64; 4] = [0.5, 1.0, 2.0, 4.0];
+
+/// The max number of the sampled keys to track. The sampling rate is chosen so
+/// that the keys fitting in the largest estimated capacity are tracked.
+const MAX_TRACKED_KEYS: f64 = 1024.0;
+
+const SAMPLING_BITS: u32 = 24;
+
+/// Estimates the hit ratios the cache would have at other capacities, by
+/// simulating an LRU cache over a sample of the read keys (a ghost cache).
+///
+/// The keys are sampled by their hashes, so a sampled key is always sampled. For
+/// every read of a sampled key, the analyzer computes the number of distinct
+/// sampled keys read since the last read of the key (the stack distance). An LRU
+/// cache holding more keys than the distance scaled by the sampling rate would
+/// have hit. This is the idea of the SHARDS algorithm.
+pub(crate) struct HitRatioAnalyzer {
+    max_capacity: u64,
+    /// The sampling rate in the range of `(0.0, 1.0]`.
+    rate: f64,
+    /// A key is sampled if the top bits of its mixed hash is below this.
+    threshold: u64,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    /// The logical time incremented on every read of a sampled key.
+    clock: u64,
+    /// The time of the last read of each sampled key.
+    last_read: HashMap<u64, u64>,
+    /// The sampled keys ordered by the time of their last read.
+    recency: BTreeMap<u64, u64>,
+    /// The number of the reads of the sampled keys.
+    sampled_reads: u64,
+    /// The number of the sampled reads that would have hit at each capacity.
+    simulated_hits: [u64; CAPACITY_FACTORS.len()],
+    /// The number of all reads and hits actually observed by the cache.
+    reads: u64,
+    hits: u64,
+}
+
+impl HitRatioAnalyzer {
+    pub(crate) fn new(max_capacity: u64) -> Self {
+        let largest = max_capacity as f64 * CAPACITY_FACTORS[CAPACITY_FACTORS.len() - 1];
+        let rate = (MAX_TRACKED_KEYS / largest.max(1.0)).min(1.0);
+        let threshold = ((rate * (1u64 << SAMPLING_BITS) as f64) as u64).max(1);
+        Self {
+            max_capacity,
+            rate: threshold as f64 / (1u64 << SAMPLING_BITS) as f64,
+            threshold,
+            state: Mutex::default(),
+        }
+    }
+
+    /// Records a read of the key with the `hash`.
+    pub(crate) fn record_read(&self, hash: u64, is_hit: bool) {
+        let mut guard = self.state.lock();
+        let state = &mut *guard;
+        state.reads += 1;
+        if is_hit {
+            state.hits += 1;
+        }
+
+        // Mix the hash to sample the keys evenly regardless of the hasher.
+        if hash.wrapping_mul(0x9E37_79B9_7F4A_7C15) >> (64 - SAMPLING_BITS) >= self.threshold {
+            return;
+        }
+
+        state.clock += 1;
+        state.sampled_reads += 1;
+        let now = state.clock;
+
+        if let Some(last) = state.last_read.insert(hash, now) {
+            state.recency.remove(&last);
+            let distance = state.recency.range(last..).count() as f64;
+            for (i, factor) in CAPACITY_FACTORS.iter().enumerate() {
+                if distance < self.max_capacity as f64 * factor * self.rate {
+                    state.simulated_hits[i] += 1;
+                }
+            }
+        }
+        state.recency.insert(now, hash);
+
+        // Forget the least recently read key that would not fit in any capacity.
+        let max_tracked = self.max_tracked_keys();
+        if state.recency.len() > max_tracked {
+            let oldest = state.recency.keys().next().copied();
+            if let Some(hash) = oldest.and_then(|ts| state.recency.remove(&ts)) {
+                state.last_read.remove(&hash);
+            }
+        }
+    }
+
+    pub(crate) fn report(&self) -> HitRatioReport {
+        let state = self.state.lock();
+        let estimates = CAPACITY_FACTORS
+            .iter()
+            .zip(state.simulated_hits)
+            .map(|(&factor, hits)| HitRatioEstimate {
+                capacity_factor: factor,
+                capacity: (self.max_capacity as f64 * factor) as u64,
+                hits,
+                reads: state.sampled_reads,
+            })
+            .collect();
+        HitRatioReport {
+            reads: state.reads,
+            hits: state.hits,
+            estimates,
+        }
+    }
+
+    fn max_tracked_keys(&self) -> usize {
+        let largest = self.max_capacity as f64 * CAPACITY_FACTORS[CAPACITY_FACTORS.len() - 1];
+        (largest * self.rate).ceil() as usize + 1
+    }
+}
+
+/// A report of the hit ratio analyzer, returned by the
+/// [`Cache::hit_ratio_report`][report-method] method.
+///
+/// It has the hit ratio actually observed by the cache, and the estimated hit
+/// ratios at 0.5x, 1x, 2x and 4x of the max capacity. The estimates are useful for
+/// capacity planning; if doubling the capacity raises the hit ratio only a little,
+/// the cache is large enough.
+///
+/// [report-method]: ./struct.Cache.html#method.hit_ratio_report
+#[derive(Clone, Debug, PartialEq)]
+pub struct HitRatioReport {
+    reads: u64,
+    hits: u64,
+    estimates: Vec<HitRatioEstimate>,
+}
+
+impl HitRatioReport {
+    /// Returns the number of the reads analyzed.
+    pub fn reads(&self) -> u64 {
+        self.reads
+    }
+
+    /// Returns the hit ratio actually observed by the cache, or `0.0` if there has
+    /// been no read.
+    pub fn hit_ratio(&self) -> f64 {
+        ratio(self.hits, self.reads)
+    }
+
+    /// Returns the estimated hit ratios, ordered by the capacity.
+    pub fn estimates(&self) -> &[HitRatioEstimate] {
+        &self.estimates
+    }
+
+    /// Merges the report of another segment of the same cache into this report.
+    pub(crate) fn merge(&mut self, other: &Self) {
+        self.reads += other.reads;
+        self.hits += other.hits;
+        for (e, o) in self.estimates.iter_mut().zip(&other.estimates) {
+            e.capacity += o.capacity;
+            e.hits += o.hits;
+            e.reads += o.reads;
+        }
+    }
+}
+
+/// An estimated hit ratio of the cache at a capacity. See [`HitRatioReport`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct HitRatioEstimate {
+    capacity_factor: f64,
+    capacity: u64,
+    hits: u64,
+    reads: u64,
+}
+
+impl HitRatioEstimate {
+    /// Returns the capacity relative to the max capacity of the cache, such as
+    /// `2.0` for the double.
+    pub fn capacity_factor(&self) -> f64 {
+        self.capacity_factor
+    }
+
+    /// Returns the capacity. If the cache has a weigher, the capacity is in the
+    /// same unit as the max capacity, but the estimate assumes that all entries
+    /// have the same weight.
+    pub fn capacity(&self) -> u64 {
+        self.capacity
+    }
+
+    /// Returns the estimated hit ratio at the capacity, or `0.0` if no read has
+    /// been sampled yet.
+    ///
+    /// The estimate is made by simulating an LRU cache, so it can differ from the
+    /// hit ratio of the cache, whose eviction policy is not LRU by default.
+    /// Compare the estimate at the capacity factor `1.0` with the observed hit
+    /// ratio to see how much they differ.
+    pub fn hit_ratio(&self) -> f64 {
+        ratio(self.hits, self.reads)
+    }
+
+    /// Returns the number of the sampled reads used for the estimate.
+    pub fn sampled_reads(&self) -> u64 {
+        self.reads
+    }
+}
+
+fn ratio(hits: u64, reads: u64) -> f64 {
+    if reads == 0 {
+        0.0
+    } else {
+        hits as f64 / reads as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HitRatioAnalyzer;
+
+    #[test]
+    fn looping_reads() {
+        let analyzer = HitRatioAnalyzer::new(100);
+
+        // Read 150 keys in a loop. An LRU cache holding fewer keys never hits, and
+        // one holding all keys hits except for the first round.
+        for _ in 0..10 {
+            for key in 0..150u64 {
+                analyzer.record_read(key, false);
+            }
+        }
+
+        let report = analyzer.report();
+        assert_eq!(report.reads(), 1500);
+        assert_eq!(report.hit_ratio(), 0.0);
+        let ratios = report
+            .estimates()
+            .iter()
+            .map(|e| (e.capacity(), e.hit_ratio()))
+            .collect::<Vec<_>>();
+        assert_eq!(ratios, [(50, 0.0), (100, 0.0), (200, 0.9), (400, 0.9)]);
+    }
+
+    #[test]
+    fn sampled_reads() {
+        // Only a part of the keys is sampled for a large cache.
+        let analyzer = HitRatioAnalyzer::new(1_000_000);
+        for _ in 0..2 {
+            for key in 0..100_000u64 {
+                analyzer.record_read(key, true);
+            }
+        }
+
+        let report = analyzer.report();
+        assert_eq!(report.reads(), 200_000);
+        assert_eq!(report.hit_ratio(), 1.0);
+        let estimate = &report.estimates()[0];
+        assert!(estimate.sampled_reads() > 0);
+        assert!(estimate.sampled_reads() < 2_000);
+        // All keys fit in the half of the capacity.
+        assert_eq!(estimate.hit_ratio(), 0.5);
+    }
+}
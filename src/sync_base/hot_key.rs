@@ -0,0 +1,42 @@
+use std::sync::Arc;
+
+use crate::common::concurrent::ValueEntry;
+
+/// Called with the key and the value of an entry when the entry becomes hot.
+pub(crate) type HotKeyListener<K, V> = Arc<dyn Fn(&Arc<K>, &V) + Send + Sync + 'static>;
+
+/// The configuration of the hot key listener set by the `on_hot_key` method of the
+/// cache builder.
+pub(crate) struct HotKeyConfig<K, V> {
+    /// The estimated frequency of the key at or above which the entry is hot. It
+    /// is in the range of `1..=15`, the range of the frequency sketch.
+    threshold: u8,
+    listener: HotKeyListener<K, V>,
+}
+
+impl<K, V> Clone for HotKeyConfig<K, V> {
+    fn clone(&self) -> Self {
+        Self {
+            threshold: self.threshold,
+            listener: Arc::clone(&self.listener),
+        }
+    }
+}
+
+impl<K, V> HotKeyConfig<K, V> {
+    pub(crate) fn new(threshold: u8, listener: HotKeyListener<K, V>) -> Self {
+        Self {
+            threshold: threshold.clamp(1, 15),
+            listener,
+        }
+    }
+
+    /// Calls the listener if the estimated frequency of the entry has reached the
+    /// threshold and the listener has not been called for the entry yet.
+    pub(crate) fn notify_if_hot(&self, entry: &ValueEntry<K, V>, frequency: u8) {
+        if frequency >= self.threshold && entry.entry_info().mark_hot() {
+            let key = &entry.entry_info().key_hash().key;
+            (self.listener)(key, &entry.value);
+        }
+    }
+}
@@ -93,6 +93,16 @@ impl<K, V, S> Invalidator<K, V, S> {
         self.is_empty.load(Ordering::Acquire)
     }
 
+    /// Returns `true` if the predicate with the `id` is still registered, i.e. it
+    /// has not been applied to all the entries yet.
+    pub(crate) fn is_predicate_registered(&self, id: &PredicateId) -> bool
+    where
+        S: BuildHasher,
+    {
+        let hash = self.predicates.hash(id);
+        self.predicates.contains_key(hash, |k| k == id)
+    }
+
     pub(crate) fn remove_predicates_registered_before(&self, ts: Instant)
     where
         K: Hash + Eq + Send + Sync + 'static,
@@ -0,0 +1,51 @@
+use std::sync::Arc;
+
+/// A report of the consistency checks between the internal data structures of a
+/// cache, returned by the [`validate_invariants`][validate-method] method of the
+/// cache.
+///
+/// [validate-method]: ./struct.Cache.html#method.validate_invariants
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvariantReport<K> {
+    /// The entry count recorded by the cache.
+    pub entry_count: u64,
+    /// The weighted size recorded by the cache.
+    pub weighted_size: u64,
+    /// The number of the entries in the internal hash table, including the ones
+    /// not admitted yet.
+    pub table_len: usize,
+    /// The number of the admitted entries in the internal hash table.
+    pub admitted_count: u64,
+    /// The inconsistencies found. Empty if the cache is consistent.
+    pub violations: Vec<InvariantViolation<K>>,
+}
+
+impl<K> InvariantReport<K> {
+    /// Returns `true` if no inconsistency was found.
+    pub fn is_ok(&self) -> bool {
+        self.violations.is_empty()
+    }
+}
+
+/// An inconsistency found by the [`validate_invariants`][validate-method] method
+/// of the cache.
+///
+/// [validate-method]: ./struct.Cache.html#method.validate_invariants
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum InvariantViolation<K> {
+    /// The recorded entry count does not match the number of the admitted entries
+    /// in the internal hash table.
+    EntryCountMismatch { recorded: u64, actual: u64 },
+    /// The recorded weighted size does not match the sum of the weights of the
+    /// admitted entries in the internal hash table.
+    WeightedSizeMismatch { recorded: u64, actual: u64 },
+    /// An admitted entry is not linked to the access order deques.
+    NotInAccessOrderDeque { key: Arc<K> },
+    /// An entry not admitted is linked to the access order deques.
+    UnadmittedInAccessOrderDeque { key: Arc<K> },
+    /// The access order deques have nodes not linked from the entries.
+    AccessOrderDequeLenMismatch { deque_len: usize, linked: u64 },
+    /// The write order deque has nodes not linked from the entries.
+    WriteOrderDequeLenMismatch { deque_len: usize, linked: u64 },
+}
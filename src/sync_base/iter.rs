@@ -1,5 +1,15 @@
 use std::{hash::Hash, sync::Arc};
 
+#[cfg(feature = "sync")]
+use super::base_cache::{BaseCache, RecencyCursor};
+#[cfg(feature = "sync")]
+use std::{collections::hash_map::RandomState, hash::BuildHasher};
+
+/// The maximum number of keys to copy from the access order deque at a time, while
+/// holding the deques lock.
+#[cfg(feature = "sync")]
+const RECENCY_SNAPSHOT_CHUNK_SIZE: usize = 512;
+
 // This trait is implemented by `sync::BaseCache` and `sync::Cache`.
 pub(crate) trait ScanningGet<K, V> {
     /// Returns the number of segments in the concurrent hash table.
@@ -124,6 +134,198 @@ where
     }
 }
 
+/// An owning iterator over the key-value pairs that were taken out of a cache, in
+/// arbitrary order.
+///
+/// Call `into_iter` or `drain` method on a [`Cache`](./struct.Cache.html) or
+/// [`SegmentedCache`](./struct.SegmentedCache.html) to obtain an `IntoIter`.
+#[cfg(feature = "sync")]
+pub struct IntoIter<K, V> {
+    entries: std::vec::IntoIter<(Arc<K>, V)>,
+}
+
+#[cfg(feature = "sync")]
+impl<K, V> IntoIter<K, V> {
+    pub(crate) fn new(entries: Vec<(Arc<K>, V)>) -> Self {
+        Self {
+            entries: entries.into_iter(),
+        }
+    }
+}
+
+#[cfg(feature = "sync")]
+impl<K, V> Iterator for IntoIter<K, V> {
+    type Item = (Arc<K>, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.entries.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.entries.size_hint()
+    }
+}
+
+#[cfg(feature = "sync")]
+impl<K, V> ExactSizeIterator for IntoIter<K, V> {}
+
+/// Parallel iterator visiting all key-value pairs in a cache in arbitrary order.
+///
+/// Call [`Cache::par_iter`](./struct.Cache.html#method.par_iter) or
+/// [`SegmentedCache::par_iter`](./struct.SegmentedCache.html#method.par_iter)
+/// method to obtain a `ParIter`.
+///
+/// Every segment of the internal concurrent hash table of every cache segment is
+/// scanned as a separate task on the rayon thread pool.
+///
+/// Requires the `rayon` crate feature.
+#[cfg(feature = "rayon")]
+#[cfg_attr(docsrs, doc(cfg(feature = "rayon")))]
+pub struct ParIter<'i, K, V> {
+    cache_segments: Box<[&'i (dyn ScanningGet<K, V> + Sync)]>,
+}
+
+#[cfg(feature = "rayon")]
+impl<'i, K, V> ParIter<'i, K, V> {
+    pub(crate) fn new(cache_segments: Box<[&'i (dyn ScanningGet<K, V> + Sync)]>) -> Self {
+        Self { cache_segments }
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<'i, K, V> rayon::iter::ParallelIterator for ParIter<'i, K, V>
+where
+    K: Eq + Hash + Send + Sync + 'static,
+    V: Clone + Send + Sync + 'static,
+{
+    type Item = (Arc<K>, V);
+
+    fn drive_unindexed<C>(self, consumer: C) -> C::Result
+    where
+        C: rayon::iter::plumbing::UnindexedConsumer<Self::Item>,
+    {
+        use rayon::iter::IntoParallelIterator;
+
+        // Split the work into (cache segment, cht segment) pairs. Each pair is
+        // scanned by `keys` and `scanning_get` in the same way as `Iter` does.
+        let tasks = self
+            .cache_segments
+            .iter()
+            .flat_map(|&cache| (0..cache.num_cht_segments()).map(move |i| (cache, i)))
+            .collect::<Vec<_>>();
+
+        tasks
+            .into_par_iter()
+            .flat_map_iter(|(cache, cht_segment)| {
+                cache
+                    .keys(cht_segment)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter_map(move |key| cache.scanning_get(&key).map(|v| (key, v)))
+            })
+            .drive_unindexed(consumer)
+    }
+}
+
+/// Iterator visiting key-value pairs in a cache from the least recently used (LRU)
+/// to the most recently used (MRU).
+///
+/// Call [`Cache::iter_by_recency`](./struct.Cache.html#method.iter_by_recency)
+/// method to obtain a `RecencyIter`.
+#[cfg(feature = "sync")]
+pub struct RecencyIter<'i, K, V, S = RandomState> {
+    cache: &'i BaseCache<K, V, S>,
+    cursor: RecencyCursor<K>,
+    keys: std::vec::IntoIter<Arc<K>>,
+}
+
+#[cfg(feature = "sync")]
+impl<'i, K, V, S> RecencyIter<'i, K, V, S> {
+    pub(crate) fn new(cache: &'i BaseCache<K, V, S>) -> Self {
+        Self {
+            cache,
+            cursor: RecencyCursor::Start,
+            keys: Vec::new().into_iter(),
+        }
+    }
+}
+
+#[cfg(feature = "sync")]
+impl<'i, K, V, S> Iterator for RecencyIter<'i, K, V, S>
+where
+    K: Eq + Hash + Send + Sync + 'static,
+    V: Clone + Send + Sync + 'static,
+    S: BuildHasher + Clone + Send + Sync + 'static,
+{
+    type Item = (Arc<K>, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            for key in self.keys.by_ref() {
+                if let Some(v) = self.cache.scanning_get(&key) {
+                    return Some((key, v));
+                }
+            }
+
+            if matches!(self.cursor, RecencyCursor::Done) {
+                return None;
+            }
+
+            self.keys = self
+                .cache
+                .next_keys_by_recency(&mut self.cursor, RECENCY_SNAPSHOT_CHUNK_SIZE)
+                .into_iter();
+        }
+    }
+}
+
+/// Iterator visiting key-value pairs in a cache from the most frequently accessed
+/// to the least frequently accessed, according to the estimated frequencies.
+///
+/// Call [`Cache::iter_by_estimated_frequency`][iter-by-freq] method to obtain a
+/// `FrequencyIter`.
+///
+/// [iter-by-freq]: ./struct.Cache.html#method.iter_by_estimated_frequency
+#[cfg(feature = "sync")]
+pub struct FrequencyIter<'i, K, V, S = RandomState> {
+    cache: &'i BaseCache<K, V, S>,
+    keys: std::vec::IntoIter<Arc<K>>,
+}
+
+#[cfg(feature = "sync")]
+impl<'i, K, V, S> FrequencyIter<'i, K, V, S>
+where
+    K: Eq + Hash + Send + Sync + 'static,
+    V: Clone + Send + Sync + 'static,
+    S: BuildHasher + Clone + Send + Sync + 'static,
+{
+    pub(crate) fn new(cache: &'i BaseCache<K, V, S>) -> Self {
+        Self {
+            cache,
+            keys: cache.keys_by_estimated_frequency().into_iter(),
+        }
+    }
+}
+
+#[cfg(feature = "sync")]
+impl<'i, K, V, S> Iterator for FrequencyIter<'i, K, V, S>
+where
+    K: Eq + Hash + Send + Sync + 'static,
+    V: Clone + Send + Sync + 'static,
+    S: BuildHasher + Clone + Send + Sync + 'static,
+{
+    type Item = (Arc<K>, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for key in self.keys.by_ref() {
+            if let Some(v) = self.cache.scanning_get(&key) {
+                return Some((key, v));
+            }
+        }
+        None
+    }
+}
+
 unsafe impl<'a, 'i, K, V> Send for Iter<'i, K, V>
 where
     K: 'a + Eq + Hash + Send,
@@ -0,0 +1,122 @@
+use parking_lot::Mutex;
+use std::time::Duration;
+
+/// The number of the buckets of the histogram. The upper bounds of the buckets
+/// are 1, 2, 4, ... 2^22 seconds (about 48 days), and the last bucket has no
+/// upper bound.
+const NUM_BUCKETS: usize = 24;
+
+/// Records the lifetimes of the entries removed from the cache into a histogram.
+pub(crate) struct EntryLifetimes {
+    histogram: Mutex<LifetimeHistogram>,
+}
+
+impl EntryLifetimes {
+    pub(crate) fn new() -> Self {
+        Self {
+            histogram: Mutex::new(LifetimeHistogram::default()),
+        }
+    }
+
+    /// Records the lifetime of a removed entry, and whether it was read at least
+    /// once.
+    pub(crate) fn record(&self, lifetime: Duration, was_hit: bool) {
+        // The index of the highest bit set plus one, e.g. 0 for less than a second
+        // and 2 for 2 or 3 seconds.
+        let index =
+            ((u64::BITS - lifetime.as_secs().leading_zeros()) as usize).min(NUM_BUCKETS - 1);
+        let mut histogram = self.histogram.lock();
+        let bucket = &mut histogram.buckets[index];
+        bucket.removed += 1;
+        if !was_hit {
+            bucket.never_hit += 1;
+        }
+    }
+
+    #[cfg(feature = "entry-stats")]
+    pub(crate) fn histogram(&self) -> LifetimeHistogram {
+        self.histogram.lock().clone()
+    }
+}
+
+/// The histogram of the lifetimes of the entries removed from the cache, returned
+/// by the [`Cache::entry_lifetime_histogram`][histogram-method] method.
+///
+/// The lifetime of an entry is the time from when its key was inserted to when
+/// the entry was removed by the expiration, the eviction or the invalidation. The
+/// updates of the value do not restart the lifetime. If most of the entries are
+/// removed long after their last hit, or never hit at all, the time-to-live is
+/// likely longer than useful.
+///
+/// [histogram-method]: ./struct.Cache.html#method.entry_lifetime_histogram
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LifetimeHistogram {
+    buckets: [LifetimeBucket; NUM_BUCKETS],
+}
+
+impl Default for LifetimeHistogram {
+    fn default() -> Self {
+        let mut buckets = [LifetimeBucket::default(); NUM_BUCKETS];
+        for (i, bucket) in buckets.iter_mut().take(NUM_BUCKETS - 1).enumerate() {
+            bucket.upper_bound = Some(Duration::from_secs(1 << i));
+        }
+        Self { buckets }
+    }
+}
+
+impl LifetimeHistogram {
+    /// Returns the buckets of the histogram in the ascending order of their upper
+    /// bounds. The upper bounds are 1, 2, 4, ... 2^22 seconds, and the last
+    /// bucket has no upper bound.
+    pub fn buckets(&self) -> &[LifetimeBucket] {
+        &self.buckets
+    }
+
+    /// Returns the number of the removed entries.
+    pub fn removed(&self) -> u64 {
+        self.buckets.iter().map(|b| b.removed).sum()
+    }
+
+    /// Returns the number of the removed entries that were never read.
+    pub fn never_hit(&self) -> u64 {
+        self.buckets.iter().map(|b| b.never_hit).sum()
+    }
+
+    /// Adds the counters of another segment of the same cache to this.
+    #[cfg(feature = "entry-stats")]
+    pub(crate) fn merge(&mut self, other: &Self) {
+        for (bucket, other) in self.buckets.iter_mut().zip(&other.buckets) {
+            bucket.removed += other.removed;
+            bucket.never_hit += other.never_hit;
+        }
+    }
+}
+
+/// A bucket of the [`LifetimeHistogram`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LifetimeBucket {
+    upper_bound: Option<Duration>,
+    removed: u64,
+    never_hit: u64,
+}
+
+impl LifetimeBucket {
+    /// Returns the exclusive upper bound of the lifetimes in this bucket, or `None`
+    /// for the last bucket. The lower bound is the upper bound of the previous
+    /// bucket.
+    pub fn upper_bound(&self) -> Option<Duration> {
+        self.upper_bound
+    }
+
+    /// Returns the number of the removed entries that lived for a duration in this
+    /// bucket.
+    pub fn removed(&self) -> u64 {
+        self.removed
+    }
+
+    /// Returns the number of the removed entries in this bucket that were never
+    /// read.
+    pub fn never_hit(&self) -> u64 {
+        self.never_hit
+    }
+}
@@ -0,0 +1,70 @@
+use crate::common::concurrent::entry_info::EntryInfo;
+
+use std::{fmt, ops::Deref, sync::Arc};
+use triomphe::Arc as TrioArc;
+
+/// A guard holding a clone of a cached value, which keeps the entry exempt from
+/// the eviction and expiration while the guard lives.
+///
+/// A `PinnedEntry` is returned by the [`get_pinned`][get-pinned-method] method of
+/// cache. When the last guard for the entry is dropped, the entry becomes subject
+/// to the eviction and expiration again. If the entry has already expired by then,
+/// it will be removed by the next housekeeping.
+///
+/// Note that pinning does not prevent the entry from being explicitly invalidated
+/// (e.g. by `invalidate` or `invalidate_all` methods), or replaced by inserting a
+/// new value for the key.
+///
+/// [get-pinned-method]: ./struct.Cache.html#method.get_pinned
+pub struct PinnedEntry<K, V> {
+    entry_info: TrioArc<EntryInfo<K>>,
+    value: V,
+}
+
+impl<K, V> PinnedEntry<K, V> {
+    /// Pins the entry and returns a guard for it.
+    pub(crate) fn new(entry_info: &TrioArc<EntryInfo<K>>, value: V) -> Self {
+        entry_info.pin();
+        Self {
+            entry_info: TrioArc::clone(entry_info),
+            value,
+        }
+    }
+
+    /// Returns a reference to the key of the pinned entry.
+    pub fn key(&self) -> &Arc<K> {
+        &self.entry_info.key_hash().key
+    }
+
+    /// Returns a reference to the value of the pinned entry.
+    pub fn value(&self) -> &V {
+        &self.value
+    }
+}
+
+impl<K, V> Deref for PinnedEntry<K, V> {
+    type Target = V;
+
+    fn deref(&self) -> &Self::Target {
+        &self.value
+    }
+}
+
+impl<K, V> Drop for PinnedEntry<K, V> {
+    fn drop(&mut self) {
+        self.entry_info.unpin();
+    }
+}
+
+impl<K, V> fmt::Debug for PinnedEntry<K, V>
+where
+    K: fmt::Debug,
+    V: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PinnedEntry")
+            .field("key", self.key())
+            .field("value", &self.value)
+            .finish()
+    }
+}
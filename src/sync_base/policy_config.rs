@@ -0,0 +1,119 @@
+use super::{
+    class_stats::KeyClassifier, hot_key::HotKeyConfig, stats::StatsCounterArc,
+    tenant::TenantQuotasConfig, victim::VictimSelector,
+};
+use crate::common::{time::Clock, zeroizer::ValueZeroizer};
+
+/// The policy options of a sync cache set by the methods of the cache builder.
+///
+/// The builder collects them into this struct and passes it down to the
+/// `BaseCache`, so that adding an option does not add a parameter to every
+/// constructor between them. The options of the housekeeper itself are kept in
+/// `HousekeeperConfig`.
+pub(crate) struct PolicyConfig<K, V> {
+    /// The fraction of the max capacity to shed while the memory is under
+    /// pressure. `None` disables watching the memory pressure. Default: `None`.
+    pub(crate) memory_pressure_shed_fraction: Option<f64>,
+    /// If `true`, the housekeeper feeds the reads to the hit ratio analyzer.
+    /// Default: `false`.
+    pub(crate) hit_ratio_analyzer: bool,
+    /// If `true`, the cache remembers the hashes of the keys recently evicted by
+    /// the size constraint. Default: `false`.
+    pub(crate) ghost_list: bool,
+    /// If `true`, the admission policy favors the candidates in the ghost list.
+    /// This also enables the ghost list. Default: `false`.
+    pub(crate) second_chance_readmission: bool,
+    /// If `true`, the housekeeper removes the expired, invalidated and excess
+    /// entries before applying the logs while the cache is over capacity.
+    /// Default: `false`.
+    pub(crate) shrink_first: bool,
+    /// If `true`, the housekeeper does not apply the read logs while the cache is
+    /// over capacity. Used only when `shrink_first` is `true`. Default: `false`.
+    pub(crate) pause_reads_while_shrinking: bool,
+    /// If `true`, the cache counts the reads and writes of each entry, and returns
+    /// the counts in the entry metadata. Default: `false`.
+    pub(crate) entry_counters: bool,
+    /// If `true`, the cache records the lifetimes of the removed entries into a
+    /// histogram. Default: `false`.
+    pub(crate) entry_lifetimes: bool,
+    /// If `true`, the cache samples the reads to record while the read log is
+    /// under pressure, instead of dropping the reads when the log is full.
+    /// Default: `false`.
+    pub(crate) read_sampling: bool,
+    /// If `true`, the frequency sketch is split into segments in the same way as
+    /// the internal hash table, each guarded by its own lock. Default: `false`.
+    pub(crate) segmented_frequency_sketch: bool,
+    /// If `true`, the cache does not record the hit, miss, eviction and load
+    /// counts. Default: `false`.
+    pub(crate) stats_disabled: bool,
+    /// If `true`, the cache gives a new version to every inserted or updated value,
+    /// so that `insert_if_version` can be used. Default: `false`.
+    pub(crate) entry_versions: bool,
+    /// If `true`, the cache records the in-flight loads of the `get_with` family
+    /// methods, so that `load_progress` can return their progress. Default:
+    /// `false`.
+    pub(crate) load_progress: bool,
+    /// The stats counter set by the `stats_counter` method. `None` uses the
+    /// `DefaultStatsCounter`. Default: `None`.
+    pub(crate) stats_counter: Option<StatsCounterArc>,
+    pub(crate) value_zeroizer: ValueZeroizer<V>,
+    pub(crate) tenant_quotas: Option<TenantQuotasConfig<K>>,
+    pub(crate) victim_selector: Option<VictimSelector<K, V>>,
+    pub(crate) hot_keys: Option<HotKeyConfig<K, V>>,
+    pub(crate) key_classifier: Option<KeyClassifier<K>>,
+    pub(crate) expiration_clock: Option<Clock>,
+}
+
+impl<K, V> Default for PolicyConfig<K, V> {
+    fn default() -> Self {
+        Self {
+            memory_pressure_shed_fraction: None,
+            hit_ratio_analyzer: false,
+            ghost_list: false,
+            second_chance_readmission: false,
+            shrink_first: false,
+            pause_reads_while_shrinking: false,
+            entry_counters: false,
+            entry_lifetimes: false,
+            read_sampling: false,
+            segmented_frequency_sketch: false,
+            stats_disabled: false,
+            entry_versions: false,
+            load_progress: false,
+            stats_counter: None,
+            value_zeroizer: ValueZeroizer::default(),
+            tenant_quotas: None,
+            victim_selector: None,
+            hot_keys: None,
+            key_classifier: None,
+            expiration_clock: None,
+        }
+    }
+}
+
+impl<K, V> Clone for PolicyConfig<K, V> {
+    fn clone(&self) -> Self {
+        Self {
+            memory_pressure_shed_fraction: self.memory_pressure_shed_fraction,
+            hit_ratio_analyzer: self.hit_ratio_analyzer,
+            ghost_list: self.ghost_list,
+            second_chance_readmission: self.second_chance_readmission,
+            shrink_first: self.shrink_first,
+            pause_reads_while_shrinking: self.pause_reads_while_shrinking,
+            entry_counters: self.entry_counters,
+            entry_lifetimes: self.entry_lifetimes,
+            read_sampling: self.read_sampling,
+            segmented_frequency_sketch: self.segmented_frequency_sketch,
+            stats_disabled: self.stats_disabled,
+            entry_versions: self.entry_versions,
+            load_progress: self.load_progress,
+            stats_counter: self.stats_counter.clone(),
+            value_zeroizer: self.value_zeroizer,
+            tenant_quotas: self.tenant_quotas.clone(),
+            victim_selector: self.victim_selector.clone(),
+            hot_keys: self.hot_keys.clone(),
+            key_classifier: self.key_classifier.clone(),
+            expiration_clock: self.expiration_clock.clone(),
+        }
+    }
+}
@@ -0,0 +1,146 @@
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+
+/// The max sampling interval, i.e. recording 1 of 64 reads.
+const MAX_SAMPLING_INTERVAL: u32 = 64;
+
+/// Samples the reads to record to the read log when the log is under pressure.
+///
+/// Instead of letting the log fill up and dropping whatever reads come after, it
+/// records every N-th read, so that the frequencies of all keys are scaled down
+/// evenly. The interval N is doubled while the log stays more than three-quarters
+/// full, and halved while it is less than a quarter full.
+pub(crate) struct ReadSampler {
+    interval: AtomicU32,
+    reads: AtomicU64,
+    sampled_out: AtomicU64,
+    dropped: AtomicU64,
+}
+
+impl ReadSampler {
+    pub(crate) fn new() -> Self {
+        Self {
+            interval: AtomicU32::new(1),
+            reads: AtomicU64::default(),
+            sampled_out: AtomicU64::default(),
+            dropped: AtomicU64::default(),
+        }
+    }
+
+    /// Returns `true` if the current read should be recorded.
+    #[inline]
+    pub(crate) fn should_record(&self) -> bool {
+        let interval = self.interval.load(Ordering::Relaxed);
+        if interval <= 1 {
+            return true;
+        }
+        let n = self.reads.fetch_add(1, Ordering::Relaxed);
+        if n % interval as u64 == 0 {
+            true
+        } else {
+            self.sampled_out.fetch_add(1, Ordering::Relaxed);
+            false
+        }
+    }
+
+    /// Counts a read dropped because the read log was full.
+    pub(crate) fn record_dropped(&self) {
+        self.dropped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Adjusts the interval to the number of the pending reads in the read log
+    /// with the `capacity`. Called by the housekeeper before applying the reads.
+    pub(crate) fn adapt(&self, pending: usize, capacity: usize) {
+        let interval = self.interval.load(Ordering::Relaxed);
+        let new_interval = if pending * 4 >= capacity * 3 {
+            (interval * 2).min(MAX_SAMPLING_INTERVAL)
+        } else if pending * 4 <= capacity {
+            (interval / 2).max(1)
+        } else {
+            interval
+        };
+        if new_interval != interval {
+            self.interval.store(new_interval, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn stats(&self) -> ReadSamplingStats {
+        ReadSamplingStats {
+            interval: self.interval.load(Ordering::Relaxed),
+            sampled_out: self.sampled_out.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// The state of the sampling of the reads, returned by the
+/// [`Cache::read_sampling_stats`][stats-method] method.
+///
+/// [stats-method]: ./struct.Cache.html#method.read_sampling_stats
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ReadSamplingStats {
+    interval: u32,
+    sampled_out: u64,
+    dropped: u64,
+}
+
+impl ReadSamplingStats {
+    /// Returns the current sampling interval. `1` means that every read is
+    /// recorded, and `N` means that one of `N` reads is recorded.
+    pub fn interval(&self) -> u32 {
+        self.interval
+    }
+
+    /// Returns the number of the reads not recorded by the sampling.
+    pub fn sampled_out(&self) -> u64 {
+        self.sampled_out
+    }
+
+    /// Returns the number of the sampled reads dropped because the read log was
+    /// still full.
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Merges the state of another segment of the same cache to this. The interval
+    /// becomes the largest of them.
+    pub(crate) fn merge(&mut self, other: &Self) {
+        self.interval = self.interval.max(other.interval);
+        self.sampled_out += other.sampled_out;
+        self.dropped += other.dropped;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ReadSampler;
+
+    #[test]
+    fn adapt_interval() {
+        let sampler = ReadSampler::new();
+        assert!((0..10).all(|_| sampler.should_record()));
+
+        // Under pressure.
+        sampler.adapt(80, 100);
+        sampler.adapt(80, 100);
+        assert_eq!(sampler.stats().interval(), 4);
+        let recorded = (0..100).filter(|_| sampler.should_record()).count();
+        assert_eq!(recorded, 25);
+        assert_eq!(sampler.stats().sampled_out(), 75);
+
+        // Capped at the max interval.
+        for _ in 0..10 {
+            sampler.adapt(100, 100);
+        }
+        assert_eq!(sampler.stats().interval(), 64);
+
+        // Neither high nor low.
+        sampler.adapt(50, 100);
+        assert_eq!(sampler.stats().interval(), 64);
+
+        // The pressure goes.
+        for _ in 0..10 {
+            sampler.adapt(0, 100);
+        }
+        assert_eq!(sampler.stats().interval(), 1);
+    }
+}
@@ -0,0 +1,108 @@
+use crate::common::frequency_sketch::FrequencySketch;
+
+use parking_lot::RwLock;
+
+/// The min capacity of a segment, so that splitting a small cache into many
+/// segments does not leave each segment with a table too small to be useful.
+const MIN_SEGMENT_CAPACITY: u32 = 16;
+
+/// A frequency sketch split into segments, each of which is guarded by its own
+/// lock.
+///
+/// An element is mapped to a segment by the upper bits of its hash, in the same
+/// way as the segments of the concurrent hash table. Each segment is sized and
+/// aged independently, so the frequencies of the elements in a segment are not
+/// affected by the other segments. With a single segment, this works exactly as a
+/// plain `FrequencySketch` behind a lock.
+pub(crate) struct SegmentedFrequencySketch {
+    segments: Box<[RwLock<FrequencySketch>]>,
+    segment_shift: u32,
+}
+
+impl SegmentedFrequencySketch {
+    /// Creates a sketch with the given number of segments, rounded up to the next
+    /// power of two.
+    pub(crate) fn new(num_segments: usize) -> Self {
+        assert!(num_segments > 0);
+        let num_segments = num_segments.next_power_of_two();
+        let segments = (0..num_segments)
+            .map(|_| RwLock::new(FrequencySketch::default()))
+            .collect::<Vec<_>>()
+            .into_boxed_slice();
+        // `hash >> 64` would overflow, so a single segment is handled by
+        // `segment()` instead.
+        let segment_shift = u64::BITS - num_segments.trailing_zeros();
+        Self {
+            segments,
+            segment_shift,
+        }
+    }
+
+    /// Ensures that the segments together can estimate the popularity of the
+    /// elements of a cache having the given capacity. See
+    /// `FrequencySketch::ensure_capacity`.
+    pub(crate) fn ensure_capacity(&self, cap: u32) {
+        let num_segments = self.segments.len() as u32;
+        let segment_cap = if num_segments == 1 {
+            cap
+        } else {
+            (cap / num_segments).max(MIN_SEGMENT_CAPACITY)
+        };
+        for segment in self.segments.iter() {
+            segment.write().ensure_capacity(segment_cap);
+        }
+    }
+
+    /// Returns the estimated number of occurrences of the element, up to the
+    /// maximum (15).
+    pub(crate) fn frequency(&self, hash: u64) -> u8 {
+        self.segment(hash).read().frequency(hash)
+    }
+
+    /// Increments the popularity of the element. Only the segment of the element
+    /// is locked.
+    pub(crate) fn increment(&self, hash: u64) {
+        self.segment(hash).write().increment(hash);
+    }
+
+    #[inline]
+    fn segment(&self, hash: u64) -> &RwLock<FrequencySketch> {
+        if self.segments.len() == 1 {
+            &self.segments[0]
+        } else {
+            &self.segments[(hash >> self.segment_shift) as usize]
+        }
+    }
+}
+
+// Methods only available for testing.
+#[cfg(test)]
+impl SegmentedFrequencySketch {
+    pub(crate) fn table_len(&self) -> usize {
+        self.segments.iter().map(|s| s.read().table_len()).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SegmentedFrequencySketch;
+
+    #[test]
+    fn segments_are_independent() {
+        let sketch = SegmentedFrequencySketch::new(4);
+        sketch.ensure_capacity(512);
+        assert_eq!(sketch.table_len(), 512);
+
+        // The upper two bits select the segment.
+        let hash_in = |segment: u64, low: u64| (segment << 62) | low;
+
+        for _ in 0..5 {
+            sketch.increment(hash_in(1, 42));
+        }
+        assert_eq!(sketch.frequency(hash_in(1, 42)), 5);
+        // The same lower bits in the other segments are not counted.
+        assert_eq!(sketch.frequency(hash_in(0, 42)), 0);
+        assert_eq!(sketch.frequency(hash_in(2, 42)), 0);
+        assert_eq!(sketch.frequency(hash_in(3, 42)), 0);
+    }
+}
@@ -0,0 +1,253 @@
+use crate::common::thread_number;
+
+use std::{
+    num::NonZeroUsize,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+pub(crate) type StatsCounterArc = Arc<dyn StatsCounter + 'static>;
+
+/// The max number of the stripes of a `StatsCounter`.
+const MAX_STRIPES: usize = 64;
+
+/// A set of the counters, aligned to a cache line so that the threads recording
+/// to different stripes do not share the line.
+#[derive(Default)]
+#[repr(align(64))]
+struct Stripe {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+    load_successes: AtomicU64,
+    load_failures: AtomicU64,
+}
+
+/// Receives the hit, miss, eviction and load events of a cache.
+///
+/// The cache records the events to a [`DefaultStatsCounter`][default-counter]
+/// unless another implementation is given by the
+/// [`stats_counter`][stats-counter-method] method of the builder, e.g. to
+/// forward the events to a metrics backend.
+///
+/// The hits and the misses are recorded by the threads reading the cache, on
+/// every read, so the methods should return quickly and must not call the
+/// methods of the same cache.
+///
+/// [default-counter]: ./struct.DefaultStatsCounter.html
+/// [stats-counter-method]: ./struct.CacheBuilder.html#method.stats_counter
+pub trait StatsCounter: Send + Sync {
+    /// Records a read that found a value in the cache.
+    fn record_hit(&self);
+
+    /// Records a read that did not find a value in the cache.
+    fn record_miss(&self);
+
+    /// Records the given number (more than zero) of the entries evicted for the
+    /// capacity or removed on expiration.
+    fn record_evictions(&self, count: u64);
+
+    /// Records an `init` closure of `get_with` or a similar method that returned a
+    /// value to insert.
+    fn record_load_success(&self);
+
+    /// Records an `init` closure of `optionally_get_with` or `try_get_with` (or
+    /// their variants) that returned `None` or an error.
+    fn record_load_failure(&self);
+
+    /// Returns the stats returned by the [`stats`][stats-method] method of the
+    /// cache. The default implementation returns all zeros, for the counters that
+    /// only forward the events.
+    ///
+    /// [stats-method]: ./struct.Cache.html#method.stats
+    fn snapshot(&self) -> CacheStats {
+        CacheStats::default()
+    }
+}
+
+/// The [`StatsCounter`][stats-counter] used by default. It records the events to
+/// the counters striped by the calling thread, so that the threads reading the
+/// cache concurrently do not contend on a single atomic counter.
+///
+/// [stats-counter]: ./trait.StatsCounter.html
+pub struct DefaultStatsCounter {
+    stripes: Box<[Stripe]>,
+    stripe_mask: usize,
+}
+
+impl Default for DefaultStatsCounter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DefaultStatsCounter {
+    /// Creates a counter with all counts zero.
+    pub fn new() -> Self {
+        let num_stripes = std::thread::available_parallelism()
+            .map_or(1, NonZeroUsize::get)
+            .next_power_of_two()
+            .min(MAX_STRIPES);
+        let stripes = (0..num_stripes)
+            .map(|_| Stripe::default())
+            .collect::<Vec<_>>()
+            .into_boxed_slice();
+        Self {
+            stripes,
+            stripe_mask: num_stripes - 1,
+        }
+    }
+
+    #[inline]
+    fn stripe(&self) -> &Stripe {
+        &self.stripes[thread_number::current() & self.stripe_mask]
+    }
+}
+
+impl StatsCounter for DefaultStatsCounter {
+    #[inline]
+    fn record_hit(&self) {
+        self.stripe().hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[inline]
+    fn record_miss(&self) {
+        self.stripe().misses.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn record_evictions(&self, count: u64) {
+        self.stripe().evictions.fetch_add(count, Ordering::Relaxed);
+    }
+
+    fn record_load_success(&self) {
+        self.stripe().load_successes.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn record_load_failure(&self) {
+        self.stripe().load_failures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Returns the sums of the counters over the stripes. The sums are not an
+    /// atomic snapshot while the other threads are recording.
+    fn snapshot(&self) -> CacheStats {
+        let sum = |f: fn(&Stripe) -> &AtomicU64| {
+            self.stripes
+                .iter()
+                .map(|s| f(s).load(Ordering::Relaxed))
+                .sum()
+        };
+        CacheStats {
+            hit_count: sum(|s| &s.hits),
+            miss_count: sum(|s| &s.misses),
+            eviction_count: sum(|s| &s.evictions),
+            load_success_count: sum(|s| &s.load_successes),
+            load_failure_count: sum(|s| &s.load_failures),
+            ..Default::default()
+        }
+    }
+}
+
+/// A snapshot of the stats of a cache, returned by the
+/// [`Cache::stats`][stats-method] method.
+///
+/// [stats-method]: ./struct.Cache.html#method.stats
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    hit_count: u64,
+    miss_count: u64,
+    eviction_count: u64,
+    load_success_count: u64,
+    load_failure_count: u64,
+    degraded_op_count: u64,
+}
+
+impl CacheStats {
+    /// Creates stats having the given counts, for the implementations of
+    /// [`StatsCounter::snapshot`][snapshot-method].
+    ///
+    /// [snapshot-method]: ./trait.StatsCounter.html#method.snapshot
+    pub fn new(
+        hit_count: u64,
+        miss_count: u64,
+        eviction_count: u64,
+        load_success_count: u64,
+        load_failure_count: u64,
+    ) -> Self {
+        Self {
+            hit_count,
+            miss_count,
+            eviction_count,
+            load_success_count,
+            load_failure_count,
+            degraded_op_count: 0,
+        }
+    }
+
+    /// Sets the number of the operations not recorded by the degraded cache. It is
+    /// kept by the cache rather than the `StatsCounter`.
+    pub(crate) fn with_degraded_op_count(self, degraded_op_count: u64) -> Self {
+        Self {
+            degraded_op_count,
+            ..self
+        }
+    }
+
+    /// Returns the number of the reads that found a value in the cache.
+    pub fn hit_count(&self) -> u64 {
+        self.hit_count
+    }
+
+    /// Returns the number of the reads that did not find a value in the cache.
+    pub fn miss_count(&self) -> u64 {
+        self.miss_count
+    }
+
+    /// Returns the number of the reads, i.e. the sum of the hits and the misses.
+    pub fn request_count(&self) -> u64 {
+        self.hit_count.saturating_add(self.miss_count)
+    }
+
+    /// Returns the ratio of the hits to the reads, or `1.0` if there has been no
+    /// read.
+    pub fn hit_rate(&self) -> f64 {
+        match self.request_count() {
+            0 => 1.0,
+            requests => self.hit_count as f64 / requests as f64,
+        }
+    }
+
+    /// Returns the number of the entries evicted for the capacity or removed on
+    /// expiration. The entries removed explicitly, such as by `invalidate` or
+    /// `invalidate_entries_if`, are not counted.
+    pub fn eviction_count(&self) -> u64 {
+        self.eviction_count
+    }
+
+    /// Returns the number of the `init` closures of `get_with` and the similar
+    /// methods that returned a value to insert.
+    pub fn load_success_count(&self) -> u64 {
+        self.load_success_count
+    }
+
+    /// Returns the number of the `init` closures of `optionally_get_with` and
+    /// `try_get_with` (and their variants) that returned `None` or an error.
+    pub fn load_failure_count(&self) -> u64 {
+        self.load_failure_count
+    }
+
+    /// Returns `true` if the cache has lost the internal channels to its
+    /// housekeeper. See [`Cache::is_degraded`][is-degraded-method].
+    ///
+    /// [is-degraded-method]: ./struct.Cache.html#method.is_degraded
+    pub fn is_degraded(&self) -> bool {
+        self.degraded_op_count > 0
+    }
+
+    /// Returns the number of the reads and writes that were not recorded for the
+    /// eviction and expiration policies because the cache was degraded.
+    pub fn degraded_op_count(&self) -> u64 {
+        self.degraded_op_count
+    }
+}
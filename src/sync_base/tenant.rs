@@ -0,0 +1,270 @@
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+/// Returns the index of the tenant of a key, or `None` if the key does not belong
+/// to any tenant.
+pub(crate) type TenantClassifier<K> = Arc<dyn Fn(&K) -> Option<usize> + Send + Sync + 'static>;
+
+/// The configuration of the tenant quotas set by the `tenant_quotas` method of the
+/// cache builder.
+pub(crate) struct TenantQuotasConfig<K> {
+    classifier: TenantClassifier<K>,
+    quotas: Vec<u64>,
+    /// The max numbers of entries of the tenants set by the `tenant_max_entries`
+    /// method of the cache builder.
+    max_entries: Vec<u64>,
+}
+
+impl<K> Clone for TenantQuotasConfig<K> {
+    fn clone(&self) -> Self {
+        Self {
+            classifier: Arc::clone(&self.classifier),
+            quotas: self.quotas.clone(),
+            max_entries: self.max_entries.clone(),
+        }
+    }
+}
+
+impl<K> TenantQuotasConfig<K> {
+    pub(crate) fn new(classifier: TenantClassifier<K>, quotas: Vec<u64>) -> Self {
+        Self {
+            classifier,
+            quotas,
+            max_entries: Vec::default(),
+        }
+    }
+
+    pub(crate) fn with_max_entries(self, max_entries: Vec<u64>) -> Self {
+        Self {
+            max_entries,
+            ..self
+        }
+    }
+
+    /// Returns the sum of the quotas.
+    pub(crate) fn total(&self) -> u64 {
+        self.quotas
+            .iter()
+            .fold(0u64, |total, quota| total.saturating_add(*quota))
+    }
+
+    /// Returns the configuration for each of `num` segments of a cache, dividing
+    /// the quotas in the same way as the max capacity.
+    pub(crate) fn split(&self, num: usize) -> Self {
+        Self {
+            classifier: Arc::clone(&self.classifier),
+            quotas: Self::split_each(&self.quotas, num),
+            max_entries: Self::split_each(&self.max_entries, num),
+        }
+    }
+
+    fn split_each(values: &[u64], num: usize) -> Vec<u64> {
+        values
+            .iter()
+            .map(|&value| (value as f64 / num as f64).ceil() as u64)
+            .collect()
+    }
+}
+
+/// The quotas and the weighted sizes of the tenants of a cache.
+///
+/// The keys not belonging to any tenant share the capacity not assigned to the
+/// tenants, as if they were another tenant at the last index. The shared tenant
+/// has no max number of entries.
+pub(crate) struct TenantQuotas<K> {
+    classifier: TenantClassifier<K>,
+    quotas: Vec<u64>,
+    max_entries: Vec<Option<u64>>,
+    /// Updated only while the deques lock is held.
+    weighted_sizes: Vec<AtomicU64>,
+    /// Updated only while the deques lock is held.
+    entry_counts: Vec<AtomicU64>,
+}
+
+impl<K> TenantQuotas<K> {
+    pub(crate) fn new(config: TenantQuotasConfig<K>, max_capacity: u64) -> Self {
+        let shared = max_capacity.saturating_sub(config.total());
+        let TenantQuotasConfig {
+            classifier,
+            mut quotas,
+            max_entries,
+        } = config;
+        quotas.push(shared);
+        // The tenants without the max number of entries given, including the shared
+        // one, are not limited.
+        let max_entries = (0..quotas.len())
+            .map(|tenant| {
+                max_entries
+                    .get(tenant)
+                    .copied()
+                    .filter(|_| tenant + 1 < quotas.len())
+            })
+            .collect();
+        let counters = || quotas.iter().map(|_| AtomicU64::default()).collect();
+        Self {
+            classifier,
+            weighted_sizes: counters(),
+            entry_counts: counters(),
+            quotas,
+            max_entries,
+        }
+    }
+
+    /// Returns the number of the tenants including the shared one.
+    pub(crate) fn num_tenants(&self) -> usize {
+        self.quotas.len()
+    }
+
+    /// Returns the index of the tenant of the `key`. The keys not belonging to any
+    /// tenant are given the index of the shared tenant.
+    pub(crate) fn tenant_of(&self, key: &K) -> usize {
+        let shared = self.quotas.len() - 1;
+        (self.classifier)(key)
+            .filter(|&tenant| tenant < shared)
+            .unwrap_or(shared)
+    }
+
+    pub(crate) fn quota_of(&self, key: &K) -> u64 {
+        self.quotas[self.tenant_of(key)]
+    }
+
+    /// Returns the weighted size of the `tenant`, or `None` if there is no such
+    /// tenant. The shared tenant is not exposed.
+    pub(crate) fn weighted_size(&self, tenant: usize) -> Option<u64> {
+        if tenant + 1 < self.weighted_sizes.len() {
+            Some(self.weighted_sizes[tenant].load(Ordering::Acquire))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the number of the entries of the `tenant`, or `None` if there is no
+    /// such tenant. The shared tenant is not exposed.
+    pub(crate) fn entry_count(&self, tenant: usize) -> Option<u64> {
+        if tenant + 1 < self.entry_counts.len() {
+            Some(self.entry_counts[tenant].load(Ordering::Acquire))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the weight by which the `tenant` exceeds its quota.
+    pub(crate) fn excess(&self, tenant: usize) -> u64 {
+        self.weighted_sizes[tenant]
+            .load(Ordering::Acquire)
+            .saturating_sub(self.quotas[tenant])
+    }
+
+    /// Returns the number of entries by which the `tenant` exceeds its max number
+    /// of entries.
+    pub(crate) fn excess_entries(&self, tenant: usize) -> u64 {
+        self.max_entries[tenant].map_or(0, |max| {
+            self.entry_counts[tenant]
+                .load(Ordering::Acquire)
+                .saturating_sub(max)
+        })
+    }
+
+    /// Returns `true` if the `tenant` can take a new entry of the `weight` without
+    /// exceeding its quota or its max number of entries.
+    pub(crate) fn has_room_for(&self, tenant: usize, weight: u64) -> bool {
+        let size = self.weighted_sizes[tenant].load(Ordering::Acquire);
+        let count = self.entry_counts[tenant].load(Ordering::Acquire);
+        size.saturating_add(weight) <= self.quotas[tenant]
+            && self.max_entries[tenant].map_or(true, |max| count < max)
+    }
+
+    /// Adds an entry of the `key` with the `weight` to its tenant.
+    pub(crate) fn add(&self, key: &K, weight: u32) {
+        let tenant = self.tenant_of(key);
+        Self::update(&self.weighted_sizes[tenant], |size| {
+            size.saturating_add(weight as u64)
+        });
+        Self::update(&self.entry_counts[tenant], |count| count.saturating_add(1));
+    }
+
+    /// Removes an entry of the `key` with the `weight` from its tenant.
+    pub(crate) fn sub(&self, key: &K, weight: u32) {
+        let tenant = self.tenant_of(key);
+        Self::update(&self.weighted_sizes[tenant], |size| {
+            size.saturating_sub(weight as u64)
+        });
+        Self::update(&self.entry_counts[tenant], |count| count.saturating_sub(1));
+    }
+
+    /// Updates the weight of an entry of the `key` from `old_weight` to
+    /// `new_weight`.
+    pub(crate) fn update_weight(&self, key: &K, old_weight: u32, new_weight: u32) {
+        Self::update(&self.weighted_sizes[self.tenant_of(key)], |size| {
+            size.saturating_sub(old_weight as u64)
+                .saturating_add(new_weight as u64)
+        });
+    }
+
+    fn update(counter: &AtomicU64, f: impl FnOnce(u64) -> u64) {
+        counter.store(f(counter.load(Ordering::Acquire)), Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TenantQuotas, TenantQuotasConfig};
+    use std::sync::Arc;
+
+    #[test]
+    fn tenant_accounting() {
+        let classifier = Arc::new(|key: &u32| (*key < 300).then_some(*key as usize / 100));
+        let config = TenantQuotasConfig::new(classifier, vec![10, 20]);
+        assert_eq!(config.total(), 30);
+        assert_eq!(config.split(4).quotas, vec![3, 5]);
+
+        let quotas = TenantQuotas::new(config, 100);
+        assert_eq!(quotas.num_tenants(), 3);
+        assert_eq!(quotas.tenant_of(&50), 0);
+        assert_eq!(quotas.tenant_of(&150), 1);
+        // Out of range tenants and unclassified keys go to the shared tenant.
+        assert_eq!(quotas.tenant_of(&250), 2);
+        assert_eq!(quotas.tenant_of(&350), 2);
+        assert_eq!(quotas.quota_of(&350), 70);
+
+        quotas.add(&1, 8);
+        quotas.add(&2, 4);
+        quotas.add(&350, 4);
+        assert_eq!(quotas.weighted_size(0), Some(12));
+        assert_eq!(quotas.weighted_size(1), Some(0));
+        assert_eq!(quotas.weighted_size(2), None);
+        assert_eq!(quotas.excess(0), 2);
+        assert_eq!(quotas.excess(2), 0);
+
+        quotas.sub(&2, 4);
+        assert_eq!(quotas.excess(0), 0);
+
+        quotas.update_weight(&1, 8, 12);
+        assert_eq!(quotas.weighted_size(0), Some(12));
+        assert_eq!(quotas.entry_count(0), Some(1));
+        assert_eq!(quotas.entry_count(2), None);
+    }
+
+    #[test]
+    fn tenant_max_entries() {
+        let classifier = Arc::new(|key: &u32| (*key < 300).then_some(*key as usize / 100));
+        let config =
+            TenantQuotasConfig::new(classifier, vec![10, 20, 30]).with_max_entries(vec![2]);
+        assert_eq!(config.split(2).max_entries, vec![1]);
+
+        let quotas = TenantQuotas::new(config, 100);
+        for key in [1, 2, 3, 101, 102, 350, 351] {
+            quotas.add(&key, 1);
+        }
+        assert_eq!(quotas.entry_count(0), Some(3));
+        assert_eq!(quotas.excess_entries(0), 1);
+        // The tenants without the max number of entries are not limited.
+        assert_eq!(quotas.excess_entries(1), 0);
+        assert_eq!(quotas.excess_entries(3), 0);
+
+        quotas.sub(&1, 1);
+        assert_eq!(quotas.excess_entries(0), 0);
+    }
+}
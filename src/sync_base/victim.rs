@@ -0,0 +1,23 @@
+use std::sync::Arc;
+
+pub(crate) type VictimSelector<K, V> =
+    Arc<dyn Fn(&K, &V) -> VictimDecision + Send + Sync + 'static>;
+
+/// The decision of the victim selector closure set by
+/// [`CacheBuilder::victim_selector`][builder-victim-selector] on a candidate for
+/// the eviction.
+///
+/// [builder-victim-selector]: ./struct.CacheBuilder.html#method.victim_selector
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VictimDecision {
+    /// The entry may be evicted. This is what the cache does without a selector.
+    Evict,
+    /// The entry must not be evicted this time. It keeps its position in the
+    /// eviction order, so it will be offered to the selector again at the next
+    /// eviction.
+    Keep,
+    /// The entry must not be evicted this time, and is moved to the end of the
+    /// eviction order, so that the other entries are offered to the selector
+    /// before it.
+    Defer,
+}
@@ -0,0 +1,412 @@
+//! Provides a response caching middleware for [Tower][tower-crate] services,
+//! powered by [`future::Cache`][future-cache].
+//!
+//! To use this module, enable a crate feature called "tower".
+//!
+//! [`CacheLayer`] is a [`Layer`] wrapping a [`Service`] into a [`CacheService`],
+//! which returns the cached response for a request whose key is in the cache, and
+//! calls the inner service otherwise. Concurrent requests for the same key are coalesced, so that
+//! only one of them calls the inner service (stampede protection). To expire the
+//! responses by their own metadata, such as the `Cache-Control` header, build the
+//! cache with [`ResponseTtl`].
+//!
+//! [tower-crate]: https://crates.io/crates/tower
+//! [future-cache]: ../future/struct.Cache.html
+//!
+//! # Example
+//!
+//! ```rust
+//! // Cargo.toml
+//! //
+//! // [dependencies]
+//! // moka = { version = "0.12", features = ["tower"] }
+//! // tower-service = "0.3"
+//! // tokio = { version = "1", features = ["rt-multi-thread", "macros" ] }
+//!
+//! use moka::{
+//!     future::Cache,
+//!     tower::{CacheLayer, ResponseTtl},
+//! };
+//! use std::{
+//!     convert::Infallible,
+//!     future::{ready, Ready},
+//!     task::{Context, Poll},
+//!     time::Duration,
+//! };
+//! use tower_service::Service;
+//!
+//! // A service returning the length of the request path with the max age of the
+//! // response.
+//! #[derive(Clone)]
+//! struct PathLen;
+//!
+//! impl Service<String> for PathLen {
+//!     type Response = (usize, Duration);
+//!     type Error = Infallible;
+//!     type Future = Ready<Result<Self::Response, Self::Error>>;
+//!
+//!     fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+//!         Poll::Ready(Ok(()))
+//!     }
+//!
+//!     fn call(&mut self, path: String) -> Self::Future {
+//!         ready(Ok((path.len(), Duration::from_secs(60))))
+//!     }
+//! }
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let cache = Cache::builder()
+//!         .max_capacity(10_000)
+//!         .expire_after(ResponseTtl::new(|(_len, max_age): &(usize, Duration)| {
+//!             Some(*max_age)
+//!         }))
+//!         .build();
+//!     let layer = CacheLayer::new(cache.clone(), |path: &String| Some(path.clone()));
+//!     let mut service = layer.layer(PathLen);
+//!
+//!     let response = service.call("/index.html".to_string()).await.unwrap();
+//!     assert_eq!(response.0, 11);
+//!     assert!(cache.contains_key("/index.html"));
+//! }
+//! ```
+//!
+//! # Using with `tower::ServiceBuilder`
+//!
+//! `CacheLayer` implements `tower::Layer`, so it can be added to a
+//! `tower::ServiceBuilder` as is:
+//!
+//! ```rust,ignore
+//! let service = tower::ServiceBuilder::new().layer(layer).service(inner);
+//! ```
+//!
+//! # Error type
+//!
+//! The error type of [`CacheService`] is `Arc<Svc::Error>`, not the error type
+//! `Svc::Error` of the inner service, because an error is shared by all the
+//! coalesced requests that were waiting for it. Adding the layer to an existing
+//! stack therefore changes its error type, and the outer layers and the error
+//! handlers need to accept the `Arc`. If the inner error type is `Clone`, it can
+//! be restored with `tower::ServiceExt::map_err`:
+//!
+//! ```rust,ignore
+//! let service = layer
+//!     .layer(inner)
+//!     .map_err(|e: Arc<MyError>| MyError::clone(&e));
+//! ```
+
+use crate::{future::Cache, Expiry};
+
+use std::{
+    fmt,
+    future::Future,
+    hash::{BuildHasher, Hash},
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+use tower_layer::Layer;
+use tower_service::Service;
+
+type KeyExtractor<Req, K> = Arc<dyn Fn(&Req) -> Option<K> + Send + Sync + 'static>;
+
+/// A layer wrapping a [`Service`] into a [`CacheService`].
+///
+/// See [the module level documentation](./index.html) for an example.
+pub struct CacheLayer<Req, K, Resp, S = std::collections::hash_map::RandomState> {
+    cache: Cache<K, Resp, S>,
+    key_extractor: KeyExtractor<Req, K>,
+}
+
+impl<Req, K, Resp, S> Clone for CacheLayer<Req, K, Resp, S> {
+    fn clone(&self) -> Self {
+        Self {
+            cache: self.cache.clone(),
+            key_extractor: Arc::clone(&self.key_extractor),
+        }
+    }
+}
+
+impl<Req, K, Resp, S> fmt::Debug for CacheLayer<Req, K, Resp, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CacheLayer").finish_non_exhaustive()
+    }
+}
+
+impl<Req, K, Resp, S> CacheLayer<Req, K, Resp, S> {
+    /// Constructs a new `CacheLayer` caching the responses in the `cache`.
+    ///
+    /// `key_extractor` returns the key of the response to a request. When it
+    /// returns `None`, the request bypasses the cache and is always handled by the
+    /// inner service.
+    pub fn new(
+        cache: Cache<K, Resp, S>,
+        key_extractor: impl Fn(&Req) -> Option<K> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            cache,
+            key_extractor: Arc::new(key_extractor),
+        }
+    }
+
+    /// Wraps the `inner` service into a [`CacheService`] sharing the cache of this
+    /// layer. This is the same as [`Layer::layer`], without importing the trait.
+    pub fn layer<Svc>(&self, inner: Svc) -> CacheService<Svc, Req, K, Resp, S> {
+        CacheService {
+            inner,
+            cache: self.cache.clone(),
+            key_extractor: Arc::clone(&self.key_extractor),
+        }
+    }
+}
+
+impl<Svc, Req, K, Resp, S> Layer<Svc> for CacheLayer<Req, K, Resp, S> {
+    type Service = CacheService<Svc, Req, K, Resp, S>;
+
+    fn layer(&self, inner: Svc) -> Self::Service {
+        CacheLayer::layer(self, inner)
+    }
+}
+
+/// A [`Service`] returning the cached responses of the inner service.
+///
+/// Created by [`CacheLayer::layer`]. The errors of the inner service are not
+/// cached, and are returned in an `Arc` as they may be shared by the coalesced
+/// requests. See [the module level documentation](./index.html#error-type) for
+/// how this changes the error type of the stack.
+pub struct CacheService<Svc, Req, K, Resp, S = std::collections::hash_map::RandomState> {
+    inner: Svc,
+    cache: Cache<K, Resp, S>,
+    key_extractor: KeyExtractor<Req, K>,
+}
+
+impl<Svc: Clone, Req, K, Resp, S> Clone for CacheService<Svc, Req, K, Resp, S> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            cache: self.cache.clone(),
+            key_extractor: Arc::clone(&self.key_extractor),
+        }
+    }
+}
+
+impl<Svc, Req, K, Resp, S> fmt::Debug for CacheService<Svc, Req, K, Resp, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CacheService").finish_non_exhaustive()
+    }
+}
+
+impl<Svc, Req, K, Resp, S> Service<Req> for CacheService<Svc, Req, K, Resp, S>
+where
+    Svc: Service<Req, Response = Resp> + Clone + Send + 'static,
+    Svc::Future: Send + 'static,
+    Svc::Error: Send + Sync + 'static,
+    Req: Send + 'static,
+    K: Hash + Eq + Send + Sync + 'static,
+    Resp: Clone + Send + Sync + 'static,
+    S: BuildHasher + Clone + Send + Sync + 'static,
+{
+    type Response = Resp;
+    type Error = Arc<Svc::Error>;
+    type Future = Pin<Box<dyn Future<Output = Result<Resp, Self::Error>> + Send + 'static>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(Arc::new)
+    }
+
+    fn call(&mut self, req: Req) -> Self::Future {
+        // Take the service that has been driven to readiness, and leave a clone in
+        // its place. See the documentation of `tower::Service` for why a clone
+        // should not be called without driving it to readiness.
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+
+        let Some(key) = (self.key_extractor)(&req) else {
+            let fut = inner.call(req);
+            return Box::pin(async move { fut.await.map_err(Arc::new) });
+        };
+
+        let cache = self.cache.clone();
+        // Call the inner service only when the response is not in the cache.
+        let init = async move { inner.call(req).await };
+        Box::pin(async move { cache.try_get_with(key, init).await })
+    }
+}
+
+/// An [`Expiry`] expiring the cached responses after the time to live given by
+/// the responses themselves.
+///
+/// The time to live is set when a response is inserted or replaced, and is not
+/// changed when it is read.
+pub struct ResponseTtl<F> {
+    ttl: F,
+}
+
+impl<F> ResponseTtl<F> {
+    /// Constructs a new `ResponseTtl`. `ttl` returns the time to live of a
+    /// response, or `None` if the response does not expire (other than by the
+    /// time to live and time to idle of the cache).
+    pub fn new<Resp>(ttl: F) -> Self
+    where
+        F: Fn(&Resp) -> Option<Duration>,
+    {
+        Self { ttl }
+    }
+}
+
+impl<F> fmt::Debug for ResponseTtl<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ResponseTtl").finish_non_exhaustive()
+    }
+}
+
+impl<K, Resp, F> Expiry<K, Resp> for ResponseTtl<F>
+where
+    F: Fn(&Resp) -> Option<Duration>,
+{
+    fn expire_after_create(
+        &self,
+        _key: &K,
+        value: &Resp,
+        _created_at: Instant,
+    ) -> Option<Duration> {
+        (self.ttl)(value)
+    }
+
+    fn expire_after_update(
+        &self,
+        _key: &K,
+        value: &Resp,
+        _updated_at: Instant,
+        _duration_until_expiry: Option<Duration>,
+    ) -> Option<Duration> {
+        (self.ttl)(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CacheLayer, CacheService, ResponseTtl};
+    use crate::{future::Cache, MockClock};
+
+    use std::{
+        future::Future,
+        pin::Pin,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        task::{Context, Poll},
+        time::Duration,
+    };
+    use tower_service::Service;
+
+    /// Returns `(request * 10, ttl)`, or an error for a negative request, after
+    /// a short delay. Counts the calls.
+    #[derive(Clone, Default)]
+    struct Times10 {
+        calls: Arc<AtomicUsize>,
+    }
+
+    impl Service<i32> for Times10 {
+        type Response = (i32, Option<Duration>);
+        type Error = String;
+        type Future = Pin<Box<dyn Future<Output = Result<Self::Response, String>> + Send>>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), String>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, req: i32) -> Self::Future {
+            self.calls.fetch_add(1, Ordering::AcqRel);
+            Box::pin(async move {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                if req < 0 {
+                    Err(format!("negative: {req}"))
+                } else {
+                    let ttl = (req == 2).then(|| Duration::from_secs(5));
+                    Ok((req * 10, ttl))
+                }
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn cache_responses() {
+        let service = Times10::default();
+        let calls = Arc::clone(&service.calls);
+        let cache = Cache::builder().max_capacity(100).build();
+        // Request 0 bypasses the cache.
+        let layer = CacheLayer::new(cache.clone(), |req: &i32| (*req != 0).then_some(*req));
+        let mut svc = layer.layer(service);
+
+        assert_eq!(svc.call(1).await.unwrap().0, 10);
+        assert_eq!(svc.call(1).await.unwrap().0, 10);
+        assert_eq!(calls.load(Ordering::Acquire), 1);
+
+        svc.call(0).await.unwrap();
+        svc.call(0).await.unwrap();
+        assert_eq!(calls.load(Ordering::Acquire), 3);
+        assert!(!cache.contains_key(&0));
+
+        // Errors are not cached.
+        assert_eq!(*svc.call(-1).await.unwrap_err(), "negative: -1");
+        assert!(svc.call(-1).await.is_err());
+        assert_eq!(calls.load(Ordering::Acquire), 5);
+        assert!(!cache.contains_key(&-1));
+    }
+
+    #[tokio::test]
+    async fn coalesce_concurrent_requests() {
+        let service = Times10::default();
+        let calls = Arc::clone(&service.calls);
+        let cache = Cache::builder().max_capacity(100).build();
+        let layer = CacheLayer::new(cache, |req: &i32| Some(*req));
+
+        let handles = (0..8)
+            .map(|_| {
+                let mut svc = layer.layer(service.clone());
+                tokio::spawn(async move { svc.call(3).await.unwrap().0 })
+            })
+            .collect::<Vec<_>>();
+        for handle in handles {
+            assert_eq!(handle.await.unwrap(), 30);
+        }
+        assert_eq!(calls.load(Ordering::Acquire), 1);
+    }
+
+    #[tokio::test]
+    async fn tower_layer() {
+        use tower_layer::Layer;
+
+        fn wrap<L: Layer<Times10>>(layer: &L, inner: Times10) -> L::Service {
+            layer.layer(inner)
+        }
+
+        let cache = Cache::builder().max_capacity(100).build();
+        let layer = CacheLayer::new(cache.clone(), |req: &i32| Some(*req));
+        let mut svc: CacheService<Times10, _, _, _> = wrap(&layer, Times10::default());
+        assert_eq!(svc.call(4).await.unwrap().0, 40);
+        assert!(cache.contains_key(&4));
+    }
+
+    #[tokio::test]
+    async fn response_ttl() {
+        let clock = MockClock::new();
+        let cache = Cache::builder()
+            .max_capacity(100)
+            .clock(clock.clone())
+            .expire_after(ResponseTtl::new(|(_, ttl): &(i32, Option<Duration>)| *ttl))
+            .build();
+        let layer = CacheLayer::new(cache.clone(), |req: &i32| Some(*req));
+        let mut svc = layer.layer(Times10::default());
+
+        svc.call(1).await.unwrap();
+        svc.call(2).await.unwrap();
+        clock.advance(Duration::from_secs(5));
+        // The response to 2 has expired by its own TTL.
+        assert!(cache.contains_key(&1));
+        assert!(!cache.contains_key(&2));
+    }
+}
@@ -0,0 +1,180 @@
+//! Provides a built-in weigher for the caches of byte blobs and strings.
+//!
+//! The [`ByteLength`][byte-length-trait] trait returns the length of a value in
+//! bytes. It is implemented for `str`, `[u8]`, `String`, `Vec<u8>`, and the shared
+//! pointers to them such as `Arc<str>` and `Arc<[u8]>`. With the `bytes` crate
+//! feature, it is also implemented for `bytes::Bytes`.
+//!
+//! The [`byte_len`][byte-len-fn] function weighs an entry by the byte length of
+//! its value, so a cache can be bounded by the total bytes of its values without
+//! writing a weigher closure. It can be set by the `weigh_by_byte_len` method of
+//! the cache builders, or passed to their `weigher` method.
+//!
+//! [byte-length-trait]: ./trait.ByteLength.html
+//! [byte-len-fn]: ./fn.byte_len.html
+//!
+//! # Example
+//!
+//! ```rust
+//! // Cargo.toml
+//! //
+//! // [dependencies]
+//! // moka = { version = "0.12", features = ["sync"] }
+//!
+//! use moka::sync::Cache;
+//! use std::sync::Arc;
+//!
+//! // A cache that holds up to 32MiB of string values.
+//! let cache: Cache<u32, Arc<str>> = Cache::builder()
+//!     .max_capacity(32 * 1024 * 1024)
+//!     .weigh_by_byte_len()
+//!     .build();
+//!
+//! cache.insert(0, Arc::from("hello"));
+//! cache.run_pending_tasks();
+//! assert_eq!(cache.weighted_size(), 5);
+//! ```
+
+use std::{rc::Rc, sync::Arc};
+
+/// A value that has a length in bytes. Used by the [`byte_len`][byte-len-fn]
+/// weigher.
+///
+/// [byte-len-fn]: ./fn.byte_len.html
+pub trait ByteLength {
+    /// Returns the length of this value in bytes.
+    fn byte_len(&self) -> usize;
+}
+
+impl ByteLength for str {
+    fn byte_len(&self) -> usize {
+        self.len()
+    }
+}
+
+impl ByteLength for [u8] {
+    fn byte_len(&self) -> usize {
+        self.len()
+    }
+}
+
+impl ByteLength for String {
+    fn byte_len(&self) -> usize {
+        self.len()
+    }
+}
+
+impl ByteLength for Vec<u8> {
+    fn byte_len(&self) -> usize {
+        self.len()
+    }
+}
+
+impl<T: ByteLength + ?Sized> ByteLength for Arc<T> {
+    fn byte_len(&self) -> usize {
+        (**self).byte_len()
+    }
+}
+
+impl<T: ByteLength + ?Sized> ByteLength for Rc<T> {
+    fn byte_len(&self) -> usize {
+        (**self).byte_len()
+    }
+}
+
+impl<T: ByteLength + ?Sized> ByteLength for Box<T> {
+    fn byte_len(&self) -> usize {
+        (**self).byte_len()
+    }
+}
+
+#[cfg(feature = "bytes")]
+#[cfg_attr(docsrs, doc(cfg(feature = "bytes")))]
+impl ByteLength for bytes::Bytes {
+    fn byte_len(&self) -> usize {
+        self.len()
+    }
+}
+
+/// A weigher returning the byte length of the value as the weight of the entry.
+/// The key is not weighed. The weight is saturated at `u32::MAX` for the values
+/// larger than 4GiB.
+pub fn byte_len<K: ?Sized, V: ByteLength + ?Sized>(_key: &K, value: &V) -> u32 {
+    u32::try_from(value.byte_len()).unwrap_or(u32::MAX)
+}
+
+/// The context of an insertion passed to a weigher closure set by the
+/// `weigher_with_context` method of the cache builder.
+///
+/// When the insertion replaces an existing entry, the context carries the weight
+/// of the replaced entry, so that the weigher can compute the new weight
+/// incrementally instead of measuring the whole value again.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WeighContext {
+    old_weight: Option<u32>,
+}
+
+impl WeighContext {
+    pub(crate) fn insert() -> Self {
+        Self { old_weight: None }
+    }
+
+    #[cfg(feature = "sync")]
+    pub(crate) fn update(old_weight: u32) -> Self {
+        Self {
+            old_weight: Some(old_weight),
+        }
+    }
+
+    /// Returns `true` if the value replaces the value of an existing entry.
+    pub fn is_update(&self) -> bool {
+        self.old_weight.is_some()
+    }
+
+    /// Returns the weight of the replaced entry, or `None` if the value is
+    /// inserted for a new entry.
+    pub fn old_weight(&self) -> Option<u32> {
+        self.old_weight
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{byte_len, ByteLength};
+
+    use std::sync::Arc;
+
+    #[test]
+    fn byte_lengths() {
+        assert_eq!("héllo".byte_len(), 6);
+        assert_eq!(Arc::<str>::from("héllo").byte_len(), 6);
+        assert_eq!(Arc::<[u8]>::from(&b"abc"[..]).byte_len(), 3);
+        assert_eq!(Box::<[u8]>::from(&b"abc"[..]).byte_len(), 3);
+        assert_eq!(vec![0u8; 10].byte_len(), 10);
+        assert_eq!(byte_len(&(), &"hello".to_string()), 5);
+        #[cfg(feature = "bytes")]
+        assert_eq!(bytes::Bytes::from_static(b"hello").byte_len(), 5);
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn weigh_by_byte_len() {
+        use crate::sync::Cache;
+
+        let cache: Cache<u32, Arc<[u8]>> = Cache::builder()
+            .max_capacity(10)
+            .weigh_by_byte_len()
+            .build();
+
+        cache.insert(0, Arc::from(&[0u8; 4][..]));
+        cache.insert(1, Arc::from(&[0u8; 4][..]));
+        cache.run_pending_tasks();
+        assert_eq!(cache.weighted_size(), 8);
+
+        // Too large to be admitted.
+        cache.insert(2, Arc::from(&[0u8; 11][..]));
+        cache.run_pending_tasks();
+        assert!(!cache.contains_key(&2));
+        assert_eq!(cache.weighted_size(), 8);
+    }
+}